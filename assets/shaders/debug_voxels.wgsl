@@ -0,0 +1,31 @@
+#import bevy_pbr::view_transformations::position_world_to_clip
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) instance_position_size: vec4<f32>,
+    @location(4) instance_color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    // Instances are already in world space, so mesh transform isn't used.
+    let world_position = vertex.position * vertex.instance_position_size.w
+        + vertex.instance_position_size.xyz;
+
+    var out: VertexOutput;
+    out.clip_position = position_world_to_clip(world_position);
+    out.color = vertex.instance_color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,50 @@
+#import bevy_pbr::{
+    mesh_functions::{get_model_matrix, mesh_position_local_to_world},
+    view_transformations::position_world_to_clip,
+}
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+};
+
+struct SkyData {
+    horizon_color: vec4<f32>,
+    zenith_color: vec4<f32>,
+};
+
+@group(2) @binding(0)
+var<uniform> sky_data: SkyData;
+
+@vertex
+fn vertex(
+    vertex: Vertex,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_position = mesh_position_local_to_world(get_model_matrix(vertex.instance_index), vec4<f32>(vertex.position, 1.0));
+    var clip_position = position_world_to_clip(world_position.xyz);
+
+    // Push the dome to the far plane (reversed z), so it is always drawn behind everything else.
+    clip_position.z = 0.0;
+
+    out.clip_position = clip_position;
+    out.direction = vertex.position;
+
+    return out;
+}
+
+struct FragmentInput {
+    @location(0) direction: vec3<f32>,
+};
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    let height = clamp(normalize(in.direction).y, 0.0, 1.0);
+    return mix(sky_data.horizon_color, sky_data.zenith_color, sqrt(height));
+}
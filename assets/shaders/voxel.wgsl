@@ -1,4 +1,10 @@
-#import bevy_pbr::mesh_functions::{get_model_matrix, mesh_position_local_to_clip}
+#import bevy_pbr::{
+    mesh_functions::{get_model_matrix, mesh_position_local_to_world},
+    mesh_view_bindings::{globals, lights, view},
+    mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT,
+    shadows::fetch_directional_shadow,
+    view_transformations::position_world_to_clip,
+}
 
 struct Vertex {
     @builtin(instance_index) instance_index: u32,
@@ -14,10 +20,19 @@ struct VertexOutput {
     @location(0) light_intensity: vec3<f32>,
     @location(1) uv: vec2<f32>,
     @location(2) tile_coord_start: vec2<f32>,
+    @location(3) world_position: vec3<f32>,
+    @location(4) normal: vec3<f32>,
 };
 
 struct MaterialData {
     tile_texture_size: f32,
+    fog_near: f32,
+    fog_far: f32,
+    natural_light_scale: f32,
+    fog_color: vec4<f32>,
+    flow: vec2<f32>,
+    opacity: f32,
+    shadow_strength: f32,
 };
 
 @group(2) @binding(0)
@@ -29,16 +44,26 @@ var atlas_sampler: sampler;
 @group(2) @binding(2)
 var<uniform> material_data: MaterialData;
 
+@group(2) @binding(3)
+var array_texture: texture_2d_array<f32>;
+
+@group(2) @binding(4)
+var array_sampler: sampler;
+
 @vertex
 fn vertex(
     vertex: Vertex,
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    out.clip_position = mesh_position_local_to_clip(get_model_matrix(vertex.instance_index), vec4<f32>(vertex.position, 1.0));
+    let world_position = mesh_position_local_to_world(get_model_matrix(vertex.instance_index), vec4<f32>(vertex.position, 1.0));
+    out.clip_position = position_world_to_clip(world_position.xyz);
+    out.world_position = world_position.xyz;
     out.light_intensity = vertex.light;
     out.uv = vertex.uv;
     out.tile_coord_start = vertex.tile_coord_start;
+    // Chunks are only translated, so normals are the same in world space.
+    out.normal = vertex.normal;
 
     return out;
 }
@@ -47,12 +72,60 @@ struct FragmentInput {
     @location(0) light_intensity: vec3<f32>,
     @location(1) uv: vec2<f32>,
     @location(2) tile_coord_start: vec2<f32>,
+    @location(3) world_position: vec3<f32>,
+    @location(4) normal: vec3<f32>,
 };
 
+/// **Returns** how much the given fragment is lit by the sun, which is the first directional light,
+/// from 0.0 when fully shadowed up to 1.0 when not shadowed at all.
+fn sun_visibility(world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if lights.n_directional_lights == 0u
+        || (lights.directional_lights[0].flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) == 0u {
+        return 1.0;
+    }
+
+    let position = vec4<f32>(world_position, 1.0);
+    let view_z = dot(vec4<f32>(
+        view.inverse_view[0].z,
+        view.inverse_view[1].z,
+        view.inverse_view[2].z,
+        view.inverse_view[3].z,
+    ), position);
+
+    return fetch_directional_shadow(0u, position, normal, view_z);
+}
+
 @fragment
 fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
-    let tiled_coord = in.uv % material_data.tile_texture_size;
+    // Flowing textures, like liquids, scroll inside their tile, so they wrap around tile edges.
+    let flow_offset = fract(material_data.flow * globals.time) * material_data.tile_texture_size;
+
+#ifdef TEXTURE_ARRAY
+    // Each tile is a layer of its own, so sampler repeats it and mip levels don't bleed neighbor
+    // tiles. Layers are laid out row by row, like tiles on atlas.
+    let tiles = round(1.0 / material_data.tile_texture_size);
+    let tile = round(in.tile_coord_start / material_data.tile_texture_size);
+    let layer = i32(tile.y * tiles + tile.x);
+    let layer_coord = (in.uv + flow_offset) / material_data.tile_texture_size;
+    var color = textureSample(array_texture, array_sampler, layer_coord, layer);
+#else
+    // Keep half texel away from tile edges, so linear filtering doesn't bleed neighbor tiles.
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(atlas_texture));
+    let tiled_coord = clamp(
+        (in.uv + flow_offset) % material_data.tile_texture_size,
+        half_texel,
+        vec2<f32>(material_data.tile_texture_size) - half_texel,
+    );
     var color = textureSample(atlas_texture, atlas_sampler, in.tile_coord_start + tiled_coord);
+#endif
+
+    // Voxel light is baked, so sun shadows only dim it, instead of lighting terrain on their own.
+    let shadow = mix(1.0 - material_data.shadow_strength, 1.0, sun_visibility(in.world_position, in.normal));
+    color = color * vec4<f32>(in.light_intensity * material_data.natural_light_scale * shadow, 1.0);
+
+    // Fog uses horizontal distance only, since landscape is loaded around the camera in XZ axis.
+    let distance = length(in.world_position.xz - view.world_position.xz);
+    let fog = smoothstep(material_data.fog_near, material_data.fog_far, distance);
 
-    return color * vec4<f32>(in.light_intensity, 1.0);
+    return vec4<f32>(mix(color.rgb, material_data.fog_color.rgb, fog), color.a * material_data.opacity);
 }
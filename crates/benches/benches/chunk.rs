@@ -0,0 +1,162 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use projekto_benches::{baseline::REGRESSION_THRESHOLD, fixture::Fixture};
+use projekto_core::{
+    chunk::{self, Chunk, ChunkColumn, ChunkStorage},
+    voxel::{self, LightTy, Voxel},
+};
+use projekto_server::{cache::ChunkCache, light, meshing};
+
+fn faces_occlusion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("faces_occlusion");
+
+    for fixture in Fixture::ALL {
+        let kind = fixture.kind();
+        let mut occlusion = ChunkStorage::default();
+        let neighborhood = [None; chunk::SIDE_COUNT];
+
+        group.bench_function(BenchmarkId::from_parameter(fixture.name()), |b| {
+            b.iter(|| {
+                meshing::faces_occlusion(&kind, &mut occlusion, &neighborhood, Default::default())
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn meshing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("meshing");
+
+    for fixture in Fixture::ALL {
+        let chunk = Chunk::default();
+        let kind = fixture.kind();
+        let light = fixture.light(&kind);
+
+        let mut occlusion = ChunkStorage::default();
+        meshing::faces_occlusion(
+            &kind,
+            &mut occlusion,
+            &[None; chunk::SIDE_COUNT],
+            Default::default(),
+        );
+
+        let mut soft_light = ChunkStorage::default();
+        light::smooth_lighting(
+            chunk,
+            &occlusion,
+            &mut soft_light,
+            &mut light::LightSamples::default(),
+            |c| (c == chunk).then_some(&kind),
+            |c| (c == chunk).then_some(&light),
+            Default::default(),
+        );
+
+        group.bench_function(BenchmarkId::from_parameter(fixture.name()), |b| {
+            b.iter(|| {
+                let faces = meshing::generate_faces(&kind, &occlusion, &soft_light);
+                meshing::generate_vertices(faces)
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn light_propagation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("light_propagation");
+
+    for fixture in Fixture::ALL {
+        let kind = fixture.kind();
+        let mut light = ChunkStorage::<voxel::Light>::default();
+        for voxel in chunk::top_voxels() {
+            light.set_type(voxel, LightTy::Natural, voxel::Light::MAX_NATURAL_INTENSITY);
+        }
+
+        group.bench_function(BenchmarkId::from_parameter(fixture.name()), |b| {
+            b.iter_batched_ref(
+                || light.clone(),
+                |light| light::propagate(&kind, light, LightTy::Natural, chunk::top_voxels()),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+fn highest_solid(c: &mut Criterion) {
+    let mut group = c.benchmark_group("highest_solid");
+
+    for fixture in Fixture::ALL {
+        let kind = fixture.kind();
+        let column = ChunkColumn::new(Chunk::default(), &kind);
+        let columns = || {
+            (0..chunk::X_AXIS_SIZE as i32)
+                .flat_map(|x| (0..chunk::Z_AXIS_SIZE as i32).map(move |z| (x, z)))
+        };
+
+        group.bench_function(BenchmarkId::new("column", fixture.name()), |b| {
+            b.iter(|| {
+                columns()
+                    .filter_map(|xz| column.highest_solid(xz.into()))
+                    .sum::<i32>()
+            });
+        });
+
+        // Baseline which scans each column from the top, without skipping empty sub-chunks.
+        group.bench_function(BenchmarkId::new("scan", fixture.name()), |b| {
+            b.iter(|| {
+                columns()
+                    .filter_map(|(x, z)| {
+                        (0..chunk::Y_AXIS_SIZE as i32)
+                            .rev()
+                            .find(|&y| kind.get(Voxel::new(x, y, z)).is_solid())
+                    })
+                    .sum::<i32>()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn archive(c: &mut Criterion) {
+    ChunkCache::init(
+        &std::env::temp_dir()
+            .join("projekto_benches")
+            .to_string_lossy(),
+    );
+
+    let mut group = c.benchmark_group("archive");
+
+    for (i, fixture) in Fixture::ALL.into_iter().enumerate() {
+        let chunk = Chunk::new(i as i32, 0);
+        let kind = fixture.kind();
+        let light = fixture.light(&kind);
+        let cache = || ChunkCache {
+            chunk,
+            kind: kind.clone(),
+            light: light.clone(),
+            ..Default::default()
+        };
+
+        group.bench_function(BenchmarkId::new("write", fixture.name()), |b| {
+            b.iter_batched(cache, ChunkCache::save, BatchSize::LargeInput);
+        });
+
+        group.bench_function(BenchmarkId::new("read", fixture.name()), |b| {
+            b.iter(|| ChunkCache::load(chunk).expect("Chunk was saved"));
+        });
+
+        ChunkCache::delete(chunk);
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().noise_threshold(REGRESSION_THRESHOLD);
+    targets = faces_occlusion, meshing, light_propagation, highest_solid, archive
+}
+criterion_main!(benches);
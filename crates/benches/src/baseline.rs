@@ -0,0 +1,156 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Benchmarks slower than baseline by more than this ratio are considered regressions.
+pub const REGRESSION_THRESHOLD: f64 = 0.10;
+
+/// Mean time, in nanoseconds, of each benchmark, by benchmark id.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline(pub BTreeMap<String, f64>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub id: String,
+    pub baseline: f64,
+    pub current: f64,
+}
+
+impl Regression {
+    /// **Returns** how much slower current run is, relative to baseline.
+    pub fn ratio(&self) -> f64 {
+        self.current / self.baseline - 1.0
+    }
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        serde_json::from_str(&json).map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, json + "\n").map_err(|err| err.to_string())
+    }
+
+    /// Reads the latest results written by criterion on the given folder, usually
+    /// `target/criterion`.
+    pub fn from_criterion(dir: &Path) -> Result<Self, String> {
+        let mut baseline = Baseline::default();
+
+        for estimates in find_estimates(dir) {
+            let json = std::fs::read_to_string(&estimates).map_err(|err| err.to_string())?;
+            let Estimates { mean } = serde_json::from_str(&json).map_err(|err| err.to_string())?;
+
+            // Results are stored on `<group>/<bench>/new/estimates.json`.
+            let id = estimates
+                .parent()
+                .and_then(Path::parent)
+                .and_then(|bench| bench.strip_prefix(dir).ok())
+                .map(|id| id.to_string_lossy().replace('\\', "/"))
+                .ok_or_else(|| format!("Invalid estimates path {estimates:?}"))?;
+
+            baseline.0.insert(id, mean.point_estimate);
+        }
+
+        Ok(baseline)
+    }
+
+    /// **Returns** benchmarks of `current` which are slower than this baseline by more than the
+    /// given threshold. Benchmarks missing on either side are ignored.
+    pub fn regressions(&self, current: &Baseline, threshold: f64) -> Vec<Regression> {
+        current
+            .0
+            .iter()
+            .filter_map(|(id, &current)| {
+                let baseline = *self.0.get(id)?;
+                Some(Regression {
+                    id: id.clone(),
+                    baseline,
+                    current,
+                })
+            })
+            .filter(|regression| regression.ratio() > threshold)
+            .collect()
+    }
+}
+
+fn find_estimates(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut found = vec![];
+    for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+        let estimates = path.join("estimates.json");
+        if path.ends_with("new") && estimates.is_file() {
+            found.push(estimates);
+        } else if path.is_dir() && !path.ends_with("report") {
+            found.extend(find_estimates(&path));
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline(items: &[(&str, f64)]) -> Baseline {
+        Baseline(items.iter().map(|(id, t)| (id.to_string(), *t)).collect())
+    }
+
+    #[test]
+    fn regressions() {
+        let old = baseline(&[("a", 100.0), ("b", 100.0), ("c", 100.0)]);
+        let new = baseline(&[("a", 109.0), ("b", 111.0), ("c", 50.0), ("d", 500.0)]);
+
+        let regressions = old.regressions(&new, REGRESSION_THRESHOLD);
+
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].id, "b");
+        assert!((regressions[0].ratio() - 0.11).abs() < 1e-9);
+    }
+
+    #[test]
+    fn from_criterion() {
+        let dir = std::env::temp_dir().join("projekto_benches_from_criterion");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for (bench, mean) in [("group/flat", 10.5), ("group/caves", 20.0)] {
+            let new = dir.join(bench).join("new");
+            std::fs::create_dir_all(&new).unwrap();
+            std::fs::write(
+                new.join("estimates.json"),
+                format!(r#"{{"mean":{{"point_estimate":{mean},"standard_error":1.0}}}}"#),
+            )
+            .unwrap();
+            std::fs::create_dir_all(dir.join(bench).join("base")).unwrap();
+        }
+        std::fs::create_dir_all(dir.join("report")).unwrap();
+
+        let baseline = Baseline::from_criterion(&dir).unwrap();
+
+        assert_eq!(
+            baseline,
+            self::baseline(&[("group/caves", 20.0), ("group/flat", 10.5)])
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,106 @@
+use projekto_core::{
+    chunk::{self, ChunkStorage},
+    voxel::{self, LightTy, Voxel},
+};
+use projekto_server::light;
+
+const STONE: u16 = 3;
+const FLAT_HEIGHT: i32 = 64;
+const NOISY_MIN_HEIGHT: i32 = 48;
+const NOISY_VARIATION: u32 = 32;
+
+/// Representative chunks, so benchmarks cover common terrain shapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixture {
+    /// Solid ground with a flat surface.
+    Flat,
+    /// Ground with random heights on each column.
+    Noisy,
+    /// Like [`Fixture::Noisy`], but with lots of holes below the surface.
+    Caves,
+}
+
+impl Fixture {
+    pub const ALL: [Fixture; 3] = [Fixture::Flat, Fixture::Noisy, Fixture::Caves];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fixture::Flat => "flat",
+            Fixture::Noisy => "noisy",
+            Fixture::Caves => "caves",
+        }
+    }
+
+    /// **Returns** the voxel kinds of this fixture. Fixtures are always the same, so results can
+    /// be compared between runs.
+    pub fn kind(&self) -> ChunkStorage<voxel::Kind> {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+
+        for voxel in chunk::voxels() {
+            let is_solid = match self {
+                Fixture::Flat => voxel.y < FLAT_HEIGHT,
+                Fixture::Noisy => voxel.y < noisy_height(voxel),
+                Fixture::Caves => voxel.y < noisy_height(voxel) && hash(voxel) % 3 < 2,
+            };
+
+            if is_solid {
+                kind.set(voxel, voxel::Kind::id(STONE));
+            }
+        }
+
+        kind
+    }
+
+    /// **Returns** the natural light of this fixture, propagated from the top of the chunk.
+    pub fn light(&self, kind: &ChunkStorage<voxel::Kind>) -> ChunkStorage<voxel::Light> {
+        let mut light = ChunkStorage::<voxel::Light>::default();
+
+        for voxel in chunk::top_voxels() {
+            light.set_type(voxel, LightTy::Natural, voxel::Light::MAX_NATURAL_INTENSITY);
+        }
+
+        light::propagate(kind, &mut light, LightTy::Natural, chunk::top_voxels());
+        light
+    }
+}
+
+fn noisy_height(voxel: Voxel) -> i32 {
+    let column = Voxel::new(voxel.x, 0, voxel.z);
+    NOISY_MIN_HEIGHT + (hash(column) % NOISY_VARIATION) as i32
+}
+
+/// Cheap integer hash, which is stable between runs and machines.
+fn hash(voxel: Voxel) -> u32 {
+    let mut h = (voxel.x as u32).wrapping_mul(0x8da6_b343)
+        ^ (voxel.y as u32).wrapping_mul(0xd816_3841)
+        ^ (voxel.z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^ (h >> 15)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_are_stable() {
+        for fixture in Fixture::ALL {
+            assert!(
+                fixture.kind() == fixture.kind(),
+                "{fixture:?} should be stable"
+            );
+        }
+    }
+
+    #[test]
+    fn caves_have_holes() {
+        let count = |kind: ChunkStorage<voxel::Kind>| kind.iter().filter(|k| k.is_solid()).count();
+
+        let noisy = count(Fixture::Noisy.kind());
+        let caves = count(Fixture::Caves.kind());
+
+        assert!(caves < noisy);
+        assert!(caves > noisy / 2);
+    }
+}
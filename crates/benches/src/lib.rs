@@ -0,0 +1,4 @@
+//! Chunk fixtures used by benchmarks and baseline comparison, to detect performance regressions.
+
+pub mod baseline;
+pub mod fixture;
@@ -0,0 +1,57 @@
+//! Compares the latest benchmark results against the stored baseline.
+//!
+//! Run `cargo bench -p projekto_benches` first, then either:
+//! - `cargo run -p projekto_benches -- check` to fail if any benchmark regressed;
+//! - `cargo run -p projekto_benches -- save` to store the latest results as the new baseline.
+
+use std::path::PathBuf;
+
+use projekto_benches::baseline::{Baseline, REGRESSION_THRESHOLD};
+
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("baseline.json")
+}
+
+fn criterion_dir() -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target"))
+        .join("criterion")
+}
+
+fn main() -> Result<(), String> {
+    let current = Baseline::from_criterion(&criterion_dir())?;
+    if current.0.is_empty() {
+        return Err("No benchmark results found. Run `cargo bench` first.".to_string());
+    }
+
+    match std::env::args().nth(1).as_deref() {
+        Some("save") => {
+            current.save(&baseline_path())?;
+            println!("Baseline saved with {} benchmarks.", current.0.len());
+            Ok(())
+        }
+        Some("check") => {
+            let baseline = Baseline::load(&baseline_path())?;
+            let regressions = baseline.regressions(&current, REGRESSION_THRESHOLD);
+
+            for regression in &regressions {
+                println!(
+                    "{}: {:.0}ns -> {:.0}ns (+{:.1}%)",
+                    regression.id,
+                    regression.baseline,
+                    regression.current,
+                    regression.ratio() * 100.0
+                );
+            }
+
+            if regressions.is_empty() {
+                println!("No regressions found.");
+                Ok(())
+            } else {
+                Err(format!("{} benchmarks regressed.", regressions.len()))
+            }
+        }
+        _ => Err("Usage: projekto_benches <check|save>".to_string()),
+    }
+}
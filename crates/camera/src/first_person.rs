@@ -4,6 +4,8 @@ use bevy::prelude::*;
 
 use bevy::input::mouse::MouseMotion;
 
+use crate::motion;
+
 /// Adds [`FirstPersonCameraConfig`] resource and internals systems gated by [`is_active`] run
 /// criteria grouped on [`CameraUpdate`] system set.
 pub struct FirstPersonCameraPlugin;
@@ -12,7 +14,10 @@ impl Plugin for FirstPersonCameraPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<FirstPersonCameraConfig>().add_systems(
             Update,
-            rotate_camera.in_set(super::CameraUpdate).run_if(is_active),
+            (rotate_camera, apply_camera_effects)
+                .chain()
+                .in_set(super::CameraUpdate)
+                .run_if(is_active),
         );
     }
 }
@@ -27,6 +32,39 @@ pub struct FirstPersonCamera;
 #[reflect(Component)]
 pub struct FirstPersonTarget;
 
+/// Movement state of [`FirstPersonTarget`] which drives camera effects, like head bob and FOV kick.
+/// It is updated by gameplay and kept on the camera entity, which must be a child of the target.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct FirstPersonEffects {
+    pub sprinting: bool,
+    pub grounded: bool,
+    /// Smoothed world height of the target, so the camera doesn't snap when climbing steps.
+    height: Option<f32>,
+    bob_phase: f32,
+    /// How much head bob is applied, from zero when stopped to one when moving.
+    bob_weight: f32,
+    fov_kick: f32,
+    /// Field of view set by this component on last update, used to detect when it was changed by
+    /// something else, like settings.
+    applied_fov: f32,
+    base_fov: f32,
+}
+
+impl Default for FirstPersonEffects {
+    fn default() -> Self {
+        Self {
+            sprinting: false,
+            grounded: true,
+            height: None,
+            bob_phase: 0.0,
+            bob_weight: 0.0,
+            fov_kick: 0.0,
+            applied_fov: 0.0,
+            base_fov: 0.0,
+        }
+    }
+}
+
 /// Allows to configure [`FirstPersonCamera`] behavior.
 #[derive(Debug, Resource)]
 pub struct FirstPersonCameraConfig {
@@ -35,6 +73,30 @@ pub struct FirstPersonCameraConfig {
 
     /// Rotate speed in units.
     pub rotate_speed: f32,
+
+    /// Smooth camera height, so climbing steps doesn't snap the camera up.
+    pub smoothing: bool,
+
+    /// How fast the camera reaches the target height when [`Self::smoothing`] is enabled.
+    pub smoothing_damping: f32,
+
+    /// Bob camera while the target walks on the ground.
+    pub head_bob: bool,
+
+    /// Vertical distance, in units, of head bob. Sideways bob is half of it.
+    pub head_bob_amplitude: f32,
+
+    /// Head bob phase, in radians, advanced for each unit walked.
+    pub head_bob_frequency: f32,
+
+    /// Widen the field of view while sprinting.
+    pub fov_kick: bool,
+
+    /// Field of view increase, in radians, while sprinting.
+    pub fov_kick_amount: f32,
+
+    /// How fast the field of view reaches its desired value.
+    pub fov_kick_damping: f32,
 }
 
 impl Default for FirstPersonCameraConfig {
@@ -42,6 +104,14 @@ impl Default for FirstPersonCameraConfig {
         Self {
             rotate_speed: PI / 25.0,
             active: false,
+            smoothing: true,
+            smoothing_damping: 15.0,
+            head_bob: true,
+            head_bob_amplitude: 0.04,
+            head_bob_frequency: PI / 1.2,
+            fov_kick: true,
+            fov_kick_amount: 10f32.to_radians(),
+            fov_kick_damping: 8.0,
         }
     }
 }
@@ -83,3 +153,87 @@ fn rotate_camera(
         transform.rotation = yaw * pitch;
     }
 }
+
+/// Minimum horizontal speed, in units per second, in which the target is considered moving.
+const HEAD_BOB_MIN_SPEED: f32 = 0.5;
+
+/// Applies height smoothing, head bob and FOV kick on the camera, using [`FirstPersonEffects`] and
+/// [`FirstPersonCameraConfig`] settings.
+/// This system is gated by [`is_active`] run criteria.
+fn apply_camera_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<FirstPersonCameraConfig>,
+    q_target: Query<&Transform, (With<FirstPersonTarget>, Without<FirstPersonCamera>)>,
+    mut q: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Projection,
+            Option<&mut FirstPersonEffects>,
+        ),
+        With<FirstPersonCamera>,
+    >,
+    mut last_position: Local<Option<Vec3>>,
+) {
+    let (Ok(target), Ok((entity, mut transform, mut projection, effects))) =
+        (q_target.get_single(), q.get_single_mut())
+    else {
+        return;
+    };
+
+    let Some(mut effects) = effects else {
+        commands
+            .entity(entity)
+            .insert(FirstPersonEffects::default());
+        return;
+    };
+
+    let dt = time.delta_seconds();
+    let smooth = |damping: f32| motion::smoothing(damping, dt);
+
+    let position = target.translation;
+    let moved = last_position.replace(position).unwrap_or(position) - position;
+    let horizontal = Vec2::new(moved.x, moved.z).length();
+
+    let height = match effects.height {
+        Some(height) if config.smoothing => {
+            height + (position.y - height) * smooth(config.smoothing_damping)
+        }
+        _ => position.y,
+    };
+    effects.height = Some(height);
+
+    let is_walking =
+        config.head_bob && effects.grounded && dt > 0.0 && horizontal / dt > HEAD_BOB_MIN_SPEED;
+    let bob_weight = if is_walking { 1.0 } else { 0.0 };
+    effects.bob_weight += (bob_weight - effects.bob_weight) * smooth(config.smoothing_damping);
+    effects.bob_phase = (effects.bob_phase + horizontal * config.head_bob_frequency) % (PI * 2.0);
+
+    let amplitude = config.head_bob_amplitude * effects.bob_weight;
+    let bob = Vec3::new(
+        effects.bob_phase.sin() * amplitude * 0.5,
+        (effects.bob_phase * 2.0).sin() * amplitude,
+        0.0,
+    );
+
+    // Camera is a child of the target, so offsets are converted into target space.
+    let lag = Vec3::Y * (height - position.y);
+    transform.translation = target.rotation.inverse() * lag + bob;
+
+    if let Projection::Perspective(perspective) = projection.as_mut() {
+        if perspective.fov != effects.applied_fov {
+            effects.base_fov = perspective.fov;
+        }
+
+        let kick = if config.fov_kick && effects.sprinting {
+            config.fov_kick_amount
+        } else {
+            0.0
+        };
+        effects.fov_kick += (kick - effects.fov_kick) * smooth(config.fov_kick_damping);
+
+        perspective.fov = effects.base_fov + effects.fov_kick;
+        effects.applied_fov = perspective.fov;
+    }
+}
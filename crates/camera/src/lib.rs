@@ -1,15 +1,20 @@
 use bevy::prelude::*;
 use first_person::FirstPersonCameraPlugin;
 use orbit::OrbitCameraPlugin;
+use path::CameraPathPlugin;
+use rts::RtsCameraPlugin;
 
 use self::fly_by::FlyByCameraPlugin;
 
 pub mod first_person;
 pub mod fly_by;
+mod motion;
 pub mod orbit;
+pub mod path;
+pub mod rts;
 
-/// This is a wrapper plugin which justs adds [`FlyByCameraPlugin`], [`FirstPersonCameraPlugin`] and
-/// [`OrbitCameraPlugin`]
+/// This is a wrapper plugin which justs adds [`FlyByCameraPlugin`], [`FirstPersonCameraPlugin`],
+/// [`OrbitCameraPlugin`], [`RtsCameraPlugin`] and [`CameraPathPlugin`]
 pub struct CameraPlugin;
 
 /// [`SystemLabel`] used by internals systems.
@@ -22,6 +27,8 @@ impl Plugin for CameraPlugin {
             FlyByCameraPlugin,
             FirstPersonCameraPlugin,
             OrbitCameraPlugin,
+            RtsCameraPlugin,
+            CameraPathPlugin,
         ));
     }
 }
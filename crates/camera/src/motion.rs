@@ -0,0 +1,31 @@
+//! Helpers shared by cameras to read mouse wheel and to smoothly follow their targets.
+
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+
+/// Mouse wheel pixels which are equivalent to a single line, when scrolling.
+const PIXELS_PER_LINE: f32 = 16.0;
+
+/// Time, in seconds, without updates after which the camera snaps into place instead of smoothly
+/// moving, like when it is activated again.
+const SNAP_AFTER_SECS: f32 = 0.25;
+
+/// **Returns** how many lines the mouse wheel scrolled. Pixel scrolling, like on touchpads, is much
+/// finer than line scrolling.
+pub(crate) fn scroll_lines(evt: &MouseWheel) -> f32 {
+    match evt.unit {
+        MouseScrollUnit::Line => evt.y,
+        MouseScrollUnit::Pixel => evt.y / PIXELS_PER_LINE,
+    }
+}
+
+/// **Returns** how much of the remaining distance to target should be moved after `delta` seconds,
+/// using frame rate independent exponential smoothing.
+pub(crate) fn smoothing(damping: f32, delta: f32) -> f32 {
+    1.0 - (-damping * delta).exp()
+}
+
+/// **Returns** how much a camera updated `since` seconds ago should move towards its target, or
+/// `None` if it should snap into place, because damping is disabled or it wasn't updated recently.
+pub(crate) fn follow_smoothing(damping: f32, since: f32, delta: f32) -> Option<f32> {
+    (damping > 0.0 && since < SNAP_AFTER_SECS).then(|| smoothing(damping, delta))
+}
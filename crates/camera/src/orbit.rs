@@ -6,6 +6,8 @@ use bevy::{
 
 use std::f32::consts::PI;
 
+use crate::motion;
+
 /// Adds [`OrbitCameraPlugin`] resource and internals systems gated by [`is_active`] run criteria
 /// grouped on [`CameraUpdate`] system set.
 pub struct OrbitCameraPlugin;
@@ -14,12 +16,8 @@ impl Plugin for OrbitCameraPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<OrbitCameraConfig>().add_systems(
             Update,
-            (
-                target_moved,
-                settings_changed,
-                move_camera_keycode,
-                move_camera_mouse,
-            )
+            (move_camera_keycode, move_camera_mouse, follow_target)
+                .chain()
                 .in_set(super::CameraUpdate)
                 .run_if(is_active),
         );
@@ -33,11 +31,40 @@ impl Plugin for OrbitCameraPlugin {
 pub struct OrbitCamera;
 
 /// Component used to tag which entity this camera will orbit around.
-/// There can be only one Entity with this component.
+/// There can be only one Entity with this component. Ignored when camera has an [`OrbitTarget`].
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct OrbitCameraTarget;
 
+/// What [`OrbitCamera`] orbits around. Add it to the camera entity to follow any entity, like the
+/// character, or a fixed point, without tagging it with [`OrbitCameraTarget`].
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub enum OrbitTarget {
+    Entity(Entity),
+    Point(Vec3),
+}
+
+impl Default for OrbitTarget {
+    fn default() -> Self {
+        Self::Point(Vec3::ZERO)
+    }
+}
+
+/// Current state of [`OrbitCamera`], which smoothly follows [`OrbitCameraConfig`] and target. It is
+/// added to the camera when it starts orbiting.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct OrbitState {
+    /// Point the camera is currently looking at.
+    pub focus: Vec3,
+    pub radial_distance: f32,
+    pub polar_angle: f32,
+    pub azimuthal_angle: f32,
+    /// Elapsed time, in seconds, of the last update, used to snap into place when the camera is
+    /// activated again.
+    last_update: f32,
+}
+
 /// Key bindings used internal systems to orbit camera around target.
 #[derive(Debug)]
 pub struct KeyBindings {
@@ -47,7 +74,7 @@ pub struct KeyBindings {
     /// Orbit right key binding, defaults to [`KeyCode::Right`].
     pub right: KeyCode,
 
-    /// RotaOrbitte up key binding, defaults to [`KeyCode::Up`].
+    /// Orbit up key binding, defaults to [`KeyCode::Up`].
     pub up: KeyCode,
 
     /// Orbit down key binding, defaults to [`KeyCode::Down`].
@@ -80,7 +107,7 @@ pub struct OrbitCameraConfig {
     pub active: bool,
 
     /// Distance, in radius, to keep from the target.
-    /// Higher values will make camera orbit closer to the target.
+    /// Lower values will make camera orbit closer to the target.
     pub radial_distance: f32,
 
     /// Minimum distance to keep from the target when zooming in.
@@ -92,10 +119,10 @@ pub struct OrbitCameraConfig {
     /// Rotation, in radius, around the polar angle (left-right) of the target.
     pub polar_angle: f32,
 
-    /// Minium polar angle to keep when orbiting downwards.
+    /// Minimum polar angle to keep when orbiting downwards.
     pub min_polar_angle: f32,
 
-    /// Maxium polar angle to keep when orbiting downwards.
+    /// Maximum polar angle to keep when orbiting upwards.
     pub max_polar_angle: f32,
 
     /// Rotation, in radius, around the azimuthal angle (up-down) of the target.
@@ -113,8 +140,12 @@ pub struct OrbitCameraConfig {
     /// Rotation speed in units when using mouse.
     pub mouse_rotate_speed: f32,
 
-    /// Zoom speed in units when using mouse.
+    /// Zoom distance in units for each mouse wheel line.
     pub mouse_zoom_speed: f32,
+
+    /// How fast the camera reaches its desired position. Higher values are snappier, while zero
+    /// disables smoothing.
+    pub damping: f32,
 }
 
 impl OrbitCameraConfig {
@@ -143,8 +174,8 @@ impl Default for OrbitCameraConfig {
             max_distance: 30.0,
 
             polar_angle: std::f32::consts::FRAC_PI_4,
-            min_polar_angle: std::f32::consts::FRAC_PI_4,
-            max_polar_angle: std::f32::consts::FRAC_PI_4,
+            min_polar_angle: 0.05,
+            max_polar_angle: std::f32::consts::FRAC_PI_2 - 0.05,
 
             azimuthal_angle: 0.0,
 
@@ -153,7 +184,9 @@ impl Default for OrbitCameraConfig {
             key_bindings: KeyBindings::default(),
 
             mouse_rotate_speed: PI / 5.0,
-            mouse_zoom_speed: 500.0,
+            mouse_zoom_speed: 1.0,
+
+            damping: 10.0,
         }
     }
 }
@@ -163,83 +196,103 @@ pub fn is_active(config: Res<OrbitCameraConfig>) -> bool {
     config.active
 }
 
-/// Calculates the spheric rotation around the target using [`OrbitCameraConfig`] settings.
+/// **Returns** the point the camera should orbit around, which is either the camera
+/// [`OrbitTarget`] or the entity tagged with [`OrbitCameraTarget`].
+fn target_focus(
+    target: Option<&OrbitTarget>,
+    q_tagged: &Query<&GlobalTransform, (With<OrbitCameraTarget>, Without<OrbitCamera>)>,
+    q_entities: &Query<&GlobalTransform, Without<OrbitCamera>>,
+) -> Option<Vec3> {
+    match target {
+        Some(OrbitTarget::Point(point)) => Some(*point),
+        Some(OrbitTarget::Entity(entity)) => q_entities
+            .get(*entity)
+            .ok()
+            .map(GlobalTransform::translation),
+        None => match q_tagged.get_single() {
+            Ok(t) => Some(t.translation()),
+            Err(QuerySingleError::NoEntities(_)) => None,
+            Err(QuerySingleError::MultipleEntities(_)) => {
+                panic!("Multiple orbit camera target detected.");
+            }
+        },
+    }
+}
+
+/// Smoothly moves camera towards the spherical position around target, using [`OrbitCameraConfig`]
+/// settings.
 ///
 /// This systems is guarded by [`is_active`] run criteria.
-///
-/// This does nothing if the [`Transform`] of an [`Entity`] with [`OrbitCameraTarget`] is not
-/// [`Changed`].
-
-#[allow(clippy::type_complexity)]
-fn target_moved(
+fn follow_target(
+    mut commands: Commands,
+    time: Res<Time>,
     config: Res<OrbitCameraConfig>,
-    target: Query<
-        &Transform,
+    q_tagged: Query<&GlobalTransform, (With<OrbitCameraTarget>, Without<OrbitCamera>)>,
+    q_entities: Query<&GlobalTransform, Without<OrbitCamera>>,
+    mut q: Query<
         (
-            With<OrbitCameraTarget>,
-            Changed<Transform>,
-            Without<OrbitCamera>,
+            Entity,
+            &mut Transform,
+            Option<&OrbitTarget>,
+            Option<&mut OrbitState>,
         ),
+        With<OrbitCamera>,
     >,
-    mut q: Query<&mut Transform, With<OrbitCamera>>,
 ) {
-    let target = match target.get_single() {
-        Ok(t) => t,
-        Err(QuerySingleError::NoEntities(_)) => {
-            return;
-        }
-        Err(QuerySingleError::MultipleEntities(_)) => {
-            panic!("Multiple orbit camera target detected.");
-        }
+    let Ok((entity, mut camera_transform, target, state)) = q.get_single_mut() else {
+        return;
     };
 
-    if let Ok(mut camera_transform) = q.get_single_mut() {
-        look_and_move_around(&mut camera_transform, target.translation, &config);
-    }
-}
-
-/// Calculates the spheric rotation around the target using [`OrbitCameraConfig`] settings.
-///
-/// This systems is guarded by [`is_active`] run criteria.
-///
-/// This system does nothing if the [`OrbitCameraConfig`] is not [`Changed`].
-fn settings_changed(
-    config: Res<OrbitCameraConfig>,
-    target: Query<&Transform, (With<OrbitCameraTarget>, Without<OrbitCamera>)>,
-    mut q: Query<&mut Transform, With<OrbitCamera>>,
-) {
-    if !config.is_changed() {
+    let Some(focus) = target_focus(target, &q_tagged, &q_entities) else {
         return;
-    }
+    };
+
+    let now = time.elapsed_seconds();
+    let desired = OrbitState {
+        focus,
+        radial_distance: config.radial_distance,
+        polar_angle: config.polar_angle,
+        azimuthal_angle: config.azimuthal_angle,
+        last_update: now,
+    };
 
-    let target = match target.get_single() {
-        Ok(t) => t,
-        Err(QuerySingleError::NoEntities(_)) => {
-            return;
+    let new_state = match state {
+        Some(mut state) => {
+            let since = now - state.last_update;
+            *state = match motion::follow_smoothing(config.damping, since, time.delta_seconds()) {
+                Some(t) => OrbitState {
+                    focus: state.focus.lerp(focus, t),
+                    radial_distance: lerp(state.radial_distance, desired.radial_distance, t),
+                    polar_angle: lerp(state.polar_angle, desired.polar_angle, t),
+                    azimuthal_angle: lerp(state.azimuthal_angle, desired.azimuthal_angle, t),
+                    last_update: now,
+                },
+                None => desired,
+            };
+            *state
         }
-        Err(QuerySingleError::MultipleEntities(_)) => {
-            panic!("Multiple orbit camera target detected.");
+        None => {
+            commands.entity(entity).insert(desired);
+            desired
         }
     };
 
-    if let Ok(mut camera_transform) = q.get_single_mut() {
-        look_and_move_around(&mut camera_transform, target.translation, &config);
-    }
+    look_and_move_around(&mut camera_transform, &new_state);
 }
 
-fn look_and_move_around(
-    camera_transform: &mut Transform,
-    target: Vec3,
-    config: &OrbitCameraConfig,
-) {
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn look_and_move_around(camera_transform: &mut Transform, state: &OrbitState) {
     camera_transform.translation = spherical_to_cartesian(
-        config.radial_distance,
-        config.polar_angle,
-        config.azimuthal_angle,
-        target,
+        state.radial_distance,
+        state.polar_angle,
+        state.azimuthal_angle,
+        state.focus,
     );
 
-    camera_transform.look_at(target, Vec3::Y);
+    camera_transform.look_at(state.focus, Vec3::Y);
 }
 
 fn spherical_to_cartesian(radius: f32, polar: f32, azimuth: f32, center: Vec3) -> Vec3 {
@@ -309,7 +362,7 @@ fn move_camera_mouse(
     }
 
     for evt in mouse_wheel.read() {
-        delta.z -= evt.y * time.delta_seconds() * config.mouse_zoom_speed;
+        delta.z -= motion::scroll_lines(evt) * config.mouse_zoom_speed;
     }
 
     if delta != Vec3::ZERO {
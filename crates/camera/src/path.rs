@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+/// Adds [`CameraPath`] playback system, which runs after [`CameraUpdate`](super::CameraUpdate), so
+/// it overrides any camera controller.
+pub struct CameraPathPlugin;
+
+impl Plugin for CameraPathPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, play_camera_path.after(super::CameraUpdate));
+    }
+}
+
+/// Easing applied when moving towards a [`CameraKeyframe`].
+#[derive(Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    /// Starts slow and speeds up.
+    EaseIn,
+    /// Starts fast and slows down.
+    EaseOut,
+    /// Starts and ends slow.
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// **Returns** the eased value of `t`, which must be on `0.0..=1.0` range.
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Camera transform to be reached at some point of a [`CameraPath`].
+#[derive(Reflect, Debug, Default, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    pub transform: Transform,
+    /// Time, in seconds, to reach this keyframe from the previous one. Ignored on first keyframe.
+    pub duration: f32,
+    /// Easing used when moving from the previous keyframe to this one.
+    pub easing: Easing,
+}
+
+/// List of keyframes which the camera goes through when playing. Add it to any camera entity and
+/// call [`CameraPath::play`] to start the playback.
+#[derive(Component, Reflect, Debug, Default, Clone, PartialEq)]
+#[reflect(Component)]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+    /// Starts over when the last keyframe is reached, instead of stopping.
+    pub looping: bool,
+    playing: bool,
+    elapsed: f32,
+}
+
+impl CameraPath {
+    /// Adds a keyframe at the end of the path.
+    pub fn push(&mut self, transform: Transform, duration: f32, easing: Easing) {
+        self.keyframes.push(CameraKeyframe {
+            transform,
+            duration,
+            easing,
+        });
+    }
+
+    /// **Returns** the time, in seconds, to go through the whole path.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.iter().skip(1).map(|k| k.duration).sum()
+    }
+
+    /// Starts the playback from the first keyframe.
+    pub fn play(&mut self) {
+        self.playing = true;
+        self.elapsed = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// **Returns** the camera transform at the given time, in seconds, since path started, or
+    /// `None` if there are no keyframes.
+    pub fn sample(&self, time: f32) -> Option<Transform> {
+        let mut from = self.keyframes.first()?.transform;
+        let mut time = time.max(0.0);
+
+        for keyframe in self.keyframes.iter().skip(1) {
+            if time < keyframe.duration {
+                let t = keyframe.easing.apply(time / keyframe.duration);
+                let to = keyframe.transform;
+
+                return Some(Transform {
+                    translation: from.translation.lerp(to.translation, t),
+                    rotation: from.rotation.slerp(to.rotation, t),
+                    scale: from.scale.lerp(to.scale, t),
+                });
+            }
+
+            time -= keyframe.duration;
+            from = keyframe.transform;
+        }
+
+        Some(from)
+    }
+}
+
+/// Moves cameras which are playing a [`CameraPath`] through its keyframes.
+fn play_camera_path(time: Res<Time>, mut q: Query<(&mut CameraPath, &mut Transform)>) {
+    for (mut path, mut transform) in &mut q {
+        if !path.playing {
+            continue;
+        }
+
+        let duration = path.duration();
+        path.elapsed += time.delta_seconds();
+
+        if path.elapsed >= duration {
+            if path.looping && duration > 0.0 {
+                path.elapsed %= duration;
+            } else {
+                path.playing = false;
+            }
+        }
+
+        if let Some(sampled) = path.sample(path.elapsed) {
+            *transform = sampled;
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use bevy::{input::mouse::MouseWheel, prelude::*, window::PrimaryWindow};
+
+use std::f32::consts::FRAC_PI_4;
+
+use crate::motion;
+
+/// Adds [`RtsCameraConfig`] resource and internals systems gated by [`is_active`] run criteria
+/// grouped on [`CameraUpdate`](super::CameraUpdate) system set.
+pub struct RtsCameraPlugin;
+
+impl Plugin for RtsCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RtsCameraConfig>().add_systems(
+            Update,
+            (pan_camera, zoom_and_rotate_camera, follow_target)
+                .chain()
+                .in_set(super::CameraUpdate)
+                .run_if(is_active),
+        );
+    }
+}
+
+/// Component used to tag entity camera.
+/// There can be only one Entity with this component.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct RtsCamera;
+
+/// Entity which [`RtsCamera`] smoothly follows. Add it to the camera entity to select which entity
+/// to follow. It is removed when the player pans the camera away.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct RtsFollow(pub Entity);
+
+/// Current state of [`RtsCamera`], which smoothly follows [`RtsCameraConfig`] and followed entity.
+/// It is added to the camera when it starts moving.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct RtsState {
+    /// Point on the ground the camera is currently looking at.
+    pub focus: Vec3,
+    pub height: f32,
+    pub yaw: f32,
+    /// Elapsed time, in seconds, of the last update, used to snap into place when the camera is
+    /// activated again.
+    last_update: f32,
+}
+
+/// Key bindings used internal systems to pan, rotate and zoom camera.
+#[derive(Debug)]
+pub struct KeyBindings {
+    /// Forwards pan key binding, defaults to [`KeyCode::KeyW`].
+    pub forward: KeyCode,
+
+    /// Backwards pan key binding, defaults to [`KeyCode::KeyS`].
+    pub backward: KeyCode,
+
+    /// Leftwards pan key binding, defaults to [`KeyCode::KeyA`].
+    pub left: KeyCode,
+
+    /// Rightwards pan key binding, defaults to [`KeyCode::KeyD`].
+    pub right: KeyCode,
+
+    /// Rotate left by [`RtsCameraConfig::rotation_step`] key binding, defaults to
+    /// [`KeyCode::KeyQ`].
+    pub rotate_left: KeyCode,
+
+    /// Rotate right by [`RtsCameraConfig::rotation_step`] key binding, defaults to
+    /// [`KeyCode::KeyE`].
+    pub rotate_right: KeyCode,
+
+    /// Zoom in key binding, defaults to [`KeyCode::PageUp`].
+    pub zoom_in: KeyCode,
+
+    /// Zoom out key binding, defaults to [`KeyCode::PageDown`].
+    pub zoom_out: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            backward: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            rotate_left: KeyCode::KeyQ,
+            rotate_right: KeyCode::KeyE,
+            zoom_in: KeyCode::PageUp,
+            zoom_out: KeyCode::PageDown,
+        }
+    }
+}
+
+/// Allows to configure [`RtsCamera`] behavior.
+#[derive(Debug, Resource)]
+pub struct RtsCameraConfig {
+    /// Enable or disable internal systems. This flag is used by [`is_active`] run criteria.
+    pub active: bool,
+
+    /// Point on the ground the camera looks at. Ignored when camera has [`RtsFollow`].
+    pub focus: Vec3,
+
+    /// Height, in units, above focus point. Lower values will make camera closer to the ground.
+    pub height: f32,
+
+    /// Minimum height to keep when zooming in.
+    pub min_height: f32,
+
+    /// Maximum height to keep when zooming out.
+    pub max_height: f32,
+
+    /// Rotation, in radians, around the focus point.
+    pub yaw: f32,
+
+    /// Angle, in radians, between the ground and the camera view direction.
+    pub pitch: f32,
+
+    /// Rotation, in radians, of each rotate key press. Yaw is always snapped to multiples of it.
+    pub rotation_step: f32,
+
+    /// Pan speed, in units per second, for each unit of height, so panning feels the same on any
+    /// zoom level.
+    pub pan_speed: f32,
+
+    /// Distance, in pixels, from window edges where the cursor pans the camera. Zero disables edge
+    /// scrolling.
+    pub edge_scroll_margin: f32,
+
+    /// Zoom speed in units when using keys.
+    pub key_zoom_speed: f32,
+
+    /// Zoom distance in units for each mouse wheel line.
+    pub mouse_zoom_speed: f32,
+
+    /// Key bindings used by camera. See [`KeyBindings`] for more info.
+    pub bindings: KeyBindings,
+
+    /// How fast the camera reaches its desired position. Higher values are snappier, while zero
+    /// disables smoothing.
+    pub damping: f32,
+}
+
+impl Default for RtsCameraConfig {
+    fn default() -> Self {
+        RtsCameraConfig {
+            active: false,
+
+            focus: Vec3::ZERO,
+
+            height: 30.0,
+            min_height: 10.0,
+            max_height: 80.0,
+
+            yaw: 0.0,
+            pitch: FRAC_PI_4 + FRAC_PI_4 / 3.0,
+            rotation_step: FRAC_PI_4,
+
+            pan_speed: 1.0,
+            edge_scroll_margin: 10.0,
+
+            key_zoom_speed: 30.0,
+            mouse_zoom_speed: 3.0,
+
+            bindings: KeyBindings::default(),
+
+            damping: 8.0,
+        }
+    }
+}
+
+/// Returns true when [`RtsCameraConfig::active`] is true.
+pub fn is_active(config: Res<RtsCameraConfig>) -> bool {
+    config.active
+}
+
+/// Pans camera focus using key bindings and edge scrolling in [`RtsCameraConfig`]. Panning stops
+/// following the [`RtsFollow`] entity.
+///
+/// This system is gated by [`is_active`] run criteria.
+fn pan_camera(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut config: ResMut<RtsCameraConfig>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(Entity, Option<&RtsState>), (With<RtsCamera>, With<RtsFollow>)>,
+) {
+    let bindings = &config.bindings;
+    let mut dir = Vec2::ZERO;
+
+    if input.pressed(bindings.forward) {
+        dir.y -= 1.0;
+    } else if input.pressed(bindings.backward) {
+        dir.y += 1.0;
+    }
+
+    if input.pressed(bindings.left) {
+        dir.x -= 1.0;
+    } else if input.pressed(bindings.right) {
+        dir.x += 1.0;
+    }
+
+    let margin = config.edge_scroll_margin;
+    if let Some((cursor, size)) = q_window
+        .get_single()
+        .ok()
+        .filter(|_| margin > 0.0)
+        .and_then(|window| {
+            Some((
+                window.cursor_position()?,
+                Vec2::new(window.width(), window.height()),
+            ))
+        })
+    {
+        if cursor.x <= margin {
+            dir.x -= 1.0;
+        } else if cursor.x >= size.x - margin {
+            dir.x += 1.0;
+        }
+
+        if cursor.y <= margin {
+            dir.y -= 1.0;
+        } else if cursor.y >= size.y - margin {
+            dir.y += 1.0;
+        }
+    }
+
+    if dir == Vec2::ZERO {
+        return;
+    }
+
+    // Stop following and pan from where the camera currently is.
+    if let Ok((entity, state)) = q_camera.get_single() {
+        commands.entity(entity).remove::<RtsFollow>();
+        if let Some(state) = state {
+            config.focus = state.focus;
+        }
+    }
+
+    let dir = Quat::from_rotation_y(config.yaw) * Vec3::new(dir.x, 0.0, dir.y).normalize();
+    let speed = config.pan_speed * config.height * time.delta_seconds();
+    config.focus += dir * speed;
+}
+
+/// Zooms camera using keys and mouse wheel and rotates it by [`RtsCameraConfig::rotation_step`].
+///
+/// This system is gated by [`is_active`] run criteria.
+fn zoom_and_rotate_camera(
+    input: Res<ButtonInput<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    time: Res<Time>,
+    mut config: ResMut<RtsCameraConfig>,
+) {
+    let mut zoom = 0.0;
+
+    if input.pressed(config.bindings.zoom_in) {
+        zoom -= config.key_zoom_speed * time.delta_seconds();
+    } else if input.pressed(config.bindings.zoom_out) {
+        zoom += config.key_zoom_speed * time.delta_seconds();
+    }
+
+    for evt in mouse_wheel.read() {
+        zoom -= motion::scroll_lines(evt) * config.mouse_zoom_speed;
+    }
+
+    if zoom != 0.0 {
+        config.height = (config.height + zoom).clamp(config.min_height, config.max_height);
+    }
+
+    let mut steps = 0.0;
+    if input.just_pressed(config.bindings.rotate_left) {
+        steps -= 1.0;
+    }
+    if input.just_pressed(config.bindings.rotate_right) {
+        steps += 1.0;
+    }
+
+    if steps != 0.0 && config.rotation_step > 0.0 {
+        let step = config.rotation_step;
+        config.yaw = ((config.yaw / step).round() + steps) * step;
+    }
+}
+
+/// Smoothly moves camera towards its desired position, looking at either the focus point on
+/// [`RtsCameraConfig`] or the [`RtsFollow`] entity.
+///
+/// This system is gated by [`is_active`] run criteria.
+fn follow_target(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<RtsCameraConfig>,
+    q_entities: Query<&GlobalTransform, Without<RtsCamera>>,
+    mut q: Query<
+        (
+            Entity,
+            &mut Transform,
+            Option<&RtsFollow>,
+            Option<&mut RtsState>,
+        ),
+        With<RtsCamera>,
+    >,
+) {
+    let Ok((entity, mut camera_transform, follow, state)) = q.get_single_mut() else {
+        return;
+    };
+
+    let focus = follow
+        .and_then(|RtsFollow(target)| q_entities.get(*target).ok())
+        .map_or(config.focus, GlobalTransform::translation);
+
+    let now = time.elapsed_seconds();
+    let desired = RtsState {
+        focus,
+        height: config.height,
+        yaw: config.yaw,
+        last_update: now,
+    };
+
+    let new_state = match state {
+        Some(mut state) => {
+            let since = now - state.last_update;
+            *state = match motion::follow_smoothing(config.damping, since, time.delta_seconds()) {
+                Some(t) => RtsState {
+                    focus: state.focus.lerp(focus, t),
+                    height: state.height + (desired.height - state.height) * t,
+                    yaw: state.yaw + (desired.yaw - state.yaw) * t,
+                    last_update: now,
+                },
+                None => desired,
+            };
+            *state
+        }
+        None => {
+            commands.entity(entity).insert(desired);
+            desired
+        }
+    };
+
+    // Camera is placed behind the focus point, relative to its yaw, looking down at pitch angle.
+    let back = new_state.height / config.pitch.tan();
+    let offset = Quat::from_rotation_y(new_state.yaw) * Vec3::new(0.0, new_state.height, back);
+
+    camera_transform.translation = new_state.focus + offset;
+    camera_transform.look_at(new_state.focus, Vec3::Y);
+}
@@ -0,0 +1,363 @@
+//! Hot reload of kinds descriptions and texture atlas, so textures can be iterated without
+//! restarting the client. Files are polled, since asset file watcher isn't enabled.
+//!
+//! Whenever atlas is loaded, it is also sliced into a mipmapped texture array, which is used by
+//! chunk material, since mip levels of a single atlas bleed neighbor tiles at distance.
+
+use std::time::{Duration, SystemTime};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{
+            Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+        },
+        texture::{ImageAddressMode, ImageFilterMode, ImageSampler, ImageSamplerDescriptor},
+    },
+    time::common_conditions::on_timer,
+};
+use projekto_core::voxel::KindsDescs;
+
+use crate::{
+    material::ChunkMaterial, ChunkMaterialHandle, ClientSettings, KindsAtlasRes, PlayerLandscape,
+};
+
+const POLL_INTERVAL_MS: u64 = 1000;
+
+pub(crate) struct AtlasReloadPlugin;
+
+impl Plugin for AtlasReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                switch_texture_pack.run_if(resource_changed::<ClientSettings>),
+                (reload_kinds_descs, reload_atlas_image)
+                    .run_if(on_timer(Duration::from_millis(POLL_INTERVAL_MS))),
+                apply_texture_filtering.run_if(
+                    on_event::<AssetEvent<Image>>().or_else(resource_changed::<ClientSettings>),
+                ),
+                update_atlas.run_if(
+                    on_event::<AssetEvent<Image>>()
+                        .or_else(resource_changed::<KindsAtlasRes>)
+                        .or_else(resource_changed::<ClientSettings>),
+                ),
+            )
+                .chain()
+                .run_if(resource_exists::<KindsAtlasRes>),
+        );
+    }
+}
+
+/// **Returns** a grid layout with `tiles` by `tiles` tiles which covers the whole atlas.
+pub(crate) fn tiles_layout(atlas_size: UVec2, tiles: u16) -> TextureAtlasLayout {
+    let tiles = tiles.max(1) as usize;
+    TextureAtlasLayout::from_grid(
+        atlas_size.as_vec2() / tiles as f32,
+        tiles,
+        tiles,
+        None,
+        None,
+    )
+}
+
+/// Slices the given atlas, with `tiles` by `tiles` tiles, into a texture array with a layer per
+/// tile, in the same order of [`KindsDescs::tile_layer`]. Each layer has its own mip chain, down to
+/// a single pixel, so tiles never bleed into each other at distance.
+///
+/// **Returns** `None` if atlas can't be sliced, like when its format isn't 8 bits RGBA, in which
+/// case the atlas itself must be used.
+pub(crate) fn slice_atlas(atlas: &Image, tiles: u16, linear: bool) -> Option<Image> {
+    const PIXEL_SIZE: usize = 4;
+
+    let format = atlas.texture_descriptor.format;
+    if !matches!(
+        format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+    ) {
+        return None;
+    }
+
+    let size = atlas.size();
+    let tiles = tiles.max(1) as u32;
+    let tile_size = size.x / tiles;
+    if size.x != size.y || tile_size == 0 || tile_size * tiles != size.x {
+        return None;
+    }
+
+    let tile_size = tile_size as usize;
+    let mip_level_count = tile_size.ilog2() + 1;
+    let atlas_row = size.x as usize * PIXEL_SIZE;
+
+    // Layers are stored one after another, each one with all of its mip levels.
+    let mut data = Vec::with_capacity(atlas.data.len() * 2);
+    for tile in 0..(tiles * tiles) as usize {
+        let (x, y) = (tile % tiles as usize, tile / tiles as usize);
+        let first_pixel = y * tile_size * atlas_row + x * tile_size * PIXEL_SIZE;
+
+        let mut level = (0..tile_size)
+            .flat_map(|row| {
+                let start = first_pixel + row * atlas_row;
+                atlas.data[start..start + tile_size * PIXEL_SIZE]
+                    .iter()
+                    .copied()
+            })
+            .collect::<Vec<_>>();
+        let mut level_size = tile_size;
+
+        loop {
+            data.extend_from_slice(&level);
+            if level_size == 1 {
+                break;
+            }
+
+            level = downsample(&level, level_size);
+            level_size /= 2;
+        }
+    }
+
+    Some(Image {
+        data,
+        texture_descriptor: bevy::render::render_resource::TextureDescriptor {
+            size: Extent3d {
+                width: tile_size as u32,
+                height: tile_size as u32,
+                depth_or_array_layers: tiles * tiles,
+            },
+            mip_level_count,
+            dimension: TextureDimension::D2,
+            ..atlas.texture_descriptor.clone()
+        },
+        sampler: ImageSampler::Descriptor(atlas_sampler(linear)),
+        texture_view_descriptor: Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        }),
+        asset_usage: atlas.asset_usage,
+    })
+}
+
+/// Sampler of atlas textures, which are minified either linearly or nearest. Magnification is
+/// always nearest, so voxels keep their pixelated look up close.
+fn atlas_sampler(linear: bool) -> ImageSamplerDescriptor {
+    let filter = if linear {
+        ImageFilterMode::Linear
+    } else {
+        ImageFilterMode::Nearest
+    };
+
+    ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        min_filter: filter,
+        mipmap_filter: filter,
+        ..ImageSamplerDescriptor::nearest()
+    }
+}
+
+/// Averages each 2x2 block of pixels of the given square RGBA level, halving its size.
+fn downsample(level: &[u8], size: usize) -> Vec<u8> {
+    let half = size / 2;
+    let pixel = |x: usize, y: usize, channel: usize| level[(y * size + x) * 4 + channel] as u32;
+
+    (0..half * half)
+        .flat_map(|i| {
+            let (x, y) = ((i % half) * 2, (i / half) * 2);
+            (0..4).map(move |c| {
+                let sum = pixel(x, y, c)
+                    + pixel(x + 1, y, c)
+                    + pixel(x, y + 1, c)
+                    + pixel(x + 1, y + 1, c);
+                (sum / 4) as u8
+            })
+        })
+        .collect()
+}
+
+/// **Returns** the last modification time of the given file, if it exists.
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Polls the given file and **returns** true when it was modified since last poll.
+fn poll_modified(path: &str, last_modified: &mut Option<SystemTime>) -> bool {
+    let Some(modified) = modified_time(path) else {
+        return false;
+    };
+
+    let previous = last_modified.replace(modified);
+    previous.is_some_and(|previous| previous != modified)
+}
+
+/// Loads the atlas on the given path and replaces the current one on UI. Chunk material is updated
+/// once the new atlas is loaded.
+fn replace_atlas(
+    path: &str,
+    atlas: &mut KindsAtlasRes,
+    asset_server: &AssetServer,
+    q_ui: &mut Query<&mut UiImage>,
+) {
+    let previous = std::mem::replace(&mut atlas.atlas, asset_server.load(path.to_string()));
+    atlas.path = path.to_string();
+
+    for mut image in q_ui.iter_mut().filter(|image| image.texture == previous) {
+        image.texture = atlas.atlas.clone();
+    }
+}
+
+fn switch_texture_pack(
+    settings: Res<ClientSettings>,
+    mut atlas: ResMut<KindsAtlasRes>,
+    asset_server: Res<AssetServer>,
+    mut q_ui: Query<&mut UiImage>,
+) {
+    let path = settings.atlas_path(KindsDescs::get());
+    if path == atlas.path {
+        return;
+    }
+
+    info!("Switching texture atlas to {path}");
+    replace_atlas(path, &mut atlas, &asset_server, &mut q_ui);
+}
+
+fn reload_kinds_descs(
+    mut last_modified: Local<Option<SystemTime>>,
+    (settings, asset_server): (Res<ClientSettings>, Res<AssetServer>),
+    mut atlas: ResMut<KindsAtlasRes>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+    material: Option<Res<ChunkMaterialHandle>>,
+    mut landscape: ResMut<PlayerLandscape>,
+    mut q_ui: Query<&mut UiImage>,
+) {
+    let path = KindsDescs::default_path();
+    if !poll_modified(&path, &mut last_modified) {
+        return;
+    }
+
+    let descs = match KindsDescs::reload(&path) {
+        Ok(descs) => descs,
+        Err(err) => {
+            warn!("Failed to reload kinds descriptions at {path}. Error: {err}");
+            return;
+        }
+    };
+
+    info!("Kinds descriptions reloaded");
+
+    let atlas_path = settings.atlas_path(descs);
+    if atlas_path != atlas.path {
+        replace_atlas(atlas_path, &mut atlas, &asset_server, &mut q_ui);
+    }
+
+    if let Some(material) = material.and_then(|handle| materials.get_mut(&handle.0)) {
+        material.tile_texture_size = 1.0 / descs.count_tiles() as f32;
+    }
+
+    // Vertices UVs are computed using kinds descriptions, so request all chunks again, which will
+    // be remeshed when received.
+    landscape.set_changed();
+}
+
+fn reload_atlas_image(
+    mut last_modified: Local<(String, Option<SystemTime>)>,
+    atlas: Res<KindsAtlasRes>,
+    asset_server: Res<AssetServer>,
+) {
+    let (watched_path, modified) = &mut *last_modified;
+    if *watched_path != atlas.path {
+        watched_path.clone_from(&atlas.path);
+        *modified = None;
+    }
+
+    let file_path = format!("{}/{}", env!("ASSETS_PATH"), atlas.path);
+    if poll_modified(&file_path, modified) {
+        info!("Reloading texture atlas {}", atlas.path);
+        asset_server.reload(atlas.path.clone());
+    }
+}
+
+/// Applies texture filtering of current graphics preset on atlas, whenever the preset changes or
+/// the atlas is loaded, since loading resets the image sampler.
+fn apply_texture_filtering(
+    mut reader: EventReader<AssetEvent<Image>>,
+    settings: Res<ClientSettings>,
+    atlas: Res<KindsAtlasRes>,
+    mut images: ResMut<Assets<Image>>,
+    mut applied: Local<Option<(AssetId<Image>, bool)>>,
+) {
+    let atlas_id = atlas.atlas.id();
+    let is_atlas_loaded = reader.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } => *id == atlas_id,
+        _ => false,
+    });
+
+    let linear = settings.graphics.linear_filtering();
+    if !is_atlas_loaded && *applied == Some((atlas_id, linear)) {
+        return;
+    }
+
+    let Some(image) = images.get_mut(atlas_id) else {
+        return;
+    };
+
+    image.sampler = ImageSampler::Descriptor(atlas_sampler(linear));
+    *applied = Some((atlas_id, linear));
+}
+
+/// Updates atlas layout and chunk material whenever the atlas is loaded, reloaded or replaced.
+/// Atlas is sliced into a texture array again too, since texture filtering is baked into it.
+fn update_atlas(
+    mut reader: EventReader<AssetEvent<Image>>,
+    (atlas, settings): (Res<KindsAtlasRes>, Res<ClientSettings>),
+    mut images: ResMut<Assets<Image>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+    material: Option<Res<ChunkMaterialHandle>>,
+    mut sliced: Local<Option<(AssetId<Image>, bool)>>,
+) {
+    let atlas_id = atlas.atlas.id();
+    let is_atlas_loaded = reader.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => *id == atlas_id,
+        _ => false,
+    });
+
+    let linear = settings.graphics.linear_filtering();
+    if !is_atlas_loaded && !atlas.is_changed() && *sliced == Some((atlas_id, linear)) {
+        return;
+    }
+
+    let Some(image) = images.get(atlas_id) else {
+        return;
+    };
+
+    let size = image.size();
+    let tiles = KindsDescs::get().count_tiles();
+    if size.x != size.y || size.x % tiles as u32 != 0 {
+        warn!(
+            "Texture atlas {} has size {size}, which can't be split into {tiles}x{tiles} tiles",
+            atlas.path
+        );
+    }
+
+    if let Some(layout) = layouts.get_mut(&atlas.layout) {
+        *layout = tiles_layout(size, tiles);
+    }
+
+    let texture_array = slice_atlas(image, tiles, linear);
+    if texture_array.is_none() {
+        warn!(
+            "Texture atlas {} can't be sliced into a texture array. Using it as is.",
+            atlas.path
+        );
+    }
+    *sliced = Some((atlas_id, linear));
+
+    // Always touch the material, so its bind group is recreated even when the image handle is the
+    // same, like when it is reloaded.
+    if let Some(material) = material.and_then(|handle| materials.get_mut(&handle.0)) {
+        material.texture = atlas.atlas.clone();
+        material.texture_array = texture_array.map(|image| images.add(image));
+    }
+}
@@ -0,0 +1,280 @@
+//! Event driven sounds: footsteps, voxel break and place, and ambience near liquids.
+//!
+//! Sounds are loaded from `sounds/<category>/<event>.ogg`, where `category` is the
+//! [`KindSoundDesc`] of a kind in lower case, like `sounds/stone/break.ogg`. Missing files are
+//! reported by the asset server and just don't play.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::common_conditions::on_timer, utils::HashMap};
+use projekto_core::{
+    chunk::Chunk,
+    math, raycast,
+    voxel::{self, KindSoundDesc, Voxel},
+};
+
+use crate::{
+    controller::{
+        character_controller::{CharacterController, CharacterMotion, CHARACTER_HALF_EXTENTS},
+        interaction::VoxelInteracted,
+    },
+    ChunkKindClientCache,
+};
+
+/// Horizontal distance, in voxels, walked between two footsteps.
+const STRIDE_LENGTH: f32 = 1.8;
+/// Max distance, in voxels, which liquid ambience can be heard.
+const AMBIENCE_RADIUS: i32 = 8;
+const AMBIENCE_UPDATE_INTERVAL_MS: u64 = 1000;
+/// Distance between left and right ears of the listener.
+const EAR_GAP: f32 = 0.3;
+
+const CATEGORIES: [KindSoundDesc; 5] = [
+    KindSoundDesc::Dirt,
+    KindSoundDesc::Grass,
+    KindSoundDesc::Stone,
+    KindSoundDesc::Glass,
+    KindSoundDesc::Water,
+];
+
+pub(crate) struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelSounds>()
+            .add_systems(Startup, load_voxel_sounds)
+            .add_systems(
+                Update,
+                (
+                    add_listener,
+                    play_footsteps,
+                    play_interaction_sounds.run_if(on_event::<VoxelInteracted>()),
+                    update_liquid_ambience
+                        .run_if(on_timer(Duration::from_millis(AMBIENCE_UPDATE_INTERVAL_MS))),
+                ),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SoundEvent {
+    Step,
+    Break,
+    Place,
+    Ambience,
+}
+
+impl SoundEvent {
+    fn name(self) -> &'static str {
+        match self {
+            SoundEvent::Step => "step",
+            SoundEvent::Break => "break",
+            SoundEvent::Place => "place",
+            SoundEvent::Ambience => "ambience",
+        }
+    }
+}
+
+fn category_name(category: KindSoundDesc) -> Option<&'static str> {
+    match category {
+        KindSoundDesc::None => None,
+        KindSoundDesc::Dirt => Some("dirt"),
+        KindSoundDesc::Grass => Some("grass"),
+        KindSoundDesc::Stone => Some("stone"),
+        KindSoundDesc::Glass => Some("glass"),
+        KindSoundDesc::Water => Some("water"),
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+struct VoxelSounds(HashMap<(KindSoundDesc, SoundEvent), Handle<AudioSource>>);
+
+impl VoxelSounds {
+    fn get(&self, category: KindSoundDesc, event: SoundEvent) -> Option<Handle<AudioSource>> {
+        self.0.get(&(category, event)).cloned()
+    }
+}
+
+/// Looping sound emitter placed at the nearest liquid voxel.
+#[derive(Component, Debug)]
+struct LiquidAmbience;
+
+fn load_voxel_sounds(asset_server: Res<AssetServer>, mut sounds: ResMut<VoxelSounds>) {
+    for category in CATEGORIES {
+        let Some(name) = category_name(category) else {
+            continue;
+        };
+
+        let events: &[SoundEvent] = if category == KindSoundDesc::Water {
+            &[SoundEvent::Step, SoundEvent::Ambience]
+        } else {
+            &[SoundEvent::Step, SoundEvent::Break, SoundEvent::Place]
+        };
+
+        for &event in events {
+            let path = format!("sounds/{name}/{}.ogg", event.name());
+            sounds.0.insert((category, event), asset_server.load(path));
+        }
+    }
+}
+
+fn add_listener(mut commands: Commands, q: Query<Entity, Added<CharacterController>>) {
+    for entity in &q {
+        commands
+            .entity(entity)
+            .insert(SpatialListener::new(EAR_GAP));
+    }
+}
+
+fn kind_at(kinds: &ChunkKindClientCache, position: Vec3) -> Option<voxel::Kind> {
+    let (chunk, voxel) = raycast::to_chunk_voxel(math::floor(position))?;
+    kinds.get(&chunk).map(|kind| kind.get(voxel))
+}
+
+fn spawn_sound(commands: &mut Commands, source: Handle<AudioSource>, position: Vec3) {
+    commands.spawn((
+        AudioBundle {
+            source,
+            settings: PlaybackSettings::DESPAWN.with_spatial(true),
+        },
+        SpatialBundle::from_transform(Transform::from_translation(position)),
+    ));
+}
+
+fn play_footsteps(
+    mut commands: Commands,
+    time: Res<Time>,
+    kinds: Res<ChunkKindClientCache>,
+    sounds: Res<VoxelSounds>,
+    q: Query<(&Transform, &CharacterMotion), With<CharacterController>>,
+    mut walked: Local<f32>,
+) {
+    let Ok((transform, motion)) = q.get_single() else {
+        return;
+    };
+
+    if !motion.is_grounded && !motion.is_swimming {
+        return;
+    }
+
+    *walked += motion.velocity.xz().length() * time.delta_seconds();
+    if *walked < STRIDE_LENGTH {
+        return;
+    }
+    *walked = 0.0;
+
+    let feet = transform.translation - Vec3::Y * CHARACTER_HALF_EXTENTS.y;
+    let category = if motion.is_swimming {
+        KindSoundDesc::Water
+    } else {
+        let Some(kind) = kind_at(&kinds, feet - Vec3::Y * 0.1) else {
+            return;
+        };
+        kind.sound()
+    };
+
+    if let Some(source) = sounds.get(category, SoundEvent::Step) {
+        spawn_sound(&mut commands, source, feet);
+    }
+}
+
+fn play_interaction_sounds(
+    mut commands: Commands,
+    sounds: Res<VoxelSounds>,
+    mut reader: EventReader<VoxelInteracted>,
+) {
+    for &VoxelInteracted {
+        chunk,
+        voxel,
+        previous,
+        kind,
+        ..
+    } in reader.read()
+    {
+        let (category, event) = if kind.is_none() {
+            (previous.sound(), SoundEvent::Break)
+        } else {
+            (kind.sound(), SoundEvent::Place)
+        };
+
+        if let Some(source) = sounds.get(category, event) {
+            let position = voxel::to_world(voxel, chunk) + Vec3::splat(0.5);
+            spawn_sound(&mut commands, source, position);
+        }
+    }
+}
+
+/// **Returns** the world position of the nearest liquid voxel around `center`, if any.
+fn find_nearest_liquid(kinds: &ChunkKindClientCache, center: Vec3) -> Option<Vec3> {
+    let center = math::floor(center);
+    let mut nearest: Option<(i32, Vec3)> = None;
+
+    for y in -AMBIENCE_RADIUS..=AMBIENCE_RADIUS {
+        for z in -AMBIENCE_RADIUS..=AMBIENCE_RADIUS {
+            for x in -AMBIENCE_RADIUS..=AMBIENCE_RADIUS {
+                let offset = IVec3::new(x, y, z);
+                let distance = offset.length_squared();
+
+                if nearest.is_some_and(|(nearest, _)| nearest <= distance) {
+                    continue;
+                }
+
+                let Some((chunk, voxel)): Option<(Chunk, Voxel)> =
+                    raycast::to_chunk_voxel(center + offset)
+                else {
+                    continue;
+                };
+
+                let is_liquid = kinds
+                    .get(&chunk)
+                    .is_some_and(|kind| kind.get(voxel).is_liquid());
+
+                if is_liquid {
+                    let position = voxel::to_world(voxel, chunk) + Vec3::splat(0.5);
+                    nearest = Some((distance, position));
+                }
+            }
+        }
+    }
+
+    nearest.map(|(_, position)| position)
+}
+
+fn update_liquid_ambience(
+    mut commands: Commands,
+    kinds: Res<ChunkKindClientCache>,
+    sounds: Res<VoxelSounds>,
+    q_listener: Query<&GlobalTransform, With<SpatialListener>>,
+    mut q_ambience: Query<(Entity, &mut Transform), With<LiquidAmbience>>,
+) {
+    let nearest = q_listener
+        .get_single()
+        .ok()
+        .and_then(|listener| find_nearest_liquid(&kinds, listener.translation()));
+
+    let Some(position) = nearest else {
+        for (entity, _) in &q_ambience {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+
+    if let Ok((_, mut transform)) = q_ambience.get_single_mut() {
+        transform.translation = position;
+        return;
+    }
+
+    let Some(source) = sounds.get(KindSoundDesc::Water, SoundEvent::Ambience) else {
+        return;
+    };
+
+    commands.spawn((
+        AudioBundle {
+            source,
+            settings: PlaybackSettings::LOOP.with_spatial(true),
+        },
+        SpatialBundle::from_transform(Transform::from_translation(position)),
+        LiquidAmbience,
+        Name::new("LiquidAmbience"),
+    ));
+}
@@ -1,8 +1,10 @@
 use bevy::prelude::*;
 use projekto_core::{chunk::Chunk, voxel};
 
-#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut)]
+#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
 pub struct ChunkLocal(pub Chunk);
 
-#[derive(Component, Default, Debug, Clone, Deref, DerefMut)]
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
 pub struct ChunkVertex(pub Vec<voxel::Vertex>);
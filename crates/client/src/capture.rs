@@ -0,0 +1,329 @@
+//! Screenshots and demo capture. A demo is a camera path recorded by the player, which can be
+//! played back later, with UI hidden, to produce consistent footage and performance comparisons.
+
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    math::cubic_splines::CubicCurve,
+    prelude::*,
+    render::view::screenshot::ScreenshotManager,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+use projekto_camera::fly_by::FlyByCamera;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    action_just_pressed,
+    controller::camera_director::{ActiveCamera, CameraDirector},
+    ClientSettings, InputAction,
+};
+
+/// Folder, relative to working directory, where screenshots are saved.
+const SCREENSHOTS_PATH: &str = "screenshots";
+/// File, relative to working directory, where the last recorded [`DemoPath`] is persisted.
+const DEMO_PATH: &str = "demo.ron";
+/// Time, in seconds, between two recorded keyframes. Playback uses the same interval, so a demo
+/// always takes the same time to play.
+const KEYFRAME_INTERVAL: f32 = 0.5;
+
+pub(crate) struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DemoCapture>()
+            .insert_resource(DemoPath::load(DEMO_PATH))
+            .add_systems(
+                Update,
+                (
+                    take_screenshot.run_if(action_just_pressed(InputAction::Screenshot)),
+                    toggle_demo_recording.run_if(action_just_pressed(InputAction::RecordDemo)),
+                    toggle_demo_playback.run_if(action_just_pressed(InputAction::PlayDemo)),
+                    record_demo,
+                    play_demo,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Camera transform recorded at a fixed interval.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DemoKeyframe {
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+/// Camera keyframes which are smoothly interpolated during demo playback.
+#[derive(Resource, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DemoPath {
+    pub keyframes: Vec<DemoKeyframe>,
+}
+
+impl DemoPath {
+    fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+
+        ron::from_str(&content).unwrap_or_else(|err| {
+            warn!("Failed to parse demo at {path:?}. Error: {err}");
+            Self::default()
+        })
+    }
+
+    fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        let result = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|content| std::fs::write(path, content).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            error!("Failed to save demo at {path:?}. Error: {err}");
+        }
+    }
+
+    /// **Returns** the duration, in seconds, of the whole path.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.len().saturating_sub(1) as f32 * KEYFRAME_INTERVAL
+    }
+
+    /// Builds a Catmull-Rom curve which passes through all keyframe translations, so keyframe `i`
+    /// is reached at `t = i`.
+    fn to_curve(&self) -> Option<CubicCurve<Vec3>> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+
+        if self.keyframes.len() < 2 {
+            return None;
+        }
+
+        // Catmull-Rom doesn't pass through first and last control points, so repeat them.
+        let points = std::iter::once(first.translation)
+            .chain(self.keyframes.iter().map(|k| k.translation))
+            .chain(std::iter::once(last.translation))
+            .collect::<Vec<_>>();
+
+        Some(CubicCardinalSpline::new_catmull_rom(points).to_curve())
+    }
+
+    /// **Returns** the interpolated rotation at the given curve parameter.
+    fn rotation(&self, t: f32) -> Quat {
+        let i = (t.floor() as usize).min(self.keyframes.len() - 1);
+        let next = (i + 1).min(self.keyframes.len() - 1);
+        self.keyframes[i]
+            .rotation
+            .slerp(self.keyframes[next].rotation, t - i as f32)
+    }
+}
+
+#[derive(Default)]
+enum DemoMode {
+    #[default]
+    Idle,
+    Recording,
+    Playing {
+        curve: CubicCurve<Vec3>,
+        /// UI root nodes and their visibility before playback started.
+        hidden_ui: Vec<(Entity, Visibility)>,
+        frames: u32,
+    },
+}
+
+/// Current state of demo capture.
+#[derive(Resource, Default)]
+pub struct DemoCapture {
+    mode: DemoMode,
+    elapsed: f32,
+}
+
+impl DemoCapture {
+    pub fn is_recording(&self) -> bool {
+        matches!(self.mode, DemoMode::Recording)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self.mode, DemoMode::Playing { .. })
+    }
+}
+
+/// Run condition which is true while a demo is being played back.
+pub(crate) fn is_demo_playing(capture: Res<DemoCapture>) -> bool {
+    capture.is_playing()
+}
+
+fn take_screenshot(
+    q_window: Query<Entity, With<PrimaryWindow>>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+) {
+    let Ok(window) = q_window.get_single() else {
+        return;
+    };
+
+    if let Err(err) = std::fs::create_dir_all(SCREENSHOTS_PATH) {
+        error!("Failed to create screenshots folder {SCREENSHOTS_PATH}. Error: {err}");
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = format!("{SCREENSHOTS_PATH}/projekto_{timestamp}.png");
+
+    if let Err(err) = screenshot_manager.save_screenshot_to_disk(window, path) {
+        warn!("Failed to take screenshot. Error: {err:?}");
+    }
+}
+
+fn toggle_demo_recording(mut capture: ResMut<DemoCapture>, mut demo: ResMut<DemoPath>) {
+    match capture.mode {
+        DemoMode::Idle => {
+            info!("Recording demo...");
+            demo.keyframes.clear();
+            capture.mode = DemoMode::Recording;
+            capture.elapsed = KEYFRAME_INTERVAL;
+        }
+        DemoMode::Recording => {
+            info!(
+                "Demo recorded with {} keyframes ({:.1}s).",
+                demo.keyframes.len(),
+                demo.duration()
+            );
+            demo.save(DEMO_PATH);
+            capture.mode = DemoMode::Idle;
+        }
+        DemoMode::Playing { .. } => (),
+    }
+}
+
+fn toggle_demo_playback(
+    mut capture: ResMut<DemoCapture>,
+    demo: Res<DemoPath>,
+    mut q_ui: Query<(Entity, &mut Visibility), (With<Node>, Without<Parent>)>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+    mut director: CameraDirector,
+    settings: Res<ClientSettings>,
+) {
+    match std::mem::take(&mut capture.mode) {
+        DemoMode::Idle => {
+            let Some(curve) = demo.to_curve() else {
+                let record = settings.input.describe(InputAction::RecordDemo);
+                warn!("There is no demo recorded. Press {record} to record one.");
+                return;
+            };
+
+            let hidden_ui = q_ui
+                .iter_mut()
+                .map(|(entity, mut visibility)| {
+                    (
+                        entity,
+                        std::mem::replace(&mut *visibility, Visibility::Hidden),
+                    )
+                })
+                .collect();
+
+            // Demo is always played on fly by camera, so player input must be disabled.
+            director.set_cam(ActiveCamera::FlyBy);
+            director.set_active(false);
+            if let Ok(mut window) = q_window.get_single_mut() {
+                window.cursor.visible = true;
+                window.cursor.grab_mode = CursorGrabMode::None;
+            }
+
+            info!("Playing demo ({:.1}s)...", demo.duration());
+            capture.mode = DemoMode::Playing {
+                curve,
+                hidden_ui,
+                frames: 0,
+            };
+            capture.elapsed = 0.0;
+        }
+        DemoMode::Playing { hidden_ui, .. } => {
+            info!("Demo playback stopped.");
+            restore_ui(hidden_ui, &mut q_ui);
+        }
+        mode @ DemoMode::Recording => capture.mode = mode,
+    }
+}
+
+fn restore_ui(
+    hidden_ui: Vec<(Entity, Visibility)>,
+    q_ui: &mut Query<(Entity, &mut Visibility), (With<Node>, Without<Parent>)>,
+) {
+    for (entity, previous) in hidden_ui {
+        if let Ok((_, mut visibility)) = q_ui.get_mut(entity) {
+            *visibility = previous;
+        }
+    }
+}
+
+fn record_demo(
+    time: Res<Time>,
+    mut capture: ResMut<DemoCapture>,
+    mut demo: ResMut<DemoPath>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+) {
+    if !capture.is_recording() {
+        return;
+    }
+
+    capture.elapsed += time.delta_seconds();
+    if capture.elapsed < KEYFRAME_INTERVAL {
+        return;
+    }
+    capture.elapsed -= KEYFRAME_INTERVAL;
+
+    let Some((_, transform)) = q_camera.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    let (_, rotation, translation) = transform.to_scale_rotation_translation();
+    demo.keyframes.push(DemoKeyframe {
+        translation,
+        rotation,
+    });
+}
+
+fn play_demo(
+    time: Res<Time>,
+    mut capture: ResMut<DemoCapture>,
+    demo: Res<DemoPath>,
+    mut q_camera: Query<&mut Transform, With<FlyByCamera>>,
+    mut q_ui: Query<(Entity, &mut Visibility), (With<Node>, Without<Parent>)>,
+) {
+    let DemoCapture { mode, elapsed } = &mut *capture;
+    let DemoMode::Playing { curve, frames, .. } = mode else {
+        return;
+    };
+
+    *elapsed += time.delta_seconds();
+    *frames += 1;
+
+    let t = *elapsed / KEYFRAME_INTERVAL;
+    let end = demo.keyframes.len().saturating_sub(1) as f32;
+
+    if let Ok(mut transform) = q_camera.get_single_mut() {
+        transform.translation = curve.position(t.min(end));
+        transform.rotation = demo.rotation(t.min(end));
+    }
+
+    if t < end {
+        return;
+    }
+
+    info!(
+        "Demo finished: {frames} frames in {:.2}s ({:.1} FPS).",
+        *elapsed,
+        *frames as f32 / *elapsed
+    );
+
+    if let DemoMode::Playing { hidden_ui, .. } = std::mem::take(mode) {
+        restore_ui(hidden_ui, &mut q_ui);
+    }
+}
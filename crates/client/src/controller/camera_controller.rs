@@ -1,40 +1,61 @@
-use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+use bevy::{
+    input::mouse::MouseMotion,
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
 use projekto_camera::{
-    first_person::{FirstPersonCamera, FirstPersonCameraConfig},
-    fly_by::{FlyByCamera, FlyByCameraConfig},
+    first_person::{FirstPersonCameraConfig, FirstPersonEffects},
+    fly_by::FlyByCameraConfig,
+    orbit::{self, OrbitCamera, OrbitState},
+    CameraUpdate,
+};
+use projekto_core::raycast;
+
+use crate::{
+    capture::is_demo_playing,
+    controller::{
+        camera_director::{ActiveCamera, CameraDirector},
+        character_controller::{CharacterController, CharacterMotion},
+    },
+    ActionInput, ChunkKindClientCache, ClientSettings, ClientState, InputAction,
 };
 
-use crate::controller::character_controller::CharacterControllerConfig;
+/// Distance kept between orbit camera and the terrain it collides with.
+const CAMERA_COLLISION_MARGIN: f32 = 0.2;
+/// How fast orbit camera moves back to its distance once there is no terrain in the way.
+const CAMERA_COLLISION_DAMPING: f32 = 8.0;
 
 pub struct CameraControllerPlugin;
 
 impl Plugin for CameraControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<ActiveCamera>()
-            .add_systems(Startup, setup_camera)
+        app.add_systems(Startup, setup_camera)
+            .add_systems(OnExit(ClientState::InGame), release_mouse)
+            .add_systems(
+                Update,
+                grab_mouse
+                    .run_if(in_state(ClientState::InGame))
+                    .run_if(not(is_demo_playing)),
+            )
             .add_systems(
                 Update,
                 (
-                    switch_camera.run_if(input_any_just_pressed([
-                        KeyCode::KeyI,
-                        KeyCode::KeyO,
-                        KeyCode::KeyP,
-                    ])),
-                    grab_mouse,
-                ),
+                    sync_first_person_effects,
+                    gamepad_look
+                        .run_if(in_state(ClientState::InGame))
+                        .run_if(not(is_demo_playing)),
+                )
+                    .before(CameraUpdate),
+            )
+            .add_systems(
+                Update,
+                resolve_camera_collision
+                    .after(CameraUpdate)
+                    .run_if(orbit::is_active),
             );
     }
 }
 
-fn input_any_just_pressed<T>(
-    inputs: impl IntoIterator<Item = T> + Copy,
-) -> impl Fn(Res<'_, ButtonInput<T>>) -> bool + Clone
-where
-    T: Clone + Copy + Eq + std::hash::Hash + Send + Sync + 'static,
-{
-    move |input: Res<ButtonInput<T>>| input.any_just_pressed(inputs)
-}
-
 fn setup_camera(
     mut flyby_config: ResMut<FlyByCameraConfig>,
     mut fp_config: ResMut<FirstPersonCameraConfig>,
@@ -43,89 +64,126 @@ fn setup_camera(
     fp_config.rotate_speed = 1.0;
 }
 
-#[derive(Default, Debug, Resource)]
-enum ActiveCamera {
-    #[default]
-    FlyBy,
-    FirstPerson,
-}
+/// Feeds character motion into first person camera, so it can bob and widen its field of view.
+fn sync_first_person_effects(
+    q_character: Query<&CharacterMotion, (With<CharacterController>, Changed<CharacterMotion>)>,
+    mut q_effects: Query<&mut FirstPersonEffects>,
+) {
+    let (Ok(motion), Ok(mut effects)) = (q_character.get_single(), q_effects.get_single_mut())
+    else {
+        return;
+    };
 
-#[derive(SystemParam)]
-struct CameraConfig<'w, 's> {
-    flyby: ResMut<'w, FlyByCameraConfig>,
-    first_person: ResMut<'w, FirstPersonCameraConfig>,
-    q: ParamSet<
-        'w,
-        's,
-        (
-            Query<'w, 's, &'static mut Camera, With<FlyByCamera>>,
-            Query<'w, 's, &'static mut Camera, With<FirstPersonCamera>>,
-        ),
-    >,
-    active_cam: ResMut<'w, ActiveCamera>,
-    character_controller: ResMut<'w, CharacterControllerConfig>,
+    if effects.sprinting != motion.is_sprinting || effects.grounded != motion.is_grounded {
+        effects.sprinting = motion.is_sprinting;
+        effects.grounded = motion.is_grounded;
+    }
 }
 
-impl<'w, 's> CameraConfig<'w, 's> {
-    fn set_cam(&mut self, active_camera: ActiveCamera) {
-        trace!("Toggling cameras");
-
-        self.first_person.active = false;
-        self.flyby.active = false;
-        self.character_controller.active = false;
-        self.q.p0().single_mut().is_active = false;
-        self.q.p1().single_mut().is_active = false;
-
-        *self.active_cam = active_camera;
-        match *self.active_cam {
-            ActiveCamera::FlyBy => {
-                self.flyby.active = true;
-                self.q.p0().single_mut().is_active = true;
-            }
-            ActiveCamera::FirstPerson => {
-                self.character_controller.active = true;
-                self.first_person.active = true;
-                self.q.p1().single_mut().is_active = true;
-            }
-        }
+/// Turns gamepad look stick into mouse motion, so all cameras can be rotated with it, using their
+/// own rotate speed.
+fn gamepad_look(
+    input: ActionInput,
+    settings: Res<ClientSettings>,
+    mut writer: EventWriter<MouseMotion>,
+) {
+    let look = input.look_axis();
+    if look == Vec2::ZERO {
+        return;
     }
 
-    fn set_active(&mut self, active: bool) {
-        match *self.active_cam {
-            ActiveCamera::FlyBy => self.flyby.active = active,
-            ActiveCamera::FirstPerson => {
-                self.character_controller.active = active;
-                self.first_person.active = active;
-            }
-        }
-    }
+    // Cameras already scale mouse motion by frame time, so it isn't scaled here. Screen Y grows
+    // down, while stick Y grows up.
+    writer.send(MouseMotion {
+        delta: Vec2::new(look.x, -look.y) * settings.gamepad.look_speed,
+    });
 }
 
-fn switch_camera(key_btn: Res<ButtonInput<KeyCode>>, mut config: CameraConfig) {
-    if key_btn.just_pressed(KeyCode::KeyI) {
-        config.set_cam(ActiveCamera::FlyBy);
-    } else if key_btn.just_pressed(KeyCode::KeyP) {
-        config.set_cam(ActiveCamera::FirstPerson);
-    }
+/// Pulls orbit camera in front of the first solid voxel between it and its focus, so it doesn't
+/// clip through terrain. Camera is pulled in right away, but moves back smoothly.
+fn resolve_camera_collision(
+    time: Res<Time>,
+    kinds: Res<ChunkKindClientCache>,
+    mut q: Query<(&mut Transform, &OrbitState), With<OrbitCamera>>,
+    mut distance: Local<Option<f32>>,
+) {
+    let Ok((mut transform, state)) = q.get_single_mut() else {
+        return;
+    };
+
+    let offset = transform.translation - state.focus;
+    let desired = offset.length();
+    let dir = offset.normalize_or_zero();
+
+    let hit = raycast::raycast(state.focus, dir, desired, |chunk, voxel| {
+        kinds.get(&chunk).map_or(0.0, |kind| {
+            let kind = kind.get(voxel);
+            if kind.is_liquid() {
+                0.0
+            } else {
+                kind.solid_height()
+            }
+        })
+    });
+
+    let allowed = hit.map_or(desired, |hit| {
+        (hit.distance - CAMERA_COLLISION_MARGIN).max(0.0)
+    });
+
+    let current = distance.unwrap_or(desired);
+    let new_distance = if allowed < current {
+        allowed
+    } else {
+        let t = 1.0 - (-CAMERA_COLLISION_DAMPING * time.delta_seconds()).exp();
+        current + (allowed - current) * t
+    };
+    *distance = Some(new_distance);
+
+    transform.translation = state.focus + dir * new_distance;
 }
 
-fn grab_mouse(
+pub(crate) fn grab_mouse(
     mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
     mouse_btn: Res<ButtonInput<MouseButton>>,
-    key_btn: Res<ButtonInput<KeyCode>>,
-    mut config: CameraConfig,
+    input: ActionInput,
+    q_interaction: Query<&Interaction>,
+    mut director: CameraDirector,
 ) {
     let Ok(mut window) = primary_window.get_single_mut() else {
         return;
     };
 
-    if window.cursor.visible && mouse_btn.just_pressed(MouseButton::Left) {
-        window.cursor.visible = false;
-        window.cursor.grab_mode = bevy::window::CursorGrabMode::Locked;
-        config.set_active(true);
-    } else if !window.cursor.visible && key_btn.just_pressed(KeyCode::Escape) {
+    // Clicks on UI elements shouldn't grab the mouse.
+    let is_over_ui = q_interaction.iter().any(|i| *i != Interaction::None);
+
+    let is_grabbed = window.cursor.grab_mode != CursorGrabMode::None;
+
+    if !is_grabbed && !is_over_ui && mouse_btn.just_pressed(MouseButton::Left) {
+        // RTS camera pans when cursor reaches window edges, so cursor is kept visible.
+        if director.active() == ActiveCamera::Rts {
+            window.cursor.grab_mode = CursorGrabMode::Confined;
+        } else {
+            window.cursor.visible = false;
+            window.cursor.grab_mode = CursorGrabMode::Locked;
+        }
+        director.set_active(true);
+    } else if is_grabbed && input.just_pressed(InputAction::ReleaseMouse) {
         window.cursor.visible = true;
-        window.cursor.grab_mode = bevy::window::CursorGrabMode::None;
-        config.set_active(false);
+        window.cursor.grab_mode = CursorGrabMode::None;
+        director.set_active(false);
     }
 }
+
+/// Releases the mouse when leaving the game, so title screen can be used.
+fn release_mouse(
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+    mut director: CameraDirector,
+) {
+    let Ok(mut window) = primary_window.get_single_mut() else {
+        return;
+    };
+
+    window.cursor.visible = true;
+    window.cursor.grab_mode = CursorGrabMode::None;
+    director.set_active(false);
+}
@@ -0,0 +1,314 @@
+//! Camera director owns which world camera is active and smoothly moves between cameras when
+//! switching, instead of cutting right away.
+//!
+//! During a transition, a dedicated camera is rendered, which goes from where the previous camera
+//! was to where the new one is. Controllers of the new camera are active during the transition, so
+//! the new camera can move while it is being reached.
+
+use bevy::{ecs::system::SystemParam, prelude::*, render::view::RenderLayers};
+use projekto_camera::{
+    first_person::{FirstPersonCamera, FirstPersonCameraConfig},
+    fly_by::{FlyByCamera, FlyByCameraConfig},
+    orbit::{OrbitCamera, OrbitCameraConfig},
+    rts::{RtsCamera, RtsCameraConfig},
+    CameraUpdate,
+};
+
+use crate::{
+    capture::is_demo_playing,
+    controller::{character_controller::CharacterControllerConfig, interaction},
+    ActionInput, InputAction,
+};
+
+/// How long, in seconds, switching between cameras takes.
+const TRANSITION_SECS: f32 = 0.4;
+
+pub(crate) struct CameraDirectorPlugin;
+
+impl Plugin for CameraDirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveCamera>()
+            .init_resource::<CameraTransition>()
+            .add_event::<CameraTransitionEvent>()
+            .add_systems(Startup, setup_transition_camera)
+            .add_systems(
+                Update,
+                (
+                    switch_camera.run_if(not(is_demo_playing)),
+                    update_transition.after(CameraUpdate),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// World camera which is currently controlled by the player.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Resource)]
+pub enum ActiveCamera {
+    #[default]
+    FlyBy,
+    FirstPerson,
+    /// Third person camera orbiting the character.
+    Orbit,
+    /// Top-down camera.
+    Rts,
+}
+
+impl ActiveCamera {
+    const ALL: [ActiveCamera; 4] = [
+        ActiveCamera::FlyBy,
+        ActiveCamera::FirstPerson,
+        ActiveCamera::Orbit,
+        ActiveCamera::Rts,
+    ];
+}
+
+/// Sent when cameras are switched, so gameplay can react to it.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraTransitionEvent {
+    /// Moving from one camera to another has started.
+    Started {
+        from: ActiveCamera,
+        to: ActiveCamera,
+    },
+    /// The given camera is now rendering, either because a transition finished or because it was
+    /// switched to right away.
+    Finished { camera: ActiveCamera },
+}
+
+/// Camera rendered while transitioning between cameras.
+#[derive(Component)]
+struct TransitionCamera;
+
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    from: Transform,
+    from_fov: f32,
+    to: ActiveCamera,
+    elapsed: f32,
+}
+
+#[derive(Resource, Default, Debug)]
+struct CameraTransition(Option<Transition>);
+
+type CameraItem = (
+    &'static mut Camera,
+    &'static GlobalTransform,
+    &'static Projection,
+);
+
+#[derive(SystemParam)]
+pub(crate) struct CameraDirector<'w, 's> {
+    flyby: ResMut<'w, FlyByCameraConfig>,
+    first_person: ResMut<'w, FirstPersonCameraConfig>,
+    orbit: ResMut<'w, OrbitCameraConfig>,
+    rts: ResMut<'w, RtsCameraConfig>,
+    q: ParamSet<
+        'w,
+        's,
+        (
+            Query<'w, 's, CameraItem, With<FlyByCamera>>,
+            Query<'w, 's, CameraItem, With<FirstPersonCamera>>,
+            Query<'w, 's, CameraItem, With<OrbitCamera>>,
+            Query<'w, 's, CameraItem, With<RtsCamera>>,
+            Query<
+                'w,
+                's,
+                (
+                    &'static mut Camera,
+                    &'static mut Transform,
+                    &'static mut Projection,
+                ),
+                With<TransitionCamera>,
+            >,
+        ),
+    >,
+    active_cam: ResMut<'w, ActiveCamera>,
+    transition: ResMut<'w, CameraTransition>,
+    character_controller: ResMut<'w, CharacterControllerConfig>,
+    writer: EventWriter<'w, CameraTransitionEvent>,
+}
+
+fn fov(projection: &Projection) -> f32 {
+    match projection {
+        Projection::Perspective(perspective) => perspective.fov,
+        Projection::Orthographic(_) => PerspectiveProjection::default().fov,
+    }
+}
+
+impl<'w, 's> CameraDirector<'w, 's> {
+    /// Sets whether the given camera is rendering.
+    fn set_rendering(&mut self, camera: ActiveCamera, is_active: bool) {
+        let set = |(mut cam, _, _): (Mut<Camera>, &GlobalTransform, &Projection)| {
+            cam.is_active = is_active;
+        };
+
+        match camera {
+            ActiveCamera::FlyBy => set(self.q.p0().single_mut()),
+            ActiveCamera::FirstPerson => set(self.q.p1().single_mut()),
+            ActiveCamera::Orbit => set(self.q.p2().single_mut()),
+            ActiveCamera::Rts => set(self.q.p3().single_mut()),
+        }
+    }
+
+    /// **Returns** the current transform and field of view of the given camera.
+    fn camera_view(&mut self, camera: ActiveCamera) -> (Transform, f32) {
+        let view = |(_, transform, projection): (Mut<Camera>, &GlobalTransform, &Projection)| {
+            (transform.compute_transform(), fov(projection))
+        };
+
+        match camera {
+            ActiveCamera::FlyBy => view(self.q.p0().single_mut()),
+            ActiveCamera::FirstPerson => view(self.q.p1().single_mut()),
+            ActiveCamera::Orbit => view(self.q.p2().single_mut()),
+            ActiveCamera::Rts => view(self.q.p3().single_mut()),
+        }
+    }
+
+    /// Activates the given camera controllers, without changing which camera is rendering.
+    fn activate_controllers(&mut self, active_camera: ActiveCamera) {
+        self.first_person.active = false;
+        self.flyby.active = false;
+        self.orbit.active = false;
+        self.rts.active = false;
+        self.character_controller.active = false;
+
+        *self.active_cam = active_camera;
+        self.set_active(true);
+    }
+
+    /// Switches to the given camera right away, canceling any ongoing transition.
+    pub(crate) fn set_cam(&mut self, active_camera: ActiveCamera) {
+        trace!("Toggling cameras");
+
+        self.activate_controllers(active_camera);
+        self.finish(active_camera);
+    }
+
+    /// Smoothly moves from the current view to the given camera.
+    pub(crate) fn transition_to(&mut self, active_camera: ActiveCamera) {
+        let from_camera = *self.active_cam;
+        if from_camera == active_camera && self.transition.0.is_none() {
+            return;
+        }
+
+        // When a transition is ongoing, start from wherever the transition camera is.
+        let (from, from_fov) = if self.transition.0.is_some() {
+            let q_transition = self.q.p4();
+            let (_, transform, projection) = q_transition.single();
+            (*transform, fov(projection))
+        } else {
+            self.camera_view(from_camera)
+        };
+
+        self.activate_controllers(active_camera);
+        for camera in ActiveCamera::ALL {
+            self.set_rendering(camera, false);
+        }
+
+        let mut q_transition = self.q.p4();
+        let (mut camera, mut transform, _) = q_transition.single_mut();
+        camera.is_active = true;
+        *transform = from;
+
+        self.transition.0 = Some(Transition {
+            from,
+            from_fov,
+            to: active_camera,
+            elapsed: 0.0,
+        });
+
+        self.writer.send(CameraTransitionEvent::Started {
+            from: from_camera,
+            to: active_camera,
+        });
+    }
+
+    fn finish(&mut self, active_camera: ActiveCamera) {
+        self.transition.0 = None;
+        self.q.p4().single_mut().0.is_active = false;
+
+        for camera in ActiveCamera::ALL {
+            self.set_rendering(camera, camera == active_camera);
+        }
+
+        self.writer.send(CameraTransitionEvent::Finished {
+            camera: active_camera,
+        });
+    }
+
+    /// **Returns** the camera currently controlled by the player, or being transitioned to.
+    pub(crate) fn active(&self) -> ActiveCamera {
+        *self.active_cam
+    }
+
+    pub(crate) fn set_active(&mut self, active: bool) {
+        match *self.active_cam {
+            ActiveCamera::FlyBy => self.flyby.active = active,
+            ActiveCamera::FirstPerson => {
+                self.character_controller.active = active;
+                self.first_person.active = active;
+            }
+            ActiveCamera::Orbit => {
+                self.character_controller.active = active;
+                self.orbit.active = active;
+            }
+            ActiveCamera::Rts => self.rts.active = active,
+        }
+    }
+}
+
+fn setup_transition_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                is_active: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        RenderLayers::from_layers(&[0, interaction::TARGET_HIGHLIGHT_LAYER]),
+        Name::new("TransitionCamera"),
+        TransitionCamera,
+    ));
+}
+
+fn switch_camera(input: ActionInput, mut director: CameraDirector) {
+    if input.just_pressed(InputAction::FlyByCamera) {
+        director.transition_to(ActiveCamera::FlyBy);
+    } else if input.just_pressed(InputAction::OrbitCamera) {
+        director.transition_to(ActiveCamera::Orbit);
+    } else if input.just_pressed(InputAction::RtsCamera) {
+        director.transition_to(ActiveCamera::Rts);
+    } else if input.just_pressed(InputAction::FirstPersonCamera) {
+        director.transition_to(ActiveCamera::FirstPerson);
+    }
+}
+
+fn update_transition(time: Res<Time>, mut director: CameraDirector) {
+    let Some(mut transition) = director.transition.0 else {
+        return;
+    };
+
+    transition.elapsed += time.delta_seconds();
+    director.transition.0 = Some(transition);
+
+    let t = (transition.elapsed / TRANSITION_SECS).min(1.0);
+    if t >= 1.0 {
+        director.finish(transition.to);
+        return;
+    }
+
+    // Target camera may be moving, so it is followed until the transition ends.
+    let (to, to_fov) = director.camera_view(transition.to);
+    let t = t * t * (3.0 - 2.0 * t);
+
+    let mut q_transition = director.q.p4();
+    let (_, mut transform, mut projection) = q_transition.single_mut();
+    transform.translation = transition.from.translation.lerp(to.translation, t);
+    transform.rotation = transition.from.rotation.slerp(to.rotation, t);
+
+    if let Projection::Perspective(perspective) = projection.as_mut() {
+        perspective.fov = transition.from_fov + (to_fov - transition.from_fov) * t;
+    }
+}
@@ -1,7 +1,12 @@
 use bevy::prelude::*;
-use projekto_core::chunk::Chunk;
+use projekto_core::{chunk::Chunk, collision, math, raycast};
 
-use crate::PlayerLandscape;
+use crate::{ActionInput, ChunkKindClientCache, InputAction, PlayerLandscape};
+
+/// Half size of the box used to collide the character with the voxel world.
+pub(crate) const CHARACTER_HALF_EXTENTS: Vec3 = Vec3::new(0.25, 1.0, 0.25);
+/// Max falling speed, in voxels per second.
+const TERMINAL_VELOCITY: f32 = 50.0;
 
 pub struct CharacterControllerPlugin;
 
@@ -11,6 +16,7 @@ impl Plugin for CharacterControllerPlugin {
             .init_resource::<CharacterPosition>()
             .init_resource::<ChunkMaterialImage>()
             .register_type::<ChunkMaterialImage>()
+            .register_type::<CharacterMotion>()
             .add_systems(
                 Update,
                 ((
@@ -32,10 +38,26 @@ pub struct CharacterPositionUpdate;
 #[derive(Component, Default, Reflect)]
 pub struct CharacterController;
 
+/// Kinematic state of a [`CharacterController`].
+#[derive(Component, Default, Debug, Reflect)]
+pub struct CharacterMotion {
+    pub velocity: Vec3,
+    pub is_grounded: bool,
+    pub is_swimming: bool,
+    pub is_sprinting: bool,
+}
+
 #[derive(Resource)]
 pub struct CharacterControllerConfig {
     pub active: bool,
     pub move_speed: f32,
+    /// Move speed multiplier while sprinting, which is only possible when moving forward.
+    pub sprint_multiplier: f32,
+    pub gravity: f32,
+    pub jump_speed: f32,
+    pub swim_speed: f32,
+    /// Max height the character can climb without jumping.
+    pub step_height: f32,
 }
 
 impl Default for CharacterControllerConfig {
@@ -43,6 +65,11 @@ impl Default for CharacterControllerConfig {
         Self {
             active: false,
             move_speed: 10.0,
+            sprint_multiplier: 1.5,
+            gravity: 30.0,
+            jump_speed: 9.0,
+            swim_speed: 4.0,
+            step_height: 1.0,
         }
     }
 }
@@ -60,55 +87,126 @@ fn is_active(char_config: Res<CharacterControllerConfig>) -> bool {
 fn move_character(
     config: Res<CharacterControllerConfig>,
     time: Res<Time>,
-    input: Res<ButtonInput<KeyCode>>,
-    mut q: Query<&mut Transform, With<CharacterController>>,
+    input: ActionInput,
+    kinds: Res<ChunkKindClientCache>,
+    mut q: Query<(&mut Transform, &mut CharacterMotion), With<CharacterController>>,
 ) {
-    let input_vec = calc_input_vector(&input);
+    let Ok((mut transform, mut motion)) = q.get_single_mut() else {
+        return;
+    };
 
-    if input_vec == Vec3::ZERO {
+    // Wait until the terrain around the character is known, or it would fall through the world.
+    if !kinds.contains_key(&Chunk::from(transform.translation)) {
         return;
     }
 
-    let Ok(mut transform) = q.get_single_mut() else {
-        return;
+    let get_kind = |world: Vec3| {
+        raycast::to_chunk_voxel(math::floor(world))
+            .and_then(|(chunk, voxel)| kinds.get(&chunk).map(|kind| kind.get(voxel)))
+    };
+
+    let input_vec = calc_input_vector(&input);
+    let dt = time.delta_seconds();
+
+    motion.is_swimming = get_kind(transform.translation).is_some_and(|kind| kind.is_liquid());
+    motion.is_sprinting =
+        !motion.is_swimming && input_vec.z > 0.0 && input.pressed(InputAction::Boost);
+
+    let move_speed = if motion.is_sprinting {
+        config.move_speed * config.sprint_multiplier
+    } else {
+        config.move_speed
     };
 
-    let forward_vector = transform.forward() * input_vec.z;
-    let right_vector = transform.right() * input_vec.x;
-    let up_vector = Vec3::Y * input_vec.y;
+    let forward_vector = flatten(*transform.forward()) * input_vec.z;
+    let right_vector = flatten(*transform.right()) * input_vec.x;
+    let horizontal_velocity = (forward_vector + right_vector) * move_speed;
+
+    if motion.is_swimming {
+        motion.velocity.y = input_vec.y * config.swim_speed;
+    } else {
+        motion.velocity.y -= config.gravity * dt;
 
-    let move_vector = forward_vector + right_vector + up_vector;
+        if motion.is_grounded && input_vec.y > 0.0 {
+            motion.velocity.y = config.jump_speed;
+        }
+    }
+    motion.velocity.y = motion.velocity.y.max(-TERMINAL_VELOCITY);
+    motion.velocity.x = horizontal_velocity.x;
+    motion.velocity.z = horizontal_velocity.z;
+
+    let step_height = if motion.is_grounded {
+        config.step_height
+    } else {
+        0.0
+    };
 
-    transform.translation += config.move_speed * time.delta_seconds() * move_vector;
+    let result = collision::move_and_slide(
+        transform.translation,
+        CHARACTER_HALF_EXTENTS,
+        motion.velocity * dt,
+        step_height,
+        |chunk, voxel| {
+            // Unknown chunks are solid, so the character doesn't walk into the void.
+            let Some(kind) = kinds.get(&chunk) else {
+                return 1.0;
+            };
+
+            let kind = kind.get(voxel);
+            if kind.is_liquid() {
+                0.0
+            } else {
+                kind.solid_height()
+            }
+        },
+    );
+
+    motion.is_grounded = result.collided.y && motion.velocity.y <= 0.0;
+    if result.collided.y {
+        motion.velocity.y = 0.0;
+    }
+
+    if transform.translation != result.position {
+        transform.translation = result.position;
+    }
 }
 
-fn calc_input_vector(input: &Res<ButtonInput<KeyCode>>) -> Vec3 {
+fn flatten(dir: Vec3) -> Vec3 {
+    Vec3::new(dir.x, 0.0, dir.z).normalize_or_zero()
+}
+
+fn calc_input_vector(input: &ActionInput) -> Vec3 {
     let mut res = Vec3::ZERO;
 
-    if input.pressed(KeyCode::KeyW) {
+    if input.pressed(InputAction::Forward) {
         res.z += 1.0;
     }
 
-    if input.pressed(KeyCode::KeyS) {
+    if input.pressed(InputAction::Backward) {
         res.z -= 1.0;
     }
 
-    if input.pressed(KeyCode::KeyD) {
+    if input.pressed(InputAction::Right) {
         res.x += 1.0;
     }
 
-    if input.pressed(KeyCode::KeyA) {
+    if input.pressed(InputAction::Left) {
         res.x -= 1.0;
     }
 
-    if input.pressed(KeyCode::Space) {
+    if input.pressed(InputAction::Up) {
         res.y += 1.0;
     }
 
-    if input.pressed(KeyCode::ControlLeft) {
+    // Only used while swimming
+    if input.pressed(InputAction::Down) {
         res.y -= 1.0;
     }
 
+    let stick = input.move_axis();
+    res.x = (res.x + stick.x).clamp(-1.0, 1.0);
+    res.z = (res.z + stick.y).clamp(-1.0, 1.0);
+
     res
 }
 
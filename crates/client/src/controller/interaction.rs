@@ -0,0 +1,374 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::view::{Layer, RenderLayers},
+    utils::HashMap,
+    window::PrimaryWindow,
+};
+use projekto_core::{
+    chunk::Chunk,
+    raycast::{self, RaycastHit},
+    voxel::{self, Voxel},
+};
+use projekto_messages::{
+    EditCommand, EditRedo, EditUndo, VoxelDig, VoxelUpdate, VoxelUpdateRejected,
+};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{
+    action_just_pressed, controller::camera_controller::grab_mouse, net::ServerConnection,
+    ui::Hotbar, ActionInput, AgreedCapabilities, ChunkKindClientCache, ClientSettings,
+    EstimatedServerTime, InputAction,
+};
+
+/// Max distance, in voxels, which the player is able to interact with.
+const INTERACTION_RANGE: f32 = 10.0;
+/// Render layer of target highlight, which must be visible by all world cameras. It has its own
+/// layer, so it can be drawn with a depth bias on top of chunk meshes, without z-fighting.
+pub(crate) const TARGET_HIGHLIGHT_LAYER: Layer = 2;
+/// How far, in voxels, the face highlight is placed in front of the targeted face.
+const FACE_HIGHLIGHT_OFFSET: f32 = 0.002;
+
+pub struct PlayerInteractionPlugin;
+
+impl Plugin for PlayerInteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<VoxelInteracted>()
+            .insert_gizmo_group(
+                TargetGizmos,
+                GizmoConfig {
+                    line_width: 1.5,
+                    depth_bias: -0.0001,
+                    render_layers: RenderLayers::layer(TARGET_HIGHLIGHT_LAYER),
+                    ..Default::default()
+                },
+            )
+            .init_resource::<PlayerTarget>()
+            .init_resource::<PendingVoxelUpdates>()
+            .init_resource::<Digging>()
+            .add_message_handler(rollback_voxel_update)
+            .add_systems(Startup, setup_face_highlight)
+            .add_systems(
+                Update,
+                (
+                    update_target,
+                    (
+                        draw_crosshair,
+                        draw_target_outline,
+                        update_face_highlight.run_if(resource_changed::<PlayerTarget>),
+                        (dig, interact)
+                            .chain()
+                            .run_if(resource_exists::<ServerConnection>)
+                            .run_if(is_player)
+                            .before(grab_mouse),
+                        send_edit_command(EditCommand::Undo(EditUndo { count: 1 }))
+                            .run_if(resource_exists::<ServerConnection>)
+                            .run_if(is_player)
+                            .run_if(action_just_pressed(InputAction::UndoEdit)),
+                        send_edit_command(EditCommand::Redo(EditRedo { count: 1 }))
+                            .run_if(resource_exists::<ServerConnection>)
+                            .run_if(is_player)
+                            .run_if(action_just_pressed(InputAction::RedoEdit)),
+                    ),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Sent when the player breaks or places a voxel, right after it is predicted locally.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct VoxelInteracted {
+    pub chunk: Chunk,
+    pub voxel: Voxel,
+    /// Kind the voxel had before the interaction.
+    pub previous: voxel::Kind,
+    pub kind: voxel::Kind,
+    /// Server tick the update is expected to be applied on, so predicted effects can be scheduled
+    /// consistently with server.
+    pub tick: u64,
+}
+
+/// Gizmos drawn on [`TARGET_HIGHLIGHT_LAYER`].
+#[derive(Default, Reflect, GizmoConfigGroup)]
+struct TargetGizmos;
+
+/// Translucent quad placed over the targeted voxel face, so it is clear where a new voxel is going
+/// to be placed.
+#[derive(Component, Debug)]
+struct FaceHighlight;
+
+/// Voxel currently targeted by the player crosshair, if any.
+#[derive(Resource, Default, Debug, Clone, Copy, Deref)]
+pub struct PlayerTarget(Option<RaycastHit>);
+
+/// Voxel updates which were predicted locally but not confirmed by server yet, with the kind the
+/// voxel had before the update, so it can be rolled back if server rejects it.
+#[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
+pub(crate) struct PendingVoxelUpdates(HashMap<(Chunk, Voxel), voxel::Kind>);
+
+/// Voxel the player is breaking, while break action is held on it.
+#[derive(Resource, Default, Debug, Clone, Copy, Deref, DerefMut)]
+pub(crate) struct Digging(Option<Dig>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Dig {
+    pub chunk: Chunk,
+    pub voxel: Voxel,
+    /// Time, in seconds, break action was held on this voxel.
+    pub elapsed: f32,
+    /// Time, in seconds, it takes to break this voxel. See [`voxel::Kind::break_secs`].
+    pub duration: f32,
+}
+
+impl Dig {
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Spectators only watch the world, so they can't edit it. Server would drop their edits anyway.
+fn is_player(settings: Res<ClientSettings>, agreed: Res<AgreedCapabilities>) -> bool {
+    !settings.spectator && !agreed.spectator
+}
+
+fn update_target(
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    kinds: Res<ChunkKindClientCache>,
+    mut target: ResMut<PlayerTarget>,
+) {
+    let hit = q_camera
+        .iter()
+        .find(|(camera, _)| camera.is_active)
+        .and_then(|(_, transform)| {
+            raycast::raycast(
+                transform.translation(),
+                transform.forward(),
+                INTERACTION_RANGE,
+                |chunk, voxel| {
+                    kinds
+                        .get(&chunk)
+                        .map_or(0.0, |kind| kind.get(voxel).solid_height())
+                },
+            )
+        });
+
+    if target.0 != hit {
+        target.0 = hit;
+    }
+}
+
+fn draw_crosshair(
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut gizmos: Gizmos,
+) {
+    let Some((_, transform)) = q_camera.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    let center = transform.translation() + transform.forward();
+    let right = transform.right() * 0.01;
+    let up = transform.up() * 0.01;
+
+    gizmos.line(center - right, center + right, Color::WHITE);
+    gizmos.line(center - up, center + up, Color::WHITE);
+}
+
+fn setup_face_highlight(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Rectangle::new(1.0, 1.0)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(1.0, 1.0, 1.0, 0.2),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                depth_bias: 1.0,
+                ..Default::default()
+            }),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        },
+        NotShadowCaster,
+        NotShadowReceiver,
+        RenderLayers::layer(TARGET_HIGHLIGHT_LAYER),
+        FaceHighlight,
+        Name::new("FaceHighlight"),
+    ));
+}
+
+fn draw_target_outline(target: Res<PlayerTarget>, mut gizmos: Gizmos<TargetGizmos>) {
+    let Some(hit) = **target else {
+        return;
+    };
+
+    gizmos.cuboid(
+        Transform::from_translation(hit.world().as_vec3() + Vec3::splat(0.5)),
+        Color::rgba(0.0, 0.0, 0.0, 0.6),
+    );
+}
+
+fn update_face_highlight(
+    target: Res<PlayerTarget>,
+    mut q: Query<(&mut Transform, &mut Visibility), With<FaceHighlight>>,
+) {
+    let Ok((mut transform, mut visibility)) = q.get_single_mut() else {
+        return;
+    };
+
+    let Some(hit) = **target else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    // Rectangle mesh faces +Z, so rotate it to face the same direction as the hit side.
+    let normal = hit.side.normal();
+    let center = hit.world().as_vec3() + Vec3::splat(0.5);
+
+    *transform = Transform::from_translation(center + normal * (0.5 + FACE_HIGHLIGHT_OFFSET))
+        .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal));
+    *visibility = Visibility::Inherited;
+}
+
+/// Keeps track of how long break action is held on the targeted voxel, letting server know when
+/// player starts or stops breaking it, so it can broadcast the break progress.
+fn dig(
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    input: ActionInput,
+    target: Res<PlayerTarget>,
+    kinds: Res<ChunkKindClientCache>,
+    time: Res<Time>,
+    server: Res<ServerConnection>,
+    mut digging: ResMut<Digging>,
+) {
+    // Only dig when mouse is grabbed, since the first click is used to grab it.
+    let grabbed = q_window.get_single().is_ok_and(|w| !w.cursor.visible);
+    let target = (**target)
+        .filter(|_| grabbed && input.pressed(InputAction::BreakVoxel))
+        .map(|hit| (hit.chunk, hit.voxel));
+
+    if let Some(dig) = digging.as_mut() {
+        if target == Some((dig.chunk, dig.voxel)) {
+            dig.elapsed += time.delta_seconds();
+            return;
+        }
+
+        if dig.duration > 0.0 {
+            let _ = server.channel().send(VoxelDig {
+                chunk: dig.chunk,
+                voxel: dig.voxel,
+                digging: false,
+            });
+        }
+    }
+
+    **digging = target.and_then(|(chunk, voxel)| {
+        let duration = kinds.get(&chunk)?.get(voxel).break_secs();
+        if duration > 0.0 {
+            let _ = server.channel().send(VoxelDig {
+                chunk,
+                voxel,
+                digging: true,
+            });
+        }
+
+        Some(Dig {
+            chunk,
+            voxel,
+            elapsed: 0.0,
+            duration,
+        })
+    });
+}
+
+fn interact(
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    input: ActionInput,
+    (target, hotbar): (Res<PlayerTarget>, Res<Hotbar>),
+    (time, server_time): (Res<Time>, Res<EstimatedServerTime>),
+    server: Res<ServerConnection>,
+    (mut kinds, mut pending, mut digging): (
+        ResMut<ChunkKindClientCache>,
+        ResMut<PendingVoxelUpdates>,
+        ResMut<Digging>,
+    ),
+    mut writer: EventWriter<VoxelInteracted>,
+) {
+    // Only interact when mouse is grabbed, since the first click is used to grab it.
+    if q_window.get_single().map_or(true, |w| w.cursor.visible) {
+        return;
+    }
+
+    let Some(hit) = **target else {
+        return;
+    };
+
+    // Voxels which break at once are broken by each press, while the others are broken once break
+    // action is held long enough on them, which may happen while the action is still held.
+    let broken = (**digging).filter(|dig| {
+        dig.is_done() && (dig.duration > 0.0 || input.just_pressed(InputAction::BreakVoxel))
+    });
+
+    let (chunk, voxel, kind) = if let Some(dig) = broken {
+        **digging = None;
+        (dig.chunk, dig.voxel, voxel::Kind::NONE)
+    } else if input.just_pressed(InputAction::PlaceVoxel) {
+        let (Some((chunk, voxel)), Some(kind)) = (hit.adjacent(), hotbar.selected_kind()) else {
+            return;
+        };
+        (chunk, voxel, kind)
+    } else {
+        return;
+    };
+
+    let Some(chunk_kind) = kinds.get_mut(&chunk) else {
+        return;
+    };
+
+    let previous = chunk_kind.get(voxel);
+    if kind.is_solid() && previous.is_solid() {
+        return;
+    }
+
+    // Predict the update locally, so it can be rolled back if server rejects it.
+    chunk_kind.set(voxel, kind);
+    pending.entry((chunk, voxel)).or_insert(previous);
+
+    let _ = server.channel().send(VoxelUpdate { chunk, voxel, kind });
+
+    writer.send(VoxelInteracted {
+        chunk,
+        voxel,
+        previous,
+        kind,
+        tick: server_time.arrival_tick(time.elapsed_seconds_f64()),
+    });
+}
+
+fn send_edit_command(command: EditCommand) -> impl Fn(Res<ServerConnection>) {
+    move |server: Res<ServerConnection>| {
+        let _ = match command {
+            EditCommand::Undo(msg) => server.channel().send(msg),
+            EditCommand::Redo(msg) => server.channel().send(msg),
+        };
+    }
+}
+
+fn rollback_voxel_update(
+    In(VoxelUpdateRejected { chunk, voxel }): In<VoxelUpdateRejected>,
+    mut kinds: ResMut<ChunkKindClientCache>,
+    mut pending: ResMut<PendingVoxelUpdates>,
+) {
+    let Some(previous) = pending.remove(&(chunk, voxel)) else {
+        return;
+    };
+
+    debug!("Voxel update {voxel} on {chunk} rejected. Rolling back to {previous:?}");
+
+    if let Some(chunk_kind) = kinds.get_mut(&chunk) {
+        chunk_kind.set(voxel, previous);
+    }
+}
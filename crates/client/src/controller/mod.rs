@@ -1,2 +1,4 @@
 pub mod camera_controller;
+pub mod camera_director;
 pub mod character_controller;
+pub mod interaction;
@@ -0,0 +1,334 @@
+//! Cracks drawn on voxels being broken, driven by server `VoxelBreakProgress`. Each crack is a
+//! decal quad over each voxel face, so chunks don't need to be remeshed for a temporary visual.
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        view::RenderLayers,
+    },
+    utils::{HashMap, HashSet},
+};
+use projekto_core::{
+    chunk::Chunk,
+    voxel::{self, Voxel},
+};
+use projekto_messages::{Teleport, VoxelBreakProgress};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{controller::interaction::TARGET_HIGHLIGHT_LAYER, net::ServerDisconnected};
+
+/// Crack textures, from barely broken to almost broken.
+const CRACK_STAGES: usize = 8;
+const CRACK_TEXTURE_SIZE: u32 = 16;
+/// Cracks grow from texture center on this many branches.
+const CRACK_BRANCHES: usize = 5;
+/// Cracks not updated for this long, in seconds, are removed, in case the message clearing them
+/// got lost, like when the player breaking it disconnected.
+const CRACK_STALE_SECS: f32 = 0.5;
+/// How far, in voxels, decals are placed in front of voxel faces.
+const CRACK_OFFSET: f32 = 0.003;
+
+pub(crate) struct CrackPlugin;
+
+impl Plugin for CrackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelCracks>()
+            .add_message_handler(update_crack)
+            .add_message_handler(clear_cracks_on_teleport)
+            .add_systems(Startup, setup_crack_assets)
+            .add_systems(
+                Update,
+                (
+                    clear_cracks_on_server_disconnect.run_if(on_event::<ServerDisconnected>()),
+                    expire_cracks,
+                    sync_crack_decals.run_if(resource_changed::<VoxelCracks>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// A voxel being broken by some player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Crack {
+    chunk: Chunk,
+    voxel: Voxel,
+    progress: f32,
+    /// Elapsed time, in seconds, when it was last updated by server.
+    updated: f32,
+}
+
+/// Crack of each player breaking a voxel.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+struct VoxelCracks(HashMap<u32, Crack>);
+
+/// Decal mesh and a material for each crack stage.
+#[derive(Resource, Debug)]
+struct CrackAssets {
+    quad: Handle<Mesh>,
+    stages: Vec<Handle<StandardMaterial>>,
+}
+
+impl CrackAssets {
+    fn material(&self, progress: f32) -> Handle<StandardMaterial> {
+        self.stages[crack_stage(progress)].clone()
+    }
+}
+
+/// Root of the decals drawn on the voxel a player is breaking.
+#[derive(Component, Debug)]
+struct CrackDecal(u32);
+
+/// Decal quad over a single voxel face.
+#[derive(Component, Debug)]
+struct CrackFace;
+
+/// **Returns** the crack stage of the given break progress.
+fn crack_stage(progress: f32) -> usize {
+    ((progress.clamp(0.0, 1.0) * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1)
+}
+
+/// **Returns** crack pixels in the order they appear as the voxel breaks, so each stage draws
+/// the first pixels of the list and keeps the cracks of the previous stages.
+fn crack_pixels() -> Vec<UVec2> {
+    const DIRECTIONS: [IVec2; 8] = [
+        IVec2::new(1, 0),
+        IVec2::new(1, 1),
+        IVec2::new(0, 1),
+        IVec2::new(-1, 1),
+        IVec2::new(-1, 0),
+        IVec2::new(-1, -1),
+        IVec2::new(0, -1),
+        IVec2::new(1, -1),
+    ];
+
+    // Fixed seed (Xorshift64), so cracks look the same on every run.
+    let mut seed = 0x2545_F491_4F6C_DD1D_u64;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+
+    let max = IVec2::splat(CRACK_TEXTURE_SIZE as i32 - 1);
+    let mut branches = [max / 2; CRACK_BRANCHES];
+    let mut pixels = vec![];
+
+    // Branches grow a step at a time, so all of them are visible since the first stage.
+    for _ in 0..CRACK_TEXTURE_SIZE / 2 {
+        for (i, pixel) in branches.iter_mut().enumerate() {
+            // Each branch heads away from center, wobbling a bit on each step.
+            let heading = i * DIRECTIONS.len() / CRACK_BRANCHES;
+            let wobble = (random() % 3) as usize;
+            let direction =
+                DIRECTIONS[(heading + wobble + DIRECTIONS.len() - 1) % DIRECTIONS.len()];
+
+            *pixel = (*pixel + direction).clamp(IVec2::ZERO, max);
+            pixels.push(pixel.as_uvec2());
+        }
+    }
+
+    pixels
+}
+
+/// **Returns** the texture of the given crack stage.
+fn crack_image(pixels: &[UVec2], stage: usize) -> Image {
+    let size = CRACK_TEXTURE_SIZE;
+    let mut data = vec![0; (size * size * 4) as usize];
+
+    let count = pixels.len() * (stage + 1) / CRACK_STAGES;
+    for pixel in &pixels[..count] {
+        let i = ((pixel.y * size + pixel.x) * 4) as usize;
+        data[i..i + 4].copy_from_slice(&[20, 20, 20, 220]);
+    }
+
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+fn setup_crack_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let pixels = crack_pixels();
+    let stages = (0..CRACK_STAGES)
+        .map(|stage| {
+            materials.add(StandardMaterial {
+                base_color_texture: Some(images.add(crack_image(&pixels, stage))),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                depth_bias: 1.0,
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    commands.insert_resource(CrackAssets {
+        quad: meshes.add(Rectangle::new(1.0, 1.0)),
+        stages,
+    });
+}
+
+fn update_crack(
+    In(VoxelBreakProgress {
+        chunk,
+        voxel,
+        player,
+        progress,
+    }): In<VoxelBreakProgress>,
+    time: Res<Time>,
+    mut cracks: ResMut<VoxelCracks>,
+) {
+    if progress <= 0.0 {
+        cracks.remove(&player);
+        return;
+    }
+
+    cracks.insert(
+        player,
+        Crack {
+            chunk,
+            voxel,
+            progress,
+            updated: time.elapsed_seconds(),
+        },
+    );
+}
+
+fn clear_cracks_on_teleport(In(_): In<Teleport>, mut cracks: ResMut<VoxelCracks>) {
+    cracks.clear();
+}
+
+fn clear_cracks_on_server_disconnect(
+    mut cracks: ResMut<VoxelCracks>,
+    mut reader: EventReader<ServerDisconnected>,
+) {
+    reader.clear();
+    cracks.clear();
+}
+
+fn expire_cracks(time: Res<Time>, mut cracks: ResMut<VoxelCracks>) {
+    let now = time.elapsed_seconds();
+    let is_stale = |crack: &Crack| now - crack.updated > CRACK_STALE_SECS;
+
+    // Only touch cracks when needed, since decals are synced when they change.
+    if cracks.values().any(is_stale) {
+        cracks.retain(|_, crack| !is_stale(crack));
+    }
+}
+
+fn sync_crack_decals(
+    mut commands: Commands,
+    cracks: Res<VoxelCracks>,
+    assets: Option<Res<CrackAssets>>,
+    mut q_decals: Query<(Entity, &CrackDecal, &mut Transform, &Children)>,
+    mut q_faces: Query<&mut Handle<StandardMaterial>, With<CrackFace>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+
+    let mut drawn = HashSet::new();
+    for (entity, &CrackDecal(player), mut transform, children) in &mut q_decals {
+        let Some(crack) = cracks.get(&player) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        transform.translation = crack_center(crack);
+
+        let material = assets.material(crack.progress);
+        for &child in children {
+            if let Ok(mut handle) = q_faces.get_mut(child) {
+                if *handle != material {
+                    *handle = material.clone();
+                }
+            }
+        }
+
+        drawn.insert(player);
+    }
+
+    for (&player, crack) in cracks.iter().filter(|(player, _)| !drawn.contains(*player)) {
+        let material = assets.material(crack.progress);
+
+        commands
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_translation(crack_center(crack))),
+                CrackDecal(player),
+                Name::new(format!("Crack {player}")),
+            ))
+            .with_children(|parent| {
+                for side in voxel::SIDES {
+                    // Rectangle mesh faces +Z, so rotate it to face the same direction as the side.
+                    let normal = side.normal();
+                    parent.spawn((
+                        PbrBundle {
+                            mesh: assets.quad.clone(),
+                            material: material.clone(),
+                            transform: Transform::from_translation(normal * (0.5 + CRACK_OFFSET))
+                                .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal)),
+                            ..Default::default()
+                        },
+                        NotShadowCaster,
+                        NotShadowReceiver,
+                        RenderLayers::layer(TARGET_HIGHLIGHT_LAYER),
+                        CrackFace,
+                    ));
+                }
+            });
+    }
+}
+
+/// **Returns** the center of the cracked voxel, in world coordinates.
+fn crack_center(crack: &Crack) -> Vec3 {
+    voxel::to_world(crack.voxel, crack.chunk) + Vec3::splat(0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_of_progress() {
+        assert_eq!(crack_stage(0.0), 0);
+        assert_eq!(crack_stage(0.5), CRACK_STAGES / 2);
+        assert_eq!(crack_stage(1.0), CRACK_STAGES - 1);
+        assert_eq!(crack_stage(2.0), CRACK_STAGES - 1);
+    }
+
+    #[test]
+    fn cracks_grow_with_stage() {
+        let pixels = crack_pixels();
+        let opaque = |stage| {
+            crack_image(&pixels, stage)
+                .data
+                .chunks_exact(4)
+                .filter(|rgba| rgba[3] > 0)
+                .count()
+        };
+
+        let counts = (0..CRACK_STAGES).map(opaque).collect::<Vec<_>>();
+        assert!(counts[0] > 0, "First stage should already show some cracks");
+        assert!(
+            counts.windows(2).all(|w| w[0] <= w[1]),
+            "Cracks should only grow: {counts:?}"
+        );
+        assert!(counts[CRACK_STAGES - 1] > counts[0]);
+    }
+}
@@ -0,0 +1,386 @@
+//! Chunk inspector shows internals of a chunk, received from server, as heatmaps of a single
+//! horizontal slice, along with occlusion stats, vertex counts and last update times. It is meant
+//! to diagnose meshing and lighting issues.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use projekto_core::{
+    chunk::{self, Chunk},
+    voxel::{self, Voxel},
+};
+use projekto_messages::{ChunkInspect, ChunkInspection};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{
+    action_just_pressed, controller::interaction::PlayerTarget, net::ServerConnection, ActionInput,
+    ChunkMap, ClientSettings, InputAction,
+};
+
+const FONT_SIZE: f32 = 16.0;
+/// Each voxel of the slice is drawn as a square of this size, in pixels.
+const HEATMAP_SCALE: f32 = 12.0;
+const EMPTY_COLOR: [u8; 4] = [0, 0, 0, 160];
+
+pub(super) struct ChunkInspectorPlugin;
+
+impl Plugin for ChunkInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkInspector>()
+            .add_event::<ChunkInspected>()
+            .set_message_handler(receive_chunk_inspection)
+            .add_systems(Startup, setup_chunk_inspector)
+            .add_systems(
+                Update,
+                (
+                    inspect_chunk
+                        .run_if(action_just_pressed(InputAction::InspectChunk))
+                        .run_if(resource_exists::<ServerConnection>),
+                    change_inspector_view,
+                    update_inspection.run_if(on_event::<ChunkInspected>()),
+                    refresh_on_mesh_update.run_if(resource_exists::<ServerConnection>),
+                    update_heatmap.run_if(resource_changed::<ChunkInspector>),
+                    update_inspector_text,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Sent when a chunk inspection is received from server, so other debug tools can use it.
+#[derive(Event, Debug)]
+pub(super) struct ChunkInspected(pub ChunkInspection);
+
+/// Layer of chunk shown on heatmap.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+enum InspectorLayer {
+    #[default]
+    Kind,
+    Light,
+}
+
+#[derive(Resource, Default)]
+struct ChunkInspector {
+    chunk: Option<Chunk>,
+    inspection: Option<ChunkInspection>,
+    layer: InspectorLayer,
+    /// Height of the slice shown on heatmap.
+    slice: i32,
+    /// Elapsed time, in seconds, when the inspection was received.
+    received_at: f32,
+    /// Elapsed time, in seconds, when the chunk mesh was last updated on client.
+    mesh_updated_at: Option<f32>,
+}
+
+#[derive(Component)]
+struct InspectorPanel;
+
+#[derive(Component)]
+struct InspectorHeatmap;
+
+#[derive(Component)]
+struct InspectorText;
+
+fn setup_chunk_inspector(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: chunk::X_AXIS_SIZE as u32,
+            height: chunk::Z_AXIS_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &EMPTY_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::all(),
+    );
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            InspectorPanel,
+            Name::new("ChunkInspector"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ),
+                InspectorText,
+            ));
+            parent.spawn((
+                ImageBundle {
+                    style: Style {
+                        width: Val::Px(chunk::X_AXIS_SIZE as f32 * HEATMAP_SCALE),
+                        height: Val::Px(chunk::Z_AXIS_SIZE as f32 * HEATMAP_SCALE),
+                        ..Default::default()
+                    },
+                    image: UiImage::new(images.add(image)),
+                    ..Default::default()
+                },
+                InspectorHeatmap,
+            ));
+        });
+}
+
+/// Inspects the chunk targeted by the player, or the one the player is in, starting on the
+/// targeted voxel slice.
+fn inspect_chunk(
+    target: Res<PlayerTarget>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    server: Res<ServerConnection>,
+    mut inspector: ResMut<ChunkInspector>,
+    mut q_panel: Query<&mut Visibility, With<InspectorPanel>>,
+) {
+    let position = target
+        .map(|hit| chunk::to_world(hit.chunk) + hit.voxel.as_vec3())
+        .or_else(|| {
+            q_camera
+                .iter()
+                .find(|(camera, _)| camera.is_active)
+                .map(|(_, transform)| transform.translation())
+        });
+
+    let Some(position) = position else {
+        return;
+    };
+
+    let chunk = Chunk::from(position);
+
+    // Inspecting the same chunk again closes the inspector.
+    let is_closing = inspector.chunk == Some(chunk);
+    for mut visibility in &mut q_panel {
+        *visibility = if is_closing {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+
+    if is_closing {
+        inspector.chunk = None;
+        return;
+    }
+
+    let _ = server.channel().send(ChunkInspect { chunk });
+
+    info!("Inspecting chunk {chunk}");
+    *inspector = ChunkInspector {
+        chunk: Some(chunk),
+        layer: inspector.layer,
+        slice: (position.y.floor() as i32).clamp(0, chunk::Y_END),
+        ..Default::default()
+    };
+}
+
+fn change_inspector_view(input: ActionInput, mut inspector: ResMut<ChunkInspector>) {
+    if inspector.chunk.is_none() {
+        return;
+    }
+
+    if input.just_pressed(InputAction::InspectorSliceUp) {
+        inspector.slice = (inspector.slice + 1).min(chunk::Y_END);
+    }
+
+    if input.just_pressed(InputAction::InspectorSliceDown) {
+        inspector.slice = (inspector.slice - 1).max(0);
+    }
+
+    if input.just_pressed(InputAction::InspectorLayer) {
+        inspector.layer = match inspector.layer {
+            InspectorLayer::Kind => InspectorLayer::Light,
+            InspectorLayer::Light => InspectorLayer::Kind,
+        };
+    }
+}
+
+/// Inspects the chunk again whenever its mesh is updated, so stats are always up to date.
+fn refresh_on_mesh_update(
+    mut reader: EventReader<AssetEvent<Mesh>>,
+    time: Res<Time>,
+    server: Res<ServerConnection>,
+    map: Res<ChunkMap>,
+    q_mesh: Query<&Handle<Mesh>>,
+    mut inspector: ResMut<ChunkInspector>,
+) {
+    let Some(mesh_id) = inspector
+        .chunk
+        .and_then(|chunk| map.get(&chunk))
+        .and_then(|&entity| q_mesh.get(entity).ok())
+        .map(Handle::id)
+    else {
+        reader.clear();
+        return;
+    };
+
+    let is_updated = reader.read().any(|event| match event {
+        AssetEvent::Added { id } | AssetEvent::Modified { id } => *id == mesh_id,
+        _ => false,
+    });
+
+    if let (true, Some(chunk)) = (is_updated, inspector.chunk) {
+        inspector.mesh_updated_at = Some(time.elapsed_seconds());
+        let _ = server.channel().send(ChunkInspect { chunk });
+    }
+}
+
+fn receive_chunk_inspection(
+    In(inspection): In<ChunkInspection>,
+    mut writer: EventWriter<ChunkInspected>,
+) {
+    writer.send(ChunkInspected(inspection));
+}
+
+fn update_inspection(
+    mut reader: EventReader<ChunkInspected>,
+    time: Res<Time>,
+    mut inspector: ResMut<ChunkInspector>,
+) {
+    let inspection = reader
+        .read()
+        .filter(|ChunkInspected(inspection)| inspector.chunk == Some(inspection.chunk))
+        .last();
+
+    if let Some(ChunkInspected(inspection)) = inspection {
+        inspector.inspection = Some(inspection.clone());
+        inspector.received_at = time.elapsed_seconds();
+    }
+}
+
+/// **Returns** a color which is distinct for each kind, or [`EMPTY_COLOR`] for empty voxels.
+fn kind_color(kind: voxel::Kind) -> [u8; 4] {
+    if kind.is_none() {
+        return EMPTY_COLOR;
+    }
+
+    // Golden angle spreads hues of sequential ids around the color wheel.
+    let hue = (u16::from(kind) as f32 * 137.5) % 360.0;
+    let [r, g, b, _] = Color::hsl(hue, 0.6, 0.5).as_rgba_u8();
+    [r, g, b, 255]
+}
+
+/// **Returns** a color from dark blue, when there is no light, to yellow, on max intensity.
+fn light_color(light: voxel::Light) -> [u8; 4] {
+    let t = light.get_greater_intensity() as f32 / voxel::Light::MAX_NATURAL_INTENSITY as f32;
+    let color = Vec3::new(0.1, 0.1, 0.4).lerp(Vec3::new(1.0, 0.9, 0.2), t.clamp(0.0, 1.0));
+    let [r, g, b, _] = Color::rgb(color.x, color.y, color.z).as_rgba_u8();
+    [r, g, b, 255]
+}
+
+fn update_heatmap(
+    inspector: Res<ChunkInspector>,
+    q_heatmap: Query<&UiImage, With<InspectorHeatmap>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(image) = q_heatmap
+        .get_single()
+        .ok()
+        .and_then(|ui_image| images.get_mut(&ui_image.texture))
+    else {
+        return;
+    };
+
+    for z in 0..chunk::Z_AXIS_SIZE {
+        for x in 0..chunk::X_AXIS_SIZE {
+            let voxel = Voxel::new(x as i32, inspector.slice, z as i32);
+            let color = match (&inspector.inspection, inspector.layer) {
+                (None, _) => EMPTY_COLOR,
+                (Some(inspection), InspectorLayer::Kind) => kind_color(inspection.kind.get(voxel)),
+                (Some(inspection), InspectorLayer::Light) => {
+                    light_color(inspection.light.get(voxel))
+                }
+            };
+
+            let index = (z * chunk::X_AXIS_SIZE + x) * 4;
+            image.data[index..index + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+fn update_inspector_text(
+    inspector: Res<ChunkInspector>,
+    time: Res<Time>,
+    settings: Res<ClientSettings>,
+    map: Res<ChunkMap>,
+    q_mesh: Query<&Handle<Mesh>>,
+    meshes: Res<Assets<Mesh>>,
+    mut q_text: Query<&mut Text, With<InspectorText>>,
+) {
+    let Ok(mut text) = q_text.get_single_mut() else {
+        return;
+    };
+
+    let Some(chunk) = inspector.chunk else {
+        return;
+    };
+
+    let now = time.elapsed_seconds();
+    let describe = |action| settings.input.describe(action);
+    let mut lines = vec![
+        format!(
+            "Chunk {chunk} - {:?} at Y {}",
+            inspector.layer, inspector.slice
+        ),
+        format!(
+            "Slice: {} / {}. Layer: {}",
+            describe(InputAction::InspectorSliceDown),
+            describe(InputAction::InspectorSliceUp),
+            describe(InputAction::InspectorLayer),
+        ),
+    ];
+
+    let Some(inspection) = &inspector.inspection else {
+        lines.push("Waiting for server...".to_string());
+        text.sections[0].value = lines.join("\n");
+        return;
+    };
+
+    let solid = chunk::voxels()
+        .filter(|&voxel| !inspection.kind.get(voxel).is_none())
+        .count();
+    let client_vertex = map
+        .get(&chunk)
+        .and_then(|&entity| q_mesh.get(entity).ok())
+        .and_then(|handle| meshes.get(handle))
+        .map_or(0, Mesh::count_vertices);
+
+    lines.extend([
+        format!("Non-empty voxels: {solid}"),
+        format!(
+            "Occluded faces: {}, fully occluded voxels: {}",
+            inspection.occluded_faces, inspection.fully_occluded_voxels
+        ),
+        format!(
+            "Vertices: {} on server, {client_vertex} on client",
+            inspection.vertex_count
+        ),
+        format!("Inspected {:.1}s ago", now - inspector.received_at),
+        match inspector.mesh_updated_at {
+            Some(updated_at) => format!("Mesh updated {:.1}s ago", now - updated_at),
+            None => "Mesh not updated since inspected".to_string(),
+        },
+    ]);
+
+    text.sections[0].value = lines.join("\n");
+}
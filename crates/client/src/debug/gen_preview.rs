@@ -0,0 +1,213 @@
+//! World gen preview shows a top-down map around the camera, sampled by server straight from world
+//! generator, so generation settings can be tuned without walking around the world.
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use projekto_messages::{PreviewLayer, WorldGenPreview, WorldGenPreviewRequest};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{action_just_pressed, net::ServerConnection, InputAction};
+
+/// Width and height, in pixels, of the requested preview.
+const PREVIEW_SIZE: u16 = 128;
+/// How many voxels each pixel of the preview covers.
+const PREVIEW_SCALE: u16 = 8;
+/// Preview is scaled up on screen, so it is easier to see.
+const PREVIEW_SCREEN_SCALE: f32 = 3.0;
+const FONT_SIZE: f32 = 16.0;
+const EMPTY_COLOR: [u8; 4] = [0, 0, 0, 160];
+
+pub(super) struct GenPreviewPlugin;
+
+impl Plugin for GenPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GenPreviewState>()
+            .set_message_handler(receive_world_gen_preview)
+            .add_systems(Startup, setup_gen_preview)
+            .add_systems(
+                Update,
+                (
+                    toggle_gen_preview.run_if(action_just_pressed(InputAction::ToggleGenPreview)),
+                    change_gen_preview_layer
+                        .run_if(action_just_pressed(InputAction::GenPreviewLayer)),
+                )
+                    .chain()
+                    .run_if(resource_exists::<ServerConnection>),
+            );
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+struct GenPreviewState {
+    visible: bool,
+    layer: PreviewLayer,
+}
+
+#[derive(Component)]
+struct GenPreviewPanel;
+
+#[derive(Component)]
+struct GenPreviewImage;
+
+#[derive(Component)]
+struct GenPreviewText;
+
+fn setup_gen_preview(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: PREVIEW_SIZE as u32,
+            height: PREVIEW_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &EMPTY_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::all(),
+    );
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            GenPreviewPanel,
+            Name::new("GenPreview"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ),
+                GenPreviewText,
+            ));
+            parent.spawn((
+                ImageBundle {
+                    style: Style {
+                        width: Val::Px(PREVIEW_SIZE as f32 * PREVIEW_SCREEN_SCALE),
+                        height: Val::Px(PREVIEW_SIZE as f32 * PREVIEW_SCREEN_SCALE),
+                        ..Default::default()
+                    },
+                    image: UiImage::new(images.add(image)),
+                    ..Default::default()
+                },
+                GenPreviewImage,
+            ));
+        });
+}
+
+/// Asks server for a preview of the given layer, centered on active camera.
+fn request_preview(
+    server: &ServerConnection,
+    q_camera: &Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    layer: PreviewLayer,
+) {
+    let Some((_, transform)) = q_camera.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    let center = transform.translation().xz().floor().as_ivec2();
+    let _ = server.channel().send(WorldGenPreviewRequest {
+        center,
+        size: PREVIEW_SIZE,
+        scale: PREVIEW_SCALE,
+        layer,
+    });
+}
+
+fn toggle_gen_preview(
+    server: Res<ServerConnection>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut q_panel: Query<&mut Visibility, With<GenPreviewPanel>>,
+    mut state: ResMut<GenPreviewState>,
+) {
+    state.visible = !state.visible;
+
+    for mut visibility in &mut q_panel {
+        *visibility = if state.visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    if state.visible {
+        request_preview(&server, &q_camera, state.layer);
+    }
+}
+
+fn change_gen_preview_layer(
+    server: Res<ServerConnection>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut state: ResMut<GenPreviewState>,
+) {
+    if !state.visible {
+        return;
+    }
+
+    state.layer = match state.layer {
+        PreviewLayer::Height => PreviewLayer::Biome,
+        PreviewLayer::Biome => PreviewLayer::Temperature,
+        PreviewLayer::Temperature => PreviewLayer::Humidity,
+        PreviewLayer::Humidity => PreviewLayer::Height,
+    };
+
+    request_preview(&server, &q_camera, state.layer);
+}
+
+fn receive_world_gen_preview(
+    In(preview): In<WorldGenPreview>,
+    q_image: Query<&UiImage, With<GenPreviewImage>>,
+    mut q_text: Query<&mut Text, With<GenPreviewText>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let WorldGenPreview {
+        center,
+        size,
+        scale,
+        layer,
+        pixels,
+    } = preview;
+
+    if size != PREVIEW_SIZE || pixels.len() != (size as usize).pow(2) {
+        warn!("Ignoring world gen preview with unexpected size {size}.");
+        return;
+    }
+
+    let Some(image) = q_image
+        .get_single()
+        .ok()
+        .and_then(|ui_image| images.get_mut(&ui_image.texture))
+    else {
+        return;
+    };
+
+    for (index, [r, g, b]) in pixels.into_iter().enumerate() {
+        image.data[index * 4..index * 4 + 4].copy_from_slice(&[r, g, b, 255]);
+    }
+
+    if let Ok(mut text) = q_text.get_single_mut() {
+        text.sections[0].value =
+            format!("World gen preview ({layer:?})\nCenter: {center}, {scale} voxels per pixel");
+    }
+}
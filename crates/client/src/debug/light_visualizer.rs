@@ -0,0 +1,163 @@
+//! Light visualizer draws light intensities of a chunk and its neighbors as translucent voxels, so
+//! propagation across chunk borders can be checked. Natural and artificial light have different
+//! hues, and voxels which are fully lit by natural light, like open sky, are skipped.
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::{
+    chunk::{self, Chunk, ChunkStorage},
+    voxel::{self, LightTy},
+};
+use projekto_messages::ChunkInspect;
+
+use crate::{
+    action_just_pressed, controller::interaction::PlayerTarget, net::ServerConnection, InputAction,
+};
+
+use super::{
+    chunk_inspector::ChunkInspected,
+    voxels::{DebugVoxel, DebugVoxelMesh, DebugVoxels, DebugVoxelsBundle},
+};
+
+/// Size of drawn voxels, so the ones behind are still visible.
+const LIGHT_VOXEL_SIZE: f32 = 0.4;
+const NATURAL_COLOR: Vec3 = Vec3::new(0.2, 0.5, 1.0);
+const ARTIFICIAL_COLOR: Vec3 = Vec3::new(1.0, 0.5, 0.1);
+const MIN_ALPHA: f32 = 0.1;
+const MAX_ALPHA: f32 = 0.6;
+
+pub(super) struct LightVisualizerPlugin;
+
+impl Plugin for LightVisualizerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightVisualizer>().add_systems(
+            Update,
+            (
+                toggle_light_visualizer
+                    .run_if(action_just_pressed(InputAction::ToggleLightVisualizer))
+                    .run_if(resource_exists::<ServerConnection>),
+                update_light_voxels.run_if(on_event::<ChunkInspected>()),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Chunk whose light, and its neighbors light, is being drawn.
+#[derive(Resource, Default, Debug)]
+struct LightVisualizer {
+    center: Option<Chunk>,
+    /// Light voxels entities of each drawn chunk.
+    entities: HashMap<Chunk, Entity>,
+}
+
+/// **Returns** the given chunk and its horizontal neighbors.
+fn chunk_and_neighbors(center: Chunk) -> impl Iterator<Item = Chunk> {
+    (-1..=1).flat_map(move |x| (-1..=1).map(move |z| Chunk::new(center.x() + x, center.z() + z)))
+}
+
+fn toggle_light_visualizer(
+    mut commands: Commands,
+    target: Res<PlayerTarget>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    server: Res<ServerConnection>,
+    mut visualizer: ResMut<LightVisualizer>,
+) {
+    if visualizer.center.take().is_some() {
+        for (_, entity) in visualizer.entities.drain() {
+            commands.entity(entity).despawn();
+        }
+        info!("Light visualizer disabled");
+        return;
+    }
+
+    let center = target.map(|hit| hit.chunk).or_else(|| {
+        q_camera
+            .iter()
+            .find(|(camera, _)| camera.is_active)
+            .map(|(_, transform)| Chunk::from(transform.translation()))
+    });
+
+    let Some(center) = center else {
+        return;
+    };
+
+    info!("Visualizing light around chunk {center}");
+    visualizer.center = Some(center);
+
+    for chunk in chunk_and_neighbors(center) {
+        let _ = server.channel().send(ChunkInspect { chunk });
+    }
+}
+
+/// **Returns** the color of the given light, or `None` if there is nothing worth drawing, like
+/// unlit voxels or voxels fully lit by natural light.
+fn light_color(light: voxel::Light) -> Option<[f32; 4]> {
+    let natural = light.get(LightTy::Natural);
+    let artificial = light.get(LightTy::Artificial);
+
+    let natural = if natural >= voxel::Light::MAX_NATURAL_INTENSITY {
+        0
+    } else {
+        natural
+    };
+
+    if natural == 0 && artificial == 0 {
+        return None;
+    }
+
+    let max = voxel::Light::MAX_NATURAL_INTENSITY as f32;
+    let (natural, artificial) = (natural as f32 / max, artificial as f32 / max);
+    let color = (NATURAL_COLOR * natural + ARTIFICIAL_COLOR * artificial) / (natural + artificial);
+    let alpha = MIN_ALPHA + (MAX_ALPHA - MIN_ALPHA) * natural.max(artificial).min(1.0);
+
+    Some([color.x, color.y, color.z, alpha])
+}
+
+/// **Returns** a small voxel on each lit voxel of the given chunk.
+fn light_voxels(chunk: Chunk, light: &ChunkStorage<voxel::Light>) -> DebugVoxels {
+    let origin = chunk::to_world(chunk);
+
+    let voxels = chunk::voxels()
+        .filter_map(|voxel| {
+            light_color(light.get(voxel)).map(|color| DebugVoxel {
+                position: origin + voxel.as_vec3() + Vec3::splat(0.5),
+                size: LIGHT_VOXEL_SIZE,
+                color,
+            })
+        })
+        .collect();
+
+    DebugVoxels::new(voxels)
+}
+
+fn update_light_voxels(
+    mut commands: Commands,
+    mut reader: EventReader<ChunkInspected>,
+    mut visualizer: ResMut<LightVisualizer>,
+    mesh: Res<DebugVoxelMesh>,
+) {
+    let Some(center) = visualizer.center else {
+        reader.clear();
+        return;
+    };
+
+    for ChunkInspected(inspection) in reader.read() {
+        let chunk = inspection.chunk;
+        if !chunk_and_neighbors(center).any(|other| other == chunk) {
+            continue;
+        }
+
+        if let Some(entity) = visualizer.entities.remove(&chunk) {
+            commands.entity(entity).despawn();
+        }
+
+        let entity = commands
+            .spawn((
+                DebugVoxelsBundle::new(&mesh, chunk, light_voxels(chunk, &inspection.light)),
+                Name::new(format!("Light Visualizer {chunk}")),
+            ))
+            .id();
+
+        visualizer.entities.insert(chunk, entity);
+    }
+}
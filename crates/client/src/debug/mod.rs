@@ -1,4 +1,29 @@
 use bevy::{app::AppExit, prelude::*};
+use projekto_camera::{
+    fly_by::FlyByCamera,
+    path::{CameraPath, Easing},
+};
+use projekto_messages::{WorldExport, WorldExported};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{
+    action_just_pressed,
+    capture::is_demo_playing,
+    controller::camera_director::{ActiveCamera, CameraDirector},
+    net::ServerConnection,
+    ActionInput, ClientSettings, InputAction, PlayerLandscape,
+};
+
+#[cfg(feature = "inspector")]
+mod chunk_inspector;
+#[cfg(feature = "inspector")]
+mod gen_preview;
+#[cfg(feature = "inspector")]
+mod light_visualizer;
+#[cfg(feature = "inspector")]
+mod seam_visualizer;
+#[cfg(feature = "inspector")]
+mod voxels;
 
 pub struct DebugPlugin;
 
@@ -6,10 +31,38 @@ impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup_hold_est_to_exit)
             // .add_system(slow_down_fps)
-            .add_systems(Update, hold_esc_to_exit);
+            .add_systems(Update, hold_esc_to_exit)
+            .set_message_handler(log_world_exported)
+            .add_systems(
+                Update,
+                export_world
+                    .run_if(action_just_pressed(InputAction::ExportWorld))
+                    .run_if(resource_exists::<ServerConnection>),
+            )
+            .add_systems(
+                Update,
+                (
+                    add_camera_path_keyframe
+                        .run_if(action_just_pressed(InputAction::AddCameraPathKeyframe)),
+                    clear_camera_path.run_if(action_just_pressed(InputAction::ClearCameraPath)),
+                    toggle_camera_path.run_if(action_just_pressed(InputAction::PlayCameraPath)),
+                    release_camera_path,
+                )
+                    .chain()
+                    .run_if(not(is_demo_playing)),
+            );
 
         #[cfg(feature = "perf_counter")]
         app.add_plugins(perf::PerfCounterPlugin);
+
+        #[cfg(feature = "inspector")]
+        app.add_plugins((
+            chunk_inspector::ChunkInspectorPlugin,
+            gen_preview::GenPreviewPlugin,
+            light_visualizer::LightVisualizerPlugin,
+            seam_visualizer::SeamVisualizerPlugin,
+            voxels::DebugVoxelsPlugin,
+        ));
     }
 }
 
@@ -25,10 +78,10 @@ fn setup_hold_est_to_exit(mut commands: Commands) {
 fn hold_esc_to_exit(
     mut esc_holding: ResMut<EscHolding>,
     time: Res<Time>,
-    input_keys: Res<ButtonInput<KeyCode>>,
+    input: ActionInput,
     mut exit_writer: EventWriter<AppExit>,
 ) {
-    if input_keys.pressed(KeyCode::Escape) {
+    if input.pressed(InputAction::ReleaseMouse) {
         esc_holding.0 += time.delta_seconds();
 
         if esc_holding.0 >= ESC_HOLD_TIMEOUT {
@@ -40,6 +93,101 @@ fn hold_esc_to_exit(
     }
 }
 
+/// Time, in seconds, to move between two camera path keyframes.
+const CAMERA_PATH_KEYFRAME_SECS: f32 = 2.0;
+
+fn add_camera_path_keyframe(
+    mut commands: Commands,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut q_path: Query<(Entity, Option<&mut CameraPath>), With<FlyByCamera>>,
+) {
+    let Some((_, transform)) = q_camera.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    let Ok((entity, path)) = q_path.get_single_mut() else {
+        return;
+    };
+
+    let transform = transform.compute_transform();
+    if let Some(mut path) = path {
+        path.push(transform, CAMERA_PATH_KEYFRAME_SECS, Easing::EaseInOut);
+        info!("Camera path keyframe {} added.", path.keyframes.len());
+    } else {
+        let mut path = CameraPath::default();
+        path.push(transform, CAMERA_PATH_KEYFRAME_SECS, Easing::EaseInOut);
+        commands.entity(entity).insert(path);
+        info!("Camera path keyframe 1 added.");
+    }
+}
+
+fn clear_camera_path(mut q_path: Query<&mut CameraPath, With<FlyByCamera>>) {
+    if let Ok(mut path) = q_path.get_single_mut() {
+        path.stop();
+        path.keyframes.clear();
+        info!("Camera path cleared.");
+    }
+}
+
+fn toggle_camera_path(
+    mut q_path: Query<&mut CameraPath, With<FlyByCamera>>,
+    mut director: CameraDirector,
+    settings: Res<ClientSettings>,
+) {
+    let Some(mut path) = q_path
+        .get_single_mut()
+        .ok()
+        .filter(|path| path.keyframes.len() > 1)
+    else {
+        let add = settings.input.describe(InputAction::AddCameraPathKeyframe);
+        warn!("Camera path needs at least two keyframes. Press {add} to add one.");
+        return;
+    };
+
+    if path.is_playing() {
+        path.stop();
+    } else {
+        // Path is played on fly by camera, so player input must be disabled.
+        director.set_cam(ActiveCamera::FlyBy);
+        director.set_active(false);
+
+        info!("Playing camera path ({:.1}s)...", path.duration());
+        path.play();
+    }
+}
+
+/// Gives camera control back to the player once camera path playback stops.
+fn release_camera_path(
+    q_path: Query<&CameraPath, With<FlyByCamera>>,
+    mut was_playing: Local<bool>,
+    mut director: CameraDirector,
+) {
+    let is_playing = q_path.get_single().is_ok_and(CameraPath::is_playing);
+
+    if *was_playing && !is_playing {
+        info!("Camera path playback stopped.");
+        director.set_active(true);
+    }
+
+    *was_playing = is_playing;
+}
+
+fn export_world(
+    server: Res<ServerConnection>,
+    landscape: Res<PlayerLandscape>,
+    settings: Res<ClientSettings>,
+) {
+    info!("Requesting world export...");
+    let _ = server.channel().send(WorldExport {
+        center: landscape.center.into(),
+        radius: settings.render_distance,
+    });
+}
+
+fn log_world_exported(In(msg): In<WorldExported>) {
+    info!("Exported {} chunks to {} on server.", msg.chunks, msg.path);
+}
+
 // fn slow_down_fps() {
 //     std::thread::sleep(std::time::Duration::from_millis(200));
 // }
@@ -0,0 +1,68 @@
+//! Seam visualizer draws faces on chunk borders which occlusion doesn't match neighbor voxels, as
+//! reported by server seam validation. Each offending voxel is outlined and a line points to the
+//! face which is wrong.
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::{
+    chunk::{self, Chunk},
+    voxel::{self, Voxel},
+};
+use projekto_messages::SeamMismatches;
+use projekto_proto::RegisterMessageHandler;
+
+use crate::net::ServerDisconnected;
+
+const SEAM_COLOR: Color = Color::rgb(1.0, 0.0, 1.0);
+
+pub(super) struct SeamVisualizerPlugin;
+
+impl Plugin for SeamVisualizerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SeamVisualizer>()
+            .set_message_handler(receive_seam_mismatches)
+            .add_systems(
+                Update,
+                (
+                    clear_seams_on_server_disconnect.run_if(on_event::<ServerDisconnected>()),
+                    draw_seams,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Mismatched faces of each chunk, as last reported by server.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+struct SeamVisualizer(HashMap<Chunk, Vec<(Voxel, voxel::Side)>>);
+
+fn receive_seam_mismatches(
+    In(SeamMismatches { chunk, faces }): In<SeamMismatches>,
+    mut visualizer: ResMut<SeamVisualizer>,
+) {
+    if faces.is_empty() {
+        visualizer.remove(&chunk);
+    } else {
+        warn!("Chunk {chunk} has {} mismatched seam faces", faces.len());
+        visualizer.insert(chunk, faces);
+    }
+}
+
+fn clear_seams_on_server_disconnect(
+    mut visualizer: ResMut<SeamVisualizer>,
+    mut reader: EventReader<ServerDisconnected>,
+) {
+    reader.clear();
+    visualizer.clear();
+}
+
+fn draw_seams(visualizer: Res<SeamVisualizer>, mut gizmos: Gizmos) {
+    for (&chunk, faces) in visualizer.iter() {
+        let origin = chunk::to_world(chunk);
+
+        for &(voxel, side) in faces {
+            let center = origin + voxel.as_vec3() + Vec3::splat(0.5);
+            gizmos.cuboid(Transform::from_translation(center), SEAM_COLOR);
+            gizmos.line(center, center + side.normal(), SEAM_COLOR);
+        }
+    }
+}
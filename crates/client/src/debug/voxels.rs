@@ -0,0 +1,347 @@
+//! Debug voxels are drawn as instances of a single cube mesh, with position, size and color per
+//! instance, so whole chunks of light or occlusion data are drawn in a single draw call per chunk.
+//!
+//! Instances are placed in world space, so the entity [`Transform`] and [`Aabb`] are only used by
+//! frustum culling and transparency sorting.
+
+use std::sync::Arc;
+
+use bevy::{
+    core::cast_slice,
+    core_pipeline::core_3d::Transparent3d,
+    ecs::{
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParamItem},
+    },
+    pbr::{
+        MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup, SetMeshViewBindGroup,
+    },
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        primitives::Aabb,
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult,
+            RenderPhase, SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::ExtractedView,
+        Render, RenderApp, RenderSet,
+    },
+    utils::HashMap,
+};
+use projekto_core::chunk::{self, Chunk};
+
+pub(super) struct DebugVoxelsPlugin;
+
+impl Plugin for DebugVoxelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<DebugVoxels>::default())
+            .add_systems(Startup, setup_debug_voxel_mesh);
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Transparent3d, DrawDebugVoxels>()
+            .init_resource::<SpecializedMeshPipelines<DebugVoxelsPipeline>>()
+            .init_resource::<DebugVoxelsBuffers>()
+            .add_systems(
+                Render,
+                (
+                    queue_debug_voxels.in_set(RenderSet::QueueMeshes),
+                    prepare_debug_voxels_buffers.in_set(RenderSet::PrepareResources),
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp)
+            .init_resource::<DebugVoxelsPipeline>();
+    }
+}
+
+/// A single debug voxel instance.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct DebugVoxel {
+    /// Center of the voxel, in world space.
+    pub position: Vec3,
+    /// Size of the voxel, where `1.0` is the same size of a real voxel.
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+impl DebugVoxel {
+    /// Size of instance data on vertex buffer.
+    const SIZE: u64 = 2 * VertexFormat::Float32x4.size();
+
+    /// **Returns** instance data, as laid out on vertex buffer.
+    fn to_instance(self) -> [f32; 8] {
+        let [x, y, z] = self.position.to_array();
+        let [r, g, b, a] = self.color;
+        [x, y, z, self.size, r, g, b, a]
+    }
+}
+
+/// Voxels drawn by an entity. Data is shared with render world, so it is only uploaded to the GPU
+/// again when replaced.
+#[derive(Component, Debug, Default, Clone, Deref)]
+pub(super) struct DebugVoxels(Arc<Vec<DebugVoxel>>);
+
+impl DebugVoxels {
+    pub fn new(voxels: Vec<DebugVoxel>) -> Self {
+        Self(Arc::new(voxels))
+    }
+}
+
+impl ExtractComponent for DebugVoxels {
+    type QueryData = &'static DebugVoxels;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::QueryData>) -> Option<Self> {
+        (!item.is_empty()).then(|| item.clone())
+    }
+}
+
+/// Cube mesh shared by all debug voxels.
+#[derive(Resource, Debug)]
+pub(super) struct DebugVoxelMesh(Handle<Mesh>);
+
+fn setup_debug_voxel_mesh(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let mesh = meshes.add(Cuboid::from_size(Vec3::ONE));
+    commands.insert_resource(DebugVoxelMesh(mesh));
+}
+
+#[derive(Bundle)]
+pub(super) struct DebugVoxelsBundle {
+    mesh: Handle<Mesh>,
+    voxels: DebugVoxels,
+    aabb: Aabb,
+    spatial: SpatialBundle,
+}
+
+impl DebugVoxelsBundle {
+    /// Creates a bundle which draws the given voxels of the given chunk. All voxels must be inside
+    /// that chunk, or they may be culled while still visible.
+    pub fn new(mesh: &DebugVoxelMesh, chunk: Chunk, voxels: DebugVoxels) -> Self {
+        let size = Vec3::new(
+            chunk::X_AXIS_SIZE as f32,
+            chunk::Y_AXIS_SIZE as f32,
+            chunk::Z_AXIS_SIZE as f32,
+        );
+
+        Self {
+            mesh: mesh.0.clone(),
+            voxels,
+            aabb: Aabb::from_min_max(Vec3::ZERO, size),
+            spatial: SpatialBundle::from_transform(Transform::from_translation(chunk::to_world(
+                chunk,
+            ))),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_debug_voxels(
+    draw_functions: Res<DrawFunctions<Transparent3d>>,
+    pipeline: Res<DebugVoxelsPipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<DebugVoxelsPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    q_voxels: Query<Entity, With<DebugVoxels>>,
+    mut q_views: Query<(&ExtractedView, &mut RenderPhase<Transparent3d>)>,
+) {
+    let draw_debug_voxels = draw_functions.read().id::<DrawDebugVoxels>();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, mut transparent_phase) in &mut q_views {
+        let view_key =
+            msaa_key | MeshPipelineKey::from_hdr(view.hdr) | MeshPipelineKey::BLEND_ALPHA;
+        let rangefinder = view.rangefinder3d();
+
+        // Only visible entities have mesh instances, so culled chunks are skipped here.
+        for entity in &q_voxels {
+            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let pipeline = match pipelines.specialize(&pipeline_cache, &pipeline, key, &mesh.layout)
+            {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    error!("Failed to specialize debug voxels pipeline: {err}");
+                    continue;
+                }
+            };
+
+            transparent_phase.add(Transparent3d {
+                entity,
+                pipeline,
+                draw_function: draw_debug_voxels,
+                distance: rangefinder
+                    .distance_translation(&mesh_instance.transforms.transform.translation),
+                batch_range: 0..1,
+                dynamic_offset: None,
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+struct DebugVoxelsBuffer {
+    buffer: Buffer,
+    length: u32,
+}
+
+/// Instance buffers of each entity, kept across frames along the data they were created from, so
+/// unchanged voxels aren't uploaded every frame.
+#[derive(Resource, Default)]
+struct DebugVoxelsBuffers(HashMap<Entity, (DebugVoxels, Buffer)>);
+
+fn prepare_debug_voxels_buffers(
+    mut commands: Commands,
+    q_voxels: Query<(Entity, &DebugVoxels)>,
+    render_device: Res<RenderDevice>,
+    mut buffers: ResMut<DebugVoxelsBuffers>,
+) {
+    let mut existing = std::mem::take(&mut buffers.0);
+
+    for (entity, voxels) in &q_voxels {
+        let buffer = match existing.remove(&entity) {
+            Some((cached, buffer)) if Arc::ptr_eq(&cached.0, &voxels.0) => buffer,
+            _ => render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("debug voxels instance buffer"),
+                contents: cast_slice(
+                    &voxels
+                        .iter()
+                        .map(|voxel| voxel.to_instance())
+                        .collect::<Vec<_>>(),
+                ),
+                usage: BufferUsages::VERTEX,
+            }),
+        };
+
+        commands.entity(entity).insert(DebugVoxelsBuffer {
+            buffer: buffer.clone(),
+            length: voxels.len() as u32,
+        });
+        buffers.0.insert(entity, (voxels.clone(), buffer));
+    }
+}
+
+#[derive(Resource)]
+struct DebugVoxelsPipeline {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for DebugVoxelsPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let shader = world
+            .resource::<AssetServer>()
+            .load("shaders/debug_voxels.wgsl");
+        let mesh_pipeline = world.resource::<MeshPipeline>().clone();
+
+        Self {
+            shader,
+            mesh_pipeline,
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for DebugVoxelsPipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        descriptor.vertex.shader = self.shader.clone();
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: DebugVoxel::SIZE,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                // Locations 0 to 2 are used by cube position, normal and UV.
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 3,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: VertexFormat::Float32x4.size(),
+                    shader_location: 4,
+                },
+            ],
+        });
+
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader = self.shader.clone();
+        }
+
+        Ok(descriptor)
+    }
+}
+
+type DrawDebugVoxels = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawVoxelsInstanced,
+);
+
+struct DrawVoxelsInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawVoxelsInstanced {
+    type Param = (SRes<RenderAssets<Mesh>>, SRes<RenderMeshInstances>);
+    type ViewQuery = ();
+    type ItemQuery = Read<DebugVoxelsBuffer>;
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        instances: Option<&'w DebugVoxelsBuffer>,
+        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(mesh_instance) = render_mesh_instances.get(&item.entity()) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(instances) = instances else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instances.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instances.length);
+            }
+            GpuBufferInfo::NonIndexed => {
+                pass.draw(0..gpu_mesh.vertex_count, 0..instances.length);
+            }
+        }
+
+        RenderCommandResult::Success
+    }
+}
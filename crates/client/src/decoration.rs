@@ -0,0 +1,184 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    utils::HashMap,
+};
+use projekto_core::chunk::{self, Chunk, DecorationKind};
+use projekto_messages::{ChunkDecorations, Teleport};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{net::ServerDisconnected, PlayerLandscape};
+
+/// Width and height of each decoration billboard, in voxels.
+const DECORATION_SIZE: Vec2 = Vec2::new(0.8, 0.6);
+const GRASS_COLOR: Color = Color::rgb(0.35, 0.65, 0.2);
+const FLOWER_COLOR: Color = Color::rgb(0.95, 0.8, 0.25);
+
+pub(crate) struct DecorationPlugin;
+
+impl Plugin for DecorationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkDecorationsMap>()
+            .set_message_handler(update_chunk_decorations)
+            .add_message_handler(clear_decorations_on_teleport)
+            .add_systems(Startup, setup_decoration_assets)
+            .add_systems(
+                Update,
+                clear_decorations_on_server_disconnect.run_if(on_event::<ServerDisconnected>()),
+            );
+    }
+}
+
+/// Mesh and material shared by all decorations of the same kind, so they are drawn as a single
+/// instanced batch.
+#[derive(Resource, Debug)]
+struct DecorationAssets(HashMap<DecorationKind, (Handle<Mesh>, Handle<StandardMaterial>)>);
+
+/// Entity holding all decorations of each chunk, as children.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+struct ChunkDecorationsMap(HashMap<Chunk, Entity>);
+
+impl ChunkDecorationsMap {
+    fn despawn_all(&mut self, commands: &mut Commands) {
+        for (_, entity) in self.drain() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn setup_decoration_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mesh = meshes.add(crossed_quads_mesh(DECORATION_SIZE));
+
+    let assets = [
+        (DecorationKind::Grass, GRASS_COLOR),
+        (DecorationKind::Flower, FLOWER_COLOR),
+    ]
+    .into_iter()
+    .map(|(kind, color)| {
+        let material = materials.add(StandardMaterial {
+            base_color: color,
+            perceptual_roughness: 1.0,
+            // Billboards are seen from both sides.
+            double_sided: true,
+            cull_mode: None,
+            ..default()
+        });
+        (kind, (mesh.clone(), material))
+    })
+    .collect();
+
+    commands.insert_resource(DecorationAssets(assets));
+}
+
+/// **Returns** two vertical quads crossing each other at their centers, with the bottom at origin,
+/// which looks like a tuft from any horizontal direction.
+fn crossed_quads_mesh(size: Vec2) -> Mesh {
+    let half = size.x / 2.0;
+    let corners = [
+        (Vec3::new(-half, 0.0, -half), Vec3::new(half, 0.0, half)),
+        (Vec3::new(-half, 0.0, half), Vec3::new(half, 0.0, -half)),
+    ];
+
+    let mut positions = vec![];
+    let mut indices = vec![];
+    for (start, end) in corners {
+        let base = positions.len() as u16;
+        positions.extend([start, end, end + Vec3::Y * size.y, start + Vec3::Y * size.y]);
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+    }
+
+    // Normals point up, so both sides are lit like the surface below them.
+    let normals = vec![Vec3::Y; positions.len()];
+    let uvs = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]].repeat(corners.len());
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U16(indices))
+}
+
+fn update_chunk_decorations(
+    In(ChunkDecorations { chunk, decorations }): In<ChunkDecorations>,
+    mut commands: Commands,
+    mut map: ResMut<ChunkDecorationsMap>,
+    assets: Res<DecorationAssets>,
+    landscape: Res<PlayerLandscape>,
+) {
+    let radius = landscape.radius as i32;
+    map.retain(|&other, entity| {
+        let keep = other.distance(landscape.center.into()).abs().max_element() <= radius;
+        if !keep {
+            commands.entity(*entity).despawn_recursive();
+        }
+        keep
+    });
+
+    // Decorations are always sent as a whole, so older ones are replaced.
+    if let Some(entity) = map.remove(&chunk) {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if decorations.is_empty() {
+        return;
+    }
+
+    let entity = commands
+        .spawn((
+            Name::new(format!("Decorations {chunk}")),
+            SpatialBundle::from_transform(Transform::from_translation(chunk::to_world(chunk))),
+        ))
+        .with_children(|parent| {
+            for decoration in decorations {
+                let Some((mesh, material)) = assets.0.get(&decoration.kind) else {
+                    continue;
+                };
+
+                // Billboards stand on the center of the empty voxel above the surface.
+                let translation = decoration.voxel().as_vec3() + Vec3::new(0.5, 0.0, 0.5);
+
+                parent.spawn((
+                    PbrBundle {
+                        mesh: mesh.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_translation(translation),
+                        ..default()
+                    },
+                    NotShadowCaster,
+                    NotShadowReceiver,
+                ));
+            }
+        })
+        .id();
+
+    map.insert(chunk, entity);
+    trace!("[update_chunk_decorations] chunk {chunk:?} decorations updated");
+}
+
+fn clear_decorations_on_teleport(
+    In(_): In<Teleport>,
+    mut commands: Commands,
+    mut map: ResMut<ChunkDecorationsMap>,
+) {
+    map.despawn_all(&mut commands);
+}
+
+fn clear_decorations_on_server_disconnect(
+    mut commands: Commands,
+    mut map: ResMut<ChunkDecorationsMap>,
+    mut reader: EventReader<ServerDisconnected>,
+) {
+    reader.clear();
+    map.despawn_all(&mut commands);
+}
@@ -0,0 +1,355 @@
+//! Input actions, so controllers and debug systems don't depend on which keys or buttons are bound
+//! to them. Bindings are persisted on [`ClientSettings`], so they can be changed by players.
+
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{ui::Hotbar, ClientSettings};
+
+/// Something the player can do, which is triggered by any of its [`InputBinding`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    /// Jump or swim up when controlling a character, or fly up.
+    Up,
+    /// Swim or fly down.
+    Down,
+    /// Move faster when flying, or sprint when controlling a character.
+    Boost,
+    BreakVoxel,
+    PlaceVoxel,
+    /// Select the given hotbar slot, starting at zero.
+    HotbarSlot(u8),
+    FlyByCamera,
+    OrbitCamera,
+    /// Top-down camera which follows the character until panned away.
+    RtsCamera,
+    FirstPersonCamera,
+    /// Release the mouse, so UI can be used. Holding it exits the game.
+    ReleaseMouse,
+    Screenshot,
+    RecordDemo,
+    PlayDemo,
+    ToggleDebugOverlay,
+    ToggleMinimap,
+    ToggleSettingsMenu,
+    /// Add current camera transform at the end of the fly by camera path.
+    AddCameraPathKeyframe,
+    /// Start or stop playing the fly by camera path.
+    PlayCameraPath,
+    ClearCameraPath,
+    /// Inspect the targeted chunk, when chunk inspector is enabled.
+    InspectChunk,
+    InspectorSliceUp,
+    InspectorSliceDown,
+    /// Switch the chunk layer shown by chunk inspector.
+    InspectorLayer,
+    /// Draw light of the targeted chunk and its neighbors, when chunk inspector is enabled.
+    ToggleLightVisualizer,
+    /// Show a top-down map around the camera, sampled straight from world generator, when chunk
+    /// inspector is enabled.
+    ToggleGenPreview,
+    /// Switch the layer shown by world gen preview.
+    GenPreviewLayer,
+    /// Ask server to export chunks in render distance to a model file.
+    ExportWorld,
+    /// Ask server to undo the last voxel edit made by the player.
+    UndoEdit,
+    /// Ask server to redo the last voxel edit undone by the player.
+    RedoEdit,
+    /// Start typing chat text or commands. Keys only type text until chat is sent or closed.
+    OpenChat,
+}
+
+/// Key, mouse button or gamepad button bound to an [`InputAction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// Button of any connected gamepad.
+    Gamepad(GamepadButtonType),
+}
+
+impl std::fmt::Display for InputBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputBinding::Key(key) => write!(f, "{key:?}"),
+            InputBinding::Mouse(button) => write!(f, "Mouse {button:?}"),
+            InputBinding::Gamepad(button) => write!(f, "Gamepad {button:?}"),
+        }
+    }
+}
+
+/// Gamepad sticks used to move and look around, and how they respond.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub move_x: GamepadAxisType,
+    pub move_y: GamepadAxisType,
+    pub look_x: GamepadAxisType,
+    pub look_y: GamepadAxisType,
+    /// Stick deflection, from zero to one, below which sticks are ignored.
+    pub deadzone: f32,
+    /// Exponent of look response curve. Higher values give finer control on small deflections,
+    /// while one is linear.
+    pub look_exponent: f32,
+    /// Mouse motion, in pixels, equivalent to a fully deflected look stick. Cameras scale mouse
+    /// motion by frame time, so it is applied every frame.
+    pub look_speed: f32,
+    pub invert_look_y: bool,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            move_x: GamepadAxisType::LeftStickX,
+            move_y: GamepadAxisType::LeftStickY,
+            look_x: GamepadAxisType::RightStickX,
+            look_y: GamepadAxisType::RightStickY,
+            deadzone: 0.15,
+            look_exponent: 2.0,
+            look_speed: 3.0,
+            invert_look_y: false,
+        }
+    }
+}
+
+/// **Returns** the given stick deflection remapped so it is zero inside `deadzone` and reaches one
+/// when fully deflected, raised to `exponent`. Direction is kept.
+pub fn response_curve(stick: Vec2, deadzone: f32, exponent: f32) -> Vec2 {
+    let deflection = stick.length();
+    if deflection <= deadzone || deadzone >= 1.0 {
+        return Vec2::ZERO;
+    }
+
+    let t = ((deflection - deadzone) / (1.0 - deadzone)).min(1.0);
+    stick / deflection * t.powf(exponent)
+}
+
+/// Bindings of each [`InputAction`]. Actions may have many bindings or none at all.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputMap(BTreeMap<InputAction, Vec<InputBinding>>);
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use InputAction::*;
+        use InputBinding::*;
+
+        let hotbar_keys = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+
+        let bindings = [
+            (Forward, Key(KeyCode::KeyW)),
+            (Backward, Key(KeyCode::KeyS)),
+            (Left, Key(KeyCode::KeyA)),
+            (Right, Key(KeyCode::KeyD)),
+            (Up, Key(KeyCode::Space)),
+            (Up, Gamepad(GamepadButtonType::South)),
+            (Down, Key(KeyCode::ControlLeft)),
+            (Down, Gamepad(GamepadButtonType::East)),
+            (Boost, Key(KeyCode::ShiftLeft)),
+            (Boost, Gamepad(GamepadButtonType::LeftThumb)),
+            (BreakVoxel, Mouse(MouseButton::Left)),
+            (BreakVoxel, Gamepad(GamepadButtonType::RightTrigger2)),
+            (PlaceVoxel, Mouse(MouseButton::Right)),
+            (PlaceVoxel, Gamepad(GamepadButtonType::LeftTrigger2)),
+            (FlyByCamera, Key(KeyCode::KeyI)),
+            (OrbitCamera, Key(KeyCode::KeyO)),
+            (RtsCamera, Key(KeyCode::KeyU)),
+            (FirstPersonCamera, Key(KeyCode::KeyP)),
+            (ReleaseMouse, Key(KeyCode::Escape)),
+            (ReleaseMouse, Gamepad(GamepadButtonType::Select)),
+            (Screenshot, Key(KeyCode::F2)),
+            (RecordDemo, Key(KeyCode::F6)),
+            (PlayDemo, Key(KeyCode::F7)),
+            (ToggleDebugOverlay, Key(KeyCode::F3)),
+            (ToggleMinimap, Key(KeyCode::KeyM)),
+            (ToggleSettingsMenu, Key(KeyCode::F10)),
+            (ToggleSettingsMenu, Gamepad(GamepadButtonType::Start)),
+            (AddCameraPathKeyframe, Key(KeyCode::F8)),
+            (PlayCameraPath, Key(KeyCode::F9)),
+            (ClearCameraPath, Key(KeyCode::F12)),
+            (InspectChunk, Mouse(MouseButton::Middle)),
+            (InspectorSliceUp, Key(KeyCode::BracketRight)),
+            (InspectorSliceDown, Key(KeyCode::BracketLeft)),
+            (InspectorLayer, Key(KeyCode::Backslash)),
+            (ToggleLightVisualizer, Key(KeyCode::F5)),
+            (ToggleGenPreview, Key(KeyCode::F1)),
+            (GenPreviewLayer, Key(KeyCode::Semicolon)),
+            (ExportWorld, Key(KeyCode::F4)),
+            (UndoEdit, Key(KeyCode::KeyZ)),
+            (RedoEdit, Key(KeyCode::KeyY)),
+            (OpenChat, Key(KeyCode::KeyT)),
+        ]
+        .into_iter()
+        .chain(
+            hotbar_keys
+                .into_iter()
+                .take(Hotbar::SLOTS)
+                .enumerate()
+                .map(|(slot, key)| (HotbarSlot(slot as u8), Key(key))),
+        )
+        .fold(
+            BTreeMap::<_, Vec<_>>::new(),
+            |mut bindings, (action, binding)| {
+                bindings.entry(action).or_default().push(binding);
+                bindings
+            },
+        );
+
+        Self(bindings)
+    }
+}
+
+impl InputMap {
+    /// **Returns** all bindings of the given action.
+    pub fn bindings(&self, action: InputAction) -> &[InputBinding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// **Returns** the first key bound to the given action, if any.
+    pub fn key(&self, action: InputAction) -> Option<KeyCode> {
+        self.bindings(action)
+            .iter()
+            .find_map(|binding| match binding {
+                InputBinding::Key(key) => Some(*key),
+                InputBinding::Mouse(_) | InputBinding::Gamepad(_) => None,
+            })
+    }
+
+    /// **Returns** a human readable list of bindings of the given action.
+    pub fn describe(&self, action: InputAction) -> String {
+        let bindings = self.bindings(action);
+        if bindings.is_empty() {
+            return "unbound".to_string();
+        }
+
+        bindings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+
+    /// Replaces all bindings of the given action.
+    pub fn bind(&mut self, action: InputAction, bindings: Vec<InputBinding>) {
+        self.0.insert(action, bindings);
+    }
+
+    /// Adds default bindings of actions which aren't on this map, like actions added after the map
+    /// was saved.
+    pub fn with_defaults(mut self) -> Self {
+        for (action, bindings) in InputMap::default().0 {
+            self.0.entry(action).or_insert(bindings);
+        }
+        self
+    }
+}
+
+/// Current state of [`InputAction`]s and gamepad sticks, based on bindings on [`ClientSettings`].
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    settings: Res<'w, ClientSettings>,
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl<'w> ActionInput<'w> {
+    fn any<K, M, G>(&self, action: InputAction, key: K, mouse: M, gamepad: G) -> bool
+    where
+        K: Fn(&ButtonInput<KeyCode>, KeyCode) -> bool,
+        M: Fn(&ButtonInput<MouseButton>, MouseButton) -> bool,
+        G: Fn(&ButtonInput<GamepadButton>, GamepadButton) -> bool,
+    {
+        self.settings
+            .input
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                InputBinding::Key(k) => key(&self.keys, k),
+                InputBinding::Mouse(m) => mouse(&self.mouse, m),
+                InputBinding::Gamepad(button) => self
+                    .gamepads
+                    .iter()
+                    .any(|pad| gamepad(&self.gamepad_buttons, GamepadButton::new(pad, button))),
+            })
+    }
+
+    /// **Returns** true while any binding of the given action is pressed.
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.any(
+            action,
+            ButtonInput::pressed,
+            ButtonInput::pressed,
+            ButtonInput::pressed,
+        )
+    }
+
+    /// **Returns** true if any binding of the given action was pressed this frame.
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.any(
+            action,
+            ButtonInput::just_pressed,
+            ButtonInput::just_pressed,
+            ButtonInput::just_pressed,
+        )
+    }
+
+    /// **Returns** the deflection of the given axes on the first gamepad which has it deflected.
+    fn stick(&self, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+        self.gamepads
+            .iter()
+            .map(|pad| {
+                let axis = |axis_type| {
+                    self.gamepad_axes
+                        .get(GamepadAxis::new(pad, axis_type))
+                        .unwrap_or_default()
+                };
+                Vec2::new(axis(x), axis(y))
+            })
+            .find(|stick| *stick != Vec2::ZERO)
+            .unwrap_or_default()
+    }
+
+    /// **Returns** the gamepad move stick, after deadzone, where positive Y is forward.
+    pub fn move_axis(&self) -> Vec2 {
+        let config = &self.settings.gamepad;
+        let stick = self.stick(config.move_x, config.move_y);
+        response_curve(stick, config.deadzone, 1.0)
+    }
+
+    /// **Returns** the gamepad look stick, after response curve, where positive Y is up.
+    pub fn look_axis(&self) -> Vec2 {
+        let config = &self.settings.gamepad;
+        let stick = self.stick(config.look_x, config.look_y);
+        let look = response_curve(stick, config.deadzone, config.look_exponent);
+
+        if config.invert_look_y {
+            Vec2::new(look.x, -look.y)
+        } else {
+            look
+        }
+    }
+}
+
+/// Run condition which is true when any binding of the given action was pressed this frame.
+pub fn action_just_pressed(action: InputAction) -> impl Fn(ActionInput) -> bool + Clone {
+    move |input: ActionInput| input.just_pressed(action)
+}
@@ -0,0 +1,210 @@
+//! Smooths remote entities by rendering them slightly in the past, between two received snapshots.
+//! Snapshots are placed on server time, by the tick they were taken on, so network jitter doesn't
+//! disturb them.
+
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_messages::{RemotePlayerLeft, RemotePlayerTransform};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{net::ServerDisconnected, server_time::EstimatedServerTime};
+
+/// How far in the past, in seconds, remote entities are rendered, besides latency, so there is
+/// usually a newer snapshot to interpolate towards. It spans two server ticks.
+const INTERPOLATION_DELAY: f32 = 0.1;
+/// Max time, in seconds, a remote entity keeps moving when no new snapshot arrives.
+const MAX_EXTRAPOLATION: f32 = 0.25;
+/// Max snapshots kept per entity.
+const MAX_SNAPSHOTS: usize = 32;
+
+pub(crate) struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RemotePlayers>()
+            .add_message_handler(update_remote_player)
+            .add_message_handler(remove_remote_player)
+            .add_systems(
+                Update,
+                (
+                    despawn_remote_players_on_server_disconnect
+                        .run_if(on_event::<ServerDisconnected>()),
+                    interpolate_transforms,
+                ),
+            );
+    }
+}
+
+/// A transform received from server at a given time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    /// Server time, in seconds, of the tick this snapshot was taken on.
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    /// Entity should jump straight to this snapshot, instead of interpolating towards it.
+    pub teleport: bool,
+}
+
+/// Snapshots received from server, used to render an entity [`INTERPOLATION_DELAY`] in the past.
+#[derive(Component, Default, Debug, Clone)]
+pub struct InterpolationBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl InterpolationBuffer {
+    /// Adds a new snapshot. Teleport snapshots discard older ones, so there is no interpolation
+    /// from previous location.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if snapshot.teleport {
+            self.snapshots.clear();
+        }
+
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Discards snapshots which are no longer needed to sample at `time` or later.
+    fn prune(&mut self, time: f32) {
+        while self.snapshots.len() > 2 && self.snapshots[1].time <= time {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// **Returns** the interpolated translation and rotation at the given `time`, extrapolating for
+    /// at most [`MAX_EXTRAPOLATION`] when there is no newer snapshot.
+    pub fn sample(&self, time: f32) -> Option<(Vec3, Quat)> {
+        let first = self.snapshots.front()?;
+        if time <= first.time {
+            return Some((first.translation, first.rotation));
+        }
+
+        if let Some(i) = self.snapshots.iter().position(|s| s.time > time) {
+            let (a, b) = (self.snapshots[i - 1], self.snapshots[i]);
+            let t = (time - a.time) / (b.time - a.time);
+            return Some((
+                a.translation.lerp(b.translation, t),
+                a.rotation.slerp(b.rotation, t),
+            ));
+        }
+
+        let last = self.snapshots.back()?;
+        let Some(previous) = self.snapshots.iter().rev().nth(1) else {
+            return Some((last.translation, last.rotation));
+        };
+
+        let velocity = (last.translation - previous.translation) / (last.time - previous.time);
+        let elapsed = (time - last.time).min(MAX_EXTRAPOLATION);
+
+        Some((last.translation + velocity * elapsed, last.rotation))
+    }
+}
+
+/// Another player connected to the same server.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct RemotePlayer(pub u32);
+
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+struct RemotePlayers(HashMap<u32, Entity>);
+
+fn update_remote_player(
+    In(RemotePlayerTransform {
+        player,
+        position,
+        rotation,
+        teleport,
+        tick,
+    }): In<RemotePlayerTransform>,
+    time: Res<Time>,
+    mut server_time: ResMut<EstimatedServerTime>,
+    mut players: ResMut<RemotePlayers>,
+    mut q: Query<&mut InterpolationBuffer>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Snapshots can't be placed on server time until clock is synced, so sync it right away.
+    if !server_time.is_synced() {
+        server_time.sync(tick, time.elapsed_seconds_f64());
+    }
+
+    let snapshot = Snapshot {
+        time: (tick as f64 * EstimatedServerTime::TICK_SECS) as f32,
+        translation: position,
+        rotation,
+        teleport,
+    };
+
+    if let Some(mut buffer) = players.get(&player).and_then(|&e| q.get_mut(e).ok()) {
+        buffer.push(snapshot);
+        return;
+    }
+
+    let mut buffer = InterpolationBuffer::default();
+    buffer.push(snapshot);
+
+    // Placeholder until there is a proper player model.
+    let entity = commands
+        .spawn((
+            PbrBundle {
+                transform: Transform::from_translation(position).with_rotation(rotation),
+                mesh: meshes.add(Capsule3d {
+                    radius: 0.25,
+                    half_length: 0.75,
+                }),
+                material: materials.add(Color::rgb(0.8, 0.4, 0.2)),
+                ..Default::default()
+            },
+            buffer,
+            RemotePlayer(player),
+            Name::new(format!("Remote Player {player}")),
+        ))
+        .id();
+
+    players.insert(player, entity);
+}
+
+fn remove_remote_player(
+    In(RemotePlayerLeft { player }): In<RemotePlayerLeft>,
+    mut players: ResMut<RemotePlayers>,
+    mut commands: Commands,
+) {
+    if let Some(entity) = players.remove(&player) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn despawn_remote_players_on_server_disconnect(
+    mut players: ResMut<RemotePlayers>,
+    mut reader: EventReader<ServerDisconnected>,
+    mut commands: Commands,
+) {
+    reader.clear();
+    for (_, entity) in players.drain() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn interpolate_transforms(
+    time: Res<Time>,
+    server_time: Res<EstimatedServerTime>,
+    mut q: Query<(&mut InterpolationBuffer, &mut Transform)>,
+) {
+    // Snapshots arrive half a round trip after they were taken.
+    let latency = server_time.rtt().map_or(0.0, |rtt| rtt.as_secs_f32() / 2.0);
+    let render_time =
+        server_time.server_secs(time.elapsed_seconds_f64()) as f32 - latency - INTERPOLATION_DELAY;
+
+    for (mut buffer, mut transform) in &mut q {
+        buffer.prune(render_time);
+
+        if let Some((translation, rotation)) = buffer.sample(render_time) {
+            transform.translation = translation;
+            transform.rotation = rotation;
+        }
+    }
+}
@@ -4,39 +4,66 @@ use bevy::{
     prelude::*,
     render::view::RenderLayers,
     utils::HashMap,
-    window::PresentMode,
 };
 use bundle::{ChunkLocal, ChunkVertex};
 use controller::{
     camera_controller::CameraControllerPlugin,
-    character_controller::{CharacterController, CharacterControllerPlugin},
+    camera_director::CameraDirectorPlugin,
+    character_controller::{CharacterController, CharacterControllerPlugin, CharacterMotion},
+    interaction::{PlayerInteractionPlugin, TARGET_HIGHLIGHT_LAYER},
 };
 use debug::DebugPlugin;
 use material::ChunkMaterial;
 use net::ServerConnection;
 use projekto_camera::{
-    first_person::{FirstPersonCamera, FirstPersonTarget},
+    first_person::{FirstPersonCamera, FirstPersonEffects, FirstPersonTarget},
     fly_by::FlyByCamera,
+    orbit::{OrbitCamera, OrbitTarget},
+    rts::{RtsCamera, RtsFollow},
     CameraPlugin,
 };
 use projekto_core::{
-    chunk::Chunk,
+    chunk::{self, Chunk, ChunkStorage},
     voxel::{self},
 };
 
+mod atlas;
+#[cfg(feature = "audio")]
+mod audio;
 mod bundle;
+mod capture;
 mod controller;
+mod crack;
 mod debug;
+mod decoration;
+mod input;
+mod interpolation;
+mod liquid;
 mod material;
+mod mesh_cache;
 mod net;
+mod server_time;
 mod set;
+mod settings;
+mod sky;
+mod ui;
 
+pub use capture::{DemoCapture, DemoKeyframe, DemoPath};
+pub use controller::camera_director::{ActiveCamera, CameraTransitionEvent};
+pub use input::{action_just_pressed, ActionInput, InputAction, InputBinding, InputMap};
+pub use interpolation::{InterpolationBuffer, RemotePlayer, Snapshot};
+pub use net::{AgreedCapabilities, ConnectionError, NetworkStats, ServerAddress};
+pub use projekto_core::time::WorldTime;
+pub use server_time::EstimatedServerTime;
 pub use set::PlayerLandscape;
+pub use settings::{ClientSettings, GraphicsPreset};
 
 pub struct ClientPlugin;
 
 impl Plugin for ClientPlugin {
     fn build(&self, app: &mut App) {
+        let settings = ClientSettings::load();
+
         app
             // This may cause problems later on. Ideally this setup should be done per image
             .insert_resource(Msaa::Sample4)
@@ -44,7 +71,7 @@ impl Plugin for ClientPlugin {
                 DefaultPlugins
                     .set(WindowPlugin {
                         primary_window: Some(Window {
-                            present_mode: PresentMode::AutoNoVsync,
+                            present_mode: settings.present_mode(),
                             ..Default::default()
                         }),
                         ..Default::default()
@@ -57,13 +84,26 @@ impl Plugin for ClientPlugin {
                 CameraPlugin,
                 DebugPlugin,
                 CameraControllerPlugin,
+                CameraDirectorPlugin,
                 CharacterControllerPlugin,
+                PlayerInteractionPlugin,
+            ))
+            .insert_resource(settings)
+            .add_plugins((
+                settings::SettingsPlugin,
+                sky::SkyPlugin,
+                ui::GameUiPlugin,
+                capture::CapturePlugin,
             ))
             .add_systems(Startup, setup_mockup_scene);
 
         // World setup
-        app.init_resource::<ChunkMap>()
+        app.init_state::<ClientState>()
+            .init_resource::<ChunkMap>()
+            .init_resource::<ChunkKindClientCache>()
             .register_type::<ChunkMaterial>()
+            .register_type::<ChunkLocal>()
+            .register_type::<ChunkVertex>()
             .configure_sets(PreUpdate, ClientSet::ReceiveMessages)
             .configure_sets(Update, ClientSet::Meshing)
             .configure_sets(
@@ -73,16 +113,35 @@ impl Plugin for ClientPlugin {
             .add_plugins(MaterialPlugin::<ChunkMaterial>::default())
             .add_plugins((
                 net::NetPlugin,
+                server_time::ServerTimePlugin,
                 set::ReceiveMessagesPlugin,
                 set::MeshingPlugin,
                 set::SendInputPlugin,
+                interpolation::InterpolationPlugin,
+                atlas::AtlasReloadPlugin,
+                mesh_cache::MeshCachePlugin,
+                decoration::DecorationPlugin,
+                liquid::LiquidPlugin,
+                crack::CrackPlugin,
             ))
             .add_systems(Startup, setup_material)
             .add_systems(PreStartup, load_assets)
             .add_systems(
                 Update,
-                (remove_unloaded_chunks.run_if(any_chunk::<Changed<ChunkVertex>>),),
+                (
+                    remove_unloaded_chunks.run_if(any_chunk::<Changed<ChunkVertex>>),
+                    update_material_fog.run_if(
+                        resource_exists::<ChunkMaterialHandle>.and_then(
+                            resource_changed::<ClientSettings>
+                                .or_else(resource_changed::<ClearColor>)
+                                .or_else(resource_added::<ChunkMaterialHandle>),
+                        ),
+                    ),
+                ),
             );
+
+        #[cfg(feature = "audio")]
+        app.add_plugins(audio::GameAudioPlugin);
     }
 }
 
@@ -98,13 +157,13 @@ fn setup_mockup_scene(
                 .looking_at(Vec3::new(0.0, 128.0, 0.0), Vec3::Y),
             ..Default::default()
         },
-        RenderLayers::from_layers(&[0, 1]),
+        RenderLayers::from_layers(&[0, 1, TARGET_HIGHLIGHT_LAYER]),
         FlyByCamera,
         Name::new("FlyByCamera"),
     ));
 
     // character
-    commands
+    let character = commands
         .spawn((
             PbrBundle {
                 transform: Transform::from_xyz(2.0, 20.0, 7.0),
@@ -117,6 +176,7 @@ fn setup_mockup_scene(
             },
             Name::new("Character"),
             CharacterController,
+            CharacterMotion::default(),
             FirstPersonTarget,
         ))
         .with_children(|p| {
@@ -137,10 +197,41 @@ fn setup_mockup_scene(
                     },
                     ..Default::default()
                 },
+                RenderLayers::from_layers(&[0, TARGET_HIGHLIGHT_LAYER]),
                 Name::new("FirstPersonCamera"),
                 FirstPersonCamera,
+                FirstPersonEffects::default(),
             ));
-        });
+        })
+        .id();
+
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                is_active: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        RenderLayers::from_layers(&[0, TARGET_HIGHLIGHT_LAYER]),
+        Name::new("OrbitCamera"),
+        OrbitCamera,
+        OrbitTarget::Entity(character),
+    ));
+
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                is_active: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        RenderLayers::from_layers(&[0, TARGET_HIGHLIGHT_LAYER]),
+        Name::new("RtsCamera"),
+        RtsCamera,
+        RtsFollow(character),
+    ));
 
     // X axis
     commands.spawn(PbrBundle {
@@ -162,11 +253,15 @@ fn setup_mockup_scene(
         material: materials.add(Color::rgb(0.3, 0.3, 1.0)),
         ..Default::default()
     });
+}
 
-    commands.spawn(PointLightBundle {
-        transform: Transform::from_xyz(4.0, 8.0, 4.0),
-        ..Default::default()
-    });
+/// Client flow, from picking a server on title screen to playing on it.
+#[derive(States, Default, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum ClientState {
+    #[default]
+    Title,
+    Connecting,
+    InGame,
 }
 
 #[derive(SystemSet, Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -181,6 +276,12 @@ pub enum ClientSet {
 #[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
 struct ChunkMap(HashMap<Chunk, Entity>);
 
+/// Voxel kinds of chunks near the player, received from server when subscribed to it, bounded by
+/// [`ClientSettings::kind_cache_radius`]. Used for local queries, like interaction and collision,
+/// and to predict voxel updates before server confirms them.
+#[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
+struct ChunkKindClientCache(HashMap<Chunk, ChunkStorage<voxel::Kind>>);
+
 #[derive(Resource, Debug, Clone)]
 pub struct ChunkMaterialHandle(pub Handle<ChunkMaterial>);
 
@@ -197,15 +298,33 @@ fn any_chunk<T: QueryFilter>(q_changed_chunks: Query<(), (T, With<ChunkLocal>)>)
 #[derive(Debug, Resource)]
 pub struct KindsAtlasRes {
     pub atlas: Handle<Image>,
+    /// Asset path which [`KindsAtlasRes::atlas`] was loaded from.
+    pub path: String,
+    /// Tiles layout of atlas, which is updated whenever atlas is loaded, so it matches the atlas
+    /// resolution.
+    pub layout: Handle<TextureAtlasLayout>,
 }
 
-fn load_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let kinds_path = format!("{}{}", env!("ASSETS_PATH"), "/voxels/kind.ron");
-    let descs = voxel::KindsDescs::init(kinds_path);
+fn load_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<ClientSettings>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let descs = voxel::KindsDescs::init(voxel::KindsDescs::default_path());
 
-    let atlas = asset_server.load(&descs.atlas_path);
+    let path = settings.atlas_path(descs).to_string();
+    let atlas = asset_server.load(&path);
+    let layout = layouts.add(atlas::tiles_layout(
+        UVec2::splat(descs.atlas_size as u32),
+        descs.count_tiles(),
+    ));
 
-    commands.insert_resource(KindsAtlasRes { atlas });
+    commands.insert_resource(KindsAtlasRes {
+        atlas,
+        path,
+        layout,
+    });
 }
 
 fn setup_material(
@@ -215,13 +334,41 @@ fn setup_material(
 ) {
     let material = materials.add(ChunkMaterial {
         texture: kinds_res.atlas.clone(),
+        // Atlas is sliced once it is loaded.
+        texture_array: None,
         tile_texture_size: 1.0 / voxel::KindsDescs::get().count_tiles() as f32,
+        fog_color: Color::NONE,
+        fog_near: 0.0,
+        fog_far: 0.0,
+        natural_light_scale: 1.0,
+        shadow_strength: 0.0,
+        flow: Vec2::ZERO,
+        opacity: 1.0,
         show_back_faces: false,
     });
 
     commands.insert_resource(ChunkMaterialHandle(material));
 }
 
+/// Keeps fog matched to render distance, so chunks being loaded and unloaded at landscape edge fade
+/// into the clear color instead of popping in.
+fn update_material_fog(
+    settings: Res<ClientSettings>,
+    clear_color: Res<ClearColor>,
+    material: Res<ChunkMaterialHandle>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    let Some(material) = materials.get_mut(&material.0) else {
+        return;
+    };
+
+    let fog_far = (settings.render_distance as usize * chunk::X_AXIS_SIZE) as f32;
+
+    material.fog_color = clear_color.0;
+    material.fog_far = fog_far;
+    material.fog_near = fog_far * settings.graphics.fog_near_ratio();
+}
+
 fn remove_unloaded_chunks(
     mut commands: Commands,
     mut map: ResMut<ChunkMap>,
@@ -0,0 +1,153 @@
+//! Liquids, like water and lava, are drawn as a translucent mesh per chunk, apart from terrain,
+//! using a copy of terrain material which scrolls its texture.
+
+use bevy::{pbr::NotShadowCaster, prelude::*, utils::HashMap};
+use projekto_core::chunk::{self, Chunk};
+use projekto_messages::{ChunkLiquidVertex, Teleport};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{
+    material::ChunkMaterial, net::ServerDisconnected, set::generate_mesh, ChunkMaterialHandle,
+    PlayerLandscape,
+};
+
+/// Texture scroll speed of liquids, in tiles per second.
+const LIQUID_FLOW: Vec2 = Vec2::new(0.05, 0.1);
+const LIQUID_OPACITY: f32 = 0.8;
+
+pub(crate) struct LiquidPlugin;
+
+impl Plugin for LiquidPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkLiquidMap>()
+            .set_message_handler(update_chunk_liquid)
+            .add_message_handler(clear_liquids_on_teleport)
+            .add_systems(
+                Update,
+                (
+                    clear_liquids_on_server_disconnect.run_if(on_event::<ServerDisconnected>()),
+                    sync_liquid_material.run_if(resource_exists::<ChunkMaterialHandle>),
+                ),
+            );
+    }
+}
+
+/// Material of liquid meshes, which is kept in sync with terrain material.
+#[derive(Resource, Debug, Clone)]
+struct LiquidMaterialHandle(Handle<ChunkMaterial>);
+
+/// Liquid mesh entity of each chunk.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+struct ChunkLiquidMap(HashMap<Chunk, Entity>);
+
+impl ChunkLiquidMap {
+    fn despawn_all(&mut self, commands: &mut Commands) {
+        for (_, entity) in self.drain() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Copies terrain material whenever it changes, like fog and day light, so liquids look the same,
+/// except for flow and opacity.
+fn sync_liquid_material(
+    mut commands: Commands,
+    mut reader: EventReader<AssetEvent<ChunkMaterial>>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+    terrain: Res<ChunkMaterialHandle>,
+    liquid: Option<Res<LiquidMaterialHandle>>,
+) {
+    let terrain_changed = reader
+        .read()
+        .any(|evt| evt.is_added(&terrain.0) || evt.is_modified(&terrain.0));
+
+    if !terrain_changed && liquid.is_some() {
+        return;
+    }
+
+    let Some(terrain) = materials.get(&terrain.0) else {
+        return;
+    };
+
+    let material = ChunkMaterial {
+        flow: LIQUID_FLOW,
+        opacity: LIQUID_OPACITY,
+        // Liquid surface must be seen from below too, like when swimming.
+        show_back_faces: true,
+        ..terrain.clone()
+    };
+
+    match liquid {
+        Some(liquid) => {
+            if let Some(existing) = materials.get_mut(&liquid.0) {
+                *existing = material;
+            }
+        }
+        None => commands.insert_resource(LiquidMaterialHandle(materials.add(material))),
+    }
+}
+
+fn update_chunk_liquid(
+    In(ChunkLiquidVertex { chunk, vertex }): In<ChunkLiquidVertex>,
+    mut commands: Commands,
+    mut map: ResMut<ChunkLiquidMap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material: Option<Res<LiquidMaterialHandle>>,
+    landscape: Res<PlayerLandscape>,
+) {
+    let radius = landscape.radius as i32;
+    map.retain(|&other, entity| {
+        let keep = other.distance(landscape.center.into()).abs().max_element() <= radius;
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+
+    // Liquid vertices are always sent as a whole, so older ones are replaced.
+    if let Some(entity) = map.remove(&chunk) {
+        commands.entity(entity).despawn();
+    }
+
+    if vertex.is_empty() {
+        return;
+    }
+
+    let Some(material) = material else {
+        warn!("Liquid material not ready. Skipping chunk {chunk} liquid.");
+        return;
+    };
+
+    let entity = commands
+        .spawn((
+            MaterialMeshBundle {
+                mesh: meshes.add(generate_mesh(&vertex)),
+                material: material.0.clone(),
+                transform: Transform::from_translation(chunk::to_world(chunk)),
+                ..Default::default()
+            },
+            NotShadowCaster,
+            Name::new(format!("Liquid {chunk}")),
+        ))
+        .id();
+
+    map.insert(chunk, entity);
+    trace!("[update_chunk_liquid] chunk {chunk:?} liquid updated");
+}
+
+fn clear_liquids_on_teleport(
+    In(_): In<Teleport>,
+    mut commands: Commands,
+    mut map: ResMut<ChunkLiquidMap>,
+) {
+    map.despawn_all(&mut commands);
+}
+
+fn clear_liquids_on_server_disconnect(
+    mut commands: Commands,
+    mut map: ResMut<ChunkLiquidMap>,
+    mut reader: EventReader<ServerDisconnected>,
+) {
+    reader.clear();
+    map.despawn_all(&mut commands);
+}
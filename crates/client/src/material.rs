@@ -4,33 +4,88 @@ use bevy::{
     pbr::MaterialPipeline,
     render::{
         mesh::MeshVertexAttribute,
-        render_resource::{AsBindGroup, Face, ShaderRef, ShaderType, VertexFormat},
+        render_asset::RenderAssets,
+        render_resource::{
+            AsBindGroup, AsBindGroupShaderType, Face, ShaderRef, ShaderType, VertexFormat,
+        },
     },
 };
 
 #[derive(Reflect, AsBindGroup, Asset, Debug, Clone)]
-#[bind_group_data(bool)]
+#[bind_group_data(ChunkMaterialKey)]
+#[uniform(2, ChunkMaterialUniform)]
 pub struct ChunkMaterial {
     #[texture(0)]
     #[sampler(1)]
     pub texture: Handle<Image>,
-    #[uniform(2)]
+    /// Atlas sliced into a texture array, with a layer per tile, so tiles can be mipmapped without
+    /// bleeding into each other. When `None`, [`ChunkMaterial::texture`] atlas is used instead.
+    #[texture(3, dimension = "2d_array")]
+    #[sampler(4)]
+    pub texture_array: Option<Handle<Image>>,
     pub tile_texture_size: f32,
 
+    /// Color which chunks fade into, at distance.
+    pub fog_color: Color,
+    /// Horizontal distance from camera where fog starts.
+    pub fog_near: f32,
+    /// Horizontal distance from camera where chunks are fully covered by fog.
+    pub fog_far: f32,
+
+    /// Scale applied to voxel light, which dims the world at night. Vertex light doesn't tell
+    /// natural and artificial light apart, so the scale is applied to both.
+    pub natural_light_scale: f32,
+    /// How much voxel light is dimmed where sun light is shadowed. Zero disables shadows.
+    pub shadow_strength: f32,
+
+    /// Texture scroll speed, in tiles per second, which animates liquid surfaces. Zero on terrain.
+    pub flow: Vec2,
+    /// Opacity applied on texture alpha. Anything below one is alpha blended, like liquids.
+    pub opacity: f32,
+
     pub show_back_faces: bool,
 }
 
-impl From<&ChunkMaterial> for bool {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkMaterialKey {
+    show_back_faces: bool,
+    texture_array: bool,
+}
+
+impl From<&ChunkMaterial> for ChunkMaterialKey {
     fn from(value: &ChunkMaterial) -> Self {
-        value.show_back_faces
+        Self {
+            show_back_faces: value.show_back_faces,
+            texture_array: value.texture_array.is_some(),
+        }
     }
 }
 
 #[derive(ShaderType)]
-struct ChunkMaterialUniform {
+pub struct ChunkMaterialUniform {
     tile_texture_size: f32,
-    clip_map_origin: Vec2,
-    clip_height: f32,
+    fog_near: f32,
+    fog_far: f32,
+    natural_light_scale: f32,
+    fog_color: Vec4,
+    flow: Vec2,
+    opacity: f32,
+    shadow_strength: f32,
+}
+
+impl AsBindGroupShaderType<ChunkMaterialUniform> for ChunkMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> ChunkMaterialUniform {
+        ChunkMaterialUniform {
+            tile_texture_size: self.tile_texture_size,
+            fog_near: self.fog_near,
+            fog_far: self.fog_far,
+            natural_light_scale: self.natural_light_scale,
+            fog_color: self.fog_color.as_linear_rgba_f32().into(),
+            flow: self.flow,
+            opacity: self.opacity,
+            shadow_strength: self.shadow_strength,
+        }
+    }
 }
 
 impl ChunkMaterial {
@@ -50,9 +105,13 @@ impl Material for ChunkMaterial {
         "shaders/voxel.wgsl".into()
     }
 
-    // fn alpha_mode(&self) -> AlphaMode {
-    //     AlphaMode::Opaque
-    // }
+    fn alpha_mode(&self) -> AlphaMode {
+        if self.opacity < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        }
+    }
 
     fn specialize(
         _pipeline: &MaterialPipeline<Self>,
@@ -60,6 +119,15 @@ impl Material for ChunkMaterial {
         layout: &bevy::render::mesh::MeshVertexBufferLayout,
         _key: MaterialPipelineKey<Self>,
     ) -> Result<(), bevy::render::render_resource::SpecializedMeshPipelineError> {
+        // Shadow and prepass pipelines use Bevy prepass shader, which already has its own layout.
+        let is_prepass = descriptor
+            .vertex
+            .shader_defs
+            .contains(&"PREPASS_PIPELINE".into());
+        if is_prepass {
+            return Ok(());
+        }
+
         let vertex_layout = layout.get_layout(&[
             Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
             Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
@@ -69,14 +137,71 @@ impl Material for ChunkMaterial {
         ])?;
         descriptor.vertex.buffers = vec![vertex_layout];
 
-        let show_back_face = _key.bind_group_data;
+        let ChunkMaterialKey {
+            show_back_faces,
+            texture_array,
+        } = _key.bind_group_data;
 
-        if show_back_face {
+        if show_back_faces {
             descriptor.primitive.cull_mode = None;
         } else {
             descriptor.primitive.cull_mode = Some(Face::Back);
         }
 
+        if let Some(fragment) = descriptor.fragment.as_mut().filter(|_| texture_array) {
+            fragment.shader_defs.push("TEXTURE_ARRAY".into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Procedural gradient rendered on a dome around the camera, behind everything else.
+#[derive(Reflect, AsBindGroup, Asset, Debug, Clone)]
+#[uniform(0, SkyMaterialUniform)]
+pub struct SkyMaterial {
+    /// Sky color at the horizon, which is also the color chunks fade into.
+    pub horizon_color: Color,
+    /// Sky color right above the camera.
+    pub zenith_color: Color,
+}
+
+#[derive(ShaderType)]
+pub struct SkyMaterialUniform {
+    horizon_color: Vec4,
+    zenith_color: Vec4,
+}
+
+impl AsBindGroupShaderType<SkyMaterialUniform> for SkyMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> SkyMaterialUniform {
+        SkyMaterialUniform {
+            horizon_color: self.horizon_color.as_linear_rgba_f32().into(),
+            zenith_color: self.zenith_color.as_linear_rgba_f32().into(),
+        }
+    }
+}
+
+impl Material for SkyMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/sky.wgsl".into()
+    }
+
+    fn vertex_shader() -> ShaderRef {
+        "shaders/sky.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut bevy::render::render_resource::RenderPipelineDescriptor,
+        layout: &bevy::render::mesh::MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), bevy::render::render_resource::SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[Mesh::ATTRIBUTE_POSITION.at_shader_location(0)])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+
+        // Camera is always inside the dome.
+        descriptor.primitive.cull_mode = None;
+
         Ok(())
     }
 }
@@ -0,0 +1,133 @@
+//! On-disk cache of chunk meshes received from servers. When landscape is updated, server sends
+//! only the vertex hash of each chunk, so chunks which didn't change since last session are loaded
+//! locally and only the ones missing or outdated are requested.
+
+use std::path::PathBuf;
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    tasks::{IoTaskPool, TaskPool},
+};
+use projekto_core::{chunk::Chunk, voxel};
+use projekto_messages::{ChunkLoad, ChunkVertexHash};
+use projekto_proto::RegisterMessageHandler;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    net::{ServerAddress, ServerConnection},
+    set::PendingChunkMeshes,
+    ClientSettings,
+};
+
+/// Folder, relative to working directory, where chunk meshes are cached, one subfolder per server.
+const MESH_CACHE_PATH: &str = "cache/meshes";
+const MESH_CACHE_EXT: &str = "bin";
+
+pub(crate) struct MeshCachePlugin;
+
+impl Plugin for MeshCachePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message_handler(load_cached_chunk_mesh);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedChunkMesh {
+    hash: u64,
+    vertex: Vec<voxel::Vertex>,
+}
+
+/// Chunk meshes cache of the server currently connected to.
+#[derive(SystemParam)]
+pub(crate) struct MeshCache<'w> {
+    address: Res<'w, ServerAddress>,
+    settings: Res<'w, ClientSettings>,
+}
+
+impl<'w> MeshCache<'w> {
+    /// **Returns** the cache folder of current server, or `None` if cache is disabled.
+    fn dir(&self) -> Option<PathBuf> {
+        if !self.settings.mesh_cache {
+            return None;
+        }
+
+        // Server address may contain characters which aren't valid on paths, like `:`.
+        let server = self
+            .address
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        Some(PathBuf::from(MESH_CACHE_PATH).join(server))
+    }
+
+    fn file_name(chunk: Chunk) -> String {
+        format!("{}_{}.{MESH_CACHE_EXT}", chunk.x(), chunk.z())
+    }
+
+    /// **Returns** the cached vertices of the given chunk, only if its hash matches the given one.
+    pub fn load(&self, chunk: Chunk, hash: u64) -> Option<Vec<voxel::Vertex>> {
+        let path = self.dir()?.join(Self::file_name(chunk));
+        let compressed = std::fs::read(path).ok()?;
+
+        let cached = lz4_flex::decompress_size_prepended(&compressed)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| {
+                bincode::deserialize::<CachedChunkMesh>(&bytes).map_err(|err| err.to_string())
+            });
+
+        match cached {
+            Ok(cached) if cached.hash == hash => Some(cached.vertex),
+            Ok(_) => None,
+            Err(err) => {
+                warn!("Failed to load cached mesh of chunk {chunk}. Error: {err}");
+                None
+            }
+        }
+    }
+
+    /// Saves the given vertices on cache. Hashing and writing is done on IO task pool, since lots
+    /// of chunks are received at once when joining a server.
+    pub fn save(&self, chunk: Chunk, vertex: Vec<voxel::Vertex>) {
+        let Some(dir) = self.dir() else {
+            return;
+        };
+
+        IoTaskPool::get_or_init(TaskPool::default)
+            .spawn(async move {
+                let cached = CachedChunkMesh {
+                    hash: voxel::vertex_hash(&vertex),
+                    vertex,
+                };
+
+                let result = std::fs::create_dir_all(&dir)
+                    .map_err(|err| err.to_string())
+                    .and_then(|_| bincode::serialize(&cached).map_err(|err| err.to_string()))
+                    .and_then(|bytes| {
+                        let compressed = lz4_flex::compress_prepend_size(&bytes);
+                        std::fs::write(dir.join(Self::file_name(chunk)), compressed)
+                            .map_err(|err| err.to_string())
+                    });
+
+                if let Err(err) = result {
+                    warn!("Failed to cache mesh of chunk {chunk}. Error: {err}");
+                }
+            })
+            .detach();
+    }
+}
+
+fn load_cached_chunk_mesh(
+    In(ChunkVertexHash { chunk, hash }): In<ChunkVertexHash>,
+    cache: MeshCache,
+    server: Res<ServerConnection>,
+    mut pending: ResMut<PendingChunkMeshes>,
+) {
+    if let Some(vertex) = cache.load(chunk, hash) {
+        trace!("[load_cached_chunk_mesh] chunk {chunk:?} loaded from cache");
+        pending.insert(chunk, vertex);
+    } else {
+        let _ = server.channel().send(ChunkLoad { chunk });
+    }
+}
@@ -1,28 +1,105 @@
-use std::{
-    io,
-    time::{Duration, Instant},
-};
+use std::{io, time::Duration};
 
 use bevy::{
     prelude::*,
     tasks::{AsyncComputeTaskPool, Task, TaskPool},
+    time::common_conditions::on_timer,
 };
 use futures_lite::future::{block_on, poll_once};
-use projekto_messages::{ClientMessage, ServerMessage};
-use projekto_proto::{connect_to_server, ClientId, MessageType, Server};
+use projekto_messages::{Capabilities, ClientMessage, Hello, ServerMessage, ShuttingDown, Welcome};
+use projekto_proto::{
+    connect_to_server, ClientId, Compression, ConnectionConfig, MessageType,
+    RegisterMessageHandler, Server,
+};
+
+use crate::{ClientSettings, ClientState};
+
+/// Address used when there is no saved server address.
+pub const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1:11223";
 
 pub(crate) struct NetPlugin;
 
 impl Plugin for NetPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<ServerDisconnected>().add_systems(
-            PreUpdate,
-            (
-                reconnect_to_server.run_if(resource_exists::<ServerConnection>),
-                server_connection.run_if(not(resource_exists::<ServerConnection>)),
-                handle_messages.run_if(resource_exists::<ServerConnection>),
-            ),
-        );
+        app.add_event::<ServerDisconnected>()
+            .init_resource::<NetworkStats>()
+            .init_resource::<ServerAddress>()
+            .init_resource::<ConnectionError>()
+            .init_resource::<AgreedCapabilities>()
+            .set_message_handler(server_shutting_down)
+            .add_message_handler(server_welcome)
+            .add_systems(OnEnter(ClientState::Connecting), start_connection)
+            .add_systems(
+                PreUpdate,
+                (
+                    detect_disconnection.run_if(resource_exists::<ServerConnection>),
+                    poll_connection.run_if(resource_exists::<ConnectionTask>),
+                    update_network_stats
+                        .run_if(on_timer(Duration::from_secs(NETWORK_STATS_INTERVAL_SECS))),
+                    handle_messages.run_if(resource_exists::<ServerConnection>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// How often, in seconds, [`NetworkStats`] rates are sampled.
+const NETWORK_STATS_INTERVAL_SECS: u64 = 1;
+
+/// Network traffic of current server connection. Totals are reset on each new connection.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub struct NetworkStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent_per_sec: u64,
+    pub bytes_received_per_sec: u64,
+    pub messages_sent_per_sec: u64,
+    pub messages_received_per_sec: u64,
+    /// Messages received from server which weren't handled yet.
+    pub pending_messages: usize,
+    /// Most messages received from server waiting to be handled at once.
+    pub peak_pending_messages: usize,
+    /// Messages waiting to be sent to server.
+    pub pending_sends: usize,
+    /// Most messages waiting to be sent to server at once.
+    pub peak_pending_sends: usize,
+    /// Messages dropped or rejected because a channel was full.
+    pub overflowed_messages: u64,
+}
+
+/// Address of the server to connect to when entering [`ClientState::Connecting`].
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Deref, DerefMut)]
+pub struct ServerAddress(pub String);
+
+impl Default for ServerAddress {
+    fn default() -> Self {
+        Self(DEFAULT_SERVER_ADDRESS.to_string())
+    }
+}
+
+/// Why the last connection attempt failed or why the server connection was lost, if any.
+#[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
+pub struct ConnectionError(pub Option<String>);
+
+/// Capabilities agreed with server on handshake. Until server replies, default ones are assumed.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Deref)]
+pub struct AgreedCapabilities(pub Capabilities);
+
+/// **Returns** the capabilities this client asks server for. Compressing costs CPU time on
+/// browsers, so wasm clients ask for uncompressed messages.
+fn local_capabilities(settings: &ClientSettings) -> Capabilities {
+    let compression = if cfg!(target_arch = "wasm32") {
+        Compression::None
+    } else {
+        Compression::Lz4
+    };
+
+    Capabilities {
+        compression,
+        spectator: settings.spectator,
+        ..default()
     }
 }
 
@@ -38,54 +115,157 @@ impl ServerConnection {
     }
 }
 
-fn reconnect_to_server(
+/// Seconds before server shut down when client disconnects, so connection is closed cleanly by
+/// client, instead of being reset by server.
+const SHUTDOWN_MARGIN_SECS: f32 = 1.0;
+
+/// Server told it is going to shut down. See [`ShuttingDown`].
+#[derive(Resource, Debug)]
+struct ServerShuttingDown {
+    reason: String,
+    timer: Timer,
+}
+
+fn server_shutting_down(
+    In(ShuttingDown { reason, seconds }): In<ShuttingDown>,
+    mut commands: Commands,
+) {
+    warn!("Server is shutting down in {seconds}s. Reason: {reason}");
+
+    let seconds = (seconds - SHUTDOWN_MARGIN_SECS).max(0.0);
+    commands.insert_resource(ServerShuttingDown {
+        reason,
+        timer: Timer::from_seconds(seconds, TimerMode::Once),
+    });
+}
+
+fn server_welcome(
+    In(Welcome { capabilities }): In<Welcome>,
+    mut agreed: ResMut<AgreedCapabilities>,
+) {
+    debug!("Server agreed capabilities: {capabilities:?}");
+    agreed.0 = capabilities;
+}
+
+fn detect_disconnection(
+    time: Res<Time>,
     connection: Res<ServerConnection>,
+    shutting_down: Option<ResMut<ServerShuttingDown>>,
     mut writer: EventWriter<ServerDisconnected>,
+    mut error: ResMut<ConnectionError>,
+    mut next_state: ResMut<NextState<ClientState>>,
     mut commands: Commands,
 ) {
-    if !connection.is_active() {
-        info!("Server connected is broken. Reconnecting...");
-        commands.remove_resource::<ServerConnection>();
-        writer.send(ServerDisconnected);
-    }
+    let message = match shutting_down {
+        Some(mut shutting_down) => {
+            shutting_down.timer.tick(time.delta());
+            if connection.is_active() && !shutting_down.timer.finished() {
+                return;
+            }
+
+            info!("Server is shutting down. Returning to title screen...");
+            connection.channel().close();
+            commands.remove_resource::<ServerShuttingDown>();
+            format!("Server shut down: {}", shutting_down.reason)
+        }
+        None if connection.is_active() => return,
+        None => {
+            info!("Server connection is broken. Returning to title screen...");
+            "Disconnected from server".to_string()
+        }
+    };
+
+    commands.remove_resource::<ServerConnection>();
+    writer.send(ServerDisconnected);
+    error.0 = Some(message);
+    next_state.set(ClientState::Title);
 }
 
 type ConnectToServerResult = Result<Server<ClientMessage, ServerMessage>, io::Error>;
 
-struct Meta {
-    task: Option<Task<ConnectToServerResult>>,
-    next_try: Instant,
+#[derive(Resource)]
+struct ConnectionTask(Task<ConnectToServerResult>);
+
+fn start_connection(address: Res<ServerAddress>, mut commands: Commands) {
+    info!("Connecting to server at {}", address.0);
+
+    let address = address.0.clone();
+    let task = AsyncComputeTaskPool::get_or_init(TaskPool::default)
+        .spawn(async move { connect_to_server(address, ConnectionConfig::default()).await });
+
+    commands.insert_resource(ConnectionTask(task));
 }
 
-impl Default for Meta {
-    fn default() -> Self {
-        Self {
-            task: Default::default(),
-            next_try: Instant::now(),
+fn poll_connection(
+    mut task: ResMut<ConnectionTask>,
+    settings: Res<ClientSettings>,
+    mut error: ResMut<ConnectionError>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut commands: Commands,
+) {
+    let Some(result) = block_on(poll_once(&mut task.0)) else {
+        return;
+    };
+
+    commands.remove_resource::<ConnectionTask>();
+
+    match result {
+        Ok(server) => {
+            info!("Connected to server!");
+            let _ = server.channel().send(Hello {
+                capabilities: local_capabilities(&settings),
+            });
+            commands.insert_resource(AgreedCapabilities::default());
+            commands.insert_resource(ServerConnection(server));
+            error.0 = None;
+            next_state.set(ClientState::InGame);
+        }
+        Err(err) => {
+            error!("Failed to connect to server. Error: {err}");
+            error.0 = Some(format!("Failed to connect: {err}"));
+            next_state.set(ClientState::Title);
         }
     }
 }
 
-fn server_connection(mut commands: Commands, mut meta: Local<Meta>) {
-    if let Some(ref mut task) = meta.task {
-        if let Some(result) = block_on(poll_once(task)) {
-            match result {
-                Ok(server) => {
-                    info!("Connected to server!");
-                    commands.insert_resource(ServerConnection(server));
-                }
-                Err(err) => {
-                    error!("Failed to connect to server. Error: {err}");
-                    meta.next_try = Instant::now() + Duration::from_secs(1);
-                }
-            }
-            let _ = meta.task.take();
+fn update_network_stats(
+    connection: Option<Res<ServerConnection>>,
+    mut network_stats: ResMut<NetworkStats>,
+) {
+    let Some(connection) = connection else {
+        if *network_stats != NetworkStats::default() {
+            *network_stats = NetworkStats::default();
         }
-    } else if meta.next_try <= Instant::now() {
-        let task = AsyncComputeTaskPool::get_or_init(TaskPool::default)
-            .spawn(async move { connect_to_server("127.0.0.1:11223").await });
-        meta.task = Some(task);
-    }
+        return;
+    };
+
+    let stats = connection.stats();
+    let channel = connection.channel();
+    let (bytes_sent, bytes_received) = (stats.bytes_sent(), stats.bytes_received());
+    let (messages_sent, messages_received) = (stats.messages_sent(), stats.messages_received());
+
+    let rate = |current: u64, previous: u64| {
+        current.saturating_sub(previous) / NETWORK_STATS_INTERVAL_SECS
+    };
+
+    *network_stats = NetworkStats {
+        bytes_sent,
+        bytes_received,
+        messages_sent,
+        messages_received,
+        bytes_sent_per_sec: rate(bytes_sent, network_stats.bytes_sent),
+        bytes_received_per_sec: rate(bytes_received, network_stats.bytes_received),
+        messages_sent_per_sec: rate(messages_sent, network_stats.messages_sent),
+        messages_received_per_sec: rate(messages_received, network_stats.messages_received),
+        pending_messages: channel.len(),
+        peak_pending_messages: channel.received_stats().peak_len(),
+        pending_sends: channel.pending_sends(),
+        peak_pending_sends: channel.sent_stats().peak_len(),
+        overflowed_messages: [channel.sent_stats(), channel.received_stats()]
+            .iter()
+            .map(|stats| stats.dropped() + stats.rejected())
+            .sum(),
+    };
 }
 
 fn handle_messages(world: &mut World) {
@@ -0,0 +1,185 @@
+//! Estimates server clock from heartbeats round trip time and server ticks, so remote entities and
+//! effects predicted locally line up with server ticks.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use projekto_messages::{Heartbeat, HeartbeatReply, TimeSync, SERVER_TICK_MS};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{net::ServerConnection, ClientState};
+
+/// How often a heartbeat is sent to measure round trip time.
+const HEARTBEAT_INTERVAL_MS: u64 = 1000;
+/// Weight of each new sample on smoothed round trip time and clock offset.
+const SMOOTHING: f64 = 0.125;
+/// Clock offset errors larger than this, in seconds, are corrected at once instead of smoothed.
+const SNAP_THRESHOLD: f64 = 0.5;
+
+pub(crate) struct ServerTimePlugin;
+
+impl Plugin for ServerTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EstimatedServerTime>()
+            .add_message_handler(heartbeat_replied)
+            .add_message_handler(time_synced)
+            .add_systems(OnEnter(ClientState::Connecting), reset_server_time)
+            .add_systems(
+                PostUpdate,
+                send_heartbeat
+                    .run_if(resource_exists::<ServerConnection>)
+                    .run_if(on_timer(Duration::from_millis(HEARTBEAT_INTERVAL_MS))),
+            );
+    }
+}
+
+/// Server clock, as estimated by this client. Until the first sync, local time is used as is.
+///
+/// Local time is the elapsed time since startup, in seconds, like [`Time::elapsed_seconds_f64`].
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct EstimatedServerTime {
+    /// Smoothed round trip time, in seconds.
+    rtt: Option<f64>,
+    /// Server time minus local time, in seconds.
+    offset: Option<f64>,
+}
+
+impl EstimatedServerTime {
+    /// Duration of a server tick, in seconds.
+    pub const TICK_SECS: f64 = SERVER_TICK_MS as f64 / 1000.0;
+
+    /// Smoothed round trip time, or `None` until the first heartbeat is replied.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt.map(Duration::from_secs_f64)
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.offset.is_some()
+    }
+
+    /// **Returns** server time, in seconds since server started, at the given local time.
+    pub fn server_secs(&self, local: f64) -> f64 {
+        local + self.offset.unwrap_or_default()
+    }
+
+    /// **Returns** server tick at the given local time, with the elapsed fraction of that tick.
+    pub fn tick_at(&self, local: f64) -> f64 {
+        self.server_secs(local) / Self::TICK_SECS
+    }
+
+    /// **Returns** local time when the given server tick starts.
+    pub fn local_secs_of(&self, tick: u64) -> f64 {
+        tick as f64 * Self::TICK_SECS - self.offset.unwrap_or_default()
+    }
+
+    /// **Returns** the server tick a message sent at the given local time is going to be handled
+    /// on, which is the first tick after it arrives. Effects predicted locally should be scheduled
+    /// on it, so they line up with server.
+    pub fn arrival_tick(&self, local: f64) -> u64 {
+        let one_way = self.rtt.unwrap_or_default() / 2.0;
+        self.tick_at(local + one_way).floor() as u64 + 1
+    }
+
+    /// Adds a round trip time sample of a heartbeat sent at `sent_at` and replied on server `tick`,
+    /// which was received at `now`, local time.
+    pub fn add_heartbeat(&mut self, sent_at: f64, tick: u64, now: f64) {
+        let rtt = (now - sent_at).max(0.0);
+        self.rtt = Some(match self.rtt {
+            Some(smoothed) => smoothed + (rtt - smoothed) * SMOOTHING,
+            None => rtt,
+        });
+
+        self.sync(tick, now);
+    }
+
+    /// Syncs clock with a server `tick` received at `now`, local time. Tick was sent about half a
+    /// round trip ago, so it is accounted for.
+    pub fn sync(&mut self, tick: u64, now: f64) {
+        let one_way = self.rtt.unwrap_or_default() / 2.0;
+        let offset = tick as f64 * Self::TICK_SECS + one_way - now;
+
+        self.offset = Some(match self.offset {
+            Some(current) if (offset - current).abs() < SNAP_THRESHOLD => {
+                current + (offset - current) * SMOOTHING
+            }
+            _ => offset,
+        });
+    }
+}
+
+fn reset_server_time(mut server_time: ResMut<EstimatedServerTime>) {
+    *server_time = EstimatedServerTime::default();
+}
+
+fn send_heartbeat(time: Res<Time>, server: Res<ServerConnection>) {
+    let _ = server.channel().send(Heartbeat {
+        sent_at: time.elapsed_seconds_f64(),
+    });
+}
+
+fn heartbeat_replied(
+    In(HeartbeatReply { sent_at, tick }): In<HeartbeatReply>,
+    time: Res<Time>,
+    mut server_time: ResMut<EstimatedServerTime>,
+) {
+    server_time.add_heartbeat(sent_at, tick, time.elapsed_seconds_f64());
+}
+
+fn time_synced(
+    In(TimeSync { tick, .. }): In<TimeSync>,
+    time: Res<Time>,
+    mut server_time: ResMut<EstimatedServerTime>,
+) {
+    server_time.sync(tick, time.elapsed_seconds_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: f64 = EstimatedServerTime::TICK_SECS;
+
+    #[test]
+    fn unsynced_uses_local_time() {
+        let server_time = EstimatedServerTime::default();
+
+        assert!(!server_time.is_synced());
+        assert_eq!(server_time.rtt(), None);
+        assert_eq!(server_time.server_secs(12.5), 12.5);
+    }
+
+    #[test]
+    fn heartbeat_syncs_clock() {
+        // Server is at tick 1000 when a heartbeat sent at 10s is replied and it arrives at 10.1s
+        let mut server_time = EstimatedServerTime::default();
+        server_time.add_heartbeat(10.0, 1000, 10.1);
+
+        let rtt = server_time.rtt().map(|rtt| rtt.as_secs_f64());
+        assert!(rtt.is_some_and(|rtt| (rtt - 0.1).abs() < 1e-6));
+
+        // Tick was replied half a round trip ago
+        let expected = 1000.0 * TICK + 0.05;
+        assert!((server_time.server_secs(10.1) - expected).abs() < 1e-9);
+        assert!((server_time.local_secs_of(1000) - 10.05).abs() < 1e-9);
+
+        // Message sent at 10.12s arrives on server 50ms later, during tick 1002, so it is handled
+        // on next one
+        assert_eq!(server_time.arrival_tick(10.12), 1003);
+    }
+
+    #[test]
+    fn sync_is_smoothed() {
+        let mut server_time = EstimatedServerTime::default();
+        server_time.sync(100, 1.0);
+        let offset = server_time.server_secs(0.0);
+
+        // A late tick, like one delayed by jitter, only moves clock a bit
+        server_time.sync(100, 1.1);
+        let smoothed = server_time.server_secs(0.0);
+        assert!(smoothed < offset && smoothed > offset - 0.1);
+
+        // A huge error, like after a server restart, is corrected at once
+        server_time.sync(10, 1.0);
+        assert!((server_time.server_secs(1.0) - 10.0 * TICK).abs() < 1e-9);
+    }
+}
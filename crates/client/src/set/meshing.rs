@@ -1,85 +1,310 @@
 use bevy::{
     prelude::*,
     render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
+    utils::HashMap,
 };
-use projekto_core::{chunk, voxel};
-use projekto_messages::ChunkVertex;
+use projekto_core::{
+    chunk::{self, Chunk},
+    voxel,
+};
+use projekto_messages::{ChunkVertex, ChunkVertexPart};
 use projekto_proto::RegisterMessageHandler;
 
 use crate::{
-    bundle::ChunkLocal, material::ChunkMaterial, net::ServerDisconnected, ChunkBundle, ChunkMap,
-    ChunkMaterialHandle,
+    bundle::ChunkLocal,
+    material::ChunkMaterial,
+    mesh_cache::MeshCache,
+    net::{ServerConnection, ServerDisconnected},
+    ChunkBundle, ChunkMap, ChunkMaterialHandle, ClientSet, ClientSettings, PlayerLandscape,
 };
 
 pub(crate) struct MeshingPlugin;
 
 impl Plugin for MeshingPlugin {
     fn build(&self, app: &mut App) {
-        app.set_message_handler(update_chunk_mesh).add_systems(
-            Update,
-            despawn_chunks_on_server_disconnect.run_if(on_event::<ServerDisconnected>()),
-        );
+        app.init_resource::<ChunkMeshBudget>()
+            .init_resource::<PendingChunkMeshes>()
+            .init_resource::<ChunkContentHashes>()
+            .init_resource::<ChunkVertexParts>()
+            .set_message_handler(queue_chunk_mesh)
+            .set_message_handler(queue_chunk_vertex_part)
+            .add_systems(
+                Update,
+                (
+                    despawn_chunks_on_server_disconnect.run_if(on_event::<ServerDisconnected>()),
+                    (
+                        build_chunk_meshes.run_if(resource_exists::<ServerConnection>),
+                        animate_chunk_appear,
+                    )
+                        .chain()
+                        .in_set(ClientSet::Meshing),
+                ),
+            );
+    }
+}
+
+/// Max number of chunk meshes built per frame, to avoid hitches when lots of chunks arrive at once.
+#[derive(Resource, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct ChunkMeshBudget(pub usize);
+
+impl Default for ChunkMeshBudget {
+    fn default() -> Self {
+        Self(8)
+    }
+}
+
+/// How long, in seconds, a newly spawned chunk takes to rise into place.
+const CHUNK_APPEAR_SECS: f32 = 0.3;
+/// How far below its final position a newly spawned chunk starts rising from.
+const CHUNK_APPEAR_DEPTH: f32 = 8.0;
+
+/// Rises a newly spawned chunk into place, to mask chunks popping in at landscape edge.
+#[derive(Component, Debug)]
+struct ChunkAppear(Timer);
+
+impl Default for ChunkAppear {
+    fn default() -> Self {
+        Self(Timer::from_seconds(CHUNK_APPEAR_SECS, TimerMode::Once))
+    }
+}
+
+/// Vertices received from server which weren't turned into meshes yet. Only the latest vertices of
+/// each chunk are kept.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub(crate) struct PendingChunkMeshes(HashMap<Chunk, Vec<voxel::Vertex>>);
+
+/// Latest content hash of each chunk received from server, so it can be checked if a chunk
+/// actually changed. See [`chunk::content_hash`].
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub(crate) struct ChunkContentHashes(HashMap<Chunk, u64>);
+
+/// Parts of chunk vertices streamed by server which didn't arrive completely yet.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub(crate) struct ChunkVertexParts(HashMap<Chunk, PartialChunkVertex>);
+
+/// Vertex parts received so far of a chunk content.
+#[derive(Debug)]
+pub(crate) struct PartialChunkVertex {
+    hash: u64,
+    parts: Vec<Option<Vec<voxel::Vertex>>>,
+    /// Parts are meshed as they arrive, since chunk had no mesh when streaming started. Otherwise
+    /// the current mesh is kept until all parts arrive, so it doesn't get holes meanwhile.
+    progressive: bool,
+}
+
+impl PartialChunkVertex {
+    fn new(hash: u64, total: u16, progressive: bool) -> Self {
+        Self {
+            hash,
+            parts: vec![None; total as usize],
+            progressive,
+        }
+    }
+
+    /// Adds the given part. Parts of another content, like when chunk changed while streaming,
+    /// replace all parts received so far.
+    fn add(&mut self, part: ChunkVertexPart) {
+        if part.hash != self.hash || part.total as usize != self.parts.len() {
+            *self = Self::new(part.hash, part.total, self.progressive);
+        }
+
+        if let Some(slot) = self.parts.get_mut(part.part as usize) {
+            *slot = Some(part.data);
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.parts.iter().all(Option::is_some)
+    }
+
+    /// **Returns** vertices of all parts received so far, in part order.
+    fn vertex(&self) -> Vec<voxel::Vertex> {
+        self.parts.iter().flatten().flatten().copied().collect()
     }
 }
 
 fn despawn_chunks_on_server_disconnect(
     mut map: ResMut<ChunkMap>,
+    mut pending: ResMut<PendingChunkMeshes>,
+    mut hashes: ResMut<ChunkContentHashes>,
+    mut parts: ResMut<ChunkVertexParts>,
     mut reader: EventReader<ServerDisconnected>,
     mut commands: Commands,
 ) {
     reader.clear();
+    pending.clear();
+    hashes.clear();
+    parts.clear();
     for (_, entity) in map.drain() {
         commands.entity(entity).despawn();
     }
 }
 
-fn update_chunk_mesh(
+fn queue_chunk_mesh(
     In(vertex): In<ChunkVertex>,
+    mut pending: ResMut<PendingChunkMeshes>,
+    mut hashes: ResMut<ChunkContentHashes>,
+    mut parts: ResMut<ChunkVertexParts>,
+    cache: MeshCache,
+) {
+    let ChunkVertex {
+        chunk,
+        hash,
+        vertex,
+    } = vertex;
+    // Whole vertices are newer than any part still being streamed.
+    parts.remove(&chunk);
+    hashes.insert(chunk, hash);
+    cache.save(chunk, vertex.clone());
+    pending.insert(chunk, vertex);
+}
+
+fn queue_chunk_vertex_part(
+    In(part): In<ChunkVertexPart>,
+    mut pending: ResMut<PendingChunkMeshes>,
+    mut hashes: ResMut<ChunkContentHashes>,
+    mut parts: ResMut<ChunkVertexParts>,
+    map: Res<ChunkMap>,
+    cache: MeshCache,
+) {
+    let (chunk, hash) = (part.chunk, part.hash);
+    let partial = parts
+        .entry(chunk)
+        .or_insert_with(|| PartialChunkVertex::new(hash, part.total, !map.contains_key(&chunk)));
+    partial.add(part);
+
+    if partial.is_complete() {
+        let vertex = partial.vertex();
+        parts.remove(&chunk);
+
+        trace!("[queue_chunk_vertex_part] chunk {chunk:?} vertices completely received");
+
+        hashes.insert(chunk, hash);
+        cache.save(chunk, vertex.clone());
+        pending.insert(chunk, vertex);
+    } else if partial.progressive {
+        pending.insert(chunk, partial.vertex());
+    }
+}
+
+fn build_chunk_meshes(
     mut commands: Commands,
     mut map: ResMut<ChunkMap>,
+    mut pending: ResMut<PendingChunkMeshes>,
     mut meshes: ResMut<Assets<Mesh>>,
     material: Res<ChunkMaterialHandle>,
+    (budget, landscape, settings): (
+        Res<ChunkMeshBudget>,
+        Res<PlayerLandscape>,
+        Res<ClientSettings>,
+    ),
+    q_mesh: Query<&Handle<Mesh>>,
 ) {
-    let ChunkVertex { chunk, vertex } = vertex;
-
-    let mesh_handler = meshes.add(generate_mesh(&vertex));
-
-    if let Some(&entity) = map.get(&chunk) {
-        commands.entity(entity).insert(mesh_handler);
-    } else {
-        let entity = commands
-            .spawn(ChunkBundle {
-                chunk: ChunkLocal(chunk),
-                mesh: MaterialMeshBundle {
-                    mesh: mesh_handler,
-                    transform: Transform::from_translation(chunk::to_world(chunk)),
-                    material: material.0.clone(),
-                    ..Default::default()
-                },
-            })
-            .insert(Name::new(format!("Client Chunk {}", chunk)))
-            .id();
-        map.insert(chunk, entity);
-    }
-
-    trace!("[update_chunk_mesh] chunk {chunk:?} mesh updated");
+    if pending.is_empty() {
+        return;
+    }
+
+    // Chunks closer to the player are built first.
+    let center: Chunk = landscape.center.into();
+    let mut chunks = pending.keys().copied().collect::<Vec<_>>();
+    chunks.sort_by_key(|chunk| chunk.distance(center).length_squared());
+
+    let ambient_occlusion = settings.graphics.ambient_occlusion();
+
+    for chunk in chunks.into_iter().take(**budget) {
+        let mut vertex = pending
+            .remove(&chunk)
+            .expect("Chunk was taken from pending keys");
+
+        if !ambient_occlusion {
+            flat_lighting(&mut vertex);
+        }
+
+        let mesh = generate_mesh(&vertex);
+
+        let existing_mesh = map
+            .get(&chunk)
+            .and_then(|&entity| q_mesh.get(entity).ok())
+            .and_then(|handle| meshes.get_mut(handle));
+
+        if let Some(existing_mesh) = existing_mesh {
+            // Reuse the same asset, so there is no need to allocate a new handle for each rebuild.
+            *existing_mesh = mesh;
+        } else if let Some(&entity) = map.get(&chunk) {
+            commands.entity(entity).insert(meshes.add(mesh));
+        } else {
+            let entity = commands
+                .spawn(ChunkBundle {
+                    chunk: ChunkLocal(chunk),
+                    mesh: MaterialMeshBundle {
+                        mesh: meshes.add(mesh),
+                        transform: Transform::from_translation(
+                            chunk::to_world(chunk) + Vec3::NEG_Y * CHUNK_APPEAR_DEPTH,
+                        ),
+                        material: material.0.clone(),
+                        ..Default::default()
+                    },
+                })
+                .insert((
+                    ChunkAppear::default(),
+                    Name::new(format!("Client Chunk {}", chunk)),
+                ))
+                .id();
+            map.insert(chunk, entity);
+        }
+
+        trace!("[build_chunk_meshes] chunk {chunk:?} mesh updated");
+    }
+}
+
+fn animate_chunk_appear(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q: Query<(Entity, &ChunkLocal, &mut Transform, &mut ChunkAppear)>,
+) {
+    for (entity, &ChunkLocal(chunk), mut transform, mut appear) in &mut q {
+        appear.0.tick(time.delta());
+
+        // Ease out cubic, so chunks slow down as they reach their place.
+        let t = 1.0 - (1.0 - appear.0.fraction()).powi(3);
+        transform.translation =
+            chunk::to_world(chunk) + Vec3::NEG_Y * CHUNK_APPEAR_DEPTH * (1.0 - t);
+
+        if appear.0.finished() {
+            commands.entity(entity).remove::<ChunkAppear>();
+        }
+    }
+}
+
+/// Lits each face with the light of its brightest vertex, which discards ambient occlusion and
+/// smooth lighting computed by server.
+///
+/// This function assumes 4 vertices per face, so it must be called before [`voxel::weld`].
+fn flat_lighting(vertices: &mut [voxel::Vertex]) {
+    for face in vertices.chunks_exact_mut(4) {
+        let light = face
+            .iter()
+            .fold(Vec3::ZERO, |light, vertex| light.max(vertex.light));
+        face.iter_mut().for_each(|vertex| vertex.light = light);
+    }
 }
 
-fn generate_mesh(vertices: &[voxel::Vertex]) -> Mesh {
+/// Builds an indexed mesh, welding vertices shared by neighbor faces.
+pub(crate) fn generate_mesh(vertices: &[voxel::Vertex]) -> Mesh {
     let mut mesh = Mesh::new(
         PrimitiveTopology::TriangleList,
         RenderAssetUsages::RENDER_WORLD,
     );
 
+    let welded = voxel::weld(vertices);
+
     let mut positions: Vec<[f32; 3]> = vec![];
     let mut normals: Vec<[f32; 3]> = vec![];
     let mut uvs: Vec<[f32; 2]> = vec![];
     let mut tile_coord_start: Vec<[f32; 2]> = vec![];
     let mut lights: Vec<[f32; 3]> = vec![];
 
-    let vertex_count = vertices.len();
-
-    for vertex in vertices {
+    for vertex in &welded.vertices {
         positions.push(vertex.position.into());
         normals.push(vertex.normal.into());
         uvs.push(vertex.uv.into());
@@ -87,7 +312,7 @@ fn generate_mesh(vertices: &[voxel::Vertex]) -> Mesh {
         lights.push(vertex.light.into());
     }
 
-    mesh.insert_indices(Indices::U32(compute_indices(vertex_count)));
+    mesh.insert_indices(Indices::U32(welded.indices));
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
@@ -96,34 +321,54 @@ fn generate_mesh(vertices: &[voxel::Vertex]) -> Mesh {
     mesh
 }
 
-/// Computes indices of a triangle list mesh.
-///
-/// This function assumes 4 vertices per face, 3 indices per triangles and all vertices are placed
-/// in CCW order.
-///
-/// It generates indices in the following order: _*0 1 2 2 3 0*_ where 0 is the first vertice and 3
-/// is the last one
-///
-/// Returns** a list of indices in the CCW order
-pub fn compute_indices(vertex_count: usize) -> Vec<u32> {
-    // Each 4 vertex is a voxel face and each voxel face has 6 indices, so we can multiply the
-    // vertex count by 1.5
-    let index_count = (vertex_count as f32 * 1.5) as usize;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(hash: u64, part: u16, total: u16, data: Vec<voxel::Vertex>) -> ChunkVertexPart {
+        ChunkVertexPart {
+            chunk: Chunk::new(0, 0),
+            hash,
+            part,
+            total,
+            data,
+        }
+    }
 
-    let mut res = vec![0; index_count];
-    let mut i = 0u32;
+    fn vertex(x: f32) -> voxel::Vertex {
+        voxel::Vertex {
+            position: Vec3::splat(x),
+            ..Default::default()
+        }
+    }
 
-    while i < vertex_count as u32 {
-        res.push(i);
-        res.push(i + 1);
-        res.push(i + 2);
+    #[test]
+    fn parts_are_reassembled_in_order() {
+        let mut partial = PartialChunkVertex::new(1, 3, true);
 
-        res.push(i + 2);
-        res.push(i + 3);
-        res.push(i);
+        partial.add(part(1, 2, 3, vec![vertex(2.0)]));
+        partial.add(part(1, 0, 3, vec![vertex(0.0)]));
+        assert!(!partial.is_complete());
+        assert_eq!(partial.vertex(), vec![vertex(0.0), vertex(2.0)]);
 
-        i += 4;
+        partial.add(part(1, 1, 3, vec![vertex(1.0)]));
+        assert!(partial.is_complete());
+        assert_eq!(
+            partial.vertex(),
+            vec![vertex(0.0), vertex(1.0), vertex(2.0)]
+        );
     }
 
-    res
+    #[test]
+    fn newer_content_replaces_parts() {
+        let mut partial = PartialChunkVertex::new(1, 2, false);
+        partial.add(part(1, 0, 2, vec![vertex(0.0)]));
+
+        // Chunk changed while streaming, so old parts are useless
+        partial.add(part(2, 1, 2, vec![vertex(5.0)]));
+
+        assert!(!partial.is_complete());
+        assert_eq!(partial.vertex(), vec![vertex(5.0)]);
+        assert!(!partial.progressive, "Meshing mode shouldn't change");
+    }
 }
@@ -1,9 +1,93 @@
 use bevy::prelude::*;
+use projekto_core::chunk::Chunk;
+use projekto_messages::{ChunkKind, Teleport};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{
+    controller::{
+        character_controller::{CharacterController, CharacterMotion},
+        interaction::PendingVoxelUpdates,
+    },
+    net::ServerDisconnected,
+    ChunkKindClientCache, ChunkMap, ClientSettings, PlayerLandscape,
+};
+
+use super::{ChunkContentHashes, ChunkVertexParts, PendingChunkMeshes};
 
 pub(crate) struct ReceiveMessagesPlugin;
 
 impl Plugin for ReceiveMessagesPlugin {
-    fn build(&self, _app: &mut App) {
-        //
+    fn build(&self, app: &mut App) {
+        app.set_message_handler(update_chunk_kind)
+            .add_message_handler(teleport_player)
+            .add_systems(
+                Update,
+                clear_chunk_kinds_on_server_disconnect.run_if(on_event::<ServerDisconnected>()),
+            );
     }
 }
+
+fn clear_chunk_kinds_on_server_disconnect(
+    mut kinds: ResMut<ChunkKindClientCache>,
+    mut reader: EventReader<ServerDisconnected>,
+) {
+    reader.clear();
+    kinds.clear();
+}
+
+fn update_chunk_kind(
+    In(ChunkKind { chunk, kind }): In<ChunkKind>,
+    mut kinds: ResMut<ChunkKindClientCache>,
+    mut pending: ResMut<PendingVoxelUpdates>,
+    landscape: Res<PlayerLandscape>,
+    settings: Res<ClientSettings>,
+) {
+    // Server kinds are authoritative, so any pending prediction on this chunk is settled.
+    pending.retain(|(pending_chunk, _), _| *pending_chunk != chunk);
+
+    let radius = settings.kind_cache_radius as i32;
+    kinds.retain(|&other, _| other.distance(landscape.center.into()).abs().max_element() <= radius);
+    kinds.insert(chunk, kind);
+
+    trace!("[update_chunk_kind] chunk {chunk:?} kinds updated");
+}
+
+/// Moves the player and discards all chunks, instead of waiting for them to be evicted, since the
+/// old landscape is probably far away. Landscape center is moved right away, so chunks around the
+/// destination are requested and meshed first.
+fn teleport_player(
+    In(Teleport { position }): In<Teleport>,
+    mut commands: Commands,
+    mut q_character: Query<
+        (&mut Transform, Option<&mut CharacterMotion>),
+        With<CharacterController>,
+    >,
+    mut landscape: ResMut<PlayerLandscape>,
+    (mut map, mut pending_meshes, mut hashes, mut vertex_parts): (
+        ResMut<ChunkMap>,
+        ResMut<PendingChunkMeshes>,
+        ResMut<ChunkContentHashes>,
+        ResMut<ChunkVertexParts>,
+    ),
+    (mut kinds, mut pending_updates): (ResMut<ChunkKindClientCache>, ResMut<PendingVoxelUpdates>),
+) {
+    debug!("[teleport_player] Teleporting to {position}");
+
+    if let Ok((mut transform, motion)) = q_character.get_single_mut() {
+        transform.translation = position;
+        if let Some(mut motion) = motion {
+            motion.velocity = Vec3::ZERO;
+        }
+    }
+
+    for (_, entity) in map.drain() {
+        commands.entity(entity).despawn();
+    }
+    pending_meshes.clear();
+    hashes.clear();
+    vertex_parts.clear();
+    kinds.clear();
+    pending_updates.clear();
+
+    landscape.center = Chunk::from(position).xz();
+}
@@ -1,6 +1,14 @@
-use bevy::prelude::*;
+use std::time::Duration;
 
-use crate::{net::ServerConnection, ClientSet};
+use bevy::{prelude::*, time::common_conditions::on_timer};
+
+use crate::{
+    controller::character_controller::CharacterController, net::ServerConnection, ClientSet,
+    ClientSettings,
+};
+
+/// How often local player transform is sent to server.
+const PLAYER_TRANSFORM_INTERVAL_MS: u64 = 50;
 
 pub(crate) struct SendInputPlugin;
 
@@ -15,6 +23,10 @@ impl Plugin for SendInputPlugin {
             (
                 update_player_landscape.run_if(resource_changed::<PlayerLandscape>),
                 send_welcome_message.run_if(resource_added::<ServerConnection>),
+                subscribe_chunk_kinds,
+                send_player_transform.run_if(on_timer(Duration::from_millis(
+                    PLAYER_TRANSFORM_INTERVAL_MS,
+                ))),
             )
                 .in_set(ClientSet::SendInput),
         );
@@ -40,3 +52,33 @@ fn send_welcome_message(server: Res<ServerConnection>, landscape: Res<PlayerLand
         .channel()
         .send(projekto_messages::LandscapeUpdate { center, radius });
 }
+
+fn subscribe_chunk_kinds(
+    server: Res<ServerConnection>,
+    settings: Res<ClientSettings>,
+    mut subscribed_radius: Local<Option<u8>>,
+) {
+    let radius = settings.kind_cache_radius;
+    if !server.is_added() && *subscribed_radius == Some(radius) {
+        return;
+    }
+
+    *subscribed_radius = Some(radius);
+    let _ = server
+        .channel()
+        .send(projekto_messages::ChunkKindSubscribe { radius });
+}
+
+fn send_player_transform(
+    server: Res<ServerConnection>,
+    q: Query<&Transform, (With<CharacterController>, Changed<Transform>)>,
+) {
+    let Ok(transform) = q.get_single() else {
+        return;
+    };
+
+    let _ = server.channel().send(projekto_messages::PlayerTransform {
+        position: transform.translation,
+        rotation: transform.rotation,
+    });
+}
@@ -0,0 +1,315 @@
+use std::path::Path;
+
+use bevy::{prelude::*, window::PresentMode};
+use projekto_camera::{
+    first_person::FirstPersonCameraConfig, fly_by::FlyByCameraConfig, rts::RtsCameraConfig,
+};
+use projekto_core::voxel;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input::{GamepadConfig, InputMap},
+    net::DEFAULT_SERVER_ADDRESS,
+    InputAction, PlayerLandscape,
+};
+
+/// File, relative to working directory, where [`ClientSettings`] are persisted.
+const SETTINGS_PATH: &str = "settings.ron";
+
+pub(crate) struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ClientSettings>() {
+            app.insert_resource(ClientSettings::load());
+        }
+
+        app.add_systems(
+            Update,
+            (
+                apply_settings.run_if(resource_changed::<ClientSettings>),
+                apply_camera_fov,
+                save_settings.run_if(
+                    resource_changed::<ClientSettings>
+                        .and_then(not(resource_added::<ClientSettings>)),
+                ),
+            ),
+        );
+    }
+}
+
+/// User settings which are persisted between sessions.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientSettings {
+    /// Radius, in chunks, of the landscape around the player.
+    pub render_distance: u8,
+    /// Radius, in chunks, of the landscape around the player which voxel kinds are kept on client.
+    /// Zero disables it, which also disables voxel interaction and character collision.
+    pub kind_cache_radius: u8,
+    pub vsync: bool,
+    /// Vertical field of view, in degrees.
+    pub fov: f32,
+    /// Bindings of all input actions, used by controllers and debug systems.
+    pub input: InputMap,
+    /// Gamepad sticks used to move and look around.
+    pub gamepad: GamepadConfig,
+    /// Addresses of servers connected before, from the most recent to the oldest.
+    pub servers: Vec<String>,
+    /// Asset path of a texture atlas used instead of the one on kinds descriptions. It must have
+    /// the same tile layout, but may have any resolution.
+    pub texture_pack: Option<String>,
+    /// Show other players on minimap.
+    pub minimap_players: bool,
+    /// Keep chunk meshes received from servers on disk, so they aren't downloaded again when
+    /// rejoining.
+    pub mesh_cache: bool,
+    pub graphics: GraphicsPreset,
+    /// Terrain casts shadows from the sun.
+    pub shadows: bool,
+    /// Smooth first person camera height when climbing steps.
+    pub camera_smoothing: bool,
+    /// Bob first person camera while walking.
+    pub head_bob: bool,
+    /// Widen first person camera field of view while sprinting.
+    pub fov_kick: bool,
+    /// Join servers as a spectator, which receives the world but can't edit it, like map viewers
+    /// and screenshot bots.
+    pub spectator: bool,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            render_distance: 32,
+            kind_cache_radius: 2,
+            vsync: false,
+            fov: 45.0,
+            input: Default::default(),
+            gamepad: Default::default(),
+            servers: vec![DEFAULT_SERVER_ADDRESS.to_string()],
+            texture_pack: None,
+            minimap_players: true,
+            mesh_cache: true,
+            graphics: GraphicsPreset::default(),
+            shadows: true,
+            camera_smoothing: true,
+            head_bob: true,
+            fov_kick: true,
+            spectator: false,
+        }
+    }
+}
+
+impl ClientSettings {
+    pub const MIN_RENDER_DISTANCE: u8 = 2;
+    pub const MAX_RENDER_DISTANCE: u8 = 64;
+    pub const MIN_FOV: f32 = 30.0;
+    pub const MAX_FOV: f32 = 120.0;
+    pub const MAX_SAVED_SERVERS: usize = 5;
+
+    /// Loads settings from [`SETTINGS_PATH`], falling back to default settings if the file doesn't
+    /// exists or is invalid.
+    pub fn load() -> Self {
+        Self::load_from(SETTINGS_PATH)
+    }
+
+    fn load_from(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        let Ok(content) = std::fs::read_to_string(path) else {
+            debug!("No settings found at {path:?}. Using default settings.");
+            return Self::default();
+        };
+
+        match ron::from_str::<ClientSettings>(&content) {
+            Ok(settings) => ClientSettings {
+                input: settings.input.with_defaults(),
+                ..settings
+            }
+            .clamped(),
+            Err(err) => {
+                warn!("Failed to parse settings at {path:?}. Using default settings. Error: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Saves settings to [`SETTINGS_PATH`].
+    pub fn save(&self) {
+        self.save_to(SETTINGS_PATH);
+    }
+
+    fn save_to(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        let result = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|content| std::fs::write(path, content).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            error!("Failed to save settings at {path:?}. Error: {err}");
+        }
+    }
+
+    /// **Returns** a copy of current settings with all values within valid ranges.
+    pub fn clamped(self) -> Self {
+        Self {
+            render_distance: self
+                .render_distance
+                .clamp(Self::MIN_RENDER_DISTANCE, Self::MAX_RENDER_DISTANCE),
+            kind_cache_radius: self.kind_cache_radius.min(self.render_distance),
+            fov: self.fov.clamp(Self::MIN_FOV, Self::MAX_FOV),
+            ..self
+        }
+    }
+
+    /// **Returns** the asset path of texture atlas, which is either the texture pack or the atlas
+    /// described on kinds descriptions.
+    pub fn atlas_path<'a>(&'a self, descs: &'a voxel::KindsDescs) -> &'a str {
+        self.texture_pack.as_deref().unwrap_or(&descs.atlas_path)
+    }
+
+    /// Switches to the given graphics preset, also changing render distance to the one of preset.
+    pub fn set_graphics_preset(&mut self, preset: GraphicsPreset) {
+        self.graphics = preset;
+        self.render_distance = preset.render_distance();
+        self.kind_cache_radius = self.kind_cache_radius.min(self.render_distance);
+    }
+
+    /// Moves the given server address to the top of saved servers, dropping the oldest ones.
+    pub fn remember_server(&mut self, address: &str) {
+        self.servers.retain(|saved| saved != address);
+        self.servers.insert(0, address.to_string());
+        self.servers.truncate(Self::MAX_SAVED_SERVERS);
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+}
+
+/// Graphics quality presets, which trade visual quality for performance.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GraphicsPreset {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl GraphicsPreset {
+    /// Render distance set when this preset is picked. It may be changed afterwards.
+    pub fn render_distance(self) -> u8 {
+        match self {
+            GraphicsPreset::Low => 8,
+            GraphicsPreset::Medium => 16,
+            GraphicsPreset::High => 32,
+        }
+    }
+
+    /// Where fog starts, relative to render distance. Lower presets have shorter render distance,
+    /// so fog starts sooner to hide chunks popping in.
+    pub fn fog_near_ratio(self) -> f32 {
+        match self {
+            GraphicsPreset::Low => 0.5,
+            GraphicsPreset::Medium => 0.6,
+            GraphicsPreset::High => 0.7,
+        }
+    }
+
+    /// Smooth lighting and ambient occlusion on chunk meshes. When disabled, each face is lit by
+    /// its brightest corner.
+    pub fn ambient_occlusion(self) -> bool {
+        self != GraphicsPreset::Low
+    }
+
+    /// Linear filtering when textures are minified, which reduces shimmering at distance.
+    pub fn linear_filtering(self) -> bool {
+        self == GraphicsPreset::High
+    }
+}
+
+fn apply_settings(
+    settings: Res<ClientSettings>,
+    mut landscape: ResMut<PlayerLandscape>,
+    mut q_window: Query<&mut Window>,
+    (mut flyby_config, mut rts_config, mut fp_config): (
+        ResMut<FlyByCameraConfig>,
+        ResMut<RtsCameraConfig>,
+        ResMut<FirstPersonCameraConfig>,
+    ),
+    mut ambient_occlusion: Local<Option<bool>>,
+) {
+    if landscape.radius != settings.render_distance {
+        landscape.radius = settings.render_distance;
+    }
+
+    // Meshes are built with or without ambient occlusion, so request all chunks again, which will
+    // be remeshed when received.
+    let ao = settings.graphics.ambient_occlusion();
+    if ambient_occlusion
+        .replace(ao)
+        .is_some_and(|previous| previous != ao)
+    {
+        landscape.set_changed();
+    }
+
+    fp_config.smoothing = settings.camera_smoothing;
+    fp_config.head_bob = settings.head_bob;
+    fp_config.fov_kick = settings.fov_kick;
+
+    let present_mode = settings.present_mode();
+    for mut window in &mut q_window {
+        if window.present_mode != present_mode {
+            window.present_mode = present_mode;
+        }
+    }
+
+    // Fly by and RTS cameras are on their own crate, so they only support a single key per action.
+    let input = &settings.input;
+    let flyby_bindings = &mut flyby_config.bindings;
+    let rts_bindings = &mut rts_config.bindings;
+    for (action, binding) in [
+        (InputAction::Forward, &mut flyby_bindings.forward),
+        (InputAction::Backward, &mut flyby_bindings.backward),
+        (InputAction::Left, &mut flyby_bindings.left),
+        (InputAction::Right, &mut flyby_bindings.right),
+        (InputAction::Up, &mut flyby_bindings.up),
+        (InputAction::Down, &mut flyby_bindings.down),
+        (InputAction::Boost, &mut flyby_bindings.boost),
+        (InputAction::Forward, &mut rts_bindings.forward),
+        (InputAction::Backward, &mut rts_bindings.backward),
+        (InputAction::Left, &mut rts_bindings.left),
+        (InputAction::Right, &mut rts_bindings.right),
+    ] {
+        if let Some(key) = input.key(action) {
+            *binding = key;
+        }
+    }
+}
+
+fn apply_camera_fov(
+    settings: Res<ClientSettings>,
+    mut q: Query<&mut Projection, With<Camera3d>>,
+    q_added: Query<(), Added<Camera3d>>,
+) {
+    if !settings.is_changed() && q_added.is_empty() {
+        return;
+    }
+
+    let fov = settings.fov.to_radians();
+    for mut projection in &mut q {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov;
+        }
+    }
+}
+
+fn save_settings(settings: Res<ClientSettings>) {
+    settings.save();
+}
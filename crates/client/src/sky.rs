@@ -0,0 +1,237 @@
+//! Sky dome, sun and moon, which follow the world time synced from server.
+
+use std::time::Duration;
+
+use bevy::{
+    pbr::{light_consts, CascadeShadowConfigBuilder, NotShadowCaster},
+    prelude::*,
+    time::common_conditions::on_timer,
+};
+use projekto_core::time::WorldTime;
+use projekto_messages::TimeSync;
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{
+    material::{ChunkMaterial, SkyMaterial},
+    ChunkMaterialHandle, ClientSettings,
+};
+
+/// Radius of the sky dome, which must be within camera far plane.
+const SKY_RADIUS: f32 = 800.0;
+/// Distance from camera where sun and moon are placed, inside the sky dome.
+const SKY_BODY_DISTANCE: f32 = 700.0;
+const SKY_BODY_RADIUS: f32 = 30.0;
+/// How often sky colors and lighting are updated. Time of the day changes slowly, so there is no
+/// need to update materials every frame.
+const SKY_UPDATE_INTERVAL_MS: u64 = 100;
+/// Voxel light scale at midnight, so the world is never completely dark.
+const MIN_NATURAL_LIGHT_SCALE: f32 = 0.15;
+/// How much voxel light is dimmed on terrain shadowed from the sun, at noon.
+const MAX_SHADOW_STRENGTH: f32 = 0.4;
+/// Distance from camera where terrain stops casting sun shadows, which is shorter than render
+/// distance, so shadow maps keep enough resolution up close.
+const SUN_SHADOW_DISTANCE: f32 = 160.0;
+
+const DAY_ZENITH_COLOR: Color = Color::rgb(0.25, 0.45, 0.85);
+const DAY_HORIZON_COLOR: Color = Color::rgb(0.65, 0.8, 0.95);
+const NIGHT_ZENITH_COLOR: Color = Color::rgb(0.01, 0.01, 0.04);
+const NIGHT_HORIZON_COLOR: Color = Color::rgb(0.04, 0.05, 0.1);
+const TWILIGHT_HORIZON_COLOR: Color = Color::rgb(0.95, 0.45, 0.2);
+
+pub(crate) struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldTime>()
+            .register_type::<WorldTime>()
+            .add_plugins(MaterialPlugin::<SkyMaterial>::default())
+            .add_message_handler(sync_world_time)
+            .add_systems(Startup, setup_sky)
+            .add_systems(
+                Update,
+                (
+                    advance_world_time,
+                    update_sky.run_if(on_timer(Duration::from_millis(SKY_UPDATE_INTERVAL_MS))),
+                    apply_shadow_settings.run_if(resource_changed::<ClientSettings>),
+                    follow_camera,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn mix_color(a: Color, b: Color, t: f32) -> Color {
+    let a = Vec4::from(a.as_rgba_f32());
+    let b = Vec4::from(b.as_rgba_f32());
+    Color::rgba_from_array(a.lerp(b, t))
+}
+
+#[derive(Component, Debug)]
+struct SkyDome;
+
+#[derive(Component, Debug)]
+struct Sun;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum SkyBody {
+    Sun,
+    Moon,
+}
+
+fn sync_world_time(
+    In(TimeSync {
+        day_time,
+        day_length,
+        ..
+    }): In<TimeSync>,
+    mut world_time: ResMut<WorldTime>,
+) {
+    // Time is advanced by dividing by day length, so a bad sync would break it until the next one.
+    if !WorldTime::is_valid_day_length(day_length) || !day_time.is_finite() {
+        warn!("Ignoring time sync with invalid day time {day_time} or day length {day_length}.");
+        return;
+    }
+
+    world_time.day_time = day_time.rem_euclid(1.0);
+    world_time.day_length = day_length;
+}
+
+fn advance_world_time(time: Res<Time>, mut world_time: ResMut<WorldTime>) {
+    world_time.advance(time.delta_seconds());
+}
+
+fn setup_sky(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut sky_materials: ResMut<Assets<SkyMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        MaterialMeshBundle {
+            mesh: meshes.add(Sphere::new(SKY_RADIUS).mesh().uv(32, 16)),
+            material: sky_materials.add(SkyMaterial {
+                horizon_color: DAY_HORIZON_COLOR,
+                zenith_color: DAY_ZENITH_COLOR,
+            }),
+            ..Default::default()
+        },
+        NotShadowCaster,
+        SkyDome,
+        Name::new("SkyDome"),
+    ));
+
+    commands.spawn((
+        DirectionalLightBundle {
+            cascade_shadow_config: CascadeShadowConfigBuilder {
+                first_cascade_far_bound: SUN_SHADOW_DISTANCE / 8.0,
+                maximum_distance: SUN_SHADOW_DISTANCE,
+                ..Default::default()
+            }
+            .into(),
+            ..Default::default()
+        },
+        Sun,
+        Name::new("Sun"),
+    ));
+
+    let body_mesh = meshes.add(Sphere::new(SKY_BODY_RADIUS).mesh().ico(3).unwrap());
+    for (body, color) in [
+        (SkyBody::Sun, Color::rgb(1.0, 0.95, 0.7)),
+        (SkyBody::Moon, Color::rgb(0.8, 0.85, 0.95)),
+    ] {
+        commands.spawn((
+            PbrBundle {
+                mesh: body_mesh.clone(),
+                material: materials.add(StandardMaterial {
+                    base_color: color,
+                    unlit: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            NotShadowCaster,
+            body,
+            Name::new(format!("{body:?}")),
+        ));
+    }
+}
+
+fn update_sky(
+    world_time: Res<WorldTime>,
+    q_dome: Query<&Handle<SkyMaterial>, With<SkyDome>>,
+    mut q_sun: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+    mut sky_materials: ResMut<Assets<SkyMaterial>>,
+    mut chunk_materials: ResMut<Assets<ChunkMaterial>>,
+    chunk_material: Option<Res<ChunkMaterialHandle>>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    let daylight = world_time.daylight();
+    let twilight = world_time.twilight();
+
+    let zenith_color = mix_color(NIGHT_ZENITH_COLOR, DAY_ZENITH_COLOR, daylight);
+    let horizon_color = mix_color(
+        mix_color(NIGHT_HORIZON_COLOR, DAY_HORIZON_COLOR, daylight),
+        TWILIGHT_HORIZON_COLOR,
+        twilight * 0.6,
+    );
+
+    for handle in &q_dome {
+        if let Some(material) = sky_materials.get_mut(handle) {
+            material.horizon_color = horizon_color;
+            material.zenith_color = zenith_color;
+        }
+    }
+
+    // Chunks fade into the clear color, so it must match the horizon.
+    clear_color.0 = horizon_color;
+
+    let sun_dir = world_time.sun_dir();
+    for (mut light, mut transform) in &mut q_sun {
+        light.illuminance = light_consts::lux::AMBIENT_DAYLIGHT * daylight;
+        light.color = mix_color(Color::WHITE, TWILIGHT_HORIZON_COLOR, twilight * 0.5);
+        *transform = Transform::IDENTITY.looking_to(-sun_dir, Vec3::Y);
+    }
+
+    if let Some(material) = chunk_material.and_then(|handle| chunk_materials.get_mut(&handle.0)) {
+        material.natural_light_scale =
+            MIN_NATURAL_LIGHT_SCALE + (1.0 - MIN_NATURAL_LIGHT_SCALE) * daylight;
+        material.shadow_strength = MAX_SHADOW_STRENGTH * daylight;
+    }
+}
+
+fn apply_shadow_settings(
+    settings: Res<ClientSettings>,
+    mut q_sun: Query<&mut DirectionalLight, With<Sun>>,
+) {
+    for mut light in &mut q_sun {
+        if light.shadows_enabled != settings.shadows {
+            light.shadows_enabled = settings.shadows;
+        }
+    }
+}
+
+fn follow_camera(
+    world_time: Res<WorldTime>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut q_dome: Query<&mut Transform, With<SkyDome>>,
+    mut q_bodies: Query<(&SkyBody, &mut Transform), Without<SkyDome>>,
+) {
+    let Some((_, camera_transform)) = q_camera.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    let camera_position = camera_transform.translation();
+
+    for mut transform in &mut q_dome {
+        transform.translation = camera_position;
+    }
+
+    let sun_dir = world_time.sun_dir();
+    for (body, mut transform) in &mut q_bodies {
+        let dir = match body {
+            SkyBody::Sun => sun_dir,
+            SkyBody::Moon => -sun_dir,
+        };
+        transform.translation = camera_position + dir * SKY_BODY_DISTANCE;
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+
+use bevy::{input::InputSystem, prelude::*, window::ReceivedCharacter};
+use projekto_messages::{Chat, ChatBroadcast, EditCommand};
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{net::ServerConnection, ClientSettings, ClientState, InputAction};
+
+const FONT_SIZE: f32 = 16.0;
+/// Chat lines shown, at most. Older ones are discarded.
+const CHAT_LINES: usize = 8;
+
+pub(super) struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatBox>()
+            .set_message_handler(server_chat_broadcast)
+            .add_systems(Startup, setup_chat)
+            .add_systems(OnExit(ClientState::InGame), clear_chat)
+            .add_systems(
+                PreUpdate,
+                type_chat
+                    .after(InputSystem)
+                    .run_if(in_state(ClientState::InGame)),
+            )
+            .add_systems(Update, update_chat.run_if(resource_changed::<ChatBox>));
+    }
+}
+
+/// Last chat lines received and the text being typed, while chat is open.
+#[derive(Resource, Debug, Default)]
+struct ChatBox {
+    lines: VecDeque<String>,
+    typing: Option<String>,
+}
+
+impl ChatBox {
+    fn push(&mut self, line: String) {
+        if self.lines.len() == CHAT_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+}
+
+#[derive(Component)]
+struct ChatText;
+
+fn setup_chat(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(80.0),
+                    max_width: Val::Percent(40.0),
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            Name::new("Chat"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ),
+                ChatText,
+            ));
+        });
+}
+
+fn clear_chat(mut chat: ResMut<ChatBox>) {
+    *chat = ChatBox::default();
+}
+
+fn type_chat(
+    settings: Res<ClientSettings>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut chat: ResMut<ChatBox>,
+    server: Option<Res<ServerConnection>>,
+) {
+    let Some(text) = chat.typing.as_mut() else {
+        let open = settings
+            .input
+            .key(InputAction::OpenChat)
+            .is_some_and(|key| keys.just_pressed(key));
+
+        if open {
+            chat.typing = Some(String::new());
+            keys.reset_all();
+        }
+
+        // Characters typed before chat is open, like the one which opened it, aren't chat text.
+        chars.clear();
+        return;
+    };
+
+    for event in chars.read() {
+        event
+            .char
+            .chars()
+            .filter(|c| !c.is_control())
+            .for_each(|c| text.push(c));
+    }
+
+    if keys.just_pressed(KeyCode::Backspace) {
+        text.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        chat.typing = None;
+    } else if keys.just_pressed(KeyCode::Enter) {
+        let text = chat.typing.take().unwrap_or_default();
+        if let Some(server) = server {
+            send_chat(&server, text.trim());
+        }
+    }
+
+    // Keys are meant for chat while typing, so game actions shouldn't see them.
+    keys.reset_all();
+}
+
+fn send_chat(server: &ServerConnection, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    // Edit history commands have their own messages, so they work the same as their keys.
+    let _ = match EditCommand::parse(text) {
+        Some(EditCommand::Undo(msg)) => server.channel().send(msg),
+        Some(EditCommand::Redo(msg)) => server.channel().send(msg),
+        None => server.channel().send(Chat {
+            text: text.to_string(),
+        }),
+    };
+}
+
+fn server_chat_broadcast(
+    In(ChatBroadcast { sender, text }): In<ChatBroadcast>,
+    mut chat: ResMut<ChatBox>,
+) {
+    let line = match sender {
+        Some(player) => format!("<{player}> {text}"),
+        None => format!("[Server] {text}"),
+    };
+    chat.push(line);
+}
+
+fn update_chat(
+    chat: Res<ChatBox>,
+    mut q_text: Query<(&mut Text, &Parent), With<ChatText>>,
+    mut q_visibility: Query<&mut Visibility>,
+) {
+    let mut lines = chat.lines.iter().cloned().collect::<Vec<_>>();
+    if let Some(typing) = &chat.typing {
+        lines.push(format!("> {typing}_"));
+    }
+
+    for (mut text, parent) in &mut q_text {
+        text.sections[0].value = lines.join("\n");
+
+        if let Ok(mut visibility) = q_visibility.get_mut(parent.get()) {
+            *visibility = if lines.is_empty() {
+                Visibility::Hidden
+            } else {
+                Visibility::Visible
+            };
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use bevy::{prelude::*, window::ReceivedCharacter};
+
+use crate::{
+    net::{ConnectionError, ServerAddress},
+    settings::ClientSettings,
+    ClientState,
+};
+
+const TITLE_FONT_SIZE: f32 = 48.0;
+const FONT_SIZE: f32 = 20.0;
+
+pub(super) struct ConnectScreenPlugin;
+
+impl Plugin for ConnectScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_connect_screen)
+            .add_systems(OnEnter(ClientState::InGame), remember_server_address)
+            .add_systems(
+                Update,
+                (
+                    (type_server_address, handle_connect_buttons)
+                        .run_if(in_state(ClientState::Title)),
+                    update_server_list.run_if(resource_changed::<ClientSettings>),
+                    update_connect_screen,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct ConnectScreen;
+
+#[derive(Component)]
+struct AddressText;
+
+#[derive(Component)]
+struct StatusText;
+
+#[derive(Component)]
+struct ServerList;
+
+#[derive(Component, Debug, Clone)]
+enum ConnectAction {
+    Connect,
+    SelectServer(String),
+}
+
+fn setup_connect_screen(
+    mut commands: Commands,
+    settings: Res<ClientSettings>,
+    mut address: ResMut<ServerAddress>,
+) {
+    if let Some(last) = settings.servers.first() {
+        address.0.clone_from(last);
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.0),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                ..Default::default()
+            },
+            ConnectScreen,
+            Name::new("ConnectScreen"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(text_bundle("Projekto".to_string(), TITLE_FONT_SIZE));
+            parent.spawn(text_bundle(
+                "Type a server address or pick a saved one".to_string(),
+                FONT_SIZE,
+            ));
+            parent.spawn((text_bundle(String::new(), FONT_SIZE), AddressText));
+            spawn_button(parent, "Connect".to_string(), ConnectAction::Connect);
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(4.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ServerList,
+            ));
+            parent.spawn((text_bundle(String::new(), FONT_SIZE), StatusText));
+        });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, text: String, action: ConnectAction) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                    ..Default::default()
+                },
+                background_color: Color::DARK_GRAY.into(),
+                ..Default::default()
+            },
+            action,
+        ))
+        .with_children(|button| {
+            button.spawn(text_bundle(text, FONT_SIZE));
+        });
+}
+
+fn text_bundle(text: String, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size,
+            color: Color::WHITE,
+            ..Default::default()
+        },
+    )
+}
+
+fn update_server_list(
+    mut commands: Commands,
+    settings: Res<ClientSettings>,
+    q: Query<Entity, With<ServerList>>,
+) {
+    for entity in &q {
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .with_children(|parent| {
+                for server in &settings.servers {
+                    spawn_button(
+                        parent,
+                        server.clone(),
+                        ConnectAction::SelectServer(server.clone()),
+                    );
+                }
+            });
+    }
+}
+
+fn type_server_address(
+    mut chars: EventReader<ReceivedCharacter>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut address: ResMut<ServerAddress>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    for event in chars.read() {
+        event
+            .char
+            .chars()
+            .filter(|c| !c.is_control() && !c.is_whitespace())
+            .for_each(|c| address.0.push(c));
+    }
+
+    if input.just_pressed(KeyCode::Backspace) {
+        address.0.pop();
+    }
+
+    if input.just_pressed(KeyCode::Enter) && !address.0.is_empty() {
+        next_state.set(ClientState::Connecting);
+    }
+}
+
+fn handle_connect_buttons(
+    q: Query<(&Interaction, &ConnectAction), Changed<Interaction>>,
+    mut address: ResMut<ServerAddress>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    for (interaction, action) in &q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match action {
+            ConnectAction::Connect => {
+                if !address.0.is_empty() {
+                    next_state.set(ClientState::Connecting);
+                }
+            }
+            ConnectAction::SelectServer(server) => address.0.clone_from(server),
+        }
+    }
+}
+
+fn update_connect_screen(
+    state: Res<State<ClientState>>,
+    address: Res<ServerAddress>,
+    error: Res<ConnectionError>,
+    mut q_screen: Query<&mut Visibility, With<ConnectScreen>>,
+    mut q_address: Query<&mut Text, (With<AddressText>, Without<StatusText>)>,
+    mut q_status: Query<&mut Text, (With<StatusText>, Without<AddressText>)>,
+) {
+    if !state.is_changed() && !address.is_changed() && !error.is_changed() {
+        return;
+    }
+
+    let visibility = if *state.get() == ClientState::InGame {
+        Visibility::Hidden
+    } else {
+        Visibility::Visible
+    };
+    for mut screen in &mut q_screen {
+        *screen = visibility;
+    }
+
+    for mut text in &mut q_address {
+        text.sections[0].value = format!("> {}_", address.0);
+    }
+
+    let status = match (state.get(), &error.0) {
+        (ClientState::Connecting, _) => format!("Connecting to {}...", address.0),
+        (ClientState::Title, Some(error)) => error.clone(),
+        _ => String::new(),
+    };
+    for mut text in &mut q_status {
+        text.sections[0].value = status.clone();
+    }
+}
+
+fn remember_server_address(address: Res<ServerAddress>, mut settings: ResMut<ClientSettings>) {
+    if settings.servers.first() != Some(&address.0) {
+        settings.remember_server(&address.0);
+    }
+}
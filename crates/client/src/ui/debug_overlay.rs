@@ -0,0 +1,259 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+use projekto_core::{chunk, voxel};
+use projekto_proto::{DeadLetterReason, DeadLetters};
+
+use crate::{
+    net::NetworkStats, set::PendingChunkMeshes, ActionInput, ChunkMap, EstimatedServerTime,
+    InputAction,
+};
+
+const FONT_SIZE: f32 = 16.0;
+/// Number of frames shown on frame time graph.
+const GRAPH_FRAMES: usize = 120;
+const GRAPH_BAR_WIDTH: f32 = 2.0;
+const GRAPH_HEIGHT: f32 = 50.0;
+/// Frame time, in milliseconds, which fills the whole graph height.
+const GRAPH_MAX_FRAME_TIME_MS: f32 = 50.0;
+
+pub(super) struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FrameTimeHistory>()
+            .add_systems(Startup, setup_debug_overlay)
+            .add_systems(
+                Update,
+                (
+                    toggle_debug_overlay,
+                    record_frame_time,
+                    (update_debug_text, update_frame_time_graph).run_if(is_overlay_visible),
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct DebugOverlay;
+
+#[derive(Component)]
+struct DebugText;
+
+#[derive(Component, Debug, Clone, Copy)]
+struct FrameTimeBar(usize);
+
+/// Last frame times, in milliseconds, from the oldest to the newest.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+struct FrameTimeHistory(VecDeque<f32>);
+
+fn setup_debug_overlay(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            DebugOverlay,
+            Name::new("DebugOverlay"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    String::new(),
+                    TextStyle {
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ),
+                DebugText,
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Px(GRAPH_HEIGHT),
+                        align_items: AlignItems::FlexEnd,
+                        ..Default::default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.1).into(),
+                    ..Default::default()
+                })
+                .with_children(|graph| {
+                    for i in 0..GRAPH_FRAMES {
+                        graph.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(GRAPH_BAR_WIDTH),
+                                    height: Val::Px(0.0),
+                                    ..Default::default()
+                                },
+                                background_color: Color::GREEN.into(),
+                                ..Default::default()
+                            },
+                            FrameTimeBar(i),
+                        ));
+                    }
+                });
+        });
+}
+
+fn is_overlay_visible(q: Query<&Visibility, With<DebugOverlay>>) -> bool {
+    q.iter().any(|visibility| *visibility != Visibility::Hidden)
+}
+
+fn toggle_debug_overlay(input: ActionInput, mut q: Query<&mut Visibility, With<DebugOverlay>>) {
+    if !input.just_pressed(InputAction::ToggleDebugOverlay) {
+        return;
+    }
+
+    for mut visibility in &mut q {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn record_frame_time(time: Res<Time>, mut history: ResMut<FrameTimeHistory>) {
+    if history.len() == GRAPH_FRAMES {
+        history.pop_front();
+    }
+    history.push_back(time.delta_seconds() * 1000.0);
+}
+
+fn update_debug_text(
+    diagnostics: Res<DiagnosticsStore>,
+    time: Res<Time>,
+    network: Res<NetworkStats>,
+    server_time: Res<EstimatedServerTime>,
+    chunks: Res<ChunkMap>,
+    pending_meshes: Res<PendingChunkMeshes>,
+    dead_letters: Option<Res<DeadLetters>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut q_text: Query<&mut Text, With<DebugText>>,
+) {
+    let diagnostic = |path| {
+        diagnostics
+            .get(path)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or_default()
+    };
+
+    let mut lines = vec![format!(
+        "FPS: {:.0} ({:.2} ms)",
+        diagnostic(&FrameTimeDiagnosticsPlugin::FPS),
+        diagnostic(&FrameTimeDiagnosticsPlugin::FRAME_TIME),
+    )];
+
+    if let Some((_, transform)) = q_camera.iter().find(|(camera, _)| camera.is_active) {
+        let position = transform.translation();
+        let forward = transform.forward();
+
+        lines.push(format!(
+            "Position: {:.2} {:.2} {:.2}",
+            position.x, position.y, position.z
+        ));
+        lines.push(format!(
+            "Chunk: {} Voxel: {}",
+            chunk::to_chunk(position),
+            voxel::to_local(position)
+        ));
+        lines.push(format!(
+            "Facing: {:.2} {:.2} {:.2}",
+            forward.x, forward.y, forward.z
+        ));
+    }
+
+    lines.push(format!(
+        "Chunks: {} loaded, {} pending meshes",
+        chunks.len(),
+        pending_meshes.len()
+    ));
+    lines.push(format!(
+        "Net in: {:.1} KB/s ({} msg/s), {} pending (peak {})",
+        network.bytes_received_per_sec as f32 / 1024.0,
+        network.messages_received_per_sec,
+        network.pending_messages,
+        network.peak_pending_messages,
+    ));
+    lines.push(format!(
+        "Net out: {:.1} KB/s ({} msg/s), {} pending (peak {})",
+        network.bytes_sent_per_sec as f32 / 1024.0,
+        network.messages_sent_per_sec,
+        network.pending_sends,
+        network.peak_pending_sends,
+    ));
+    if let Some(rtt) = server_time.rtt() {
+        lines.push(format!(
+            "Ping: {:.0} ms, server tick {:.0}",
+            rtt.as_secs_f32() * 1000.0,
+            server_time.tick_at(time.elapsed_seconds_f64()).floor(),
+        ));
+    }
+    if network.overflowed_messages > 0 {
+        lines.push(format!(
+            "Net overflow: {} messages",
+            network.overflowed_messages
+        ));
+    }
+    if let Some((letters, letter)) = dead_letters
+        .as_ref()
+        .and_then(|letters| Some((letters, letters.last()?)))
+    {
+        let reason = match &letter.reason {
+            DeadLetterReason::NoHandler => "no handler",
+            DeadLetterReason::Failed(err) => err.as_str(),
+        };
+        lines.push(format!(
+            "Dead letters: {} (last {}: {reason})",
+            letters.total(),
+            letter.message,
+        ));
+    }
+
+    for mut text in &mut q_text {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn update_frame_time_graph(
+    history: Res<FrameTimeHistory>,
+    mut q: Query<(&FrameTimeBar, &mut Style, &mut BackgroundColor)>,
+) {
+    // Newest frames are placed on the right side of the graph.
+    let offset = GRAPH_FRAMES - history.len();
+
+    for (&FrameTimeBar(i), mut style, mut color) in &mut q {
+        let frame_time = i
+            .checked_sub(offset)
+            .and_then(|i| history.get(i))
+            .copied()
+            .unwrap_or_default();
+
+        let ratio = (frame_time / GRAPH_MAX_FRAME_TIME_MS).min(1.0);
+        style.height = Val::Px(ratio * GRAPH_HEIGHT);
+        color.0 = if frame_time > 1000.0 / 30.0 {
+            Color::RED
+        } else if frame_time > 1000.0 / 60.0 {
+            Color::YELLOW
+        } else {
+            Color::GREEN
+        };
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+use projekto_core::voxel::{self, KindSidesDesc, KindsDescs};
+
+use crate::{ActionInput, InputAction, KindsAtlasRes};
+
+const SLOT_SIZE: f32 = 48.0;
+const SLOT_BORDER: f32 = 2.0;
+
+pub(super) struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hotbar>()
+            .add_systems(Startup, setup_hotbar)
+            .add_systems(
+                Update,
+                (
+                    select_hotbar_slot,
+                    update_hotbar_slots.run_if(resource_changed::<Hotbar>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Voxel kinds which the player can place, selected by [`InputAction::HotbarSlot`] or mouse wheel.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Hotbar {
+    slots: Vec<voxel::Kind>,
+    selected: usize,
+}
+
+impl Hotbar {
+    pub const SLOTS: usize = 9;
+
+    /// Fills hotbar slots with the first kinds which can be rendered.
+    pub fn from_descs(descs: &KindsDescs) -> Self {
+        let slots = descs
+            .descriptions
+            .iter()
+            .filter(|desc| !matches!(desc.sides, KindSidesDesc::None))
+            .map(|desc| voxel::Kind::id(desc.id))
+            .take(Self::SLOTS)
+            .collect();
+
+        Self { slots, selected: 0 }
+    }
+
+    /// **Returns** the kind on the selected slot, if the slot isn't empty.
+    pub fn selected_kind(&self) -> Option<voxel::Kind> {
+        self.slots.get(self.selected).copied()
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+struct HotbarSlot(usize);
+
+/// **Returns** the atlas tile index used as icon of the given kind.
+fn icon_index(descs: &KindsDescs, kind: voxel::Kind) -> Option<usize> {
+    let desc = descs
+        .descriptions
+        .iter()
+        .find(|desc| desc.id == u16::from(kind))?;
+
+    let texture = match desc.sides {
+        KindSidesDesc::None => return None,
+        KindSidesDesc::All(texture) => texture,
+        KindSidesDesc::Unique { front, .. } => front,
+    };
+
+    let columns = descs.count_tiles() as usize;
+    Some(texture.offset.y as usize * columns + texture.offset.x as usize)
+}
+
+fn setup_hotbar(mut commands: Commands, mut hotbar: ResMut<Hotbar>, atlas: Res<KindsAtlasRes>) {
+    let descs = KindsDescs::get();
+    *hotbar = Hotbar::from_descs(descs);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(4.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Name::new("Hotbar"),
+        ))
+        .with_children(|parent| {
+            for slot in 0..Hotbar::SLOTS {
+                parent
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Px(SLOT_SIZE),
+                                height: Val::Px(SLOT_SIZE),
+                                border: UiRect::all(Val::Px(SLOT_BORDER)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                            border_color: Color::DARK_GRAY.into(),
+                            ..Default::default()
+                        },
+                        HotbarSlot(slot),
+                    ))
+                    .with_children(|slot_node| {
+                        let Some(index) = hotbar
+                            .slots
+                            .get(slot)
+                            .and_then(|&kind| icon_index(descs, kind))
+                        else {
+                            return;
+                        };
+
+                        slot_node.spawn(AtlasImageBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..Default::default()
+                            },
+                            texture_atlas: TextureAtlas {
+                                layout: atlas.layout.clone(),
+                                index,
+                            },
+                            image: UiImage::new(atlas.atlas.clone()),
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+}
+
+fn select_hotbar_slot(
+    input: ActionInput,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut hotbar: ResMut<Hotbar>,
+) {
+    let mut selected = (0..Hotbar::SLOTS)
+        .find(|&slot| input.just_pressed(InputAction::HotbarSlot(slot as u8)))
+        .unwrap_or(hotbar.selected);
+
+    let scroll = mouse_wheel.read().map(|event| event.y).sum::<f32>();
+    if scroll > 0.0 {
+        selected = (selected + Hotbar::SLOTS - 1) % Hotbar::SLOTS;
+    } else if scroll < 0.0 {
+        selected = (selected + 1) % Hotbar::SLOTS;
+    }
+
+    if hotbar.selected != selected {
+        hotbar.selected = selected;
+    }
+}
+
+fn update_hotbar_slots(hotbar: Res<Hotbar>, mut q: Query<(&HotbarSlot, &mut BorderColor)>) {
+    for (&HotbarSlot(slot), mut border) in &mut q {
+        *border = if slot == hotbar.selected {
+            Color::WHITE
+        } else {
+            Color::DARK_GRAY
+        }
+        .into();
+    }
+}
@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    time::common_conditions::on_timer,
+    utils::HashMap,
+};
+use projekto_core::chunk::{self, Chunk, ColumnSummary};
+use projekto_messages::ChunkColumnSummary;
+use projekto_proto::RegisterMessageHandler;
+
+use crate::{
+    action_just_pressed, net::ServerDisconnected, ClientSettings, InputAction, PlayerLandscape,
+    RemotePlayer,
+};
+
+/// Radius, in voxels, of the area around the player shown on minimap. Each voxel is a pixel.
+const MINIMAP_RADIUS: i32 = 64;
+const MINIMAP_SIZE: u32 = MINIMAP_RADIUS as u32 * 2;
+/// Minimap is scaled up on screen, so voxels are easier to see.
+const MINIMAP_SCALE: f32 = 1.5;
+const MINIMAP_UPDATE_INTERVAL_MS: u64 = 100;
+const PLAYER_ARROW_SIZE: f32 = 5.0;
+const UNLOADED_COLOR: [u8; 4] = [0, 0, 0, 160];
+const PLAYER_COLOR: [u8; 4] = [255, 255, 255, 255];
+const REMOTE_PLAYER_COLOR: [u8; 4] = [230, 60, 40, 255];
+
+pub(super) struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkColumns>()
+            .set_message_handler(update_chunk_columns)
+            .add_systems(Startup, setup_minimap)
+            .add_systems(
+                Update,
+                (
+                    clear_chunk_columns_on_server_disconnect
+                        .run_if(on_event::<ServerDisconnected>()),
+                    toggle_minimap.run_if(action_just_pressed(InputAction::ToggleMinimap)),
+                    draw_minimap
+                        .run_if(on_timer(Duration::from_millis(MINIMAP_UPDATE_INTERVAL_MS))),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Column summaries of chunks loaded on player landscape, received from server.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+struct ChunkColumns(HashMap<Chunk, Vec<ColumnSummary>>);
+
+impl ChunkColumns {
+    fn get_column(&self, x: i32, z: i32) -> Option<ColumnSummary> {
+        let chunk = chunk::to_chunk(Vec3::new(x as f32, 0.0, z as f32));
+        let local = IVec2::new(
+            x.rem_euclid(chunk::X_AXIS_SIZE as i32),
+            z.rem_euclid(chunk::Z_AXIS_SIZE as i32),
+        );
+
+        self.get(&chunk)
+            .and_then(|columns| {
+                columns.get(local.x as usize * chunk::Z_AXIS_SIZE + local.y as usize)
+            })
+            .copied()
+    }
+}
+
+#[derive(Component)]
+struct Minimap;
+
+fn update_chunk_columns(
+    In(ChunkColumnSummary { chunk, columns }): In<ChunkColumnSummary>,
+    mut map: ResMut<ChunkColumns>,
+    landscape: Res<PlayerLandscape>,
+) {
+    let radius = landscape.radius as i32;
+    map.retain(|&other, _| other.distance(landscape.center.into()).abs().max_element() <= radius);
+    map.insert(chunk, columns);
+}
+
+fn clear_chunk_columns_on_server_disconnect(
+    mut map: ResMut<ChunkColumns>,
+    mut reader: EventReader<ServerDisconnected>,
+) {
+    reader.clear();
+    map.clear();
+}
+
+fn setup_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: MINIMAP_SIZE,
+            height: MINIMAP_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &UNLOADED_COLOR,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::all(),
+    );
+
+    commands.spawn((
+        ImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                width: Val::Px(MINIMAP_SIZE as f32 * MINIMAP_SCALE),
+                height: Val::Px(MINIMAP_SIZE as f32 * MINIMAP_SCALE),
+                border: UiRect::all(Val::Px(2.0)),
+                ..Default::default()
+            },
+            image: UiImage::new(images.add(image)),
+            ..Default::default()
+        },
+        BorderColor(Color::rgba(0.0, 0.0, 0.0, 0.8)),
+        Minimap,
+        Name::new("Minimap"),
+    ));
+}
+
+fn toggle_minimap(mut q: Query<&mut Visibility, With<Minimap>>) {
+    for mut visibility in &mut q {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// **Returns** the color of the given column, darkened when it is lower than its northern
+/// neighbor, so terrain relief is visible.
+fn shade_column(column: ColumnSummary, north: Option<ColumnSummary>) -> [u8; 4] {
+    let relief = north.map_or(0, |north| column.height as i32 - north.height as i32);
+    let shade = (1.0 + relief as f32 * 0.15).clamp(0.6, 1.3);
+
+    let [r, g, b] = column
+        .color
+        .map(|c| (c as f32 * shade).clamp(0.0, u8::MAX as f32) as u8);
+    [r, g, b, 255]
+}
+
+fn set_pixel(data: &mut [u8], pixel: IVec2, color: [u8; 4]) {
+    if pixel.cmplt(IVec2::ZERO).any() || pixel.cmpge(IVec2::splat(MINIMAP_SIZE as i32)).any() {
+        return;
+    }
+
+    let index = (pixel.y as usize * MINIMAP_SIZE as usize + pixel.x as usize) * 4;
+    data[index..index + 4].copy_from_slice(&color);
+}
+
+/// Draws a triangle pointing towards `dir`, which is a normalized direction on XZ plane.
+fn draw_arrow(data: &mut [u8], center: IVec2, dir: Vec2) {
+    let tip = dir * PLAYER_ARROW_SIZE;
+    let side = dir.perp() * PLAYER_ARROW_SIZE * 0.6;
+    let back = -dir * PLAYER_ARROW_SIZE * 0.6;
+    let (a, b, c) = (tip, back + side, back - side);
+
+    let edge = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    let size = PLAYER_ARROW_SIZE.ceil() as i32;
+
+    for y in -size..=size {
+        for x in -size..=size {
+            let p = Vec2::new(x as f32, y as f32);
+            let (w0, w1, w2) = (edge(b, c, p), edge(c, a, p), edge(a, b, p));
+            let inside =
+                (w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0) || (w0 <= 0.0 && w1 <= 0.0 && w2 <= 0.0);
+
+            if inside {
+                set_pixel(data, center + IVec2::new(x, y), PLAYER_COLOR);
+            }
+        }
+    }
+}
+
+fn draw_minimap(
+    map: Res<ChunkColumns>,
+    settings: Res<ClientSettings>,
+    q_minimap: Query<(&UiImage, &ViewVisibility), With<Minimap>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    q_players: Query<&GlobalTransform, With<RemotePlayer>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok((ui_image, visibility)) = q_minimap.get_single() else {
+        return;
+    };
+
+    if !visibility.get() {
+        return;
+    }
+
+    let Some((_, camera)) = q_camera.iter().find(|(camera, _)| camera.is_active) else {
+        return;
+    };
+
+    let Some(image) = images.get_mut(&ui_image.texture) else {
+        return;
+    };
+
+    // Map is oriented with -Z on top, so world XZ maps directly into image XY.
+    let position = camera.translation();
+    let origin = position.xz().floor().as_ivec2() - IVec2::splat(MINIMAP_RADIUS);
+
+    for y in 0..MINIMAP_SIZE as i32 {
+        for x in 0..MINIMAP_SIZE as i32 {
+            let world = origin + IVec2::new(x, y);
+            let color = map
+                .get_column(world.x, world.y)
+                .map_or(UNLOADED_COLOR, |column| {
+                    shade_column(column, map.get_column(world.x, world.y - 1))
+                });
+
+            set_pixel(&mut image.data, IVec2::new(x, y), color);
+        }
+    }
+
+    if settings.minimap_players {
+        for transform in &q_players {
+            let pixel = transform.translation().xz().floor().as_ivec2() - origin;
+            for offset in [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE] {
+                set_pixel(&mut image.data, pixel + offset, REMOTE_PLAYER_COLOR);
+            }
+        }
+    }
+
+    let forward = camera.forward().xz().normalize_or_zero();
+    let dir = if forward == Vec2::ZERO {
+        Vec2::NEG_Y
+    } else {
+        forward
+    };
+    draw_arrow(&mut image.data, IVec2::splat(MINIMAP_RADIUS), dir);
+}
@@ -0,0 +1,45 @@
+//! In-game user interface, which is rendered on top of world cameras by a dedicated camera.
+
+use bevy::{prelude::*, render::camera::ClearColorConfig};
+
+mod chat;
+mod connect_screen;
+mod debug_overlay;
+mod hotbar;
+mod minimap;
+mod settings_menu;
+
+pub use hotbar::Hotbar;
+
+pub(crate) struct GameUiPlugin;
+
+impl Plugin for GameUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            chat::ChatPlugin,
+            connect_screen::ConnectScreenPlugin,
+            debug_overlay::DebugOverlayPlugin,
+            hotbar::HotbarPlugin,
+            minimap::MinimapPlugin,
+            settings_menu::SettingsMenuPlugin,
+        ))
+        .add_systems(Startup, setup_ui_camera);
+    }
+}
+
+/// World cameras are swapped at runtime, so UI is rendered by its own camera, which is always
+/// active and drawn after world cameras.
+fn setup_ui_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                clear_color: ClearColorConfig::None,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        IsDefaultUiCamera,
+        Name::new("UiCamera"),
+    ));
+}
@@ -0,0 +1,250 @@
+use bevy::prelude::*;
+
+use crate::{
+    settings::{ClientSettings, GraphicsPreset},
+    ActionInput, InputAction,
+};
+
+const FONT_SIZE: f32 = 18.0;
+
+pub(super) struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_settings_menu).add_systems(
+            Update,
+            (
+                toggle_settings_menu,
+                handle_settings_buttons,
+                update_settings_labels.run_if(resource_changed::<ClientSettings>),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Component)]
+struct SettingsMenu;
+
+#[derive(Component, Debug, Clone, Copy)]
+enum SettingsLabel {
+    Graphics,
+    RenderDistance,
+    Vsync,
+    Shadows,
+    Fov,
+    CameraSmoothing,
+    HeadBob,
+    FovKick,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+enum SettingsAction {
+    Graphics(GraphicsPreset),
+    RenderDistance(i8),
+    ToggleVsync,
+    ToggleShadows,
+    Fov(f32),
+    ToggleCameraSmoothing,
+    ToggleHeadBob,
+    ToggleFovKick,
+}
+
+fn setup_settings_menu(mut commands: Commands, settings: Res<ClientSettings>) {
+    let toggle = settings.input.describe(InputAction::ToggleSettingsMenu);
+    let release = settings.input.describe(InputAction::ReleaseMouse);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                visibility: Visibility::Hidden,
+                ..Default::default()
+            },
+            SettingsMenu,
+            Name::new("SettingsMenu"),
+        ))
+        .with_children(|parent| {
+            parent.spawn(text_bundle(format!(
+                "Settings ({toggle}). Press {release} to release the mouse."
+            )));
+
+            spawn_row(
+                parent,
+                SettingsLabel::Graphics,
+                &[
+                    ("Low", SettingsAction::Graphics(GraphicsPreset::Low)),
+                    ("Medium", SettingsAction::Graphics(GraphicsPreset::Medium)),
+                    ("High", SettingsAction::Graphics(GraphicsPreset::High)),
+                ],
+            );
+            spawn_row(
+                parent,
+                SettingsLabel::RenderDistance,
+                &[
+                    ("-", SettingsAction::RenderDistance(-1)),
+                    ("+", SettingsAction::RenderDistance(1)),
+                ],
+            );
+            spawn_row(
+                parent,
+                SettingsLabel::Vsync,
+                &[("Toggle", SettingsAction::ToggleVsync)],
+            );
+            spawn_row(
+                parent,
+                SettingsLabel::Shadows,
+                &[("Toggle", SettingsAction::ToggleShadows)],
+            );
+            spawn_row(
+                parent,
+                SettingsLabel::Fov,
+                &[
+                    ("-", SettingsAction::Fov(-5.0)),
+                    ("+", SettingsAction::Fov(5.0)),
+                ],
+            );
+            spawn_row(
+                parent,
+                SettingsLabel::CameraSmoothing,
+                &[("Toggle", SettingsAction::ToggleCameraSmoothing)],
+            );
+            spawn_row(
+                parent,
+                SettingsLabel::HeadBob,
+                &[("Toggle", SettingsAction::ToggleHeadBob)],
+            );
+            spawn_row(
+                parent,
+                SettingsLabel::FovKick,
+                &[("Toggle", SettingsAction::ToggleFovKick)],
+            );
+        });
+}
+
+fn spawn_row(
+    parent: &mut ChildBuilder,
+    label: SettingsLabel,
+    actions: &[(&'static str, SettingsAction)],
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(4.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with_children(|row| {
+            row.spawn((text_bundle(String::new()), label));
+
+            for &(text, action) in actions {
+                row.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            padding: UiRect::horizontal(Val::Px(6.0)),
+                            ..Default::default()
+                        },
+                        background_color: Color::DARK_GRAY.into(),
+                        ..Default::default()
+                    },
+                    action,
+                ))
+                .with_children(|button| {
+                    button.spawn(text_bundle(text.to_string()));
+                });
+            }
+        });
+}
+
+fn text_bundle(text: String) -> TextBundle {
+    TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size: FONT_SIZE,
+            color: Color::WHITE,
+            ..Default::default()
+        },
+    )
+}
+
+fn toggle_settings_menu(input: ActionInput, mut q: Query<&mut Visibility, With<SettingsMenu>>) {
+    if !input.just_pressed(InputAction::ToggleSettingsMenu) {
+        return;
+    }
+
+    for mut visibility in &mut q {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn handle_settings_buttons(
+    q: Query<(&Interaction, &SettingsAction), Changed<Interaction>>,
+    mut settings: ResMut<ClientSettings>,
+) {
+    for (interaction, &action) in &q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let mut new_settings = settings.clone();
+        match action {
+            SettingsAction::Graphics(preset) => new_settings.set_graphics_preset(preset),
+            SettingsAction::RenderDistance(delta) => {
+                new_settings.render_distance =
+                    new_settings.render_distance.saturating_add_signed(delta);
+            }
+            SettingsAction::ToggleVsync => new_settings.vsync = !new_settings.vsync,
+            SettingsAction::ToggleShadows => new_settings.shadows = !new_settings.shadows,
+            SettingsAction::Fov(delta) => new_settings.fov += delta,
+            SettingsAction::ToggleCameraSmoothing => {
+                new_settings.camera_smoothing = !new_settings.camera_smoothing;
+            }
+            SettingsAction::ToggleHeadBob => new_settings.head_bob = !new_settings.head_bob,
+            SettingsAction::ToggleFovKick => new_settings.fov_kick = !new_settings.fov_kick,
+        }
+
+        let new_settings = new_settings.clamped();
+        if *settings != new_settings {
+            *settings = new_settings;
+        }
+    }
+}
+
+fn update_settings_labels(
+    settings: Res<ClientSettings>,
+    mut q: Query<(&mut Text, &SettingsLabel)>,
+) {
+    let on_off = |value: bool| if value { "on" } else { "off" };
+
+    for (mut text, label) in &mut q {
+        text.sections[0].value = match label {
+            SettingsLabel::Graphics => format!("Graphics: {:?}", settings.graphics),
+            SettingsLabel::RenderDistance => {
+                format!("Render distance: {}", settings.render_distance)
+            }
+            SettingsLabel::Vsync => format!("VSync: {}", on_off(settings.vsync)),
+            SettingsLabel::Shadows => format!("Shadows: {}", on_off(settings.shadows)),
+            SettingsLabel::Fov => format!("FOV: {:.0}", settings.fov),
+            SettingsLabel::CameraSmoothing => {
+                format!("Camera smoothing: {}", on_off(settings.camera_smoothing))
+            }
+            SettingsLabel::HeadBob => format!("Head bob: {}", on_off(settings.head_bob)),
+            SettingsLabel::FovKick => format!("Sprint FOV kick: {}", on_off(settings.fov_kick)),
+        };
+    }
+}
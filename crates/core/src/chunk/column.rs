@@ -0,0 +1,186 @@
+use bevy::math::IVec2;
+use serde::{Deserialize, Serialize};
+
+use crate::voxel::{self, Voxel};
+
+use super::{Chunk, ChunkStorage, SubChunkStorage, X_AXIS_SIZE, Y_AXIS_SIZE, Z_AXIS_SIZE};
+
+/// Height, in voxels, of each sub-chunk stacked on a [`ChunkColumn`].
+pub const SUB_CHUNK_HEIGHT: usize = 16;
+/// Number of sub-chunks stacked on a [`ChunkColumn`].
+pub const SUB_CHUNK_COUNT: usize = Y_AXIS_SIZE / SUB_CHUNK_HEIGHT;
+
+/// Voxel kinds of a chunk, seen as a vertical stack of sub-chunks, each one [`SUB_CHUNK_HEIGHT`]
+/// voxels tall, starting from the bottom. Kinds are kept on a [`SubChunkStorage`], so empty
+/// sub-chunks are known without looking at their voxels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkColumn {
+    chunk: Chunk,
+    kind: SubChunkStorage<voxel::Kind>,
+}
+
+impl ChunkColumn {
+    pub fn new(chunk: Chunk, kind: &ChunkStorage<voxel::Kind>) -> Self {
+        Self {
+            chunk,
+            kind: kind.into(),
+        }
+    }
+
+    pub fn chunk(&self) -> Chunk {
+        self.chunk
+    }
+
+    pub fn kind(&self) -> &SubChunkStorage<voxel::Kind> {
+        &self.kind
+    }
+
+    pub fn get(&self, voxel: Voxel) -> voxel::Kind {
+        self.kind.get(voxel)
+    }
+
+    pub fn set(&mut self, voxel: Voxel, kind: voxel::Kind) {
+        self.kind.set(voxel, kind);
+    }
+
+    /// **Returns** the kinds of this column as a regular [`ChunkStorage`].
+    pub fn to_kind(&self) -> ChunkStorage<voxel::Kind> {
+        (&self.kind).into()
+    }
+
+    /// **Returns** an iterator over all sub-chunks, from the bottom to the top.
+    pub fn sub_chunks(&self) -> impl DoubleEndedIterator<Item = SubChunk<'_>> {
+        (0..SUB_CHUNK_COUNT).map(|index| SubChunk {
+            index,
+            kind: &self.kind,
+        })
+    }
+
+    /// **Returns** the height of the highest solid voxel on the given column, or `None` if there
+    /// is no solid voxel on it. Empty sub-chunks are skipped as a whole.
+    pub fn highest_solid(&self, column: IVec2) -> Option<i32> {
+        debug_assert!(
+            column.x >= 0
+                && column.x < X_AXIS_SIZE as i32
+                && column.y >= 0
+                && column.y < Z_AXIS_SIZE as i32,
+            "Column {column} is outside of chunk"
+        );
+
+        self.sub_chunks()
+            .rev()
+            .filter(|sub_chunk| !sub_chunk.is_empty())
+            .find_map(|sub_chunk| {
+                sub_chunk
+                    .heights()
+                    .rev()
+                    .find(|&y| self.kind.get(Voxel::new(column.x, y, column.y)).is_solid())
+            })
+    }
+}
+
+/// A horizontal slice of a [`ChunkColumn`], [`SUB_CHUNK_HEIGHT`] voxels tall.
+#[derive(Debug, Clone, Copy)]
+pub struct SubChunk<'a> {
+    index: usize,
+    kind: &'a SubChunkStorage<voxel::Kind>,
+}
+
+impl<'a> SubChunk<'a> {
+    /// **Returns** the position of this sub-chunk on the stack, starting from the bottom.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// **Returns** heights, in chunk voxels, covered by this sub-chunk, from bottom to top.
+    pub fn heights(&self) -> std::ops::Range<i32> {
+        let min = (self.index * SUB_CHUNK_HEIGHT) as i32;
+        min..min + SUB_CHUNK_HEIGHT as i32
+    }
+
+    /// **Returns** all voxels of this sub-chunk, in chunk coordinates.
+    pub fn voxels(&self) -> impl Iterator<Item = Voxel> {
+        let heights = self.heights();
+        (0..X_AXIS_SIZE as i32).flat_map(move |x| {
+            let heights = heights.clone();
+            (0..Z_AXIS_SIZE as i32)
+                .flat_map(move |z| heights.clone().map(move |y| Voxel::new(x, y, z)))
+        })
+    }
+
+    /// Checks if all voxels of this sub-chunk are empty. This is tracked by [`SubChunkStorage`] as
+    /// voxels are set, so it doesn't look at any voxel.
+    pub fn is_empty(&self) -> bool {
+        self.kind.is_section_empty(self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sub_chunks() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set(Voxel::new(3, 17, 5), voxel::Kind::id(1));
+
+        let column = ChunkColumn::new(Chunk::new(1, 2), &kind);
+        let sub_chunks = column.sub_chunks().collect::<Vec<_>>();
+
+        assert_eq!(sub_chunks.len(), SUB_CHUNK_COUNT);
+        assert_eq!(sub_chunks[1].heights(), 16..32);
+        assert_eq!(
+            sub_chunks[0].voxels().count(),
+            X_AXIS_SIZE * Z_AXIS_SIZE * SUB_CHUNK_HEIGHT
+        );
+        assert_eq!(
+            sub_chunks
+                .iter()
+                .filter(|sub_chunk| !sub_chunk.is_empty())
+                .map(SubChunk::index)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn highest_solid() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set(Voxel::new(0, 10, 0), voxel::Kind::id(1));
+        kind.set(Voxel::new(0, 200, 0), voxel::Kind::id(3));
+        kind.set(Voxel::new(1, 0, 2), voxel::Kind::id(2));
+
+        let column = ChunkColumn::new(Chunk::default(), &kind);
+
+        assert_eq!(column.highest_solid(IVec2::new(0, 0)), Some(200));
+        assert_eq!(column.highest_solid(IVec2::new(1, 2)), Some(0));
+        assert_eq!(column.highest_solid(IVec2::new(5, 5)), None);
+    }
+
+    #[test]
+    fn set_tracks_empty_sub_chunks() {
+        let mut column = ChunkColumn::new(Chunk::default(), &ChunkStorage::default());
+        let voxel = Voxel::new(2, 40, 7);
+
+        column.set(voxel, voxel::Kind::id(1));
+        assert!(!column.sub_chunks().nth(2).unwrap().is_empty());
+        assert_eq!(column.highest_solid(IVec2::new(2, 7)), Some(40));
+
+        column.set(voxel, voxel::Kind::none());
+        assert!(column.sub_chunks().all(|sub_chunk| sub_chunk.is_empty()));
+        assert_eq!(column.highest_solid(IVec2::new(2, 7)), None);
+        assert_eq!(column.to_kind(), ChunkStorage::default());
+    }
+
+    #[test]
+    fn serde() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set(Voxel::new(4, 100, 9), voxel::Kind::id(2));
+        let column = ChunkColumn::new(Chunk::new(-3, 7), &kind);
+
+        let ron = ron::to_string(&column).unwrap();
+        let deserialized: ChunkColumn = ron::from_str(&ron).unwrap();
+
+        assert_eq!(deserialized, column);
+    }
+}
@@ -2,9 +2,21 @@ use crate::{
     math,
     voxel::{self, Voxel},
 };
-use bevy::math::{IVec2, IVec3, Vec3};
+use bevy::{
+    math::{IVec2, IVec3, Vec3},
+    reflect::Reflect,
+};
 use serde::{Deserialize, Serialize};
 
+pub mod column;
+pub mod sub_chunk;
+pub mod tracked;
+pub mod zip;
+
+pub use column::ChunkColumn;
+pub use sub_chunk::SubChunkStorage;
+pub use tracked::TrackedStorage;
+
 pub const X_AXIS_SIZE: usize = 16;
 pub const Y_AXIS_SIZE: usize = 256;
 pub const Z_AXIS_SIZE: usize = 16;
@@ -27,7 +39,7 @@ const Y_MASK: usize = Y_AXIS_SIZE - 1;
 pub static ALLOC_COUNT: once_cell::sync::Lazy<std::sync::atomic::AtomicUsize> =
     once_cell::sync::Lazy::new(std::sync::atomic::AtomicUsize::default);
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
 pub struct Chunk(IVec2);
 
 impl Chunk {
@@ -113,7 +125,9 @@ impl std::fmt::Display for Chunk {
 
 pub const SIDE_COUNT: usize = 4;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Hash, Default, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Hash, Default, Serialize, Deserialize, Reflect,
+)]
 pub enum ChunkSide {
     #[default]
     Right = 0,
@@ -130,14 +144,14 @@ pub const SIDES: [ChunkSide; SIDE_COUNT] = [
 ];
 
 impl ChunkSide {
-    // pub const fn opposite(&self) -> ChunkSide {
-    //     match self {
-    //         ChunkSide::Right => ChunkSide::Left,
-    //         ChunkSide::Left => ChunkSide::Right,
-    //         ChunkSide::Front => ChunkSide::Back,
-    //         ChunkSide::Back => ChunkSide::Front,
-    //     }
-    // }
+    pub const fn opposite(&self) -> ChunkSide {
+        match self {
+            ChunkSide::Right => ChunkSide::Left,
+            ChunkSide::Left => ChunkSide::Right,
+            ChunkSide::Front => ChunkSide::Back,
+            ChunkSide::Back => ChunkSide::Front,
+        }
+    }
 
     pub const fn index(&self) -> usize {
         match self {
@@ -193,7 +207,7 @@ impl ChunkStorageType for voxel::Light {}
 impl ChunkStorageType for voxel::FacesOcclusion {}
 impl ChunkStorageType for voxel::FacesSoftLight {}
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Reflect)]
 pub struct ChunkStorage<T>(Vec<T>);
 
 impl<T: ChunkStorageType> Default for ChunkStorage<T> {
@@ -392,6 +406,128 @@ pub fn overlap_voxel(voxel: Voxel) -> (IVec2, Voxel) {
     (overlapping_dir, overlapping_voxel)
 }
 
+/// Color and height of the top most solid voxel of a chunk column, used to draw maps.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+pub struct ColumnSummary {
+    /// Map color of top most voxel kind. See [`voxel::Kind::map_color`].
+    pub color: [u8; 3],
+    /// Height of top most voxel.
+    pub height: u8,
+}
+
+/// **Returns** the summary of each column of the given chunk, indexed by `x * Z_AXIS_SIZE + z`.
+/// Columns with no solid voxel have a default summary.
+pub fn column_summaries(kind: &ChunkStorage<voxel::Kind>) -> Vec<ColumnSummary> {
+    top_voxels()
+        .map(|top| {
+            (0..=top.y)
+                .rev()
+                .map(|y| (y, kind.get(Voxel::new(top.x, y, top.z))))
+                .find(|(_, kind)| kind.is_solid())
+                .map(|(y, kind)| ColumnSummary {
+                    color: kind.map_color(),
+                    height: y as u8,
+                })
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Chance of a scattered decoration being a flower, instead of a grass tuft.
+const FLOWER_CHANCE: f32 = 0.1;
+
+/// Which billboard a [`Decoration`] is drawn with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
+pub enum DecorationKind {
+    #[default]
+    Grass,
+    Flower,
+}
+
+/// A non-blocking billboard, like grass tufts or flowers, placed on top of a surface voxel. It
+/// doesn't occupy a voxel slot, so it neither collides nor blocks light.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+pub struct Decoration {
+    /// Index of the empty voxel right above the decorated surface. See [`from_index`].
+    pub index: u16,
+    pub kind: DecorationKind,
+}
+
+impl Decoration {
+    /// **Returns** the empty voxel where this decoration is placed.
+    pub fn voxel(&self) -> Voxel {
+        from_index(self.index as usize)
+    }
+}
+
+/// **Returns** decorations scattered on top of the surface of each column of the given chunk,
+/// according to [`voxel::Kind::decoration_chance`] of top most voxel.
+///
+/// Scatter is based on world position of each surface voxel, so a surface always has the same
+/// decorations, no matter when or where it is generated.
+pub fn decorations(chunk: Chunk, kind: &ChunkStorage<voxel::Kind>) -> Vec<Decoration> {
+    top_voxels()
+        .filter_map(|top| {
+            let (y, surface) = (0..top.y)
+                .rev()
+                .map(|y| (y, kind.get(Voxel::new(top.x, y, top.z))))
+                .find(|(_, kind)| kind.is_solid())?;
+
+            let above = Voxel::new(top.x, y + 1, top.z);
+            if !kind.get(above).is_none() {
+                return None;
+            }
+
+            let chance = surface.decoration_chance();
+            if chance <= 0.0 {
+                return None;
+            }
+
+            let world = IVec3::new(
+                chunk.x() * X_AXIS_SIZE as i32 + above.x,
+                above.y,
+                chunk.z() * Z_AXIS_SIZE as i32 + above.z,
+            );
+            let hash = voxel::fnv1a(world.to_array().into_iter().flat_map(i32::to_le_bytes));
+            let roll = |bits: u64| (bits & 0xFFFF) as f32 / 0x10000 as f32;
+
+            if roll(hash) >= chance {
+                return None;
+            }
+
+            Some(Decoration {
+                index: to_index(above) as u16,
+                kind: if roll(hash >> 16) < FLOWER_CHANCE {
+                    DecorationKind::Flower
+                } else {
+                    DecorationKind::Grass
+                },
+            })
+        })
+        .collect()
+}
+
+/// **Returns** a hash of the given chunk kinds and light, which is stable between runs and
+/// machines, so it can be persisted and compared to check if a chunk content actually changed.
+pub fn content_hash(kind: &ChunkStorage<voxel::Kind>, light: &ChunkStorage<voxel::Light>) -> u64 {
+    let kinds = kind.iter().flat_map(|&k| u16::from(k).to_le_bytes());
+    let lights = light.iter().map(|&l| u8::from(l));
+
+    voxel::fnv1a(kinds.chain(lights))
+}
+
+/// **Returns** a hash of the given chunk kinds only. Like [`content_hash`], it is stable between
+/// runs and machines.
+pub fn kind_hash(kind: &ChunkStorage<voxel::Kind>) -> u64 {
+    voxel::fnv1a(kind.iter().flat_map(|&k| u16::from(k).to_le_bytes()))
+}
+
+/// **Returns** a hash of the given hashes, in order, like content hashes of many chunks. Like
+/// [`content_hash`], it is stable between runs and machines.
+pub fn combined_hash(hashes: impl IntoIterator<Item = u64>) -> u64 {
+    voxel::fnv1a(hashes.into_iter().flat_map(u64::to_le_bytes))
+}
+
 #[cfg(test)]
 mod tests {
     use bevy::math::IVec3;
@@ -597,4 +733,89 @@ mod tests {
         let chunk = Chunk::new(-1, 9999);
         assert_eq!(chunk.path(), format!("chunk://-1_9999"));
     }
+
+    #[test]
+    fn column_summaries() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set(Voxel::new(0, 10, 0), voxel::Kind::id(1));
+        kind.set(Voxel::new(0, 5, 0), voxel::Kind::id(3));
+        kind.set(Voxel::new(1, 0, 2), voxel::Kind::id(2));
+
+        let summaries = super::column_summaries(&kind);
+
+        assert_eq!(summaries.len(), X_AXIS_SIZE * Z_AXIS_SIZE);
+        assert_eq!(
+            summaries[0],
+            ColumnSummary {
+                color: voxel::Kind::id(1).map_color(),
+                height: 10
+            },
+            "Only top most voxel should be summarized"
+        );
+        assert_eq!(summaries[Z_AXIS_SIZE + 2].height, 0);
+        assert_eq!(
+            summaries[Z_AXIS_SIZE + 2].color,
+            voxel::Kind::id(2).map_color()
+        );
+        assert_eq!(summaries[1], ColumnSummary::default(), "Empty column");
+    }
+
+    #[test]
+    fn decorations() {
+        let grass = voxel::Kind::id(2);
+        let rock = voxel::Kind::id(3);
+        assert!(grass.decoration_chance() > 0.0);
+        assert_eq!(rock.decoration_chance(), 0.0);
+
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        super::top_voxels().for_each(|top| kind.set(Voxel::new(top.x, 10, top.z), grass));
+        // Only top most voxel may be decorated.
+        kind.set(Voxel::new(0, 20, 0), rock);
+
+        let chunk = Chunk::new(3, -2);
+        let decorations = super::decorations(chunk, &kind);
+
+        assert!(!decorations.is_empty());
+        assert!(decorations.len() < X_AXIS_SIZE * Z_AXIS_SIZE);
+        assert!(decorations.iter().all(|d| d.voxel().y == 11));
+        assert_eq!(
+            decorations,
+            super::decorations(chunk, &kind),
+            "Scatter must be stable"
+        );
+        assert_ne!(
+            decorations,
+            super::decorations(Chunk::new(0, 0), &kind),
+            "Scatter depends on world position"
+        );
+    }
+
+    #[test]
+    fn content_hash() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut light = ChunkStorage::<voxel::Light>::default();
+        let hash = super::content_hash(&kind, &light);
+
+        assert_eq!(
+            hash,
+            super::content_hash(&kind, &light),
+            "Hash must be stable"
+        );
+
+        kind.set(Voxel::new(1, 2, 3), voxel::Kind::id(1));
+        let kind_hash = super::content_hash(&kind, &light);
+        assert_ne!(hash, kind_hash, "Kind changes must change hash");
+
+        light.set_type(Voxel::new(1, 2, 3), voxel::LightTy::Natural, 3);
+        assert_ne!(
+            kind_hash,
+            super::content_hash(&kind, &light),
+            "Light changes must change hash"
+        );
+        assert_eq!(
+            super::kind_hash(&kind),
+            super::kind_hash(&kind.clone()),
+            "Kind hash must be stable"
+        );
+    }
 }
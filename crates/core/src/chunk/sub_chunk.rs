@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::voxel::Voxel;
+
+use super::{
+    column::{SUB_CHUNK_COUNT, SUB_CHUNK_HEIGHT},
+    voxels, ChunkStorage, ChunkStorageType, X_AXIS_SIZE, Z_AXIS_SIZE,
+};
+
+/// Number of voxels on each sub-chunk section.
+pub const SECTION_SIZE: usize = X_AXIS_SIZE * Z_AXIS_SIZE * SUB_CHUNK_HEIGHT;
+
+/// A sub-chunk section, which is only allocated when it has any non-default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Section<T> {
+    values: Vec<T>,
+    /// Number of non-default values, so the section is freed as soon as it becomes empty.
+    non_default: u16,
+}
+
+/// Sparse version of [`ChunkStorage`], which stores a chunk as [`SUB_CHUNK_COUNT`] vertical
+/// sections. Sections with only default values, like air above the terrain, allocates nothing.
+///
+/// Sections are allocated and freed transparently by [`SubChunkStorage::set`].
+///
+/// Chunk assets store kinds this way and [`super::ChunkColumn`] is backed by it, but chunk
+/// components are still dense, since meshing and light propagation read whole chunks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubChunkStorage<T> {
+    sections: Vec<Option<Section<T>>>,
+}
+
+impl<T: ChunkStorageType> Default for SubChunkStorage<T> {
+    fn default() -> Self {
+        Self {
+            sections: vec![None; SUB_CHUNK_COUNT],
+        }
+    }
+}
+
+impl<T: ChunkStorageType> SubChunkStorage<T> {
+    pub fn get(&self, voxel: Voxel) -> T {
+        let (section, index) = to_section_index(voxel);
+        self.sections[section]
+            .as_ref()
+            .map(|section| section.values[index])
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, voxel: Voxel, value: T) {
+        let (section_index, index) = to_section_index(voxel);
+        let is_default = value == T::default();
+
+        let section = match &mut self.sections[section_index] {
+            Some(section) => section,
+            // Setting default value on an empty section changes nothing.
+            None if is_default => return,
+            none => none.insert(Section {
+                values: vec![T::default(); SECTION_SIZE],
+                non_default: 0,
+            }),
+        };
+
+        let was_default = section.values[index] == T::default();
+        section.values[index] = value;
+
+        match (was_default, is_default) {
+            (true, false) => section.non_default += 1,
+            (false, true) => section.non_default -= 1,
+            _ => (),
+        }
+
+        if section.non_default == 0 {
+            self.sections[section_index] = None;
+        }
+    }
+
+    /// **Returns** how many sections are allocated.
+    pub fn allocated_sections(&self) -> usize {
+        self.sections.iter().flatten().count()
+    }
+
+    /// Checks if the given section has only default values.
+    pub fn is_section_empty(&self, section: usize) -> bool {
+        self.sections[section].is_none()
+    }
+}
+
+impl<T: ChunkStorageType> From<&ChunkStorage<T>> for SubChunkStorage<T> {
+    fn from(storage: &ChunkStorage<T>) -> Self {
+        let mut sparse = Self::default();
+        voxels().for_each(|voxel| sparse.set(voxel, storage.get(voxel)));
+        sparse
+    }
+}
+
+impl<T: ChunkStorageType> From<&SubChunkStorage<T>> for ChunkStorage<T> {
+    fn from(sparse: &SubChunkStorage<T>) -> Self {
+        let mut storage = Self::default();
+        voxels().for_each(|voxel| storage.set(voxel, sparse.get(voxel)));
+        storage
+    }
+}
+
+/// **Returns** the section of the given voxel and its index inside that section.
+#[inline]
+fn to_section_index(voxel: Voxel) -> (usize, usize) {
+    let section = voxel.y as usize / SUB_CHUNK_HEIGHT;
+    let local_y = voxel.y as usize % SUB_CHUNK_HEIGHT;
+    let index = (voxel.x as usize * Z_AXIS_SIZE + voxel.z as usize) * SUB_CHUNK_HEIGHT + local_y;
+
+    (section, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::voxel;
+
+    use super::*;
+
+    #[test]
+    fn set_promotes_and_demotes_sections() {
+        let mut storage = SubChunkStorage::<voxel::Kind>::default();
+        assert_eq!(storage.allocated_sections(), 0);
+
+        storage.set(Voxel::new(1, 20, 3), voxel::Kind::id(1));
+        storage.set(Voxel::new(2, 21, 3), voxel::Kind::id(2));
+
+        assert_eq!(storage.allocated_sections(), 1);
+        assert!(!storage.is_section_empty(1));
+        assert_eq!(storage.get(Voxel::new(1, 20, 3)), voxel::Kind::id(1));
+        assert_eq!(storage.get(Voxel::new(1, 200, 3)), voxel::Kind::none());
+
+        storage.set(Voxel::new(1, 20, 3), voxel::Kind::none());
+        assert_eq!(storage.allocated_sections(), 1);
+
+        storage.set(Voxel::new(2, 21, 3), voxel::Kind::none());
+        assert_eq!(
+            storage.allocated_sections(),
+            0,
+            "Section should be freed once it becomes empty"
+        );
+
+        storage.set(Voxel::new(0, 0, 0), voxel::Kind::none());
+        assert_eq!(storage.allocated_sections(), 0);
+    }
+
+    #[test]
+    fn chunk_storage_conversion() {
+        let mut storage = ChunkStorage::<u8>::default();
+        storage.set(Voxel::new(15, 0, 15), 3);
+        storage.set(Voxel::new(0, 255, 7), 9);
+
+        let sparse = SubChunkStorage::from(&storage);
+
+        assert_eq!(sparse.allocated_sections(), 2);
+        assert!(sparse.is_section_empty(1));
+        assert_eq!(ChunkStorage::from(&sparse), storage);
+    }
+}
@@ -0,0 +1,124 @@
+use bevy::{reflect::Reflect, utils::HashSet};
+
+use crate::voxel::Voxel;
+
+use super::{ChunkSide, ChunkStorage, ChunkStorageType, SIDES, X_END, Z_END};
+
+/// A [`ChunkStorage`] which records voxels modified since last [`TrackedStorage::reset`], so
+/// systems can tell what actually changed, instead of assuming the whole chunk did.
+///
+/// Only [`TrackedStorage::set`] may modify values, so there is no mutable access to the inner
+/// storage.
+#[derive(Debug, Default, Clone, PartialEq, Reflect)]
+pub struct TrackedStorage<T: ChunkStorageType> {
+    storage: ChunkStorage<T>,
+    #[reflect(ignore)]
+    changed: HashSet<Voxel>,
+}
+
+impl<T: ChunkStorageType> TrackedStorage<T> {
+    pub fn new(storage: ChunkStorage<T>) -> Self {
+        Self {
+            storage,
+            changed: HashSet::default(),
+        }
+    }
+
+    /// Sets the value of the given voxel. Voxel is only recorded as changed if its value is
+    /// different than the current one.
+    pub fn set(&mut self, voxel: Voxel, value: T) {
+        if self.storage.get(voxel) != value {
+            self.storage.set(voxel, value);
+            self.changed.insert(voxel);
+        }
+    }
+
+    /// **Returns** the voxels modified since last reset, in no particular order.
+    pub fn changed(&self) -> impl Iterator<Item = Voxel> + '_ {
+        self.changed.iter().copied()
+    }
+
+    /// Checks if any voxel was modified since last reset.
+    pub fn has_changes(&self) -> bool {
+        !self.changed.is_empty()
+    }
+
+    /// **Returns** the chunk sides which have any modified voxel on its border, which means the
+    /// neighbor chunk on that side is affected by the changes.
+    pub fn changed_sides(&self) -> impl Iterator<Item = ChunkSide> + '_ {
+        SIDES.into_iter().filter(|side| {
+            self.changed.iter().any(|voxel| match side {
+                ChunkSide::Right => voxel.x == X_END,
+                ChunkSide::Left => voxel.x == 0,
+                ChunkSide::Front => voxel.z == Z_END,
+                ChunkSide::Back => voxel.z == 0,
+            })
+        })
+    }
+
+    /// Forgets all changes recorded so far.
+    pub fn reset(&mut self) {
+        self.changed.clear();
+    }
+
+    pub fn storage(&self) -> &ChunkStorage<T> {
+        &self.storage
+    }
+
+    pub fn into_inner(self) -> ChunkStorage<T> {
+        self.storage
+    }
+}
+
+impl<T: ChunkStorageType> From<ChunkStorage<T>> for TrackedStorage<T> {
+    fn from(storage: ChunkStorage<T>) -> Self {
+        Self::new(storage)
+    }
+}
+
+impl<T: ChunkStorageType> std::ops::Deref for TrackedStorage<T> {
+    type Target = ChunkStorage<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::voxel;
+
+    use super::*;
+
+    #[test]
+    fn set_records_changes() {
+        let mut storage = TrackedStorage::<voxel::Kind>::default();
+        assert!(!storage.has_changes());
+
+        storage.set(Voxel::new(3, 2, 1), voxel::Kind::none());
+        assert!(!storage.has_changes(), "Same value isn't a change");
+
+        storage.set(Voxel::new(3, 2, 1), voxel::Kind::id(1));
+        storage.set(Voxel::new(3, 2, 1), voxel::Kind::id(2));
+        assert_eq!(storage.changed().collect::<Vec<_>>(), [Voxel::new(3, 2, 1)]);
+        assert_eq!(storage.get(Voxel::new(3, 2, 1)), voxel::Kind::id(2));
+
+        storage.reset();
+        assert!(!storage.has_changes());
+        assert_eq!(storage.get(Voxel::new(3, 2, 1)), voxel::Kind::id(2));
+    }
+
+    #[test]
+    fn changed_sides() {
+        let mut storage = TrackedStorage::<voxel::Kind>::default();
+
+        storage.set(Voxel::new(5, 0, 5), voxel::Kind::id(1));
+        assert_eq!(storage.changed_sides().count(), 0);
+
+        storage.set(Voxel::new(X_END, 10, 0), voxel::Kind::id(1));
+        assert_eq!(
+            storage.changed_sides().collect::<Vec<_>>(),
+            [ChunkSide::Right, ChunkSide::Back]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Iterates multiple [`ChunkStorage`] layers of the same chunk, like kind, light and occlusion, in
+//! a single pass. Layers are walked in buffer order, which is cheaper than looking up each layer
+//! by voxel on nested loops.
+
+use crate::voxel::Voxel;
+
+use super::{from_index, ChunkStorage};
+
+/// **Returns** an iterator over each voxel and its value on both given layers.
+pub fn zip<'a, A, B>(
+    a: &'a ChunkStorage<A>,
+    b: &'a ChunkStorage<B>,
+) -> impl Iterator<Item = (Voxel, &'a A, &'a B)> {
+    a.0.iter()
+        .zip(b.0.iter())
+        .enumerate()
+        .map(|(index, (a, b))| (from_index(index), a, b))
+}
+
+/// Same as [`zip`], but the last layer is mutable.
+pub fn zip_mut<'a, A, B>(
+    a: &'a ChunkStorage<A>,
+    b: &'a mut ChunkStorage<B>,
+) -> impl Iterator<Item = (Voxel, &'a A, &'a mut B)> {
+    a.0.iter()
+        .zip(b.0.iter_mut())
+        .enumerate()
+        .map(|(index, (a, b))| (from_index(index), a, b))
+}
+
+/// **Returns** an iterator over each voxel and its value on all three given layers.
+pub fn zip3<'a, A, B, C>(
+    a: &'a ChunkStorage<A>,
+    b: &'a ChunkStorage<B>,
+    c: &'a ChunkStorage<C>,
+) -> impl Iterator<Item = (Voxel, &'a A, &'a B, &'a C)> {
+    a.0.iter()
+        .zip(b.0.iter())
+        .zip(c.0.iter())
+        .enumerate()
+        .map(|(index, ((a, b), c))| (from_index(index), a, b, c))
+}
+
+/// Same as [`zip3`], but the last layer is mutable.
+pub fn zip3_mut<'a, A, B, C>(
+    a: &'a ChunkStorage<A>,
+    b: &'a ChunkStorage<B>,
+    c: &'a mut ChunkStorage<C>,
+) -> impl Iterator<Item = (Voxel, &'a A, &'a B, &'a mut C)> {
+    a.0.iter()
+        .zip(b.0.iter())
+        .zip(c.0.iter_mut())
+        .enumerate()
+        .map(|(index, ((a, b), c))| (from_index(index), a, b, c))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{chunk, voxel};
+
+    use super::*;
+
+    #[test]
+    fn zip_matches_get() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut light = ChunkStorage::<u8>::default();
+        let mut height = ChunkStorage::<u8>::default();
+        kind.set(Voxel::new(3, 40, 9), voxel::Kind::id(2));
+        light.set(Voxel::new(3, 40, 9), 7);
+
+        zip3_mut(&kind, &light, &mut height).for_each(|(voxel, _, _, height)| {
+            *height = voxel.y as u8;
+        });
+
+        assert_eq!(zip(&kind, &light).count(), chunk::BUFFER_SIZE);
+        assert!(zip3(&kind, &light, &height).all(|(voxel, &k, &l, &h)| {
+            k == kind.get(voxel) && l == light.get(voxel) && h == voxel.y as u8
+        }));
+    }
+}
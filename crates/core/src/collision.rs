@@ -0,0 +1,270 @@
+use bevy::math::{BVec3, Vec3};
+
+use crate::{chunk::Chunk, math, raycast::solid_at, voxel::Voxel};
+
+/// Gap kept between a moving box and the voxel it collided with, to avoid being stuck on floating
+/// point errors.
+const SKIN: f32 = 0.001;
+/// Max distance moved at once, so fast moving boxes don't tunnel through voxels.
+const MAX_SUB_STEP: f32 = 0.5;
+
+/// Outcome of [`move_and_slide`].
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct MoveResult {
+    /// Final position of the box center.
+    pub position: Vec3,
+    /// Which axis had the motion blocked by a voxel.
+    pub collided: BVec3,
+    /// Whether the box was raised to climb over an obstacle.
+    pub stepped_up: bool,
+}
+
+/// Checks if an axis aligned box, centered at `position`, overlaps the solid part of any voxel, as
+/// given by `solid_height`. See [`solid_at`].
+pub fn overlaps(
+    position: Vec3,
+    half_extents: Vec3,
+    solid_height: &mut impl FnMut(Chunk, Voxel) -> f32,
+) -> bool {
+    overlapping_top(position, half_extents, solid_height).is_some()
+}
+
+/// **Returns** the top of the highest solid part overlapped by the box, if any. Partial voxels,
+/// like layers, only fill the bottom of the voxel, so boxes above their top don't overlap them.
+fn overlapping_top(
+    position: Vec3,
+    half_extents: Vec3,
+    solid_height: &mut impl FnMut(Chunk, Voxel) -> f32,
+) -> Option<f32> {
+    let bottom = position.y - half_extents.y + SKIN * 0.1;
+    let min = math::floor(position - half_extents + SKIN * 0.1);
+    let max = math::floor(position + half_extents - SKIN * 0.1);
+
+    let mut highest = None;
+    for y in min.y..=max.y {
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                if let Some((_, _, height)) = solid_at((x, y, z).into(), solid_height) {
+                    let top = y as f32 + height;
+                    if top > bottom {
+                        highest = Some(highest.map_or(top, |highest: f32| highest.max(top)));
+                    }
+                }
+            }
+        }
+    }
+
+    highest
+}
+
+/// Moves an axis aligned box, centered at `position`, by `motion`, one axis at a time, stopping
+/// each axis when it hits the solid part of a voxel, as given by `solid_height`, so the box slides
+/// along walls and floors and lands on top of layers.
+///
+/// When horizontal motion is blocked and there is enough room, the box is raised by up to
+/// `step_height` to climb over the obstacle, at most once per call. Use zero to disable it.
+///
+/// **Returns** the final position and which axis collided.
+pub fn move_and_slide(
+    position: Vec3,
+    half_extents: Vec3,
+    motion: Vec3,
+    mut step_height: f32,
+    mut solid_height: impl FnMut(Chunk, Voxel) -> f32,
+) -> MoveResult {
+    let sub_steps = (motion.abs().max_element() / MAX_SUB_STEP).ceil().max(1.0);
+    let sub_motion = motion / sub_steps;
+
+    let mut position = position;
+    let mut collided = [false; 3];
+    let mut stepped_up = false;
+
+    for _ in 0..sub_steps as usize {
+        // Vertical axis first, so the box lands before sliding horizontally.
+        for axis in [1, 0, 2] {
+            let delta = sub_motion[axis];
+            if delta == 0.0 || collided[axis] {
+                continue;
+            }
+
+            let mut target = position;
+            target[axis] += delta;
+
+            let Some(top) = overlapping_top(target, half_extents, &mut solid_height) else {
+                position = target;
+                continue;
+            };
+
+            if axis != 1 && step_height > 0.0 {
+                let raised = target + Vec3::Y * step_height;
+                let above = position + Vec3::Y * step_height;
+
+                if !overlaps(above, half_extents, &mut solid_height)
+                    && !overlaps(raised, half_extents, &mut solid_height)
+                {
+                    position = raised;
+                    stepped_up = true;
+                    step_height = 0.0;
+                    continue;
+                }
+            }
+
+            // Snap against the face of the voxel which blocked the motion.
+            target[axis] = if axis == 1 && delta < 0.0 {
+                // Floors may be partial voxels, so box lands on the top of their solid part.
+                top + half_extents.y + SKIN
+            } else if delta > 0.0 {
+                (target[axis] + half_extents[axis]).floor() - half_extents[axis] - SKIN
+            } else {
+                (target[axis] - half_extents[axis]).floor() + 1.0 + half_extents[axis] + SKIN
+            };
+
+            if !overlaps(target, half_extents, &mut solid_height) {
+                position = target;
+            }
+
+            collided[axis] = true;
+        }
+    }
+
+    MoveResult {
+        position,
+        collided: BVec3::new(collided[0], collided[1], collided[2]),
+        stepped_up,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HALF_EXTENTS: Vec3 = Vec3::new(0.25, 1.0, 0.25);
+
+    fn solid(is_solid: bool) -> f32 {
+        if is_solid {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn floor_at(height: i32) -> impl FnMut(Chunk, Voxel) -> f32 {
+        move |_, voxel| solid(voxel.y <= height)
+    }
+
+    #[test]
+    fn overlaps() {
+        assert!(super::overlaps(
+            Vec3::new(0.5, 10.5, 0.5),
+            HALF_EXTENTS,
+            &mut floor_at(9)
+        ));
+        assert!(
+            !super::overlaps(Vec3::new(0.5, 11.0, 0.5), HALF_EXTENTS, &mut floor_at(9)),
+            "Touching a voxel face isn't overlapping"
+        );
+    }
+
+    #[test]
+    fn move_and_slide_land() {
+        let result = super::move_and_slide(
+            Vec3::new(0.5, 12.0, 0.5),
+            HALF_EXTENTS,
+            Vec3::new(0.0, -5.0, 0.0),
+            0.0,
+            floor_at(9),
+        );
+
+        assert!(result.collided.y);
+        assert!((result.position.y - 11.0).abs() < 0.01, "{result:?}");
+    }
+
+    #[test]
+    fn move_and_slide_land_on_layer() {
+        let layer = |_: Chunk, voxel: Voxel| match voxel.y {
+            ..=9 => 1.0,
+            10 => 0.125,
+            _ => 0.0,
+        };
+
+        let result = super::move_and_slide(
+            Vec3::new(0.5, 12.0, 0.5),
+            HALF_EXTENTS,
+            Vec3::new(0.0, -5.0, 0.0),
+            0.0,
+            layer,
+        );
+
+        assert!(result.collided.y);
+        assert!(
+            (result.position.y - 11.125).abs() < 0.01,
+            "Should stand on layer top: {result:?}"
+        );
+    }
+
+    #[test]
+    fn move_and_slide_free() {
+        let motion = Vec3::new(1.0, 2.0, -3.0);
+        let result = super::move_and_slide(
+            Vec3::new(0.5, 20.0, 0.5),
+            HALF_EXTENTS,
+            motion,
+            0.0,
+            floor_at(9),
+        );
+
+        assert_eq!(result.collided, BVec3::FALSE);
+        assert!(result.position.distance(Vec3::new(0.5, 20.0, 0.5) + motion) < 0.001);
+    }
+
+    #[test]
+    fn move_and_slide_wall() {
+        let wall = |_: Chunk, voxel: Voxel| solid(voxel.x >= 2 || voxel.y <= 9);
+
+        let result = super::move_and_slide(
+            Vec3::new(0.5, 11.01, 0.5),
+            HALF_EXTENTS,
+            Vec3::new(3.0, 0.0, 1.0),
+            0.0,
+            wall,
+        );
+
+        assert!(result.collided.x);
+        assert!(!result.collided.z, "Should slide along the wall");
+        assert!((result.position.x - 1.75).abs() < 0.01, "{result:?}");
+        assert!((result.position.z - 1.5).abs() < 0.01, "{result:?}");
+    }
+
+    #[test]
+    fn move_and_slide_step_up() {
+        let step = |_: Chunk, voxel: Voxel| solid(voxel.y <= 9 || (voxel.x >= 2 && voxel.y == 10));
+
+        let result = super::move_and_slide(
+            Vec3::new(1.5, 11.01, 0.5),
+            HALF_EXTENTS,
+            Vec3::new(0.5, 0.0, 0.0),
+            1.0,
+            step,
+        );
+
+        assert!(result.stepped_up);
+        assert!(!result.collided.x);
+        assert!((result.position.y - 12.01).abs() < 0.01, "{result:?}");
+    }
+
+    #[test]
+    fn move_and_slide_no_step_up_on_high_wall() {
+        let wall = |_: Chunk, voxel: Voxel| solid(voxel.y <= 9 || (voxel.x >= 2 && voxel.y <= 11));
+
+        let result = super::move_and_slide(
+            Vec3::new(1.5, 11.01, 0.5),
+            HALF_EXTENTS,
+            Vec3::new(0.5, 0.0, 0.0),
+            1.0,
+            wall,
+        );
+
+        assert!(!result.stepped_up);
+        assert!(result.collided.x);
+    }
+}
@@ -1,5 +1,8 @@
 pub mod chunk;
+pub mod collision;
 pub mod landscape;
 pub mod math;
 // pub mod query;
+pub mod raycast;
+pub mod time;
 pub mod voxel;
@@ -0,0 +1,261 @@
+use bevy::math::{IVec3, Vec3};
+
+use crate::{
+    chunk::{self, Chunk},
+    math,
+    voxel::{self, Voxel},
+};
+
+/// Describes which voxel was hit by a [`raycast`] and where.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// Chunk which contains the hit voxel.
+    pub chunk: Chunk,
+    /// Local voxel position inside [`RaycastHit::chunk`].
+    pub voxel: Voxel,
+    /// Voxel side which was hit by the ray.
+    pub side: voxel::Side,
+    /// World position where the ray hit the voxel.
+    pub position: Vec3,
+    /// Distance from ray origin until the hit position.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// **Returns** the world position of the hit voxel.
+    pub fn world(&self) -> IVec3 {
+        math::floor(voxel::to_world(self.voxel, self.chunk))
+    }
+
+    /// **Returns** the chunk and voxel adjacent to the hit side, which is where a new voxel should
+    /// be placed.
+    pub fn adjacent(&self) -> Option<(Chunk, Voxel)> {
+        to_chunk_voxel(self.world() + self.side.dir())
+    }
+}
+
+/// Converts a world voxel position into a chunk and local voxel position.
+///
+/// **Returns** [`None`] if given world voxel is above or below the world.
+pub fn to_chunk_voxel(world: IVec3) -> Option<(Chunk, Voxel)> {
+    if world.y < 0 || world.y >= chunk::Y_AXIS_SIZE as i32 {
+        return None;
+    }
+
+    let world = world.as_vec3();
+    Some((chunk::to_chunk(world), voxel::to_local(world)))
+}
+
+/// Gets the height of the solid part of the voxel at the given world position, from its bottom, by
+/// calling `solid_height`. Voxels above or below the world are never solid.
+///
+/// **Returns** the chunk and local voxel position of the voxel, with its solid height, if it is
+/// solid.
+pub fn solid_at(
+    world: IVec3,
+    solid_height: &mut impl FnMut(Chunk, Voxel) -> f32,
+) -> Option<(Chunk, Voxel, f32)> {
+    let (chunk, voxel) = to_chunk_voxel(world)?;
+    let height = solid_height(chunk, voxel).min(1.0);
+
+    (height > 0.0).then_some((chunk, voxel, height))
+}
+
+/// Walks the voxel grid from `origin` towards `dir` until it hits a solid voxel or until `range` is
+/// reached, based on [A Fast Voxel Traversal Algorithm](http://www.cse.yorku.ca/~amana/research/grid.pdf).
+/// Partial voxels, like layers, are only hit on their solid part. See [`solid_at`].
+///
+/// **Returns** the first voxel hit by the ray, if any.
+pub fn raycast(
+    origin: Vec3,
+    dir: Vec3,
+    range: f32,
+    mut solid_height: impl FnMut(Chunk, Voxel) -> f32,
+) -> Option<RaycastHit> {
+    let dir = dir.normalize_or_zero();
+    if dir == Vec3::ZERO {
+        return None;
+    }
+
+    let mut current = math::floor(origin);
+    let step = dir.signum().as_ivec3();
+
+    let axis_t = |axis: usize| {
+        if dir[axis] == 0.0 {
+            (f32::INFINITY, f32::INFINITY)
+        } else {
+            let boundary = (current[axis] + step[axis].max(0)) as f32;
+            (
+                (boundary - origin[axis]) / dir[axis],
+                dir[axis].abs().recip(),
+            )
+        }
+    };
+
+    let (mut t_max, t_delta) = {
+        let (x, y, z) = (axis_t(0), axis_t(1), axis_t(2));
+        (Vec3::new(x.0, y.0, z.0), Vec3::new(x.1, y.1, z.1))
+    };
+
+    // When the origin is already inside a solid voxel, the hit side is the one facing the ray.
+    let mut side = match math::abs_max_element(dir) {
+        math::Vec3Element::X => voxel::Side::from_dir(IVec3::X * -step.x),
+        math::Vec3Element::Y => voxel::Side::from_dir(IVec3::Y * -step.y),
+        math::Vec3Element::Z => voxel::Side::from_dir(IVec3::Z * -step.z),
+    };
+    let mut distance = 0.0;
+
+    while distance <= range {
+        if let Some((chunk, voxel, height)) = solid_at(current, &mut solid_height) {
+            let top = current.y as f32 + height;
+            let hit = if height >= 1.0 || origin.y + dir.y * distance <= top {
+                Some((distance, side))
+            } else if dir.y < 0.0 {
+                // Ray entered above the solid part, so it may still go down through its top before
+                // leaving the voxel.
+                let t = (top - origin.y) / dir.y;
+                (t <= t_max.min_element() && t <= range).then_some((t, voxel::Side::Up))
+            } else {
+                None
+            };
+
+            if let Some((distance, side)) = hit {
+                return Some(RaycastHit {
+                    chunk,
+                    voxel,
+                    side,
+                    position: origin + dir * distance,
+                    distance,
+                });
+            }
+        }
+
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+
+        current[axis] += step[axis];
+        distance = t_max[axis];
+        t_max[axis] += t_delta[axis];
+
+        let mut entered_dir = IVec3::ZERO;
+        entered_dir[axis] = -step[axis];
+        side = voxel::Side::from_dir(entered_dir);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor_at(height: i32) -> impl FnMut(Chunk, Voxel) -> f32 {
+        move |_, voxel| if voxel.y <= height { 1.0 } else { 0.0 }
+    }
+
+    #[test]
+    fn raycast_down() {
+        let hit = super::raycast(Vec3::new(0.5, 20.5, 0.5), Vec3::NEG_Y, 100.0, floor_at(10))
+            .expect("Should hit the floor");
+
+        assert_eq!(hit.chunk, Chunk::new(0, 0));
+        assert_eq!(hit.voxel, Voxel::new(0, 10, 0));
+        assert_eq!(hit.side, voxel::Side::Up);
+        assert!((hit.distance - 9.5).abs() < f32::EPSILON);
+        assert_eq!(
+            hit.adjacent(),
+            Some((Chunk::new(0, 0), Voxel::new(0, 11, 0)))
+        );
+    }
+
+    #[test]
+    fn raycast_out_of_range() {
+        let hit = super::raycast(Vec3::new(0.5, 20.5, 0.5), Vec3::NEG_Y, 5.0, floor_at(10));
+
+        assert_eq!(hit, None, "Floor is beyond ray range");
+    }
+
+    #[test]
+    fn raycast_no_dir() {
+        let hit = super::raycast(Vec3::new(0.5, 20.5, 0.5), Vec3::ZERO, 5.0, |_, _| 1.0);
+
+        assert_eq!(hit, None, "There should be no hit without a direction");
+    }
+
+    #[test]
+    fn raycast_neighbor_chunk() {
+        let wall = |chunk: Chunk, voxel: Voxel| {
+            if chunk == Chunk::new(-1, 0) && voxel.x == 10 {
+                1.0
+            } else {
+                0.0
+            }
+        };
+
+        let hit = super::raycast(Vec3::new(1.5, 5.5, 1.5), Vec3::NEG_X, 100.0, wall)
+            .expect("Should hit the wall on left chunk");
+
+        assert_eq!(hit.chunk, Chunk::new(-1, 0));
+        assert_eq!(hit.voxel, Voxel::new(10, 5, 1));
+        assert_eq!(hit.side, voxel::Side::Right);
+        assert_eq!(hit.world(), IVec3::new(-6, 5, 1));
+        assert_eq!(
+            hit.adjacent(),
+            Some((Chunk::new(-1, 0), Voxel::new(11, 5, 1)))
+        );
+    }
+
+    #[test]
+    fn raycast_diagonal() {
+        let hit = super::raycast(
+            Vec3::new(0.5, 20.5, 0.5),
+            Vec3::new(1.0, -1.0, 1.0),
+            100.0,
+            floor_at(10),
+        )
+        .expect("Should hit the floor");
+
+        assert_eq!(hit.voxel.y, 10);
+        assert_eq!(hit.side, voxel::Side::Up);
+    }
+
+    #[test]
+    fn raycast_layer() {
+        let layer = |_: Chunk, voxel: Voxel| if voxel.y == 10 { 0.25 } else { 0.0 };
+
+        let hit = super::raycast(Vec3::new(0.5, 20.5, 0.5), Vec3::NEG_Y, 100.0, layer)
+            .expect("Should hit the layer top");
+        assert_eq!(hit.voxel, Voxel::new(0, 10, 0));
+        assert_eq!(hit.side, voxel::Side::Up);
+        assert!((hit.position.y - 10.25).abs() < 1e-4, "{hit:?}");
+
+        let hit = super::raycast(Vec3::new(0.5, 10.5, 0.5), Vec3::X, 3.0, layer);
+        assert_eq!(hit, None, "Ray should pass over the layer");
+    }
+
+    #[test]
+    fn raycast_above_world() {
+        let hit = super::raycast(
+            Vec3::new(0.5, chunk::Y_AXIS_SIZE as f32 + 10.0, 0.5),
+            Vec3::Y,
+            100.0,
+            |_, _| 1.0,
+        );
+
+        assert_eq!(hit, None, "There is no voxel above the world");
+    }
+
+    #[test]
+    fn to_chunk_voxel() {
+        assert_eq!(
+            super::to_chunk_voxel(IVec3::new(-1, 3, 17)),
+            Some((Chunk::new(-1, 1), Voxel::new(chunk::X_END, 3, 1)))
+        );
+        assert_eq!(super::to_chunk_voxel(IVec3::new(0, -1, 0)), None);
+    }
+}
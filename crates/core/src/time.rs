@@ -0,0 +1,92 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+/// Time of the day on the world, which drives day and night cycle. Server advances it and syncs it
+/// to clients, which advance it locally between syncs.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct WorldTime {
+    /// Current time of the day, in range [0.0, 1.0), where 0.0 is midnight and 0.5 is noon.
+    pub day_time: f32,
+    /// Duration of a full day, in seconds. Must be positive, see
+    /// [`WorldTime::is_valid_day_length`].
+    pub day_length: f32,
+}
+
+impl Default for WorldTime {
+    fn default() -> Self {
+        Self {
+            day_time: 0.3,
+            day_length: 20.0 * 60.0,
+        }
+    }
+}
+
+impl WorldTime {
+    /// **Returns** `true` if the given day length can be used to advance time, which means it is
+    /// finite and positive.
+    pub fn is_valid_day_length(day_length: f32) -> bool {
+        day_length.is_finite() && day_length > 0.0
+    }
+
+    /// Advances current time of the day by `delta` seconds, wrapping around at midnight.
+    pub fn advance(&mut self, delta: f32) {
+        debug_assert!(
+            Self::is_valid_day_length(self.day_length),
+            "Invalid day length {}",
+            self.day_length
+        );
+        self.day_time = (self.day_time + delta / self.day_length).rem_euclid(1.0);
+    }
+
+    /// **Returns** the direction pointing towards the sun. The sun rises at 0.25 and sets at 0.75.
+    pub fn sun_dir(&self) -> Vec3 {
+        let angle = (self.day_time - 0.25) * TAU;
+        Vec3::new(angle.cos(), angle.sin(), 0.2).normalize()
+    }
+
+    /// **Returns** how much of daylight reaches the world, from 0.0 at night to 1.0 at day.
+    pub fn daylight(&self) -> f32 {
+        smoothstep(-0.1, 0.2, self.sun_dir().y)
+    }
+
+    /// **Returns** how close the sun is to the horizon, from 0.0 to 1.0 at dusk and dawn.
+    pub fn twilight(&self) -> f32 {
+        (1.0 - self.sun_dir().y.abs() / 0.3).clamp(0.0, 1.0)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_wraps_at_midnight() {
+        let mut world_time = WorldTime {
+            day_time: 0.9,
+            day_length: 100.0,
+        };
+
+        world_time.advance(20.0);
+
+        assert!(
+            (world_time.day_time - 0.1).abs() < 0.0001,
+            "Should wrap around to next day, got {}",
+            world_time.day_time
+        );
+    }
+
+    #[test]
+    fn valid_day_length() {
+        assert!(WorldTime::is_valid_day_length(1.0));
+        assert!(!WorldTime::is_valid_day_length(0.0));
+        assert!(!WorldTime::is_valid_day_length(-10.0));
+        assert!(!WorldTime::is_valid_day_length(f32::NAN));
+        assert!(!WorldTime::is_valid_day_length(f32::INFINITY));
+    }
+}
@@ -1,12 +1,17 @@
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::atomic::{AtomicPtr, Ordering},
+};
 
-use bevy::{log::trace, math::IVec2};
-use once_cell::sync::OnceCell;
+use bevy::{log::trace, math::IVec2, reflect::Reflect};
 use serde::{Deserialize, Serialize};
 
 use super::{Face, Side};
 
-static KINDS_DESCS: OnceCell<KindsDescs> = OnceCell::new();
+/// Global [`KindsDescs`] reference. Descriptions are never freed, since [`KindsDescs::get`] hands
+/// out `'static` references, so each [`KindsDescs::reload`] leaks the previous one. This is fine
+/// since reloading only happens when assets are edited during development.
+static KINDS_DESCS: AtomicPtr<KindsDescs> = AtomicPtr::new(std::ptr::null_mut());
 
 /// Describes what color and offset on texture atlas to be used.
 #[derive(Debug, Copy, Clone, Deserialize, Default)]
@@ -48,6 +53,30 @@ pub enum KindLightDesc {
     Emitter(u8),
 }
 
+/// Describes which sounds are played when walking on, breaking or placing this kind.
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq, Eq, Hash)]
+pub enum KindSoundDesc {
+    /// No sound at all
+    #[default]
+    None,
+    Dirt,
+    Grass,
+    Stone,
+    Glass,
+    Water,
+}
+
+/// Describes the shape of a voxel of this kind.
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq)]
+pub enum KindShapeDesc {
+    /// Fills the whole voxel.
+    #[default]
+    Full,
+    /// Thin layer resting on voxel bottom, like snow, with the given height in range
+    /// [0.0 ~ 1.0]. Layers need a voxel below them to rest on.
+    Layer(f32),
+}
+
 // TODO: Find a better way to describe this
 #[derive(Debug, Clone, Deserialize, Default)]
 pub enum KindSourceDesc {
@@ -66,6 +95,26 @@ pub struct KindDescItem {
     pub sides: KindSidesDesc,
     pub light: KindLightDesc,
     pub source: KindSourceDesc,
+    /// Solid kinds which doesn't hide neighbor faces, like glass or leaves.
+    #[serde(default)]
+    pub transparent: bool,
+    /// Kinds which entities can swim through, like water.
+    #[serde(default)]
+    pub liquid: bool,
+    #[serde(default)]
+    pub shape: KindShapeDesc,
+    #[serde(default)]
+    pub sound: KindSoundDesc,
+    /// RGB Color in scalar range [0.0 ~ 1.0] used when drawing this kind on maps.
+    #[serde(default)]
+    pub map_color: (f32, f32, f32),
+    /// Chance, in scalar range [0.0 ~ 1.0], of a decoration, like grass tufts or flowers, being
+    /// scattered on top of this kind.
+    #[serde(default)]
+    pub decoration: f32,
+    /// Time, in seconds, a player takes to break this kind. Kinds without hardness break at once.
+    #[serde(default)]
+    pub hardness: f32,
 }
 
 /// Holds a list of [`KindDescItem`] and other global data.
@@ -84,6 +133,12 @@ impl KindsDescs {
         self.atlas_size / self.atlas_tile_size
     }
 
+    /// **Returns** the layer of the given atlas tile offset, when atlas is sliced into a texture
+    /// array. Tiles are laid out row by row.
+    pub fn tile_layer(&self, offset: IVec2) -> u32 {
+        (offset.y * self.count_tiles() as i32 + offset.x) as u32
+    }
+
     /// **Returns** how a given face should be rendered
     pub fn get_face_desc(&self, face: &Face) -> KindSideTexture {
         let kind_desc = self
@@ -118,39 +173,100 @@ impl KindsDescs {
     /// This function should be first called on a controlled context to avoid blocking.
     /// Subsequent calls just get a static reference from loaded struct.
     pub fn get() -> &'static Self {
-        #[cfg(feature = "auto_load_kinds_descs")]
-        if KINDS_DESCS.get().is_none() {
-            return Self::init(format!("{}/voxels/kind.ron", env!("ASSETS_PATH")));
+        #[cfg(all(feature = "auto_load_kinds_descs", not(target_arch = "wasm32")))]
+        if KINDS_DESCS.load(Ordering::Acquire).is_null() {
+            return Self::init(Self::default_path());
         }
 
-        KINDS_DESCS
-            .get()
+        // There is no file system on browsers, so the descriptions embedded on binary are used.
+        #[cfg(all(feature = "auto_load_kinds_descs", target_arch = "wasm32"))]
+        if KINDS_DESCS.load(Ordering::Acquire).is_null() {
+            return Self::init_from_str(include_str!(concat!(
+                env!("ASSETS_PATH"),
+                "/voxels/kind.ron"
+            )));
+        }
+
+        // SAFETY: Pointer is either null or points to a leaked `Box`, which is never freed.
+        unsafe { KINDS_DESCS.load(Ordering::Acquire).as_ref() }
             .expect("KindsDescs should be initialized before used")
     }
 
+    /// **Returns** the path of kinds descriptions file inside assets folder.
+    pub fn default_path() -> String {
+        format!("{}/voxels/kind.ron", env!("ASSETS_PATH"))
+    }
+
     pub fn init(path: impl AsRef<Path>) -> &'static Self {
         trace!(
             "Loading kinds descriptions on path {:?}",
             path.as_ref().as_os_str()
         );
-        match std::fs::File::open(&path) {
-            Ok(file) => {
-                let kinds_descs: KindsDescs = ron::de::from_reader(file).unwrap();
-                KINDS_DESCS.set(kinds_descs).ok();
-                Self::get()
-            }
+        match Self::read(&path) {
+            Ok(kinds_descs) => Self::set(kinds_descs),
             Err(e) => {
                 let path = path.as_ref().to_str().unwrap();
                 panic!("Failed to init kinds descriptions on path {path}. Error: {e}");
             }
         }
     }
+
+    /// Same as [`KindsDescs::init`], but parses the given ron contents instead of reading a file,
+    /// which is needed on targets without file system access.
+    pub fn init_from_str(ron: &str) -> &'static Self {
+        trace!("Loading kinds descriptions from memory");
+        match ron::from_str(ron) {
+            Ok(kinds_descs) => Self::set(kinds_descs),
+            Err(e) => panic!("Failed to init kinds descriptions from memory. Error: {e}"),
+        }
+    }
+
+    /// Sets the global [`KindsDescs`], if it wasn't set yet.
+    fn set(kinds_descs: Self) -> &'static Self {
+        let ptr = Box::into_raw(Box::new(kinds_descs));
+        if KINDS_DESCS
+            .compare_exchange(
+                std::ptr::null_mut(),
+                ptr,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // SAFETY: Pointer was just created and wasn't shared, since it failed to be
+            // set.
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+        Self::get()
+    }
+
+    /// Reads the ron file on the given path and replaces the global [`KindsDescs`], so subsequent
+    /// calls to [`KindsDescs::get`] returns the new descriptions. Current descriptions are kept if
+    /// the file can't be read.
+    pub fn reload(path: impl AsRef<Path>) -> Result<&'static Self, String> {
+        trace!(
+            "Reloading kinds descriptions on path {:?}",
+            path.as_ref().as_os_str()
+        );
+        let kinds_descs = Self::read(path)?;
+        KINDS_DESCS.store(Box::into_raw(Box::new(kinds_descs)), Ordering::Release);
+        Ok(Self::get())
+    }
+
+    fn read(path: impl AsRef<Path>) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        ron::de::from_reader(file).map_err(|e| e.to_string())
+    }
 }
 
-/// Kind id reference.
+/// Number of levels a liquid voxel may have. A full liquid voxel has this level.
+pub const LIQUID_LEVELS: u8 = 8;
+
 /// This function uses [`KindsDescs`] to determine how this kind should behave.
 /// May panic if current kind id doesn't exists on [`KindsDescs`].
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Default, Deserialize, Serialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Default, Deserialize, Serialize, Reflect,
+)]
 pub struct Kind(u16);
 
 impl From<u16> for Kind {
@@ -166,6 +282,9 @@ impl From<Kind> for u16 {
 }
 
 impl Kind {
+    /// The empty (air) [`Kind`], which has id 0.
+    pub const NONE: Kind = Kind(0);
+
     /// Creates a new [`Kind`] with the given id
     pub fn id(id: u16) -> Self {
         Kind(id)
@@ -173,17 +292,103 @@ impl Kind {
 
     /// Creates a new [`Kind`] with id 0.
     pub fn none() -> Self {
-        Kind(0)
+        Self::NONE
+    }
+
+    /// Checks if current kind id exists on [`KindsDescs`]. Useful to validate ids received from
+    /// untrusted sources before calling any other function, since those may panic.
+    pub fn exists(&self) -> bool {
+        KindsDescs::get()
+            .descriptions
+            .iter()
+            .any(|desc| desc.id == self.0)
     }
 
     // TODO: Find better name, since this is similar to Option<T>::is_none
     /// Checks if current kind is the None [`Kind`], which has id 0.
     pub fn is_none(&self) -> bool {
-        self.0 == 0
+        *self == Self::NONE
     }
 
-    /// Checks if current kind is [`KindLightDesc::Opaque`].
+    /// Checks if current kind occupies space on the voxel world, which means it is rendered and
+    /// collides with other entities. Air, which has [`KindSidesDesc::None`], is never solid.
+    pub fn is_solid(&self) -> bool {
+        !self.is_none() && !matches!(self.desc().sides, KindSidesDesc::None)
+    }
+
+    /// Checks if current kind is solid and fully hides neighbor faces.
+    /// Transparent solids, like glass, are not opaque.
     pub fn is_opaque(&self) -> bool {
+        self.is_solid() && !self.desc().transparent
+    }
+
+    /// Checks if current kind is a liquid, which entities can swim through.
+    pub fn is_liquid(&self) -> bool {
+        self.desc().liquid
+    }
+
+    /// **Returns** the level of this liquid, in range [1 ~ [`LIQUID_LEVELS`]], or zero if this kind
+    /// isn't a liquid. Liquids don't flow, so they are full when covered by the same liquid and a
+    /// level short otherwise, which lowers their surface.
+    pub fn liquid_level(&self, above: Kind) -> u8 {
+        if !self.is_liquid() {
+            0
+        } else if above == *self {
+            LIQUID_LEVELS
+        } else {
+            LIQUID_LEVELS - 1
+        }
+    }
+
+    /// Checks if current kind is a [`KindShapeDesc::Layer`], which rests on the voxel below it.
+    pub fn is_layer(&self) -> bool {
+        matches!(self.desc().shape, KindShapeDesc::Layer(_))
+    }
+
+    /// **Returns** the height of this kind shape, in range [0.0 ~ 1.0]. Full voxels have height
+    /// 1.0.
+    pub fn shape_height(&self) -> f32 {
+        match self.desc().shape {
+            KindShapeDesc::Full => 1.0,
+            KindShapeDesc::Layer(height) => height.clamp(0.0, 1.0),
+        }
+    }
+
+    /// **Returns** the height of the solid part of this kind, from voxel bottom, or zero if it
+    /// isn't solid. Layers are only solid up to their [`shape_height`](Self::shape_height).
+    pub fn solid_height(&self) -> f32 {
+        if self.is_solid() {
+            self.shape_height()
+        } else {
+            0.0
+        }
+    }
+
+    /// **Returns** which sounds should be played when interacting with this kind.
+    pub fn sound(&self) -> KindSoundDesc {
+        self.desc().sound
+    }
+
+    /// **Returns** the RGB color used when drawing this kind on maps.
+    pub fn map_color(&self) -> [u8; 3] {
+        let (r, g, b) = self.desc().map_color;
+        [r, g, b].map(|c| (c.clamp(0.0, 1.0) * u8::MAX as f32) as u8)
+    }
+
+    /// **Returns** the chance of a decoration being scattered on top of this kind.
+    pub fn decoration_chance(&self) -> f32 {
+        self.desc().decoration.clamp(0.0, 1.0)
+    }
+
+    /// **Returns** the time, in seconds, a player takes to break this kind, or zero if it breaks at
+    /// once.
+    pub fn break_secs(&self) -> f32 {
+        self.desc().hardness.max(0.0)
+    }
+
+    /// Checks if current kind is [`KindLightDesc::Opaque`], which means light can't propagate
+    /// through it.
+    pub fn blocks_light(&self) -> bool {
         matches!(self.desc().light, KindLightDesc::Opaque)
     }
 
@@ -201,15 +406,13 @@ impl Kind {
         }
     }
 
-    // TODO: rework this, to use noise layers and get generated kind for each layer
-    pub fn get_kind_with_height_source(surface: usize, height: usize) -> Self {
-        let depth = height as i32 - surface as i32;
-
-        match depth {
-            0 => Kind(2),
-            depth if (-3..=-1).contains(&depth) => Kind(1),
-            _ => Kind(3),
-        }
+    /// **Returns** the kind with the given name on [`KindsDescs`], if there is any.
+    pub fn by_name(name: &str) -> Option<Self> {
+        KindsDescs::get()
+            .descriptions
+            .iter()
+            .find(|desc| desc.name == name)
+            .map(|desc| Kind(desc.id))
     }
 
     /// Get the [`KindDescItem`] corresponding to this kind id.
@@ -233,9 +436,113 @@ mod tests {
 
     #[test]
     fn load_kind_descriptions() {
-        let input_path = format!("{}/voxels/kind.ron", env!("ASSETS_PATH"));
+        let input_path = KindsDescs::default_path();
         let f = std::fs::File::open(input_path).expect("Failed opening kind descriptions file");
 
         let _: KindsDescs = from_reader(f).unwrap();
     }
+
+    #[test]
+    fn reload_kind_descriptions() {
+        let count = KindsDescs::get().descriptions.len();
+
+        let reloaded = KindsDescs::reload(KindsDescs::default_path()).unwrap();
+
+        assert_eq!(reloaded.descriptions.len(), count);
+        assert!(
+            std::ptr::eq(reloaded, KindsDescs::get()),
+            "Reloaded descriptions should replace global ones"
+        );
+        assert!(
+            KindsDescs::reload("invalid/kind.ron").is_err(),
+            "Invalid path should keep current descriptions"
+        );
+        assert!(std::ptr::eq(reloaded, KindsDescs::get()));
+    }
+
+    #[test]
+    fn none_kind() {
+        let none = Kind::NONE;
+
+        assert!(none.is_none());
+        assert_eq!(none, Kind::none());
+        assert_eq!(none, Kind::default());
+        assert!(!none.is_solid(), "Air should never be solid");
+        assert!(!none.is_opaque(), "Air should never be opaque");
+        assert!(!none.blocks_light(), "Air should never block light");
+        assert_eq!(none.sound(), KindSoundDesc::None);
+    }
+
+    #[test]
+    fn opaque_kind() {
+        let dirt = Kind::id(1);
+
+        assert!(!dirt.is_none());
+        assert!(dirt.is_solid());
+        assert!(dirt.is_opaque());
+        assert!(dirt.blocks_light());
+        assert!(!dirt.is_liquid());
+        assert_eq!(dirt.sound(), KindSoundDesc::Dirt);
+        assert_ne!(dirt.map_color(), [0, 0, 0]);
+    }
+
+    #[test]
+    fn light_emitter_kind() {
+        let lamp = Kind::id(4);
+
+        assert!(lamp.is_solid());
+        assert!(lamp.is_opaque(), "Lamp should hide neighbor faces");
+        assert!(!lamp.blocks_light(), "Light emitter should not block light");
+    }
+
+    #[test]
+    fn liquid_kind() {
+        let water = Kind::id(5);
+
+        assert!(water.is_liquid());
+        assert!(water.is_solid(), "Liquids should be rendered");
+        assert!(!water.is_opaque(), "Liquids should not hide neighbor faces");
+        assert_eq!(water.liquid_level(water), LIQUID_LEVELS);
+        assert_eq!(water.liquid_level(Kind::NONE), LIQUID_LEVELS - 1);
+        assert_eq!(Kind::id(1).liquid_level(Kind::NONE), 0);
+    }
+
+    #[test]
+    fn layer_kind() {
+        let snow = Kind::by_name("Snow").unwrap();
+
+        assert!(snow.is_layer());
+        assert!(snow.is_solid());
+        assert!(!snow.is_opaque(), "Layers should not hide neighbor faces");
+        assert!(!snow.blocks_light(), "Layers should not block light");
+        assert!(snow.shape_height() < 1.0);
+        assert!(!Kind::id(1).is_layer());
+        assert_eq!(Kind::id(1).shape_height(), 1.0);
+    }
+
+    #[test]
+    fn kind_exists() {
+        assert!(Kind::NONE.exists());
+        assert!(Kind::id(4).exists());
+        assert!(!Kind::id(u16::MAX).exists());
+    }
+
+    #[test]
+    fn kind_by_name() {
+        assert_eq!(Kind::by_name("Water"), Some(Kind::id(5)));
+        assert_eq!(Kind::by_name("Invalid"), None);
+    }
+
+    #[test]
+    fn tile_layer() {
+        let descs = KindsDescs {
+            atlas_size: 64,
+            atlas_tile_size: 16,
+            ..Default::default()
+        };
+
+        assert_eq!(descs.tile_layer(IVec2::new(0, 0)), 0);
+        assert_eq!(descs.tile_layer(IVec2::new(3, 0)), 3);
+        assert_eq!(descs.tile_layer(IVec2::new(1, 2)), 9);
+    }
 }
@@ -1,4 +1,7 @@
-use bevy::math::{IVec3, Vec2, Vec3};
+use bevy::{
+    math::{IVec3, Vec2, Vec3},
+    reflect::Reflect,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -9,7 +12,11 @@ use crate::{
 use super::chunk;
 
 mod kind;
+mod remap;
+mod weld;
 pub use kind::*;
+pub use remap::{KindRemap, KindsTable, MissingKind};
+pub use weld::{weld, WeldedMesh};
 
 pub const SIDE_COUNT: usize = 6;
 
@@ -37,7 +44,9 @@ impl LightTy {
 
 pub type Voxel = IVec3;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Default, Deserialize, Serialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Default, Deserialize, Serialize, Reflect,
+)]
 pub struct Light(u8);
 
 impl Light {
@@ -75,7 +84,9 @@ impl From<Light> for u8 {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Default, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Default, Serialize, Deserialize, Reflect,
+)]
 pub enum Side {
     #[default]
     Right = 0,
@@ -159,7 +170,9 @@ impl Side {
     }
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Deserialize, Serialize)]
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Deserialize, Serialize, Reflect,
+)]
 pub struct FacesOcclusion(u8);
 
 const FULL_OCCLUDED_MASK: u8 = 0b0011_1111;
@@ -221,7 +234,7 @@ impl ChunkFacesOcclusion {
 }
 
 /// Contains smoothed vertex light for each face
-#[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize, Reflect)]
 pub struct FacesSoftLight([[f32; 4]; SIDE_COUNT]);
 
 impl FacesSoftLight {
@@ -250,7 +263,7 @@ pub struct Face {
     pub light: [f32; 4],
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, Reflect)]
 pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
@@ -260,6 +273,31 @@ pub struct Vertex {
     // TODO: color
 }
 
+/// **Returns** a hash of the given vertices content, which is stable between runs and machines, so
+/// it can be persisted and compared by both client and server.
+pub fn vertex_hash(vertices: &[Vertex]) -> u64 {
+    fnv1a(vertices.iter().flat_map(|v| {
+        v.position
+            .to_array()
+            .into_iter()
+            .chain(v.normal.to_array())
+            .chain(v.uv.to_array())
+            .chain(v.tile_coord_start.to_array())
+            .chain(v.light.to_array())
+            .flat_map(|f| f.to_bits().to_le_bytes())
+    }))
+}
+
+/// FNV-1a hash of the given bytes, since std hasher isn't guaranteed to be stable.
+pub(crate) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
 pub fn to_local(world: Vec3) -> IVec3 {
     // First round world coords to integer.
     // This transform (1.1, -0.3, 17.5) into (1, -1, 17)
@@ -377,6 +415,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn vertex_hash() {
+        let vertex = Vertex {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            light: Vec3::ONE,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::vertex_hash(&[vertex, vertex]),
+            super::vertex_hash(&[vertex, vertex])
+        );
+        assert_ne!(
+            super::vertex_hash(&[vertex]),
+            super::vertex_hash(&[vertex, vertex])
+        );
+        assert_ne!(
+            super::vertex_hash(&[vertex]),
+            super::vertex_hash(&[Vertex {
+                uv: Vec2::X,
+                ..vertex
+            }])
+        );
+    }
+
     #[test]
     fn to_local() {
         assert_eq!(
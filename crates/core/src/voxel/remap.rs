@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::{ChunkStorage, BUFFER_SIZE};
+
+use super::{Kind, KindsDescs};
+
+/// Id of each kind name, persisted along with saves, so saved kinds can still be found when
+/// [`KindsDescs`] ids are reordered.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KindsTable(pub BTreeMap<String, u16>);
+
+impl KindsTable {
+    /// **Returns** the table of the given kinds descriptions.
+    pub fn new(descs: &KindsDescs) -> Self {
+        Self(
+            descs
+                .descriptions
+                .iter()
+                .map(|desc| (desc.name.clone(), desc.id))
+                .collect(),
+        )
+    }
+}
+
+/// A saved kind which doesn't exist on current [`KindsDescs`] anymore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingKind {
+    pub id: u16,
+    /// Name of the saved kind, or `None` if its id isn't even on saved table.
+    pub name: Option<String>,
+}
+
+impl std::fmt::Display for MissingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "Saved kind {name} ({}) no longer exists", self.id),
+            None => write!(f, "Saved kind id {} isn't on saved kinds table", self.id),
+        }
+    }
+}
+
+impl std::error::Error for MissingKind {}
+
+/// Maps kind ids of a save to current ids, by matching kind names of saved and current
+/// [`KindsTable`]. Saved kinds which no longer exists are only reported when used, so chunks
+/// which doesn't have them can still be loaded.
+#[derive(Debug, Clone, Default)]
+pub struct KindRemap {
+    ids: HashMap<u16, u16>,
+    names: HashMap<u16, String>,
+}
+
+impl KindRemap {
+    pub fn new(saved: &KindsTable, current: &KindsTable) -> Self {
+        let ids = saved
+            .0
+            .iter()
+            .filter_map(|(name, &saved_id)| Some((saved_id, *current.0.get(name)?)))
+            .collect();
+        let names = saved
+            .0
+            .iter()
+            .map(|(name, &id)| (id, name.clone()))
+            .collect();
+
+        Self { ids, names }
+    }
+
+    /// Checks if all saved kinds exists and have the same id, so there is nothing to remap.
+    pub fn is_identity(&self) -> bool {
+        self.ids.len() == self.names.len()
+            && self.ids.iter().all(|(saved, current)| saved == current)
+    }
+
+    /// **Returns** the current kind of the given saved kind.
+    pub fn get(&self, kind: Kind) -> Result<Kind, MissingKind> {
+        let id = u16::from(kind);
+        self.ids
+            .get(&id)
+            .map(|&id| Kind::id(id))
+            .ok_or_else(|| MissingKind {
+                id,
+                name: self.names.get(&id).cloned(),
+            })
+    }
+
+    /// Maps all saved kinds of the given storage to current ones. Fails on the first missing kind,
+    /// leaving the storage partially mapped.
+    pub fn apply(&self, kinds: &mut ChunkStorage<Kind>) -> Result<(), MissingKind> {
+        for i in 0..BUFFER_SIZE {
+            kinds[i] = self.get(kinds[i])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(kinds: &[(&str, u16)]) -> KindsTable {
+        KindsTable(
+            kinds
+                .iter()
+                .map(|&(name, id)| (name.to_string(), id))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn remap_reordered() {
+        let saved = table(&[("Air", 0), ("Dirt", 1), ("Stone", 2)]);
+        let current = table(&[("Air", 0), ("Stone", 1), ("Dirt", 2), ("Sand", 3)]);
+
+        let remap = KindRemap::new(&saved, &current);
+
+        assert!(!remap.is_identity());
+        assert_eq!(remap.get(Kind::id(1)), Ok(Kind::id(2)));
+        assert_eq!(remap.get(Kind::id(2)), Ok(Kind::id(1)));
+
+        let mut kinds = ChunkStorage::default();
+        kinds.set([0, 1, 2].into(), Kind::id(1));
+        remap.apply(&mut kinds).unwrap();
+        assert_eq!(kinds.get([0, 1, 2].into()), Kind::id(2));
+        assert_eq!(kinds.get([0, 0, 0].into()), Kind::NONE);
+    }
+
+    #[test]
+    fn remap_identity() {
+        let saved = table(&[("Air", 0), ("Dirt", 1)]);
+        let current = table(&[("Air", 0), ("Dirt", 1), ("Sand", 2)]);
+
+        assert!(
+            KindRemap::new(&saved, &current).is_identity(),
+            "New kinds doesn't affect saved ones"
+        );
+    }
+
+    #[test]
+    fn remap_missing() {
+        let saved = table(&[("Air", 0), ("Dirt", 1), ("Lava", 2)]);
+        let current = table(&[("Air", 0), ("Dirt", 1)]);
+
+        let remap = KindRemap::new(&saved, &current);
+
+        assert!(!remap.is_identity());
+        assert_eq!(remap.get(Kind::id(1)), Ok(Kind::id(1)));
+        assert_eq!(
+            remap.get(Kind::id(2)),
+            Err(MissingKind {
+                id: 2,
+                name: Some("Lava".to_string())
+            })
+        );
+        assert_eq!(
+            remap.get(Kind::id(9)),
+            Err(MissingKind { id: 9, name: None })
+        );
+
+        let mut kinds = ChunkStorage::default();
+        kinds.set([1, 1, 1].into(), Kind::id(2));
+        assert!(remap.apply(&mut kinds).is_err());
+    }
+}
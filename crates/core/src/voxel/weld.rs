@@ -0,0 +1,127 @@
+//! Turns mesher output, which has 4 vertices per face, into an indexed triangle list, so vertices
+//! shared by neighbor faces are stored only once.
+
+use bevy::utils::HashMap;
+
+use super::Vertex;
+
+/// Faces with a smaller area than this, in voxels, are considered degenerate and dropped.
+const MIN_FACE_AREA: f32 = 1e-6;
+
+/// Indexed triangle list, built by [`weld`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WeldedMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+/// Welds identical vertices of the given faces, using an index remap table, and drops zero-area
+/// faces and triangles, which are invisible but may cause z-fighting.
+///
+/// This function assumes 4 vertices per face, in CCW order. Each face is split in triangles
+/// _*0 1 2*_ and _*2 3 0*_.
+pub fn weld(vertices: &[Vertex]) -> WeldedMesh {
+    debug_assert!(
+        vertices.chunks_exact(4).remainder().is_empty(),
+        "Each face should have 4 vertices"
+    );
+
+    let mut mesh = WeldedMesh::default();
+    let mut remap = HashMap::<[u32; 13], u32>::default();
+
+    for face in vertices.chunks_exact(4) {
+        let area = (face[1].position - face[0].position)
+            .cross(face[3].position - face[0].position)
+            .length();
+        if area < MIN_FACE_AREA {
+            continue;
+        }
+
+        let [i0, i1, i2, i3] = [0, 1, 2, 3].map(|i| {
+            *remap.entry(vertex_key(&face[i])).or_insert_with(|| {
+                mesh.vertices.push(face[i]);
+                (mesh.vertices.len() - 1) as u32
+            })
+        });
+
+        for triangle in [[i0, i1, i2], [i2, i3, i0]] {
+            let [a, b, c] = triangle;
+            if a != b && b != c && c != a {
+                mesh.indices.extend(triangle);
+            }
+        }
+    }
+
+    mesh
+}
+
+/// Bits of all vertex attributes, so only vertices which are exactly the same are welded.
+fn vertex_key(vertex: &Vertex) -> [u32; 13] {
+    let values = vertex
+        .position
+        .to_array()
+        .into_iter()
+        .chain(vertex.normal.to_array())
+        .chain(vertex.uv.to_array())
+        .chain(vertex.tile_coord_start.to_array())
+        .chain(vertex.light.to_array());
+
+    let mut key = [0; 13];
+    // Negative zero is the same value as zero, but has other bits.
+    key.iter_mut()
+        .zip(values)
+        .for_each(|(key, value)| *key = (value + 0.0).to_bits());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{Vec2, Vec3};
+
+    use super::*;
+
+    fn quad(x: f32, width: f32) -> [Vertex; 4] {
+        [(x, 0.0), (x + width, 0.0), (x + width, 1.0), (x, 1.0)].map(|(x, z)| Vertex {
+            position: Vec3::new(x, 1.0, z),
+            normal: Vec3::Y,
+            uv: Vec2::new(x, z),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn weld_shared_vertices() {
+        let vertices = [quad(0.0, 1.0), quad(1.0, 1.0)].concat();
+
+        let mesh = weld(&vertices);
+
+        assert_eq!(mesh.vertices.len(), 6, "Shared edge should be welded");
+        assert_eq!(mesh.indices.len(), 12);
+        assert_eq!(&mesh.indices[..6], &[0, 1, 2, 2, 3, 0]);
+        assert!(mesh.indices[6..].contains(&1) && mesh.indices[6..].contains(&2));
+    }
+
+    #[test]
+    fn weld_keeps_different_attributes() {
+        let mut right = quad(1.0, 1.0);
+        right.iter_mut().for_each(|vertex| vertex.light = Vec3::ONE);
+
+        let mesh = weld(&[quad(0.0, 1.0), right].concat());
+
+        assert_eq!(
+            mesh.vertices.len(),
+            8,
+            "Vertices with other light aren't the same"
+        );
+    }
+
+    #[test]
+    fn weld_drops_degenerate_faces() {
+        let vertices = [quad(0.0, 1.0), quad(1.0, 0.0)].concat();
+
+        let mesh = weld(&vertices);
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices.len(), 6, "Zero-area face should be dropped");
+    }
+}
@@ -1,12 +1,226 @@
 use bevy::prelude::*;
-use projekto_core::{chunk::Chunk, voxel};
-use projekto_proto::MessageSource;
+use projekto_core::{
+    chunk::{Chunk, ChunkStorage, ColumnSummary, Decoration},
+    voxel::{self, Voxel},
+};
+use projekto_proto::{Compression, MessageSource};
 use projekto_proto_macros::message_source;
+use serde::{Deserialize, Serialize};
+
+/// Duration of a server tick, in milliseconds. Server updates its world once per tick.
+pub const SERVER_TICK_MS: u64 = 50;
+
+/// Max vertices sent on a single message. Chunks with more vertices are streamed in
+/// `ChunkVertexPart`s, so a huge chunk doesn't stall other messages. Must be a multiple of 4, so
+/// faces aren't split between parts.
+pub const CHUNK_VERTEX_PART_LEN: usize = 4096;
 
 #[message_source(MessageSource::Client)]
 pub enum ClientMessage {
-    ChunkLoad { pub chunk: Chunk },
-    LandscapeUpdate { pub center: IVec2, pub radius: u8 },
+    ChunkLoad {
+        pub chunk: Chunk,
+    },
+    LandscapeUpdate {
+        pub center: IVec2,
+        pub radius: u8,
+    },
+    VoxelUpdate {
+        pub chunk: Chunk,
+        pub voxel: Voxel,
+        pub kind: voxel::Kind,
+    },
+    ChunkKindSubscribe {
+        pub radius: u8,
+    },
+    PlayerTransform {
+        pub position: Vec3,
+        pub rotation: Quat,
+    },
+    ChunkInspect {
+        pub chunk: Chunk,
+    },
+    /// Asks server to export chunks around the given one to a file, on server machine.
+    WorldExport {
+        pub center: Chunk,
+        pub radius: u8,
+    },
+    /// Asks server to undo the last `count` voxel edits made by this client.
+    EditUndo {
+        pub count: u16,
+    },
+    /// Asks server to redo the last `count` voxel edits undone by this client.
+    EditRedo {
+        pub count: u16,
+    },
+    /// Copies voxels between `min` and `max`, inclusive, in world coordinates, to this client
+    /// clipboard on server. Replied with `StructureCopied`.
+    StructureCopy {
+        pub min: IVec3,
+        pub max: IVec3,
+    },
+    /// Pastes the structure on this client clipboard with its min corner at `origin`, in world
+    /// coordinates, after mirroring it on X axis, if asked to, and turning it clockwise `rotation`
+    /// quarter turns.
+    StructurePaste {
+        pub origin: IVec3,
+        pub rotation: u8,
+        pub mirror: bool,
+    },
+    /// Asks server for a [`WorldSnapshot`] of the loaded world. Replied with `WorldSnapshotReply`.
+    WorldSnapshotRequest,
+    /// Protects chunks between `min` and `max`, inclusive, so only this client and `allowed`
+    /// players can edit voxels there, besides what `flags` allows anyone to do. Admins only.
+    #[no_copy]
+    RegionClaim {
+        pub min: Chunk,
+        pub max: Chunk,
+        pub allowed: Vec<u32>,
+        pub flags: RegionFlags,
+    },
+    /// Removes the protection of all regions containing the given chunk. Admins only.
+    RegionUnclaim {
+        pub chunk: Chunk,
+    },
+    /// Asks server for a top-down map of `size` by `size` pixels centered on `center`, in world
+    /// coordinates, sampled straight from world generator every `scale` voxels. Replied with
+    /// `WorldGenPreview`.
+    WorldGenPreviewRequest {
+        pub center: IVec2,
+        pub size: u16,
+        pub scale: u16,
+        pub layer: PreviewLayer,
+    },
+    /// Handshake, sent right after connecting, with what this client is able to handle. Replied
+    /// with `Welcome`.
+    Hello {
+        pub capabilities: Capabilities,
+    },
+    /// Chat text typed by the player. Text starting with `/` is a console command, which is run by
+    /// server instead of being broadcast, and replied with `ChatBroadcast`.
+    #[no_copy]
+    Chat {
+        pub text: String,
+    },
+    /// Sent periodically to measure round trip time, with the client time, in seconds, when it was
+    /// sent. Replied right away with `HeartbeatReply`.
+    Heartbeat {
+        pub sent_at: f64,
+    },
+    /// Asks server for some internal state, used by external tools, like dashboards and tests.
+    /// Admins only, others are replied with [`InspectorReply::Denied`]. Replied with
+    /// `InspectorResponse`.
+    #[no_copy]
+    InspectorRequest {
+        pub query: InspectorQuery,
+    },
+    /// Player started breaking the given voxel, or stopped it, when `digging` is `false`. Server
+    /// broadcasts `VoxelBreakProgress` while it lasts. Voxel is still broken by a `VoxelUpdate`,
+    /// once player held it for the voxel kind break time.
+    VoxelDig {
+        pub chunk: Chunk,
+        pub voxel: Voxel,
+        pub digging: bool,
+    },
+}
+
+impl ClientMessage {
+    /// **Returns** `true` if this message edits the world, which spectators aren't allowed to do.
+    pub fn is_edit(self) -> bool {
+        matches!(
+            self,
+            Self::VoxelUpdate
+                | Self::VoxelDig
+                | Self::EditUndo
+                | Self::EditRedo
+                | Self::StructurePaste
+                | Self::RegionClaim
+                | Self::RegionUnclaim
+        )
+    }
+}
+
+/// Features a client is able to handle, so server only sends what it understands. Clients which
+/// never sent a `Hello` are assumed to have the default ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Best codec client is able to decode. Clients with a tight CPU budget, like wasm ones,
+    /// should prefer [`Compression::None`].
+    pub compression: Compression,
+    /// Client caches chunk meshes, so server sends only `ChunkVertexHash` of chunks already
+    /// meshed and waits for a `ChunkLoad` of the ones missing, instead of all vertices.
+    pub delta_updates: bool,
+    /// Client keeps a cache of chunk kinds, so it is able to subscribe to `ChunkKind` updates.
+    pub kind_cache: bool,
+    /// Client only watches the world, like map viewers and screenshot bots. Server drops any edit
+    /// sent by it and doesn't show it to other players.
+    pub spectator: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            compression: Compression::None,
+            delta_updates: true,
+            kind_cache: true,
+            spectator: false,
+        }
+    }
+}
+
+/// What is drawn on a `WorldGenPreview`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PreviewLayer {
+    /// Terrain height, in grayscale, with oceans in blue.
+    #[default]
+    Height,
+    /// Surface kind of each column biome, shaded by height.
+    Biome,
+    /// Temperature, from cold in blue to hot in red.
+    Temperature,
+    /// Humidity, from dry in yellow to wet in green.
+    Humidity,
+}
+
+/// What anyone, besides region owner and allowed players, can do inside a protected region.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegionFlags {
+    /// Place solid voxels.
+    pub place: bool,
+    /// Remove solid voxels or place non-solid ones, like air or water.
+    pub remove: bool,
+}
+
+/// Edit history command, typed by players.
+#[derive(Debug, Clone, Copy)]
+pub enum EditCommand {
+    Undo(EditUndo),
+    Redo(EditRedo),
+}
+
+impl EditCommand {
+    /// Parses an `/undo [n]` or `/redo [n]` command. When `n` is omitted, a single edit is undone
+    /// or redone.
+    ///
+    /// **Returns** `None` if the given command isn't a valid edit history command.
+    pub fn parse(command: &str) -> Option<Self> {
+        let mut args = command.split_whitespace();
+        let name = args.next()?;
+
+        let count = match args.next() {
+            Some(count) => count.parse().ok().filter(|&count| count > 0)?,
+            None => 1,
+        };
+
+        if args.next().is_some() {
+            return None;
+        }
+
+        match name {
+            "/undo" => Some(Self::Undo(EditUndo { count })),
+            "/redo" => Some(Self::Redo(EditRedo { count })),
+            _ => None,
+        }
+    }
 }
 
 #[message_source(MessageSource::Server)]
@@ -14,6 +228,394 @@ pub enum ServerMessage {
     #[no_copy]
     ChunkVertex {
         pub chunk: Chunk,
+        /// Hash of chunk kinds and light. See [`projekto_core::chunk::content_hash`].
+        pub hash: u64,
         pub vertex: Vec<voxel::Vertex>,
     },
+    #[no_copy]
+    ChunkKind {
+        pub chunk: Chunk,
+        pub kind: ChunkStorage<voxel::Kind>,
+    },
+    VoxelUpdateRejected {
+        pub chunk: Chunk,
+        pub voxel: Voxel,
+    },
+    TimeSync {
+        pub day_time: f32,
+        pub day_length: f32,
+        /// Server tick when this was sent.
+        pub tick: u64,
+    },
+    RemotePlayerTransform {
+        pub player: u32,
+        pub position: Vec3,
+        pub rotation: Quat,
+        pub teleport: bool,
+        /// Server tick when player transform was received.
+        pub tick: u64,
+    },
+    RemotePlayerLeft {
+        pub player: u32,
+    },
+    #[no_copy]
+    ChunkColumnSummary {
+        pub chunk: Chunk,
+        pub columns: Vec<ColumnSummary>,
+    },
+    ChunkVertexHash {
+        pub chunk: Chunk,
+        pub hash: u64,
+    },
+    /// Chunk internals, used by debug tools.
+    #[no_copy]
+    ChunkInspection {
+        pub chunk: Chunk,
+        pub kind: ChunkStorage<voxel::Kind>,
+        pub light: ChunkStorage<voxel::Light>,
+        /// Faces of non-empty voxels which are hidden by neighbors.
+        pub occluded_faces: u32,
+        /// Non-empty voxels which have all faces hidden by neighbors.
+        pub fully_occluded_voxels: u32,
+        pub vertex_count: u32,
+    },
+    /// Reply of `WorldExport`, with the written file path.
+    #[no_copy]
+    WorldExported {
+        pub path: String,
+        pub chunks: u32,
+    },
+    /// Moves the local player to the given position. Chunks around the old position are discarded,
+    /// since server is going to stream the ones around the new position instead.
+    Teleport {
+        pub position: Vec3,
+    },
+    /// Reply of `StructureCopy`, with the size of the copied structure.
+    StructureCopied {
+        pub size: IVec3,
+    },
+    /// Decorations scattered on chunk surface. Replaces any decoration previously sent.
+    #[no_copy]
+    ChunkDecorations {
+        pub chunk: Chunk,
+        pub decorations: Vec<Decoration>,
+    },
+    /// Faces on chunk border which occlusion doesn't match neighbor voxels, found by seam
+    /// validation. Empty when a previously reported chunk got fixed.
+    #[no_copy]
+    SeamMismatches {
+        pub chunk: Chunk,
+        pub faces: Vec<(Voxel, voxel::Side)>,
+    },
+    /// Reply of `WorldSnapshotRequest`.
+    #[no_copy]
+    WorldSnapshotReply {
+        pub snapshot: WorldSnapshot,
+    },
+    /// Vertices of liquid voxels, which are drawn apart from `ChunkVertex`. Replaces any liquid
+    /// vertices previously sent.
+    #[no_copy]
+    ChunkLiquidVertex {
+        pub chunk: Chunk,
+        pub vertex: Vec<voxel::Vertex>,
+    },
+    /// Reply of `WorldGenPreviewRequest`, with RGB pixels row by row, where -Z is on top.
+    #[no_copy]
+    WorldGenPreview {
+        pub center: IVec2,
+        pub size: u16,
+        pub scale: u16,
+        pub layer: PreviewLayer,
+        pub pixels: Vec<[u8; 3]>,
+    },
+    /// Reply of `Hello`, with the capabilities server is going to use with this client, which may
+    /// be less than what was asked, like when server is out of compression budget.
+    Welcome {
+        pub capabilities: Capabilities,
+    },
+    /// Server is going to shut down in `seconds`, so clients should disconnect before that.
+    #[no_copy]
+    ShuttingDown {
+        pub reason: String,
+        pub seconds: f32,
+    },
+    /// Chat text sent by `sender` player, or by server itself when `None`, like console command
+    /// replies.
+    #[no_copy]
+    ChatBroadcast {
+        pub sender: Option<u32>,
+        pub text: String,
+    },
+    /// Reply of `Heartbeat`, with the server tick it was replied on.
+    HeartbeatReply {
+        pub sent_at: f64,
+        pub tick: u64,
+    },
+    /// Reply of `InspectorRequest`.
+    #[no_copy]
+    InspectorResponse {
+        pub reply: InspectorReply,
+    },
+    /// A slice of chunk vertices, sent instead of `ChunkVertex` when chunk has more than
+    /// [`CHUNK_VERTEX_PART_LEN`] vertices. Each part has whole faces, so it can be meshed as soon
+    /// as it arrives, and all `total` parts together are the chunk vertices, in `part` order.
+    #[no_copy]
+    ChunkVertexPart {
+        pub chunk: Chunk,
+        /// Hash of chunk kinds and light, the same on all parts of a chunk.
+        pub hash: u64,
+        pub part: u16,
+        pub total: u16,
+        pub data: Vec<voxel::Vertex>,
+    },
+    /// How much of the given voxel was broken by `player`, from 0 to 1. Zero means player stopped
+    /// breaking it, so any crack drawn on it should be removed.
+    VoxelBreakProgress {
+        pub chunk: Chunk,
+        pub voxel: Voxel,
+        pub player: u32,
+        pub progress: f32,
+    },
+}
+
+/// What an external tool wants to know about server. See `InspectorRequest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InspectorQuery {
+    /// All loaded chunks.
+    LoadedChunks,
+    /// Voxel kinds of the given chunk.
+    ChunkKinds(Chunk),
+    Metrics,
+    /// Runs a console command line, without the leading `/`, like it was typed on chat.
+    Command(String),
+}
+
+/// Reply of an [`InspectorQuery`], with the same variant it was asked for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InspectorReply {
+    /// Loaded chunks, sorted by chunk.
+    LoadedChunks(Vec<Chunk>),
+    /// Voxel kinds of the given chunk, or `None` if it isn't loaded.
+    ChunkKinds {
+        chunk: Chunk,
+        kinds: Option<ChunkStorage<voxel::Kind>>,
+    },
+    Metrics(MetricsReport),
+    /// Command reply, or why it failed.
+    Command(Result<String, String>),
+    /// Client isn't an admin.
+    Denied,
+}
+
+/// Server metrics since it started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsReport {
+    /// Server tick when this was taken.
+    pub tick: u64,
+    pub clients: u32,
+    pub loaded_chunks: u32,
+    pub generated: TimingReport,
+    pub meshed: TimingReport,
+    /// Vertices generated by all meshed chunks.
+    pub vertices: u64,
+    pub saved: u64,
+    pub saved_bytes: u64,
+    /// Client messages dropped by rate limiting.
+    pub rate_limited: u64,
+    /// Clients disconnected by rate limiting.
+    pub rate_limit_disconnects: u64,
+    /// Client messages which weren't handled, either due to missing or failed handlers.
+    pub dead_letters: u64,
+}
+
+/// Count, average and max time taken by some chunk processing, in micro seconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimingReport {
+    pub count: u64,
+    pub avg_micros: u64,
+    pub max_micros: u64,
+}
+
+/// Loaded world state at some server tick, used by external tools and tests to check global
+/// invariants, like every chunk being meshed once nothing is pending.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    /// Loaded chunks and the hash of their kinds, sorted by chunk.
+    pub chunks: Vec<(Chunk, u64)>,
+    /// Connected players and their positions, sorted by player.
+    pub players: Vec<(u32, Vec3)>,
+    pub pending: PendingWork,
+}
+
+/// Number of chunks waiting on each processing queue.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingWork {
+    /// Chunks requested but not loaded or generated yet.
+    pub loading: u32,
+    pub faces_occlusion: u32,
+    pub light_recompute: u32,
+    /// Chunks sent to meshing workers, which results weren't collected yet.
+    pub meshing: u32,
+}
+
+impl PendingWork {
+    /// Checks if there is no chunk waiting on any queue.
+    pub fn is_idle(&self) -> bool {
+        self.loading == 0
+            && self.faces_occlusion == 0
+            && self.light_recompute == 0
+            && self.meshing == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use projekto_proto::{Message, MessageType};
+
+    use super::*;
+
+    /// Checks the given message fits on its max size.
+    ///
+    /// **Returns** the message type, so it is known which ones were checked.
+    fn assert_fits<T: MessageType, M: Message<T> + Serialize>(msg: M) -> T {
+        let msg_type = msg.msg_type();
+        let max_size = msg_type
+            .max_size()
+            .unwrap_or_else(|| panic!("{msg_type:?} should be bounded"));
+        let size = bincode::serialized_size(&msg).unwrap() as usize;
+
+        assert!(
+            size <= max_size,
+            "{msg_type:?} has {size} bytes, but its max size is {max_size}"
+        );
+
+        msg_type
+    }
+
+    fn assert_all_bounded_checked<T: MessageType + Copy + PartialEq>(checked: &[T]) {
+        let mut code = 0;
+        while let Ok(msg_type) = T::try_from_code(code) {
+            if msg_type.max_size().is_some() {
+                assert!(checked.contains(&msg_type), "{msg_type:?} wasn't checked");
+            }
+            code += 1;
+        }
+    }
+
+    #[test]
+    fn client_messages_fit_max_size() {
+        // arrange
+        let chunk = Chunk::new(i32::MAX, i32::MIN);
+        let voxel = Voxel::new(15, 255, 15);
+
+        // act
+        let checked = [
+            assert_fits(ChunkLoad { chunk }),
+            assert_fits(LandscapeUpdate {
+                center: IVec2::MIN,
+                radius: u8::MAX,
+            }),
+            assert_fits(VoxelUpdate {
+                chunk,
+                voxel,
+                kind: u16::MAX.into(),
+            }),
+            assert_fits(ChunkKindSubscribe { radius: u8::MAX }),
+            assert_fits(PlayerTransform {
+                position: Vec3::MAX,
+                rotation: Quat::IDENTITY,
+            }),
+            assert_fits(ChunkInspect { chunk }),
+            assert_fits(WorldExport {
+                center: chunk,
+                radius: u8::MAX,
+            }),
+            assert_fits(EditUndo { count: u16::MAX }),
+            assert_fits(EditRedo { count: u16::MAX }),
+            assert_fits(StructureCopy {
+                min: IVec3::MIN,
+                max: IVec3::MAX,
+            }),
+            assert_fits(StructurePaste {
+                origin: IVec3::MAX,
+                rotation: 3,
+                mirror: true,
+            }),
+            assert_fits(WorldSnapshotRequest),
+            assert_fits(RegionUnclaim { chunk }),
+            assert_fits(WorldGenPreviewRequest {
+                center: IVec2::MAX,
+                size: u16::MAX,
+                scale: u16::MAX,
+                layer: PreviewLayer::Humidity,
+            }),
+            assert_fits(Hello {
+                capabilities: Capabilities {
+                    compression: Compression::Lz4,
+                    ..Default::default()
+                },
+            }),
+            assert_fits(Heartbeat { sent_at: f64::MAX }),
+            assert_fits(VoxelDig {
+                chunk,
+                voxel,
+                digging: true,
+            }),
+        ];
+
+        // assert
+        assert_all_bounded_checked(&checked);
+    }
+
+    #[test]
+    fn server_messages_fit_max_size() {
+        // arrange
+        let chunk = Chunk::new(i32::MIN, i32::MAX);
+        let voxel = Voxel::new(15, 255, 15);
+
+        // act
+        let checked = [
+            assert_fits(VoxelUpdateRejected { chunk, voxel }),
+            assert_fits(TimeSync {
+                day_time: f32::MAX,
+                day_length: f32::MAX,
+                tick: u64::MAX,
+            }),
+            assert_fits(RemotePlayerTransform {
+                player: u32::MAX,
+                position: Vec3::MIN,
+                rotation: Quat::IDENTITY,
+                teleport: true,
+                tick: u64::MAX,
+            }),
+            assert_fits(RemotePlayerLeft { player: u32::MAX }),
+            assert_fits(ChunkVertexHash {
+                chunk,
+                hash: u64::MAX,
+            }),
+            assert_fits(Teleport {
+                position: Vec3::MAX,
+            }),
+            assert_fits(StructureCopied { size: IVec3::MAX }),
+            assert_fits(Welcome {
+                capabilities: Capabilities {
+                    compression: Compression::Lz4,
+                    ..Default::default()
+                },
+            }),
+            assert_fits(HeartbeatReply {
+                sent_at: f64::MAX,
+                tick: u64::MAX,
+            }),
+            assert_fits(VoxelBreakProgress {
+                chunk,
+                voxel,
+                player: u32::MAX,
+                progress: 1.0,
+            }),
+        ];
+
+        // assert
+        assert_all_bounded_checked(&checked);
+    }
 }
@@ -53,9 +53,9 @@ fn generate_simplified_enum(
             #name::#v_name => {
                 match boxed.downcast::<#v_name>() {
                     Ok(msg) => {
-                        let size = bincode::serialized_size(&msg)?;
-                        bincode::serialize_into(buf, &msg)?;
-                        Ok(size as u32)
+                        let start = buf.len();
+                        bincode::serialize_into(&mut *buf, &msg)?;
+                        Ok((buf.len() - start) as u32)
                     },
                     Err(boxed) => Err(projekto_proto::MessageError::Downcasting(boxed.msg_source()))
                 }
@@ -105,6 +105,22 @@ fn generate_simplified_enum(
         }
     });
 
+    // Copy variants can't own heap data, so their size is bounded. Others may have any size.
+    let max_size_items = variants.iter().map(|v| {
+        let v_name = &v.ident;
+        let no_copy = v.attrs.iter().any(|attr| attr.path().is_ident("no_copy"));
+        if no_copy {
+            quote! { None }
+        } else {
+            quote! { projekto_proto::max_serialized_size::<#v_name>() }
+        }
+    });
+
+    let all_variants = variants.iter().map(|v| {
+        let v_name = &v.ident;
+        quote! { #name::#v_name }
+    });
+
     let variant_names = variants.iter().map(|v| &v.ident);
     quote! {
         #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
@@ -113,17 +129,31 @@ fn generate_simplified_enum(
         }
 
         impl projekto_proto::MessageType for #name {
+            fn max_message_size() -> usize {
+                [#(#all_variants),*]
+                    .iter()
+                    .filter_map(<Self as projekto_proto::MessageType>::max_size)
+                    .max()
+                    .unwrap_or_default()
+            }
+
             fn source() -> MessageSource {
                 #source
             }
 
+            fn max_size(&self) -> Option<usize> {
+                // Sizes are computed by probing each message type, so it is done only once.
+                static MAX_SIZES: std::sync::OnceLock<Vec<Option<usize>>> = std::sync::OnceLock::new();
+                MAX_SIZES.get_or_init(|| vec![#(#max_size_items),*])[<Self as projekto_proto::MessageType>::code(self) as usize]
+            }
+
             fn deserialize_boxed(&self, buf: &[u8]) -> Result<projekto_proto::BoxedMessage<Self>, projekto_proto::MessageError> {
                 match self {
                     #(#des_boxed_match_items),*
                 }
             }
 
-            fn serialize_boxed(&self, boxed: projekto_proto::BoxedMessage<Self>, buf: &mut [u8]) -> Result<u32, projekto_proto::MessageError> {
+            fn serialize_boxed(&self, boxed: projekto_proto::BoxedMessage<Self>, buf: &mut Vec<u8>) -> Result<u32, projekto_proto::MessageError> {
                 match self {
                     #(#ser_boxed_match_items),*
                 }
@@ -1,9 +1,81 @@
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-use async_channel::{Receiver, Sender};
+use async_channel::{Receiver, Sender, TrySendError};
 
 use super::{BoxedMessage, Message, MessageType};
 
+/// What happens when a message is sent to a channel which is full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Message isn't sent and an error is returned.
+    #[default]
+    Error,
+    /// Oldest message waiting on channel is dropped, to make room for the new one. Useful for bulk
+    /// messages, where newer ones replace older ones.
+    DropOldest,
+    /// Async senders wait until there is room on channel. Sync senders can't wait, so an error
+    /// is returned, just like [`OverflowPolicy::Error`].
+    Block,
+}
+
+/// Settings of a single direction of a [`ChannelPair`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelConfig {
+    /// Max messages waiting to be received, or `None` to never be full.
+    pub capacity: Option<usize>,
+    pub policy: OverflowPolicy,
+}
+
+impl ChannelConfig {
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            capacity: Some(capacity),
+            policy,
+        }
+    }
+}
+
+/// Counters of a single direction of a [`ChannelPair`], shared by both ends.
+#[derive(Debug, Default)]
+pub struct QueueStats {
+    peak_len: AtomicUsize,
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+    blocked: AtomicU64,
+}
+
+impl QueueStats {
+    /// Most messages waiting to be received at once.
+    pub fn peak_len(&self) -> usize {
+        self.peak_len.load(Ordering::Relaxed)
+    }
+
+    /// Messages dropped by [`OverflowPolicy::DropOldest`] to make room for newer ones.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Messages which weren't sent, because channel was full.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Times an async sender had to wait for room on channel, by [`OverflowPolicy::Block`].
+    pub fn blocked(&self) -> u64 {
+        self.blocked.load(Ordering::Relaxed)
+    }
+
+    fn record_len(&self, len: usize) {
+        self.peak_len.fetch_max(len, Ordering::Relaxed);
+    }
+}
+
 pub struct ChannelPair<S, R> {
     pub client: Channel<S, R>,
     pub server: Channel<R, S>,
@@ -13,6 +85,9 @@ pub struct ChannelPair<S, R> {
 pub struct Channel<S, R> {
     sender: Sender<BoxedMessage<S>>,
     receiver: Receiver<BoxedMessage<R>>,
+    policy: OverflowPolicy,
+    sent_stats: Arc<QueueStats>,
+    received_stats: Arc<QueueStats>,
 }
 
 impl<S, R> Channel<S, R> {
@@ -25,6 +100,7 @@ impl<S, R> Channel<S, R> {
         let _ = self.sender.close();
     }
 
+    /// Messages waiting to be received on this end.
     pub fn len(&self) -> usize {
         self.receiver.len()
     }
@@ -32,28 +108,71 @@ impl<S, R> Channel<S, R> {
     pub fn is_empty(&self) -> bool {
         self.receiver.is_empty()
     }
+
+    /// Messages sent by this end, which are still waiting to be received on the other end.
+    pub fn pending_sends(&self) -> usize {
+        self.sender.len()
+    }
+
+    /// Counters of messages sent by this end.
+    pub fn sent_stats(&self) -> &QueueStats {
+        &self.sent_stats
+    }
+
+    /// Counters of messages received on this end.
+    pub fn received_stats(&self) -> &QueueStats {
+        &self.received_stats
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum ChannelError {
     #[error("Failed to send message. Channel is closed.")]
     Send(),
+    #[error("Failed to send message. Channel is full.")]
+    Full(),
     #[error("Failed to receive message. Channel is empty and closed.")]
     Recv(#[from] async_channel::RecvError),
 }
 
+fn new_queue<T>(config: ChannelConfig) -> (Sender<T>, Receiver<T>) {
+    match config.capacity {
+        Some(capacity) => async_channel::bounded(capacity),
+        None => async_channel::unbounded(),
+    }
+}
+
 impl<S: MessageType, R: MessageType> Channel<S, R> {
+    /// Creates a pair of unbounded channels.
     pub fn new_pair() -> ChannelPair<S, R> {
-        let (server_sender, server_receiver) = async_channel::unbounded();
-        let (client_sender, client_receiver) = async_channel::unbounded();
+        Self::new_pair_with(ChannelConfig::default(), ChannelConfig::default())
+    }
+
+    /// Creates a pair of channels, where messages sent by `client` end follow `client_config`
+    /// and messages sent by `server` end follow `server_config`.
+    pub fn new_pair_with(
+        client_config: ChannelConfig,
+        server_config: ChannelConfig,
+    ) -> ChannelPair<S, R> {
+        let (server_sender, server_receiver) = new_queue(client_config);
+        let (client_sender, client_receiver) = new_queue(server_config);
+        let client_stats = Arc::new(QueueStats::default());
+        let server_stats = Arc::new(QueueStats::default());
+
         ChannelPair {
             client: Channel::<S, R> {
                 sender: server_sender,
                 receiver: client_receiver,
+                policy: client_config.policy,
+                sent_stats: client_stats.clone(),
+                received_stats: server_stats.clone(),
             },
             server: Channel::<R, S> {
                 sender: client_sender,
                 receiver: server_receiver,
+                policy: server_config.policy,
+                sent_stats: server_stats,
+                received_stats: client_stats,
             },
         }
     }
@@ -81,10 +200,55 @@ impl<S: MessageType, R: MessageType> Channel<S, R> {
         self.send_boxed(boxed)
     }
 
+    /// Sends the given message, following channel [`OverflowPolicy`] if it is full. Since this
+    /// never waits, [`OverflowPolicy::Block`] fails just like [`OverflowPolicy::Error`].
     pub fn send_boxed(&self, boxed: BoxedMessage<S>) -> Result<(), ChannelError> {
-        self.sender
-            .try_send(boxed)
-            .map_err(|_| ChannelError::Send())
+        let result = if self.policy == OverflowPolicy::DropOldest {
+            match self.sender.force_send(boxed) {
+                Ok(Some(_)) => {
+                    self.sent_stats.dropped.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+                Ok(None) => Ok(()),
+                Err(_) => Err(ChannelError::Send()),
+            }
+        } else {
+            self.sender.try_send(boxed).map_err(|err| match err {
+                TrySendError::Full(_) => {
+                    self.sent_stats.rejected.fetch_add(1, Ordering::Relaxed);
+                    ChannelError::Full()
+                }
+                TrySendError::Closed(_) => ChannelError::Send(),
+            })
+        };
+
+        self.sent_stats.record_len(self.sender.len());
+        result
+    }
+
+    /// Sends the given message, waiting for room on channel if it is full and its policy is
+    /// [`OverflowPolicy::Block`]. Other policies behave just like [`Channel::send_boxed`].
+    pub async fn send_boxed_async(&self, boxed: BoxedMessage<S>) -> Result<(), ChannelError> {
+        if self.policy != OverflowPolicy::Block {
+            return self.send_boxed(boxed);
+        }
+
+        let boxed = match self.sender.try_send(boxed) {
+            Ok(()) => None,
+            Err(TrySendError::Full(boxed)) => Some(boxed),
+            Err(TrySendError::Closed(_)) => return Err(ChannelError::Send()),
+        };
+
+        if let Some(boxed) = boxed {
+            self.sent_stats.blocked.fetch_add(1, Ordering::Relaxed);
+            self.sender
+                .send(boxed)
+                .await
+                .map_err(|_| ChannelError::Send())?;
+        }
+
+        self.sent_stats.record_len(self.sender.len());
+        Ok(())
     }
 }
 
@@ -93,6 +257,9 @@ impl<S, R> Clone for Channel<S, R> {
         Self {
             sender: self.sender.clone(),
             receiver: self.receiver.clone(),
+            policy: self.policy,
+            sent_stats: self.sent_stats.clone(),
+            received_stats: self.received_stats.clone(),
         }
     }
 }
@@ -103,7 +270,8 @@ mod tests {
     use projekto_proto_macros::message_source;
 
     use crate::{
-        self as projekto_proto, Channel, ChannelPair, Message, MessageSource, MessageType,
+        self as projekto_proto, Channel, ChannelConfig, ChannelError, ChannelPair, Message,
+        MessageSource, MessageType, OverflowPolicy,
     };
 
     #[message_source(MessageSource::Client)]
@@ -165,4 +333,64 @@ mod tests {
 
         assert!(server.is_closed());
     }
+
+    fn bounded_pair(policy: OverflowPolicy) -> ChannelPair<ClientTestMsg, ClientTestMsg> {
+        Channel::new_pair_with(ChannelConfig::bounded(2, policy), ChannelConfig::default())
+    }
+
+    #[test]
+    fn overflow_error() {
+        let ChannelPair { client, server } = bounded_pair(OverflowPolicy::Error);
+        client.send(A).unwrap();
+        client.send(A).unwrap();
+
+        assert!(matches!(client.send(B), Err(ChannelError::Full())));
+        assert_eq!(client.pending_sends(), 2);
+        assert_eq!(client.sent_stats().rejected(), 1);
+        assert_eq!(server.received_stats().peak_len(), 2);
+        assert_eq!(server.len(), 2);
+    }
+
+    #[test]
+    fn overflow_drop_oldest() {
+        let ChannelPair { client, server } = bounded_pair(OverflowPolicy::DropOldest);
+        client.send(A).unwrap();
+        client.send(A).unwrap();
+        client.send(B).expect("Oldest message should make room");
+
+        assert_eq!(client.sent_stats().dropped(), 1);
+
+        let codes = server
+            .try_recv_all()
+            .into_iter()
+            .map(|boxed| boxed.msg_type().code())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![A.msg_type().code(), B.msg_type().code()]);
+    }
+
+    #[test]
+    fn overflow_block() {
+        let ChannelPair { client, server } = bounded_pair(OverflowPolicy::Block);
+        client.send(A).unwrap();
+        client.send(A).unwrap();
+
+        assert!(
+            matches!(client.send(B), Err(ChannelError::Full())),
+            "Sync senders can't wait"
+        );
+
+        let blocked_client = client.clone();
+        let sender = std::thread::spawn(move || {
+            block_on(blocked_client.send_boxed_async(Box::new(B))).expect("Should wait for room");
+        });
+
+        // Wait sender be blocked
+        while client.sent_stats().blocked() == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(server.try_recv().is_some());
+
+        sender.join().unwrap();
+        assert_eq!(server.len(), 2);
+    }
 }
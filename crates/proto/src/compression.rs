@@ -0,0 +1,142 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Messages smaller than this, in bytes, are always sent uncompressed, since compressing them
+/// costs more CPU than the bandwidth it saves.
+pub const MIN_COMPRESS_SIZE: usize = 512;
+
+/// Compressed payloads start with their uncompressed size.
+const SIZE_LEN: usize = std::mem::size_of::<u32>();
+
+/// Codec used to compress message payloads sent over network.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+}
+
+impl Compression {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Lz4,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Compression used when sending messages on a connection. It is shared with the network task, so
+/// it can be changed at any time, like after a handshake, and is applied to the next messages.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SharedCompression(Arc<AtomicU8>);
+
+impl SharedCompression {
+    pub fn get(&self) -> Compression {
+        Compression::from_u8(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, compression: Compression) {
+        self.0.store(compression as u8, Ordering::Relaxed);
+    }
+}
+
+/// Compresses `payload` into `output`, starting at `offset`, with the uncompressed size prepended.
+/// `output` grows as needed.
+///
+/// **Returns** the compressed size, or `None` if payload is too small or compression didn't make
+/// it any smaller, in which case it should be sent uncompressed.
+pub(crate) fn compress(
+    compression: Compression,
+    payload: &[u8],
+    output: &mut Vec<u8>,
+    offset: usize,
+) -> Option<usize> {
+    if compression == Compression::None || payload.len() < MIN_COMPRESS_SIZE {
+        return None;
+    }
+
+    let max_size = offset + SIZE_LEN + lz4_flex::block::get_maximum_output_size(payload.len());
+    if output.len() < max_size {
+        output.resize(max_size, 0);
+    }
+
+    let output = &mut output[offset..];
+    output[..SIZE_LEN].copy_from_slice(&(payload.len() as u32).to_be_bytes());
+    let size = SIZE_LEN + lz4_flex::block::compress_into(payload, &mut output[SIZE_LEN..]).ok()?;
+
+    (size < payload.len()).then_some(size)
+}
+
+/// Decompresses a payload compressed by [`compress`] into `output`, which is resized to fit it.
+///
+/// **Returns** the decompressed size, or `None` if payload is corrupted or decompressed size is
+/// larger than `max_size`.
+pub(crate) fn decompress(payload: &[u8], output: &mut Vec<u8>, max_size: usize) -> Option<usize> {
+    let size_bytes: [u8; SIZE_LEN] = payload.get(..SIZE_LEN)?.try_into().ok()?;
+    let size = u32::from_be_bytes(size_bytes) as usize;
+
+    if size > max_size {
+        return None;
+    }
+
+    output.resize(size, 0);
+    let decompressed = lz4_flex::block::decompress_into(&payload[SIZE_LEN..], output).ok()?;
+
+    (decompressed == size).then_some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress_round_trip() {
+        let payload = (0..4096).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        let mut compressed = vec![];
+
+        let size = compress(Compression::Lz4, &payload, &mut compressed, 0)
+            .expect("Repetitive payload should be compressed");
+        assert!(size < payload.len());
+
+        let mut output = vec![];
+        assert_eq!(
+            decompress(&compressed[..size], &mut output, payload.len()),
+            Some(payload.len())
+        );
+        assert_eq!(output, payload);
+
+        // Decompressed payload is larger than allowed
+        assert_eq!(decompress(&compressed[..size], &mut output, 10), None);
+    }
+
+    #[test]
+    fn compress_skipped() {
+        let mut compressed = vec![];
+
+        let payload = vec![0; 4096];
+        assert_eq!(
+            compress(Compression::None, &payload, &mut compressed, 0),
+            None
+        );
+
+        let payload = vec![0; MIN_COMPRESS_SIZE - 1];
+        assert_eq!(
+            compress(Compression::Lz4, &payload, &mut compressed, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn shared_compression() {
+        let shared = SharedCompression::default();
+        assert_eq!(shared.get(), Compression::None);
+
+        shared.clone().set(Compression::Lz4);
+        assert_eq!(shared.get(), Compression::Lz4);
+    }
+}
@@ -1,17 +1,113 @@
-use bevy::{ecs::system::SystemId, prelude::*};
+use std::collections::VecDeque;
+
+use bevy::{
+    ecs::system::{RegisteredSystemError, SystemId},
+    prelude::*,
+};
 
 use crate::net::ClientId;
 
 use super::{Message, MessageType};
 
+/// Dead letters kept, at most. Older ones are discarded as new ones arrive.
+const MAX_DEAD_LETTERS: usize = 256;
+
+/// What every message handler returns, once its output is converted by [`HandlerOutput`].
+type HandlerResult = Result<(), String>;
+
+/// Output of message handler systems. Handlers may either return nothing or a `Result`, in which
+/// case failures are logged and pushed to [`DeadLetters`].
+pub trait HandlerOutput: Send + Sync + 'static {
+    fn into_result(self) -> HandlerResult;
+}
+
+impl HandlerOutput for () {
+    fn into_result(self) -> HandlerResult {
+        Ok(())
+    }
+}
+
+impl<E: std::fmt::Display + Send + Sync + 'static> HandlerOutput for Result<(), E> {
+    fn into_result(self) -> HandlerResult {
+        self.map_err(|err| err.to_string())
+    }
+}
+
+fn into_handler_result<O: HandlerOutput>(In(output): In<O>) -> HandlerResult {
+    output.into_result()
+}
+
+/// Why a message ended up on [`DeadLetters`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// There is no handler registered for the message.
+    NoHandler,
+    /// A handler failed or returned an error.
+    Failed(String),
+}
+
+/// A message which couldn't be delivered or whose handler failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub client_id: ClientId,
+    /// Message type, since the message itself is consumed by handlers.
+    pub message: String,
+    pub reason: DeadLetterReason,
+}
+
+/// Messages which couldn't be delivered or whose handlers failed, kept for inspection on tests and
+/// debug UI. Only the last [`MAX_DEAD_LETTERS`] are kept. This resource is inserted on the first
+/// dead letter.
+#[derive(Resource, Debug, Default)]
+pub struct DeadLetters {
+    letters: VecDeque<DeadLetter>,
+    total: u64,
+}
+
+impl DeadLetters {
+    pub fn push(&mut self, letter: DeadLetter) {
+        if self.letters.len() == MAX_DEAD_LETTERS {
+            self.letters.pop_front();
+        }
+        self.letters.push_back(letter);
+        self.total += 1;
+    }
+
+    /// Dead letters kept, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &DeadLetter> {
+        self.letters.iter()
+    }
+
+    pub fn last(&self) -> Option<&DeadLetter> {
+        self.letters.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.letters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.letters.is_empty()
+    }
+
+    /// Dead letters received so far, including discarded ones.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn clear(&mut self) {
+        self.letters.clear();
+    }
+}
+
 #[derive(Resource, Default, Clone, Debug, Deref, DerefMut)]
-pub(crate) struct CopyHandlers<I: Copy>(Vec<SystemId<I, ()>>);
+pub(crate) struct CopyHandlers<I: Copy>(Vec<SystemId<I, HandlerResult>>);
 
 pub trait NoCopy {}
 impl<M> NoCopy for (ClientId, M) where M: NoCopy {}
 
 #[derive(Resource, Debug, Clone, Copy, Deref, DerefMut)]
-pub(crate) struct MoveHandler<I: NoCopy>(SystemId<I, ()>);
+pub(crate) struct MoveHandler<I: NoCopy>(SystemId<I, HandlerResult>);
 
 pub trait MessageHandlerInput<T, M> {}
 
@@ -21,8 +117,9 @@ impl<T: MessageType, M> MessageHandlerInput<T, M> for (ClientId, M) where M: Mes
 pub trait RegisterMessageHandler<T: MessageType, M: Message<T>> {
     fn set_message_handler<
         I: MessageHandlerInput<T, M> + NoCopy + 'static,
+        O: HandlerOutput,
         Marker,
-        S: IntoSystem<I, (), Marker> + 'static,
+        S: IntoSystem<I, O, Marker> + 'static,
     >(
         &mut self,
         system: S,
@@ -32,8 +129,9 @@ pub trait RegisterMessageHandler<T: MessageType, M: Message<T>> {
 
     fn add_message_handler<
         I: MessageHandlerInput<T, M> + Copy + 'static,
+        O: HandlerOutput,
         Marker,
-        S: IntoSystem<I, (), Marker> + 'static,
+        S: IntoSystem<I, O, Marker> + 'static,
     >(
         &mut self,
         system: S,
@@ -43,8 +141,9 @@ pub trait RegisterMessageHandler<T: MessageType, M: Message<T>> {
 impl<T: MessageType, M: Message<T>> RegisterMessageHandler<T, M> for App {
     fn set_message_handler<
         I: MessageHandlerInput<T, M> + NoCopy + 'static,
+        O: HandlerOutput,
         Marker,
-        S: IntoSystem<I, (), Marker> + 'static,
+        S: IntoSystem<I, O, Marker> + 'static,
     >(
         &mut self,
         system: S,
@@ -52,7 +151,9 @@ impl<T: MessageType, M: Message<T>> RegisterMessageHandler<T, M> for App {
     where
         M: NoCopy,
     {
-        let id = self.world.register_system(system);
+        let id = self
+            .world
+            .register_system(system.pipe(into_handler_result::<O>));
 
         if self.world.contains_resource::<MoveHandler<M>>()
             || self.world.contains_resource::<MoveHandler<(ClientId, M)>>()
@@ -67,13 +168,16 @@ impl<T: MessageType, M: Message<T>> RegisterMessageHandler<T, M> for App {
 
     fn add_message_handler<
         I: MessageHandlerInput<T, M> + Copy + 'static,
+        O: HandlerOutput,
         Marker,
-        S: IntoSystem<I, (), Marker> + 'static,
+        S: IntoSystem<I, O, Marker> + 'static,
     >(
         &mut self,
         system: S,
     ) -> &mut Self {
-        let id = self.world.register_system(system);
+        let id = self
+            .world
+            .register_system(system.pipe(into_handler_result::<O>));
 
         self.world
             .get_resource_or_insert_with(|| CopyHandlers(Vec::new()))
@@ -83,6 +187,42 @@ impl<T: MessageType, M: Message<T>> RegisterMessageHandler<T, M> for App {
     }
 }
 
+/// Flattens the result of running a handler system, so both failing to run it and the handler
+/// returning an error are treated the same.
+fn flatten<I: 'static>(
+    result: Result<HandlerResult, RegisteredSystemError<I, HandlerResult>>,
+) -> HandlerResult {
+    result.map_err(|err| err.to_string())?
+}
+
+fn push_dead_letter<T: MessageType>(
+    world: &mut World,
+    client_id: ClientId,
+    msg_type: &T,
+    reason: DeadLetterReason,
+) {
+    world
+        .get_resource_or_insert_with(DeadLetters::default)
+        .push(DeadLetter {
+            client_id,
+            message: format!("{msg_type:?}"),
+            reason,
+        });
+}
+
+/// Logs and pushes a failed handler to [`DeadLetters`], if it failed.
+fn report<T: MessageType>(
+    world: &mut World,
+    client_id: ClientId,
+    msg_type: &T,
+    result: HandlerResult,
+) {
+    if let Err(err) = result {
+        error!("Failed to execute handler for message {msg_type:?}({client_id}). Error: {err}");
+        push_dead_letter(world, client_id, msg_type, DeadLetterReason::Failed(err));
+    }
+}
+
 //
 pub trait RunMessageHandlers<T: MessageType> {
     fn run_handlers<M: Message<T> + Copy>(&mut self, client_id: ClientId, msg: Box<dyn Message<T>>);
@@ -99,7 +239,7 @@ impl<T: MessageType> RunMessageHandlers<T> for World {
         client_id: ClientId,
         msg: Box<dyn Message<T>>,
     ) {
-        let src = msg.msg_source();
+        let msg_type = msg.msg_type();
 
         let (copy_handlers, copy_id_handlers) = (
             self.get_resource::<CopyHandlers<M>>().cloned(),
@@ -108,30 +248,27 @@ impl<T: MessageType> RunMessageHandlers<T> for World {
 
         if copy_handlers.is_none() && copy_id_handlers.is_none() {
             warn!("No handlers found for message {msg:?}. Skipping it");
+            push_dead_letter(self, client_id, &msg_type, DeadLetterReason::NoHandler);
             return;
         }
 
         let msg = msg
             .downcast::<M>()
-            .expect("To be able to downcast message {src:?}.");
+            .unwrap_or_else(|_| panic!("To be able to downcast message {msg_type:?}."));
 
         if let Some(CopyHandlers(system_ids)) = copy_handlers {
             for system_id in system_ids {
                 // Only Copy types are allowed to be added on MessageHandlers
-                if let Err(err) = self.run_system_with_input(system_id, msg) {
-                    error!("Failed to execute handler for message {src:?}. Error: {err}");
-                }
+                let result = flatten(self.run_system_with_input(system_id, msg));
+                report(self, client_id, &msg_type, result);
             }
         }
 
         if let Some(CopyHandlers(system_ids)) = copy_id_handlers {
             for system_id in system_ids {
                 // Only Copy types are allowed to be added on MessageHandlers
-                if let Err(err) = self.run_system_with_input(system_id, (client_id, msg)) {
-                    error!(
-                        "Failed to execute handler for message {src:?}({client_id}). Error: {err}"
-                    );
-                }
+                let result = flatten(self.run_system_with_input(system_id, (client_id, msg)));
+                report(self, client_id, &msg_type, result);
             }
         }
     }
@@ -141,26 +278,25 @@ impl<T: MessageType> RunMessageHandlers<T> for World {
         client_id: ClientId,
         msg: Box<dyn Message<T>>,
     ) {
-        let src = msg.msg_source();
+        let msg_type = msg.msg_type();
 
         let msg = msg
             .downcast::<M>()
-            .expect("To be able to downcast message {src:?}.");
+            .unwrap_or_else(|_| panic!("To be able to downcast message {msg_type:?}."));
 
-        if let Some(&MoveHandler(system_id)) = self.get_resource::<MoveHandler<M>>() {
-            if let Err(err) = self.run_system_with_input(system_id, msg) {
-                error!("Failed to execute handler for message {src:?}. Error: {err}");
-            }
+        let result = if let Some(&MoveHandler(system_id)) = self.get_resource::<MoveHandler<M>>() {
+            flatten(self.run_system_with_input(system_id, msg))
         } else if let Some(&MoveHandler(system_id)) =
             self.get_resource::<MoveHandler<(ClientId, M)>>()
         {
-            if let Err(err) = self.run_system_with_input(system_id, (client_id, msg)) {
-                error!("Failed to execute handler for message {src:?}({client_id}). Error: {err}");
-            }
+            flatten(self.run_system_with_input(system_id, (client_id, msg)))
         } else {
             warn!("No handlers found for message {msg:?}. Skipping it");
+            push_dead_letter(self, client_id, &msg_type, DeadLetterReason::NoHandler);
             return;
-        }
+        };
+
+        report(self, client_id, &msg_type, result);
     }
 }
 
@@ -174,7 +310,7 @@ mod tests {
         RegisterMessageHandler, RunMessageHandlers,
     };
 
-    use super::CopyHandlers;
+    use super::{CopyHandlers, DeadLetter, DeadLetterReason, DeadLetters, MAX_DEAD_LETTERS};
 
     #[message_source(MessageSource::Client)]
     enum TestMsg {
@@ -190,10 +326,10 @@ mod tests {
 
         // Act
         for _ in 0..10 {
-            app.add_message_handler(|_: In<A>| todo!());
+            app.add_message_handler(|_: In<A>| {});
         }
         for _ in 0..9 {
-            app.add_message_handler(|_: In<(ClientId, A)>| todo!());
+            app.add_message_handler(|_: In<(ClientId, A)>| {});
         }
 
         // Assert
@@ -218,7 +354,7 @@ mod tests {
         let mut app = App::new();
 
         // Act
-        app.set_message_handler(|_: In<B>| todo!());
+        app.set_message_handler(|_: In<B>| {});
 
         // Assert
         let _ = app
@@ -233,7 +369,7 @@ mod tests {
         let mut app = App::new();
 
         // Act
-        app.set_message_handler(|_: In<(ClientId, B)>| todo!());
+        app.set_message_handler(|_: In<(ClientId, B)>| {});
 
         // Assert
         let _ = app
@@ -249,8 +385,8 @@ mod tests {
         let mut app = App::new();
 
         // Act
-        app.set_message_handler(|_: In<B>| todo!());
-        app.set_message_handler(|_: In<B>| todo!());
+        app.set_message_handler(|_: In<B>| {});
+        app.set_message_handler(|_: In<B>| {});
 
         // Assert
     }
@@ -262,8 +398,8 @@ mod tests {
         let mut app = App::new();
 
         // Act
-        app.set_message_handler(|_: In<B>| todo!());
-        app.set_message_handler(|_: In<(ClientId, B)>| todo!());
+        app.set_message_handler(|_: In<B>| {});
+        app.set_message_handler(|_: In<(ClientId, B)>| {});
 
         // Assert
     }
@@ -349,4 +485,90 @@ mod tests {
             "Handler system must run and should match given value and id"
         );
     }
+
+    #[test]
+    fn run_handlers_failed() {
+        // Arrange
+        let mut app = App::new();
+        app.add_message_handler(|_: In<A>| -> Result<(), String> { Ok(()) });
+        app.add_message_handler(|_: In<(ClientId, A)>| -> Result<(), String> {
+            Err("Invalid A".to_string())
+        });
+        let boxed: BoxedMessage<TestMsg> = Box::new(A);
+
+        // Act
+        app.world.run_handlers::<A>(42.into(), boxed);
+
+        // Assert
+        let dead_letters = app
+            .world
+            .get_resource::<DeadLetters>()
+            .expect("Failed handler should insert DeadLetters");
+
+        assert_eq!(
+            dead_letters.total(),
+            1,
+            "Only failed handler should be dead"
+        );
+        assert_eq!(
+            dead_letters.last(),
+            Some(&DeadLetter {
+                client_id: 42.into(),
+                message: "A".to_string(),
+                reason: DeadLetterReason::Failed("Invalid A".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn run_handler_without_handler() {
+        // Arrange
+        let mut app = App::new();
+        let boxed: BoxedMessage<TestMsg> = Box::new(B(11));
+
+        // Act
+        app.world.run_handler::<B>(42.into(), boxed);
+
+        // Assert
+        let dead_letters = app
+            .world
+            .get_resource::<DeadLetters>()
+            .expect("Undeliverable message should insert DeadLetters");
+
+        assert_eq!(
+            dead_letters.last(),
+            Some(&DeadLetter {
+                client_id: 42.into(),
+                message: "B".to_string(),
+                reason: DeadLetterReason::NoHandler,
+            })
+        );
+    }
+
+    #[test]
+    fn dead_letters_bounded() {
+        // Arrange
+        let mut dead_letters = DeadLetters::default();
+
+        // Act
+        for i in 0..MAX_DEAD_LETTERS + 10 {
+            dead_letters.push(DeadLetter {
+                client_id: ClientId::default(),
+                message: i.to_string(),
+                reason: DeadLetterReason::NoHandler,
+            });
+        }
+
+        // Assert
+        assert_eq!(dead_letters.len(), MAX_DEAD_LETTERS);
+        assert_eq!(dead_letters.total(), MAX_DEAD_LETTERS as u64 + 10);
+        assert_eq!(
+            dead_letters
+                .iter()
+                .next()
+                .map(|letter| letter.message.as_str()),
+            Some("10"),
+            "Oldest letters should be discarded"
+        );
+    }
 }
@@ -3,13 +3,33 @@ use std::any::Any;
 use bevy::{ecs::world::World, log::error};
 
 mod channel;
-pub use channel::{Channel, ChannelError, ChannelPair};
+pub use channel::{Channel, ChannelConfig, ChannelError, ChannelPair, OverflowPolicy, QueueStats};
+
+mod compression;
+pub use compression::{Compression, MIN_COMPRESS_SIZE};
+
+mod pool;
+pub use pool::{BufferPool, PooledBuffer};
 
 mod net;
-pub use net::{connect_to_server, start_server, Client, ClientId, Server};
+#[cfg(not(target_arch = "wasm32"))]
+pub use net::{connect_to_server, start_server};
+pub use net::{Client, ClientId, ConnectionConfig, NetStats, Server};
+
+// Only meant for tests, so it isn't built by default.
+#[cfg(any(test, feature = "simulation"))]
+mod simulation;
+#[cfg(any(test, feature = "simulation"))]
+pub use simulation::{LinkConditions, SimulatedLink};
+
+mod size;
+pub use size::max_serialized_size;
 
 mod ecs;
-pub use ecs::{NoCopy, RegisterMessageHandler, RunMessageHandlers};
+pub use ecs::{
+    DeadLetter, DeadLetterReason, DeadLetters, HandlerOutput, NoCopy, RegisterMessageHandler,
+    RunMessageHandlers,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum MessageError {
@@ -23,9 +43,14 @@ pub enum MessageError {
     Channel(#[from] channel::ChannelError),
     #[error("Failed to parse {0}. Invalid message code {1}.")]
     InvalidMessage(&'static str, u16),
+    #[error("Failed to decompress message {0}.")]
+    Decompress(u16),
 }
 
 pub trait MessageType: std::fmt::Debug + Send + Sync + 'static {
+    /// Largest [`MessageType::max_size`] of all bounded messages.
+    fn max_message_size() -> usize;
+
     fn name() -> &'static str;
     fn source() -> MessageSource;
     fn code(&self) -> u16;
@@ -33,13 +58,21 @@ pub trait MessageType: std::fmt::Debug + Send + Sync + 'static {
     where
         Self: Sized;
     fn deserialize_boxed(&self, buf: &[u8]) -> Result<BoxedMessage<Self>, MessageError>;
+    /// Serializes the given message at the end of `buf`, so a header can be written before it
+    /// without copying the message around.
+    ///
+    /// **Returns** the serialized message size.
     fn serialize_boxed(
         &self,
         boxed: BoxedMessage<Self>,
-        buf: &mut [u8],
+        buf: &mut Vec<u8>,
     ) -> Result<u32, MessageError>;
     fn run_handlers(&self, boxed: BoxedMessage<Self>, client_id: net::ClientId, world: &mut World);
     fn is_unit_type(&self) -> bool;
+    /// Max serialized size of this message, or `None` if it is unbounded, like messages which
+    /// hold a `Vec`. Unbounded messages are fragmented when sent over network. See
+    /// [`max_serialized_size`].
+    fn max_size(&self) -> Option<usize>;
 }
 
 #[derive(Debug, Hash, Eq, PartialEq)]
@@ -123,7 +156,7 @@ mod tests {
         assert!(TestMsg::UnitMsg.is_unit_type());
 
         let boxed: BoxedMessage<TestMsg> = Box::new(UnitMsg);
-        let mut buf = vec![0u8; 0];
+        let mut buf = vec![];
         let size = TestMsg::UnitMsg
             .serialize_boxed(boxed, &mut buf)
             .expect("Unit types should generate not byte when serializing");
@@ -142,7 +175,7 @@ mod tests {
         assert!(!TestMsg::UnnamedMsg.is_unit_type());
 
         let boxed: BoxedMessage<TestMsg> = Box::new(UnnamedMsg(1, 2, true));
-        let mut buf = vec![0u8; 64];
+        let mut buf = vec![];
         let size = TestMsg::UnnamedMsg
             .serialize_boxed(boxed, &mut buf)
             .unwrap();
@@ -170,7 +203,7 @@ mod tests {
             c: (22, 33),
         });
 
-        let mut buf = vec![0u8; 64];
+        let mut buf = vec![];
         let size = TestMsg::NamedMsg.serialize_boxed(boxed, &mut buf).unwrap();
 
         assert!(size > 0);
@@ -194,7 +227,7 @@ mod tests {
 
         let boxed: BoxedMessage<TestMsg> = Box::new(NoCopyMsg("msg".to_string(), vec![1, 2, 3]));
 
-        let mut buf = vec![0u8; 64];
+        let mut buf = vec![];
         let size = TestMsg::NoCopyMsg.serialize_boxed(boxed, &mut buf).unwrap();
 
         assert!(size > 0);
@@ -208,4 +241,45 @@ mod tests {
         assert_eq!(s, "msg".to_string());
         assert_eq!(v, vec![1, 2, 3]);
     }
+
+    #[test]
+    fn macro_message_source_max_size() {
+        assert_eq!(TestMsg::UnitMsg.max_size(), Some(0));
+        assert_eq!(TestMsg::NoCopyMsg.max_size(), None);
+
+        let named = TestMsg::NamedMsg.max_size().unwrap();
+        let unnamed = TestMsg::UnnamedMsg.max_size().unwrap();
+        assert_eq!(named, 1 + 4 + 2);
+        assert_eq!(unnamed, 4 + 1 + 1);
+        assert_eq!(TestMsg::max_message_size(), named.max(unnamed));
+
+        // Max size must hold any value
+        let boxed: BoxedMessage<TestMsg> = Box::new(NamedMsg {
+            a: i8::MAX,
+            b: f32::MAX,
+            c: (u8::MAX, u8::MAX),
+        });
+        let mut buf = vec![];
+        let size = TestMsg::NamedMsg.serialize_boxed(boxed, &mut buf).unwrap();
+        assert!(size as usize <= named);
+    }
+
+    #[test]
+    fn macro_message_source_append() {
+        let header = [7u8; 6];
+        let mut buf = header.to_vec();
+
+        let boxed: BoxedMessage<TestMsg> = Box::new(UnnamedMsg(1, 2, true));
+        let size = TestMsg::UnnamedMsg
+            .serialize_boxed(boxed, &mut buf)
+            .unwrap();
+
+        assert_eq!(buf.len(), header.len() + size as usize);
+        assert_eq!(buf[..header.len()], header, "Header should be kept");
+
+        let boxed = TestMsg::UnnamedMsg
+            .deserialize_boxed(&buf[header.len()..])
+            .unwrap();
+        assert_eq!(boxed.downcast::<UnnamedMsg>().unwrap().0, 1);
+    }
 }
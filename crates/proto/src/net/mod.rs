@@ -0,0 +1,513 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    channel::{Channel, ChannelConfig, ChannelPair, OverflowPolicy},
+    compression::SharedCompression,
+    Compression, MessageType,
+};
+
+// There are no TCP sockets on browsers, so a different transport is needed there.
+#[cfg(not(target_arch = "wasm32"))]
+mod tcp;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tcp::{connect_to_server, start_server};
+
+/// Messages received from network which weren't handled yet, before network task stops reading
+/// from socket, so a stalled consumer slows down the sender, instead of piling up messages.
+const INCOMING_CAPACITY: usize = 1024;
+/// Messages waiting to be sent over network, before sending more fails. Chunk updates are sent in
+/// bursts, so this must fit a whole landscape worth of messages.
+const OUTGOING_CAPACITY: usize = 64 * 1024;
+
+/// Channel settings of a network connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionConfig {
+    /// Messages received from network, waiting to be handled.
+    pub incoming: ChannelConfig,
+    /// Messages waiting to be sent over network.
+    pub outgoing: ChannelConfig,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            incoming: ChannelConfig::bounded(INCOMING_CAPACITY, OverflowPolicy::Block),
+            outgoing: ChannelConfig::bounded(OUTGOING_CAPACITY, OverflowPolicy::Error),
+        }
+    }
+}
+
+/// Traffic counters of a single connection, updated by network tasks as packets are sent and
+/// received.
+#[derive(Debug, Default)]
+pub struct NetStats {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+}
+
+impl NetStats {
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn messages_received(&self) -> u64 {
+        self.messages_received.load(Ordering::Relaxed)
+    }
+
+    fn add_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn add_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.messages_received.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct ClientId(u32);
+
+impl std::fmt::Display for ClientId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<ClientId> for u32 {
+    fn from(value: ClientId) -> Self {
+        value.0
+    }
+}
+
+impl From<u32> for ClientId {
+    fn from(value: u32) -> Self {
+        ClientId(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Client<S, R> {
+    id: ClientId,
+    addr: SocketAddr,
+    channel: Channel<R, S>,
+    closed: Arc<AtomicBool>,
+    stats: Arc<NetStats>,
+    compression: SharedCompression,
+}
+
+impl<S: MessageType, R: MessageType> Client<S, R> {
+    fn new(
+        id: ClientId,
+        addr: SocketAddr,
+        server: Channel<R, S>,
+        closed: Arc<AtomicBool>,
+        stats: Arc<NetStats>,
+        compression: SharedCompression,
+    ) -> Self {
+        Self {
+            id,
+            addr,
+            channel: server,
+            closed,
+            stats,
+            compression,
+        }
+    }
+
+    /// Creates a client which isn't backed by a network connection, so messages are exchanged
+    /// in-process, like on tests.
+    ///
+    /// **Returns** the client and the channel on the other end, which acts like the remote client.
+    pub fn loopback(id: u32) -> (Self, Channel<S, R>) {
+        let ChannelPair { client, server } = Channel::<S, R>::new_pair();
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let closed = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(NetStats::default());
+        let compression = SharedCompression::default();
+
+        (
+            Self::new(ClientId(id), addr, server, closed, stats, compression),
+            client,
+        )
+    }
+
+    /// Creates a client like [`Client::loopback`], but messages are exchanged through a
+    /// [`crate::SimulatedLink`] with the given conditions, which must be advanced by caller.
+    ///
+    /// **Returns** the client, the channel on the other end, which acts like the remote client,
+    /// and the link between them.
+    #[cfg(any(test, feature = "simulation"))]
+    pub fn simulated(
+        id: u32,
+        conditions: crate::LinkConditions,
+    ) -> (Self, Channel<S, R>, crate::SimulatedLink<S, R>) {
+        let (ChannelPair { client, server }, link) = crate::SimulatedLink::pair(conditions);
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let closed = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(NetStats::default());
+        let compression = SharedCompression::default();
+
+        (
+            Self::new(ClientId(id), addr, server, closed, stats, compression),
+            client,
+            link,
+        )
+    }
+
+    pub fn channel(&self) -> &Channel<R, S> {
+        &self.channel
+    }
+
+    pub fn id(&self) -> ClientId {
+        self.id
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn stats(&self) -> &NetStats {
+        &self.stats
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(std::sync::atomic::Ordering::Relaxed) || self.channel().is_closed()
+    }
+
+    /// Compression of messages sent to this client. See [`Client::set_compression`].
+    pub fn compression(&self) -> Compression {
+        self.compression.get()
+    }
+
+    /// Sets the compression of messages sent to this client, from now on. Client must be able to
+    /// decode the given codec, so this should only be changed after asking it.
+    pub fn set_compression(&self, compression: Compression) {
+        self.compression.set(compression);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Server<S, R> {
+    channel: Channel<S, R>,
+    closed: Arc<AtomicBool>,
+    stats: Arc<NetStats>,
+}
+
+impl<S: MessageType, R: MessageType> Server<S, R> {
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    fn new(server: Channel<S, R>, closed: Arc<AtomicBool>, stats: Arc<NetStats>) -> Self {
+        Self {
+            channel: server,
+            closed,
+            stats,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn channel(&self) -> &Channel<S, R> {
+        &self.channel
+    }
+
+    pub fn stats(&self) -> &NetStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::{
+        tasks::{AsyncComputeTaskPool, TaskPool},
+        utils::default,
+    };
+    use futures_lite::future::block_on;
+    use projekto_proto_macros::message_source;
+
+    use crate::{
+        self as projekto_proto, connect_to_server, start_server, Client, Compression, Message,
+        MessageSource,
+    };
+
+    #[message_source(MessageSource::Client)]
+    enum TestMsg {
+        A,
+        B(u32),
+        #[no_copy]
+        C {
+            v: Vec<u8>,
+            s: bool,
+        },
+    }
+
+    #[test]
+    fn connection() {
+        let bind_addr = "127.0.0.1:11225";
+        let clients = Arc::new(Mutex::new(vec![]));
+        let connected_clients = clients.clone();
+        let server_task = AsyncComputeTaskPool::get_or_init(TaskPool::default).spawn(async move {
+            let _ = start_server(bind_addr, default(), |client: Client<TestMsg, TestMsg>| {
+                connected_clients.lock().unwrap().push(client);
+            })
+            .await;
+        });
+
+        // Wait server open socket
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let client_task = AsyncComputeTaskPool::get_or_init(TaskPool::default).spawn(async move {
+            connect_to_server::<TestMsg, TestMsg>(bind_addr, default()).await
+        });
+
+        let result = block_on(client_task);
+        assert!(result.is_ok(), "Should be able to connect to server");
+
+        // Wait packet be sent
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        assert_eq!(
+            clients.lock().unwrap().len(),
+            1,
+            "One client should be connected"
+        );
+
+        let client = clients.lock().unwrap()[0].clone();
+        assert!(!client.is_closed(), "Client channel must be open");
+
+        block_on(async move { server_task.cancel().await });
+
+        assert!(client.is_closed(), "Client channel must be closed");
+    }
+
+    fn test_client_send_msg<M: Message<TestMsg>>(msg: M, port: u32) -> M {
+        let bind_addr = format!("127.0.0.1:{port}");
+        let clients = Arc::new(Mutex::new(vec![]));
+        let connected_clients = clients.clone();
+
+        let server_bind_addr = bind_addr.clone();
+        let server_task = AsyncComputeTaskPool::get_or_init(TaskPool::default).spawn(async move {
+            let _ = start_server(
+                server_bind_addr,
+                default(),
+                |client: Client<TestMsg, TestMsg>| {
+                    connected_clients.lock().unwrap().push(client);
+                },
+            )
+            .await;
+        });
+
+        // Wait server open socket
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let client_task = AsyncComputeTaskPool::get_or_init(TaskPool::default).spawn(async move {
+            connect_to_server::<TestMsg, TestMsg>(bind_addr, default()).await
+        });
+
+        let server_conn = block_on(client_task).expect("Should be connected to server");
+
+        server_conn.channel().send(msg).unwrap();
+
+        let mut attempts = 10;
+        let boxed = loop {
+            let Some(client) = clients.lock().unwrap().first().cloned() else {
+                continue;
+            };
+
+            if let Some(boxed) = client.channel().try_recv() {
+                assert_eq!(client.stats().messages_received(), 1);
+                assert_eq!(
+                    client.stats().bytes_received(),
+                    server_conn.stats().bytes_sent()
+                );
+                break boxed;
+            }
+
+            assert!(
+                !client.is_closed(),
+                "client should not be closed. This means a possible error on channel"
+            );
+
+            // Avoid spin lock
+            std::thread::sleep(std::time::Duration::from_millis(10));
+
+            attempts -= 1;
+            if attempts <= 0 {
+                panic!("Timeout while waiting from message");
+            }
+        };
+
+        block_on(async move { server_task.cancel().await });
+        match boxed.downcast() {
+            Ok(msg) => msg,
+            Err(err) => panic!("{err:?}"),
+        }
+    }
+
+    #[test]
+    fn loopback() {
+        let (client, remote) = Client::<TestMsg, TestMsg>::loopback(7);
+        assert_eq!(client.id(), 7.into());
+
+        remote.send(B(42)).unwrap();
+        let msg = client
+            .channel()
+            .try_recv()
+            .expect("Message should be received");
+        assert_eq!(msg.downcast::<B>().unwrap().0, 42);
+
+        client.channel().send(A).unwrap();
+        assert!(remote.try_recv().is_some());
+        assert!(!client.is_closed());
+    }
+
+    #[test]
+    fn simulated() {
+        let conditions = crate::LinkConditions {
+            latency: std::time::Duration::from_millis(100),
+            ..Default::default()
+        };
+        let (client, remote, mut link) = Client::<TestMsg, TestMsg>::simulated(3, conditions);
+
+        remote.send(B(42)).unwrap();
+        link.advance(std::time::Duration::from_millis(50));
+        assert!(client.channel().try_recv().is_none());
+
+        link.advance(std::time::Duration::from_millis(50));
+        let msg = client
+            .channel()
+            .try_recv()
+            .expect("Message should arrive after latency");
+        assert_eq!(msg.downcast::<B>().unwrap().0, 42);
+
+        link.disconnect();
+        assert!(client.is_closed());
+    }
+
+    #[test]
+    fn client_send_unit_msg() {
+        let res = test_client_send_msg(A, 11227);
+        assert_eq!(res, A);
+    }
+
+    #[test]
+    fn client_send_unnamed_msg() {
+        let res = test_client_send_msg(B(42), 11228);
+        assert_eq!(res.0, 42);
+    }
+
+    #[test]
+    fn client_send_named_msg() {
+        let res = test_client_send_msg(
+            C {
+                v: vec![10, 11, 12],
+                s: true,
+            },
+            11229,
+        );
+        assert_eq!(res.v, vec![10, 11, 12]);
+        assert!(res.s);
+    }
+
+    #[test]
+    fn client_send_fragmented_msg() {
+        // Large enough to be split in a few fragments
+        let v = (0..200_000).map(|i| i as u8).collect::<Vec<_>>();
+        let res = test_client_send_msg(
+            C {
+                v: v.clone(),
+                s: true,
+            },
+            11231,
+        );
+        assert_eq!(res.v, v);
+        assert!(res.s);
+    }
+
+    #[test]
+    fn server_send_compressed_msg() {
+        let bind_addr = "127.0.0.1:11230";
+        let clients = Arc::new(Mutex::new(vec![]));
+        let connected_clients = clients.clone();
+        let server_task = AsyncComputeTaskPool::get_or_init(TaskPool::default).spawn(async move {
+            let _ = start_server(bind_addr, default(), |client: Client<TestMsg, TestMsg>| {
+                connected_clients.lock().unwrap().push(client);
+            })
+            .await;
+        });
+
+        // Wait server open socket
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let client_task = AsyncComputeTaskPool::get_or_init(TaskPool::default).spawn(async move {
+            connect_to_server::<TestMsg, TestMsg>(bind_addr, default()).await
+        });
+        let server_conn = block_on(client_task).expect("Should be connected to server");
+
+        // Wait client be accepted
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let client = clients.lock().unwrap()[0].clone();
+        client.set_compression(Compression::Lz4);
+        assert_eq!(client.compression(), Compression::Lz4);
+
+        let v = vec![42; 4096];
+        client
+            .channel()
+            .send(C {
+                v: v.clone(),
+                s: true,
+            })
+            .unwrap();
+
+        let mut attempts = 10;
+        let boxed = loop {
+            if let Some(boxed) = server_conn.channel().try_recv() {
+                break boxed;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(10));
+
+            attempts -= 1;
+            if attempts <= 0 {
+                panic!("Timeout while waiting from message");
+            }
+        };
+
+        assert!(
+            server_conn.stats().bytes_received() < v.len() as u64,
+            "Message should be compressed"
+        );
+        assert_eq!(
+            server_conn.stats().bytes_received(),
+            client.stats().bytes_sent()
+        );
+
+        block_on(async move { server_task.cancel().await });
+
+        let msg = boxed.downcast::<C>().unwrap();
+        assert_eq!(msg.v, v);
+        assert!(msg.s);
+    }
+}
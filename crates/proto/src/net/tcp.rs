@@ -0,0 +1,385 @@
+use std::{
+    io,
+    mem::size_of,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use async_net::{AsyncToSocketAddrs, TcpListener, TcpStream};
+use bevy::{
+    log::{debug, info},
+    tasks::{AsyncComputeTaskPool, TaskPool},
+};
+use futures_lite::{AsyncReadExt, AsyncWriteExt, StreamExt};
+
+use super::{Client, ClientId, ConnectionConfig, NetStats, Server};
+use crate::{
+    channel::{Channel, ChannelPair},
+    compression::{self, SharedCompression},
+    BufferPool, Compression, MessageError, MessageType, PooledBuffer,
+};
+
+/// Messages larger than this are rejected, so a corrupted or malicious size doesn't make the
+/// receiver allocate that much.
+const MAX_PAYLOAD_SIZE: usize = 1024 * 1024 * 32; // 32 MB
+
+/// Payloads larger than this are split in fragments of this size, at most, so a single huge
+/// message never needs a huge read. Bounded messages should always fit a single fragment.
+const MAX_FRAGMENT_SIZE: usize = 64 * 1024; // 64 KB
+
+/// Set on message code of packets which payload is compressed.
+const COMPRESSED_FLAG: u16 = 1 << 15;
+/// Set on message code of fragments which are followed by more fragments of the same message.
+const FRAGMENT_FLAG: u16 = 1 << 14;
+
+/// Packet header is the msg type (2 bytes) followed by msg size (4 bytes).
+const HEADER_SIZE: usize = size_of::<u16>() + size_of::<u32>();
+
+fn write_header(packet: &mut [u8], code: u16, msg_size: u32) {
+    packet[..size_of::<u16>()].copy_from_slice(&code.to_be_bytes());
+    packet[size_of::<u16>()..HEADER_SIZE].copy_from_slice(&msg_size.to_be_bytes());
+}
+
+fn invalid_data() -> MessageError {
+    MessageError::Io(std::io::ErrorKind::InvalidData.into())
+}
+
+/// Compresses the payload of the given packet into a new packet, with a flagged msg type.
+///
+/// **Returns** `None` if payload shouldn't be compressed. See [`compression::compress`].
+fn compress_packet(
+    compression: Compression,
+    code: u16,
+    packet: &[u8],
+    pool: &BufferPool,
+) -> Option<PooledBuffer> {
+    if compression == Compression::None {
+        return None;
+    }
+
+    // Compressed payload is written at the same offset on its own buffer.
+    let mut compressed = pool.take();
+    let payload = &packet[HEADER_SIZE..];
+    let size = compression::compress(compression, payload, &mut compressed, HEADER_SIZE)?;
+
+    compressed.truncate(HEADER_SIZE + size);
+    write_header(&mut compressed, code | COMPRESSED_FLAG, size as u32);
+
+    Some(compressed)
+}
+
+/// Writes the given packet, splitting its payload in fragments when it is larger than
+/// [`MAX_FRAGMENT_SIZE`].
+///
+/// **Returns** how many bytes were written.
+async fn write_packet(stream: &mut TcpStream, packet: &[u8]) -> io::Result<usize> {
+    if packet.len() <= HEADER_SIZE + MAX_FRAGMENT_SIZE {
+        stream.write_all(packet).await?;
+        return Ok(packet.len());
+    }
+
+    let code = u16::from_be_bytes([packet[0], packet[1]]);
+    let mut fragments = packet[HEADER_SIZE..].chunks(MAX_FRAGMENT_SIZE).peekable();
+    let mut header = [0; HEADER_SIZE];
+    let mut written = 0;
+
+    while let Some(fragment) = fragments.next() {
+        let flag = if fragments.peek().is_some() {
+            FRAGMENT_FLAG
+        } else {
+            0
+        };
+
+        write_header(&mut header, code | flag, fragment.len() as u32);
+        stream.write_all(&header).await?;
+        stream.write_all(fragment).await?;
+        written += HEADER_SIZE + fragment.len();
+    }
+
+    Ok(written)
+}
+
+async fn net_to_channel<S: MessageType, R: MessageType>(
+    mut stream: TcpStream,
+    channel: Channel<S, R>,
+    stats: Arc<NetStats>,
+    pool: BufferPool,
+) -> Result<(), MessageError> {
+    let mut msg_code = [0; size_of::<u16>()];
+    let mut msg_len = [0; size_of::<u32>()];
+
+    // Code and payload of the fragments received so far of current message, if any.
+    let mut pending: Option<(u16, PooledBuffer)> = None;
+    // Bytes received of current message, including the headers of all its fragments.
+    let mut received = 0;
+
+    loop {
+        // First get the message type and check if it is a valid one.
+        stream.read_exact(&mut msg_code).await?;
+        let code = u16::from_be_bytes(msg_code);
+        let compressed = code & COMPRESSED_FLAG != 0;
+        let fragment = code & FRAGMENT_FLAG != 0;
+        let msg_type = S::try_from_code(code & !(COMPRESSED_FLAG | FRAGMENT_FLAG))?;
+
+        let boxed = if msg_type.is_unit_type() {
+            // Fragments of a message are never interleaved with other messages.
+            if pending.is_some() {
+                return Err(invalid_data());
+            }
+
+            // Unit type doesn't have content
+            stats.add_received(msg_code.len());
+            msg_type.deserialize_boxed(&[])?
+        } else {
+            // Then check if the message len is also valid.
+            stream.read_exact(&mut msg_len).await?;
+            let msg_len = u32::from_be_bytes(msg_len) as usize;
+
+            if msg_len == 0 {
+                return Err(MessageError::Io(std::io::ErrorKind::BrokenPipe.into()));
+            }
+
+            if msg_len > MAX_FRAGMENT_SIZE {
+                return Err(invalid_data());
+            }
+
+            // Get a pooled buffer which fits the incomming message.
+            let mut buffer = pool.take();
+            buffer.resize(msg_len, 0);
+            stream.read_exact(&mut buffer).await?;
+            received += HEADER_SIZE + msg_len;
+
+            // Fragments are appended to the first one, until the last one arrives.
+            let code = code & !FRAGMENT_FLAG;
+            if let Some((pending_code, mut payload)) = pending.take() {
+                if pending_code != code {
+                    return Err(invalid_data());
+                }
+
+                payload.extend_from_slice(&buffer);
+                buffer = payload;
+            }
+
+            // Bounded messages can't be larger than their max size, even when compressed.
+            let max_size = msg_type.max_size().unwrap_or(MAX_PAYLOAD_SIZE);
+            if buffer.len() > max_size {
+                return Err(invalid_data());
+            }
+
+            if fragment {
+                pending = Some((code, buffer));
+                continue;
+            }
+
+            stats.add_received(std::mem::take(&mut received));
+
+            if compressed {
+                let mut decompressed = pool.take();
+                compression::decompress(&buffer, &mut decompressed, max_size)
+                    .ok_or(MessageError::Decompress(code))?;
+                msg_type.deserialize_boxed(&decompressed)?
+            } else {
+                msg_type.deserialize_boxed(&buffer)?
+            }
+        };
+
+        channel.send_boxed_async(boxed).await?;
+    }
+}
+
+async fn channel_to_net<S: MessageType, R: MessageType>(
+    mut stream: TcpStream,
+    channel: Channel<S, R>,
+    stats: Arc<NetStats>,
+    compression: SharedCompression,
+    pool: BufferPool,
+) -> Result<(), MessageError> {
+    debug_assert!(
+        S::max_message_size() <= MAX_FRAGMENT_SIZE,
+        "Bounded messages should fit a single fragment"
+    );
+
+    while let Ok(boxed) = channel.recv().await {
+        let msg_type = boxed.msg_type();
+        let code = msg_type.code();
+
+        let mut packet = pool.take();
+
+        if msg_type.is_unit_type() {
+            // Unit type doesn't have content. Send only msg type
+            packet.extend_from_slice(&code.to_be_bytes());
+        } else {
+            // Serialize right after header, which is written in place once msg size is known, so
+            // the message is never copied. Bounded messages never need to grow the buffer.
+            packet.resize(HEADER_SIZE, 0);
+            packet.reserve(msg_type.max_size().unwrap_or_default());
+            let msg_size = msg_type.serialize_boxed(boxed, &mut packet)?;
+
+            match compress_packet(compression.get(), code, &packet, &pool) {
+                Some(compressed) => packet = compressed,
+                None => write_header(&mut packet, code, msg_size),
+            }
+        }
+
+        let sent = write_packet(&mut stream, &packet).await?;
+        stats.add_sent(sent);
+        stream.flush().await?;
+    }
+
+    stream.close().await?;
+    channel.close();
+
+    Ok(())
+}
+
+struct CloseOnDrop<S, R>(Channel<S, R>, Channel<R, S>);
+impl<S, R> CloseOnDrop<S, R> {
+    fn is_closed(&self) -> bool {
+        self.0.is_closed() || self.1.is_closed()
+    }
+}
+
+impl<S, R> Drop for CloseOnDrop<S, R> {
+    fn drop(&mut self) {
+        self.0.close();
+        self.1.close();
+    }
+}
+
+pub async fn start_server<F, S: MessageType, R: MessageType>(
+    addr: impl AsyncToSocketAddrs,
+    config: ConnectionConfig,
+    on_client_connected: F,
+) -> Result<(), io::Error>
+where
+    F: Fn(Client<S, R>),
+{
+    let listener = TcpListener::bind(addr).await?;
+
+    let mut incoming = listener.incoming();
+
+    let bind_addr = listener.local_addr()?;
+    info!("[Networking] Starting to listen: {bind_addr}");
+
+    let mut channel_guards = vec![];
+    // Buffers are shared by all connections, so idle ones don't hold any memory.
+    let pool = BufferPool::default();
+
+    let mut client_idx = 0;
+    while let Some(stream) = incoming.next().await {
+        let stream = stream?;
+        stream.set_nodelay(true)?;
+
+        let addr = stream.peer_addr()?;
+
+        client_idx += 1;
+        let id = ClientId(client_idx);
+
+        info!("[Networking] Client {id}({addr}) connected!");
+
+        // `client` end is used by network tasks, so it sends incoming messages.
+        let ChannelPair { client, server } =
+            Channel::<S, R>::new_pair_with(config.incoming, config.outgoing);
+        let closed = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(NetStats::default());
+        let compression = SharedCompression::default();
+
+        let stream_clone = stream.clone();
+        let client_clone = client.clone();
+        let recv_closed = closed.clone();
+        let recv_stats = stats.clone();
+        let recv_pool = pool.clone();
+        AsyncComputeTaskPool::get_or_init(TaskPool::default)
+            .spawn(async move {
+                if let Err(err) =
+                    net_to_channel(stream_clone, client_clone, recv_stats, recv_pool).await
+                {
+                    debug!("[{id}] Failed to receive messages from {addr}: Error: {err}");
+                    recv_closed.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+            })
+            .detach();
+
+        let send_closed = closed.clone();
+        let client_clone = client.clone();
+        let send_stats = stats.clone();
+        let send_compression = compression.clone();
+        let send_pool = pool.clone();
+        AsyncComputeTaskPool::get_or_init(TaskPool::default)
+            .spawn(async move {
+                let result = channel_to_net(
+                    stream,
+                    client_clone,
+                    send_stats,
+                    send_compression,
+                    send_pool,
+                )
+                .await;
+                if let Err(err) = result {
+                    debug!("[{id}] Failed to send messages to {addr}: Error: {err}");
+                    send_closed.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+            })
+            .detach();
+
+        on_client_connected(Client::new(
+            id,
+            addr,
+            server.clone(),
+            closed,
+            stats,
+            compression,
+        ));
+
+        channel_guards.push(CloseOnDrop(client, server));
+        channel_guards.retain(|t| !t.is_closed());
+    }
+
+    Ok(())
+}
+
+pub async fn connect_to_server<S: MessageType, R: MessageType>(
+    addr: impl AsyncToSocketAddrs,
+    config: ConnectionConfig,
+) -> Result<Server<S, R>, io::Error> {
+    let stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+
+    // `server` end is used by network tasks, so it sends incoming messages.
+    let ChannelPair { client, server } =
+        Channel::<S, R>::new_pair_with(config.outgoing, config.incoming);
+
+    let closed = Arc::new(AtomicBool::new(false));
+    let stats = Arc::new(NetStats::default());
+    let pool = BufferPool::default();
+
+    let stream_clone = stream.clone();
+    let server_clone = server.clone();
+    let send_closed = closed.clone();
+    let recv_stats = stats.clone();
+    let recv_pool = pool.clone();
+    AsyncComputeTaskPool::get_or_init(TaskPool::default)
+        .spawn(async move {
+            if let Err(err) =
+                net_to_channel(stream_clone, server_clone, recv_stats, recv_pool).await
+            {
+                debug!("Failed to receive messages from server: Error: {err:?}");
+                send_closed.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        })
+        .detach();
+
+    let recv_closed = closed.clone();
+    let send_stats = stats.clone();
+    AsyncComputeTaskPool::get_or_init(TaskPool::default)
+        .spawn(async move {
+            // Server messages are the large ones, so messages sent to server aren't compressed.
+            let compression = SharedCompression::default();
+            if let Err(err) = channel_to_net(stream, server, send_stats, compression, pool).await {
+                debug!("Failed to send messages to server: Error: {err:?}");
+                recv_closed.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        })
+        .detach();
+
+    Ok(Server::new(client, closed, stats))
+}
@@ -0,0 +1,114 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+/// Buffers kept on pool, at most. Extra buffers are freed when returned.
+const MAX_POOLED_BUFFERS: usize = 64;
+/// Buffers which grew larger than this, in bytes, are freed when returned, so a single huge message
+/// doesn't keep its memory around forever.
+const MAX_POOLED_CAPACITY: usize = 1024 * 1024; // 1 MB
+
+/// Pool of byte buffers reused by network tasks, so serializing and sending messages doesn't
+/// allocate on each message. It is cheap to clone and all clones share the same buffers.
+#[derive(Debug, Default, Clone)]
+pub struct BufferPool(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl BufferPool {
+    /// Takes an empty buffer from pool, or a new one if pool is empty. Buffer is returned to pool
+    /// when dropped.
+    pub fn take(&self) -> PooledBuffer {
+        let buffer = self.0.lock().ok().and_then(|mut buffers| buffers.pop());
+
+        PooledBuffer {
+            buffer: buffer.unwrap_or_default(),
+            pool: self.clone(),
+        }
+    }
+
+    /// Number of buffers available on pool.
+    pub fn len(&self) -> usize {
+        self.0
+            .lock()
+            .map(|buffers| buffers.len())
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn give_back(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 || buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+
+        if let Ok(mut buffers) = self.0.lock() {
+            if buffers.len() < MAX_POOLED_BUFFERS {
+                buffer.clear();
+                buffers.push(buffer);
+            }
+        }
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`], which is returned to it when dropped.
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: BufferPool,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.give_back(std::mem::take(&mut self.buffer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = BufferPool::default();
+        assert!(pool.is_empty());
+
+        let mut buffer = pool.take();
+        buffer.extend_from_slice(&[1, 2, 3]);
+        let ptr = buffer.as_ptr();
+        drop(buffer);
+
+        assert_eq!(pool.len(), 1);
+
+        let buffer = pool.take();
+        assert!(buffer.is_empty(), "Returned buffers should be cleared");
+        assert_eq!(buffer.as_ptr(), ptr, "Buffer memory should be reused");
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn huge_buffers_are_freed() {
+        let pool = BufferPool::default();
+
+        let mut buffer = pool.take();
+        buffer.resize(MAX_POOLED_CAPACITY + 1, 0);
+        drop(buffer);
+
+        assert!(pool.is_empty());
+    }
+}
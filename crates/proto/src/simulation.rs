@@ -0,0 +1,331 @@
+//! Simulated network link, which injects latency, jitter, reordering and losses between both ends
+//! of a channel, so tests can exercise bad connections without a real socket.
+//!
+//! Link never runs on its own. Time only moves when [`SimulatedLink::advance`] is called and random
+//! conditions come from a seeded generator, so the same test always sees the same outcome.
+
+use std::time::Duration;
+
+use crate::{BoxedMessage, Channel, ChannelPair, MessageType};
+
+/// Network conditions applied by a [`SimulatedLink`], on both directions.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LinkConditions {
+    /// Time each message takes to arrive.
+    pub latency: Duration,
+    /// Random extra time, up to this, each message takes to arrive.
+    pub jitter: Duration,
+    /// Chance, from 0 to 1, of a message being lost.
+    pub loss: f32,
+    /// Chance, from 0 to 1, of a message skipping latency, arriving before messages sent earlier.
+    pub reorder: f32,
+    /// Seed of random conditions.
+    pub seed: u64,
+}
+
+/// Small and deterministic random generator (SplitMix64), so simulation doesn't depend on any
+/// random crate behavior.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// **Returns** a random number in `[0, 1)`.
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn chance(&mut self, chance: f32) -> bool {
+        chance > 0.0 && self.next_f32() < chance
+    }
+}
+
+struct InFlight<T> {
+    deliver_at: Duration,
+    /// Send order, so messages arriving at the same time keep it.
+    seq: u64,
+    boxed: BoxedMessage<T>,
+}
+
+/// Messages travelling on a single direction of the link.
+struct Lane<T>(Vec<InFlight<T>>);
+
+impl<T: MessageType> Lane<T> {
+    fn take_due(&mut self, now: Duration) -> Vec<BoxedMessage<T>> {
+        let (mut due, in_flight) = std::mem::take(&mut self.0)
+            .into_iter()
+            .partition::<Vec<_>, _>(|msg| msg.deliver_at <= now);
+        self.0 = in_flight;
+
+        due.sort_by_key(|msg| (msg.deliver_at, msg.seq));
+        due.into_iter().map(|msg| msg.boxed).collect()
+    }
+}
+
+/// A link between two channel ends, which delivers messages according to [`LinkConditions`]. See
+/// [`SimulatedLink::pair`].
+pub struct SimulatedLink<S, R> {
+    conditions: LinkConditions,
+    rng: Rng,
+    now: Duration,
+    seq: u64,
+    dropped: u64,
+    /// Receives what client end sends and sends what server end sent to it.
+    client_side: Channel<R, S>,
+    /// Receives what server end sends and sends what client end sent to it.
+    server_side: Channel<S, R>,
+    to_server: Lane<S>,
+    to_client: Lane<R>,
+}
+
+impl<S: MessageType, R: MessageType> SimulatedLink<S, R> {
+    /// Creates a pair of channels connected by a simulated link. Messages sent by any end only
+    /// arrive on the other one as the link is advanced.
+    pub fn pair(conditions: LinkConditions) -> (ChannelPair<S, R>, Self) {
+        let ChannelPair {
+            client,
+            server: client_side,
+        } = Channel::<S, R>::new_pair();
+        let ChannelPair {
+            client: server_side,
+            server,
+        } = Channel::<S, R>::new_pair();
+
+        let link = Self {
+            conditions,
+            rng: Rng(conditions.seed),
+            now: Duration::ZERO,
+            seq: 0,
+            dropped: 0,
+            client_side,
+            server_side,
+            to_server: Lane(vec![]),
+            to_client: Lane(vec![]),
+        };
+
+        (ChannelPair { client, server }, link)
+    }
+
+    pub fn conditions(&self) -> LinkConditions {
+        self.conditions
+    }
+
+    /// Changes link conditions. Messages already in flight aren't affected.
+    pub fn set_conditions(&mut self, conditions: LinkConditions) {
+        self.conditions = conditions;
+    }
+
+    /// Simulated time elapsed since link was created.
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Messages lost so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Messages sent which didn't arrive yet.
+    pub fn in_flight(&self) -> usize {
+        self.to_server.0.len() + self.to_client.0.len()
+    }
+
+    /// Breaks the link, closing both ends, like a dropped connection. Messages in flight are lost.
+    pub fn disconnect(&mut self) {
+        self.client_side.close();
+        self.server_side.close();
+        self.to_server.0.clear();
+        self.to_client.0.clear();
+    }
+
+    /// Sends messages sent by both ends since last advance, moves time by `delta` and delivers
+    /// messages which arrived by then.
+    pub fn advance(&mut self, delta: Duration) {
+        for boxed in self.client_side.try_recv_all() {
+            if let Some(msg) = self.schedule(boxed) {
+                self.to_server.0.push(msg);
+            }
+        }
+
+        for boxed in self.server_side.try_recv_all() {
+            if let Some(msg) = self.schedule(boxed) {
+                self.to_client.0.push(msg);
+            }
+        }
+
+        self.now += delta;
+
+        for boxed in self.to_server.take_due(self.now) {
+            let _ = self.server_side.send_boxed(boxed);
+        }
+
+        for boxed in self.to_client.take_due(self.now) {
+            let _ = self.client_side.send_boxed(boxed);
+        }
+    }
+
+    /// **Returns** the given message in flight, or `None` if it was lost.
+    fn schedule<T: MessageType>(&mut self, boxed: BoxedMessage<T>) -> Option<InFlight<T>> {
+        let LinkConditions {
+            latency,
+            jitter,
+            loss,
+            reorder,
+            ..
+        } = self.conditions;
+
+        if self.rng.chance(loss) {
+            self.dropped += 1;
+            return None;
+        }
+
+        let delay = if self.rng.chance(reorder) {
+            Duration::ZERO
+        } else {
+            latency + jitter.mul_f32(self.rng.next_f32())
+        };
+
+        self.seq += 1;
+        Some(InFlight {
+            deliver_at: self.now + delay,
+            seq: self.seq,
+            boxed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use projekto_proto_macros::message_source;
+
+    use super::*;
+    use crate::{self as projekto_proto, MessageSource};
+
+    #[message_source(MessageSource::Client)]
+    enum TestMsg {
+        Num(u32),
+    }
+
+    fn received(channel: &Channel<TestMsg, TestMsg>) -> Vec<u32> {
+        channel
+            .try_recv_all()
+            .into_iter()
+            .map(|boxed| boxed.downcast::<Num>().unwrap().0)
+            .collect()
+    }
+
+    fn send_all(channel: &Channel<TestMsg, TestMsg>, count: u32) {
+        for n in 0..count {
+            channel.send(Num(n)).unwrap();
+        }
+    }
+
+    #[test]
+    fn latency() {
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let (ChannelPair { client, server }, mut link) = SimulatedLink::pair(conditions);
+
+        client.send(Num(1)).unwrap();
+        server.send(Num(2)).unwrap();
+
+        link.advance(Duration::from_millis(50));
+        assert!(server.is_empty(), "Message should still be in flight");
+        assert_eq!(link.in_flight(), 2);
+
+        link.advance(Duration::from_millis(50));
+        assert_eq!(received(&server), vec![1]);
+        assert_eq!(received(&client), vec![2]);
+        assert_eq!(link.now(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn loss() {
+        let conditions = LinkConditions {
+            loss: 0.5,
+            seed: 42,
+            ..Default::default()
+        };
+        let (ChannelPair { client, server }, mut link) = SimulatedLink::pair(conditions);
+
+        send_all(&client, 100);
+        link.advance(Duration::ZERO);
+
+        let received = received(&server);
+        assert_eq!(received.len() as u64 + link.dropped(), 100);
+        assert!(link.dropped() > 20 && link.dropped() < 80);
+        assert!(
+            received.windows(2).all(|w| w[0] < w[1]),
+            "Order should be kept"
+        );
+    }
+
+    #[test]
+    fn reorder() {
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(100),
+            reorder: 0.2,
+            seed: 7,
+            ..Default::default()
+        };
+        let (ChannelPair { client, server }, mut link) = SimulatedLink::pair(conditions);
+
+        send_all(&client, 50);
+        link.advance(Duration::from_millis(100));
+
+        let received = received(&server);
+        assert_eq!(received.len(), 50, "No message should be lost");
+        assert!(
+            received.windows(2).any(|w| w[0] > w[1]),
+            "Some messages should be reordered"
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(20),
+            jitter: Duration::from_millis(50),
+            loss: 0.1,
+            reorder: 0.1,
+            seed: 1234,
+        };
+
+        let run = || {
+            let (ChannelPair { client, server }, mut link) = SimulatedLink::pair(conditions);
+            send_all(&client, 100);
+
+            (0..10)
+                .map(|_| {
+                    link.advance(Duration::from_millis(10));
+                    received(&server)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn disconnect() {
+        let (ChannelPair { client, server }, mut link) =
+            SimulatedLink::<TestMsg, TestMsg>::pair(LinkConditions::default());
+
+        client.send(Num(1)).unwrap();
+        link.advance(Duration::ZERO);
+        link.disconnect();
+
+        assert!(client.is_closed());
+        assert!(server.is_closed());
+        assert_eq!(link.in_flight(), 0);
+    }
+}
@@ -0,0 +1,311 @@
+use serde::{
+    de::{
+        self, value::Error, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer,
+        Visitor,
+    },
+    forward_to_deserialize_any,
+};
+
+/// Max size of any value of `T`, once serialized by bincode, or `None` if it is unbounded, like
+/// types which hold a `Vec` or a `String`.
+///
+/// Bincode encodes each primitive with a fixed size, so the size of a value only depends on which
+/// enum variants it holds. `T` is deserialized from a probe, which adds up the size of each field
+/// instead of reading it, once for each combination of enum variants, so the largest one is found.
+pub fn max_serialized_size<T: DeserializeOwned>() -> Option<usize> {
+    let mut choices = vec![];
+    let mut max = 0;
+
+    loop {
+        let mut probe = Probe {
+            choices: &mut choices,
+            branch: 0,
+            size: 0,
+        };
+        T::deserialize(&mut probe).ok()?;
+        max = max.max(probe.size);
+
+        // Moves to the next combination of variants, starting from the innermost enum.
+        loop {
+            let Some((chosen, count)) = choices.last_mut() else {
+                return Some(max);
+            };
+
+            *chosen += 1;
+            if *chosen < *count {
+                break;
+            }
+            choices.pop();
+        }
+    }
+}
+
+/// Deserializer which never reads anything, it only sums the size bincode would read.
+struct Probe<'a> {
+    /// Chosen variant and variant count of each enum visited, in the order they are visited.
+    choices: &'a mut Vec<(u32, u32)>,
+    /// Index of the next enum visited on `choices`.
+    branch: usize,
+    size: usize,
+}
+
+impl Probe<'_> {
+    /// **Returns** the variant to be visited on the next enum, which has `count` variants.
+    fn choose(&mut self, count: u32) -> u32 {
+        if self.branch == self.choices.len() {
+            self.choices.push((0, count));
+        }
+
+        let (chosen, _) = self.choices[self.branch];
+        self.branch += 1;
+        chosen
+    }
+}
+
+macro_rules! fixed_size {
+    ($($method:ident => $visit:ident($value:expr)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.size += std::mem::size_of_val(&$value);
+                visitor.$visit($value)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for &mut Probe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("unbounded or self described type"))
+    }
+
+    // Strings, sequences and maps are prefixed by their length, so they may have any size.
+    forward_to_deserialize_any! {
+        str string bytes byte_buf seq map identifier ignored_any
+    }
+
+    // Chars are encoded as UTF-8, which is never larger than a `char`.
+    fixed_size! {
+        deserialize_bool => visit_bool(false),
+        deserialize_i8 => visit_i8(0i8),
+        deserialize_i16 => visit_i16(0i16),
+        deserialize_i32 => visit_i32(0i32),
+        deserialize_i64 => visit_i64(0i64),
+        deserialize_i128 => visit_i128(0i128),
+        deserialize_u8 => visit_u8(0u8),
+        deserialize_u16 => visit_u16(0u16),
+        deserialize_u32 => visit_u32(0u32),
+        deserialize_u64 => visit_u64(0u64),
+        deserialize_u128 => visit_u128(0u128),
+        deserialize_f32 => visit_f32(0f32),
+        deserialize_f64 => visit_f64(0f64),
+        deserialize_char => visit_char('\0'),
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Tag byte, which is followed by the value only on `Some`, so it is the largest one.
+        self.size += 1;
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Fields {
+            probe: self,
+            left: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // Variant index is encoded as `u32`.
+        self.size += std::mem::size_of::<u32>();
+        let index = self.choose(variants.len() as u32);
+        visitor.visit_enum(Variant { probe: self, index })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Fields of structs and tuples, which are encoded one after another, without any length.
+struct Fields<'a, 'b> {
+    probe: &'a mut Probe<'b>,
+    left: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Fields<'_, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.left == 0 {
+            return Ok(None);
+        }
+
+        self.left -= 1;
+        seed.deserialize(&mut *self.probe).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+struct Variant<'a, 'b> {
+    probe: &'a mut Probe<'b>,
+    index: u32,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant<'_, '_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let index: de::value::U32Deserializer<Error> = self.index.into_deserializer();
+        let value = seed.deserialize(index)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'_, '_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.probe)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        Deserializer::deserialize_tuple(self.probe, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        Deserializer::deserialize_tuple(self.probe, fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Point(u8),
+        Line { from: (u8, u8), to: (u8, u8) },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Nested {
+        a: Shape,
+        b: Option<Shape>,
+        c: [char; 2],
+    }
+
+    #[test]
+    fn max_serialized_size_fixed() {
+        assert_eq!(max_serialized_size::<()>(), Some(0));
+        assert_eq!(max_serialized_size::<bool>(), Some(1));
+        assert_eq!(max_serialized_size::<(u8, i16, f32, u64)>(), Some(15));
+        assert_eq!(max_serialized_size::<[u32; 3]>(), Some(12));
+        assert_eq!(max_serialized_size::<Option<u16>>(), Some(3));
+    }
+
+    #[test]
+    fn max_serialized_size_enum() {
+        // Variant index and the largest variant.
+        assert_eq!(max_serialized_size::<Shape>(), Some(8));
+        assert_eq!(max_serialized_size::<Nested>(), Some(8 + 1 + 8 + 8));
+        assert_eq!(max_serialized_size::<Option<Option<bool>>>(), Some(3));
+    }
+
+    #[test]
+    fn max_serialized_size_unbounded() {
+        assert_eq!(max_serialized_size::<String>(), None);
+        assert_eq!(max_serialized_size::<Vec<u8>>(), None);
+        assert_eq!(max_serialized_size::<(u8, Option<Vec<u8>>)>(), None);
+    }
+
+    #[test]
+    fn max_serialized_size_holds_any_value() {
+        // arrange
+        let values = [
+            Nested {
+                a: Shape::Empty,
+                b: None,
+                c: ['a', 'b'],
+            },
+            Nested {
+                a: Shape::Point(u8::MAX),
+                b: Some(Shape::Line {
+                    from: (1, 2),
+                    to: (3, 4),
+                }),
+                c: ['\u{10FFFF}', '\u{10FFFF}'],
+            },
+        ];
+
+        // act
+        let max = max_serialized_size::<Nested>().unwrap();
+
+        // assert
+        for value in values {
+            assert!(bincode::serialized_size(&value).unwrap() as usize <= max);
+        }
+    }
+}
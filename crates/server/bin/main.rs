@@ -1,9 +1,8 @@
 use std::time::Duration;
 
 use bevy::{app::ScheduleRunnerPlugin, log::LogPlugin, prelude::*};
-use projekto_server::WorldServerPlugin;
-
-const TICK_EVERY_MILLIS: u64 = 50;
+use projekto_messages::SERVER_TICK_MS;
+use projekto_server::{debug::StressTest, WorldServerPlugin};
 
 fn main() {
     let mut app = App::new();
@@ -16,9 +15,27 @@ fn main() {
     app.add_plugins((
         AssetPlugin::default(),
         MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(
-            TICK_EVERY_MILLIS,
+            SERVER_TICK_MS,
         ))),
         WorldServerPlugin,
-    ))
-    .run();
+    ));
+
+    #[cfg(feature = "scripting")]
+    app.add_plugins(projekto_server::scripting::ScriptingPlugin::default());
+
+    // Debug commands are given as arguments, like `main stress 10 20`.
+    let command = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+    if !command.is_empty() {
+        match StressTest::parse_command(&command) {
+            Some(stress) => {
+                app.insert_resource(stress);
+            }
+            None => {
+                error!("Unknown command: {command}. Usage: stress <players> <speed>");
+                return;
+            }
+        }
+    }
+
+    app.run();
 }
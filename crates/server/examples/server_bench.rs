@@ -33,6 +33,7 @@ fn setup(mut commands: Commands) {
     commands.insert_resource(Landscape {
         center: IVec2::ZERO,
         radius: 4,
+        ..Default::default()
     });
 }
 
@@ -0,0 +1,275 @@
+//! Stable extension surface for game-specific crates, so they can plug in without patching
+//! projekto internals. Besides the events below, [`Kinds`], [`ChunkMap`](crate::bundle::ChunkMap),
+//! [`WorldTime`](crate::WorldTime) and [`ServerTick`] resources are public, and [`WorldSet`]
+//! documents when each event is sent and what can be relied on on each set.
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::{
+    chunk::Chunk,
+    voxel::{KindsDescs, Voxel},
+};
+use projekto_proto::ClientId;
+
+use crate::{
+    bundle::{ChunkKind, ChunkLocal},
+    net::{Clients, NetSet},
+    time::{self, ServerTick},
+    WorldSet,
+};
+
+pub(crate) struct ApiPlugin;
+
+impl Plugin for ApiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChunkLoaded>()
+            .add_event::<ChunkUnloaded>()
+            .add_event::<ChunkEdited>()
+            .add_event::<PlayerJoined>()
+            .add_event::<PlayerLeft>()
+            .add_event::<WorldTick>()
+            .insert_resource(Kinds(KindsDescs::get()))
+            .add_systems(First, send_world_tick.after(time::advance_server_tick))
+            .add_systems(
+                PreUpdate,
+                track_players
+                    .after(NetSet::Connections)
+                    .before(NetSet::Messages),
+            )
+            .add_systems(
+                PostUpdate,
+                (track_chunks, track_chunk_edits).before(WorldSet::SendResponses),
+            );
+    }
+}
+
+/// Voxel kinds descriptions, loaded once at startup. Same as [`KindsDescs::get`], but as a
+/// resource, so systems can declare they depend on it.
+#[derive(Resource, Debug, Clone, Copy, Deref)]
+pub struct Kinds(pub &'static KindsDescs);
+
+/// A chunk was spawned on world, either loaded from cache or just generated. Sent on `PostUpdate`,
+/// before [`WorldSet::SendResponses`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLoaded {
+    pub chunk: Chunk,
+    pub entity: Entity,
+}
+
+/// A chunk was despawned from world. Sent on `PostUpdate`, before [`WorldSet::SendResponses`], of
+/// the tick it was despawned, or the next one, if it was despawned after that.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkUnloaded {
+    pub chunk: Chunk,
+}
+
+/// Voxel kinds of a chunk were changed on this tick, by players or by the world itself. Sent on
+/// `PostUpdate`, before [`WorldSet::SendResponses`].
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ChunkEdited {
+    pub chunk: Chunk,
+    pub voxels: Vec<Voxel>,
+}
+
+/// A player connected. Sent on `PreUpdate`, before any message of this player is handled.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerJoined {
+    pub client: ClientId,
+}
+
+/// A player disconnected. Sent on `PreUpdate`, after it was removed from clients.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerLeft {
+    pub client: ClientId,
+}
+
+/// A new server tick started. Sent on `First`, after [`ServerTick`] is advanced.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldTick {
+    pub tick: u64,
+}
+
+fn send_world_tick(tick: Res<ServerTick>, mut writer: EventWriter<WorldTick>) {
+    writer.send(WorldTick { tick: tick.0 });
+}
+
+/// Players are tracked by comparing connected clients on each tick, so every way a client is
+/// added or removed is reported, including disconnections detected anywhere.
+fn track_players(
+    clients: Res<Clients>,
+    mut known: Local<Vec<ClientId>>,
+    mut joined: EventWriter<PlayerJoined>,
+    mut left: EventWriter<PlayerLeft>,
+) {
+    known.retain(|&client| {
+        let connected = clients.contains_key(&client);
+        if !connected {
+            left.send(PlayerLeft { client });
+        }
+        connected
+    });
+
+    for &client in clients.keys() {
+        if !known.contains(&client) {
+            known.push(client);
+            joined.send(PlayerJoined { client });
+        }
+    }
+}
+
+/// Chunks of each entity are kept, since they are already gone when the entity is despawned.
+fn track_chunks(
+    q_added: Query<(Entity, &ChunkLocal), Added<ChunkLocal>>,
+    mut removed: RemovedComponents<ChunkLocal>,
+    mut spawned: Local<HashMap<Entity, Chunk>>,
+    mut loaded: EventWriter<ChunkLoaded>,
+    mut unloaded: EventWriter<ChunkUnloaded>,
+) {
+    for entity in removed.read() {
+        if let Some(chunk) = spawned.remove(&entity) {
+            unloaded.send(ChunkUnloaded { chunk });
+        }
+    }
+
+    for (entity, &ChunkLocal(chunk)) in &q_added {
+        spawned.insert(entity, chunk);
+        loaded.send(ChunkLoaded { chunk, entity });
+    }
+}
+
+/// Kind changes are reset on `Last`, so they are still available here.
+fn track_chunk_edits(
+    q: Query<(&ChunkLocal, &ChunkKind), Changed<ChunkKind>>,
+    mut writer: EventWriter<ChunkEdited>,
+) {
+    for (&ChunkLocal(chunk), kind) in &q {
+        if kind.0.has_changes() {
+            writer.send(ChunkEdited {
+                chunk,
+                voxels: kind.0.changed().collect(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::ScheduleRunnerPlugin;
+    use projekto_core::voxel::Kind;
+    use projekto_proto::Client;
+
+    use crate::bundle::ChunkBundle;
+
+    use super::*;
+
+    fn setup_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_once()),
+            ApiPlugin,
+        ))
+        .init_resource::<Clients>()
+        .init_resource::<ServerTick>();
+        app
+    }
+
+    fn read<E: Event>(app: &mut App) -> Vec<E> {
+        let mut events = app.world.resource_mut::<Events<E>>();
+        events.drain().collect()
+    }
+
+    #[test]
+    fn chunk_lifecycle() {
+        // arrange
+        let mut app = setup_app();
+        let chunk = Chunk::new(1, 2);
+
+        // act
+        let entity = app
+            .world
+            .spawn(ChunkBundle {
+                local: ChunkLocal(chunk),
+                ..Default::default()
+            })
+            .id();
+        app.update();
+
+        // assert
+        assert_eq!(
+            read::<ChunkLoaded>(&mut app),
+            vec![ChunkLoaded { chunk, entity }]
+        );
+        assert!(read::<ChunkEdited>(&mut app).is_empty());
+
+        // act
+        let voxel = Voxel::new(1, 1, 1);
+        app.world
+            .get_mut::<ChunkKind>(entity)
+            .unwrap()
+            .0
+            .set(voxel, Kind::by_name("Rock").unwrap());
+        app.update();
+
+        // assert
+        assert_eq!(
+            read::<ChunkEdited>(&mut app),
+            vec![ChunkEdited {
+                chunk,
+                voxels: vec![voxel]
+            }]
+        );
+
+        // act
+        app.world.despawn(entity);
+        app.update();
+
+        // assert
+        assert_eq!(
+            read::<ChunkUnloaded>(&mut app),
+            vec![ChunkUnloaded { chunk }]
+        );
+        assert!(read::<ChunkLoaded>(&mut app).is_empty());
+    }
+
+    #[test]
+    fn player_join_and_leave() {
+        // arrange
+        let mut app = setup_app();
+        let (client, _channel) = Client::loopback(1);
+        let id = client.id();
+
+        // act
+        app.world.resource_mut::<Clients>().insert(id, client);
+        app.update();
+        app.update();
+
+        // assert
+        assert_eq!(
+            read::<PlayerJoined>(&mut app),
+            vec![PlayerJoined { client: id }],
+            "Player should join only once"
+        );
+
+        // act
+        app.world.resource_mut::<Clients>().remove(&id);
+        app.update();
+
+        // assert
+        assert_eq!(
+            read::<PlayerLeft>(&mut app),
+            vec![PlayerLeft { client: id }]
+        );
+    }
+
+    #[test]
+    fn world_tick() {
+        // arrange
+        let mut app = setup_app();
+        app.world.insert_resource(ServerTick(41));
+
+        // act
+        app.update();
+
+        // assert
+        assert_eq!(read::<WorldTick>(&mut app), vec![WorldTick { tick: 41 }]);
+    }
+}
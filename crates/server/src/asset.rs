@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use async_channel::Sender;
 use async_lock::OnceCell;
@@ -14,13 +14,21 @@ use bevy::{
     utils::BoxedFuture,
 };
 use projekto_core::{
-    chunk::{Chunk, ChunkStorage},
-    voxel,
+    chunk::{self, Chunk, ChunkStorage, SubChunkStorage},
+    voxel::{self, KindRemap, MissingKind},
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::gen;
+use crate::{
+    debug::GenMetricsReceiver,
+    error::{GenError, ServerError},
+    gen::{self, GenStage, WorldClimate, WorldGenConfig},
+    light, meshing, meta,
+};
+
+/// Folder, relative to assets base path, where chunks are stored.
+const CHUNKS_DIR: &str = "chunks";
 
 pub(crate) struct ChunkAssetPlugin;
 
@@ -38,17 +46,46 @@ pub fn setup_chunk_asset_loader(app: &mut App) {
 
     let (sender, receiver) = async_channel::unbounded();
 
+    let create_root = true;
+    let recovery = Arc::new(ChunkRecovery {
+        sender,
+        writer: AssetSource::get_default_writer(CHUNKS_DIR.to_string())(create_root),
+        policy: app
+            .world
+            .get_resource::<CorruptChunkPolicy>()
+            .copied()
+            .unwrap_or_default(),
+    });
+    app.insert_resource(ChunkRecoveryRef(recovery.clone()));
+
+    let chunks_dir = bevy::asset::io::file::FileAssetReader::get_base_path().join(CHUNKS_DIR);
+    let remap = meta::load_kind_remap(&chunks_dir).map(Arc::new);
+
     app.world
         .get_resource_or_insert_with::<AssetSourceBuilders>(Default::default)
         .insert(
             "chunk",
-            AssetSourceBuilder::default()
-                .with_reader(move || Box::new(ChunkAssetReader::new(sender.clone()))),
+            AssetSourceBuilder::default().with_reader(move || {
+                Box::new(ChunkAssetReader {
+                    recovery: recovery.clone(),
+                    remap: remap.clone(),
+                    reader: AssetSource::get_default_reader(CHUNKS_DIR.to_string())(),
+                })
+            }),
         );
 
     trace!("Chunk asset source was added.");
 
-    gen::start(receiver);
+    let (metrics_sender, metrics_receiver) = async_channel::unbounded();
+    app.insert_resource(GenMetricsReceiver(metrics_receiver));
+
+    let config = app
+        .world
+        .get_resource::<WorldGenConfig>()
+        .copied()
+        .unwrap_or_default();
+    app.insert_resource(WorldClimate::new(&config));
+    gen::start(receiver, metrics_sender, config);
 }
 
 #[derive(Debug, Clone)]
@@ -67,10 +104,12 @@ impl ChunkAssetGenRequest {
 
     async fn get_result(self) -> Result<Vec<u8>, ()> {
         let _ = self.cell.wait().await;
-        Arc::into_inner(self.cell)
-            .expect("To have only a single ref to cell.")
-            .into_inner()
-            .expect("To be initialized")
+
+        // Generator may wake us up before dropping its own ref, so the result is cloned instead.
+        match Arc::try_unwrap(self.cell) {
+            Ok(cell) => cell.into_inner().expect("To be initialized"),
+            Err(cell) => cell.get().cloned().expect("To be initialized"),
+        }
     }
 
     pub(crate) fn finish(self, result: Result<Vec<u8>, ()>) {
@@ -80,25 +119,331 @@ impl ChunkAssetGenRequest {
     }
 }
 
+/// Tells whether light persisted along with kinds is still valid. Light must be recomputed when
+/// kinds were modified without updating it, like when edited offline, or when it was computed by
+/// another [`light::LIGHT_VERSION`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightValidity {
+    /// Light version which computed the light. Zero means light was never computed.
+    pub version: u16,
+    /// Hash of kinds which light was computed from. See [`projekto_core::chunk::kind_hash`].
+    pub kind_hash: u64,
+}
+
+impl LightValidity {
+    /// Marks light as computed from the given kinds, by current light version.
+    pub fn new(kind: &ChunkStorage<voxel::Kind>) -> Self {
+        Self {
+            version: light::LIGHT_VERSION,
+            kind_hash: chunk::kind_hash(kind),
+        }
+    }
+
+    /// **Returns** `true` if light was computed from the given kinds, by current light version.
+    pub fn is_valid(&self, kind: &ChunkStorage<voxel::Kind>) -> bool {
+        self.version == light::LIGHT_VERSION && self.kind_hash == chunk::kind_hash(kind)
+    }
+}
+
+/// Tells whether vertices persisted along with kinds are still valid. Vertices must be generated
+/// again when content of the chunk or of any surrounding chunk changes, or when they were generated
+/// by another [`meshing::MESHING_VERSION`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+pub struct MeshValidity {
+    /// Meshing version which generated vertices. Zero means vertices were never generated.
+    pub version: u16,
+    /// Content hash of chunk and its surrounding chunks. See [`meshing::neighborhood_hash`].
+    pub hash: u64,
+}
+
+impl MeshValidity {
+    /// Marks vertices as generated from the given neighborhood hash, by current meshing version.
+    pub fn new(hash: u64) -> Self {
+        Self {
+            version: meshing::MESHING_VERSION,
+            hash,
+        }
+    }
+
+    /// **Returns** `true` if vertices were generated from the given neighborhood hash, by current
+    /// meshing version.
+    pub fn is_valid(&self, hash: u64) -> bool {
+        self.version == meshing::MESHING_VERSION && self.hash == hash
+    }
+}
+
 #[derive(Asset, Default, Debug, TypePath, Serialize, Deserialize)]
 pub struct ChunkAsset {
     pub chunk: Chunk,
+    /// Hash of kinds and light. See [`projekto_core::chunk::content_hash`].
+    pub hash: u64,
+    /// Stage the chunk was generated up to. Chunks are only dispatched once fully generated, but
+    /// it is persisted so passes added later on can be applied to stored chunks.
+    pub stage: GenStage,
+    pub light_validity: LightValidity,
     pub kind: ChunkStorage<voxel::Kind>,
     pub light: ChunkStorage<voxel::Light>,
     pub occlusion: ChunkStorage<voxel::FacesOcclusion>,
     pub soft_light: ChunkStorage<voxel::FacesSoftLight>,
+    pub mesh_validity: MeshValidity,
     pub vertex: Vec<voxel::Vertex>,
+    pub liquid_vertex: Vec<voxel::Vertex>,
+}
+
+impl ChunkAsset {
+    /// Serializes each section on its own, so they can be decoded independently later on, using
+    /// [`ChunkAssetView`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        let kind = ChunkAssetView::encode_kind(&self.kind)?;
+        let light = bincode::serialize(&self.light)?;
+        let occlusion = bincode::serialize(&self.occlusion)?;
+        let soft_light = bincode::serialize(&self.soft_light)?;
+        let vertex = bincode::serialize(&self.vertex)?;
+        let liquid_vertex = bincode::serialize(&self.liquid_vertex)?;
+
+        bincode::serialize(&ChunkAssetView {
+            chunk: self.chunk,
+            hash: self.hash,
+            stage: self.stage,
+            light_validity: self.light_validity,
+            mesh_validity: self.mesh_validity,
+            kind: &kind,
+            light: &light,
+            occlusion: &occlusion,
+            soft_light: &soft_light,
+            vertex: &vertex,
+            liquid_vertex: &liquid_vertex,
+        })
+    }
+
+    /// Decodes all sections of a chunk asset serialized by [`ChunkAsset::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        let view = ChunkAssetView::new(bytes)?;
+
+        Ok(Self {
+            chunk: view.chunk(),
+            hash: view.hash(),
+            stage: view.stage(),
+            light_validity: view.light_validity(),
+            mesh_validity: view.mesh_validity(),
+            kind: view.kind()?,
+            light: view.light()?,
+            occlusion: view.occlusion()?,
+            soft_light: view.soft_light()?,
+            vertex: view.vertex()?,
+            liquid_vertex: view.liquid_vertex()?,
+        })
+    }
 }
 
-#[derive(Default)]
-struct ChunkAssetLoader;
+/// Serialized [`ChunkAsset`], which only has its sections decoded when requested. This is useful
+/// when only a subset of sections is needed, like kinds and light, since vertices are by far the
+/// most expensive section to decode.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ChunkAssetView<'a> {
+    chunk: Chunk,
+    hash: u64,
+    stage: GenStage,
+    light_validity: LightValidity,
+    mesh_validity: MeshValidity,
+    kind: &'a [u8],
+    light: &'a [u8],
+    occlusion: &'a [u8],
+    soft_light: &'a [u8],
+    vertex: &'a [u8],
+    liquid_vertex: &'a [u8],
+}
+
+impl<'a> ChunkAssetView<'a> {
+    /// Reads the sections table of a chunk asset serialized by [`ChunkAsset::to_bytes`]. No
+    /// section is decoded nor copied.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+
+    pub fn chunk(&self) -> Chunk {
+        self.chunk
+    }
+
+    /// Hash of kinds and light, which is available without decoding any section.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn stage(&self) -> GenStage {
+        self.stage
+    }
+
+    pub fn light_validity(&self) -> LightValidity {
+        self.light_validity
+    }
+
+    pub fn mesh_validity(&self) -> MeshValidity {
+        self.mesh_validity
+    }
+
+    /// Kinds are stored as a [`SubChunkStorage`], since most of a chunk is usually air, which
+    /// takes no space at all.
+    pub fn kind(&self) -> Result<ChunkStorage<voxel::Kind>, bincode::Error> {
+        Self::decode::<SubChunkStorage<_>>(self.kind).map(|sparse| ChunkStorage::from(&sparse))
+    }
+
+    pub fn light(&self) -> Result<ChunkStorage<voxel::Light>, bincode::Error> {
+        Self::decode(self.light)
+    }
+
+    pub fn occlusion(&self) -> Result<ChunkStorage<voxel::FacesOcclusion>, bincode::Error> {
+        Self::decode(self.occlusion)
+    }
+
+    pub fn soft_light(&self) -> Result<ChunkStorage<voxel::FacesSoftLight>, bincode::Error> {
+        Self::decode(self.soft_light)
+    }
+
+    pub fn vertex(&self) -> Result<Vec<voxel::Vertex>, bincode::Error> {
+        Self::decode(self.vertex)
+    }
+
+    pub fn liquid_vertex(&self) -> Result<Vec<voxel::Vertex>, bincode::Error> {
+        Self::decode(self.liquid_vertex)
+    }
+
+    fn decode<T: DeserializeOwned>(section: &[u8]) -> Result<T, bincode::Error> {
+        bincode::deserialize(section)
+    }
+
+    fn encode_kind(kind: &ChunkStorage<voxel::Kind>) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(&SubChunkStorage::from(kind))
+    }
+
+    /// Maps saved kinds to current ids. Content hash and light validity depends on kinds ids, so
+    /// those are updated too. **Returns** the serialized asset with mapped kinds.
+    pub(crate) fn remap_kinds(&self, remap: &KindRemap) -> Result<Vec<u8>, ChunkAssetLoaderError> {
+        let mut kind = self.kind()?;
+        let light = self.light()?;
+        let light_valid = self.light_validity.is_valid(&kind);
+
+        remap.apply(&mut kind)?;
+
+        let kind_bytes = Self::encode_kind(&kind)?;
+        let light_validity = if light_valid {
+            LightValidity::new(&kind)
+        } else {
+            self.light_validity
+        };
+
+        Ok(bincode::serialize(&ChunkAssetView {
+            hash: chunk::content_hash(&kind, &light),
+            light_validity,
+            kind: &kind_bytes,
+            ..*self
+        })?)
+    }
+}
+
+/// Folder, inside chunks folder, where corrupted chunks are moved to, so they can be inspected or
+/// recovered by hand later on.
+const CORRUPT_DIR: &str = "corrupt";
+
+/// What to do when a chunk stored on disk can't be read or decoded. Must be inserted before
+/// [`setup_chunk_asset_loader`], since it is only read there.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptChunkPolicy {
+    /// Chunk fails to load, so it is quarantined. See [`crate::Quarantine`].
+    Fail,
+    /// Corrupted file is moved to [`CORRUPT_DIR`] and the chunk is generated again, so a single
+    /// bad file never bricks the world.
+    #[default]
+    Regenerate,
+}
+
+/// Generates chunks which aren't stored on disk and recovers the corrupted ones, according to
+/// [`CorruptChunkPolicy`]. Shared by [`ChunkAssetReader`] and [`ChunkAssetLoader`], since a
+/// chunk may fail while reading or while decoding.
+struct ChunkRecovery {
+    sender: Sender<ChunkAssetGenRequest>,
+    writer: Option<Box<dyn AssetWriter>>,
+    policy: CorruptChunkPolicy,
+}
+
+impl ChunkRecovery {
+    /// Requests world generator to generate the chunk at the given path.
+    async fn generate(&self, path: &Path) -> Result<Vec<u8>, AssetReaderError> {
+        let request = ChunkAssetGenRequest::new(path);
+        if self.sender.try_send(request.clone()).is_err() {
+            let (chunk, source) = (request.chunk, GenError::Closed);
+            error!("{}", ServerError::Gen { chunk, source });
+            return Err(AssetReaderError::NotFound(path.to_path_buf()));
+        }
+
+        request
+            .get_result()
+            .await
+            .map_err(|_| AssetReaderError::NotFound(path.to_path_buf()))
+    }
+
+    /// Moves the corrupted chunk at the given path to [`CORRUPT_DIR`] and generates it again.
+    ///
+    /// **Returns** `None` if current policy doesn't allow it, so the original error must be kept.
+    async fn recover(
+        &self,
+        path: &Path,
+        error: &(dyn std::fmt::Display + Sync),
+    ) -> Option<Vec<u8>> {
+        let (CorruptChunkPolicy::Regenerate, Some(writer)) = (self.policy, &self.writer) else {
+            return None;
+        };
+
+        let chunk = Chunk::from_path(path);
+        let corrupt = Path::new(CORRUPT_DIR).join(path);
+        error!("Chunk {chunk} is corrupted. Moving it to {corrupt:?} and generating it again. Error: {error}");
+
+        // Chunk is generated anyway, since a corrupted file can't be recovered by loading it again.
+        if let Err(err) = writer.rename(path, &corrupt).await {
+            warn!("Failed to move corrupted chunk {chunk}. Error: {err}");
+        }
+
+        self.generate(path).await.ok()
+    }
+}
+
+#[derive(Resource, Clone, Deref)]
+struct ChunkRecoveryRef(Arc<ChunkRecovery>);
+
+struct ChunkAssetLoader {
+    /// Chunk assets may be loaded without world generator, like on tests, so there is nothing to
+    /// recover from.
+    recovery: Option<Arc<ChunkRecovery>>,
+}
+
+impl FromWorld for ChunkAssetLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            recovery: world
+                .get_resource::<ChunkRecoveryRef>()
+                .map(|r| r.0.clone()),
+        }
+    }
+}
 
 #[derive(Debug, Error)]
-enum ChunkAssetLoaderError {
+pub(crate) enum ChunkAssetLoaderError {
     #[error("Failed to deserialize chunk. Error: {0}")]
     Deserialize(#[from] bincode::Error),
     #[error("Could not load chunk. Error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Could not remap chunk kinds. Error: {0}")]
+    MissingKind(#[from] MissingKind),
+}
+
+impl ChunkAssetLoader {
+    fn decode(bytes: &[u8]) -> Result<ChunkAsset, bincode::Error> {
+        // Span can't be kept across await points, so only deserialization is measured.
+        #[cfg(feature = "trace")]
+        let _span = info_span!("deserialize_chunk", bytes = bytes.len()).entered();
+
+        ChunkAsset::from_bytes(bytes)
+    }
 }
 
 impl AssetLoader for ChunkAssetLoader {
@@ -112,14 +457,26 @@ impl AssetLoader for ChunkAssetLoader {
         &'a self,
         reader: &'a mut Reader,
         _settings: &'a Self::Settings,
-        _load_context: &'a mut LoadContext,
+        load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
         // TODO: Get the exact size from .meta file
         Box::pin(async move {
             let mut bytes = vec![];
-            reader.read_to_end(&mut bytes).await?;
-
-            let asset = bincode::deserialize::<ChunkAsset>(&bytes)?;
+            let result = match reader.read_to_end(&mut bytes).await {
+                Ok(_) => Self::decode(&bytes).map_err(ChunkAssetLoaderError::from),
+                Err(err) => Err(err.into()),
+            };
+
+            let asset = match (result, &self.recovery) {
+                (Ok(asset), _) => asset,
+                (Err(err), Some(recovery)) => {
+                    let Some(bytes) = recovery.recover(load_context.path(), &err).await else {
+                        return Err(err);
+                    };
+                    Self::decode(&bytes)?
+                }
+                (Err(err), None) => return Err(err),
+            };
 
             trace!("[AssetLoader] Loaded asset: {asset:?}");
 
@@ -128,38 +485,52 @@ impl AssetLoader for ChunkAssetLoader {
     }
 }
 
+/// Reads chunks from [`CHUNKS_DIR`], generating the missing ones. Each chunk is stored on its own
+/// file, which is only kept open while the chunk is read, so no file handle outlives a load.
 struct ChunkAssetReader {
-    sender: Sender<ChunkAssetGenRequest>,
+    recovery: Arc<ChunkRecovery>,
+    /// Only set when kinds ids changed since the world was saved. See [`meta::load_kind_remap`].
+    remap: Option<Arc<KindRemap>>,
     reader: Box<dyn AssetReader>,
-    _writer: Box<dyn AssetWriter>,
 }
 
 impl ChunkAssetReader {
-    fn new(sender: Sender<ChunkAssetGenRequest>) -> Self {
-        let create_root = true;
-        Self {
-            sender,
-            reader: AssetSource::get_default_reader("chunks".to_string())(),
-            _writer: AssetSource::get_default_writer("chunks".to_string())(create_root).unwrap(),
-        }
+    async fn generate<'a>(&'a self, path: &'a Path) -> Result<Box<Reader<'a>>, AssetReaderError> {
+        trace!("Chunk asset {path:?} not found local. Requesting to generate it.");
+
+        let bytes = self.recovery.generate(path).await?;
+        Ok(Box::new(VecReader::new(bytes)))
     }
 
-    async fn generate<'a>(
+    /// Maps kinds of a chunk read from disk to current ids. Generated chunks already uses current
+    /// ids, so those are never remapped.
+    async fn remap<'a>(
         &'a self,
-        path: &'a std::path::Path,
+        path: &'a Path,
+        mut reader: Box<Reader<'a>>,
+        remap: &KindRemap,
     ) -> Result<Box<Reader<'a>>, AssetReaderError> {
-        trace!("Chunk asset {path:?} not found local. Requesting to generate it.");
-
-        let request = ChunkAssetGenRequest::new(path);
-        self.sender.try_send(request.clone()).unwrap();
-
-        if let Ok(bytes) = request.get_result().await {
-            // if let Err(err) = self.writer.write_bytes(path, &bytes).await {
-            //     error!("Failed to save chunk {path:?} to disk: {err}");
-            // }
-            Ok(Box::new(VecReader::new(bytes)))
-        } else {
-            Err(AssetReaderError::NotFound(path.to_path_buf()))
+        let mut bytes = vec![];
+        let result = match reader.read_to_end(&mut bytes).await {
+            Ok(_) => ChunkAssetView::new(&bytes)
+                .map_err(ChunkAssetLoaderError::from)
+                .and_then(|view| view.remap_kinds(remap)),
+            Err(err) => Err(err.into()),
+        };
+
+        match result {
+            Ok(bytes) => Ok(Box::new(VecReader::new(bytes))),
+            // Regenerating would silently discard player changes, so chunk fails to load instead.
+            Err(ChunkAssetLoaderError::MissingKind(err)) => Err(AssetReaderError::Io(Arc::new(
+                std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+            ))),
+            Err(err) => match self.recovery.recover(path, &err).await {
+                Some(bytes) => Ok(Box::new(VecReader::new(bytes))),
+                None => Err(AssetReaderError::Io(Arc::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    err.to_string(),
+                )))),
+            },
         }
     }
 }
@@ -171,10 +542,16 @@ impl AssetReader for ChunkAssetReader {
     ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
         Box::pin(async move {
             trace!("Loading chunk at {path:?}");
-            let result = self.reader.read(path).await;
-            match result {
+            match self.reader.read(path).await {
                 Err(AssetReaderError::NotFound(_)) => self.generate(path).await,
-                _ => result,
+                Err(err) => match self.recovery.recover(path, &err).await {
+                    Some(bytes) => Ok(Box::new(VecReader::new(bytes)) as Box<Reader>),
+                    None => Err(err),
+                },
+                Ok(reader) => match &self.remap {
+                    Some(remap) => self.remap(path, reader, remap).await,
+                    None => Ok(reader),
+                },
             }
         })
     }
@@ -220,4 +597,205 @@ mod tests {
         assert_eq!(asset.light, serde_asset.light);
         assert_eq!(asset.vertex, serde_asset.vertex);
     }
+
+    #[test]
+    fn asset_sections() {
+        let mut asset = ChunkAsset {
+            chunk: Chunk::new(1, -2),
+            hash: 42,
+            stage: GenStage::Lit,
+            light_validity: LightValidity::new(&Default::default()),
+            mesh_validity: MeshValidity::new(7),
+            ..Default::default()
+        };
+        asset.kind.set([0, 1, 2].into(), 3.into());
+        asset
+            .light
+            .set_type([0, 1, 2].into(), voxel::LightTy::Natural, 5);
+        asset.vertex.push(voxel::Vertex {
+            position: Vec3::ONE,
+            ..Default::default()
+        });
+
+        let bytes = asset.to_bytes().unwrap();
+
+        let view = ChunkAssetView::new(&bytes).unwrap();
+        assert_eq!(view.chunk(), asset.chunk);
+        assert_eq!(view.hash(), asset.hash);
+        assert_eq!(view.stage(), asset.stage);
+        assert_eq!(view.kind().unwrap(), asset.kind);
+        assert_eq!(view.light().unwrap(), asset.light);
+
+        let decoded = ChunkAsset::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.chunk, asset.chunk);
+        assert_eq!(decoded.hash, asset.hash);
+        assert_eq!(decoded.stage, asset.stage);
+        assert_eq!(decoded.light_validity, asset.light_validity);
+        assert_eq!(decoded.mesh_validity, asset.mesh_validity);
+        assert_eq!(decoded.kind, asset.kind);
+        assert_eq!(decoded.light, asset.light);
+        assert_eq!(decoded.occlusion, asset.occlusion);
+        assert_eq!(decoded.soft_light, asset.soft_light);
+        assert_eq!(decoded.vertex, asset.vertex);
+        assert_eq!(decoded.liquid_vertex, asset.liquid_vertex);
+    }
+
+    #[test]
+    fn asset_kind_section_is_sparse() {
+        // arrange
+        let mut asset = ChunkAsset::default();
+        asset.kind.set([0, 1, 2].into(), 3.into());
+        let dense = bincode::serialize(&asset.kind).unwrap();
+
+        // act
+        let bytes = asset.to_bytes().unwrap();
+
+        // assert
+        let view = ChunkAssetView::new(&bytes).unwrap();
+        assert!(
+            view.kind.len() * 10 < dense.len(),
+            "Only the section with a solid voxel should be stored, got {} bytes",
+            view.kind.len()
+        );
+        assert_eq!(view.kind().unwrap(), asset.kind);
+    }
+
+    #[test]
+    fn light_validity() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let validity = LightValidity::new(&kind);
+
+        assert!(validity.is_valid(&kind));
+        assert!(
+            !LightValidity::default().is_valid(&kind),
+            "Light which was never computed must be invalid"
+        );
+
+        let outdated = LightValidity {
+            version: light::LIGHT_VERSION - 1,
+            ..validity
+        };
+        assert!(!outdated.is_valid(&kind), "Outdated light must be invalid");
+
+        kind.set([0, 1, 2].into(), 3.into());
+        assert!(
+            !validity.is_valid(&kind),
+            "Light must be invalid when kinds changes"
+        );
+    }
+
+    #[test]
+    fn mesh_validity() {
+        let validity = MeshValidity::new(42);
+
+        assert!(validity.is_valid(42));
+        assert!(
+            !validity.is_valid(43),
+            "Mesh must be invalid when content changes"
+        );
+        assert!(
+            !MeshValidity::default().is_valid(0),
+            "Mesh which was never generated must be invalid"
+        );
+
+        let outdated = MeshValidity {
+            version: meshing::MESHING_VERSION - 1,
+            ..validity
+        };
+        assert!(!outdated.is_valid(42), "Outdated mesh must be invalid");
+    }
+
+    #[test]
+    fn asset_remap_kinds() {
+        use projekto_core::voxel::KindsTable;
+
+        let table = |kinds: &[(&str, u16)]| {
+            KindsTable(kinds.iter().map(|&(n, id)| (n.to_string(), id)).collect())
+        };
+        let saved = table(&[("Air", 0), ("Dirt", 1), ("Stone", 2)]);
+        let current = table(&[("Air", 0), ("Stone", 1), ("Dirt", 2)]);
+
+        let mut asset = ChunkAsset::default();
+        asset.kind.set([0, 1, 2].into(), 1.into());
+        asset.light_validity = LightValidity::new(&asset.kind);
+        asset.mesh_validity = MeshValidity::new(7);
+        let bytes = asset.to_bytes().unwrap();
+
+        let remap = KindRemap::new(&saved, &current);
+        let remapped = ChunkAssetView::new(&bytes)
+            .unwrap()
+            .remap_kinds(&remap)
+            .unwrap();
+        let decoded = ChunkAsset::from_bytes(&remapped).unwrap();
+
+        assert_eq!(decoded.kind.get([0, 1, 2].into()), 2.into());
+        assert_eq!(
+            decoded.hash,
+            chunk::content_hash(&decoded.kind, &decoded.light)
+        );
+        assert!(
+            decoded.light_validity.is_valid(&decoded.kind),
+            "Light stays valid, since only ids changed"
+        );
+        assert_eq!(decoded.mesh_validity, asset.mesh_validity);
+
+        let missing = KindRemap::new(&table(&[("Air", 0), ("Lava", 1)]), &current);
+        assert!(matches!(
+            ChunkAssetView::new(&bytes).unwrap().remap_kinds(&missing),
+            Err(ChunkAssetLoaderError::MissingKind(_))
+        ));
+    }
+
+    #[test]
+    fn asset_sections_skip_corrupted() {
+        let asset = ChunkAsset::default();
+        let bytes = asset.to_bytes().unwrap();
+
+        let mut view = ChunkAssetView::new(&bytes).unwrap();
+        // Vertices are a list, so a truncated length prefix can't be decoded.
+        view.vertex = &[1, 2, 3];
+
+        assert_eq!(view.kind().unwrap(), asset.kind);
+        assert!(view.vertex().is_err());
+    }
+
+    #[test]
+    fn corrupted_chunk_is_regenerated() {
+        let chunk = Chunk::new(-7777, 7777);
+        let name = chunk.path().trim_start_matches("chunk://").to_string();
+        let root = bevy::asset::io::file::FileAssetReader::get_base_path().join(CHUNKS_DIR);
+        let (file, corrupt) = (root.join(&name), root.join(CORRUPT_DIR).join(&name));
+
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(&file, b"not a chunk").unwrap();
+        let _ = std::fs::remove_file(&corrupt);
+
+        let mut app = App::new();
+        setup_chunk_asset_loader(&mut app);
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), ChunkAssetPlugin));
+
+        let handle = app
+            .world
+            .resource::<AssetServer>()
+            .load::<ChunkAsset>(chunk.path());
+
+        for _ in 0..1000 {
+            app.update();
+            if app.world.resource::<Assets<ChunkAsset>>().contains(&handle) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let asset = app
+            .world
+            .resource::<Assets<ChunkAsset>>()
+            .get(&handle)
+            .expect("Corrupted chunk should be generated again");
+        assert_eq!(asset.chunk, chunk);
+        assert!(!file.exists(), "Corrupted chunk should be moved");
+        assert!(corrupt.exists(), "Corrupted chunk should be kept aside");
+
+        let _ = std::fs::remove_file(&corrupt);
+    }
 }
@@ -0,0 +1,263 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::WorldSet;
+
+/// Max time world sets may take on a single tick, which keeps server at 20 ticks per second.
+const DEFAULT_BUDGET_MS: u64 = 50;
+
+/// Sets which runs on [`Update`], in order, so time spent on each of them can be measured.
+const MEASURED_SETS: [WorldSet; 6] = [
+    WorldSet::LandscapeUpdate,
+    WorldSet::ChunkManagement,
+    WorldSet::ChunkInitialization,
+    WorldSet::Propagation,
+    WorldSet::CollectAsync,
+    WorldSet::Meshing,
+];
+
+/// Sets which may be deferred to next tick when current tick is over budget.
+const DEFERRABLE_SETS: [WorldSet; 2] = [WorldSet::Propagation, WorldSet::Meshing];
+
+pub(crate) struct TickBudgetPlugin;
+
+impl Plugin for TickBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TickBudget>()
+            .add_systems(First, start_tick)
+            .add_systems(Last, report_overload);
+
+        for (i, &set) in MEASURED_SETS.iter().enumerate() {
+            let next = MEASURED_SETS.get(i + 1).copied();
+            let mark = (move |mut budget: ResMut<TickBudget>| {
+                budget.mark(set, next, Instant::now());
+            })
+            .after(set);
+
+            if let Some(next) = next {
+                app.add_systems(Update, mark.before(next));
+            } else {
+                app.add_systems(Update, mark);
+            }
+        }
+    }
+}
+
+/// Time budget of each server tick. Each measured set reports the time it took and, once the tick
+/// is over budget, remaining deferrable work, like light propagation and meshing, is deferred to
+/// next tick, instead of stretching the current one.
+///
+/// A set is never deferred twice in a row, since events it consumes only lives for two ticks.
+#[derive(Resource, Debug)]
+pub struct TickBudget {
+    /// Max time world sets may take on a single tick.
+    pub budget: Duration,
+    tick_start: Instant,
+    last_mark: Instant,
+    spent: HashMap<WorldSet, Duration>,
+    deferred: HashSet<WorldSet>,
+    last_deferred: HashSet<WorldSet>,
+    /// Time spent by world sets on previous tick.
+    last_tick: Duration,
+    overloaded_ticks: u64,
+}
+
+impl Default for TickBudget {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(DEFAULT_BUDGET_MS))
+    }
+}
+
+impl TickBudget {
+    pub fn new(budget: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            budget,
+            tick_start: now,
+            last_mark: now,
+            spent: default(),
+            deferred: default(),
+            last_deferred: default(),
+            last_tick: Duration::ZERO,
+            overloaded_ticks: 0,
+        }
+    }
+
+    /// **Returns** the time spent on the given set, on current tick.
+    pub fn spent(&self, set: WorldSet) -> Duration {
+        self.spent.get(&set).copied().unwrap_or_default()
+    }
+
+    /// **Returns** `true` if the given set was deferred to next tick.
+    pub fn is_deferred(&self, set: WorldSet) -> bool {
+        self.deferred.contains(&set)
+    }
+
+    /// **Returns** `true` if previous tick took less than half of the budget, so there is spare
+    /// time for optional work, like meshing chunks ahead of time.
+    pub fn has_spare_time(&self) -> bool {
+        self.last_deferred.is_empty() && self.last_tick < self.budget / 2
+    }
+
+    /// **Returns** how many ticks went over budget, since server started.
+    pub fn overloaded_ticks(&self) -> u64 {
+        self.overloaded_ticks
+    }
+
+    fn start_tick(&mut self, now: Instant) {
+        self.last_tick = self.last_mark.saturating_duration_since(self.tick_start);
+        self.tick_start = now;
+        self.last_mark = now;
+        self.spent.clear();
+        self.last_deferred = std::mem::take(&mut self.deferred);
+    }
+
+    /// Records the time spent on the `finished` set and checks if the `next` one should be
+    /// deferred.
+    fn mark(&mut self, finished: WorldSet, next: Option<WorldSet>, now: Instant) {
+        self.spent
+            .insert(finished, now.saturating_duration_since(self.last_mark));
+        self.last_mark = now;
+
+        let Some(next) = next else {
+            return;
+        };
+
+        if DEFERRABLE_SETS.contains(&next)
+            && !self.last_deferred.contains(&next)
+            && now.saturating_duration_since(self.tick_start) > self.budget
+        {
+            self.deferred.insert(next);
+        }
+    }
+
+    fn is_overloaded(&self) -> bool {
+        !self.deferred.is_empty() || self.last_mark.duration_since(self.tick_start) > self.budget
+    }
+}
+
+/// Run condition which skips the given set when it was deferred by [`TickBudget`].
+pub(crate) fn not_deferred(set: WorldSet) -> impl Fn(Res<TickBudget>) -> bool {
+    move |budget: Res<TickBudget>| !budget.is_deferred(set)
+}
+
+fn start_tick(mut budget: ResMut<TickBudget>) {
+    budget.start_tick(Instant::now());
+}
+
+fn report_overload(mut budget: ResMut<TickBudget>) {
+    if !budget.is_overloaded() {
+        return;
+    }
+
+    budget.overloaded_ticks += 1;
+
+    let elapsed = budget.last_mark.duration_since(budget.tick_start);
+    let spent = MEASURED_SETS
+        .iter()
+        .map(|&set| format!("{set:?}: {:?}", budget.spent(set)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let deferred = budget.deferred.iter().collect::<Vec<_>>();
+
+    warn!(
+        "[TickBudget] Tick took {elapsed:?}, over budget of {:?}. Deferred: {deferred:?}. Spent: {spent}",
+        budget.budget
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn mark_records_spent() {
+        let mut budget = TickBudget::new(MS * 50);
+        let start = Instant::now();
+        budget.start_tick(start);
+
+        budget.mark(
+            WorldSet::LandscapeUpdate,
+            Some(WorldSet::ChunkManagement),
+            start + MS * 2,
+        );
+        budget.mark(
+            WorldSet::ChunkManagement,
+            Some(WorldSet::ChunkInitialization),
+            start + MS * 5,
+        );
+
+        assert_eq!(budget.spent(WorldSet::LandscapeUpdate), MS * 2);
+        assert_eq!(budget.spent(WorldSet::ChunkManagement), MS * 3);
+        assert_eq!(budget.spent(WorldSet::Meshing), Duration::ZERO);
+        assert!(!budget.is_overloaded());
+    }
+
+    #[test]
+    fn defer_when_over_budget() {
+        let mut budget = TickBudget::new(MS * 50);
+        let start = Instant::now();
+        budget.start_tick(start);
+
+        // Not deferrable.
+        budget.mark(
+            WorldSet::ChunkManagement,
+            Some(WorldSet::ChunkInitialization),
+            start + MS * 60,
+        );
+        assert!(!budget.is_deferred(WorldSet::ChunkInitialization));
+
+        budget.mark(
+            WorldSet::ChunkInitialization,
+            Some(WorldSet::Propagation),
+            start + MS * 61,
+        );
+        assert!(budget.is_deferred(WorldSet::Propagation));
+        assert!(budget.is_overloaded());
+
+        // Deferred work must run on next tick, even if it is also over budget.
+        let start = start + MS * 100;
+        budget.start_tick(start);
+        assert!(!budget.is_deferred(WorldSet::Propagation));
+
+        budget.mark(
+            WorldSet::ChunkInitialization,
+            Some(WorldSet::Propagation),
+            start + MS * 70,
+        );
+        assert!(!budget.is_deferred(WorldSet::Propagation));
+        assert!(budget.is_overloaded());
+    }
+
+    #[test]
+    fn spare_time() {
+        let mut budget = TickBudget::new(MS * 50);
+        let start = Instant::now();
+        budget.start_tick(start);
+
+        budget.mark(
+            WorldSet::LandscapeUpdate,
+            Some(WorldSet::ChunkManagement),
+            start + MS * 30,
+        );
+
+        let start = start + MS * 50;
+        budget.start_tick(start);
+        assert!(!budget.has_spare_time(), "Last tick took over half budget");
+
+        budget.mark(
+            WorldSet::LandscapeUpdate,
+            Some(WorldSet::ChunkManagement),
+            start + MS * 10,
+        );
+
+        budget.start_tick(start + MS * 50);
+        assert!(budget.has_spare_time());
+    }
+}
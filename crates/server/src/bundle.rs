@@ -7,44 +7,107 @@ use bevy::{
     utils::HashMap,
 };
 use projekto_core::{
-    chunk::{Chunk, ChunkStorage},
+    chunk::{self, Chunk, ChunkStorage, ColumnSummary, Decoration, TrackedStorage},
     voxel,
 };
 
-#[derive(Component, Default, Debug, Clone, Deref, DerefMut)]
-pub struct ChunkKind(pub ChunkStorage<voxel::Kind>);
+use crate::{light::LightSamples, MeshValidity};
 
-#[derive(Component, Default, Debug, Clone, Deref, DerefMut)]
+/// Voxel kinds of a chunk. Changes are tracked until the end of the tick, so systems can check
+/// which voxels were actually modified.
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkKind(pub TrackedStorage<voxel::Kind>);
+
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
 pub struct ChunkLight(pub ChunkStorage<voxel::Light>);
 
-#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut)]
+/// Samples of [`ChunkLight`] and of neighbors light around it, which are reused by light smoothing
+/// until invalidated by light propagation or kind changes.
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut)]
+pub struct ChunkLightSamples(pub LightSamples);
+
+#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
 pub struct ChunkLocal(pub Chunk);
 
-#[derive(Component, Default, Debug, Clone, Deref, DerefMut)]
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
 pub struct ChunkFacesOcclusion(pub ChunkStorage<voxel::FacesOcclusion>);
 
-#[derive(Component, Default, Debug, Clone, Deref, DerefMut)]
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
 pub struct ChunkFacesSoftLight(pub ChunkStorage<voxel::FacesSoftLight>);
 
-#[derive(Component, Default, Debug, Clone, Deref, DerefMut)]
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
 pub struct ChunkVertex(pub Vec<voxel::Vertex>);
 
+/// Vertices of liquid voxels, which are drawn apart from [`ChunkVertex`], since liquids are
+/// translucent and animated.
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkLiquidVertex(pub Vec<voxel::Vertex>);
+
+/// Tells which content [`ChunkVertex`] and [`ChunkLiquidVertex`] were generated from, so chunks
+/// loaded with valid vertices aren't meshed again.
+#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkMeshValidity(pub MeshValidity);
+
+/// Hash of [`ChunkVertex`] content, so clients can validate their cached meshes.
+#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkVertexHash(pub u64);
+
+/// Hash of [`ChunkKind`] and [`ChunkLight`] content, so it is cheap to check if a chunk actually
+/// changed. See [`projekto_core::chunk::content_hash`].
+#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkContentHash(pub u64);
+
+/// Entities of neighbor chunks, indexed by [`chunk::ChunkSide`]. Kept up to date as chunks are
+/// spawned and despawned, so neighbors can be reached without looking up [`ChunkMap`].
+#[derive(Component, Default, Debug, Clone, Copy, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkNeighbors(pub [Option<Entity>; chunk::SIDE_COUNT]);
+
+/// Summary of each chunk column, used by clients to draw maps.
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkColumns(pub Vec<ColumnSummary>);
+
+/// Decorations scattered on chunk surface, which are drawn by clients but never collide.
+#[derive(Component, Default, Debug, Clone, Deref, DerefMut, Reflect)]
+#[reflect(Component)]
+pub struct ChunkDecorations(pub Vec<Decoration>);
+
 #[derive(Bundle, Default)]
 pub struct ChunkBundle {
     pub kind: ChunkKind,
     pub light: ChunkLight,
+    pub light_samples: ChunkLightSamples,
     pub local: ChunkLocal,
     pub occlusion: ChunkFacesOcclusion,
     pub soft_light: ChunkFacesSoftLight,
     pub vertex: ChunkVertex,
+    pub liquid_vertex: ChunkLiquidVertex,
+    pub mesh_validity: ChunkMeshValidity,
+    pub vertex_hash: ChunkVertexHash,
+    pub content_hash: ChunkContentHash,
+    pub neighbors: ChunkNeighbors,
+    pub columns: ChunkColumns,
+    pub decorations: ChunkDecorations,
 }
 
 pub fn any_chunk<T: QueryFilter>(q_changed_chunks: Query<(), (T, With<ChunkLocal>)>) -> bool {
     !q_changed_chunks.is_empty()
 }
 
+/// Entity of each chunk spawned on world, including the ones still being loaded or generated.
 #[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
-pub(crate) struct ChunkMap(pub HashMap<Chunk, Entity>);
+pub struct ChunkMap(pub HashMap<Chunk, Entity>);
 
 #[derive(SystemParam)]
 pub(crate) struct ChunkQuery<'w, 's, Q: QueryData + 'static, F: QueryFilter + 'static = ()> {
@@ -57,24 +120,23 @@ impl<'w, 's, Q: QueryData + 'static, F: QueryFilter + 'static> ChunkQuery<'w, 's
     //     self.map.0.get(&chunk).copied()
     // }
 
+    /// **Returns** the query item of the given chunk, or `None` if the chunk isn't on the map or if
+    /// its entity doesn't match the query, which is logged, since all chunks should match.
     pub fn get_chunk(&self, chunk: Chunk) -> Option<QueryItem<'_, <Q as QueryData>::ReadOnly>> {
-        self.map.0.get(&chunk).map(|&entity| {
-            self.query
-                .get(entity)
-                .expect("All entities inside the map must exists")
-        })
+        let &entity = self.map.0.get(&chunk)?;
+        self.query
+            .get(entity)
+            .map_err(|err| error!("Chunk {chunk} entity {entity:?} doesn't match query. {err}"))
+            .ok()
     }
 
+    /// Mutable version of [`ChunkQuery::get_chunk`].
     pub fn get_chunk_mut(&mut self, chunk: Chunk) -> Option<Q::Item<'_>> {
-        self.map.0.get(&chunk).map(|&entity| {
-            self.query
-                .get_mut(entity)
-                .expect("All entities inside the map must exists")
-        })
-    }
-
-    pub fn chunk_exists(&self, chunk: Chunk) -> bool {
-        self.map.0.contains_key(&chunk)
+        let &entity = self.map.0.get(&chunk)?;
+        self.query
+            .get_mut(entity)
+            .map_err(|err| error!("Chunk {chunk} entity {entity:?} doesn't match query. {err}"))
+            .ok()
     }
 
     // fn get_chunk_component_mut<T: Component>(&mut self, chunk: IVec3) -> Option<Mut<'_, T>> {
@@ -0,0 +1,272 @@
+//! Inspection and best-effort repair of a chunk cache folder.
+//!
+//! There is no header table on disk, since each chunk is stored on its own file, named after the
+//! chunk coordinates. So file names act as the header, which points to the chunk stored on it.
+//! Files whose name doesn't point to any chunk are orphans, which may still be recovered by
+//! decoding their contents.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use projekto_core::chunk::Chunk;
+use thiserror::Error;
+
+use super::{ChunkCache, CACHE_EXT};
+
+#[derive(Debug, Error)]
+pub enum InspectError {
+    #[error("Failed to read entry. Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to decompress entry. Error: {0}")]
+    Decompress(#[from] lz4_flex::block::DecompressError),
+    #[error("Failed to deserialize entry. Error: {0}")]
+    Deserialize(#[from] bincode::Error),
+    #[error("Entry is named after chunk {named}, but contains chunk {found}")]
+    Mismatch { named: Chunk, found: Chunk },
+    #[error("Chunk {0} is already stored on another entry")]
+    Duplicated(Chunk),
+}
+
+/// A file on cache folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// Chunk this entry is named after, or `None` if it is an orphan.
+    pub chunk: Option<Chunk>,
+    /// Size, in bytes, of the file on disk.
+    pub size: u64,
+    /// Size, in bytes, of the decompressed contents, as stated on the file.
+    pub decompressed_size: Option<u32>,
+}
+
+/// Result of [`rebuild`].
+#[derive(Debug, Default)]
+pub struct RebuildReport {
+    /// Entries which were renamed after the chunk they contain.
+    pub recovered: Vec<(PathBuf, Chunk)>,
+    /// Entries which couldn't be recovered and were left untouched.
+    pub failed: Vec<(PathBuf, InspectError)>,
+}
+
+/// **Returns** the chunk the given file is named after, if it is a valid cache entry name.
+pub fn parse_name(path: &Path) -> Option<Chunk> {
+    if path.extension()? != CACHE_EXT {
+        return None;
+    }
+
+    let (x, z) = path.file_stem()?.to_str()?.split_once('_')?;
+    Some(Chunk::new(x.parse().ok()?, z.parse().ok()?))
+}
+
+/// Lists all files on the given cache folder, sorted by path.
+pub fn dump(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = vec![];
+
+    for dir_entry in std::fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let metadata = dir_entry.metadata()?;
+
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let path = dir_entry.path();
+        let decompressed_size = std::fs::File::open(&path).ok().and_then(|mut file| {
+            let mut size = [0; 4];
+            io::Read::read_exact(&mut file, &mut size).ok()?;
+            Some(u32::from_le_bytes(size))
+        });
+
+        entries.push(Entry {
+            chunk: parse_name(&path),
+            path,
+            size: metadata.len(),
+            decompressed_size,
+        });
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(entries)
+}
+
+/// Decodes the given file contents, the same way it is done when loading it.
+pub fn decode(path: &Path) -> Result<ChunkCache, InspectError> {
+    let compressed = std::fs::read(path)?;
+    let decompressed = lz4_flex::decompress_size_prepended(&compressed)?;
+    Ok(bincode::deserialize(&decompressed)?)
+}
+
+/// Checks if the given entry decodes and contains the chunk it is named after.
+///
+/// **Returns** the chunk stored on entry.
+pub fn validate(entry: &Entry) -> Result<Chunk, InspectError> {
+    let found = decode(&entry.path)?.chunk;
+
+    match entry.chunk {
+        Some(named) if named != found => Err(InspectError::Mismatch { named, found }),
+        _ => Ok(found),
+    }
+}
+
+/// Validates all entries on the given cache folder.
+pub fn validate_all(dir: &Path) -> io::Result<Vec<(Entry, Result<Chunk, InspectError>)>> {
+    Ok(dump(dir)?
+        .into_iter()
+        .map(|entry| {
+            let result = validate(&entry);
+            (entry, result)
+        })
+        .collect())
+}
+
+/// **Returns** all entries which aren't named after any chunk, so they are never loaded.
+pub fn orphans(dir: &Path) -> io::Result<Vec<Entry>> {
+    Ok(dump(dir)?
+        .into_iter()
+        .filter(|entry| entry.chunk.is_none())
+        .collect())
+}
+
+/// Renames entries which are orphans or named after the wrong chunk, after the chunk they contain.
+/// Entries which can't be decoded, or whose chunk is already stored on a valid entry, are left
+/// untouched.
+pub fn rebuild(dir: &Path) -> io::Result<RebuildReport> {
+    let mut report = RebuildReport::default();
+
+    for (entry, result) in validate_all(dir)? {
+        let found = match result {
+            // Already valid.
+            Ok(_) if entry.chunk.is_some() => continue,
+            Ok(found) | Err(InspectError::Mismatch { found, .. }) => found,
+            Err(error) => {
+                report.failed.push((entry.path, error));
+                continue;
+            }
+        };
+
+        let target = dir
+            .join(ChunkCache::file_name(found))
+            .with_extension(CACHE_EXT);
+
+        if target.exists() {
+            report
+                .failed
+                .push((entry.path, InspectError::Duplicated(found)));
+            continue;
+        }
+
+        match std::fs::rename(&entry.path, &target) {
+            Ok(()) => report.recovered.push((entry.path, found)),
+            Err(error) => report.failed.push((entry.path, error.into())),
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("projekto_inspect_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, file_name: &str, chunk: Chunk) {
+        let cache = ChunkCache {
+            chunk,
+            ..Default::default()
+        };
+        let bytes = bincode::serialize(&cache).unwrap();
+        std::fs::write(dir.join(file_name), lz4_flex::compress_prepend_size(&bytes)).unwrap();
+    }
+
+    #[test]
+    fn parse_name() {
+        assert_eq!(
+            super::parse_name(Path::new("a/-2_3.bin")),
+            Some(Chunk::new(-2, 3))
+        );
+        assert_eq!(super::parse_name(Path::new("-2_3.tmp")), None);
+        assert_eq!(super::parse_name(Path::new("-2_x.bin")), None);
+        assert_eq!(super::parse_name(Path::new("-2.bin")), None);
+    }
+
+    #[test]
+    fn validate_all() {
+        // arrange
+        let dir = setup_dir("validate_all");
+        write(&dir, "0_0.bin", Chunk::new(0, 0));
+        write(&dir, "1_0.bin", Chunk::new(2, 0));
+        write(&dir, "lost.bin", Chunk::new(3, 0));
+        std::fs::write(dir.join("4_0.bin"), [1, 2, 3]).unwrap();
+
+        // act
+        let results = super::validate_all(&dir).unwrap();
+
+        // assert
+        let results = results
+            .iter()
+            .map(|(entry, result)| (entry.path.file_name().unwrap().to_str().unwrap(), result))
+            .collect::<Vec<_>>();
+
+        assert!(matches!(results[0], ("0_0.bin", Ok(_))));
+        assert!(matches!(
+            results[1],
+            ("1_0.bin", Err(InspectError::Mismatch { .. }))
+        ));
+        assert!(matches!(results[2], ("4_0.bin", Err(_))));
+        assert!(matches!(results[3], ("lost.bin", Ok(chunk)) if *chunk == Chunk::new(3, 0)));
+
+        let orphans = super::orphans(&dir).unwrap();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].path, dir.join("lost.bin"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rebuild() {
+        // arrange
+        let dir = setup_dir("rebuild");
+        write(&dir, "0_0.bin", Chunk::new(0, 0));
+        write(&dir, "1_0.bin", Chunk::new(2, 0));
+        write(&dir, "lost.bin", Chunk::new(3, 0));
+        write(&dir, "copy.bin", Chunk::new(0, 0));
+
+        // act
+        let report = super::rebuild(&dir).unwrap();
+
+        // assert
+        assert_eq!(
+            report.recovered,
+            vec![
+                (dir.join("1_0.bin"), Chunk::new(2, 0)),
+                (dir.join("lost.bin"), Chunk::new(3, 0)),
+            ]
+        );
+        assert_eq!(report.failed.len(), 1);
+        assert!(matches!(
+            report.failed[0],
+            (ref path, InspectError::Duplicated(_)) if *path == dir.join("copy.bin")
+        ));
+
+        let chunks = dump(&dir)
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry| entry.chunk)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![Chunk::new(0, 0), Chunk::new(2, 0), Chunk::new(3, 0)]
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -11,6 +11,8 @@ use projekto_core::{
 };
 use serde::{Deserialize, Serialize};
 
+pub mod inspect;
+
 const CACHE_DIR: &str = "world/chunks/";
 const CACHE_EXT: &str = "bin";
 
@@ -45,6 +47,9 @@ impl ChunkCache {
     }
 
     pub fn load(chunk: Chunk) -> Option<Self> {
+        #[cfg(feature = "trace")]
+        let _span = info_span!("chunk_cache_load", chunk = %chunk).entered();
+
         let path = Self::path(chunk);
         let file = std::fs::OpenOptions::new().read(true).open(path);
 
@@ -77,6 +82,9 @@ impl ChunkCache {
     }
 
     pub fn save(self) -> bool {
+        #[cfg(feature = "trace")]
+        let _span = info_span!("chunk_cache_save", chunk = %self.chunk).entered();
+
         let path = Self::path(self.chunk);
         let file = std::fs::OpenOptions::new()
             .write(true)
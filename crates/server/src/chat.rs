@@ -0,0 +1,368 @@
+//! Chat between players. Chat text starting with [`COMMAND_PREFIX`] is routed to console commands
+//! instead of being broadcast, and command replies are sent back only to whoever typed it.
+
+use bevy::{ecs::system::SystemId, prelude::*, utils::HashMap};
+use projekto_messages::{self as messages, Chat};
+use projekto_proto::{ClientId, RegisterMessageHandler};
+
+use crate::{debug::StressTest, net::Clients, protection::Admins};
+
+/// Chat text starting with this is a console command.
+pub const COMMAND_PREFIX: char = '/';
+/// Max chars of chat text. Longer text is cut.
+pub const MAX_CHAT_LEN: usize = 256;
+
+pub(crate) struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatFilters>()
+            .init_resource::<ConsoleCommands>()
+            .init_resource::<Admins>()
+            .set_message_handler(handle_chat)
+            .add_console_command("help", help_command)
+            .add_console_command("stress", stress_command);
+    }
+}
+
+/// What a [`ChatFilters`] hook decided about a chat text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatVerdict {
+    Allow,
+    /// Text is replaced by the given one, like when masking words.
+    Replace(String),
+    /// Text isn't broadcast and sender is told the given reason.
+    Reject(String),
+}
+
+type ChatFilter = Box<dyn Fn(ClientId, &str) -> ChatVerdict + Send + Sync>;
+
+/// Hooks which check chat text before it is broadcast, in the order they were added. Commands
+/// aren't filtered.
+#[derive(Resource, Default)]
+pub struct ChatFilters(Vec<ChatFilter>);
+
+impl ChatFilters {
+    pub fn add(&mut self, filter: impl Fn(ClientId, &str) -> ChatVerdict + Send + Sync + 'static) {
+        self.0.push(Box::new(filter));
+    }
+
+    /// Runs all filters on the given text, each one receiving the text replaced by the previous
+    /// ones.
+    ///
+    /// **Returns** the text to be broadcast, or the reason it was rejected.
+    pub fn apply(&self, sender: ClientId, mut text: String) -> Result<String, String> {
+        for filter in &self.0 {
+            match filter(sender, &text) {
+                ChatVerdict::Allow => (),
+                ChatVerdict::Replace(replaced) => text = replaced,
+                ChatVerdict::Reject(reason) => return Err(reason),
+            }
+        }
+
+        Ok(text)
+    }
+}
+
+/// A console command typed by a player, without [`COMMAND_PREFIX`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleCommand {
+    pub sender: ClientId,
+    pub line: String,
+}
+
+impl ConsoleCommand {
+    /// Parses a chat text as a command.
+    ///
+    /// **Returns** `None` if the given text doesn't start with [`COMMAND_PREFIX`] or has no
+    /// command.
+    pub fn parse(sender: ClientId, text: &str) -> Option<Self> {
+        let line = text.strip_prefix(COMMAND_PREFIX)?.trim();
+
+        (!line.is_empty()).then(|| Self {
+            sender,
+            line: line.to_string(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.line.split_whitespace().next().unwrap_or_default()
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = &str> {
+        self.line.split_whitespace().skip(1)
+    }
+}
+
+/// Reply sent back to whoever typed a command, or why it failed.
+pub type CommandResult = Result<String, String>;
+
+/// Systems which run each console command, by command name.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct ConsoleCommands(HashMap<String, SystemId<ConsoleCommand, CommandResult>>);
+
+pub trait RegisterConsoleCommand {
+    /// Registers a system which runs when the given command is typed on chat.
+    fn add_console_command<Marker>(
+        &mut self,
+        name: &str,
+        system: impl IntoSystem<ConsoleCommand, CommandResult, Marker> + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterConsoleCommand for App {
+    fn add_console_command<Marker>(
+        &mut self,
+        name: &str,
+        system: impl IntoSystem<ConsoleCommand, CommandResult, Marker> + 'static,
+    ) -> &mut Self {
+        let id = self.world.register_system(system);
+
+        let mut commands = self
+            .world
+            .get_resource_or_insert_with(ConsoleCommands::default);
+        if commands.insert(name.to_string(), id).is_some() {
+            panic!("Already exists a console command named {name}");
+        }
+
+        self
+    }
+}
+
+/// Runs the given command on the system registered for it.
+///
+/// **Returns** the command reply, or why it failed.
+pub fn dispatch_command(world: &mut World, command: ConsoleCommand) -> CommandResult {
+    let name = command.name().to_string();
+    let Some(&id) = world
+        .get_resource::<ConsoleCommands>()
+        .and_then(|commands| commands.get(&name))
+    else {
+        return Err(format!(
+            "Unknown command: {name}. Type /help for a list of commands"
+        ));
+    };
+
+    world
+        .run_system_with_input(id, command)
+        .map_err(|err| format!("Failed to run command {name}. Error: {err}"))?
+}
+
+fn handle_chat(In((id, Chat { text })): In<(ClientId, Chat)>, world: &mut World) {
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+
+    if let Some(command) = ConsoleCommand::parse(id, text) {
+        debug!("[{id}] Running command: {}", command.line);
+
+        let reply = dispatch_command(world, command).unwrap_or_else(|err| err);
+        send_reply(world, id, reply);
+        return;
+    }
+
+    let text = text.chars().take(MAX_CHAT_LEN).collect::<String>();
+    let text = match world.resource::<ChatFilters>().apply(id, text) {
+        Ok(text) => text,
+        Err(reason) => {
+            debug!("[{id}] Chat rejected: {reason}");
+            send_reply(world, id, reason);
+            return;
+        }
+    };
+
+    for client in world.resource::<Clients>().values() {
+        let _ = client.channel().send(messages::ChatBroadcast {
+            sender: Some(id.into()),
+            text: text.clone(),
+        });
+    }
+}
+
+/// Sends a server text only to the given client.
+fn send_reply(world: &World, id: ClientId, text: String) {
+    if let Some(client) = world.resource::<Clients>().get(&id) {
+        let _ = client
+            .channel()
+            .send(messages::ChatBroadcast { sender: None, text });
+    }
+}
+
+fn help_command(In(_): In<ConsoleCommand>, commands: Res<ConsoleCommands>) -> CommandResult {
+    let mut names = commands.keys().map(String::as_str).collect::<Vec<_>>();
+    names.sort_unstable();
+
+    Ok(format!("Commands: {}", names.join(", ")))
+}
+
+fn stress_command(
+    In(command): In<ConsoleCommand>,
+    mut commands: Commands,
+    admins: Res<Admins>,
+) -> CommandResult {
+    if !admins.contains(&command.sender) {
+        return Err("Only admins can run stress tests".to_string());
+    }
+
+    let stress = StressTest::parse_command(&command.line)
+        .ok_or_else(|| "Usage: /stress <players> <speed>".to_string())?;
+    commands.insert_resource(stress);
+
+    Ok(format!(
+        "Stress test started with {} players at {} voxels/s",
+        stress.players, stress.speed
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use projekto_messages::{ChatBroadcast, ClientMessage, ServerMessage};
+    use projekto_proto::{Channel, Client};
+
+    use super::*;
+
+    fn setup_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Clients>().add_plugins(ChatPlugin);
+        app
+    }
+
+    fn connect(app: &mut App, id: u32) -> Channel<ClientMessage, ServerMessage> {
+        let (client, channel) = Client::loopback(id);
+        app.world
+            .resource_mut::<Clients>()
+            .insert(client.id(), client);
+        channel
+    }
+
+    fn received(channel: &Channel<ClientMessage, ServerMessage>) -> Vec<(Option<u32>, String)> {
+        channel
+            .try_recv_all()
+            .into_iter()
+            .filter_map(|boxed| boxed.downcast::<ChatBroadcast>().ok())
+            .map(|ChatBroadcast { sender, text }| (sender, text))
+            .collect()
+    }
+
+    fn chat(app: &mut App, id: u32, text: &str) {
+        let msg = Chat {
+            text: text.to_string(),
+        };
+        app.world
+            .run_system_once_with((ClientId::from(id), msg), handle_chat);
+    }
+
+    #[test]
+    fn chat_is_broadcast() {
+        // arrange
+        let mut app = setup_app();
+        let (first, second) = (connect(&mut app, 1), connect(&mut app, 2));
+
+        // act
+        chat(&mut app, 1, "  Hello  ");
+
+        // assert
+        let expected = (Some(1), "Hello".to_string());
+        assert_eq!(received(&first), vec![expected.clone()]);
+        assert_eq!(received(&second), vec![expected]);
+    }
+
+    #[test]
+    fn chat_filters() {
+        // arrange
+        let mut app = setup_app();
+        let first = connect(&mut app, 1);
+        let second = connect(&mut app, 2);
+
+        let mut filters = app.world.resource_mut::<ChatFilters>();
+        filters.add(|_, text| ChatVerdict::Replace(text.replace("darn", "****")));
+        filters.add(|sender, _| {
+            if sender == ClientId::from(2) {
+                ChatVerdict::Reject("You are muted".to_string())
+            } else {
+                ChatVerdict::Allow
+            }
+        });
+
+        // act
+        chat(&mut app, 1, "darn it");
+        chat(&mut app, 2, "Hi");
+
+        // assert
+        assert_eq!(received(&first), vec![(Some(1), "**** it".to_string())]);
+        assert_eq!(
+            received(&second),
+            vec![
+                (Some(1), "**** it".to_string()),
+                (None, "You are muted".to_string()),
+            ],
+            "Rejected chat should only be replied to sender"
+        );
+    }
+
+    #[test]
+    fn command_is_routed() {
+        // arrange
+        let mut app = setup_app();
+        let first = connect(&mut app, 1);
+        let second = connect(&mut app, 2);
+        app.add_console_command("echo", |In(command): In<ConsoleCommand>| -> CommandResult {
+            Ok(command.args().collect::<Vec<_>>().join(" "))
+        });
+
+        // act
+        chat(&mut app, 1, "/echo hello world");
+        chat(&mut app, 1, "/unknown");
+
+        // assert
+        let replies = received(&first);
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0], (None, "hello world".to_string()));
+        assert!(replies[1].1.starts_with("Unknown command: unknown"));
+        assert!(
+            received(&second).is_empty(),
+            "Command replies should only be sent to sender"
+        );
+    }
+
+    #[test]
+    fn stress_command_admin_only() {
+        // arrange
+        let mut app = setup_app();
+        let _player = connect(&mut app, 1);
+        let _admin = connect(&mut app, 2);
+        app.world.resource_mut::<Admins>().insert(2.into());
+
+        // act
+        chat(&mut app, 1, "/stress 10 5");
+
+        // assert
+        assert!(!app.world.contains_resource::<StressTest>());
+
+        // act
+        chat(&mut app, 2, "/stress 10 5");
+
+        // assert
+        assert_eq!(
+            app.world.get_resource::<StressTest>(),
+            Some(&StressTest {
+                players: 10,
+                speed: 5.0
+            })
+        );
+    }
+
+    #[test]
+    fn parse_command() {
+        let id = ClientId::from(1);
+
+        let command = ConsoleCommand::parse(id, "/tp  1 2 3 ").unwrap();
+        assert_eq!(command.name(), "tp");
+        assert_eq!(command.args().collect::<Vec<_>>(), vec!["1", "2", "3"]);
+
+        assert_eq!(ConsoleCommand::parse(id, "tp 1 2 3"), None);
+        assert_eq!(ConsoleCommand::parse(id, "/  "), None);
+    }
+}
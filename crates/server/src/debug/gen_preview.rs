@@ -0,0 +1,50 @@
+//! Replies world gen preview requests, so generation settings can be tuned from client debug UI.
+
+use bevy::prelude::*;
+use projekto_messages::{WorldGenPreview, WorldGenPreviewRequest};
+use projekto_proto::{ClientId, RegisterMessageHandler};
+
+use crate::{
+    gen::preview::{GenPreview, MAX_PREVIEW_SIZE},
+    net::Clients,
+    WorldGenConfig,
+};
+
+pub(super) struct GenPreviewPlugin;
+
+impl Plugin for GenPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message_handler(handle_world_gen_preview_request);
+    }
+}
+
+fn handle_world_gen_preview_request(
+    In((id, msg)): In<(ClientId, WorldGenPreviewRequest)>,
+    config: Option<Res<WorldGenConfig>>,
+    clients: Res<Clients>,
+) {
+    trace!("[{id}], handle_world_gen_preview_request");
+
+    let Some(client) = clients.get(&id) else {
+        return;
+    };
+
+    let WorldGenPreviewRequest {
+        center,
+        size,
+        scale,
+        layer,
+    } = msg;
+
+    // This is a debug command, so it is fine to block while sampling.
+    let config = config.map(|config| *config).unwrap_or_default();
+    let pixels = GenPreview::new(&config).render(layer, center, size, scale);
+
+    let _ = client.channel().send(WorldGenPreview {
+        center,
+        size: size.min(MAX_PREVIEW_SIZE),
+        scale: scale.max(1),
+        layer,
+        pixels,
+    });
+}
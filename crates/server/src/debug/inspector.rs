@@ -0,0 +1,229 @@
+//! Inspector queries, which let external tools, like dashboards and tests, introspect a running
+//! server over regular client messages, without embedding Bevy. Only admins are answered.
+
+use bevy::prelude::*;
+use projekto_messages::{
+    InspectorQuery, InspectorReply, InspectorRequest, InspectorResponse, MetricsReport,
+};
+use projekto_proto::{ClientId, DeadLetters, RegisterMessageHandler};
+
+use crate::{
+    bundle::{ChunkKind, ChunkMap},
+    chat::{self, ConsoleCommand},
+    net::Clients,
+    protection::Admins,
+    ServerTick,
+};
+
+use super::Metrics;
+
+pub(super) struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Admins>()
+            .set_message_handler(handle_inspector_request);
+    }
+}
+
+/// **Returns** the reply of the given query, asked by `sender`.
+pub fn inspect(world: &mut World, sender: ClientId, query: InspectorQuery) -> InspectorReply {
+    match query {
+        InspectorQuery::LoadedChunks => {
+            let mut chunks = world
+                .get_resource::<ChunkMap>()
+                .map(|map| map.keys().copied().collect::<Vec<_>>())
+                .unwrap_or_default();
+            chunks.sort_by_key(|chunk| (chunk.x(), chunk.z()));
+
+            InspectorReply::LoadedChunks(chunks)
+        }
+        InspectorQuery::ChunkKinds(chunk) => {
+            let kinds = world
+                .get_resource::<ChunkMap>()
+                .and_then(|map| map.get(&chunk))
+                .and_then(|&entity| world.get::<ChunkKind>(entity))
+                .map(|kind| kind.storage().clone());
+
+            InspectorReply::ChunkKinds { chunk, kinds }
+        }
+        InspectorQuery::Metrics => InspectorReply::Metrics(metrics_report(world)),
+        InspectorQuery::Command(line) => {
+            let command = ConsoleCommand {
+                sender,
+                line: line.trim().to_string(),
+            };
+            InspectorReply::Command(chat::dispatch_command(world, command))
+        }
+    }
+}
+
+fn metrics_report(world: &World) -> MetricsReport {
+    let metrics = world.get_resource::<Metrics>().cloned().unwrap_or_default();
+
+    MetricsReport {
+        tick: world
+            .get_resource::<ServerTick>()
+            .map(|tick| tick.0)
+            .unwrap_or_default(),
+        clients: world
+            .get_resource::<Clients>()
+            .map(|clients| clients.len() as u32)
+            .unwrap_or_default(),
+        loaded_chunks: world
+            .get_resource::<ChunkMap>()
+            .map(|map| map.len() as u32)
+            .unwrap_or_default(),
+        generated: metrics.generated.report(),
+        meshed: metrics.meshed.report(),
+        vertices: metrics.vertices,
+        saved: metrics.saved,
+        saved_bytes: metrics.saved_bytes,
+        rate_limited: metrics.rate_limited,
+        rate_limit_disconnects: metrics.rate_limit_disconnects,
+        dead_letters: world
+            .get_resource::<DeadLetters>()
+            .map(DeadLetters::total)
+            .unwrap_or_default(),
+    }
+}
+
+fn handle_inspector_request(
+    In((id, InspectorRequest { query })): In<(ClientId, InspectorRequest)>,
+    world: &mut World,
+) {
+    trace!("[{id}], handle_inspector_request {query:?}");
+
+    let reply = if world.resource::<Admins>().contains(&id) {
+        inspect(world, id, query)
+    } else {
+        debug!("[{id}] Inspector query denied, since client isn't an admin");
+        InspectorReply::Denied
+    };
+
+    if let Some(client) = world.resource::<Clients>().get(&id) {
+        let _ = client.channel().send(InspectorResponse { reply });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use projekto_core::chunk::Chunk;
+    use projekto_messages::LandscapeUpdate;
+
+    use crate::test_harness::{TestClient, TestServer};
+
+    use super::*;
+
+    fn ask(
+        client: &mut TestClient,
+        server: &mut TestServer,
+        query: InspectorQuery,
+    ) -> InspectorReply {
+        client.send(InspectorRequest { query });
+        client
+            .await_message(server, |_: &InspectorResponse| true)
+            .reply
+    }
+
+    #[test]
+    fn only_admins_are_answered() {
+        // arrange
+        let mut server = TestServer::new();
+        let mut player = server.connect();
+        let mut admin = server.connect();
+        server.app.world.resource_mut::<Admins>().insert(admin.id);
+
+        // act
+        let denied = ask(&mut player, &mut server, InspectorQuery::Metrics);
+        let answered = ask(&mut admin, &mut server, InspectorQuery::Metrics);
+
+        // assert
+        assert_eq!(denied, InspectorReply::Denied);
+        let InspectorReply::Metrics(report) = answered else {
+            panic!("Admin should get metrics, got {answered:?}");
+        };
+        assert_eq!(report.clients, 2);
+        assert_eq!(report.tick, server.app.world.resource::<ServerTick>().0);
+    }
+
+    #[test]
+    fn loaded_chunks_and_kinds() {
+        // arrange
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        server.app.world.resource_mut::<Admins>().insert(client.id);
+
+        client.send(LandscapeUpdate {
+            center: IVec2::ZERO,
+            radius: 1,
+        });
+        server.tick_until("landscape to load", |app| {
+            app.world.resource::<ChunkMap>().len() == 9
+        });
+
+        // act
+        let loaded = ask(&mut client, &mut server, InspectorQuery::LoadedChunks);
+        let chunk = Chunk::new(0, 0);
+        let kinds = ask(&mut client, &mut server, InspectorQuery::ChunkKinds(chunk));
+        let missing = ask(
+            &mut client,
+            &mut server,
+            InspectorQuery::ChunkKinds(Chunk::new(100, 100)),
+        );
+
+        // assert
+        let InspectorReply::LoadedChunks(chunks) = loaded else {
+            panic!("Expected loaded chunks, got {loaded:?}");
+        };
+        assert_eq!(chunks.len(), 9);
+        assert!(chunks.contains(&chunk));
+
+        let entity = server.app.world.resource::<ChunkMap>()[&chunk];
+        let expected = server.app.world.get::<ChunkKind>(entity).unwrap().storage();
+        assert_eq!(
+            kinds,
+            InspectorReply::ChunkKinds {
+                chunk,
+                kinds: Some(expected.clone())
+            }
+        );
+        assert_eq!(
+            missing,
+            InspectorReply::ChunkKinds {
+                chunk: Chunk::new(100, 100),
+                kinds: None
+            }
+        );
+    }
+
+    #[test]
+    fn command_is_run() {
+        // arrange
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        server.app.world.resource_mut::<Admins>().insert(client.id);
+
+        // act
+        let help = ask(
+            &mut client,
+            &mut server,
+            InspectorQuery::Command("help".to_string()),
+        );
+        let unknown = ask(
+            &mut client,
+            &mut server,
+            InspectorQuery::Command("unknown".to_string()),
+        );
+
+        // assert
+        assert!(
+            matches!(&help, InspectorReply::Command(Ok(reply)) if reply.contains("help")),
+            "{help:?}"
+        );
+        assert!(
+            matches!(unknown, InspectorReply::Command(Err(_))),
+            "{unknown:?}"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Chunk processing metrics. Each set emits an event for every chunk it processes, which are
+//! aggregated on [`Metrics`], so it can be inspected or sent to admins.
+
+use async_channel::Receiver;
+use bevy::prelude::*;
+use projekto_core::chunk::Chunk;
+use projekto_messages::{ClientMessage, TimingReport};
+use projekto_proto::ClientId;
+
+use crate::WorldSet;
+
+pub(super) struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Metrics>()
+            .add_event::<ChunkGenerated>()
+            .add_event::<ChunkMeshed>()
+            .add_event::<ChunkSaved>()
+            .add_event::<MessageRateLimited>()
+            .add_systems(
+                Update,
+                receive_gen_metrics
+                    .run_if(resource_exists::<GenMetricsReceiver>)
+                    .in_set(WorldSet::ChunkManagement),
+            )
+            .add_systems(Last, aggregate_metrics);
+    }
+}
+
+/// Chunk structure and light was generated by world gen.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkGenerated {
+    pub chunk: Chunk,
+    pub micros: u64,
+}
+
+/// Chunk vertices were generated.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkMeshed {
+    pub chunk: Chunk,
+    pub vertices: usize,
+    pub micros: u64,
+}
+
+/// Chunk was serialized, after being generated, so it can be loaded.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSaved {
+    pub bytes: usize,
+}
+
+/// Client message was dropped, since client exceeded its rate limit. See [`crate::RateLimits`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageRateLimited {
+    pub client: ClientId,
+    pub msg_type: ClientMessage,
+    /// Client was disconnected, due to too many dropped messages.
+    pub disconnected: bool,
+}
+
+/// World gen runs on its own thread and app, so its events are sent through this channel.
+#[derive(Resource, Deref)]
+pub(crate) struct GenMetricsReceiver(pub Receiver<(ChunkGenerated, ChunkSaved)>);
+
+/// Count, total and max time taken by some chunk processing.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub count: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+}
+
+impl Timing {
+    pub fn add(&mut self, micros: u64) {
+        self.count += 1;
+        self.total_micros += micros;
+        self.max_micros = self.max_micros.max(micros);
+    }
+
+    /// **Returns** the average time in micro seconds, or zero if nothing was measured.
+    pub fn avg_micros(&self) -> u64 {
+        self.total_micros
+            .checked_div(self.count)
+            .unwrap_or_default()
+    }
+
+    pub fn report(&self) -> TimingReport {
+        TimingReport {
+            count: self.count,
+            avg_micros: self.avg_micros(),
+            max_micros: self.max_micros,
+        }
+    }
+}
+
+/// Aggregated chunk processing metrics since server started.
+#[derive(Resource, Default, Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub generated: Timing,
+    pub meshed: Timing,
+    /// Vertices generated by all meshed chunks.
+    pub vertices: u64,
+    pub saved: u64,
+    pub saved_bytes: u64,
+    /// Client messages dropped by rate limiting.
+    pub rate_limited: u64,
+    /// Clients disconnected by rate limiting.
+    pub rate_limit_disconnects: u64,
+}
+
+fn receive_gen_metrics(
+    receiver: Res<GenMetricsReceiver>,
+    mut generated_writer: EventWriter<ChunkGenerated>,
+    mut saved_writer: EventWriter<ChunkSaved>,
+) {
+    while let Ok((generated, saved)) = receiver.try_recv() {
+        generated_writer.send(generated);
+        saved_writer.send(saved);
+    }
+}
+
+fn aggregate_metrics(
+    mut metrics: ResMut<Metrics>,
+    mut generated_reader: EventReader<ChunkGenerated>,
+    mut meshed_reader: EventReader<ChunkMeshed>,
+    mut saved_reader: EventReader<ChunkSaved>,
+    mut rate_limited_reader: EventReader<MessageRateLimited>,
+) {
+    for ChunkGenerated { chunk, micros } in generated_reader.read() {
+        trace!("[aggregate_metrics] Chunk {chunk} generated in {micros}us");
+        metrics.generated.add(*micros);
+    }
+
+    for ChunkMeshed {
+        chunk,
+        vertices,
+        micros,
+    } in meshed_reader.read()
+    {
+        trace!("[aggregate_metrics] Chunk {chunk} meshed in {micros}us. {vertices} vertices");
+        metrics.meshed.add(*micros);
+        metrics.vertices += *vertices as u64;
+    }
+
+    for ChunkSaved { bytes } in saved_reader.read() {
+        metrics.saved += 1;
+        metrics.saved_bytes += *bytes as u64;
+    }
+
+    for MessageRateLimited {
+        client,
+        msg_type,
+        disconnected,
+    } in rate_limited_reader.read()
+    {
+        trace!("[aggregate_metrics] Client {client} message {msg_type:?} rate limited");
+        metrics.rate_limited += 1;
+        if *disconnected {
+            metrics.rate_limit_disconnects += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::ScheduleRunnerPlugin;
+
+    use super::*;
+
+    #[test]
+    fn timing_avg() {
+        let mut timing = Timing::default();
+        assert_eq!(timing.avg_micros(), 0);
+
+        timing.add(10);
+        timing.add(30);
+
+        assert_eq!(timing.avg_micros(), 20);
+        assert_eq!(timing.max_micros, 30);
+    }
+
+    #[test]
+    fn aggregate_metrics() {
+        // arrange
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_once()),
+            MetricsPlugin,
+        ));
+
+        let (sender, receiver) = async_channel::unbounded();
+        app.insert_resource(GenMetricsReceiver(receiver));
+
+        let chunk = Chunk::new(1, 2);
+        sender
+            .try_send((
+                ChunkGenerated { chunk, micros: 100 },
+                ChunkSaved { bytes: 50 },
+            ))
+            .unwrap();
+        app.world.send_event(ChunkMeshed {
+            chunk,
+            vertices: 8,
+            micros: 20,
+        });
+        app.world.send_event(ChunkMeshed {
+            chunk,
+            vertices: 4,
+            micros: 40,
+        });
+
+        // act
+        app.update();
+
+        // assert
+        let metrics = app.world.resource::<Metrics>();
+        assert_eq!(metrics.generated.count, 1);
+        assert_eq!(metrics.generated.avg_micros(), 100);
+        assert_eq!(metrics.meshed.count, 2);
+        assert_eq!(metrics.meshed.max_micros, 40);
+        assert_eq!(metrics.vertices, 12);
+        assert_eq!(metrics.saved, 1);
+        assert_eq!(metrics.saved_bytes, 50);
+    }
+}
@@ -0,0 +1,33 @@
+//! Tools to measure and stress the server, which aren't needed by regular gameplay.
+
+use bevy::prelude::*;
+
+mod gen_preview;
+mod inspector;
+mod metrics;
+#[cfg(feature = "seam_validation")]
+mod seams;
+mod snapshot;
+mod stress;
+
+pub use inspector::inspect;
+pub use metrics::*;
+pub use snapshot::take_snapshot;
+pub use stress::StressTest;
+
+pub(crate) struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            gen_preview::GenPreviewPlugin,
+            inspector::InspectorPlugin,
+            metrics::MetricsPlugin,
+            snapshot::SnapshotPlugin,
+            stress::StressPlugin,
+        ));
+
+        #[cfg(feature = "seam_validation")]
+        app.add_plugins(seams::SeamValidationPlugin);
+    }
+}
@@ -0,0 +1,92 @@
+//! Seam validation, which checks borders of meshed chunks against their neighbors. Seams are the
+//! most common meshing bug and they are hard to spot by eye, since a missing quad on a chunk
+//! border looks just like a cave entrance.
+
+use bevy::{prelude::*, utils::HashSet};
+use projekto_core::{
+    chunk::{self, Chunk},
+    voxel::{self, Voxel},
+};
+use projekto_messages::SeamMismatches;
+
+use crate::{
+    bundle::{ChunkFacesOcclusion, ChunkKind, ChunkLocal, ChunkNeighbors, ChunkVertex},
+    meshing,
+    net::Clients,
+    set::FacesOcclusionTask,
+    WorldSet,
+};
+
+/// Max faces listed on each log entry. All of them are still sent to clients.
+const MAX_LOGGED_FACES: usize = 8;
+
+pub(super) struct SeamValidationPlugin;
+
+impl Plugin for SeamValidationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, validate_seams.after(WorldSet::Meshing));
+    }
+}
+
+/// Checks each border of chunks which were meshed on this tick against its neighbor. Chunks with
+/// pending occlusion are skipped, since their occlusion is expected to be outdated. Neighbors
+/// which are affected by this chunk are meshed again too, so they are checked as well.
+fn validate_seams(
+    clients: Res<Clients>,
+    q_meshed: Query<(Entity, &ChunkLocal, &ChunkNeighbors), Changed<ChunkVertex>>,
+    q_chunks: Query<(&ChunkKind, &ChunkFacesOcclusion), Without<FacesOcclusionTask>>,
+    mut reported: Local<HashSet<Chunk>>,
+) {
+    for (entity, &ChunkLocal(chunk), neighbors) in &q_meshed {
+        let Ok((kind, occlusion)) = q_chunks.get(entity) else {
+            continue;
+        };
+
+        let mut faces = vec![];
+        for side in chunk::SIDES {
+            let Some(Ok((neighbor_kind, _))) =
+                neighbors[side.index()].map(|entity| q_chunks.get(entity))
+            else {
+                continue;
+            };
+
+            faces.extend(meshing::seam_mismatches(
+                kind,
+                occlusion,
+                side,
+                neighbor_kind,
+            ));
+        }
+
+        report(&clients, &mut reported, chunk, faces);
+    }
+}
+
+/// Logs and sends the given mismatched faces to clients. Chunks which were reported before are
+/// reported again once fixed, so clients stop showing them.
+fn report(
+    clients: &Clients,
+    reported: &mut HashSet<Chunk>,
+    chunk: Chunk,
+    faces: Vec<(Voxel, voxel::Side)>,
+) {
+    if faces.is_empty() && !reported.remove(&chunk) {
+        return;
+    }
+
+    if !faces.is_empty() {
+        reported.insert(chunk);
+        warn!(
+            "[validate_seams] {} faces on chunk {chunk} border don't match its neighbors: {:?}",
+            faces.len(),
+            &faces[..faces.len().min(MAX_LOGGED_FACES)]
+        );
+    }
+
+    for client in clients.values() {
+        let _ = client.channel().send(SeamMismatches {
+            chunk,
+            faces: faces.clone(),
+        });
+    }
+}
@@ -0,0 +1,137 @@
+//! Snapshot of loaded world state, so external tools and tests can check global invariants
+//! without knowing server internals.
+
+use bevy::{
+    ecs::system::{SystemParam, SystemState},
+    prelude::*,
+};
+use projekto_core::chunk;
+use projekto_messages::{PendingWork, WorldSnapshot, WorldSnapshotReply, WorldSnapshotRequest};
+use projekto_proto::{ClientId, RegisterMessageHandler};
+
+use crate::{
+    bundle::{ChunkKind, ChunkLocal},
+    net::Clients,
+    set::{FacesOcclusionTask, LightRecomputeTask, MeshingTask, PlayerTransforms},
+    ChunkAsset,
+};
+
+pub(super) struct SnapshotPlugin;
+
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message_handler(handle_world_snapshot_request);
+    }
+}
+
+/// Everything needed to take a [`WorldSnapshot`].
+#[derive(SystemParam)]
+pub(crate) struct WorldSnapshotParam<'w, 's> {
+    q_chunks: Query<'w, 's, (&'static ChunkLocal, &'static ChunkKind)>,
+    q_loading: Query<'w, 's, (), (With<Handle<ChunkAsset>>, Without<ChunkLocal>)>,
+    q_faces_occlusion: Query<'w, 's, (), With<FacesOcclusionTask>>,
+    q_light_recompute: Query<'w, 's, (), With<LightRecomputeTask>>,
+    q_meshing: Query<'w, 's, (), With<MeshingTask>>,
+    players: Res<'w, PlayerTransforms>,
+}
+
+impl<'w, 's> WorldSnapshotParam<'w, 's> {
+    pub fn take(&self) -> WorldSnapshot {
+        let mut chunks = self
+            .q_chunks
+            .iter()
+            .map(|(ChunkLocal(chunk), ChunkKind(kind))| (*chunk, chunk::kind_hash(kind)))
+            .collect::<Vec<_>>();
+        chunks.sort_by_key(|(chunk, _)| (chunk.x(), chunk.z()));
+
+        let mut players = self
+            .players
+            .iter()
+            .map(|(&id, transform)| (id.into(), transform.translation))
+            .collect::<Vec<_>>();
+        players.sort_by_key(|&(id, _)| id);
+
+        WorldSnapshot {
+            chunks,
+            players,
+            pending: PendingWork {
+                loading: self.q_loading.iter().count() as u32,
+                faces_occlusion: self.q_faces_occlusion.iter().count() as u32,
+                light_recompute: self.q_light_recompute.iter().count() as u32,
+                meshing: self.q_meshing.iter().count() as u32,
+            },
+        }
+    }
+}
+
+/// Takes a [`WorldSnapshot`] outside of systems, like on tests or tools which own the server app.
+pub fn take_snapshot(world: &mut World) -> WorldSnapshot {
+    SystemState::<WorldSnapshotParam>::new(world)
+        .get(world)
+        .take()
+}
+
+fn handle_world_snapshot_request(
+    In((id, _)): In<(ClientId, WorldSnapshotRequest)>,
+    snapshot: WorldSnapshotParam,
+    clients: Res<Clients>,
+) {
+    trace!("[{id}], handle_world_snapshot_request");
+
+    if let Some(client) = clients.get(&id) {
+        let _ = client.channel().send(WorldSnapshotReply {
+            snapshot: snapshot.take(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use projekto_core::chunk::Chunk;
+    use projekto_messages::{LandscapeUpdate, PlayerTransform};
+
+    use crate::test_harness::TestServer;
+
+    use super::*;
+
+    #[test]
+    fn snapshot_after_landscape_settles() {
+        // arrange
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        let position = Vec3::new(8.0, 200.0, 8.0);
+
+        client.send(PlayerTransform {
+            position,
+            rotation: Quat::IDENTITY,
+        });
+        client.send(LandscapeUpdate {
+            center: IVec2::ZERO,
+            radius: 1,
+        });
+        client.await_chunk_vertex(&mut server, Chunk::new(0, 0), |vertex| !vertex.is_empty());
+        server.tick_until("landscape to load", |app| {
+            take_snapshot(&mut app.world).chunks.len() == 9
+        });
+        server.settle();
+
+        // act
+        client.send(WorldSnapshotRequest);
+        let snapshot = client
+            .await_message(&mut server, |_: &WorldSnapshotReply| true)
+            .snapshot;
+
+        // assert
+        assert_eq!(snapshot, take_snapshot(&mut server.app.world));
+        assert!(snapshot.pending.is_idle(), "{:?}", snapshot.pending);
+        assert_eq!(snapshot.players, vec![(client.id.into(), position)]);
+        assert!(snapshot
+            .chunks
+            .iter()
+            .all(|(chunk, _)| chunk.x().abs() <= 1 && chunk.z().abs() <= 1));
+        assert!(
+            snapshot.chunks.windows(2).all(|w| w[0].0 != w[1].0),
+            "Each chunk must be loaded only once"
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Stress test, which connects virtual players roaming around in random directions, so landscape
+//! keeps loading and unloading chunks. It makes leaks on chunk management and growth of chunk
+//! loading queue easy to reproduce.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use projekto_core::chunk::{self, Chunk};
+use projekto_messages::{ClientMessage, LandscapeUpdate, PlayerTransform, ServerMessage};
+use projekto_proto::{Channel, Client};
+
+use crate::{bundle::ChunkMap, net::Clients, ChunkAsset};
+
+/// Virtual players ids starts here, far away from ids given to network clients.
+const FIRST_PLAYER_ID: u32 = u32::MAX / 2;
+/// Landscape radius requested by each virtual player.
+const LANDSCAPE_RADIUS: u8 = 4;
+/// Height virtual players roam at, above most of the terrain.
+const ROAM_HEIGHT: f32 = chunk::Y_AXIS_SIZE as f32;
+/// Range, in seconds, of how long a virtual player keeps walking in the same direction.
+const TURN_EVERY_SECS: (f32, f32) = (2.0, 10.0);
+const STATS_EVERY_SECS: u64 = 5;
+
+pub(super) struct StressPlugin;
+
+impl Plugin for StressPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                connect_virtual_players.run_if(resource_exists_and_changed::<StressTest>),
+                disconnect_virtual_players.run_if(resource_removed::<StressTest>()),
+                roam_virtual_players.run_if(resource_exists::<StressTest>),
+                log_stress_stats
+                    .run_if(resource_exists::<StressTest>)
+                    .run_if(on_timer(Duration::from_secs(STATS_EVERY_SECS))),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Parameters of the stress test. Insert this resource to start it and remove it to stop it.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct StressTest {
+    pub players: u32,
+    /// Speed, in voxels per second, of each virtual player.
+    pub speed: f32,
+}
+
+impl StressTest {
+    /// Parses a `stress <players> <speed>` command.
+    ///
+    /// **Returns** `None` if the given command isn't a valid stress command.
+    pub fn parse_command(command: &str) -> Option<Self> {
+        let mut args = command.split_whitespace();
+
+        if args.next()? != "stress" {
+            return None;
+        }
+
+        let players = args.next()?.parse().ok()?;
+        let speed = args
+            .next()?
+            .parse()
+            .ok()
+            .filter(|speed: &f32| speed.is_finite())?;
+
+        if args.next().is_some() {
+            return None;
+        }
+
+        Some(Self { players, speed })
+    }
+}
+
+#[derive(Component)]
+struct VirtualPlayer {
+    /// Remote end of the loopback client, used to send requests as if it was a real player.
+    channel: Channel<ClientMessage, ServerMessage>,
+    position: Vec3,
+    direction: Vec3,
+    /// Seconds left until a new direction is picked.
+    turn_in: f32,
+    /// Chunk of the current landscape center.
+    center: Option<Chunk>,
+    /// State of the random number generator of this player.
+    seed: u32,
+}
+
+impl VirtualPlayer {
+    /// Xorshift, since virtual players movement just need to look random.
+    ///
+    /// **Returns** a random number in range [0.0, 1.0).
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn turn(&mut self) {
+        let angle = self.random() * std::f32::consts::TAU;
+        self.direction = Vec3::new(angle.cos(), 0.0, angle.sin());
+
+        let (min, max) = TURN_EVERY_SECS;
+        self.turn_in = min + (max - min) * self.random();
+    }
+}
+
+fn connect_virtual_players(
+    mut commands: Commands,
+    stress: Res<StressTest>,
+    mut clients: ResMut<Clients>,
+    q: Query<(Entity, &VirtualPlayer)>,
+) {
+    // Test may be restarted with different parameters, so start over.
+    for (entity, player) in &q {
+        player.channel.close();
+        commands.entity(entity).despawn();
+    }
+
+    let StressTest { players, speed } = *stress;
+    info!("[Stress] Connecting {players} virtual players, roaming at {speed} voxels per second");
+
+    for i in 0..players {
+        let (client, channel) = Client::loopback(FIRST_PLAYER_ID + i);
+        clients.insert(client.id(), client);
+
+        let mut player = VirtualPlayer {
+            channel,
+            position: Vec3::new(0.0, ROAM_HEIGHT, 0.0),
+            direction: Vec3::ZERO,
+            turn_in: 0.0,
+            center: None,
+            // Xorshift state can't be zero.
+            seed: (i + 1).wrapping_mul(0x9E37_79B9),
+        };
+        player.turn();
+
+        commands.spawn((player, Name::new(format!("Virtual Player {i}"))));
+    }
+}
+
+fn disconnect_virtual_players(mut commands: Commands, q: Query<(Entity, &VirtualPlayer)>) {
+    info!("[Stress] Disconnecting {} virtual players", q.iter().len());
+
+    // Closed clients are removed by networking.
+    for (entity, player) in &q {
+        player.channel.close();
+        commands.entity(entity).despawn();
+    }
+}
+
+fn roam_virtual_players(
+    time: Res<Time>,
+    stress: Res<StressTest>,
+    mut q: Query<&mut VirtualPlayer>,
+) {
+    let delta = time.delta_seconds();
+
+    for mut player in &mut q {
+        // Responses aren't needed, but must be drained, or they would pile up forever.
+        player.channel.try_recv_all();
+
+        player.turn_in -= delta;
+        if player.turn_in <= 0.0 {
+            player.turn();
+        }
+
+        let offset = player.direction * stress.speed * delta;
+        player.position += offset;
+
+        let _ = player.channel.send(PlayerTransform {
+            position: player.position,
+            rotation: Quat::IDENTITY,
+        });
+
+        let chunk = Chunk::from(player.position);
+        if player.center != Some(chunk) {
+            player.center = Some(chunk);
+            let _ = player.channel.send(LandscapeUpdate {
+                center: chunk.xz(),
+                radius: LANDSCAPE_RADIUS,
+            });
+        }
+    }
+}
+
+fn log_stress_stats(
+    q_players: Query<&VirtualPlayer>,
+    q_loading: Query<(), With<Handle<ChunkAsset>>>,
+    chunk_map: Res<ChunkMap>,
+    clients: Res<Clients>,
+) {
+    let players = q_players.iter().len();
+    let chunks = chunk_map.len();
+    let loading = q_loading.iter().len();
+    let clients = clients.len();
+
+    info!("[Stress] {players} players, {clients} clients, {chunks} chunks loaded, {loading} chunks loading");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_command() {
+        assert_eq!(
+            StressTest::parse_command("stress 10 20.5"),
+            Some(StressTest {
+                players: 10,
+                speed: 20.5
+            })
+        );
+        assert_eq!(StressTest::parse_command("stress 10"), None);
+        assert_eq!(StressTest::parse_command("stress -1 10"), None);
+        assert_eq!(StressTest::parse_command("stress 1 NaN"), None);
+        assert_eq!(StressTest::parse_command("stress 1 2 3"), None);
+        assert_eq!(StressTest::parse_command("other 1 2"), None);
+    }
+}
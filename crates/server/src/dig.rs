@@ -0,0 +1,281 @@
+//! Voxel breaking progress. Players hold the break button for the voxel kind break time, so server
+//! keeps track of what each player is breaking and broadcasts its progress, which clients draw as
+//! cracks on the voxel.
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::{
+    chunk::{self, Chunk},
+    voxel::Voxel,
+};
+use projekto_messages::{VoxelBreakProgress, VoxelDig};
+use projekto_proto::{ClientId, RegisterMessageHandler};
+
+use crate::{
+    bundle::{ChunkKind, ChunkQuery},
+    net::Clients,
+    WorldSet,
+};
+
+pub(crate) struct DigPlugin;
+
+impl Plugin for DigPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelBreaks>()
+            .add_message_handler(handle_voxel_dig)
+            .add_systems(
+                PostUpdate,
+                broadcast_break_progress.in_set(WorldSet::SendResponses),
+            );
+    }
+}
+
+/// A voxel being broken by a player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoxelBreak {
+    pub chunk: Chunk,
+    pub voxel: Voxel,
+    /// Elapsed time, in seconds, when player started breaking it.
+    pub started: f32,
+    /// Time, in seconds, it takes to break it. See [`projekto_core::voxel::Kind::break_secs`].
+    pub duration: f32,
+}
+
+impl VoxelBreak {
+    /// **Returns** how much of the voxel was broken at the given elapsed time, from 0 to 1.
+    pub fn progress(&self, now: f32) -> f32 {
+        ((now - self.started) / self.duration).clamp(0.0, 1.0)
+    }
+}
+
+/// Voxel each player is breaking, if any.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct VoxelBreaks(HashMap<ClientId, VoxelBreak>);
+
+fn handle_voxel_dig(
+    In((id, msg)): In<(ClientId, VoxelDig)>,
+    q: ChunkQuery<&ChunkKind>,
+    time: Res<Time>,
+    clients: Res<Clients>,
+    mut breaks: ResMut<VoxelBreaks>,
+) {
+    trace!("[{id}], handle_voxel_dig");
+
+    let VoxelDig {
+        chunk,
+        voxel,
+        digging,
+    } = msg;
+
+    // Players break a single voxel at a time, so any previous one was given up.
+    if let Some(previous) = breaks.remove(&id) {
+        broadcast(&clients, id, previous.chunk, previous.voxel, 0.0);
+    }
+
+    if !digging || !chunk::is_inside(voxel) {
+        return;
+    }
+
+    let Some(kind) = q.get_chunk(chunk).map(|kind| kind.get(voxel)) else {
+        return;
+    };
+
+    // Voxels which break at once have no progress to show.
+    let duration = kind.break_secs();
+    if !kind.is_solid() || duration <= 0.0 {
+        return;
+    }
+
+    breaks.insert(
+        id,
+        VoxelBreak {
+            chunk,
+            voxel,
+            started: time.elapsed_seconds(),
+            duration,
+        },
+    );
+}
+
+fn broadcast_break_progress(
+    time: Res<Time>,
+    clients: Res<Clients>,
+    q: ChunkQuery<&ChunkKind>,
+    mut breaks: ResMut<VoxelBreaks>,
+) {
+    if breaks.is_empty() {
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    breaks.retain(|&id, voxel_break| {
+        let VoxelBreak { chunk, voxel, .. } = *voxel_break;
+
+        // Voxel is gone, like when player finished breaking it, or player left.
+        let broken = q
+            .get_chunk(chunk)
+            .map_or(true, |kind| !kind.get(voxel).is_solid());
+        if broken || !clients.contains_key(&id) {
+            broadcast(&clients, id, chunk, voxel, 0.0);
+            return false;
+        }
+
+        broadcast(&clients, id, chunk, voxel, voxel_break.progress(now));
+        true
+    });
+}
+
+fn broadcast(clients: &Clients, id: ClientId, chunk: Chunk, voxel: Voxel, progress: f32) {
+    for client in clients.values() {
+        let _ = client.channel().send(VoxelBreakProgress {
+            chunk,
+            voxel,
+            player: id.into(),
+            progress,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{app::ScheduleRunnerPlugin, ecs::system::RunSystemOnce, time::TimeUpdateStrategy};
+    use projekto_core::voxel;
+    use projekto_messages::{ClientMessage, ServerMessage};
+    use projekto_proto::{Channel, Client};
+
+    use crate::bundle::{ChunkBundle, ChunkLocal, ChunkMap};
+
+    use super::*;
+
+    const SOLID: Voxel = Voxel::new(1, 1, 1);
+
+    fn setup_app() -> (App, Channel<ClientMessage, ServerMessage>) {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_once()),
+            DigPlugin,
+        ))
+        .init_resource::<Clients>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            100,
+        )));
+
+        let mut bundle = ChunkBundle {
+            local: ChunkLocal(Chunk::new(0, 0)),
+            ..Default::default()
+        };
+        bundle
+            .kind
+            .0
+            .set(SOLID, voxel::Kind::by_name("Rock").unwrap());
+
+        let entity = app.world.spawn(bundle).id();
+        app.world
+            .insert_resource(ChunkMap([(Chunk::new(0, 0), entity)].into_iter().collect()));
+
+        let (client, channel) = Client::loopback(1);
+        app.world
+            .resource_mut::<Clients>()
+            .insert(client.id(), client);
+
+        (app, channel)
+    }
+
+    fn dig(app: &mut App, voxel: Voxel, digging: bool) {
+        let msg = VoxelDig {
+            chunk: Chunk::new(0, 0),
+            voxel,
+            digging,
+        };
+        app.world
+            .run_system_once_with((ClientId::from(1), msg), handle_voxel_dig);
+    }
+
+    fn progress(channel: &Channel<ClientMessage, ServerMessage>) -> Vec<f32> {
+        channel
+            .try_recv_all()
+            .into_iter()
+            .filter_map(|boxed| boxed.downcast::<VoxelBreakProgress>().ok())
+            .map(|msg| msg.progress)
+            .collect()
+    }
+
+    #[test]
+    fn progress_is_broadcast() {
+        // arrange
+        let (mut app, channel) = setup_app();
+        app.update();
+
+        // act
+        dig(&mut app, SOLID, true);
+        app.update();
+        app.update();
+
+        // assert
+        let received = progress(&channel);
+        assert_eq!(received.len(), 2);
+        assert!(received[0] > 0.0 && received[0] < received[1] && received[1] < 1.0);
+
+        // act
+        for _ in 0..20 {
+            app.update();
+        }
+
+        // assert
+        assert_eq!(
+            progress(&channel).last(),
+            Some(&1.0),
+            "Progress should stop at 1 until voxel is broken"
+        );
+    }
+
+    #[test]
+    fn progress_is_cleared() {
+        // arrange
+        let (mut app, channel) = setup_app();
+        dig(&mut app, SOLID, true);
+        app.update();
+        progress(&channel);
+
+        // act
+        dig(&mut app, SOLID, false);
+        app.update();
+
+        // assert
+        assert_eq!(progress(&channel), vec![0.0]);
+        assert!(app.world.resource::<VoxelBreaks>().is_empty());
+
+        // act
+        dig(&mut app, SOLID, true);
+        let entity = app.world.resource::<ChunkMap>()[&Chunk::new(0, 0)];
+        app.world
+            .get_mut::<ChunkKind>(entity)
+            .unwrap()
+            .0
+            .set(SOLID, voxel::Kind::NONE);
+        app.update();
+
+        // assert
+        assert_eq!(
+            progress(&channel),
+            vec![0.0],
+            "Broken voxel should have its progress cleared"
+        );
+        assert!(app.world.resource::<VoxelBreaks>().is_empty());
+    }
+
+    #[test]
+    fn empty_voxel_is_ignored() {
+        // arrange
+        let (mut app, channel) = setup_app();
+
+        // act
+        dig(&mut app, Voxel::new(2, 2, 2), true);
+        app.update();
+
+        // assert
+        assert!(progress(&channel).is_empty());
+        assert!(app.world.resource::<VoxelBreaks>().is_empty());
+    }
+}
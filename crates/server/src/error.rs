@@ -0,0 +1,125 @@
+//! Errors of the chunk pipeline. Those are scoped to a single chunk, so a bad chunk is logged and
+//! quarantined, instead of taking down the whole server.
+
+use std::any::Any;
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::chunk::Chunk;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error("Failed to load chunk {chunk}. Error: {source}")]
+    Load { chunk: Chunk, source: LoadError },
+    #[error("Failed to generate chunk {chunk}. Error: {source}")]
+    Gen { chunk: Chunk, source: GenError },
+    #[error("Failed to mesh chunk {chunk}. Error: {source}")]
+    Mesh { chunk: Chunk, source: MeshError },
+}
+
+impl ServerError {
+    /// **Returns** the chunk which failed.
+    pub fn chunk(&self) -> Chunk {
+        match self {
+            ServerError::Load { chunk, .. }
+            | ServerError::Gen { chunk, .. }
+            | ServerError::Mesh { chunk, .. } => *chunk,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("Asset failed to load")]
+    Failed,
+    #[error("Asset was never requested to load")]
+    NotLoaded,
+    #[error("Asset was loaded, but it was removed before spawning")]
+    Missing,
+    #[error("Asset contains chunk {0}")]
+    Mismatch(Chunk),
+}
+
+#[derive(Debug, Error)]
+pub enum GenError {
+    #[error("World generator isn't running anymore")]
+    Closed,
+    #[error("World generator panicked. Error: {0}")]
+    Panicked(String),
+    #[error("Failed to serialize chunk. Error: {0}")]
+    Serialize(#[from] bincode::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum MeshError {
+    #[error("Mesher panicked. Error: {0}")]
+    Panicked(String),
+}
+
+/// **Returns** the message of a caught panic, which is usually a `&str` or a `String`.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .unwrap_or_else(|| "Unknown panic".to_string()),
+    }
+}
+
+/// Chunks which failed to load, generate or mesh, along with the error. Those are skipped until
+/// server restarts, so a bad chunk fails only once, instead of on every landscape update.
+#[derive(Resource, Default, Debug)]
+pub struct Quarantine(HashMap<Chunk, ServerError>);
+
+impl Quarantine {
+    /// Logs the given error and quarantines the chunk which failed.
+    pub fn insert(&mut self, error: ServerError) {
+        error!("{error}. Chunk quarantined.");
+        self.0.insert(error.chunk(), error);
+    }
+
+    pub fn contains(&self, chunk: Chunk) -> bool {
+        self.0.contains_key(&chunk)
+    }
+
+    pub fn get(&self, chunk: Chunk) -> Option<&ServerError> {
+        self.0.get(&chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(super::panic_message(payload), "static");
+
+        let value = 42;
+        let payload = std::panic::catch_unwind(|| panic!("formatted {value}")).unwrap_err();
+        assert_eq!(super::panic_message(payload), "formatted 42");
+    }
+
+    #[test]
+    fn quarantine() {
+        let mut quarantine = Quarantine::default();
+        let chunk = Chunk::new(1, -1);
+
+        quarantine.insert(ServerError::Load {
+            chunk,
+            source: LoadError::Failed,
+        });
+
+        assert!(quarantine.contains(chunk));
+        assert!(!quarantine.contains(Chunk::new(0, 0)));
+        assert!(matches!(
+            quarantine.get(chunk),
+            Some(ServerError::Load {
+                source: LoadError::Failed,
+                ..
+            })
+        ));
+    }
+}
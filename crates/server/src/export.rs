@@ -0,0 +1,212 @@
+//! Exports chunks vertices as a Wavefront OBJ, with a MTL material pointing to the texture atlas,
+//! so terrain can be inspected on modeling tools, like Blender.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use projekto_core::{
+    chunk::{self, Chunk},
+    voxel,
+};
+
+/// Folder, relative to working directory, where exported worlds are written.
+pub(crate) const EXPORT_PATH: &str = "exports";
+const MATERIAL_NAME: &str = "atlas";
+
+/// Writes the given chunks vertices on [`EXPORT_PATH`], centered around the given chunk.
+///
+/// **Returns** the path of the written OBJ file.
+pub(crate) fn export_world<'a>(
+    center: Chunk,
+    chunks: impl IntoIterator<Item = (Chunk, &'a [voxel::Vertex])>,
+) -> std::io::Result<PathBuf> {
+    let descs = voxel::KindsDescs::get();
+    let tile_texture_size = (descs.count_tiles() as f32).recip();
+    let atlas_path = Path::new(env!("ASSETS_PATH")).join(&descs.atlas_path);
+
+    let dir = PathBuf::from(EXPORT_PATH);
+    let name = format!("world_{}_{}", center.x(), center.z());
+    let obj_path = dir.join(format!("{name}.obj"));
+
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+        dir.join(format!("{name}.mtl")),
+        write_mtl(&atlas_path.to_string_lossy()),
+    )?;
+    std::fs::write(
+        &obj_path,
+        write_obj(center, chunks, tile_texture_size, &format!("{name}.mtl")),
+    )?;
+
+    Ok(obj_path)
+}
+
+fn write_mtl(atlas_path: &str) -> String {
+    format!("newmtl {MATERIAL_NAME}\nKd 1.0 1.0 1.0\nillum 0\nmap_Kd {atlas_path}\n")
+}
+
+/// Writes the given chunks vertices as OBJ. Light is written as vertex color.
+///
+/// Merged faces repeat the same tile many times, which can't be done using a texture atlas outside
+/// of voxel shader, so those faces are split into one quad per voxel.
+fn write_obj<'a>(
+    center: Chunk,
+    chunks: impl IntoIterator<Item = (Chunk, &'a [voxel::Vertex])>,
+    tile_texture_size: f32,
+    mtl_file: &str,
+) -> String {
+    let mut obj = format!("mtllib {mtl_file}\nusemtl {MATERIAL_NAME}\n");
+    let mut vertex_count = 0;
+
+    // Keep exported model around origin, since chunks far away from it are hard to navigate.
+    let origin = chunk::to_world(center);
+
+    // Sub quad uvs, relative to tile start, in the same order as face vertices.
+    let tile_uvs = [
+        Vec2::new(0.0, tile_texture_size),
+        Vec2::new(tile_texture_size, tile_texture_size),
+        Vec2::new(tile_texture_size, 0.0),
+        Vec2::ZERO,
+    ];
+
+    for (chunk, vertices) in chunks {
+        let offset = chunk::to_world(chunk) - origin;
+
+        for face in vertices.chunks_exact(4) {
+            let [v0, v1, _, v3] = [&face[0], &face[1], &face[2], &face[3]];
+            let x_axis = v1.position - v0.position;
+            let y_axis = v3.position - v0.position;
+            let columns = x_axis.length().round().max(1.0) as u32;
+            let rows = y_axis.length().round().max(1.0) as u32;
+
+            // Face lights are bilinear interpolated along the sub quads.
+            let light = |x: f32, y: f32| {
+                let bottom = face[0].light.lerp(face[1].light, x);
+                let top = face[3].light.lerp(face[2].light, x);
+                bottom.lerp(top, y)
+            };
+
+            for column in 0..columns {
+                for row in 0..rows {
+                    let corners = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(x, y)| {
+                        (
+                            (column + x) as f32 / columns as f32,
+                            (row + y) as f32 / rows as f32,
+                        )
+                    });
+
+                    for ((x, y), uv) in corners.into_iter().zip(tile_uvs) {
+                        let position = offset + v0.position + x_axis * x + y_axis * y;
+                        let color = light(x, y);
+                        let uv = v0.tile_coord_start + uv;
+                        let normal = v0.normal;
+
+                        let _ = writeln!(
+                            obj,
+                            "v {} {} {} {} {} {}",
+                            position.x, position.y, position.z, color.x, color.y, color.z
+                        );
+                        // OBJ texture coordinates starts at bottom left.
+                        let _ = writeln!(obj, "vt {} {}", uv.x, 1.0 - uv.y);
+                        let _ = writeln!(obj, "vn {} {} {}", normal.x, normal.y, normal.z);
+                    }
+
+                    // OBJ indices are 1-based.
+                    let [a, b, c, d] = [1, 2, 3, 4].map(|i| vertex_count + i);
+                    let _ = writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}");
+                    let _ = writeln!(obj, "f {c}/{c}/{c} {d}/{d}/{d} {a}/{a}/{a}");
+                    vertex_count += 4;
+                }
+            }
+        }
+    }
+
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(width: f32, light: f32) -> Vec<voxel::Vertex> {
+        [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(width, 1.0, 0.0),
+            Vec3::new(width, 1.0, 1.0),
+            Vec3::new(0.0, 1.0, 1.0),
+        ]
+        .into_iter()
+        .map(|position| voxel::Vertex {
+            position,
+            normal: Vec3::Y,
+            light: Vec3::splat(light),
+            tile_coord_start: Vec2::new(0.5, 0.25),
+            ..Default::default()
+        })
+        .collect()
+    }
+
+    fn lines<'a>(obj: &'a str, prefix: &str) -> Vec<&'a str> {
+        obj.lines()
+            .filter(|line| line.starts_with(prefix))
+            .collect()
+    }
+
+    #[test]
+    fn write_obj_split_merged_faces() {
+        // arrange
+        let vertices = face(3.0, 1.0);
+
+        // act
+        let obj = write_obj(
+            Chunk::new(0, 0),
+            [(Chunk::new(0, 0), &vertices[..])],
+            0.25,
+            "a.mtl",
+        );
+
+        // assert
+        assert_eq!(
+            lines(&obj, "v ").len(),
+            12,
+            "Each voxel should have its own quad"
+        );
+        assert_eq!(lines(&obj, "f ").len(), 6);
+        assert_eq!(lines(&obj, "f ").last(), Some(&"f 11/11/11 12/12/12 9/9/9"));
+        assert_eq!(lines(&obj, "v ")[4], "v 1 1 0 1 1 1");
+
+        for line in lines(&obj, "vt ") {
+            let uv = line
+                .split_whitespace()
+                .skip(1)
+                .map(|n| n.parse::<f32>().unwrap())
+                .collect::<Vec<_>>();
+            assert!((0.5..=0.75).contains(&uv[0]), "{line} is outside of tile");
+            assert!((0.5..=0.75).contains(&uv[1]), "{line} is outside of tile");
+        }
+    }
+
+    #[test]
+    fn write_obj_relative_to_center() {
+        // arrange
+        let vertices = face(1.0, 0.0);
+
+        // act
+        let obj = write_obj(
+            Chunk::new(1, 1),
+            [(Chunk::new(2, 1), &vertices[..])],
+            0.25,
+            "a.mtl",
+        );
+
+        // assert
+        assert_eq!(
+            lines(&obj, "v ")[0],
+            format!("v {} 1 0 0 0 0", chunk::X_AXIS_SIZE)
+        );
+        assert!(obj.starts_with("mtllib a.mtl\nusemtl atlas\n"));
+    }
+}
@@ -0,0 +1,285 @@
+//! Biomes and their surface rules, which tells which kinds terrain is made of. Those are described
+//! on a ron file, so new biomes doesn't need code changes.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use projekto_core::voxel::Kind;
+use serde::Deserialize;
+
+use super::Climate;
+
+/// Describes which kinds, by name, terrain surface is made of, from top to bottom.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SurfaceDesc {
+    /// Topmost voxel of terrain, when above sea level.
+    pub top: String,
+    /// Voxels right below the top one.
+    pub filler: String,
+    /// How many voxels of filler there are, below the top one.
+    pub filler_depth: u8,
+    /// Topmost voxel of terrain, when below sea level.
+    pub underwater: String,
+    /// Voxels below filler, down to the bottom of the world.
+    pub base: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BiomeDesc {
+    pub name: String,
+    pub surface: SurfaceDesc,
+}
+
+/// Holds a list of [`BiomeDesc`]. This struct is created from a ron file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BiomesDescs {
+    pub biomes: Vec<BiomeDesc>,
+    /// Biomes names by climate. Rows go from driest to wettest and columns from coldest to
+    /// hottest, each covering an equal slice of climate range. Without a table, the first biome
+    /// covers the whole world.
+    #[serde(default)]
+    pub table: Vec<Vec<String>>,
+}
+
+impl BiomesDescs {
+    /// **Returns** the path of biomes descriptions file inside assets folder.
+    pub fn default_path() -> String {
+        format!("{}/world/biomes.ron", env!("ASSETS_PATH"))
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        ron::de::from_reader(file).map_err(|e| e.to_string())
+    }
+}
+
+/// [`SurfaceDesc`] with kinds resolved from their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Surface {
+    pub top: Kind,
+    pub filler: Kind,
+    pub filler_depth: u8,
+    pub underwater: Kind,
+    pub base: Kind,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self {
+            top: Kind::id(2),
+            filler: Kind::id(1),
+            filler_depth: 3,
+            underwater: Kind::id(1),
+            base: Kind::id(3),
+        }
+    }
+}
+
+impl Surface {
+    fn new(desc: &SurfaceDesc) -> Result<Self, String> {
+        let kind = |name: &str| Kind::by_name(name).ok_or_else(|| format!("Unknown kind {name}"));
+
+        Ok(Self {
+            top: kind(&desc.top)?,
+            filler: kind(&desc.filler)?,
+            filler_depth: desc.filler_depth,
+            underwater: kind(&desc.underwater)?,
+            base: kind(&desc.base)?,
+        })
+    }
+
+    /// **Returns** the kind of a terrain voxel `depth` voxels below the topmost one, on a column
+    /// whose top is either `underwater` or not.
+    pub fn kind_at(&self, depth: i32, underwater: bool) -> Kind {
+        match depth {
+            0 if underwater => self.underwater,
+            0 => self.top,
+            depth if depth <= self.filler_depth as i32 => self.filler,
+            _ => self.base,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Biome {
+    pub name: String,
+    pub surface: Surface,
+}
+
+/// Biomes available to world generation, in the order they are described, along with the lookup
+/// table of biome indices by climate. See [`BiomesDescs::table`].
+#[derive(Resource, Debug, Clone)]
+pub(crate) struct Biomes {
+    biomes: Vec<Biome>,
+    table: Vec<Vec<usize>>,
+}
+
+impl Default for Biomes {
+    fn default() -> Self {
+        Self {
+            biomes: vec![Biome {
+                name: "Default".to_string(),
+                surface: Surface::default(),
+            }],
+            table: vec![],
+        }
+    }
+}
+
+impl Biomes {
+    /// Resolves kinds of all biomes described, failing if any kind doesn't exist or there is no
+    /// biome at all.
+    pub fn new(descs: &BiomesDescs) -> Result<Self, String> {
+        let biomes = descs
+            .biomes
+            .iter()
+            .map(|desc| {
+                Surface::new(&desc.surface)
+                    .map(|surface| Biome {
+                        name: desc.name.clone(),
+                        surface,
+                    })
+                    .map_err(|e| format!("Invalid biome {}. Error: {e}", desc.name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if biomes.is_empty() {
+            return Err("There must be at least one biome".to_string());
+        }
+
+        let table = descs
+            .table
+            .iter()
+            .map(|row| {
+                if row.is_empty() {
+                    return Err("Biomes table rows can't be empty".to_string());
+                }
+
+                row.iter()
+                    .map(|name| {
+                        biomes
+                            .iter()
+                            .position(|biome| &biome.name == name)
+                            .ok_or_else(|| format!("Unknown biome {name} on biomes table"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { biomes, table })
+    }
+
+    /// Reads biomes descriptions on the given path. Default biome is used when it can't be read,
+    /// so a bad file doesn't stop world generation.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match BiomesDescs::read(path).and_then(|descs| Self::new(&descs)) {
+            Ok(biomes) => biomes,
+            Err(err) => {
+                error!("Failed to load biomes on path {path:?}. Using default one. Error: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    /// **Returns** the biome of the given climate, looked up on biomes table.
+    pub fn select(&self, climate: Climate) -> &Biome {
+        let slice = |t: f32, len: usize| ((t.clamp(0.0, 1.0) * len as f32) as usize).min(len - 1);
+
+        let index = if self.table.is_empty() {
+            0
+        } else {
+            let row = &self.table[slice(climate.humidity, self.table.len())];
+            row[slice(climate.temperature, row.len())]
+        };
+
+        &self.biomes[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_biomes_descriptions() {
+        let descs = BiomesDescs::read(BiomesDescs::default_path()).unwrap();
+        let biomes = Biomes::new(&descs).unwrap();
+
+        assert_eq!(biomes.biomes.len(), descs.biomes.len());
+    }
+
+    #[test]
+    fn invalid_biomes() {
+        let mut descs = BiomesDescs {
+            biomes: Default::default(),
+            table: Default::default(),
+        };
+        assert!(Biomes::new(&descs).is_err(), "There must be a biome");
+
+        descs.biomes.push(BiomeDesc {
+            name: "Invalid".to_string(),
+            surface: SurfaceDesc {
+                top: "Invalid".to_string(),
+                filler: "Dirt".to_string(),
+                filler_depth: 1,
+                underwater: "Dirt".to_string(),
+                base: "Rock".to_string(),
+            },
+        });
+        assert!(Biomes::new(&descs).is_err(), "Kinds must exist");
+
+        descs.biomes[0].surface.top = "Grass".to_string();
+        descs.table = vec![vec!["Unknown".to_string()]];
+        assert!(Biomes::new(&descs).is_err(), "Table biomes must exist");
+
+        descs.table = vec![vec![]];
+        assert!(Biomes::new(&descs).is_err(), "Table rows can't be empty");
+
+        let biomes = Biomes::load("invalid/biomes.ron");
+        assert_eq!(biomes.biomes, Biomes::default().biomes);
+    }
+
+    #[test]
+    fn select_biome() {
+        let biome = |name: &str| Biome {
+            name: name.to_string(),
+            surface: Surface::default(),
+        };
+        let biomes = Biomes {
+            biomes: vec![biome("Cold"), biome("Hot"), biome("Wet")],
+            table: vec![vec![0, 1], vec![2]],
+        };
+        let select = |temperature, humidity| {
+            &biomes
+                .select(Climate {
+                    temperature,
+                    humidity,
+                })
+                .name
+        };
+
+        assert_eq!(select(0.0, 0.0), "Cold");
+        assert_eq!(select(0.49, 0.2), "Cold");
+        assert_eq!(select(0.5, 0.2), "Hot");
+        assert_eq!(select(1.0, 0.0), "Hot");
+        assert_eq!(select(0.0, 0.5), "Wet");
+        assert_eq!(select(1.0, 1.0), "Wet");
+        assert_eq!(
+            Biomes::default().select(Climate::default()).name,
+            "Default",
+            "First biome should be used without a table"
+        );
+    }
+
+    #[test]
+    fn surface_kind_at() {
+        let surface = Surface::default();
+
+        assert_eq!(surface.kind_at(0, false), surface.top);
+        assert_eq!(surface.kind_at(0, true), surface.underwater);
+        assert_eq!(surface.kind_at(1, false), surface.filler);
+        assert_eq!(surface.kind_at(3, true), surface.filler);
+        assert_eq!(surface.kind_at(4, false), surface.base);
+    }
+}
@@ -0,0 +1,131 @@
+//! Climate fields, temperature and humidity, which biomes are selected from. Those are also
+//! available to gameplay through [`WorldClimate::climate_at`], so systems like crop growth or snow
+//! can react to climate.
+
+use bevy::prelude::*;
+
+use super::{
+    noise::{FractalParams, NoiseSource},
+    WorldGenConfig,
+};
+
+/// Height, in voxels, above which temperature drops. See [`ALTITUDE_COOLING`].
+const COOLING_HEIGHT: f32 = 160.0;
+/// How much temperature drops for each voxel above [`COOLING_HEIGHT`].
+const ALTITUDE_COOLING: f32 = 0.005;
+
+/// Climate of some place in the world. Both fields are in range [0.0 ~ 1.0].
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    /// From coldest to hottest.
+    pub temperature: f32,
+    /// From driest to wettest.
+    pub humidity: f32,
+}
+
+/// Low frequency noise channels of each climate field. Each one has its own seed, so they are
+/// independent from each other and from terrain shape.
+pub(crate) struct ClimateNoise {
+    temperature: Box<dyn NoiseSource>,
+    humidity: Box<dyn NoiseSource>,
+}
+
+impl ClimateNoise {
+    pub fn new(config: &WorldGenConfig) -> Self {
+        let params = FractalParams {
+            frequency: 0.002,
+            octaves: 2,
+            gain: 0.5,
+            lacunarity: 2.0,
+        };
+
+        Self {
+            temperature: config.noise.source(config.seed.wrapping_add(3), params),
+            humidity: config.noise.source(config.seed.wrapping_add(4), params),
+        }
+    }
+
+    /// **Returns** the climate of the given world column, at ground level.
+    pub fn get(&self, x: f32, z: f32) -> Climate {
+        let normalize = |n: f32| (n.clamp(-1.0, 1.0) + 1.0) / 2.0;
+
+        Climate {
+            temperature: normalize(self.temperature.get(x, z)),
+            humidity: normalize(self.humidity.get(x, z)),
+        }
+    }
+
+    /// **Returns** the climate at the given world position. Temperature drops on high places, so
+    /// mountain tops are colder than valleys around them.
+    pub fn at(&self, world_pos: Vec3) -> Climate {
+        let climate = self.get(world_pos.x, world_pos.z);
+        let cooling = (world_pos.y - COOLING_HEIGHT).max(0.0) * ALTITUDE_COOLING;
+
+        Climate {
+            temperature: (climate.temperature - cooling).max(0.0),
+            ..climate
+        }
+    }
+}
+
+/// Climate fields of the world, matching the ones used by world generator. Inserted by
+/// [`crate::setup_chunk_asset_loader`].
+#[derive(Resource)]
+pub struct WorldClimate(ClimateNoise);
+
+impl WorldClimate {
+    pub fn new(config: &WorldGenConfig) -> Self {
+        Self(ClimateNoise::new(config))
+    }
+
+    /// **Returns** the climate at the given world position. See [`ClimateNoise::at`].
+    pub fn climate_at(&self, world_pos: Vec3) -> Climate {
+        self.0.at(world_pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn climate_range() {
+        let climate = WorldClimate::new(&WorldGenConfig::default());
+
+        for i in 0..64 {
+            let pos = Vec3::new(i as f32 * 97.0, 0.0, i as f32 * -53.0);
+            let Climate {
+                temperature,
+                humidity,
+            } = climate.climate_at(pos);
+
+            assert!((0.0..=1.0).contains(&temperature));
+            assert!((0.0..=1.0).contains(&humidity));
+        }
+    }
+
+    #[test]
+    fn climate_independent_fields() {
+        let climate = ClimateNoise::new(&WorldGenConfig::default());
+
+        let differs = (0..64)
+            .map(|i| climate.get(i as f32 * 131.0, i as f32 * 71.0))
+            .any(|c| (c.temperature - c.humidity).abs() > 0.01);
+        assert!(differs, "Temperature and humidity should be independent");
+    }
+
+    #[test]
+    fn climate_altitude_cooling() {
+        let climate = WorldClimate::new(&WorldGenConfig::default());
+        let ground = climate.climate_at(Vec3::new(10.0, COOLING_HEIGHT, 10.0));
+        let top = climate.climate_at(Vec3::new(10.0, COOLING_HEIGHT + 50.0, 10.0));
+
+        assert!(top.temperature <= ground.temperature);
+        assert_eq!(top.humidity, ground.humidity);
+        assert_eq!(
+            climate.climate_at(Vec3::new(10.0, 0.0, 10.0)),
+            ground,
+            "Low places shouldn't be cooled"
+        );
+    }
+}
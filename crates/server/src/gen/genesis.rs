@@ -1,3 +1,4 @@
+use bevy::math::Vec3;
 use projekto_core::{
     chunk::{self, Chunk, ChunkStorage},
     voxel::{self, Kind},
@@ -5,17 +6,70 @@ use projekto_core::{
 
 use crate::light;
 
-use super::noise::Noise;
+use super::{biome::Biomes, noise::Noise};
 
-/// Generates a new chunk filling it with [`ChunkKind`] randomly generated by seeded noise
-pub fn generate_chunk(noise: &Noise, chunk: Chunk, chunk_kind: &mut ChunkStorage<voxel::Kind>) {
+/// Generates a new chunk filling it with [`ChunkKind`] randomly generated by seeded noise. Terrain
+/// is layered following surface rules of each column biome. Empty voxels below `sea_level` are
+/// filled with water.
+pub fn generate_chunk(
+    noise: &Noise,
+    biomes: &Biomes,
+    sea_level: i32,
+    chunk: Chunk,
+    chunk_kind: &mut ChunkStorage<voxel::Kind>,
+) {
     let world = chunk::to_world(chunk);
+    let water_level = sea_level.min(chunk::Y_AXIS_SIZE as i32);
 
     for x in 0..chunk::X_AXIS_SIZE {
         for z in 0..chunk::Z_AXIS_SIZE {
-            let end = noise.stone(world.x + x as f32, world.z + z as f32);
+            let (wx, wz) = (world.x + x as f32, world.z + z as f32);
+            let end = noise.stone(wx, wz);
+            let surface = biomes.select(noise.climate(wx, wz)).surface;
+            let underwater = end < sea_level;
+
             for y in 0..end {
-                chunk_kind.set((x as i32, y, z as i32).into(), Kind::id(3));
+                let kind = surface.kind_at(end - 1 - y, underwater);
+                chunk_kind.set((x as i32, y, z as i32).into(), kind);
+            }
+
+            for y in end.max(0)..water_level {
+                chunk_kind.set((x as i32, y, z as i32).into(), Kind::id(5));
+            }
+        }
+    }
+}
+
+/// Temperature, in climate range, below which exposed surfaces are covered by snow.
+pub const SNOW_TEMPERATURE: f32 = 0.2;
+
+/// Covers exposed top surfaces of cold places, like cold biomes or high altitudes, with snow
+/// layers. Only voxels which block light, like dirt or rock, are covered, so snow never rests on
+/// water, lamps or other layers.
+pub fn cover_surface(noise: &Noise, chunk: Chunk, chunk_kind: &mut ChunkStorage<voxel::Kind>) {
+    let Some(snow) = Kind::by_name("Snow") else {
+        return;
+    };
+
+    let world = chunk::to_world(chunk);
+
+    for x in 0..chunk::X_AXIS_SIZE as i32 {
+        for z in 0..chunk::Z_AXIS_SIZE as i32 {
+            let Some(y) = (0..chunk::Y_END)
+                .rev()
+                .find(|&y| !chunk_kind.get((x, y, z).into()).is_none())
+            else {
+                continue;
+            };
+
+            if !chunk_kind.get((x, y, z).into()).blocks_light() {
+                continue;
+            }
+
+            let above = (x, y + 1, z).into();
+            let climate = noise.climate_at(world + Vec3::new(x as f32, (y + 1) as f32, z as f32));
+            if climate.temperature < SNOW_TEMPERATURE {
+                chunk_kind.set(above, snow);
             }
         }
     }
@@ -26,23 +80,48 @@ pub fn init_light(
     chunk_kind: &ChunkStorage<voxel::Kind>,
     chunk_light: &mut ChunkStorage<voxel::Light>,
 ) {
-    // Set sun light on top voxels
-    chunk::top_voxels()
-        .filter(|&voxel| !chunk_kind.get(voxel).is_opaque())
-        .for_each(|voxel| {
-            chunk_light.set_type(
-                voxel,
-                voxel::LightTy::Natural,
-                voxel::Light::MAX_NATURAL_INTENSITY,
-            );
-        });
-
-    let _neighbor_propagation = light::propagate(
-        chunk_kind,
-        chunk_light,
-        voxel::LightTy::Natural,
-        chunk::top_voxels(),
-    );
+    let _neighbor_propagation = light::init_natural(chunk_kind, chunk_light);
 
     // TODO: Emit light propagation events
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::IVec3;
+
+    use super::*;
+
+    #[test]
+    fn cover_surface_only_blocking_light() {
+        let noise = Noise::default();
+        let chunk = Chunk::new(3, -1);
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+
+        // Tall rock column, water surface and a lamp, all high enough to be cold.
+        let top = chunk::Y_END - 1;
+        for y in 0..=top {
+            kind.set((0, y, 0).into(), Kind::id(3));
+        }
+        kind.set((1, top, 0).into(), Kind::id(5));
+        kind.set((2, top, 0).into(), Kind::id(4));
+
+        cover_surface(&noise, chunk, &mut kind);
+
+        let snow = Kind::by_name("Snow").unwrap();
+        let climate =
+            noise.climate_at(chunk::to_world(chunk) + IVec3::new(0, top + 1, 0).as_vec3());
+        assert_eq!(
+            kind.get((0, top + 1, 0).into()) == snow,
+            climate.temperature < SNOW_TEMPERATURE
+        );
+        assert!(
+            kind.get((1, top + 1, 0).into()).is_none(),
+            "Water is never covered"
+        );
+        assert!(
+            kind.get((2, top + 1, 0).into()).is_none(),
+            "Lamp is never covered"
+        );
+        assert!(kind.iter().filter(|&&k| k == snow).count() <= 1);
+    }
+}
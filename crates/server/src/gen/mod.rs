@@ -1,17 +1,29 @@
-use std::time::Duration;
+use std::{
+    panic::AssertUnwindSafe,
+    time::{Duration, Instant},
+};
 
-use async_channel::Receiver;
+use async_channel::{Receiver, Sender};
 use bevy::{app::ScheduleRunnerPlugin, ecs::schedule::ExecutorKind, prelude::*};
+use projekto_core::chunk::{self, ChunkStorage};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    asset::{ChunkAsset, ChunkAssetGenRequest},
-    bundle::{ChunkKind, ChunkLight, ChunkMap},
+    asset::{ChunkAsset, ChunkAssetGenRequest, LightValidity},
+    bundle::{ChunkKind, ChunkLight, ChunkLocal, ChunkMap},
+    debug::{ChunkGenerated, ChunkSaved},
+    error::{self, GenError, ServerError},
 };
 
-use self::noise::Noise;
+use self::{biome::Biomes, noise::Noise};
 
+pub(crate) mod biome;
+mod climate;
 mod genesis;
 pub(crate) mod noise;
+pub(crate) mod preview;
+
+pub use climate::{Climate, WorldClimate};
 
 #[derive(Component, Debug, Deref, DerefMut)]
 struct ChunkRequest(ChunkAssetGenRequest);
@@ -19,9 +31,113 @@ struct ChunkRequest(ChunkAssetGenRequest);
 #[derive(Resource, Deref, DerefMut)]
 pub(crate) struct ChunkAssetGenReceiver(pub Receiver<ChunkAssetGenRequest>);
 
+#[derive(Resource, Deref)]
+struct GenMetricsSender(Sender<(ChunkGenerated, ChunkSaved)>);
+
+/// Marks a chunk which failed on some generation pass, so remaining passes are skipped.
+#[derive(Component, Debug)]
+struct GenFailure(GenError);
+
+/// Time spent generating a chunk, on all passes.
+#[derive(Component, Default, Debug, Deref, DerefMut)]
+struct ChunkGenTime(Duration);
+
 const TICK_EVERY_MILLIS: u64 = 1000;
 
-pub(crate) fn start(receiver: Receiver<ChunkAssetGenRequest>) {
+/// Generation stages of a chunk, in the order generation passes reach them. Passes only read the
+/// chunk they generate, so chunks never wait on their neighbors.
+///
+/// There is no dependency resolver between chunks yet, so a pass which reads or writes neighbors,
+/// like structures crossing chunk borders, must add one along with it.
+#[derive(
+    Component,
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum GenStage {
+    /// Nothing was generated yet.
+    #[default]
+    Empty,
+    /// Terrain shape and sea were generated.
+    Terrain,
+    /// Surface was covered, like snow on cold places.
+    Decorated,
+    /// Light was computed, so chunk is fully generated.
+    Lit,
+}
+
+/// Settings of world generation. Must be inserted before [`crate::setup_chunk_asset_loader`],
+/// since it is only read there. Changing it on an existing world makes new chunks not match the
+/// ones already stored.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct WorldGenConfig {
+    pub seed: u64,
+    /// Which noise implementation generates terrain.
+    pub noise: NoiseBackend,
+    /// How many voxels terrain noise is bent by domain warping. Zero disables it.
+    pub domain_warp: f32,
+    /// How many height steps terrain is rounded to, making terraces. Zero disables it.
+    pub terraces: u32,
+    /// Where land is, so worlds can be islands or continents surrounded by oceans.
+    pub landmass: LandmassMask,
+    /// Height which empty voxels below it are filled with water, making oceans and lakes.
+    pub sea_level: i32,
+}
+
+impl Default for WorldGenConfig {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            noise: Default::default(),
+            domain_warp: 0.0,
+            terraces: 0,
+            landmass: Default::default(),
+            sea_level: 0,
+        }
+    }
+}
+
+/// Large scale mask which scales terrain height down to zero, where oceans are. It should be used
+/// along with [`WorldGenConfig::sea_level`], so oceans are filled with water.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum LandmassMask {
+    /// Land covers the whole world.
+    #[default]
+    None,
+    /// A single island centered on world origin, falling off to ocean up to `radius` voxels.
+    Island { radius: f32 },
+    /// Continents where a low frequency noise is above `threshold`, in range [-1.0, 1.0], with
+    /// oceans between them.
+    Continents { frequency: f32, threshold: f32 },
+}
+
+/// Implementation of coherent noise used by world generation.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseBackend {
+    /// `bracket_noise` crate.
+    #[default]
+    Bracket,
+    /// `fastnoise_lite` crate. Requires `fastnoise_lite` feature.
+    #[cfg(feature = "fastnoise_lite")]
+    FastNoiseLite,
+    /// Value noise implemented on this crate, without any dependency.
+    Fallback,
+}
+
+pub(crate) fn start(
+    receiver: Receiver<ChunkAssetGenRequest>,
+    metrics: Sender<(ChunkGenerated, ChunkSaved)>,
+    config: WorldGenConfig,
+) {
     // Force schedules to be single threaded, to avoid using thread pool.
     let (mut first_schedule, mut update_schedule, mut last_schedule) = (
         Schedule::new(First),
@@ -42,16 +158,24 @@ pub(crate) fn start(receiver: Receiver<ChunkAssetGenRequest>) {
         ))),
     ))
     .insert_resource(ChunkAssetGenReceiver(receiver))
+    .insert_resource(GenMetricsSender(metrics))
+    .insert_resource(Noise::new(&config))
+    .insert_resource(Biomes::load(biome::BiomesDescs::default_path()))
+    .insert_resource(config)
     .init_resource::<ChunkMap>()
     .add_schedule(first_schedule)
     .add_schedule(update_schedule)
     .add_schedule(last_schedule)
-    .configure_sets(Update, GenSet::Structure.before(GenSet::Light))
+    .configure_sets(
+        Update,
+        (GenSet::Structure, GenSet::Decoration, GenSet::Light).chain(),
+    )
     .add_systems(First, collect_requests)
     .add_systems(
         Update,
         (
             generate_structure.in_set(GenSet::Structure),
+            decorate.in_set(GenSet::Decoration),
             init_light.in_set(GenSet::Light),
         ),
     )
@@ -69,6 +193,7 @@ pub(crate) fn start(receiver: Receiver<ChunkAssetGenRequest>) {
 #[derive(SystemSet, Debug, Clone, Eq, PartialEq, Hash)]
 enum GenSet {
     Structure,
+    Decoration,
     Light,
 }
 
@@ -87,8 +212,11 @@ fn collect_requests(
         let entity = commands
             .spawn((
                 ChunkRequest(msg),
+                ChunkLocal(chunk),
+                GenStage::Empty,
                 ChunkKind::default(),
                 ChunkLight::default(),
+                ChunkGenTime::default(),
             ))
             .id();
 
@@ -100,32 +228,125 @@ fn collect_requests(
     trace!("[collect_request] {count} chunks requests received.");
 }
 
-fn generate_structure(mut q: Query<(&mut ChunkKind, &ChunkRequest)>, noise: Local<Noise>) {
-    if q.is_empty() {
-        return;
-    }
+/// Runs the given generation pass, catching any panic, so a single bad chunk doesn't stop the
+/// generator.
+fn run_pass(f: impl FnOnce()) -> Result<(), GenError> {
+    std::panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|payload| GenError::Panicked(error::panic_message(payload)))
+}
 
-    let mut count = 0;
-    for (mut kind, req) in q.iter_mut() {
-        count += 1;
-        genesis::generate_chunk(&noise, req.chunk, &mut kind);
-    }
+fn generate_structure(
+    mut commands: Commands,
+    mut q: Query<
+        (
+            Entity,
+            &mut ChunkKind,
+            &mut GenStage,
+            &mut ChunkGenTime,
+            &ChunkLocal,
+        ),
+        Without<GenFailure>,
+    >,
+    noise: Res<Noise>,
+    biomes: Res<Biomes>,
+    config: Res<WorldGenConfig>,
+) {
+    for (entity, mut kind, mut stage, mut time, local) in q.iter_mut() {
+        if *stage != GenStage::Empty {
+            continue;
+        }
 
-    trace!("[generate_structure] {count} chunks structures generated.");
-}
+        #[cfg(feature = "trace")]
+        let _span = info_span!("generate_structure", chunk = %local.0).entered();
 
-fn init_light(mut q: Query<(&mut ChunkLight, &ChunkKind, &ChunkRequest)>) {
-    if q.is_empty() {
-        return;
+        let start = Instant::now();
+        let mut storage = ChunkStorage::default();
+        match run_pass(|| {
+            genesis::generate_chunk(&noise, &biomes, config.sea_level, local.0, &mut storage);
+        }) {
+            Ok(()) => {
+                kind.0 = storage.into();
+                *stage = GenStage::Terrain;
+            }
+            Err(err) => {
+                commands.entity(entity).insert(GenFailure(err));
+            }
+        }
+        **time += start.elapsed();
     }
+}
 
-    let mut count = 0;
-    for (mut chunk_light, chunk_kind, req) in q.iter_mut() {
-        count += 1;
-        genesis::init_light(req.chunk, chunk_kind, &mut chunk_light);
+/// Promotes chunks to [`GenStage::Decorated`], covering their surface.
+fn decorate(
+    mut commands: Commands,
+    mut q: Query<
+        (
+            Entity,
+            &mut ChunkKind,
+            &mut GenStage,
+            &mut ChunkGenTime,
+            &ChunkLocal,
+        ),
+        Without<GenFailure>,
+    >,
+    noise: Res<Noise>,
+) {
+    for (entity, mut kind, mut stage, mut time, local) in q.iter_mut() {
+        if *stage != GenStage::Terrain {
+            continue;
+        }
+
+        #[cfg(feature = "trace")]
+        let _span = info_span!("decorate", chunk = %local.0).entered();
+
+        // Decorations are scattered by server once chunks are loaded, so only surface cover is
+        // placed here.
+        let start = Instant::now();
+        let mut storage = kind.0.storage().clone();
+        match run_pass(|| genesis::cover_surface(&noise, local.0, &mut storage)) {
+            Ok(()) => {
+                kind.0 = storage.into();
+                *stage = GenStage::Decorated;
+            }
+            Err(err) => {
+                commands.entity(entity).insert(GenFailure(err));
+            }
+        }
+        **time += start.elapsed();
     }
+}
 
-    trace!("[init_light] {count} chunks light initialized.");
+fn init_light(
+    mut commands: Commands,
+    mut q: Query<
+        (
+            Entity,
+            &mut ChunkLight,
+            &mut GenStage,
+            &mut ChunkGenTime,
+            &ChunkKind,
+            &ChunkLocal,
+        ),
+        Without<GenFailure>,
+    >,
+) {
+    for (entity, mut chunk_light, mut stage, mut time, chunk_kind, local) in q.iter_mut() {
+        if *stage != GenStage::Decorated {
+            continue;
+        }
+
+        #[cfg(feature = "trace")]
+        let _span = info_span!("init_light", chunk = %local.0).entered();
+
+        let start = Instant::now();
+        match run_pass(|| genesis::init_light(local.0, chunk_kind, &mut chunk_light)) {
+            Ok(()) => *stage = GenStage::Lit,
+            Err(err) => {
+                commands.entity(entity).insert(GenFailure(err));
+            }
+        }
+        **time += start.elapsed();
+    }
 }
 
 fn dispatch_requests(world: &mut World) {
@@ -133,19 +354,44 @@ fn dispatch_requests(world: &mut World) {
         .query_filtered::<Entity, With<ChunkRequest>>()
         .iter(world)
         .collect::<Vec<_>>();
+    let metrics = world.resource::<GenMetricsSender>().0.clone();
 
     entities.into_iter().for_each(|entity| {
-        let (ChunkRequest(req), ChunkKind(kind), ChunkLight(light)) = world
-            .entity_mut(entity)
-            .take::<(ChunkRequest, ChunkKind, ChunkLight)>()
-            .expect("All components to exists");
-
+        let mut entity_mut = world.entity_mut(entity);
+        let failure = entity_mut.take::<GenFailure>();
+        let components =
+            entity_mut.take::<(ChunkRequest, GenStage, ChunkKind, ChunkLight, ChunkGenTime)>();
         world.despawn(entity);
 
+        let Some((
+            ChunkRequest(req),
+            stage,
+            ChunkKind(kind),
+            ChunkLight(light),
+            ChunkGenTime(time),
+        )) = components
+        else {
+            error!("Chunk request {entity:?} is missing components. Skipping it.");
+            return;
+        };
+
+        // Chunks aren't persisted yet, so the same chunk is requested again once revisited.
+        world.resource_mut::<ChunkMap>().remove(&req.chunk);
+
+        if let Some(GenFailure(source)) = failure {
+            let chunk = req.chunk;
+            error!("{}", ServerError::Gen { chunk, source });
+            req.finish(Err(()));
+            return;
+        }
+
         let asset = ChunkAsset {
             chunk: req.chunk,
+            hash: chunk::content_hash(&kind, &light),
+            light_validity: LightValidity::new(&kind),
             light,
-            kind,
+            kind: kind.into_inner(),
+            stage,
             ..Default::default()
         };
 
@@ -154,16 +400,35 @@ fn dispatch_requests(world: &mut World) {
             req.chunk
         );
 
-        if let Ok(bytes) = bincode::serialize(&asset) {
-            trace!(
-                "[dispatch_requests] Chunk {} serialized. Size: {} bytes",
-                req.chunk,
-                bytes.len()
-            );
-            req.finish(Ok(bytes));
-        } else {
-            let chunk = asset.chunk;
-            error!("Failed to serialize chunk {chunk:?}.");
-        }
+        #[cfg(feature = "trace")]
+        let _span = info_span!("serialize_chunk", chunk = %req.chunk).entered();
+
+        let bytes = match asset.to_bytes() {
+            Ok(bytes) => bytes,
+            Err(source) => {
+                let chunk = asset.chunk;
+                let source = GenError::Serialize(source);
+                error!("{}", ServerError::Gen { chunk, source });
+                // Asset reader is still waiting for this request, so it must always be finished.
+                req.finish(Err(()));
+                return;
+            }
+        };
+
+        trace!(
+            "[dispatch_requests] Chunk {} serialized. Size: {} bytes",
+            req.chunk,
+            bytes.len()
+        );
+
+        let generated = ChunkGenerated {
+            chunk: req.chunk,
+            micros: time.as_micros() as u64,
+        };
+        let saved = ChunkSaved { bytes: bytes.len() };
+        // Server may not be listening anymore, which is fine, since those are just metrics.
+        let _ = metrics.try_send((generated, saved));
+
+        req.finish(Ok(bytes));
     });
 }
@@ -1,21 +1,279 @@
 use bevy::{math::vec2, prelude::*};
 use bracket_noise::prelude::*;
 
+use super::{
+    climate::{Climate, ClimateNoise},
+    LandmassMask, NoiseBackend, WorldGenConfig,
+};
+
+/// Source of 2D coherent noise, which returns values roughly in range [-1.0, 1.0].
+pub(crate) trait NoiseSource: Send + Sync {
+    fn get(&self, x: f32, z: f32) -> f32;
+}
+
+/// Settings of a fractal noise, shared by all [`NoiseBackend`], so all of them generate similar
+/// shapes, even if not the same values.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FractalParams {
+    pub frequency: f32,
+    pub octaves: u32,
+    pub gain: f32,
+    pub lacunarity: f32,
+}
+
+impl NoiseBackend {
+    /// Creates a new [`NoiseSource`] of this backend, with the given seed and fractal settings.
+    pub(crate) fn source(self, seed: u64, params: FractalParams) -> Box<dyn NoiseSource> {
+        match self {
+            NoiseBackend::Bracket => Box::new(BracketNoise::new(seed, params)),
+            #[cfg(feature = "fastnoise_lite")]
+            NoiseBackend::FastNoiseLite => Box::new(FastNoiseLiteSource::new(seed, params)),
+            NoiseBackend::Fallback => Box::new(ValueNoise::new(seed, params)),
+        }
+    }
+}
+
+/// Simplex fractal noise of `bracket_noise` crate.
+pub(crate) struct BracketNoise(FastNoise);
+
+impl BracketNoise {
+    pub fn new(seed: u64, params: FractalParams) -> Self {
+        let mut noise = FastNoise::seeded(seed);
+        noise.set_noise_type(NoiseType::SimplexFractal);
+        noise.set_frequency(params.frequency);
+        noise.set_fractal_type(FractalType::FBM);
+        noise.set_fractal_octaves(params.octaves as i32);
+        noise.set_fractal_gain(params.gain);
+        noise.set_fractal_lacunarity(params.lacunarity);
+        Self(noise)
+    }
+}
+
+impl NoiseSource for BracketNoise {
+    fn get(&self, x: f32, z: f32) -> f32 {
+        self.0.get_noise(x, z)
+    }
+}
+
+/// OpenSimplex2 fractal noise of `fastnoise_lite` crate.
+#[cfg(feature = "fastnoise_lite")]
+pub(crate) struct FastNoiseLiteSource(fastnoise_lite::FastNoiseLite);
+
+#[cfg(feature = "fastnoise_lite")]
+impl FastNoiseLiteSource {
+    pub fn new(seed: u64, params: FractalParams) -> Self {
+        use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+
+        let mut noise = FastNoiseLite::with_seed(seed as i32);
+        noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+        noise.set_frequency(Some(params.frequency));
+        noise.set_fractal_type(Some(FractalType::FBm));
+        noise.set_fractal_octaves(Some(params.octaves as i32));
+        noise.set_fractal_gain(Some(params.gain));
+        noise.set_fractal_lacunarity(Some(params.lacunarity));
+        Self(noise)
+    }
+}
+
+#[cfg(feature = "fastnoise_lite")]
+impl NoiseSource for FastNoiseLiteSource {
+    fn get(&self, x: f32, z: f32) -> f32 {
+        self.0.get_noise_2d(x, z)
+    }
+}
+
+/// Fractal value noise without any dependency, used when no other backend is available. It is
+/// blockier than gradient noises, but cheap and deterministic across platforms.
+pub(crate) struct ValueNoise {
+    seed: u32,
+    params: FractalParams,
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64, params: FractalParams) -> Self {
+        Self {
+            seed: (seed ^ (seed >> 32)) as u32,
+            params,
+        }
+    }
+
+    /// **Returns** a random value in range [-1.0, 1.0] for the given lattice point.
+    fn lattice(&self, x: i32, z: i32, octave: u32) -> f32 {
+        let mut hash = self.seed.wrapping_add(octave.wrapping_mul(0x9E37_79B9));
+        hash ^= (x as u32).wrapping_mul(0x85EB_CA6B);
+        hash = hash.rotate_left(13) ^ (z as u32).wrapping_mul(0xC2B2_AE35);
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x7FEB_352D);
+        hash ^= hash >> 15;
+
+        (hash as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    fn octave(&self, x: f32, z: f32, octave: u32) -> f32 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+        let (x0, z0) = (x0 as i32, z0 as i32);
+
+        let top = self.lattice(x0, z0, octave) * (1.0 - tx) + self.lattice(x0 + 1, z0, octave) * tx;
+        let bottom = self.lattice(x0, z0 + 1, octave) * (1.0 - tx)
+            + self.lattice(x0 + 1, z0 + 1, octave) * tx;
+
+        top * (1.0 - tz) + bottom * tz
+    }
+}
+
+impl NoiseSource for ValueNoise {
+    fn get(&self, x: f32, z: f32) -> f32 {
+        let FractalParams {
+            frequency,
+            octaves,
+            gain,
+            lacunarity,
+        } = self.params;
+
+        let (mut sum, mut amplitude_sum) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (frequency, 1.0);
+
+        for octave in 0..octaves.max(1) {
+            sum += self.octave(x * frequency, z * frequency, octave) * amplitude;
+            amplitude_sum += amplitude;
+            frequency *= lacunarity;
+            amplitude *= gain;
+        }
+
+        sum / amplitude_sum
+    }
+}
+
+/// Offsets coordinates of `source` by `warp` noise, scaled by `strength`, so its shapes are bent
+/// and look less regular.
+pub(crate) struct DomainWarp {
+    pub source: Box<dyn NoiseSource>,
+    pub warp: Box<dyn NoiseSource>,
+    pub strength: f32,
+}
+
+impl NoiseSource for DomainWarp {
+    fn get(&self, x: f32, z: f32) -> f32 {
+        // Warp is sampled far away for Z axis, so both axis aren't offset by the same amount.
+        const Z_OFFSET: f32 = 5_197.0;
+
+        let warp_x = self.warp.get(x, z) * self.strength;
+        let warp_z = self.warp.get(x + Z_OFFSET, z + Z_OFFSET) * self.strength;
+        self.source.get(x + warp_x, z + warp_z)
+    }
+}
+
+/// Rounds `source` to the given number of evenly spaced steps in range [-1.0, 1.0], which makes
+/// terrain look like terraces.
+pub(crate) struct Terrace {
+    pub source: Box<dyn NoiseSource>,
+    pub steps: u32,
+}
+
+impl NoiseSource for Terrace {
+    fn get(&self, x: f32, z: f32) -> f32 {
+        let steps = self.steps as f32;
+        let t = (self.source.get(x, z).clamp(-1.0, 1.0) + 1.0) / 2.0;
+        ((t * steps).round() / steps) * 2.0 - 1.0
+    }
+}
+
+/// Large scale mask of where land is, in range [0.0, 1.0], which scales terrain height down to
+/// make oceans. See [`LandmassMask`].
+pub(crate) enum Landmass {
+    Island {
+        radius: f32,
+    },
+    Continents {
+        source: Box<dyn NoiseSource>,
+        threshold: f32,
+    },
+}
+
+impl Landmass {
+    /// Width of coast, in noise units, where continents fall off into oceans.
+    const COAST_WIDTH: f32 = 0.1;
+
+    pub fn new(mask: LandmassMask, config: &WorldGenConfig) -> Option<Self> {
+        match mask {
+            LandmassMask::None => None,
+            LandmassMask::Island { radius } => Some(Self::Island { radius }),
+            LandmassMask::Continents {
+                frequency,
+                threshold,
+            } => {
+                let params = FractalParams {
+                    frequency,
+                    octaves: 2,
+                    gain: 0.5,
+                    lacunarity: 2.0,
+                };
+                Some(Self::Continents {
+                    source: config.noise.source(config.seed.wrapping_add(2), params),
+                    threshold,
+                })
+            }
+        }
+    }
+
+    pub fn get(&self, x: f32, z: f32) -> f32 {
+        let smoothstep = |t: f32| {
+            let t = t.clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        };
+
+        match self {
+            Self::Island { radius } => 1.0 - smoothstep(vec2(x, z).length() / radius),
+            Self::Continents { source, threshold } => {
+                let n = source.get(x, z);
+                smoothstep((n - threshold) / Self::COAST_WIDTH + 0.5)
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
 pub(crate) struct Noise {
-    continentalness: FastNoise,
+    continentalness: Box<dyn NoiseSource>,
+    landmass: Option<Landmass>,
+    climate: ClimateNoise,
     curve: Vec<Vec2>,
 }
 
 impl Noise {
-    pub fn new() -> Self {
+    pub fn new(config: &WorldGenConfig) -> Self {
         // TODO: Move this to a config per-biome
-        let mut continentalness = FastNoise::seeded(42);
-        continentalness.set_noise_type(NoiseType::SimplexFractal);
-        continentalness.set_frequency(0.03);
-        continentalness.set_fractal_type(FractalType::FBM);
-        continentalness.set_fractal_octaves(3);
-        continentalness.set_fractal_gain(0.9);
-        continentalness.set_fractal_lacunarity(0.10);
+        let params = FractalParams {
+            frequency: 0.03,
+            octaves: 3,
+            gain: 0.9,
+            lacunarity: 0.10,
+        };
+        let mut continentalness = config.noise.source(config.seed, params);
+
+        if config.domain_warp > 0.0 {
+            let warp_params = FractalParams {
+                frequency: 0.01,
+                octaves: 1,
+                ..params
+            };
+            continentalness = Box::new(DomainWarp {
+                source: continentalness,
+                warp: config
+                    .noise
+                    .source(config.seed.wrapping_add(1), warp_params),
+                strength: config.domain_warp,
+            });
+        }
+
+        if config.terraces > 0 {
+            continentalness = Box::new(Terrace {
+                source: continentalness,
+                steps: config.terraces,
+            });
+        }
 
         let curve = vec![
             vec2(-1.0, 50.0),
@@ -26,6 +284,8 @@ impl Noise {
 
         Noise {
             continentalness,
+            landmass: Landmass::new(config.landmass, config),
+            climate: ClimateNoise::new(config),
             curve,
         }
     }
@@ -56,14 +316,124 @@ impl Noise {
     }
 
     pub fn stone(&self, x: f32, z: f32) -> i32 {
-        let n = self.continentalness.get_noise(x, z);
+        // Backends only return roughly normalized values, so it is clamped to curve range.
+        let n = self.continentalness.get(x, z).clamp(-1.0, 1.0);
         let add = self.lerp(n);
-        100 + add
+        let height = 100 + add;
+
+        match &self.landmass {
+            Some(landmass) => (height as f32 * landmass.get(x, z)) as i32,
+            None => height,
+        }
+    }
+
+    /// **Returns** the climate of the given world column, which biomes are selected from.
+    pub fn climate(&self, x: f32, z: f32) -> Climate {
+        self.climate.get(x, z)
+    }
+
+    /// **Returns** the climate at the given world position, cooled down by altitude.
+    pub fn climate_at(&self, world_pos: Vec3) -> Climate {
+        self.climate.at(world_pos)
     }
 }
 
 impl Default for Noise {
     fn default() -> Self {
-        Self::new()
+        Self::new(&WorldGenConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: FractalParams = FractalParams {
+        frequency: 0.03,
+        octaves: 3,
+        gain: 0.5,
+        lacunarity: 2.0,
+    };
+
+    #[test]
+    fn value_noise_range_and_determinism() {
+        let noise = ValueNoise::new(42, PARAMS);
+        let other = ValueNoise::new(42, PARAMS);
+
+        for i in 0..1000 {
+            let (x, z) = (i as f32 * 1.7 - 500.0, i as f32 * -0.3 + 20.0);
+            let value = noise.get(x, z);
+            assert!((-1.0..=1.0).contains(&value), "{value} out of range");
+            assert_eq!(
+                value,
+                other.get(x, z),
+                "Same seed should generate same noise"
+            );
+        }
+
+        let seeded = ValueNoise::new(7, PARAMS);
+        assert!(
+            (0..100).any(|i| seeded.get(i as f32, 0.0) != noise.get(i as f32, 0.0)),
+            "Other seed should generate other noise"
+        );
+    }
+
+    #[test]
+    fn terrace_steps() {
+        let terrace = Terrace {
+            source: Box::new(ValueNoise::new(42, PARAMS)),
+            steps: 4,
+        };
+
+        for i in 0..100 {
+            let value = terrace.get(i as f32 * 3.1, i as f32 * 0.7);
+            let step = (value + 1.0) / 2.0 * 4.0;
+            assert!(
+                (step - step.round()).abs() < 0.0001,
+                "{value} isn't on a terrace step"
+            );
+        }
+    }
+
+    #[test]
+    fn island_landmass() {
+        let island = Landmass::Island { radius: 100.0 };
+
+        assert_eq!(island.get(0.0, 0.0), 1.0, "Center is full land");
+        assert_eq!(island.get(70.0, 80.0), 0.0, "Beyond radius is ocean");
+
+        let near = island.get(30.0, 0.0);
+        let far = island.get(60.0, 0.0);
+        assert!(
+            1.0 > near && near > far && far > 0.0,
+            "Land falls off to coast"
+        );
+    }
+
+    #[test]
+    fn continents_landmass() {
+        let continents = Landmass::Continents {
+            source: Box::new(ValueNoise::new(42, PARAMS)),
+            threshold: 0.0,
+        };
+
+        let masks = (0..1000)
+            .map(|i| continents.get(i as f32 * 13.0, i as f32 * -7.0))
+            .collect::<Vec<_>>();
+        assert!(masks.iter().all(|m| (0.0..=1.0).contains(m)));
+        assert!(masks.iter().any(|&m| m == 0.0), "Should have oceans");
+        assert!(masks.iter().any(|&m| m == 1.0), "Should have land");
+    }
+
+    #[test]
+    fn domain_warp_without_strength() {
+        let warped = DomainWarp {
+            source: Box::new(ValueNoise::new(42, PARAMS)),
+            warp: Box::new(ValueNoise::new(43, PARAMS)),
+            strength: 0.0,
+        };
+        let source = ValueNoise::new(42, PARAMS);
+
+        assert_eq!(warped.get(12.5, -3.2), source.get(12.5, -3.2));
     }
 }
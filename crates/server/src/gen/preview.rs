@@ -0,0 +1,132 @@
+//! Top-down maps sampled straight from world generator, without generating nor spawning chunks,
+//! which is useful to tune generation settings.
+
+use bevy::prelude::*;
+use projekto_core::{chunk, voxel::Kind};
+use projekto_messages::PreviewLayer;
+
+use super::{biome::Biomes, noise::Noise, WorldGenConfig};
+
+/// Max width and height, in pixels, of a preview.
+pub const MAX_PREVIEW_SIZE: u16 = 256;
+
+/// World generator state needed to render previews. It is created on every request, so changes
+/// on [`WorldGenConfig`] or biomes descriptions are visible right away.
+pub(crate) struct GenPreview {
+    noise: Noise,
+    biomes: Biomes,
+    sea_level: i32,
+}
+
+impl GenPreview {
+    pub fn new(config: &WorldGenConfig) -> Self {
+        Self {
+            noise: Noise::new(config),
+            biomes: Biomes::load(super::biome::BiomesDescs::default_path()),
+            sea_level: config.sea_level,
+        }
+    }
+
+    /// Renders `size` by `size` RGB pixels, row by row, centered on `center`, in world
+    /// coordinates, with a pixel every `scale` voxels. Size is clamped to [`MAX_PREVIEW_SIZE`].
+    pub fn render(
+        &self,
+        layer: PreviewLayer,
+        center: IVec2,
+        size: u16,
+        scale: u16,
+    ) -> Vec<[u8; 3]> {
+        let size = size.min(MAX_PREVIEW_SIZE) as i32;
+        let scale = scale.max(1) as i32;
+        let origin = center - IVec2::splat(size / 2 * scale);
+
+        (0..size)
+            .flat_map(|y| (0..size).map(move |x| origin + IVec2::new(x, y) * scale))
+            .map(|world| self.sample(layer, world.x as f32, world.y as f32))
+            .collect()
+    }
+
+    fn sample(&self, layer: PreviewLayer, x: f32, z: f32) -> [u8; 3] {
+        let height = self.noise.stone(x, z);
+        let underwater = height < self.sea_level;
+        // Higher terrain is brighter, so relief is visible on any layer.
+        let t = (height as f32 / chunk::Y_AXIS_SIZE as f32).clamp(0.0, 1.0);
+
+        let climate = self.noise.climate(x, z);
+
+        let color = match (layer, underwater) {
+            (PreviewLayer::Height, false) => [255; 3],
+            (PreviewLayer::Height, true) => [40, 80, 220],
+            (PreviewLayer::Biome, false) => {
+                let surface = self.biomes.select(climate).surface;
+                surface.kind_at(0, false).map_color()
+            }
+            // Kind id 5 is water, which fills oceans. See [`super::genesis::generate_chunk`].
+            (PreviewLayer::Biome, true) => Kind::id(5).map_color(),
+            (PreviewLayer::Temperature, _) => {
+                gradient([40, 90, 230], [230, 60, 30], climate.temperature)
+            }
+            (PreviewLayer::Humidity, _) => {
+                gradient([200, 170, 90], [30, 140, 60], climate.humidity)
+            }
+        };
+
+        let shade = match layer {
+            PreviewLayer::Height => t,
+            PreviewLayer::Biome => 0.5 + t * 0.5,
+            // Climate fields are independent of terrain, so those aren't shaded.
+            PreviewLayer::Temperature | PreviewLayer::Humidity => 1.0,
+        };
+        color.map(|c| (c as f32 * shade) as u8)
+    }
+}
+
+/// **Returns** the color between `from` and `to` at `t`, in range [0.0 ~ 1.0].
+fn gradient(from: [u8; 3], to: [u8; 3], t: f32) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    [0, 1, 2].map(|i| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * t) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_size() {
+        let preview = GenPreview::new(&WorldGenConfig::default());
+
+        let pixels = preview.render(PreviewLayer::Height, IVec2::ZERO, 16, 2);
+        assert_eq!(pixels.len(), 16 * 16);
+
+        let pixels = preview.render(PreviewLayer::Biome, IVec2::ZERO, u16::MAX, 0);
+        assert_eq!(pixels.len(), (MAX_PREVIEW_SIZE as usize).pow(2));
+    }
+
+    #[test]
+    fn render_oceans() {
+        let config = WorldGenConfig {
+            sea_level: chunk::Y_AXIS_SIZE as i32,
+            ..Default::default()
+        };
+        let preview = GenPreview::new(&config);
+
+        let pixels = preview.render(PreviewLayer::Height, IVec2::new(100, -30), 8, 1);
+        assert!(
+            pixels.iter().all(|&[r, _, b]| b > r),
+            "Terrain below sea level should be blue"
+        );
+    }
+
+    #[test]
+    fn render_same_as_generator() {
+        let config = WorldGenConfig::default();
+        let preview = GenPreview::new(&config);
+        let noise = Noise::new(&config);
+
+        let pixels = preview.render(PreviewLayer::Height, IVec2::new(5, 5), 2, 1);
+        let [r, ..] = pixels[3];
+        let t = noise.stone(5.0, 5.0) as f32 / chunk::Y_AXIS_SIZE as f32;
+
+        assert_eq!(r, (255.0 * t.clamp(0.0, 1.0)) as u8);
+    }
+}
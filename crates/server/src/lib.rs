@@ -1,16 +1,29 @@
 use std::time::Duration;
 
+use api::ApiPlugin;
 use asset::ChunkAssetPlugin;
-use bevy::{prelude::*, time::common_conditions::on_timer};
+use bevy::prelude::*;
+use budget::TickBudgetPlugin;
 use net::NetPlugin;
 
+pub mod api;
 pub mod app;
-mod light;
-mod meshing;
+pub mod chat;
+pub mod debug;
+pub mod dig;
+pub mod error;
+mod export;
+pub mod light;
+pub mod meshing;
+pub mod meta;
+pub mod protection;
 
 mod asset;
 
-pub use asset::{setup_chunk_asset_loader, ChunkAsset};
+pub use asset::{
+    setup_chunk_asset_loader, ChunkAsset, ChunkAssetView, CorruptChunkPolicy, LightValidity,
+    MeshValidity,
+};
 
 mod net;
 
@@ -19,6 +32,32 @@ pub mod gen;
 
 pub mod bundle;
 pub mod set;
+pub mod terraform;
+
+mod budget;
+mod mesher;
+mod rate_limit;
+mod time;
+
+#[cfg(feature = "scripting")]
+pub mod scripting;
+
+#[cfg(feature = "trace")]
+mod trace;
+
+#[cfg(test)]
+mod test_harness;
+
+pub use api::{
+    ChunkEdited, ChunkLoaded, ChunkUnloaded, Kinds, PlayerJoined, PlayerLeft, WorldTick,
+};
+pub use budget::TickBudget;
+pub use error::{Quarantine, ServerError};
+pub use gen::{Climate, GenStage, LandmassMask, NoiseBackend, WorldClimate, WorldGenConfig};
+pub use net::CompressionBudget;
+pub use projekto_core::time::WorldTime;
+pub use rate_limit::{RateLimit, RateLimits};
+pub use time::ServerTick;
 
 const MESHING_TICK_MS: u64 = 500;
 
@@ -32,14 +71,22 @@ impl Plugin for WorldServerPlugin {
                 (
                     WorldSet::LandscapeUpdate,
                     WorldSet::ChunkManagement,
-                    WorldSet::Propagation,
-                    WorldSet::Meshing.run_if(on_timer(Duration::from_millis(MESHING_TICK_MS))),
+                    WorldSet::ChunkInitialization,
+                    WorldSet::Propagation.run_if(budget::not_deferred(WorldSet::Propagation)),
+                    WorldSet::CollectAsync,
+                    WorldSet::Meshing
+                        .run_if(meshing_due)
+                        .run_if(budget::not_deferred(WorldSet::Meshing)),
                 )
                     .chain(),
             )
             .configure_sets(PostUpdate, WorldSet::SendResponses)
             .add_plugins((
+                ApiPlugin,
                 ChunkAssetPlugin,
+                chat::ChatPlugin,
+                debug::DebugPlugin,
+                dig::DigPlugin,
                 NetPlugin,
                 set::LandscapePlugin,
                 set::ChunkManagementPlugin,
@@ -48,18 +95,67 @@ impl Plugin for WorldServerPlugin {
                 set::MeshingPlugin,
                 set::SendResponsesPlugin,
                 set::ReceiveRequestsPlugin,
-            ));
+                terraform::TerraformPlugin,
+                time::WorldTimePlugin,
+                TickBudgetPlugin,
+            ))
+            .insert_resource(MeshingTimer(Timer::new(
+                Duration::from_millis(MESHING_TICK_MS),
+                TimerMode::Once,
+            )))
+            .add_systems(First, tick_meshing_timer)
+            .add_systems(Update, restart_meshing_timer.in_set(WorldSet::Meshing));
+
+        #[cfg(feature = "trace")]
+        app.add_plugins(trace::TracePlugin);
     }
 }
 
+/// Meshing runs at most once every [`MESHING_TICK_MS`], so changes are batched. Timer only restarts
+/// when meshing actually runs, so deferred meshing runs on next tick.
+#[derive(Resource, Debug, Deref, DerefMut)]
+struct MeshingTimer(Timer);
+
+fn tick_meshing_timer(time: Res<Time>, mut timer: ResMut<MeshingTimer>) {
+    timer.tick(time.delta());
+}
+
+fn meshing_due(timer: Res<MeshingTimer>) -> bool {
+    timer.finished()
+}
+
+fn restart_meshing_timer(mut timer: ResMut<MeshingTimer>) {
+    timer.reset();
+}
+
+/// Sets every server system runs on. This ordering is a contract game-specific crates can rely on
+/// when adding their own systems, by placing them `before` or `after` a set:
+///
+/// - `PreUpdate`: [`ReceiveRequests`](Self::ReceiveRequests).
+/// - `Update`: [`LandscapeUpdate`](Self::LandscapeUpdate) to [`Meshing`](Self::Meshing), always in
+///   the order they are declared here.
+/// - `PostUpdate`: [`SendResponses`](Self::SendResponses).
+///
+/// [`Propagation`](Self::Propagation) and [`Meshing`](Self::Meshing) may be skipped on a tick, so
+/// systems shouldn't expect them to run every tick. See [`api`] for the events sent between sets.
 #[derive(SystemSet, Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum WorldSet {
+    /// Handles client messages, like voxel edits. Players already joined when it runs.
     ReceiveRequests,
+    /// Moves landscape around players, requesting chunks to be loaded or unloaded.
     LandscapeUpdate,
+    /// Loads, generates and unloads chunks. Chunks spawned here are on [`bundle::ChunkMap`] after
+    /// this set.
     ChunkManagement,
     ChunkInitialization,
+    /// Propagates light of changed chunks. Skipped when tick is over [`TickBudget`].
     Propagation,
+    /// Collects results of async tasks spawned by previous sets.
+    CollectAsync,
+    /// Meshes changed chunks, batching changes of a few ticks. Skipped when tick is over
+    /// [`TickBudget`].
     Meshing,
+    /// Sends world changes to clients. Chunk and player events of this tick were already sent.
     SendResponses,
 }
 
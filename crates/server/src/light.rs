@@ -1,12 +1,18 @@
 use std::collections::VecDeque;
 
-use bevy::math::IVec3;
+use bevy::math::{IVec2, IVec3};
 use projekto_core::{
     chunk::{self, Chunk, ChunkSide, ChunkStorage, GetChunkStorage},
     math,
     voxel::{self, LightTy, Voxel},
 };
 
+use crate::meshing::MissingNeighborPolicy;
+
+/// Version of light propagation rules. Bump it whenever they change, so light persisted by an
+/// older version is recomputed when loaded.
+pub const LIGHT_VERSION: u16 = 1;
+
 /// Number of neighbors per voxel
 // 3 voxels (-1..=1) per axis.
 // -1 to skip self (0, 0, 0)
@@ -82,71 +88,217 @@ const NEIGHBOR_VERTEX_LOOKUP: [[[usize; VERTEX_COUNT]; VERTEX_NEIGHBOR_COUNT]; v
     ],
 ];
 
-fn gather_neighborhood_light<'a>(
-    chunk: Chunk,
-    voxel: Voxel,
-    get_kind: impl GetChunkStorage<'a, voxel::Kind>,
-    get_light: impl GetChunkStorage<'a, voxel::Light>,
-) -> [Option<u8>; NEIGHBOR_COUNT] {
-    let mut neighborhood = [Default::default(); NEIGHBOR_COUNT];
+/// Columns of [`LightSamples`] on each axis, which includes a column of each neighbor chunk.
+const SAMPLES_X: usize = chunk::X_AXIS_SIZE + 2;
+const SAMPLES_Z: usize = chunk::Z_AXIS_SIZE + 2;
+
+/// Light intensity of each voxel of a chunk and of the neighbor voxels around it, where `None`
+/// means the voxel blocks light. Samples are kept along with chunk light, so smoothing reads them
+/// for every face, instead of looking up neighbor chunks for each voxel, and only samples which
+/// were invalidated since last smoothing are gathered again.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LightSamples {
+    /// Empty until all samples are gathered, like when chunk is spawned.
+    samples: Vec<Option<u8>>,
+    /// Samples, relative to chunk, which must be gathered again.
+    dirty: Vec<Voxel>,
+}
+
+impl LightSamples {
+    /// Invalidates all samples, so they are gathered from scratch on next smoothing.
+    pub fn invalidate_all(&mut self) {
+        self.samples = vec![];
+        self.dirty.clear();
+    }
 
-    let light = get_light(chunk).expect("base chunk must exists");
-    let kind = get_kind(chunk).expect("base chunk must exists");
+    /// Invalidates samples of the given voxels, which belong to the chunk `offset` chunks away
+    /// from this one. Voxels which aren't sampled by this chunk are skipped.
+    pub fn invalidate(&mut self, offset: IVec2, voxels: impl IntoIterator<Item = Voxel>) {
+        if self.samples.is_empty() {
+            return;
+        }
 
-    let mut i = 0;
-    for y in -1..=1 {
-        for z in -1..=1 {
-            for x in -1..=1 {
-                let dir = IVec3::new(x, y, z);
+        let shift = IVec3::new(
+            offset.x * chunk::X_AXIS_SIZE as i32,
+            0,
+            offset.y * chunk::Z_AXIS_SIZE as i32,
+        );
+        self.dirty.extend(
+            voxels
+                .into_iter()
+                .map(|voxel| voxel + shift)
+                .filter(|&sample| Self::is_sampled(sample)),
+        );
+        self.limit_dirty();
+    }
 
-                if dir == IVec3::ZERO {
-                    continue;
-                }
+    /// Invalidates all samples of the chunk `offset` chunks away from this one, like when its
+    /// light is computed from scratch.
+    pub fn invalidate_border(&mut self, offset: IVec2) {
+        if self.samples.is_empty() {
+            return;
+        }
 
-                let side_voxel = voxel + dir;
+        let range = |offset: i32, size: usize| match offset {
+            ..=-1 => -1..=-1,
+            0 => 0..=size as i32 - 1,
+            _ => size as i32..=size as i32,
+        };
 
-                let intensity = if chunk::is_inside(side_voxel) {
-                    let intensity = light.get(side_voxel).get_greater_intensity();
+        for x in range(offset.x, chunk::X_AXIS_SIZE) {
+            for z in range(offset.y, chunk::Z_AXIS_SIZE) {
+                self.dirty
+                    .extend((0..chunk::Y_AXIS_SIZE as i32).map(|y| Voxel::new(x, y, z)));
+            }
+        }
+        self.limit_dirty();
+    }
 
-                    // Check if returned block is opaque
-                    if intensity == 0 && kind.get(side_voxel).is_opaque() {
-                        None
-                    } else {
-                        Some(intensity)
-                    }
-                } else if y != 0 {
-                    // There is no chunk above or below
-                    Some(voxel::Light::MAX_NATURAL_INTENSITY)
+    /// Invalidated samples aren't deduplicated, so once there are more of them than samples, it
+    /// is cheaper to gather all of them again.
+    fn limit_dirty(&mut self) {
+        if self.dirty.len() > self.samples.len() {
+            self.invalidate_all();
+        }
+    }
+
+    /// Checks if the given voxel, relative to chunk, is sampled, which are chunk voxels and the
+    /// ones up to one voxel away from it on X and Z axis.
+    fn is_sampled(sample: Voxel) -> bool {
+        (-1..=chunk::X_AXIS_SIZE as i32).contains(&sample.x)
+            && (-1..=chunk::Z_AXIS_SIZE as i32).contains(&sample.z)
+            && (0..chunk::Y_AXIS_SIZE as i32).contains(&sample.y)
+    }
+
+    /// Gathers invalidated samples, or all of them, if they weren't gathered yet.
+    fn refresh<'a>(
+        &mut self,
+        chunk: Chunk,
+        get_kind: impl GetChunkStorage<'a, voxel::Kind>,
+        get_light: impl GetChunkStorage<'a, voxel::Light>,
+        missing: MissingNeighborPolicy,
+    ) {
+        // Heights are only needed when a neighbor is missing, so they are computed on demand.
+        let heights = std::cell::OnceCell::new();
+        let is_solid = |sample: Voxel| {
+            let heights = heights.get_or_init(|| get_kind(chunk).and_then(|k| missing.heights(k)));
+            MissingNeighborPolicy::is_solid(heights.as_deref(), sample)
+        };
+
+        if self.samples.is_empty() {
+            self.dirty.clear();
+            self.samples = Self::gather_all(chunk, get_kind, get_light, is_solid);
+            return;
+        }
+
+        for sample in std::mem::take(&mut self.dirty) {
+            let (owner, voxel) = if chunk::is_inside(sample) {
+                (chunk, sample)
+            } else {
+                let (dir, voxel) = chunk::overlap_voxel(sample);
+                (chunk.neighbor(dir), voxel)
+            };
+
+            self.samples[Self::index(sample.x, sample.y, sample.z)] =
+                match (get_kind(owner), get_light(owner)) {
+                    (Some(kind), Some(light)) => Self::sample(kind, light, voxel),
+                    _ if is_solid(sample) => None,
+                    _ => Some(voxel::Light::MAX_NATURAL_INTENSITY),
+                };
+        }
+    }
+
+    fn gather_all<'a>(
+        chunk: Chunk,
+        get_kind: impl GetChunkStorage<'a, voxel::Kind>,
+        get_light: impl GetChunkStorage<'a, voxel::Light>,
+        is_solid: impl Fn(Voxel) -> bool,
+    ) -> Vec<Option<u8>> {
+        // Samples of missing neighbors are only replaced when policy assumes them solid.
+        let mut samples = vec![
+            Some(voxel::Light::MAX_NATURAL_INTENSITY);
+            SAMPLES_X * SAMPLES_Z * chunk::Y_AXIS_SIZE
+        ];
+
+        for x in -1..=chunk::X_AXIS_SIZE as i32 {
+            for z in -1..=chunk::Z_AXIS_SIZE as i32 {
+                let column = Voxel::new(x, 0, z);
+                let (owner, column) = if chunk::is_inside(column) {
+                    (chunk, column)
                 } else {
-                    let (dir, neighbor_voxel) = chunk::overlap_voxel(side_voxel);
-                    let neighbor_chunk = chunk.neighbor(dir);
-
-                    // TODO: Change this when if-let chains stabilizes
-                    if let Some(kind) = get_kind(neighbor_chunk) {
-                        if let Some(light) = get_light(neighbor_chunk) {
-                            let intensity = light.get(neighbor_voxel).get_greater_intensity();
-
-                            // Check if returned block is opaque
-                            if intensity == 0 && kind.get(neighbor_voxel).is_opaque() {
-                                None
-                            } else {
-                                Some(intensity)
-                            }
-                        } else {
-                            Some(voxel::Light::MAX_NATURAL_INTENSITY)
+                    let (dir, neighbor_column) = chunk::overlap_voxel(column);
+                    (chunk.neighbor(dir), neighbor_column)
+                };
+
+                let (Some(kind), Some(light)) = (get_kind(owner), get_light(owner)) else {
+                    for y in 0..chunk::Y_AXIS_SIZE as i32 {
+                        if is_solid(Voxel::new(x, y, z)) {
+                            samples[Self::index(x, y, z)] = None;
                         }
-                    } else {
-                        Some(voxel::Light::MAX_NATURAL_INTENSITY)
                     }
+                    continue;
                 };
 
-                neighborhood[i] = intensity;
-                i += 1;
+                for y in 0..chunk::Y_AXIS_SIZE as i32 {
+                    let voxel = Voxel::new(column.x, y, column.z);
+                    samples[Self::index(x, y, z)] = Self::sample(kind, light, voxel);
+                }
             }
         }
+
+        samples
+    }
+
+    fn sample(
+        kind: &ChunkStorage<voxel::Kind>,
+        light: &ChunkStorage<voxel::Light>,
+        voxel: Voxel,
+    ) -> Option<u8> {
+        let intensity = light.get(voxel).get_greater_intensity();
+        if intensity == 0 && kind.get(voxel).blocks_light() {
+            None
+        } else {
+            Some(intensity)
+        }
     }
 
-    neighborhood
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        ((x + 1) as usize * SAMPLES_Z + (z + 1) as usize) * chunk::Y_AXIS_SIZE + y as usize
+    }
+
+    /// **Returns** the sample of the given voxel, which may be up to one voxel away from chunk.
+    fn get(&self, voxel: Voxel) -> Option<u8> {
+        if voxel.y < 0 || voxel.y >= chunk::Y_AXIS_SIZE as i32 {
+            // There is no chunk above or below
+            Some(voxel::Light::MAX_NATURAL_INTENSITY)
+        } else {
+            self.samples[Self::index(voxel.x, voxel.y, voxel.z)]
+        }
+    }
+
+    /// **Returns** the samples around the given voxel, in the order used by
+    /// [`NEIGHBOR_VERTEX_LOOKUP`].
+    fn neighborhood(&self, voxel: Voxel) -> [Option<u8>; NEIGHBOR_COUNT] {
+        let mut neighborhood = [Default::default(); NEIGHBOR_COUNT];
+
+        let mut i = 0;
+        for y in -1..=1 {
+            for z in -1..=1 {
+                for x in -1..=1 {
+                    let dir = IVec3::new(x, y, z);
+
+                    if dir == IVec3::ZERO {
+                        continue;
+                    }
+
+                    neighborhood[i] = self.get(voxel + dir);
+                    i += 1;
+                }
+            }
+        }
+
+        neighborhood
+    }
 }
 
 /// Calculates the ambient occlusion and light smoothness based on [0fps article](https://0fps.net/2013/07/03/ambient-occlusion-for-minecraft-like-worlds/)
@@ -187,23 +339,27 @@ pub fn smooth_lighting<'a>(
     chunk: Chunk,
     occlusion: &ChunkStorage<voxel::FacesOcclusion>,
     soft_light: &mut ChunkStorage<voxel::FacesSoftLight>,
+    samples: &mut LightSamples,
     get_kind: impl GetChunkStorage<'a, voxel::Kind>,
     get_light: impl GetChunkStorage<'a, voxel::Light>,
+    missing: MissingNeighborPolicy,
 ) {
     let kind = get_kind(chunk).expect("Chunk must exists");
     let light = get_light(chunk).expect("Chunk must exists");
+    samples.refresh(chunk, get_kind, get_light, missing);
 
-    chunk::voxels().for_each(|voxel| {
-        if occlusion.get(voxel).is_fully_occluded() {
-            return;
+    for (voxel, voxel_kind, voxel_occlusion, voxel_soft_light) in
+        chunk::zip::zip3_mut(kind, occlusion, soft_light)
+    {
+        if voxel_occlusion.is_fully_occluded() {
+            continue;
         }
 
-        let faces_soft_light = if kind.get(voxel).is_light_emitter() {
+        *voxel_soft_light = if voxel_kind.is_light_emitter() {
             let intensity = light.get(voxel).get_greater_intensity();
             voxel::FacesSoftLight::with_intensity(intensity)
         } else {
-            let voxel_occlusion = occlusion.get(voxel);
-            let neighbors = gather_neighborhood_light(chunk, voxel, get_kind, get_light);
+            let neighbors = samples.neighborhood(voxel);
             let faces_soft_light = voxel::SIDES.map(|side| {
                 if !voxel_occlusion.is_occluded(side) {
                     soft_vertex_light(&neighbors, side)
@@ -214,9 +370,7 @@ pub fn smooth_lighting<'a>(
 
             voxel::FacesSoftLight::new(faces_soft_light)
         };
-
-        soft_light.set(voxel, faces_soft_light);
-    });
+    }
 }
 
 fn calc_propagated_intensity(ty: LightTy, side: voxel::Side, intensity: u8) -> u8 {
@@ -231,6 +385,42 @@ fn calc_propagated_intensity(ty: LightTy, side: voxel::Side, intensity: u8) -> u
     }
 }
 
+/// Computes the light intensity the given voxel receives from its direct neighbors.
+/// Only neighbors inside the same chunk are considered.
+pub fn neighborhood_intensity(light: &ChunkStorage<voxel::Light>, voxel: Voxel, ty: LightTy) -> u8 {
+    voxel::SIDES
+        .iter()
+        .filter_map(|side| {
+            let neighbor = voxel + side.dir();
+            if !chunk::is_inside(neighbor) {
+                return None;
+            }
+
+            let intensity = light.get(neighbor).get(ty);
+            (intensity > 0).then(|| calc_propagated_intensity(ty, side.opposite(), intensity))
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+/// Computes natural light of the given chunk from scratch, by propagating sun light down from top
+/// voxels.
+///
+/// **Returns** light propagated into neighbors, which is discarded by callers which only care
+/// about this chunk.
+pub fn init_natural(
+    kind: &ChunkStorage<voxel::Kind>,
+    light: &mut ChunkStorage<voxel::Light>,
+) -> Vec<NeighborLightPropagation> {
+    chunk::top_voxels()
+        .filter(|&voxel| !kind.get(voxel).blocks_light())
+        .for_each(|voxel| {
+            light.set_type(voxel, LightTy::Natural, voxel::Light::MAX_NATURAL_INTENSITY);
+        });
+
+    propagate(kind, light, LightTy::Natural, chunk::top_voxels())
+}
+
 pub struct NeighborLightPropagation {
     pub side: ChunkSide,
     pub voxel: Voxel,
@@ -238,24 +428,142 @@ pub struct NeighborLightPropagation {
     pub intensity: u8,
 }
 
+/// Computes natural light of the given chunk from scratch, like [`init_natural`], but sun light
+/// comes slanted instead of straight down, moving `slant` voxels along X axis for each voxel it
+/// goes down. Voxels outside of the chunk are assumed to not block sun light.
+///
+/// **Returns** light propagated into neighbors, which is discarded by callers which only care
+/// about this chunk.
+pub fn init_natural_slanted(
+    kind: &ChunkStorage<voxel::Kind>,
+    light: &mut ChunkStorage<voxel::Light>,
+    slant: f32,
+) -> Vec<NeighborLightPropagation> {
+    let index = |x: i32, z: i32| x as usize * chunk::Z_AXIS_SIZE + z as usize;
+    let offset = |depth: i32| (slant * depth as f32).round() as i32;
+
+    // Which voxels of the layer above are reached by sun light.
+    let mut lit_above = vec![true; chunk::X_AXIS_SIZE * chunk::Z_AXIS_SIZE];
+    let mut sun_voxels = vec![];
+
+    for y in (0..=chunk::Y_END).rev() {
+        let depth = chunk::Y_END - y;
+        let shift = offset(depth) - offset(depth - 1);
+        let mut lit = vec![false; lit_above.len()];
+
+        for x in 0..=chunk::X_END {
+            for z in 0..=chunk::Z_END {
+                let voxel = Voxel::new(x, y, z);
+                if kind.get(voxel).blocks_light() {
+                    continue;
+                }
+
+                let source_x = x - shift;
+                if (0..=chunk::X_END).contains(&source_x) && !lit_above[index(source_x, z)] {
+                    continue;
+                }
+
+                lit[index(x, z)] = true;
+                light.set_type(voxel, LightTy::Natural, voxel::Light::MAX_NATURAL_INTENSITY);
+                sun_voxels.push(voxel);
+            }
+        }
+
+        lit_above = lit;
+    }
+
+    // Sun light was already spread down by slanted rays, so it always loses intensity from there.
+    propagate_with(
+        kind,
+        light,
+        LightTy::Natural,
+        sun_voxels.into_iter(),
+        false,
+        None,
+    )
+}
+
 pub fn propagate(
     kind: &ChunkStorage<voxel::Kind>,
     light: &mut ChunkStorage<voxel::Light>,
     light_ty: LightTy,
     voxels: impl Iterator<Item = Voxel>,
+) -> Vec<NeighborLightPropagation> {
+    propagate_with(kind, light, light_ty, voxels, true, None)
+}
+
+/// Same as [`propagate`], but voxels which light was changed are added to `changed`, so anything
+/// derived from them, like [`LightSamples`], can be invalidated.
+pub fn propagate_tracked(
+    kind: &ChunkStorage<voxel::Kind>,
+    light: &mut ChunkStorage<voxel::Light>,
+    light_ty: LightTy,
+    voxels: impl Iterator<Item = Voxel>,
+    changed: &mut Vec<Voxel>,
+) -> Vec<NeighborLightPropagation> {
+    propagate_with(kind, light, light_ty, voxels, true, Some(changed))
+}
+
+/// Computes light voxels on the given border propagate into the neighbor on that side, so a
+/// neighbor linked after this chunk was lit receives it. Nothing is changed on this chunk.
+///
+/// **Returns** light propagated into the neighbor, like [`propagate`] does.
+pub fn propagate_border(
+    kind: &ChunkStorage<voxel::Kind>,
+    light: &ChunkStorage<voxel::Light>,
+    side: ChunkSide,
+) -> Vec<NeighborLightPropagation> {
+    let dir = IVec3::new(side.dir().x, 0, side.dir().y);
+
+    chunk::voxels()
+        .filter(|&voxel| !chunk::is_inside(voxel + dir) && !kind.get(voxel).blocks_light())
+        .flat_map(|voxel| {
+            let (_, neighbor_voxel) = chunk::overlap_voxel(voxel + dir);
+            let light = light.get(voxel);
+
+            [LightTy::Natural, LightTy::Artificial]
+                .into_iter()
+                .filter(move |&ty| light.get(ty) > 1)
+                .map(move |ty| NeighborLightPropagation {
+                    side,
+                    voxel: neighbor_voxel,
+                    ty,
+                    intensity: light.get(ty) - 1,
+                })
+        })
+        .collect()
+}
+
+/// Same as [`propagate`], but max natural light only propagates down without losing intensity if
+/// `vertical_sun` is set.
+fn propagate_with(
+    kind: &ChunkStorage<voxel::Kind>,
+    light: &mut ChunkStorage<voxel::Light>,
+    light_ty: LightTy,
+    voxels: impl Iterator<Item = Voxel>,
+    vertical_sun: bool,
+    mut changed: Option<&mut Vec<Voxel>>,
 ) -> Vec<NeighborLightPropagation> {
     let mut queue = voxels.collect::<VecDeque<_>>();
     let mut neighbor_light_propagation = vec![];
 
     while let Some(voxel) = queue.pop_front() {
-        if kind.get(voxel).is_opaque() {
+        if kind.get(voxel).blocks_light() {
             continue;
         }
 
+        // Voxels may have been removed by `remove` since they were queued.
         let current_intensity = light.get(voxel).get(light_ty);
+        if current_intensity == 0 {
+            continue;
+        }
 
         for side in voxel::SIDES {
-            let propagated_intensity = calc_propagated_intensity(light_ty, side, current_intensity);
+            let propagated_intensity = if vertical_sun {
+                calc_propagated_intensity(light_ty, side, current_intensity)
+            } else {
+                current_intensity - 1
+            };
 
             if propagated_intensity == 0 {
                 continue;
@@ -285,7 +593,7 @@ pub fn propagate(
             }
 
             let side_kind = kind.get(side_voxel);
-            if side_kind.is_opaque() {
+            if side_kind.blocks_light() {
                 continue;
             }
 
@@ -295,6 +603,9 @@ pub fn propagate(
             }
 
             light.set_type(side_voxel, light_ty, propagated_intensity);
+            if let Some(changed) = changed.as_deref_mut() {
+                changed.push(side_voxel);
+            }
 
             if propagated_intensity > 1 {
                 queue.push_back(side_voxel);
@@ -305,9 +616,175 @@ pub fn propagate(
     neighbor_light_propagation
 }
 
+/// **Returns** the intensity the given voxel emits by itself, which is sun light on top voxels or
+/// the emission of emitters.
+fn source_intensity(kind: voxel::Kind, voxel: Voxel, ty: LightTy) -> u8 {
+    match ty {
+        LightTy::Natural if voxel.y == chunk::Y_END && !kind.blocks_light() => {
+            voxel::Light::MAX_NATURAL_INTENSITY
+        }
+        LightTy::Natural => 0,
+        LightTy::Artificial => kind.light_emission(),
+    }
+}
+
+/// How light of a voxel must be updated after its kind is changed. See [`edit`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LightEdit {
+    /// Light on voxel must be removed, along with light propagated from it, before any
+    /// propagation. See [`remove`].
+    pub remove: bool,
+    /// Intensity to be propagated into voxel.
+    pub intensity: u8,
+}
+
+/// **Returns** how light of the given type must be updated when the given voxel kind is changed
+/// from `before` to `after`. `light` must be the chunk light before the change.
+pub fn edit(
+    light: &ChunkStorage<voxel::Light>,
+    voxel: Voxel,
+    ty: LightTy,
+    before: voxel::Kind,
+    after: voxel::Kind,
+) -> LightEdit {
+    let current = light.get(voxel).get(ty);
+
+    if after.blocks_light() {
+        return LightEdit {
+            remove: current > 0,
+            intensity: 0,
+        };
+    }
+
+    let source = source_intensity(after, voxel, ty);
+
+    // Neighbors may still be lit by removed emitter, so they are only propagated back by removal.
+    if ty == LightTy::Artificial && before.is_light_emitter() {
+        return LightEdit {
+            remove: current > 0,
+            intensity: source,
+        };
+    }
+
+    LightEdit {
+        remove: false,
+        intensity: neighborhood_intensity(light, voxel, ty).max(source),
+    }
+}
+
+/// Light removed by [`remove`].
+#[derive(Default)]
+pub struct RemovedLight {
+    /// Voxels still lit by other sources, which must be propagated again to refill removed ones.
+    pub refill: Vec<Voxel>,
+    /// Light removed from neighbor chunks voxels, which must be removed there too.
+    pub neighbors: Vec<NeighborLightPropagation>,
+    /// Voxels which light was changed, either removed or lit again.
+    pub changed: Vec<Voxel>,
+}
+
+/// Removes light of the given voxels and all light propagated from them, using a breadth first
+/// search, like [`propagate`] does.
+///
+/// Each voxel is paired with the intensity being removed from it, so voxels lit by a stronger
+/// source are kept and refilled instead. Neighbors dimmer than removed light were lit by it and
+/// are removed too, while brighter ones are lit by other sources and are refilled. Light sources
+/// inside removed area, like emitters and top voxels lit by sun, are lit again.
+pub fn remove(
+    kind: &ChunkStorage<voxel::Kind>,
+    light: &mut ChunkStorage<voxel::Light>,
+    light_ty: LightTy,
+    voxels: impl Iterator<Item = (Voxel, u8)>,
+) -> RemovedLight {
+    let mut removal = RemovedLight::default();
+    let mut queue = VecDeque::new();
+
+    for (voxel, intensity) in voxels {
+        let current = light.get(voxel).get(light_ty);
+
+        if current > intensity {
+            removal.refill.push(voxel);
+        } else {
+            light.set_type(voxel, light_ty, 0);
+            removal.changed.push(voxel);
+            queue.push_back((voxel, current));
+        }
+    }
+
+    let mut sources = vec![];
+
+    while let Some((voxel, intensity)) = queue.pop_front() {
+        if source_intensity(kind.get(voxel), voxel, light_ty) > 0 {
+            sources.push(voxel);
+        }
+
+        for side in voxel::SIDES {
+            // Voxels without light, like edited ones, can't have lit anyone, so only refill.
+            let propagated_intensity = if intensity > 0 {
+                calc_propagated_intensity(light_ty, side, intensity)
+            } else {
+                0
+            };
+
+            let side_voxel = voxel + side.dir();
+            if !chunk::is_inside(side_voxel) {
+                if let Some(chunk_side) = ChunkSide::from_voxel_side(side) {
+                    if propagated_intensity > 0 {
+                        removal.neighbors.push(NeighborLightPropagation {
+                            side: chunk_side,
+                            voxel: math::euclid_rem(
+                                side_voxel,
+                                IVec3::new(
+                                    chunk::X_AXIS_SIZE as i32,
+                                    chunk::Y_AXIS_SIZE as i32,
+                                    chunk::Z_AXIS_SIZE as i32,
+                                ),
+                            ),
+                            ty: light_ty,
+                            intensity: propagated_intensity,
+                        });
+                    }
+                }
+
+                continue;
+            }
+
+            let side_intensity = light.get(side_voxel).get(light_ty);
+            if side_intensity == 0 {
+                continue;
+            }
+
+            if side_intensity > propagated_intensity {
+                removal.refill.push(side_voxel);
+            } else {
+                light.set_type(side_voxel, light_ty, 0);
+                removal.changed.push(side_voxel);
+                queue.push_back((side_voxel, side_intensity));
+            }
+        }
+    }
+
+    for voxel in sources {
+        light.set_type(
+            voxel,
+            light_ty,
+            source_intensity(kind.get(voxel), voxel, light_ty),
+        );
+        removal.refill.push(voxel);
+        removal.changed.push(voxel);
+    }
+
+    // Voxels may be removed after being added to refill, when reached by a brighter removal.
+    removal
+        .refill
+        .retain(|&voxel| light.get(voxel).get(light_ty) > 0);
+
+    removal
+}
+
 #[cfg(test)]
 mod test {
-    use bevy::utils::HashMap;
+    use bevy::{math::IVec2, utils::HashMap};
 
     use super::*;
 
@@ -471,6 +948,39 @@ mod test {
             });
     }
 
+    #[test]
+    fn propagate_border_to_neighbor() {
+        // arrange
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut light = ChunkStorage::<voxel::Light>::default();
+
+        (0..=chunk::Z_END)
+            .flat_map(|z| (0..=chunk::Y_END).map(move |y| IVec3::new(0, y, z)))
+            .for_each(|v| kind.set(v, 1.into()));
+        init_natural(&kind, &mut light);
+
+        // act
+        let left = propagate_border(&kind, &light, ChunkSide::Left);
+        let right = propagate_border(&kind, &light, ChunkSide::Right);
+
+        // assert
+        assert!(left.is_empty(), "Wall should block light to left");
+        assert_eq!(right.len(), chunk::Y_AXIS_SIZE * chunk::Z_AXIS_SIZE);
+        right.into_iter().for_each(
+            |NeighborLightPropagation {
+                 side,
+                 voxel,
+                 ty,
+                 intensity,
+             }| {
+                assert_eq!(side, ChunkSide::Right);
+                assert_eq!(voxel.x, 0, "Voxel should be on neighbor left border");
+                assert_eq!(ty, LightTy::Natural);
+                assert_eq!(intensity, voxel::Light::MAX_NATURAL_INTENSITY - 1);
+            },
+        );
+    }
+
     #[test]
     fn gather_neighborhood_light() {
         let chunk = Chunk::default();
@@ -491,7 +1001,9 @@ mod test {
         let get_kind = |_| -> _ { Some(&kind) };
         let get_light = |_| -> _ { Some(&light) };
 
-        let neighbors = super::gather_neighborhood_light(chunk, voxel, get_kind, get_light);
+        let mut samples = LightSamples::default();
+        samples.refresh(chunk, get_kind, get_light, MissingNeighborPolicy::Open);
+        let neighbors = samples.neighborhood(voxel);
 
         let mut i = 0;
         for y in -1..=1 {
@@ -514,6 +1026,192 @@ mod test {
         }
     }
 
+    #[test]
+    fn light_samples_neighbor_chunks() {
+        let chunk = Chunk::default();
+        let left = chunk.neighbor(IVec2::NEG_X);
+        let kind = ChunkStorage::<voxel::Kind>::default();
+        let mut solid = ChunkStorage::<voxel::Kind>::default();
+        solid.set(Voxel::new(chunk::X_END, 5, 3), 1.into());
+
+        let mut light = ChunkStorage::<voxel::Light>::default();
+        light.set(Voxel::new(chunk::X_END, 6, 3), voxel::Light::natural(7));
+
+        let mut samples = LightSamples::default();
+        samples.refresh(
+            chunk,
+            |c| {
+                (c == chunk)
+                    .then_some(&kind)
+                    .or((c == left).then_some(&solid))
+            },
+            |c| (c == chunk || c == left).then_some(&light),
+            MissingNeighborPolicy::Open,
+        );
+
+        assert_eq!(samples.get(Voxel::new(-1, 6, 3)), Some(7));
+        assert_eq!(
+            samples.get(Voxel::new(-1, 5, 3)),
+            None,
+            "Solid voxel blocks light"
+        );
+        assert_eq!(samples.get(Voxel::new(0, 5, 3)), Some(0));
+        assert_eq!(
+            samples.get(Voxel::new(chunk::X_AXIS_SIZE as i32, 5, 3)),
+            Some(voxel::Light::MAX_NATURAL_INTENSITY),
+            "Missing neighbor is fully lit"
+        );
+        assert_eq!(
+            samples.get(Voxel::new(0, -1, 0)),
+            Some(voxel::Light::MAX_NATURAL_INTENSITY),
+            "There is no chunk below"
+        );
+    }
+
+    #[test]
+    fn light_samples_missing_heightmap() {
+        let chunk = Chunk::default();
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        for y in 0..4 {
+            kind.set(Voxel::new(0, y, 3), 1.into());
+        }
+        let light = ChunkStorage::<voxel::Light>::default();
+
+        let mut samples = LightSamples::default();
+        samples.refresh(
+            chunk,
+            |c| (c == chunk).then_some(&kind),
+            |c| (c == chunk).then_some(&light),
+            MissingNeighborPolicy::Heightmap,
+        );
+
+        assert_eq!(
+            samples.get(Voxel::new(-1, 2, 3)),
+            None,
+            "Below border height is dark"
+        );
+        assert_eq!(
+            samples.get(Voxel::new(-1, 3, 3)),
+            Some(voxel::Light::MAX_NATURAL_INTENSITY),
+            "Above border height is lit by sky"
+        );
+        assert_eq!(
+            samples.get(Voxel::new(-1, 2, 4)),
+            Some(voxel::Light::MAX_NATURAL_INTENSITY),
+            "Empty border column has no height"
+        );
+    }
+
+    #[test]
+    fn light_samples_invalidate() {
+        // arrange
+        let chunk = Chunk::default();
+        let left = chunk.neighbor(IVec2::NEG_X);
+        let kind = ChunkStorage::<voxel::Kind>::default();
+        let mut light = ChunkStorage::<voxel::Light>::default();
+        let mut left_light = ChunkStorage::<voxel::Light>::default();
+
+        let mut samples = LightSamples::default();
+        samples.refresh(
+            chunk,
+            |c| (c == chunk || c == left).then_some(&kind),
+            |c| {
+                (c == chunk)
+                    .then_some(&light)
+                    .or((c == left).then_some(&left_light))
+            },
+            MissingNeighborPolicy::Open,
+        );
+
+        let (own, stale) = (Voxel::new(3, 4, 5), Voxel::new(6, 7, 8));
+        let border = Voxel::new(chunk::X_END, 4, 5);
+        light.set(own, voxel::Light::natural(9));
+        light.set(stale, voxel::Light::natural(9));
+        left_light.set(border, voxel::Light::natural(3));
+
+        // act
+        samples.invalidate(IVec2::ZERO, [own]);
+        samples.invalidate(IVec2::NEG_X, [border, Voxel::new(0, 4, 5)]);
+        samples.refresh(
+            chunk,
+            |c| (c == chunk || c == left).then_some(&kind),
+            |c| {
+                (c == chunk)
+                    .then_some(&light)
+                    .or((c == left).then_some(&left_light))
+            },
+            MissingNeighborPolicy::Open,
+        );
+
+        // assert
+        assert_eq!(samples.get(own), Some(9));
+        assert_eq!(samples.get(Voxel::new(-1, 4, 5)), Some(3));
+        assert_eq!(
+            samples.get(stale),
+            Some(0),
+            "Samples which weren't invalidated are reused"
+        );
+
+        // act
+        samples.invalidate_border(IVec2::ZERO);
+        samples.refresh(
+            chunk,
+            |c| (c == chunk || c == left).then_some(&kind),
+            |c| {
+                (c == chunk)
+                    .then_some(&light)
+                    .or((c == left).then_some(&left_light))
+            },
+            MissingNeighborPolicy::Open,
+        );
+
+        // assert
+        assert_eq!(samples.get(stale), Some(9));
+    }
+
+    #[test]
+    fn init_natural_slanted_without_slant() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set(Voxel::new(4, 100, 8), 1.into());
+        kind.set(Voxel::new(5, 90, 8), 1.into());
+        chunk::voxels()
+            .filter(|voxel| voxel.y < 10)
+            .for_each(|voxel| kind.set(voxel, 1.into()));
+
+        let mut vertical = ChunkStorage::default();
+        let _ = init_natural(&kind, &mut vertical);
+
+        let mut slanted = ChunkStorage::default();
+        let _ = init_natural_slanted(&kind, &mut slanted, 0.0);
+
+        assert_eq!(slanted, vertical);
+    }
+
+    #[test]
+    fn init_natural_slanted_shadow() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set(Voxel::new(4, 100, 8), 1.into());
+
+        let mut light = ChunkStorage::default();
+        let _ = init_natural_slanted(&kind, &mut light, 1.0);
+
+        assert_eq!(
+            light.get(Voxel::new(4, 99, 8)).get(LightTy::Natural),
+            voxel::Light::MAX_NATURAL_INTENSITY,
+            "Voxel right below block is reached by slanted sun light"
+        );
+        assert_eq!(
+            light.get(Voxel::new(5, 99, 8)).get(LightTy::Natural),
+            voxel::Light::MAX_NATURAL_INTENSITY - 1,
+            "Shadow is cast along slant"
+        );
+        assert_eq!(
+            light.get(Voxel::new(6, 98, 8)).get(LightTy::Natural),
+            voxel::Light::MAX_NATURAL_INTENSITY - 1,
+            "Shadow is cast along slant"
+        );
+    }
+
     #[test]
     fn neighbor_lookup_table() {
         let mut count = vec![0; NEIGHBOR_COUNT];
@@ -534,4 +1232,36 @@ mod test {
             assert_eq!(cnt, expected, "In Lookup each neighbor should appears 4 times, except corners, which should appears 3 times.");
         }
     }
+
+    #[test]
+    fn neighborhood_intensity() {
+        let mut light = ChunkStorage::<voxel::Light>::default();
+        let voxel = Voxel::new(1, 10, 1);
+
+        assert_eq!(
+            super::neighborhood_intensity(&light, voxel, LightTy::Natural),
+            0
+        );
+
+        light.set_type(voxel + IVec3::X, LightTy::Artificial, 10);
+        light.set_type(voxel - IVec3::X, LightTy::Artificial, 7);
+
+        assert_eq!(
+            super::neighborhood_intensity(&light, voxel, LightTy::Artificial),
+            9,
+            "Should use the brightest neighbor"
+        );
+
+        light.set_type(
+            voxel + IVec3::Y,
+            LightTy::Natural,
+            voxel::Light::MAX_NATURAL_INTENSITY,
+        );
+
+        assert_eq!(
+            super::neighborhood_intensity(&light, voxel, LightTy::Natural),
+            voxel::Light::MAX_NATURAL_INTENSITY,
+            "Max natural light should propagate down without losing intensity"
+        );
+    }
 }
@@ -0,0 +1,322 @@
+//! Dedicated worker threads which mesh chunks apart from server schedule, so a burst of dirty
+//! chunks never stretches a tick. Jobs are picked closer to players first and results are sent
+//! back through a channel, to be collected on [`crate::WorldSet::CollectAsync`].
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Condvar, Mutex},
+    time::Instant,
+};
+
+use async_channel::{Receiver, Sender};
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::{
+    chunk::{Chunk, ChunkStorage},
+    voxel,
+};
+
+use crate::{
+    error::{self, MeshError},
+    meshing,
+};
+
+/// Max number of meshing workers. Remaining cores are left to server schedule and async tasks.
+const MAX_WORKERS: usize = 4;
+
+/// Everything needed to mesh a chunk, copied from its components, since workers outlive systems.
+#[derive(Debug)]
+pub(crate) struct MeshingJob {
+    pub chunk: Chunk,
+    /// Identifies this job, so results of outdated jobs can be discarded.
+    pub id: u64,
+    /// Jobs with lower priority values are picked first, like distance to closest player.
+    pub priority: u32,
+    /// Neighborhood hash which vertices are generated from. See [`meshing::neighborhood_hash`].
+    pub hash: u64,
+    pub kind: ChunkStorage<voxel::Kind>,
+    pub occlusion: ChunkStorage<voxel::FacesOcclusion>,
+    pub soft_light: ChunkStorage<voxel::FacesSoftLight>,
+}
+
+#[derive(Debug)]
+pub(crate) struct MeshingResult {
+    pub chunk: Chunk,
+    pub id: u64,
+    pub hash: u64,
+    pub vertex: Vec<voxel::Vertex>,
+    pub liquid_vertex: Vec<voxel::Vertex>,
+    pub micros: u64,
+}
+
+/// A job which panicked while meshing, so its chunk can be quarantined, instead of taking down
+/// the worker.
+#[derive(Debug)]
+pub(crate) struct MeshingFailure {
+    pub chunk: Chunk,
+    pub id: u64,
+    pub error: MeshError,
+}
+
+#[derive(Default)]
+struct JobQueue {
+    /// Priority, id and chunk coordinates of each queued job, lower priority values first.
+    order: BinaryHeap<Reverse<(u32, u64, i32, i32)>>,
+    /// Only the latest job of each chunk is kept, so outdated ones are never meshed.
+    jobs: HashMap<Chunk, MeshingJob>,
+    closed: bool,
+}
+
+impl JobQueue {
+    fn push(&mut self, job: MeshingJob) {
+        let chunk = job.chunk;
+        self.order
+            .push(Reverse((job.priority, job.id, chunk.x(), chunk.z())));
+        self.jobs.insert(chunk, job);
+    }
+
+    fn pop(&mut self) -> Option<MeshingJob> {
+        while let Some(Reverse((_, id, x, z))) = self.order.pop() {
+            let chunk = Chunk::new(x, z);
+            if self.jobs.get(&chunk).is_some_and(|job| job.id == id) {
+                return self.jobs.remove(&chunk);
+            }
+        }
+
+        None
+    }
+}
+
+/// Worker pool which meshes chunks on dedicated threads. Workers are stopped when the pool is
+/// dropped.
+#[derive(Resource)]
+pub(crate) struct MeshingPool {
+    queue: Arc<(Mutex<JobQueue>, Condvar)>,
+    results: Receiver<Result<MeshingResult, MeshingFailure>>,
+    next_id: u64,
+}
+
+impl MeshingPool {
+    pub fn start() -> Self {
+        let workers = std::thread::available_parallelism()
+            .map_or(1, |n| n.get() / 2)
+            .clamp(1, MAX_WORKERS);
+
+        let queue = Arc::new((Mutex::new(JobQueue::default()), Condvar::new()));
+        let (sender, results) = async_channel::unbounded();
+
+        for i in 0..workers {
+            let queue = queue.clone();
+            let sender = sender.clone();
+
+            let _ = std::thread::Builder::new()
+                .name(format!("Meshing {i}"))
+                .spawn(move || {
+                    trace!("Starting meshing worker {i}");
+                    run_worker(&queue, &sender);
+                    trace!("Stopping meshing worker {i}");
+                });
+        }
+
+        Self {
+            queue,
+            results,
+            next_id: 0,
+        }
+    }
+
+    /// **Returns** a new job id, which must be used by the next job sent.
+    pub fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Queues the given job, replacing any job of the same chunk which wasn't picked yet.
+    pub fn send(&self, job: MeshingJob) {
+        let (queue, cvar) = &*self.queue;
+        queue
+            .lock()
+            .expect("Meshing queue to not be poisoned")
+            .push(job);
+        cvar.notify_one();
+    }
+
+    /// **Returns** the next finished or failed job, if any.
+    pub fn try_recv(&self) -> Option<Result<MeshingResult, MeshingFailure>> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl Drop for MeshingPool {
+    fn drop(&mut self) {
+        let (queue, cvar) = &*self.queue;
+        if let Ok(mut queue) = queue.lock() {
+            queue.closed = true;
+        }
+        cvar.notify_all();
+    }
+}
+
+fn run_worker(
+    queue: &(Mutex<JobQueue>, Condvar),
+    sender: &Sender<Result<MeshingResult, MeshingFailure>>,
+) {
+    let (queue, cvar) = queue;
+
+    loop {
+        let job = {
+            let mut queue = queue.lock().expect("Meshing queue to not be poisoned");
+            loop {
+                if queue.closed {
+                    return;
+                }
+
+                if let Some(job) = queue.pop() {
+                    break job;
+                }
+
+                queue = cvar.wait(queue).expect("Meshing queue to not be poisoned");
+            }
+        };
+
+        let (chunk, id) = (job.chunk, job.id);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| mesh(job))).map_err(|payload| {
+            MeshingFailure {
+                chunk,
+                id,
+                error: MeshError::Panicked(error::panic_message(payload)),
+            }
+        });
+
+        if sender.send_blocking(result).is_err() {
+            return;
+        }
+    }
+}
+
+fn mesh(job: MeshingJob) -> MeshingResult {
+    let MeshingJob {
+        chunk,
+        id,
+        hash,
+        kind,
+        occlusion,
+        soft_light,
+        ..
+    } = job;
+
+    #[cfg(feature = "trace")]
+    let _span = info_span!("mesh", chunk = %chunk).entered();
+
+    let start = Instant::now();
+
+    // let faces = meshing::faces_merge(kind, faces_occlusion, faces_soft_light);
+    let faces = meshing::generate_faces(&kind, &occlusion, &soft_light);
+    // Chunks may have only liquid faces.
+    let vertex = if faces.is_empty() {
+        vec![]
+    } else {
+        meshing::generate_vertices(faces)
+    };
+    let liquid_vertex = meshing::generate_liquid_vertices(&kind, &occlusion, &soft_light);
+
+    MeshingResult {
+        chunk,
+        id,
+        hash,
+        vertex,
+        liquid_vertex,
+        micros: start.elapsed().as_micros() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use projekto_core::chunk;
+
+    use super::*;
+
+    fn job(id: u64, chunk: Chunk, priority: u32) -> MeshingJob {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set([0, 0, 0].into(), 3.into());
+
+        let mut occlusion = ChunkStorage::default();
+        meshing::faces_occlusion(
+            &kind,
+            &mut occlusion,
+            &[None; chunk::SIDE_COUNT],
+            Default::default(),
+        );
+
+        MeshingJob {
+            chunk,
+            id,
+            priority,
+            hash: 0,
+            kind,
+            occlusion,
+            soft_light: Default::default(),
+        }
+    }
+
+    fn recv(pool: &MeshingPool) -> Result<MeshingResult, MeshingFailure> {
+        for _ in 0..1000 {
+            if let Some(result) = pool.try_recv() {
+                return result;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        panic!("Timeout while waiting for meshing result");
+    }
+
+    #[test]
+    fn queue_picks_lower_priority_and_latest_job() {
+        let (a, b) = (Chunk::new(0, 0), Chunk::new(1, 0));
+
+        let mut queue = JobQueue::default();
+        queue.push(job(1, a, 5));
+        queue.push(job(2, b, 1));
+        queue.push(job(3, a, 2));
+
+        assert_eq!(queue.pop().map(|job| job.chunk), Some(b));
+        assert_eq!(queue.pop().map(|job| (job.chunk, job.id)), Some((a, 3)));
+        assert!(queue.pop().is_none(), "Outdated job should be skipped");
+    }
+
+    #[test]
+    fn pool_meshes_jobs() {
+        let mut pool = MeshingPool::start();
+        let chunk = Chunk::new(2, 3);
+
+        let id = pool.next_id();
+        pool.send(job(id, chunk, 0));
+
+        let result = recv(&pool).expect("Meshing should not fail");
+        assert_eq!((result.chunk, result.id), (chunk, id));
+        assert_eq!(result.vertex.len(), 24, "Single voxel should have 6 faces");
+        assert!(result.liquid_vertex.is_empty());
+    }
+
+    #[test]
+    fn pool_survives_failed_job() {
+        let mut pool = MeshingPool::start();
+        let (bad, good) = (Chunk::new(0, 0), Chunk::new(1, 0));
+
+        let mut bad_job = job(pool.next_id(), bad, 0);
+        bad_job.kind.set([0, 0, 0].into(), u16::MAX.into());
+        pool.send(bad_job);
+
+        let failure = recv(&pool).expect_err("Unknown kind should fail meshing");
+        assert_eq!(failure.chunk, bad);
+        assert!(matches!(failure.error, MeshError::Panicked(_)));
+
+        let id = pool.next_id();
+        pool.send(job(id, good, 0));
+        let result = recv(&pool).expect("Workers should keep meshing");
+        assert_eq!(result.chunk, good);
+    }
+}
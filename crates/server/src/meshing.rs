@@ -1,10 +1,17 @@
-use bevy::math::{IVec3, Vec3};
+use bevy::{
+    ecs::system::Resource,
+    math::{IVec2, IVec3, Vec2, Vec3},
+};
 use projekto_core::{
-    chunk::{self, ChunkSide, ChunkStorage},
+    chunk::{self, Chunk, ChunkSide, ChunkStorage, ColumnSummary},
     math,
-    voxel::{self, FacesOcclusion},
+    voxel::{self, FacesOcclusion, Voxel},
 };
 
+/// Version of meshing algorithm. Bump it whenever generated vertices change, so persisted vertices
+/// are generated again.
+pub const MESHING_VERSION: u16 = 1;
+
 // v3               v2
 // +-----------+
 // v7  / |      v6 / |
@@ -41,29 +48,37 @@ pub const VERTICES_INDICES: [[usize; 4]; 6] = [
     [1, 0, 3, 2], // BACK
 ];
 
+/// **Returns** a hash of content of the given chunk and its surrounding chunks, including diagonal
+/// ones, since vertices of a chunk depends on all of them. Missing chunks are hashed as zero.
+pub fn neighborhood_hash(chunk: Chunk, content_hash: impl Fn(Chunk) -> Option<u64>) -> u64 {
+    let hashes = (-1..=1)
+        .flat_map(|z| (-1..=1).map(move |x| IVec2::new(x, z)))
+        .map(|dir| content_hash(chunk.neighbor(dir)).unwrap_or_default());
+
+    chunk::combined_hash(hashes)
+}
+
 /// Generates vertices data from a given [`voxel::Face`] list.
 ///
 /// All generated indices will be relative to a triangle list.
 ///
 /// **Returns** a list of generated [`voxel::Vertex`].
-pub(super) fn generate_vertices(faces: Vec<voxel::Face>) -> Vec<voxel::Vertex> {
+pub fn generate_vertices(faces: Vec<voxel::Face>) -> Vec<voxel::Vertex> {
     let mut vertices = vec![];
     let kinds_descs = voxel::KindsDescs::get();
     let tile_texture_size = (kinds_descs.count_tiles() as f32).recip();
 
     for face in faces {
-        let normal = face.side.normal();
-
-        let face_desc = kinds_descs.get_face_desc(&face);
-        let tile_coord_start = face_desc.offset.as_vec2() * tile_texture_size;
-
+        // Layers, like snow, are lowered to their height, so side faces are shortened too.
+        let height = face.kind.shape_height();
         let faces_vertices = face
             .vertices
             .iter()
             .enumerate()
             .map(|(i, v)| {
                 let base_vertex_idx = VERTICES_INDICES[face.side as usize][i];
-                let base_vertex: Vec3 = VERTICES[base_vertex_idx].into();
+                let mut base_vertex: Vec3 = VERTICES[base_vertex_idx].into();
+                base_vertex.y *= height;
 
                 base_vertex + v.as_vec3()
             })
@@ -74,102 +89,330 @@ pub(super) fn generate_vertices(faces: Vec<voxel::Face>) -> Vec<voxel::Vertex> {
             "Each face should have 4 vertices"
         );
 
-        fn calc_tile_size(min: Vec3, max: Vec3) -> f32 {
-            (min.x - max.x).abs() + (min.y - max.y).abs() + (min.z - max.z).abs()
+        push_face_vertices(
+            &mut vertices,
+            &face,
+            &faces_vertices,
+            kinds_descs,
+            tile_texture_size,
+        );
+    }
+
+    debug_assert!(!vertices.is_empty());
+    vertices
+}
+
+/// Pushes the vertices of the given face, placed at the given positions, computing its UVs from
+/// positions, so texture tiles are repeated instead of stretched.
+///
+/// UVs are continuous across coplanar faces, since the shader wraps them inside the tile. This way
+/// neighbor faces with the same kind and light share the same vertices, which are welded later on.
+fn push_face_vertices(
+    vertices: &mut Vec<voxel::Vertex>,
+    face: &voxel::Face,
+    positions: &[Vec3],
+    kinds_descs: &voxel::KindsDescs,
+    tile_texture_size: f32,
+) {
+    let normal = face.side.normal();
+
+    let face_desc = kinds_descs.get_face_desc(face);
+    let tile_coord_start = face_desc.offset.as_vec2() * tile_texture_size;
+
+    let u_axis = (positions[1] - positions[0]).normalize_or_zero();
+    let v_axis = (positions[3] - positions[0]).normalize_or_zero();
+    // Keeps UVs positive, since the shader wraps them using remainder, which keeps the sign.
+    let origin = chunk::Y_AXIS_SIZE as f32;
+
+    let light_fraction = (voxel::Light::MAX_NATURAL_INTENSITY as f32).recip();
+
+    for (i, &v) in positions.iter().enumerate() {
+        vertices.push(voxel::Vertex {
+            position: v,
+            normal,
+            uv: Vec2::new(v.dot(u_axis) + origin, origin - v.dot(v_axis)) * tile_texture_size,
+            tile_coord_start,
+            light: Vec3::splat(face.light[i] * light_fraction),
+        });
+    }
+}
+
+/// Generates vertices of liquid voxels, which are drawn as a separate translucent mesh, alongside
+/// the one generated by [`generate_vertices`].
+///
+/// Liquid surface is lowered by its level, so side faces are shortened too. Faces between the
+/// same liquid are hidden by [`faces_occlusion`], so only liquid bodies boundaries are drawn.
+pub fn generate_liquid_vertices(
+    kind: &ChunkStorage<voxel::Kind>,
+    occlusion: &ChunkStorage<voxel::FacesOcclusion>,
+    soft_light: &ChunkStorage<voxel::FacesSoftLight>,
+) -> Vec<voxel::Vertex> {
+    let mut vertices = vec![];
+    let kinds_descs = voxel::KindsDescs::get();
+    let tile_texture_size = (kinds_descs.count_tiles() as f32).recip();
+
+    for (voxel, &voxel_kind, occlusion, soft_light) in chunk::zip::zip3(kind, occlusion, soft_light)
+    {
+        if !voxel_kind.is_liquid() {
+            continue;
         }
 
-        let x_tile = calc_tile_size(faces_vertices[0], faces_vertices[1]) * tile_texture_size;
-        let y_tile = calc_tile_size(faces_vertices[0], faces_vertices[3]) * tile_texture_size;
-
-        let tile_uv = [
-            (0.0, y_tile).into(),
-            (x_tile, y_tile).into(),
-            (x_tile, 0.0).into(),
-            (0.0, 0.0).into(),
-        ];
-
-        let light_fraction = (voxel::Light::MAX_NATURAL_INTENSITY as f32).recip();
-
-        for (i, v) in faces_vertices.into_iter().enumerate() {
-            vertices.push(voxel::Vertex {
-                position: v,
-                normal,
-                uv: tile_uv[i],
-                tile_coord_start,
-                light: Vec3::splat(face.light[i] * light_fraction),
+        let level = voxel_kind.liquid_level(kind_above(kind, voxel));
+        let height = level as f32 / voxel::LIQUID_LEVELS as f32;
+
+        for side in voxel::SIDES {
+            if occlusion.is_occluded(side) {
+                continue;
+            }
+
+            let positions = VERTICES_INDICES[side as usize].map(|i| {
+                let mut base_vertex: Vec3 = VERTICES[i].into();
+                base_vertex.y *= height;
+                base_vertex + voxel.as_vec3()
             });
+
+            let face = voxel::Face {
+                vertices: [voxel; 4],
+                side,
+                kind: voxel_kind,
+                light: soft_light.get(side),
+            };
+
+            push_face_vertices(
+                &mut vertices,
+                &face,
+                &positions,
+                kinds_descs,
+                tile_texture_size,
+            );
         }
     }
 
-    debug_assert!(!vertices.is_empty());
     vertices
 }
 
-pub(super) fn faces_occlusion(
+/// What voxels of neighbor chunks which aren't loaded yet are assumed to be, so chunks on the
+/// border of loaded area look plausible until their neighbors are loaded.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingNeighborPolicy {
+    /// Missing voxels are empty and fully lit, so all faces on chunk border are drawn.
+    Open,
+    /// Missing voxels mirror the height of chunk border columns: below the top most solid voxel
+    /// they are solid and dark, like underground, and above it they are empty and lit by sky.
+    #[default]
+    Heightmap,
+}
+
+impl MissingNeighborPolicy {
+    /// **Returns** the height of each column of the given chunk, needed to check missing voxels,
+    /// or `None` if this policy doesn't need them.
+    pub fn heights(self, kind: &ChunkStorage<voxel::Kind>) -> Option<Vec<ColumnSummary>> {
+        match self {
+            Self::Open => None,
+            Self::Heightmap => Some(chunk::column_summaries(kind)),
+        }
+    }
+
+    /// Checks if the given voxel, which is on a missing neighbor, is assumed to be solid. `heights`
+    /// must be the ones returned by [`MissingNeighborPolicy::heights`].
+    pub fn is_solid(heights: Option<&[ColumnSummary]>, voxel: Voxel) -> bool {
+        let Some(heights) = heights else {
+            return false;
+        };
+
+        // Border column of this chunk, which is next to the given voxel.
+        let x = voxel.x.clamp(0, chunk::X_END) as usize;
+        let z = voxel.z.clamp(0, chunk::Z_END) as usize;
+        voxel.y < heights[x * chunk::Z_AXIS_SIZE + z].height as i32
+    }
+}
+
+pub fn faces_occlusion(
     kind: &ChunkStorage<voxel::Kind>,
     faces_occlusion: &mut ChunkStorage<voxel::FacesOcclusion>,
     neighboorhood: &[Option<&ChunkStorage<voxel::Kind>>; chunk::SIDE_COUNT],
+    missing: MissingNeighborPolicy,
 ) {
-    chunk::voxels().for_each(|voxel| {
-        if kind.get(voxel).is_none() {
-            faces_occlusion.set(voxel, voxel::FacesOcclusion::fully_occluded());
+    let heights = if neighboorhood.iter().any(Option::is_none) {
+        missing.heights(kind)
+    } else {
+        None
+    };
+
+    chunk::zip::zip_mut(kind, faces_occlusion).for_each(|(voxel, voxel_kind, occlusion)| {
+        if !voxel_kind.is_solid() {
+            *occlusion = voxel::FacesOcclusion::fully_occluded();
         } else {
             let mut faces = FacesOcclusion::default();
             voxel::SIDES.iter().for_each(|&side| {
                 let neighbor = voxel + side.dir();
 
-                let neighbor_kind = if chunk::is_inside(neighbor) {
-                    kind.get(neighbor)
-                } else {
-                    let Some(chunk_side) = ChunkSide::from_voxel_side(side) else {
-                        return;
-                    };
-
-                    let Some(neighbor_kind) = neighboorhood[chunk_side as usize] else {
-                        return;
-                    };
-
-                    let neighbor_chunk_voxel = math::euclid_rem(
-                        neighbor,
-                        IVec3::new(
-                            chunk::X_AXIS_SIZE as i32,
-                            chunk::Y_AXIS_SIZE as i32,
-                            chunk::Z_AXIS_SIZE as i32,
-                        ),
-                    );
-                    neighbor_kind.get(neighbor_chunk_voxel)
+                let Some(neighbor_kind) = kind_across(kind, neighboorhood, neighbor) else {
+                    // Out of bounds on Y isn't a missing chunk, since there is no chunk above or
+                    // below.
+                    let missing = (0..=chunk::Y_END).contains(&neighbor.y);
+                    if missing && MissingNeighborPolicy::is_solid(heights.as_deref(), neighbor) {
+                        faces.set(side, true);
+                    }
+                    return;
                 };
 
-                faces.set(side, !neighbor_kind.is_none());
+                let occluded = is_face_occluded(side, *voxel_kind, neighbor_kind, || {
+                    let neighbor_above = kind_across(kind, neighboorhood, neighbor + IVec3::Y);
+                    (kind_above(kind, voxel), neighbor_above.unwrap_or_default())
+                });
+                faces.set(side, occluded);
             });
-            faces_occlusion.set(voxel, faces);
+            *occlusion = faces;
         }
     });
 }
 
-pub(super) fn generate_faces(
+/// Checks if the face on the given side of a voxel is hidden by the neighbor voxel on that side.
+///
+/// Liquids are also hidden by the same liquid, unless the neighbor surface is lower, so only liquid
+/// bodies boundaries are drawn. Kinds above both voxels are only needed to compare liquid levels,
+/// so `aboves` is only called then.
+///
+/// Layers top is below the voxel above them, so it is never hidden, while their sides are also
+/// hidden by neighbor layers of the same kind, which have the same height.
+fn is_face_occluded(
+    side: voxel::Side,
+    kind: voxel::Kind,
+    neighbor: voxel::Kind,
+    aboves: impl FnOnce() -> (voxel::Kind, voxel::Kind),
+) -> bool {
+    if kind.is_layer() {
+        return match side {
+            voxel::Side::Up => false,
+            voxel::Side::Down => neighbor.is_opaque(),
+            _ => neighbor.is_opaque() || neighbor == kind,
+        };
+    }
+
+    if neighbor.is_opaque() {
+        return true;
+    }
+
+    if !kind.is_liquid() || neighbor != kind {
+        return false;
+    }
+
+    match side {
+        voxel::Side::Up | voxel::Side::Down => true,
+        _ => {
+            let (above, neighbor_above) = aboves();
+            neighbor.liquid_level(neighbor_above) >= kind.liquid_level(above)
+        }
+    }
+}
+
+/// **Returns** the kind above the given voxel, or none if it is on chunk top.
+fn kind_above(kind: &ChunkStorage<voxel::Kind>, voxel: Voxel) -> voxel::Kind {
+    let above = voxel + IVec3::Y;
+    if chunk::is_inside(above) {
+        kind.get(above)
+    } else {
+        voxel::Kind::none()
+    }
+}
+
+/// **Returns** the kind of the given voxel, which may be across chunk border, on a neighbor chunk.
+/// `None` is returned when that neighbor isn't loaded or when voxel is above or below chunk.
+fn kind_across(
+    kind: &ChunkStorage<voxel::Kind>,
+    neighboorhood: &[Option<&ChunkStorage<voxel::Kind>>; chunk::SIDE_COUNT],
+    voxel: Voxel,
+) -> Option<voxel::Kind> {
+    if chunk::is_inside(voxel) {
+        return Some(kind.get(voxel));
+    }
+
+    if voxel.y < 0 || voxel.y > chunk::Y_END {
+        return None;
+    }
+
+    let dir = IVec3::new(
+        voxel.x.div_euclid(chunk::X_AXIS_SIZE as i32),
+        0,
+        voxel.z.div_euclid(chunk::Z_AXIS_SIZE as i32),
+    );
+    let chunk_side = ChunkSide::from_voxel_side(voxel::Side::from_dir(dir))?;
+    let neighbor_kind = neighboorhood[chunk_side as usize]?;
+
+    let neighbor_chunk_voxel = math::euclid_rem(
+        voxel,
+        IVec3::new(
+            chunk::X_AXIS_SIZE as i32,
+            chunk::Y_AXIS_SIZE as i32,
+            chunk::Z_AXIS_SIZE as i32,
+        ),
+    );
+    Some(neighbor_kind.get(neighbor_chunk_voxel))
+}
+
+/// Checks faces of voxels on the given chunk border against neighbor voxels across the border.
+///
+/// **Returns** faces which occlusion doesn't match the neighbor voxel, like a missing quad, which
+/// leaves a hole on the seam, or an extra quad hidden behind a neighbor voxel.
+pub fn seam_mismatches(
+    kind: &ChunkStorage<voxel::Kind>,
+    occlusion: &ChunkStorage<voxel::FacesOcclusion>,
+    chunk_side: ChunkSide,
+    neighbor_kind: &ChunkStorage<voxel::Kind>,
+) -> Vec<(Voxel, voxel::Side)> {
+    let Some(side) = voxel::SIDES
+        .into_iter()
+        .find(|&side| ChunkSide::from_voxel_side(side) == Some(chunk_side))
+    else {
+        return vec![];
+    };
+
+    chunk::voxels()
+        .filter(|&voxel| !chunk::is_inside(voxel + side.dir()) && kind.get(voxel).is_solid())
+        .filter(|&voxel| {
+            let across = math::euclid_rem(
+                voxel + side.dir(),
+                IVec3::new(
+                    chunk::X_AXIS_SIZE as i32,
+                    chunk::Y_AXIS_SIZE as i32,
+                    chunk::Z_AXIS_SIZE as i32,
+                ),
+            );
+            let expected =
+                is_face_occluded(side, kind.get(voxel), neighbor_kind.get(across), || {
+                    (kind_above(kind, voxel), kind_above(neighbor_kind, across))
+                });
+            occlusion.get(voxel).is_occluded(side) != expected
+        })
+        .map(|voxel| (voxel, side))
+        .collect()
+}
+
+pub fn generate_faces(
     kind: &ChunkStorage<voxel::Kind>,
     occlusion: &ChunkStorage<voxel::FacesOcclusion>,
     soft_light: &ChunkStorage<voxel::FacesSoftLight>,
 ) -> Vec<voxel::Face> {
     let mut faces_vertices = vec![];
 
-    for voxel in chunk::voxels() {
-        for side in voxel::SIDES {
-            let kind = kind.get(voxel);
+    for (voxel, &kind, occlusion, soft_light) in chunk::zip::zip3(kind, occlusion, soft_light) {
+        // Liquids have their own mesh. See [`generate_liquid_vertices`].
+        if !kind.is_solid() || kind.is_liquid() {
+            continue;
+        }
 
-            if kind.is_none() || (occlusion.get(voxel).is_occluded(side)) {
+        for side in voxel::SIDES {
+            if occlusion.is_occluded(side) {
                 continue;
             }
 
-            let voxel_soft_light = soft_light.get(voxel);
-
             let (v1, v2, v3, v4) = (voxel, voxel, voxel, voxel);
             faces_vertices.push(voxel::Face {
                 vertices: [v1, v2, v3, v4],
                 side,
                 kind,
-                light: voxel_soft_light.get(side),
+                light: soft_light.get(side),
             });
         }
     }
@@ -187,7 +430,12 @@ mod test {
         let mut faces_occlusion = Default::default();
         let neighborhood = [None; chunk::SIDE_COUNT];
 
-        super::faces_occlusion(&kind, &mut faces_occlusion, &neighborhood);
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
 
         assert!(
             faces_occlusion.iter().all(|occ| occ.is_fully_occluded()),
@@ -203,7 +451,12 @@ mod test {
 
         kind.set([0, 0, 0].into(), 1.into());
 
-        super::faces_occlusion(&kind, &mut faces_occlusion, &neighborhood);
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
 
         let occ = faces_occlusion.get([0, 0, 0].into());
 
@@ -212,6 +465,50 @@ mod test {
         });
     }
 
+    #[test]
+    fn faces_occlusion_missing_heightmap() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut faces_occlusion = Default::default();
+        let neighborhood = [None; chunk::SIDE_COUNT];
+
+        // A column on left border, 3 voxels tall.
+        for y in 0..3 {
+            kind.set([0, y, 5].into(), 1.into());
+        }
+
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Heightmap,
+        );
+
+        assert!(
+            faces_occlusion
+                .get([0, 1, 5].into())
+                .is_occluded(voxel::Side::Left),
+            "Missing neighbor below border height is solid"
+        );
+        assert!(
+            !faces_occlusion
+                .get([0, 2, 5].into())
+                .is_occluded(voxel::Side::Left),
+            "Top most voxel is lit by sky"
+        );
+        assert!(
+            !faces_occlusion
+                .get([0, 1, 5].into())
+                .is_occluded(voxel::Side::Right),
+            "Neighbors inside chunk aren't affected"
+        );
+        assert!(
+            !faces_occlusion
+                .get([0, 0, 5].into())
+                .is_occluded(voxel::Side::Down),
+            "There is no chunk below"
+        );
+    }
+
     #[test]
     fn faces_occlusion_neighbor() {
         let mut kind = ChunkStorage::<voxel::Kind>::default();
@@ -223,7 +520,12 @@ mod test {
         neighbor_kind.set([chunk::X_END, 0, 0].into(), 1.into());
         neighborhood[voxel::Side::Left as usize] = Some(&neighbor_kind);
 
-        super::faces_occlusion(&kind, &mut faces_occlusion, &neighborhood);
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
 
         let occ = faces_occlusion.get([0, 0, 0].into());
 
@@ -238,4 +540,208 @@ mod test {
             }
         });
     }
+
+    #[test]
+    fn seam_mismatches() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut neighbor_kind = ChunkStorage::<voxel::Kind>::default();
+        let mut faces_occlusion = Default::default();
+        let mut neighborhood = [None; chunk::SIDE_COUNT];
+
+        kind.set([0, 0, 0].into(), 1.into());
+        kind.set([0, 1, 0].into(), 1.into());
+        neighbor_kind.set([chunk::X_END, 0, 0].into(), 1.into());
+
+        // Occlusion computed before neighbor was loaded is outdated.
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
+
+        assert_eq!(
+            super::seam_mismatches(&kind, &faces_occlusion, ChunkSide::Left, &neighbor_kind),
+            vec![([0, 0, 0].into(), voxel::Side::Left)]
+        );
+
+        neighborhood[voxel::Side::Left as usize] = Some(&neighbor_kind);
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
+
+        assert!(
+            super::seam_mismatches(&kind, &faces_occlusion, ChunkSide::Left, &neighbor_kind)
+                .is_empty()
+        );
+        assert!(
+            super::seam_mismatches(&kind, &faces_occlusion, ChunkSide::Right, &neighbor_kind)
+                .is_empty(),
+            "Only voxels on the given border should be checked"
+        );
+    }
+
+    #[test]
+    fn liquid_vertices() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut faces_occlusion = Default::default();
+        let soft_light = Default::default();
+        let neighborhood = [None; chunk::SIDE_COUNT];
+
+        let water = voxel::Kind::id(5);
+        kind.set([0, 0, 1].into(), water);
+        kind.set([1, 0, 1].into(), water);
+        kind.set([2, 0, 1].into(), water);
+        kind.set([2, 1, 1].into(), water);
+
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
+
+        let occ = |voxel: [i32; 3]| faces_occlusion.get(voxel.into());
+        assert!(
+            occ([0, 0, 1]).is_occluded(voxel::Side::Right)
+                && occ([1, 0, 1]).is_occluded(voxel::Side::Left),
+            "Liquids on the same level should hide each other"
+        );
+        assert!(occ([1, 0, 1]).is_occluded(voxel::Side::Right));
+        assert!(
+            !occ([2, 0, 1]).is_occluded(voxel::Side::Left),
+            "Full liquid should be seen above a lower neighbor"
+        );
+        assert!(occ([2, 0, 1]).is_occluded(voxel::Side::Up));
+        assert!(!occ([2, 1, 1]).is_occluded(voxel::Side::Up));
+
+        assert!(
+            super::generate_faces(&kind, &faces_occlusion, &soft_light).is_empty(),
+            "Liquids should not be on opaque mesh"
+        );
+
+        let vertices = super::generate_liquid_vertices(&kind, &faces_occlusion, &soft_light);
+        let surface = (voxel::LIQUID_LEVELS - 1) as f32 / voxel::LIQUID_LEVELS as f32;
+
+        assert_eq!(vertices.len() % 4, 0);
+        assert!(vertices.iter().all(|v| v.position.y <= 1.0 + surface));
+        assert!(vertices
+            .iter()
+            .any(|v| v.position == Vec3::new(1.0, surface, 1.0)));
+        assert!(vertices
+            .iter()
+            .any(|v| v.position == Vec3::new(3.0, 1.0 + surface, 2.0)));
+    }
+
+    #[test]
+    fn layer_faces() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut faces_occlusion = Default::default();
+        let soft_light = Default::default();
+        let neighborhood = [None; chunk::SIDE_COUNT];
+        let snow = voxel::Kind::by_name("Snow").unwrap();
+
+        // Two snow layers side by side, resting on rock, with rock above the first one.
+        kind.set([0, 0, 0].into(), 3.into());
+        kind.set([1, 0, 0].into(), 3.into());
+        kind.set([0, 1, 0].into(), snow);
+        kind.set([1, 1, 0].into(), snow);
+        kind.set([0, 2, 0].into(), 3.into());
+
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
+
+        let occ = |voxel: [i32; 3]| faces_occlusion.get(voxel.into());
+        assert!(
+            occ([0, 1, 0]).is_occluded(voxel::Side::Right)
+                && occ([1, 1, 0]).is_occluded(voxel::Side::Left),
+            "Layers of the same kind should hide each other"
+        );
+        assert!(occ([0, 1, 0]).is_occluded(voxel::Side::Down));
+        assert!(
+            !occ([0, 1, 0]).is_occluded(voxel::Side::Up),
+            "Layer top should be seen even below opaque voxels"
+        );
+        assert!(
+            !occ([0, 0, 0]).is_occluded(voxel::Side::Up),
+            "Layers should not hide voxels below them"
+        );
+
+        let faces = super::generate_faces(&kind, &faces_occlusion, &soft_light)
+            .into_iter()
+            .filter(|face| face.kind == snow)
+            .collect::<Vec<_>>();
+        let vertices = super::generate_vertices(faces);
+        let top = 1.0 + snow.shape_height();
+
+        assert!(vertices.iter().all(|v| v.position.y <= top));
+        assert!(vertices
+            .iter()
+            .any(|v| v.position == Vec3::new(2.0, top, 1.0)));
+    }
+
+    #[test]
+    fn welded_vertices() {
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        let mut faces_occlusion = Default::default();
+        let soft_light = Default::default();
+        let neighborhood = [None; chunk::SIDE_COUNT];
+
+        kind.set([0, 0, 0].into(), 3.into());
+        kind.set([1, 0, 0].into(), 3.into());
+
+        super::faces_occlusion(
+            &kind,
+            &mut faces_occlusion,
+            &neighborhood,
+            MissingNeighborPolicy::Open,
+        );
+        let faces = super::generate_faces(&kind, &faces_occlusion, &soft_light);
+        let vertices = super::generate_vertices(faces);
+
+        assert_eq!(vertices.len(), 40);
+
+        let mesh = voxel::weld(&vertices);
+
+        assert_eq!(
+            mesh.vertices.len(),
+            32,
+            "Coplanar neighbor faces should share their edge vertices"
+        );
+        assert_eq!(mesh.indices.len(), 60);
+    }
+
+    #[test]
+    fn neighborhood_hash() {
+        let chunk = Chunk::new(3, -2);
+        let hash = super::neighborhood_hash(chunk, |_| Some(1));
+
+        assert_eq!(hash, super::neighborhood_hash(chunk, |_| Some(1)));
+        assert_ne!(
+            hash,
+            super::neighborhood_hash(chunk, |other| Some(if other == Chunk::new(4, -1) {
+                2
+            } else {
+                1
+            })),
+            "Diagonal neighbors must be hashed too"
+        );
+        assert_ne!(
+            hash,
+            super::neighborhood_hash(chunk, |other| (other != Chunk::new(2, -2)).then_some(1)),
+            "Missing neighbors must be hashed too"
+        );
+        assert_eq!(
+            hash,
+            super::neighborhood_hash(chunk, |other| (other.x().abs() < 5).then_some(1)),
+            "Chunks farther away must not be hashed"
+        );
+    }
 }
@@ -0,0 +1,108 @@
+//! World metadata, persisted along with chunks, which tells how saved chunks must be read.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use projekto_core::voxel::{KindRemap, KindsDescs, KindsTable};
+use serde::{Deserialize, Serialize};
+
+/// Name of metadata file, inside chunks folder.
+pub(crate) const META_FILE: &str = "world.ron";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldMeta {
+    /// Kinds ids which chunks were saved with.
+    pub kinds: KindsTable,
+}
+
+impl WorldMeta {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        ron::de::from_reader(file).map_err(|e| e.to_string())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let contents =
+            ron::ser::to_string_pretty(self, Default::default()).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+/// **Returns** the remap from kinds of the world on the given folder to current
+/// [`KindsDescs`], or `None` if no saved kind changed its id.
+///
+/// Metadata is created from current kinds when the world has none yet, and it is updated when
+/// kinds were only added, since saved chunks stays valid in this case.
+pub(crate) fn load_kind_remap(dir: &Path) -> Option<KindRemap> {
+    let path = dir.join(META_FILE);
+    let current = KindsTable::new(KindsDescs::get());
+
+    let saved = if path.exists() {
+        match WorldMeta::read(&path) {
+            Ok(meta) => meta.kinds,
+            Err(err) => {
+                error!(
+                    "Failed to read world metadata {path:?}. Assuming current kinds. Error: {err}"
+                );
+                return None;
+            }
+        }
+    } else {
+        KindsTable::default()
+    };
+
+    let remap = KindRemap::new(&saved, &current);
+    if !remap.is_identity() {
+        warn!("Kinds ids changed since world was saved. Saved chunks are remapped when loaded.");
+        return Some(remap);
+    }
+
+    if saved != current {
+        let meta = WorldMeta { kinds: current };
+        if let Err(err) = meta.write(&path) {
+            error!("Failed to write world metadata {path:?}. Error: {err}");
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use projekto_core::voxel::Kind;
+
+    use super::*;
+
+    #[test]
+    fn kind_remap_from_meta() {
+        let dir = std::env::temp_dir().join("projekto_meta_test");
+        let path = dir.join(META_FILE);
+        let _ = std::fs::remove_file(&path);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert!(
+            load_kind_remap(&dir).is_none(),
+            "New world has nothing to remap"
+        );
+        let current = KindsTable::new(KindsDescs::get());
+        assert_eq!(WorldMeta::read(&path).unwrap().kinds, current);
+
+        // Swaps ids of the first two solid kinds, like when kinds are reordered.
+        let mut kinds = current.clone();
+        let (a, b) = (1, 2);
+        kinds.0.values_mut().for_each(|id| {
+            if *id == a {
+                *id = b;
+            } else if *id == b {
+                *id = a;
+            }
+        });
+        WorldMeta { kinds }.write(&path).unwrap();
+
+        let remap = load_kind_remap(&dir).expect("Reordered kinds should be remapped");
+        assert_eq!(remap.get(Kind::id(a)), Ok(Kind::id(b)));
+        assert_eq!(remap.get(Kind::id(b)), Ok(Kind::id(a)));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,34 +1,136 @@
 use std::sync::mpsc::{self, Receiver};
 
 use bevy::{
+    app::AppExit,
     prelude::*,
     tasks::{AsyncComputeTaskPool, TaskPool},
     utils::{synccell::SyncCell, HashMap},
 };
 
-use projekto_messages::{ClientMessage, ServerMessage};
-use projekto_proto::{Client, ClientId, MessageType};
+use projekto_messages::{
+    self as messages, Capabilities, ClientMessage, Heartbeat, Hello, ServerMessage,
+};
+use projekto_proto::{
+    Client, ClientId, Compression, ConnectionConfig, MessageType, RegisterMessageHandler,
+};
+
+use crate::{
+    debug::MessageRateLimited,
+    rate_limit::{RateCheck, RateLimiter, RateLimits},
+    ServerTick,
+};
 
 pub(crate) struct NetPlugin;
 
 impl Plugin for NetPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Clients>()
+            .init_resource::<ClientCapabilities>()
+            .init_resource::<CompressionBudget>()
+            .init_resource::<RateLimits>()
+            .init_resource::<RateLimiter>()
+            .add_event::<Shutdown>()
+            .add_message_handler(handle_hello)
+            .add_message_handler(handle_heartbeat)
             .add_systems(Startup, start_network_server)
+            .configure_sets(PreUpdate, NetSet::Connections.before(NetSet::Messages))
             .add_systems(
                 PreUpdate,
                 (
-                    new_client_connected,
-                    remove_disconnected_clients,
-                    handle_messages,
+                    (new_client_connected, remove_disconnected_clients).in_set(NetSet::Connections),
+                    handle_messages.in_set(NetSet::Messages),
                 ),
+            )
+            .add_systems(
+                Last,
+                (
+                    begin_shutdown,
+                    finish_shutdown.run_if(resource_exists::<ShuttingDown>),
+                )
+                    .chain(),
             );
     }
 }
 
+/// Sets network systems run on `PreUpdate`.
+#[derive(SystemSet, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub(crate) enum NetSet {
+    /// Adds newly connected clients and removes disconnected ones.
+    Connections,
+    /// Handles messages received from clients.
+    Messages,
+}
+
+/// Starts shutting down the server. Clients are told why and when, so they can disconnect
+/// cleanly, and server exits after `seconds` or once all clients are gone.
+#[derive(Event, Debug, Clone)]
+pub struct Shutdown {
+    pub reason: String,
+    pub seconds: f32,
+}
+
+/// Server is shutting down. See [`Shutdown`].
+#[derive(Resource, Debug)]
+struct ShuttingDown {
+    reason: String,
+    timer: Timer,
+}
+
+impl ShuttingDown {
+    fn notify(&self, client: &Client<ClientMessage, ServerMessage>) {
+        let _ = client.channel().send(messages::ShuttingDown {
+            reason: self.reason.clone(),
+            seconds: self.timer.remaining_secs(),
+        });
+    }
+}
+
 #[derive(Resource, Default, Deref, DerefMut)]
 pub struct Clients(HashMap<ClientId, Client<ClientMessage, ServerMessage>>);
 
+/// Capabilities agreed with each connected client on handshake. See [`Capabilities`].
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub(crate) struct ClientCapabilities(HashMap<ClientId, Capabilities>);
+
+impl ClientCapabilities {
+    /// **Returns** the capabilities of the given client, or the default ones if it didn't send a
+    /// `Hello` yet.
+    pub fn of(&self, id: ClientId) -> Capabilities {
+        self.get(&id).copied().unwrap_or_default()
+    }
+}
+
+/// Compression costs server CPU time on each message sent, so only a limited number of clients
+/// get compressed messages. Must be inserted before [`crate::WorldServerPlugin`] to replace the
+/// default budget.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionBudget {
+    /// Max clients receiving compressed messages at once. Clients connected after that receive
+    /// uncompressed messages, even if they asked for compression.
+    pub max_clients: usize,
+}
+
+impl Default for CompressionBudget {
+    fn default() -> Self {
+        Self { max_clients: 16 }
+    }
+}
+
+/// **Returns** the capabilities server is going to use with a client which `asked` for them, given
+/// how many other clients already receive compressed messages.
+fn negotiate(asked: Capabilities, compressed: usize, budget: CompressionBudget) -> Capabilities {
+    let compression = if compressed < budget.max_clients {
+        asked.compression
+    } else {
+        Compression::None
+    };
+
+    Capabilities {
+        compression,
+        ..asked
+    }
+}
+
 #[derive(Resource, Deref, DerefMut)]
 struct OnClientConnectedReceiver(SyncCell<Receiver<Client<ClientMessage, ServerMessage>>>);
 
@@ -36,7 +138,8 @@ fn start_network_server(mut commands: Commands) {
     let (sender, receiver) = mpsc::channel();
     AsyncComputeTaskPool::get_or_init(TaskPool::default)
         .spawn(async move {
-            let _ = projekto_proto::start_server("127.0.0.1:11223", |client| {
+            let config = ConnectionConfig::default();
+            let _ = projekto_proto::start_server("127.0.0.1:11223", config, |client| {
                 let id = client.id();
                 if let Err(err) = sender.send(client) {
                     error!("Failed to get client {id}. Error: {err}");
@@ -49,24 +152,78 @@ fn start_network_server(mut commands: Commands) {
     commands.insert_resource(OnClientConnectedReceiver(SyncCell::new(receiver)));
 }
 
-fn remove_disconnected_clients(mut clients: ResMut<Clients>) {
+fn remove_disconnected_clients(
+    mut clients: ResMut<Clients>,
+    mut limiter: ResMut<RateLimiter>,
+    mut capabilities: ResMut<ClientCapabilities>,
+) {
     clients.retain(|_, client| {
+        let id = client.id();
+        let addr = client.addr();
+
         if client.is_closed() {
-            let id = client.id();
-            let addr = client.addr();
             debug!("[Networking] Removing disconnected client {id}({addr})");
             false
+        } else if client.channel().sent_stats().rejected() > 0 {
+            // Client isn't keeping up and already missed some messages, so its world is stale.
+            warn!("[Networking] Disconnecting client {id}({addr}): too many pending messages");
+            client.channel().close();
+            false
         } else {
             true
         }
     });
+
+    limiter.retain(|id| clients.contains_key(&id));
+    capabilities.retain(|id, _| clients.contains_key(id));
+}
+
+fn handle_hello(
+    In((id, Hello { capabilities })): In<(ClientId, Hello)>,
+    clients: Res<Clients>,
+    budget: Res<CompressionBudget>,
+    mut agreed: ResMut<ClientCapabilities>,
+) {
+    let Some(client) = clients.get(&id) else {
+        return;
+    };
+
+    let compressed = agreed
+        .iter()
+        .filter(|(other, caps)| **other != id && caps.compression != Compression::None)
+        .count();
+    let capabilities = negotiate(capabilities, compressed, *budget);
+
+    debug!("[Networking] Client {id} capabilities: {capabilities:?}");
+
+    let _ = client.channel().send(messages::Welcome { capabilities });
+    client.set_compression(capabilities.compression);
+    agreed.insert(id, capabilities);
+}
+
+fn handle_heartbeat(
+    In((id, Heartbeat { sent_at })): In<(ClientId, Heartbeat)>,
+    clients: Res<Clients>,
+    tick: Res<ServerTick>,
+) {
+    if let Some(client) = clients.get(&id) {
+        let _ = client.channel().send(messages::HeartbeatReply {
+            sent_at,
+            tick: tick.0,
+        });
+    }
 }
 
 fn new_client_connected(
     mut receiver: ResMut<OnClientConnectedReceiver>,
     mut clients: ResMut<Clients>,
+    shutting_down: Option<Res<ShuttingDown>>,
 ) {
     for new_client in receiver.get().try_iter() {
+        if let Some(shutting_down) = &shutting_down {
+            shutting_down.notify(&new_client);
+        }
+
         let id = new_client.id();
         if clients.insert(id, new_client).is_some() {
             panic!("Duplicated client detected {id}.");
@@ -75,16 +232,305 @@ fn new_client_connected(
 }
 
 fn handle_messages(world: &mut World) {
+    let now = world.resource::<Time>().elapsed_seconds();
     let clients = world
         .resource::<Clients>()
         .iter()
         .map(|(id, client)| (*id, client.channel().try_recv_all()))
         .collect::<Vec<_>>();
 
-    for (id, messages) in clients {
-        for boxed in messages {
-            let msg_type = boxed.msg_type();
-            msg_type.run_handlers(boxed, id, world);
+    world.resource_scope(|world, mut limiter: Mut<RateLimiter>| {
+        for (id, messages) in clients {
+            for boxed in messages {
+                let msg_type = boxed.msg_type();
+
+                // Spectators can't edit the world, so there is nothing to validate.
+                if msg_type.is_edit() && world.resource::<ClientCapabilities>().of(id).spectator {
+                    trace!("[Networking] Dropping {msg_type:?} from spectator {id}");
+                    continue;
+                }
+
+                let check = limiter.check(world.resource::<RateLimits>(), id, msg_type, now);
+
+                if check == RateCheck::Allowed {
+                    msg_type.run_handlers(boxed, id, world);
+                    continue;
+                }
+
+                let disconnected = check == RateCheck::Disconnect;
+                world.send_event(MessageRateLimited {
+                    client: id,
+                    msg_type,
+                    disconnected,
+                });
+
+                if disconnected {
+                    warn!("[Networking] Disconnecting client {id}: too many messages dropped");
+                    if let Some(client) = world.resource::<Clients>().get(&id) {
+                        client.channel().close();
+                    }
+                    break;
+                }
+            }
+        }
+    });
+}
+
+fn begin_shutdown(
+    mut commands: Commands,
+    mut reader: EventReader<Shutdown>,
+    clients: Res<Clients>,
+    shutting_down: Option<Res<ShuttingDown>>,
+) {
+    let Some(Shutdown { reason, seconds }) = reader.read().last() else {
+        return;
+    };
+
+    if shutting_down.is_some() {
+        warn!("[Networking] Server is already shutting down. Ignoring shutdown: {reason}");
+        return;
+    }
+
+    info!("[Networking] Shutting down in {seconds}s. Reason: {reason}");
+
+    let shutting_down = ShuttingDown {
+        reason: reason.clone(),
+        timer: Timer::from_seconds(seconds.max(0.0), TimerMode::Once),
+    };
+    clients
+        .values()
+        .for_each(|client| shutting_down.notify(client));
+
+    commands.insert_resource(shutting_down);
+}
+
+fn finish_shutdown(
+    time: Res<Time>,
+    mut shutting_down: ResMut<ShuttingDown>,
+    clients: Res<Clients>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    shutting_down.timer.tick(time.delta());
+
+    if !shutting_down.timer.finished() && !clients.is_empty() {
+        return;
+    }
+
+    // Messages already sent are still flushed, since connections are closed by the network task.
+    for client in clients.values() {
+        client.channel().close();
+    }
+
+    info!("[Networking] Server shut down.");
+    exit_writer.send(AppExit);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use projekto_proto::Channel;
+
+    use super::*;
+
+    #[test]
+    fn shutdown_notifies_clients() {
+        // arrange
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Clients>()
+            .add_event::<Shutdown>()
+            .add_event::<AppExit>();
+
+        let (client, channel) = Client::loopback(1);
+        app.world
+            .resource_mut::<Clients>()
+            .insert(client.id(), client);
+
+        app.world.send_event(Shutdown {
+            reason: "Maintenance".to_string(),
+            seconds: 30.0,
+        });
+
+        // act
+        app.world.run_system_once(begin_shutdown);
+        app.world.run_system_once(finish_shutdown);
+
+        // assert
+        let msg = channel
+            .try_recv_all()
+            .into_iter()
+            .find_map(|boxed| boxed.downcast::<messages::ShuttingDown>().ok())
+            .expect("Client should be notified");
+        assert_eq!(msg.reason, "Maintenance");
+        assert_eq!(msg.seconds, 30.0);
+
+        assert!(
+            app.world.resource::<Events<AppExit>>().is_empty(),
+            "Server should wait clients to disconnect"
+        );
+
+        // act
+        app.world.resource_mut::<Clients>().clear();
+        app.world.run_system_once(finish_shutdown);
+
+        // assert
+        assert!(
+            !app.world.resource::<Events<AppExit>>().is_empty(),
+            "Server should exit once all clients are gone"
+        );
+    }
+
+    #[test]
+    fn negotiate_compression_budget() {
+        let asked = Capabilities {
+            compression: Compression::Lz4,
+            delta_updates: false,
+            kind_cache: true,
+            spectator: true,
+        };
+        let budget = CompressionBudget { max_clients: 2 };
+
+        assert_eq!(negotiate(asked, 1, budget), asked);
+        assert_eq!(
+            negotiate(asked, 2, budget),
+            Capabilities {
+                compression: Compression::None,
+                ..asked
+            },
+            "Compression should be disabled once out of budget"
+        );
+    }
+
+    #[test]
+    fn hello_agrees_capabilities() {
+        // arrange
+        let mut app = App::new();
+        app.init_resource::<Clients>()
+            .init_resource::<ClientCapabilities>()
+            .insert_resource(CompressionBudget { max_clients: 1 });
+
+        let (first, first_channel) = Client::loopback(1);
+        let (second, second_channel) = Client::loopback(2);
+        for client in [first.clone(), second.clone()] {
+            app.world
+                .resource_mut::<Clients>()
+                .insert(client.id(), client);
+        }
+
+        let capabilities = Capabilities {
+            compression: Compression::Lz4,
+            ..default()
+        };
+
+        // act
+        app.world
+            .run_system_once_with((first.id(), Hello { capabilities }), handle_hello);
+        app.world
+            .run_system_once_with((second.id(), Hello { capabilities }), handle_hello);
+
+        // assert
+        let welcome = |channel: &Channel<ClientMessage, ServerMessage>| {
+            channel
+                .try_recv_all()
+                .into_iter()
+                .find_map(|boxed| boxed.downcast::<messages::Welcome>().ok())
+                .expect("Client should be welcomed")
+                .capabilities
+        };
+
+        assert_eq!(welcome(&first_channel).compression, Compression::Lz4);
+        assert_eq!(first.compression(), Compression::Lz4);
+
+        assert_eq!(
+            welcome(&second_channel).compression,
+            Compression::None,
+            "Server is out of compression budget"
+        );
+        assert_eq!(second.compression(), Compression::None);
+
+        let agreed = app.world.resource::<ClientCapabilities>();
+        assert_eq!(agreed.of(first.id()).compression, Compression::Lz4);
+        assert_eq!(agreed.of(second.id()).compression, Compression::None);
+        assert_eq!(agreed.of(3.into()), Capabilities::default());
+    }
+
+    #[test]
+    fn spectator_edits_are_dropped() {
+        // arrange
+        #[derive(Resource, Default)]
+        struct Edits(Vec<ClientId>);
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Clients>()
+            .init_resource::<ClientCapabilities>()
+            .init_resource::<RateLimits>()
+            .init_resource::<RateLimiter>()
+            .init_resource::<Edits>()
+            .add_message_handler(
+                |In((id, _)): In<(ClientId, messages::VoxelUpdate)>, mut edits: ResMut<Edits>| {
+                    edits.0.push(id);
+                },
+            );
+
+        let (player, player_channel) = Client::loopback(1);
+        let (spectator, spectator_channel) = Client::loopback(2);
+        for client in [player.clone(), spectator.clone()] {
+            app.world
+                .resource_mut::<Clients>()
+                .insert(client.id(), client);
         }
+        app.world.resource_mut::<ClientCapabilities>().insert(
+            spectator.id(),
+            Capabilities {
+                spectator: true,
+                ..default()
+            },
+        );
+
+        let update = messages::VoxelUpdate {
+            chunk: Default::default(),
+            voxel: Default::default(),
+            kind: Default::default(),
+        };
+        player_channel.send(update).unwrap();
+        spectator_channel.send(update).unwrap();
+
+        // act
+        app.world.run_system_once(handle_messages);
+
+        // assert
+        assert_eq!(
+            app.world.resource::<Edits>().0,
+            vec![player.id()],
+            "Only player edits should be handled"
+        );
+    }
+
+    #[test]
+    fn heartbeat_is_replied() {
+        // arrange
+        let mut app = App::new();
+        app.init_resource::<Clients>()
+            .insert_resource(ServerTick(42));
+
+        let (client, channel) = Client::loopback(1);
+        app.world
+            .resource_mut::<Clients>()
+            .insert(client.id(), client.clone());
+
+        // act
+        app.world
+            .run_system_once_with((client.id(), Heartbeat { sent_at: 1.5 }), handle_heartbeat);
+
+        // assert
+        let reply = channel
+            .try_recv_all()
+            .into_iter()
+            .find_map(|boxed| boxed.downcast::<messages::HeartbeatReply>().ok())
+            .expect("Heartbeat should be replied");
+
+        assert_eq!(reply.sent_at, 1.5);
+        assert_eq!(reply.tick, 42);
     }
 }
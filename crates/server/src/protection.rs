@@ -0,0 +1,114 @@
+//! Protected regions, where only allowed players can edit voxels, so multiplayer servers can keep
+//! places like spawn from being modified.
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashSet};
+use projekto_core::{chunk::Chunk, voxel};
+use projekto_messages::RegionFlags;
+use projekto_proto::ClientId;
+
+/// Clients which can claim and unclaim regions and which can edit voxels anywhere.
+#[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
+pub struct Admins(pub HashSet<ClientId>);
+
+/// Chunks between `min` and `max`, inclusive, which can only be edited by owner and allowed
+/// players, besides what [`RegionFlags`] allows anyone to do.
+#[derive(Component, Debug, Clone)]
+pub struct ProtectedRegion {
+    pub min: Chunk,
+    pub max: Chunk,
+    pub owner: ClientId,
+    pub allowed: HashSet<ClientId>,
+    pub flags: RegionFlags,
+}
+
+impl ProtectedRegion {
+    /// Creates a region between the given chunks, in any order.
+    pub fn new(a: Chunk, b: Chunk, owner: ClientId) -> Self {
+        Self {
+            min: a.xz().min(b.xz()).into(),
+            max: a.xz().max(b.xz()).into(),
+            owner,
+            allowed: Default::default(),
+            flags: Default::default(),
+        }
+    }
+
+    pub fn contains(&self, chunk: Chunk) -> bool {
+        chunk.xz().cmpge(self.min.xz()).all() && chunk.xz().cmple(self.max.xz()).all()
+    }
+
+    /// Checks if the given client can set a voxel to the given kind inside this region.
+    pub fn can_edit(&self, id: ClientId, kind: voxel::Kind) -> bool {
+        if id == self.owner || self.allowed.contains(&id) {
+            return true;
+        }
+
+        if kind.is_solid() {
+            self.flags.place
+        } else {
+            self.flags.remove
+        }
+    }
+}
+
+/// Checks voxel edits against [`ProtectedRegion`]s and [`Admins`].
+#[derive(SystemParam)]
+pub(crate) struct Protection<'w, 's> {
+    admins: Res<'w, Admins>,
+    q_regions: Query<'w, 's, &'static ProtectedRegion>,
+}
+
+impl<'w, 's> Protection<'w, 's> {
+    pub fn is_admin(&self, id: ClientId) -> bool {
+        self.admins.contains(&id)
+    }
+
+    /// Checks if the given client can set a voxel of the given chunk to the given kind. Edits are
+    /// only allowed when every region containing the chunk allows it.
+    pub fn can_edit(&self, id: ClientId, chunk: Chunk, kind: voxel::Kind) -> bool {
+        self.is_admin(id)
+            || self
+                .q_regions
+                .iter()
+                .filter(|region| region.contains(chunk))
+                .all(|region| region.can_edit(id, kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_contains() {
+        let region = ProtectedRegion::new(Chunk::new(2, -1), Chunk::new(-1, 3), 1.into());
+
+        assert_eq!(region.min, Chunk::new(-1, -1));
+        assert_eq!(region.max, Chunk::new(2, 3));
+        assert!(region.contains(Chunk::new(-1, 3)));
+        assert!(region.contains(Chunk::new(0, 0)));
+        assert!(!region.contains(Chunk::new(3, 0)));
+        assert!(!region.contains(Chunk::new(0, -2)));
+    }
+
+    #[test]
+    fn region_can_edit() {
+        let (owner, friend, stranger) = (1.into(), 2.into(), 3.into());
+        let stone = voxel::Kind::id(1);
+
+        let mut region = ProtectedRegion::new(Chunk::new(0, 0), Chunk::new(0, 0), owner);
+        region.allowed.insert(friend);
+
+        assert!(region.can_edit(owner, stone));
+        assert!(region.can_edit(friend, voxel::Kind::NONE));
+        assert!(!region.can_edit(stranger, stone));
+        assert!(!region.can_edit(stranger, voxel::Kind::NONE));
+
+        region.flags.remove = true;
+        assert!(region.can_edit(stranger, voxel::Kind::NONE));
+        assert!(
+            !region.can_edit(stranger, stone),
+            "Only removing is allowed"
+        );
+    }
+}
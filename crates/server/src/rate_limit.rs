@@ -0,0 +1,216 @@
+//! Per client and message type rate limiting, so a misbehaving client can't flood the server.
+//!
+//! Each limited message type has a token bucket per client. Messages received while the bucket is
+//! empty are dropped and counted as violations, and clients with too many violations are
+//! disconnected.
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_messages::ClientMessage;
+use projekto_proto::ClientId;
+
+/// Token bucket settings of a message type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Messages allowed per second, on average.
+    pub per_sec: f32,
+    /// Messages allowed at once, after a quiet period.
+    pub burst: f32,
+}
+
+impl RateLimit {
+    pub fn new(per_sec: f32, burst: f32) -> Self {
+        Self { per_sec, burst }
+    }
+}
+
+/// Rate limits of client messages. Must be inserted before [`crate::WorldServerPlugin`] to
+/// replace the default limits.
+#[derive(Resource, Debug, Clone)]
+pub struct RateLimits {
+    /// Limits of each message type. Message types without limits are never dropped.
+    pub limits: HashMap<ClientMessage, RateLimit>,
+    /// Dropped messages a client can have before being disconnected, or `None` to only drop them.
+    pub disconnect_after: Option<u32>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        // Clients send player transform every 50ms and landscape updates when crossing chunks, so
+        // limits are well above that. Chunk loads are requested in bulk on cache misses, so those
+        // aren't limited. Handshake is sent only once per connection, chat is typed by hand and
+        // heartbeats are sent once per second. Debug requests are sent by hand too, inspecting a
+        // chunk and its neighbors at once, and exports write files on server machine.
+        let limits = [
+            (ClientMessage::LandscapeUpdate, RateLimit::new(10.0, 20.0)),
+            (ClientMessage::PlayerTransform, RateLimit::new(40.0, 40.0)),
+            (ClientMessage::VoxelUpdate, RateLimit::new(20.0, 40.0)),
+            (ClientMessage::VoxelDig, RateLimit::new(20.0, 40.0)),
+            (ClientMessage::Hello, RateLimit::new(1.0, 2.0)),
+            (ClientMessage::Chat, RateLimit::new(2.0, 5.0)),
+            (ClientMessage::Heartbeat, RateLimit::new(2.0, 4.0)),
+            (ClientMessage::ChunkInspect, RateLimit::new(2.0, 18.0)),
+            (ClientMessage::WorldExport, RateLimit::new(0.1, 2.0)),
+        ];
+
+        Self {
+            limits: limits.into_iter().collect(),
+            disconnect_after: Some(100),
+        }
+    }
+}
+
+/// Outcome of a received message. See [`RateLimiter::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RateCheck {
+    Allowed,
+    Dropped,
+    /// Message was dropped and client has too many violations.
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f32,
+    /// Time, in seconds, when tokens were last refilled.
+    last: f32,
+}
+
+impl TokenBucket {
+    /// Refills tokens elapsed since last take and takes one. **Returns** `false` if there was no
+    /// token to take.
+    fn take(&mut self, limit: RateLimit, now: f32) -> bool {
+        self.tokens = (self.tokens + (now - self.last) * limit.per_sec).min(limit.burst);
+        self.last = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClientRate {
+    buckets: HashMap<ClientMessage, TokenBucket>,
+    violations: u32,
+}
+
+/// Token buckets of each connected client.
+#[derive(Resource, Default, Debug)]
+pub(crate) struct RateLimiter(HashMap<ClientId, ClientRate>);
+
+impl RateLimiter {
+    /// Checks if a message of the given type, received from the given client at `now` seconds, is
+    /// within limits.
+    pub fn check(
+        &mut self,
+        limits: &RateLimits,
+        id: ClientId,
+        msg_type: ClientMessage,
+        now: f32,
+    ) -> RateCheck {
+        let Some(&limit) = limits.limits.get(&msg_type) else {
+            return RateCheck::Allowed;
+        };
+
+        let client = self.0.entry(id).or_default();
+        let bucket = client.buckets.entry(msg_type).or_insert(TokenBucket {
+            tokens: limit.burst,
+            last: now,
+        });
+
+        if bucket.take(limit, now) {
+            return RateCheck::Allowed;
+        }
+
+        client.violations += 1;
+        match limits.disconnect_after {
+            Some(max) if client.violations > max => RateCheck::Disconnect,
+            _ => RateCheck::Dropped,
+        }
+    }
+
+    /// Forgets clients which aren't connected anymore.
+    pub fn retain(&mut self, mut connected: impl FnMut(ClientId) -> bool) {
+        self.0.retain(|&id, _| connected(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(disconnect_after: Option<u32>) -> RateLimits {
+        RateLimits {
+            limits: [(ClientMessage::VoxelUpdate, RateLimit::new(2.0, 3.0))]
+                .into_iter()
+                .collect(),
+            disconnect_after,
+        }
+    }
+
+    #[test]
+    fn burst_then_refill() {
+        let limits = limits(None);
+        let mut limiter = RateLimiter::default();
+        let id = ClientId::from(1);
+        let mut check = |now| limiter.check(&limits, id, ClientMessage::VoxelUpdate, now);
+
+        for _ in 0..3 {
+            assert_eq!(check(0.0), RateCheck::Allowed);
+        }
+        assert_eq!(check(0.0), RateCheck::Dropped, "Burst is over");
+
+        // Two tokens per second, so half a second refills a single one.
+        assert_eq!(check(0.5), RateCheck::Allowed);
+        assert_eq!(check(0.5), RateCheck::Dropped);
+
+        assert_eq!(check(100.0), RateCheck::Allowed);
+        assert_eq!(check(100.0), RateCheck::Allowed);
+        assert_eq!(check(100.0), RateCheck::Allowed);
+        assert_eq!(
+            check(100.0),
+            RateCheck::Dropped,
+            "Refill is capped by burst"
+        );
+    }
+
+    #[test]
+    fn unlimited_and_per_client() {
+        let limits = limits(None);
+        let mut limiter = RateLimiter::default();
+
+        for _ in 0..100 {
+            assert_eq!(
+                limiter.check(&limits, 1.into(), ClientMessage::ChunkLoad, 0.0),
+                RateCheck::Allowed
+            );
+        }
+
+        for _ in 0..3 {
+            limiter.check(&limits, 1.into(), ClientMessage::VoxelUpdate, 0.0);
+        }
+        assert_eq!(
+            limiter.check(&limits, 2.into(), ClientMessage::VoxelUpdate, 0.0),
+            RateCheck::Allowed,
+            "Each client has its own buckets"
+        );
+    }
+
+    #[test]
+    fn disconnect_after_violations() {
+        let limits = limits(Some(2));
+        let mut limiter = RateLimiter::default();
+        let id = ClientId::from(1);
+        let mut check = || limiter.check(&limits, id, ClientMessage::VoxelUpdate, 0.0);
+
+        for _ in 0..3 {
+            assert_eq!(check(), RateCheck::Allowed);
+        }
+        assert_eq!(check(), RateCheck::Dropped);
+        assert_eq!(check(), RateCheck::Dropped);
+        assert_eq!(check(), RateCheck::Disconnect);
+    }
+}
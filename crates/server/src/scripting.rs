@@ -0,0 +1,790 @@
+//! Rhai scripts which customize server behavior without recompiling. Scripts are loaded from a
+//! folder at startup, in file name order, and react to [`api`](crate::api) events by defining hook
+//! functions:
+//!
+//! - `on_player_joined(player)` and `on_player_left(player)`.
+//! - `on_chunk_edited(chunk_x, chunk_z, voxels)`, where `voxels` are `[x, y, z]` world coordinates.
+//!   Edits made by scripts trigger it too, on the next tick.
+//!
+//! Scripts only touch the world through this API:
+//!
+//! - `get_voxel(x, y, z)`: kind name of the given voxel, or `()` if its chunk isn't loaded.
+//! - `set_voxel(x, y, z, kind)`: changes the kind of the given voxel. Kind and coordinates are
+//!   validated, and changes are applied on next tick, like any other [`ApplyChunkDiff`].
+//! - `players()`: ids of connected players.
+//! - `broadcast(text)` and `send(player, text)`: server chat text to everyone or a single player.
+//! - `register_command(name, function)`: a console command which calls `function(player, args)` and
+//!   replies whatever it returns.
+//! - `after(secs, function)` and `every(secs, function)`: calls `function()` once or repeatedly.
+//!   Seconds are floats, like `after(5.0, "reset")`.
+//!
+//! Scripts run on server thread, so each call is limited to [`MAX_SCRIPT_OPERATIONS`] and can't
+//! reach files or modules.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use projekto_core::{
+    chunk,
+    voxel::{self, KindsDescs},
+};
+use projekto_messages::ChatBroadcast;
+use projekto_proto::ClientId;
+use rhai::{
+    module_resolvers::DummyModuleResolver, Array, CallFnOptions, Dynamic, Engine, EvalAltResult,
+    Scope, AST, FLOAT, INT,
+};
+
+use crate::{
+    bundle::{ChunkKind, ChunkMap},
+    chat::{CommandResult, ConsoleCommand, ConsoleCommands, MAX_CHAT_LEN},
+    net::Clients,
+    terraform::{self, ApplyChunkDiff, ChunkDiff},
+    ChunkEdited, PlayerJoined, PlayerLeft, WorldSet,
+};
+
+/// Scripts are loaded from this folder, relative to working directory, unless another is given.
+pub const DEFAULT_SCRIPTS_PATH: &str = "scripts";
+/// Max operations a single script call can run, so a script stuck on a loop doesn't stall server.
+pub const MAX_SCRIPT_OPERATIONS: u64 = 100_000;
+/// Max voxels a single script call can change.
+pub const MAX_SCRIPT_EDITS: usize = 4096;
+/// Repeating timers shorter than this, in seconds, are refused, since they would run every tick.
+const MIN_REPEAT_SECS: FLOAT = 0.05;
+
+/// Loads scripts from the given folder. Not added by
+/// [`WorldServerPlugin`](crate::WorldServerPlugin), so servers opt in to it.
+pub struct ScriptingPlugin {
+    pub path: PathBuf,
+}
+
+impl Default for ScriptingPlugin {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_SCRIPTS_PATH.into(),
+        }
+    }
+}
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Scripts::new(self.path.clone()))
+            .add_systems(Startup, load_scripts)
+            .add_systems(
+                PostUpdate,
+                (queue_hooks, run_scripts)
+                    .chain()
+                    .in_set(WorldSet::SendResponses),
+            );
+    }
+}
+
+/// Changes requested by a script call, which are validated as they are requested and applied
+/// once the script returns.
+#[derive(Default)]
+struct ScriptRequests {
+    edits: ChunkDiff,
+    chat: Vec<(Option<ClientId>, String)>,
+    commands: Vec<(String, String)>,
+    timers: Vec<(FLOAT, TimerMode, String)>,
+}
+
+/// State shared with API functions registered on script engine.
+#[derive(Default)]
+struct ScriptShared {
+    /// World is lent to API functions while a script runs, so they can read it.
+    world: Option<World>,
+    requests: ScriptRequests,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    /// Top level variables of the script, kept between calls.
+    scope: Scope<'static>,
+}
+
+impl Script {
+    fn has_fn(&self, name: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == params)
+    }
+}
+
+/// A function to be called on a script.
+struct ScriptCall {
+    script: usize,
+    function: String,
+    args: Vec<Dynamic>,
+}
+
+struct ScriptTimer {
+    script: usize,
+    function: String,
+    timer: Timer,
+}
+
+/// Loaded scripts and their engine.
+#[derive(Resource)]
+pub struct Scripts {
+    path: PathBuf,
+    engine: Engine,
+    shared: Arc<Mutex<ScriptShared>>,
+    scripts: Vec<Script>,
+    timers: Vec<ScriptTimer>,
+    pending: Vec<ScriptCall>,
+}
+
+impl Scripts {
+    fn new(path: PathBuf) -> Self {
+        let shared = Arc::new(Mutex::new(ScriptShared::default()));
+
+        Self {
+            path,
+            engine: create_engine(&shared),
+            shared,
+            scripts: vec![],
+            timers: vec![],
+            pending: vec![],
+        }
+    }
+
+    /// **Returns** names of loaded scripts, in the order they were loaded.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.scripts.iter().map(|script| script.name.as_str())
+    }
+
+    /// Compiles the given script source and runs its top level statements.
+    ///
+    /// **Returns** why the script failed to load, if it did. Nothing it requested is applied.
+    pub fn load(&mut self, world: &mut World, name: &str, source: &str) -> Result<(), String> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|err| format!("Failed to compile script {name}. Error: {err}"))?;
+
+        self.scripts.push(Script {
+            name: name.to_string(),
+            ast,
+            scope: Scope::new(),
+        });
+
+        let index = self.scripts.len() - 1;
+        let result = self.lend(world, index, |engine, script| {
+            engine
+                .run_ast_with_scope(&mut script.scope, &script.ast)
+                .map(|()| Dynamic::UNIT)
+        });
+
+        result.map(|_| ()).map_err(|err| {
+            self.scripts.pop();
+            format!("Failed to run script {name}. Error: {err}")
+        })
+    }
+
+    /// Queues a call to the given hook on every script which defines it. Arguments are only built
+    /// when there is any.
+    fn queue_hook(&mut self, hook: &str, params: usize, args: impl FnOnce() -> Vec<Dynamic>) {
+        let scripts = self
+            .scripts
+            .iter()
+            .enumerate()
+            .filter(|(_, script)| script.has_fn(hook, params))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        if scripts.is_empty() {
+            return;
+        }
+
+        let args = args();
+        for script in scripts {
+            self.pending.push(ScriptCall {
+                script,
+                function: hook.to_string(),
+                args: args.clone(),
+            });
+        }
+    }
+
+    /// Ticks timers by `delta` and queues a call for each one which finished.
+    fn tick_timers(&mut self, delta: Duration) {
+        for timer in &mut self.timers {
+            if timer.timer.tick(delta).just_finished() {
+                self.pending.push(ScriptCall {
+                    script: timer.script,
+                    function: timer.function.clone(),
+                    args: vec![],
+                });
+            }
+        }
+
+        self.timers
+            .retain(|timer| timer.timer.mode() == TimerMode::Repeating || !timer.timer.finished());
+    }
+
+    /// Calls the given function on a script.
+    ///
+    /// **Returns** what the function returned, or why it failed.
+    fn call(&mut self, world: &mut World, call: ScriptCall) -> Result<Dynamic, String> {
+        let ScriptCall {
+            script,
+            function,
+            args,
+        } = call;
+
+        self.lend(world, script, |engine, script| {
+            let options = CallFnOptions::new().eval_ast(false);
+            engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut script.scope,
+                &script.ast,
+                &function,
+                args,
+            )
+        })
+        .map_err(|err| format!("Failed to call {function}. Error: {err}"))
+    }
+
+    /// Lends the world to API functions while `f` runs on the given script, and applies what the
+    /// script requested afterwards, unless it failed.
+    fn lend(
+        &mut self,
+        world: &mut World,
+        index: usize,
+        f: impl FnOnce(&Engine, &mut Script) -> Result<Dynamic, Box<EvalAltResult>>,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        lock(&self.shared).world = Some(std::mem::take(world));
+
+        let result = f(&self.engine, &mut self.scripts[index]);
+
+        let (lent, requests) = {
+            let mut shared = lock(&self.shared);
+            (shared.world.take(), std::mem::take(&mut shared.requests))
+        };
+        *world = lent.expect("World to be given back by scripts");
+
+        if result.is_ok() {
+            self.apply(world, index, requests);
+        }
+
+        result
+    }
+
+    fn apply(&mut self, world: &mut World, index: usize, requests: ScriptRequests) {
+        let ScriptRequests {
+            edits,
+            chat,
+            commands,
+            timers,
+        } = requests;
+
+        if !edits.is_empty() {
+            world.send_event(ApplyChunkDiff(edits));
+        }
+
+        if let Some(clients) = world.get_resource::<Clients>() {
+            for (target, text) in chat {
+                let msg = ChatBroadcast { sender: None, text };
+                match target {
+                    Some(id) => {
+                        if let Some(client) = clients.get(&id) {
+                            let _ = client.channel().send(msg);
+                        }
+                    }
+                    None => {
+                        for client in clients.values() {
+                            let _ = client.channel().send(msg.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        let script = &self.scripts[index];
+        for (name, function) in commands {
+            if !script.has_fn(&function, 2) {
+                warn!(
+                    "Script {} has no function {function}(player, args) for command {name}.",
+                    script.name
+                );
+                continue;
+            }
+
+            if world
+                .get_resource::<ConsoleCommands>()
+                .is_some_and(|console| console.contains_key(&name))
+            {
+                warn!("Script {} can't replace command {name}.", script.name);
+                continue;
+            }
+
+            let id = world.register_system(script_command(index, function));
+            world
+                .get_resource_or_insert_with(ConsoleCommands::default)
+                .insert(name, id);
+        }
+
+        for (secs, mode, function) in timers {
+            if !script.has_fn(&function, 0) {
+                warn!(
+                    "Script {} has no function {function}() for timer.",
+                    script.name
+                );
+                continue;
+            }
+
+            self.timers.push(ScriptTimer {
+                script: index,
+                function,
+                timer: Timer::from_seconds(secs as f32, mode),
+            });
+        }
+    }
+}
+
+fn lock(shared: &Mutex<ScriptShared>) -> MutexGuard<'_, ScriptShared> {
+    shared.lock().expect("Script state to not be poisoned")
+}
+
+fn player_id(id: ClientId) -> Dynamic {
+    INT::from(u32::from(id)).into()
+}
+
+/// **Returns** the world coordinates given by a script, if they are inside world height.
+fn world_voxel(x: INT, y: INT, z: INT) -> Result<IVec3, Box<EvalAltResult>> {
+    let to_i32 = |v: INT| i32::try_from(v).ok();
+    match (to_i32(x), to_i32(y), to_i32(z)) {
+        (Some(x), Some(y), Some(z)) if (0..=chunk::Y_END).contains(&y) => Ok(IVec3::new(x, y, z)),
+        _ => Err(format!("Voxel ({x}, {y}, {z}) is outside world").into()),
+    }
+}
+
+fn kind_name(kind: voxel::Kind) -> Dynamic {
+    let id = u16::from(kind);
+    KindsDescs::get()
+        .descriptions
+        .iter()
+        .find(|desc| desc.id == id)
+        .map_or(Dynamic::UNIT, |desc| desc.name.clone().into())
+}
+
+/// Creates a sandboxed engine with the script API registered on it.
+fn create_engine(shared: &Arc<Mutex<ScriptShared>>) -> Engine {
+    let mut engine = Engine::new();
+
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_SCRIPT_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(MAX_CHAT_LEN * 16)
+        .set_max_array_size(MAX_SCRIPT_EDITS * 4)
+        .on_print(|text| info!("[script] {text}"))
+        .on_debug(|text, source, pos| debug!("[script] {} {pos}: {text}", source.unwrap_or("")));
+
+    let state = shared.clone();
+    engine.register_fn(
+        "get_voxel",
+        move |x: INT, y: INT, z: INT| -> Result<Dynamic, Box<EvalAltResult>> {
+            let (chunk, voxel) = terraform::split_world(world_voxel(x, y, z)?);
+            let shared = lock(&state);
+            let Some(world) = shared.world.as_ref() else {
+                return Ok(Dynamic::UNIT);
+            };
+
+            let kind = world
+                .get_resource::<ChunkMap>()
+                .and_then(|map| map.get(&chunk))
+                .and_then(|&entity| world.get::<ChunkKind>(entity))
+                .map(|kind| kind.get(voxel));
+
+            Ok(kind.map_or(Dynamic::UNIT, kind_name))
+        },
+    );
+
+    let state = shared.clone();
+    engine.register_fn(
+        "set_voxel",
+        move |x: INT, y: INT, z: INT, kind: &str| -> Result<(), Box<EvalAltResult>> {
+            let world = world_voxel(x, y, z)?;
+            let kind =
+                voxel::Kind::by_name(kind).ok_or_else(|| format!("Unknown voxel kind: {kind}"))?;
+
+            let mut shared = lock(&state);
+            if shared.requests.edits.len() >= MAX_SCRIPT_EDITS {
+                return Err(format!(
+                    "Scripts can't change more than {MAX_SCRIPT_EDITS} voxels at once"
+                )
+                .into());
+            }
+
+            shared.requests.edits.set_world(world, kind);
+            Ok(())
+        },
+    );
+
+    let state = shared.clone();
+    engine.register_fn("players", move || -> Array {
+        lock(&state)
+            .world
+            .as_ref()
+            .and_then(|world| world.get_resource::<Clients>())
+            .map(|clients| clients.keys().map(|&id| player_id(id)).collect())
+            .unwrap_or_default()
+    });
+
+    let state = shared.clone();
+    engine.register_fn("broadcast", move |text: &str| {
+        let text = text.chars().take(MAX_CHAT_LEN).collect();
+        lock(&state).requests.chat.push((None, text));
+    });
+
+    let state = shared.clone();
+    engine.register_fn(
+        "send",
+        move |player: INT, text: &str| -> Result<(), Box<EvalAltResult>> {
+            let id = u32::try_from(player).map_err(|_| format!("Invalid player: {player}"))?;
+            let text = text.chars().take(MAX_CHAT_LEN).collect();
+            lock(&state)
+                .requests
+                .chat
+                .push((Some(ClientId::from(id)), text));
+            Ok(())
+        },
+    );
+
+    let state = shared.clone();
+    engine.register_fn(
+        "register_command",
+        move |name: &str, function: &str| -> Result<(), Box<EvalAltResult>> {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(format!("Invalid command name: {name}").into());
+            }
+
+            lock(&state)
+                .requests
+                .commands
+                .push((name.to_string(), function.to_string()));
+            Ok(())
+        },
+    );
+
+    for (name, mode) in [("after", TimerMode::Once), ("every", TimerMode::Repeating)] {
+        let state = shared.clone();
+        engine.register_fn(
+            name,
+            move |secs: FLOAT, function: &str| -> Result<(), Box<EvalAltResult>> {
+                let min = if mode == TimerMode::Repeating {
+                    MIN_REPEAT_SECS
+                } else {
+                    0.0
+                };
+                if !secs.is_finite() || secs <= min {
+                    return Err(format!("Invalid timer duration: {secs}").into());
+                }
+
+                lock(&state)
+                    .requests
+                    .timers
+                    .push((secs, mode, function.to_string()));
+                Ok(())
+            },
+        );
+    }
+
+    engine
+}
+
+/// **Returns** a console command system which calls the given script function.
+fn script_command(
+    script: usize,
+    function: String,
+) -> impl FnMut(In<ConsoleCommand>, &mut World) -> CommandResult {
+    move |In(command), world| {
+        let args = command
+            .args()
+            .map(|arg| Dynamic::from(arg.to_string()))
+            .collect::<Array>();
+
+        let call = ScriptCall {
+            script,
+            function: function.clone(),
+            args: vec![player_id(command.sender), args.into()],
+        };
+
+        world.resource_scope(|world, mut scripts: Mut<Scripts>| {
+            let reply = scripts.call(world, call)?;
+            Ok(if reply.is_unit() {
+                String::new()
+            } else {
+                reply.to_string()
+            })
+        })
+    }
+}
+
+fn load_scripts(world: &mut World) {
+    world.resource_scope(|world, mut scripts: Mut<Scripts>| {
+        let path = scripts.path.clone();
+        let Ok(entries) = std::fs::read_dir(&path) else {
+            info!("No scripts folder at {path:?}. Skipping scripts.");
+            return;
+        };
+
+        let mut files = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect::<Vec<_>>();
+        files.sort();
+
+        for file in files {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            let result = std::fs::read_to_string(&file)
+                .map_err(|err| format!("Failed to read script {name}. Error: {err}"))
+                .and_then(|source| scripts.load(world, &name, &source));
+
+            match result {
+                Ok(()) => info!("Script {name} loaded."),
+                Err(err) => error!("{err}"),
+            }
+        }
+    });
+}
+
+fn queue_hooks(
+    mut scripts: ResMut<Scripts>,
+    mut joined: EventReader<PlayerJoined>,
+    mut left: EventReader<PlayerLeft>,
+    mut edited: EventReader<ChunkEdited>,
+) {
+    for &PlayerJoined { client } in joined.read() {
+        scripts.queue_hook("on_player_joined", 1, || vec![player_id(client)]);
+    }
+
+    for &PlayerLeft { client } in left.read() {
+        scripts.queue_hook("on_player_left", 1, || vec![player_id(client)]);
+    }
+
+    for ChunkEdited { chunk, voxels } in edited.read() {
+        scripts.queue_hook("on_chunk_edited", 3, || {
+            let voxels = voxels
+                .iter()
+                .map(|&voxel| {
+                    let world = voxel::to_world(voxel, *chunk).as_ivec3();
+                    let coords: Array = vec![
+                        INT::from(world.x).into(),
+                        INT::from(world.y).into(),
+                        INT::from(world.z).into(),
+                    ];
+                    Dynamic::from(coords)
+                })
+                .collect::<Array>();
+
+            vec![
+                INT::from(chunk.x()).into(),
+                INT::from(chunk.z()).into(),
+                voxels.into(),
+            ]
+        });
+    }
+}
+
+fn run_scripts(world: &mut World) {
+    world.resource_scope(|world, mut scripts: Mut<Scripts>| {
+        let delta = world.resource::<Time>().delta();
+        scripts.tick_timers(delta);
+
+        for call in std::mem::take(&mut scripts.pending) {
+            let name = scripts.scripts[call.script].name.clone();
+            if let Err(err) = scripts.call(world, call) {
+                error!("[{name}] {err}");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{app::ScheduleRunnerPlugin, time::TimeUpdateStrategy};
+    use projekto_core::chunk::Chunk;
+    use projekto_messages::{ClientMessage, ServerMessage};
+    use projekto_proto::{Channel, Client};
+
+    use crate::{
+        bundle::{ChunkBundle, ChunkLocal},
+        chat::{self, RegisterConsoleCommand},
+    };
+
+    use super::*;
+
+    fn setup_app() -> (App, Channel<ClientMessage, ServerMessage>) {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_once()),
+            ScriptingPlugin {
+                path: "missing_scripts_folder".into(),
+            },
+        ))
+        .init_resource::<Clients>()
+        .add_event::<PlayerJoined>()
+        .add_event::<PlayerLeft>()
+        .add_event::<ChunkEdited>()
+        .add_event::<ApplyChunkDiff>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            100,
+        )));
+
+        let mut bundle = ChunkBundle {
+            local: ChunkLocal(Chunk::new(0, 0)),
+            ..Default::default()
+        };
+        bundle
+            .kind
+            .0
+            .set(IVec3::new(1, 1, 1), voxel::Kind::by_name("Rock").unwrap());
+        let entity = app.world.spawn(bundle).id();
+        app.world
+            .insert_resource(ChunkMap([(Chunk::new(0, 0), entity)].into_iter().collect()));
+
+        let (client, channel) = Client::loopback(1);
+        app.world
+            .resource_mut::<Clients>()
+            .insert(client.id(), client);
+
+        (app, channel)
+    }
+
+    fn load(app: &mut App, source: &str) -> Result<(), String> {
+        app.world
+            .resource_scope(|world, mut scripts: Mut<Scripts>| scripts.load(world, "test", source))
+    }
+
+    fn chat(channel: &Channel<ClientMessage, ServerMessage>) -> Vec<String> {
+        channel
+            .try_recv_all()
+            .into_iter()
+            .filter_map(|boxed| boxed.downcast::<ChatBroadcast>().ok())
+            .map(|msg| msg.text)
+            .collect()
+    }
+
+    #[test]
+    fn hooks_and_timers() {
+        // arrange
+        let (mut app, channel) = setup_app();
+        load(
+            &mut app,
+            r#"
+                after(0.15, "late");
+                fn late() { broadcast("late"); }
+                fn on_player_joined(player) { send(player, "Welcome " + player); }
+            "#,
+        )
+        .unwrap();
+
+        // act
+        app.world.send_event(PlayerJoined {
+            client: ClientId::from(1),
+        });
+        app.update();
+
+        // assert
+        assert_eq!(chat(&channel), vec!["Welcome 1".to_string()]);
+
+        // act
+        app.update();
+        app.update();
+        app.update();
+
+        // assert
+        assert_eq!(
+            chat(&channel),
+            vec!["late".to_string()],
+            "Timer should run only once"
+        );
+    }
+
+    #[test]
+    fn voxels_are_validated() {
+        // arrange
+        let (mut app, _channel) = setup_app();
+
+        // act
+        let loaded = load(
+            &mut app,
+            r#"
+                if get_voxel(1, 1, 1) == "Rock" && get_voxel(100, 1, 100) == () {
+                    set_voxel(2, 2, 2, "Dirt");
+                }
+            "#,
+        );
+
+        // assert
+        assert_eq!(loaded, Ok(()));
+        let events = app.world.resource::<Events<ApplyChunkDiff>>();
+        let diffs = events
+            .get_reader()
+            .read(events)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].0.changes(Chunk::new(0, 0)),
+            &[(IVec3::new(2, 2, 2), voxel::Kind::by_name("Dirt").unwrap())]
+        );
+
+        // assert
+        assert!(load(&mut app, r#"set_voxel(0, 0, 0, "Unobtainium");"#).is_err());
+        assert!(load(&mut app, r#"set_voxel(0, -1, 0, "Dirt");"#).is_err());
+        assert!(
+            load(&mut app, "loop {}").is_err(),
+            "Endless scripts should be stopped"
+        );
+        assert_eq!(
+            app.world.resource::<Scripts>().names().count(),
+            1,
+            "Failed scripts shouldn't be kept"
+        );
+    }
+
+    #[test]
+    fn command_is_registered() {
+        // arrange
+        let (mut app, _channel) = setup_app();
+        app.add_console_command("help", |In(_): In<ConsoleCommand>| -> CommandResult {
+            Ok(String::new())
+        });
+
+        // act
+        load(
+            &mut app,
+            r#"
+                register_command("greet", "greet");
+                register_command("help", "greet");
+                fn greet(player, args) { "Hi " + args[0] + " from " + player }
+            "#,
+        )
+        .unwrap();
+
+        // assert
+        let run = |app: &mut App, line: &str| {
+            let command = ConsoleCommand::parse(ClientId::from(1), line).unwrap();
+            chat::dispatch_command(&mut app.world, command)
+        };
+        assert_eq!(run(&mut app, "/greet Bob"), Ok("Hi Bob from 1".to_string()));
+        assert_eq!(
+            run(&mut app, "/help"),
+            Ok(String::new()),
+            "Scripts shouldn't replace existing commands"
+        );
+    }
+}
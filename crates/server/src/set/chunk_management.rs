@@ -1,29 +1,51 @@
-use bevy::prelude::*;
-use projekto_core::chunk::Chunk;
+use bevy::{prelude::*, utils::HashSet};
+use projekto_core::chunk::{self, Chunk};
 
 use crate::{
     asset::ChunkAsset,
     bundle::{
-        ChunkBundle, ChunkFacesOcclusion, ChunkFacesSoftLight, ChunkKind, ChunkLight, ChunkLocal,
-        ChunkMap, ChunkVertex,
+        ChunkBundle, ChunkColumns, ChunkContentHash, ChunkDecorations, ChunkFacesOcclusion,
+        ChunkFacesSoftLight, ChunkKind, ChunkLight, ChunkLiquidVertex, ChunkLocal, ChunkMap,
+        ChunkMeshValidity, ChunkNeighbors, ChunkVertex, ChunkVertexHash,
     },
+    error::{LoadError, Quarantine, ServerError},
+    gen::GenStage,
     WorldSet,
 };
 
+use super::{Landscape, LightRecomputeTask};
+
 pub struct ChunkManagementPlugin;
 
 impl Plugin for ChunkManagementPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ChunkMap>()
+            .init_resource::<Quarantine>()
+            // Chunk components are registered, so they can be browsed by inspectors.
+            .register_type::<ChunkKind>()
+            .register_type::<ChunkLight>()
+            .register_type::<ChunkLocal>()
+            .register_type::<ChunkFacesOcclusion>()
+            .register_type::<ChunkFacesSoftLight>()
+            .register_type::<ChunkVertex>()
+            .register_type::<ChunkLiquidVertex>()
+            .register_type::<ChunkMeshValidity>()
+            .register_type::<ChunkVertexHash>()
+            .register_type::<ChunkContentHash>()
+            .register_type::<ChunkNeighbors>()
+            .register_type::<ChunkColumns>()
+            .register_type::<ChunkDecorations>()
             .add_event::<ChunkUnload>()
             .add_event::<ChunkLoad>()
             .add_event::<ChunkGen>()
+            .add_event::<NeighborhoodChanged>()
             .add_systems(
                 Update,
                 (
                     chunks_unload.run_if(on_event::<ChunkUnload>()),
                     chunks_load.run_if(on_event::<ChunkLoad>()),
                     chunks_spawn.run_if(any_chunk_to_spawn),
+                    link_neighbors,
                 )
                     .chain()
                     .in_set(WorldSet::ChunkManagement),
@@ -40,17 +62,25 @@ pub struct ChunkLoad(pub Chunk);
 #[derive(Event, Debug, Clone, Copy)]
 pub struct ChunkGen(pub Chunk);
 
+/// Sent when a chunk gains or loses a neighbor, so its borders can be updated.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct NeighborhoodChanged {
+    pub chunk: Chunk,
+    pub entity: Entity,
+}
+
 fn chunks_unload(
     mut commands: Commands,
     mut chunk_map: ResMut<ChunkMap>,
     mut reader: EventReader<ChunkUnload>,
+    quarantine: Res<Quarantine>,
 ) {
     let mut count = 0;
     reader.read().for_each(|evt| {
         if let Some(entity) = chunk_map.remove(&evt.0) {
             commands.entity(entity).despawn();
             count += 1;
-        } else {
+        } else if !quarantine.contains(evt.0) {
             let local = evt.0;
             warn!("Chunk {local} entity not found.");
         }
@@ -62,10 +92,24 @@ fn chunks_load(
     mut commands: Commands,
     mut reader: EventReader<ChunkLoad>,
     asset_server: Res<AssetServer>,
+    q_loading: Query<&Handle<ChunkAsset>, Without<ChunkLocal>>,
+    quarantine: Res<Quarantine>,
 ) {
+    // Landscape may change while chunks are still loading, which requests them again. Loading
+    // the same asset twice returns the same handle, so skip it, since it can be spawned only once.
+    let mut loading = q_loading.iter().map(Handle::id).collect::<HashSet<_>>();
+
+    // All chunks requested on the same frame are loaded at once, each one on its own asset task,
+    // so there is no need to batch them, since chunks aren't grouped on region files.
     for &ChunkLoad(chunk) in reader.read() {
+        if quarantine.contains(chunk) {
+            continue;
+        }
+
         let handle = asset_server.load::<ChunkAsset>(chunk.path());
-        commands.spawn(handle);
+        if loading.insert(handle.id()) {
+            commands.spawn(handle);
+        }
     }
 }
 
@@ -78,53 +122,98 @@ fn chunks_spawn(
     mut chunk_map: ResMut<ChunkMap>,
     asset_server: Res<AssetServer>,
     mut assets: ResMut<Assets<ChunkAsset>>,
+    landscape: Option<Res<Landscape>>,
     q: Query<(Entity, &Handle<ChunkAsset>), Without<ChunkLocal>>,
+    mut quarantine: ResMut<Quarantine>,
 ) {
     let mut count = 0;
     for (entity, handle) in &q {
-        let loaded = match asset_server.load_state(handle) {
+        // Handles are only created by `chunks_load`, which always loads them from a path.
+        let Some(requested) = handle.path().map(|path| Chunk::from_path(path.path())) else {
+            error!("Chunk asset {:?} has no path. Skipping it.", handle.id());
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let result = match asset_server.load_state(handle) {
             bevy::asset::LoadState::Loading => continue,
-            bevy::asset::LoadState::NotLoaded => {
-                let path = handle.path().expect("All chunk assets must have a path");
-                warn!("Chunk not loaded: {path:?}");
-                false
+            bevy::asset::LoadState::NotLoaded => Err(LoadError::NotLoaded),
+            bevy::asset::LoadState::Failed => Err(LoadError::Failed),
+            bevy::asset::LoadState::Loaded => match assets.remove(handle) {
+                Some(asset) if asset.chunk == requested => Ok(asset),
+                Some(asset) => Err(LoadError::Mismatch(asset.chunk)),
+                None => Err(LoadError::Missing),
+            },
+        };
+
+        let asset = match result {
+            Ok(asset) => asset,
+            Err(source) => {
+                quarantine.insert(ServerError::Load {
+                    chunk: requested,
+                    source,
+                });
+                commands.entity(entity).despawn();
+                continue;
             }
-            bevy::asset::LoadState::Loaded => true,
-            bevy::asset::LoadState::Failed => false,
         };
 
-        if loaded {
-            let ChunkAsset {
-                chunk,
-                kind,
-                light,
-                occlusion,
-                soft_light,
-                vertex,
-            } = assets.remove(handle).expect("Chunk asset exists");
-
-            let entity = commands
-                .spawn((
-                    ChunkBundle {
-                        kind: ChunkKind(kind),
-                        light: ChunkLight(light),
-                        local: ChunkLocal(chunk),
-                        occlusion: ChunkFacesOcclusion(occlusion),
-                        soft_light: ChunkFacesSoftLight(soft_light),
-                        vertex: ChunkVertex(vertex),
-                    },
-                    Name::new(format!("Server Chunk {chunk:?}")),
-                ))
-                .id();
+        let ChunkAsset {
+            chunk,
+            hash,
+            stage,
+            light_validity,
+            kind,
+            light,
+            occlusion,
+            soft_light,
+            mesh_validity,
+            vertex,
+            liquid_vertex,
+        } = asset;
 
-            if chunk_map.insert(chunk, entity).is_some() {
-                warn!("Chunk {chunk:?} overwritten an existing entity on map.");
-            }
+        // Handle entity is no longer needed, whether the chunk is spawned or not.
+        commands.entity(entity).despawn();
 
-            count += 1;
+        // Landscape may have moved away while the chunk was loading. Since only chunks which
+        // left landscape are unloaded, this one would be never unloaded.
+        if landscape.as_ref().is_some_and(|l| !l.contains(chunk)) {
+            trace!("Chunk {chunk:?} left landscape while loading. Skipping it.");
+            continue;
         }
 
-        commands.entity(entity).despawn();
+        // Chunks which weren't lit by generator have no light to keep, whatever validity says.
+        let light_valid = stage >= GenStage::Lit && light_validity.is_valid(&kind);
+        let recompute_light = (!light_valid).then(|| LightRecomputeTask::spawn(kind.clone()));
+
+        let mut chunk_entity = commands.spawn((
+            ChunkBundle {
+                kind: ChunkKind(kind.into()),
+                light: ChunkLight(light),
+                local: ChunkLocal(chunk),
+                occlusion: ChunkFacesOcclusion(occlusion),
+                soft_light: ChunkFacesSoftLight(soft_light),
+                vertex: ChunkVertex(vertex),
+                liquid_vertex: ChunkLiquidVertex(liquid_vertex),
+                mesh_validity: ChunkMeshValidity(mesh_validity),
+                content_hash: ChunkContentHash(hash),
+                ..Default::default()
+            },
+            Name::new(format!("Server Chunk {chunk:?}")),
+        ));
+
+        if let Some(task) = recompute_light {
+            debug!("Chunk {chunk:?} light is outdated. Recomputing it.");
+            chunk_entity.insert(task);
+        }
+
+        let entity = chunk_entity.id();
+
+        if chunk_map.insert(chunk, entity).is_some() {
+            warn!("Chunk {chunk:?} overwritten an existing entity on map.");
+        }
+
+        count += 1;
     }
 
     if count > 0 {
@@ -132,6 +221,47 @@ fn chunks_spawn(
     }
 }
 
+/// Updates [`ChunkNeighbors`] of spawned chunks and of neighbors of spawned or despawned ones.
+fn link_neighbors(
+    chunk_map: Res<ChunkMap>,
+    mut reader: EventReader<ChunkUnload>,
+    q_added: Query<&ChunkLocal, Added<ChunkLocal>>,
+    mut q_neighbors: Query<&mut ChunkNeighbors>,
+    mut writer: EventWriter<NeighborhoodChanged>,
+) {
+    let affected = q_added
+        .iter()
+        .map(|local| **local)
+        .chain(reader.read().map(|evt| evt.0))
+        .flat_map(|chunk| {
+            std::iter::once(chunk).chain(chunk::SIDES.map(|s| chunk.neighbor(s.dir())))
+        })
+        .collect::<HashSet<_>>();
+
+    let mut count = 0;
+    for chunk in affected {
+        let Some(&entity) = chunk_map.get(&chunk) else {
+            continue;
+        };
+
+        let links = chunk::SIDES.map(|side| chunk_map.get(&chunk.neighbor(side.dir())).copied());
+        let Ok(mut neighbors) = q_neighbors.get_mut(entity) else {
+            error!("Chunk {chunk} entity {entity:?} has no neighbors. Skipping it.");
+            continue;
+        };
+
+        if neighbors.0 != links {
+            neighbors.0 = links;
+            writer.send(NeighborhoodChanged { chunk, entity });
+            count += 1;
+        }
+    }
+
+    if count > 0 {
+        trace!("[link_neighbors] {count} chunks neighborhood changed.");
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use bevy::app::ScheduleRunnerPlugin;
@@ -192,8 +322,58 @@ fn chunks_spawn(
 //             .unwrap();
 //
 //         assert!(
-//             kind.iter().any(|kind| kind.is_opaque()),
+//             kind.iter().any(|kind| kind.is_solid()),
 //             "Every chunk should have at least on solid block"
 //         );
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_neighbors() {
+        // arrange
+        let mut app = App::new();
+        app.init_resource::<ChunkMap>()
+            .init_resource::<Quarantine>()
+            .add_event::<ChunkUnload>()
+            .add_event::<NeighborhoodChanged>()
+            .add_systems(Update, (chunks_unload, super::link_neighbors).chain());
+
+        let spawn = |app: &mut App, chunk: Chunk| {
+            let entity = app
+                .world
+                .spawn(ChunkBundle {
+                    local: ChunkLocal(chunk),
+                    ..Default::default()
+                })
+                .id();
+            app.world.resource_mut::<ChunkMap>().insert(chunk, entity);
+            entity
+        };
+        let neighbors =
+            |app: &App, entity: Entity| app.world.get::<ChunkNeighbors>(entity).unwrap().0;
+
+        let center = spawn(&mut app, Chunk::new(0, 0));
+        let right = spawn(&mut app, Chunk::new(1, 0));
+
+        // act
+        app.update();
+
+        // assert
+        let right_side = chunk::ChunkSide::Right.index();
+        let left_side = chunk::ChunkSide::Left.index();
+        assert_eq!(neighbors(&app, center)[right_side], Some(right));
+        assert_eq!(neighbors(&app, right)[left_side], Some(center));
+        assert_eq!(app.world.resource::<Events<NeighborhoodChanged>>().len(), 2);
+
+        // act
+        app.world.send_event(ChunkUnload(Chunk::new(1, 0)));
+        app.update();
+
+        // assert
+        assert_eq!(neighbors(&app, center), [None; chunk::SIDE_COUNT]);
+    }
+}
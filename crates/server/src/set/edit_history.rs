@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::{
+    chunk::Chunk,
+    voxel::{self, Voxel},
+};
+use projekto_proto::ClientId;
+
+/// Max edits each player is able to undo. Older edits are forgotten.
+const MAX_HISTORY: usize = 256;
+
+/// A voxel kind change, made by a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct VoxelEdit {
+    pub chunk: Chunk,
+    pub voxel: Voxel,
+    pub before: voxel::Kind,
+    pub after: voxel::Kind,
+}
+
+impl VoxelEdit {
+    /// **Returns** an edit which reverts this one.
+    pub fn inverse(&self) -> Self {
+        Self {
+            before: self.after,
+            after: self.before,
+            ..*self
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PlayerHistory {
+    undo: VecDeque<VoxelEdit>,
+    redo: Vec<VoxelEdit>,
+}
+
+/// Voxel edits made by each player, so they can be undone and redone.
+#[derive(Resource, Debug, Default)]
+pub(crate) struct EditHistory(HashMap<ClientId, PlayerHistory>);
+
+impl EditHistory {
+    /// Records an edit made by the given player. Edits which were undone can't be redone anymore.
+    pub fn record(&mut self, id: ClientId, edit: VoxelEdit) {
+        let history = self.0.entry(id).or_default();
+
+        history.redo.clear();
+        history.undo.push_back(edit);
+
+        if history.undo.len() > MAX_HISTORY {
+            history.undo.pop_front();
+        }
+    }
+
+    /// Takes up to `count` of the last edits made by the given player.
+    ///
+    /// **Returns** edits which reverts them, starting from the most recent one.
+    pub fn undo(&mut self, id: ClientId, count: usize) -> Vec<VoxelEdit> {
+        let Some(history) = self.0.get_mut(&id) else {
+            return vec![];
+        };
+
+        let count = count.min(history.undo.len());
+        let undone = history
+            .undo
+            .drain(history.undo.len() - count..)
+            .rev()
+            .collect::<Vec<_>>();
+
+        history.redo.extend(undone.iter().copied());
+
+        undone.iter().map(VoxelEdit::inverse).collect()
+    }
+
+    /// Takes up to `count` of the last edits undone by the given player.
+    ///
+    /// **Returns** edits which reapplies them, starting from the least recent one.
+    pub fn redo(&mut self, id: ClientId, count: usize) -> Vec<VoxelEdit> {
+        let Some(history) = self.0.get_mut(&id) else {
+            return vec![];
+        };
+
+        let count = count.min(history.redo.len());
+        let redone = history
+            .redo
+            .drain(history.redo.len() - count..)
+            .rev()
+            .collect::<Vec<_>>();
+
+        history.undo.extend(redone.iter().copied());
+
+        redone
+    }
+
+    /// Forgets history of players which aren't connected anymore.
+    pub fn retain(&mut self, mut f: impl FnMut(&ClientId) -> bool) {
+        self.0.retain(|id, _| f(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(x: i32, after: u16) -> VoxelEdit {
+        VoxelEdit {
+            chunk: Chunk::new(0, 0),
+            voxel: Voxel::new(x, 0, 0),
+            before: voxel::Kind::NONE,
+            after: voxel::Kind::id(after),
+        }
+    }
+
+    #[test]
+    fn undo_redo() {
+        // arrange
+        let id = ClientId::default();
+        let mut history = EditHistory::default();
+        history.record(id, edit(0, 1));
+        history.record(id, edit(1, 2));
+        history.record(id, edit(2, 3));
+
+        // act
+        let undone = history.undo(id, 2);
+
+        // assert
+        assert_eq!(undone, vec![edit(2, 3).inverse(), edit(1, 2).inverse()]);
+
+        // act
+        let redone = history.redo(id, 5);
+
+        // assert
+        assert_eq!(redone, vec![edit(1, 2), edit(2, 3)]);
+        assert_eq!(
+            history.undo(id, 5).len(),
+            3,
+            "Redone edits should be undone again"
+        );
+    }
+
+    #[test]
+    fn record_clears_redo() {
+        // arrange
+        let id = ClientId::default();
+        let mut history = EditHistory::default();
+        history.record(id, edit(0, 1));
+        history.undo(id, 1);
+
+        // act
+        history.record(id, edit(1, 2));
+
+        // assert
+        assert!(history.redo(id, 1).is_empty());
+    }
+
+    #[test]
+    fn history_limit() {
+        // arrange
+        let id = ClientId::default();
+        let mut history = EditHistory::default();
+
+        // act
+        for x in 0..MAX_HISTORY + 10 {
+            history.record(id, edit(x as i32, 1));
+        }
+
+        // assert
+        let undone = history.undo(id, usize::MAX);
+        assert_eq!(undone.len(), MAX_HISTORY);
+        assert_eq!(undone.last(), Some(&edit(10, 1).inverse()));
+    }
+}
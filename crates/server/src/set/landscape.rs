@@ -1,69 +1,240 @@
-use bevy::prelude::*;
-use projekto_core::chunk::Chunk;
+use std::time::Duration;
 
-use crate::{bundle::ChunkMap, WorldSet};
+use bevy::{prelude::*, time::common_conditions::on_timer, utils::HashSet};
+use projekto_core::chunk::{self, Chunk};
 
-use super::{ChunkLoad, ChunkUnload};
+use crate::{
+    asset::ChunkAsset,
+    bundle::{ChunkLocal, ChunkMap},
+    error::Quarantine,
+    TickBudget, WorldSet,
+};
+
+use super::{
+    ChunkLoad, ChunkUnload, FacesOcclusionTask, LightRecomputeTask, MeshingTask, PlayerTransforms,
+    PlayerVelocities,
+};
+
+/// Time, in seconds, which landscape is loaded ahead of player movement.
+const PREFETCH_LEAD_SECS: f32 = 2.0;
+/// Max number of chunks loaded ahead of player movement, on each axis.
+const MAX_PREFETCH: i32 = 4;
+/// Default value of [`PremeshRing`].
+const DEFAULT_PREMESH_RING: u8 = 2;
+/// Interval, in seconds, which loaded and loading chunks are swept against landscape.
+const SWEEP_INTERVAL_SECS: u64 = 1;
 
 pub(crate) struct LandscapePlugin;
 
 impl Plugin for LandscapePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (update_landscape.run_if(resource_changed_or_removed::<Landscape>()),)
-                .in_set(WorldSet::LandscapeUpdate),
-        );
+        app.init_resource::<PremeshRing>()
+            .register_type::<Landscape>()
+            .register_type::<PremeshRing>()
+            .add_systems(
+                Update,
+                (
+                    grow_premesh_ring.run_if(resource_exists::<TickBudget>),
+                    prefetch_landscape.run_if(resource_exists::<PlayerVelocities>),
+                    update_landscape.run_if(resource_changed_or_removed::<Landscape>()),
+                    // Chunks requested by the delta path aren't loading yet, so skip the sweep
+                    // when landscape was just updated, to not request them twice.
+                    sweep_landscape.run_if(
+                        on_timer(Duration::from_secs(SWEEP_INTERVAL_SECS))
+                            .and_then(not(resource_changed_or_removed::<Landscape>())),
+                    ),
+                )
+                    .chain()
+                    .in_set(WorldSet::LandscapeUpdate),
+            );
+    }
+}
+
+/// Max number of chunks, around landscape radius, which are loaded and meshed ahead of time while
+/// server is idle. Those chunks aren't sent to clients until requested, so expanding render
+/// distance or turning around is instant.
+#[derive(Resource, Debug, Clone, Copy, Deref, DerefMut, Reflect)]
+#[reflect(Resource)]
+pub struct PremeshRing(pub u8);
+
+impl Default for PremeshRing {
+    fn default() -> Self {
+        Self(DEFAULT_PREMESH_RING)
     }
 }
 
 #[derive(Resource, Default, Debug, Clone, Copy, Reflect)]
+#[reflect(Resource)]
 pub struct Landscape {
     pub center: IVec2,
     pub radius: u8,
+    /// Number of chunks, on each axis, which landscape is extended towards player movement. See
+    /// [`prefetch_landscape`].
+    pub prefetch: IVec2,
+    /// Number of chunks loaded around radius ahead of time. See [`PremeshRing`].
+    pub ring: u8,
+}
+
+impl Landscape {
+    /// **Returns** `true` if the given chunk is inside this landscape, including chunks loaded
+    /// ahead of time.
+    pub fn contains(&self, chunk: Chunk) -> bool {
+        let (min, max) = self.bounds();
+        let chunk = chunk.xz();
+        chunk.cmpge(min).all() && chunk.cmple(max).all()
+    }
+
+    /// **Returns** `true` if the given chunk is inside the radius requested by clients, so chunks
+    /// loaded ahead of time aren't sent until requested.
+    pub fn is_visible(&self, chunk: Chunk) -> bool {
+        super::is_within_radius(self.center, chunk, self.radius)
+    }
+
+    /// **Returns** min and max chunk coordinates inside this landscape, including chunks loaded
+    /// ahead of time.
+    fn bounds(&self) -> (IVec2, IVec2) {
+        let radius = IVec2::splat(self.radius as i32 + self.ring as i32);
+        (
+            self.center - radius + self.prefetch.min(IVec2::ZERO),
+            self.center + radius + self.prefetch.max(IVec2::ZERO),
+        )
+    }
+
+    /// **Returns** chunks inside this landscape which aren't inside the `other` one.
+    ///
+    /// Only chunks on the difference are visited, so moving a landscape by a single chunk only
+    /// visits the border chunks which entered or left it.
+    fn difference(&self, other: Option<&Landscape>) -> Vec<Chunk> {
+        let (min, max) = self.bounds();
+        let (min_z, max_z) = (min.y, max.y);
+        let other = other.map(Landscape::bounds);
+
+        (min.x..=max.x)
+            .flat_map(|x| {
+                let other_z = other
+                    .filter(|(other_min, other_max)| (other_min.x..=other_max.x).contains(&x))
+                    .map(|(other_min, other_max)| (other_min.y, other_max.y));
+
+                // Whole column is outside of other landscape, or only the parts of it which are
+                // before and after other landscape column.
+                let (before, after) = match other_z {
+                    Some((other_min_z, other_max_z)) => (
+                        min_z..=max_z.min(other_min_z - 1),
+                        min_z.max(other_max_z + 1)..=max_z,
+                    ),
+                    None => (min_z..=max_z, max_z + 1..=max_z),
+                };
+
+                before.chain(after).map(move |z| Chunk::new(x, z))
+            })
+            .collect()
+    }
+}
+
+/// Grows landscape ring by one chunk each tick which server is idle, up to [`PremeshRing`], so
+/// chunks around landscape radius are loaded and meshed ahead of time.
+fn grow_premesh_ring(
+    landscape: Option<ResMut<Landscape>>,
+    max_ring: Res<PremeshRing>,
+    budget: Res<TickBudget>,
+    q_busy: Query<
+        (),
+        Or<(
+            (With<Handle<ChunkAsset>>, Without<ChunkLocal>),
+            With<FacesOcclusionTask>,
+            With<LightRecomputeTask>,
+            With<MeshingTask>,
+        )>,
+    >,
+) {
+    let Some(mut landscape) = landscape else {
+        return;
+    };
+
+    if landscape.ring > **max_ring {
+        landscape.ring = **max_ring;
+    } else if landscape.ring < **max_ring && budget.has_spare_time() && q_busy.is_empty() {
+        landscape.ring += 1;
+        trace!("[grow_premesh_ring] Premeshing ring {}", landscape.ring);
+    }
+}
+
+/// Extends landscape towards where the player closest to landscape center is moving, so fast
+/// movement doesn't outrun chunk streaming.
+fn prefetch_landscape(
+    landscape: Option<ResMut<Landscape>>,
+    players: Res<PlayerTransforms>,
+    velocities: Res<PlayerVelocities>,
+    time: Res<Time>,
+) {
+    let Some(mut landscape) = landscape else {
+        return;
+    };
+
+    let center: Chunk = landscape.center.into();
+    let velocity = players
+        .iter()
+        .min_by_key(|(_, transform)| {
+            let chunk: Chunk = transform.translation.into();
+            chunk.distance(center).abs().max_element()
+        })
+        .and_then(|(id, _)| velocities.get(id))
+        .map_or(Vec3::ZERO, |velocity| {
+            velocity.current(time.elapsed_seconds())
+        });
+
+    let prefetch = prefetch_offset(velocity);
+    if landscape.prefetch != prefetch {
+        trace!("[prefetch_landscape] Prefetching {prefetch} chunks ahead");
+        landscape.prefetch = prefetch;
+    }
+}
+
+/// **Returns** the number of chunks, on each axis, to load ahead of a player moving with the given
+/// velocity.
+fn prefetch_offset(velocity: Vec3) -> IVec2 {
+    let chunk_size = Vec2::new(chunk::X_AXIS_SIZE as f32, chunk::Z_AXIS_SIZE as f32);
+    let ahead = velocity.xz() * PREFETCH_LEAD_SECS / chunk_size;
+
+    ahead
+        .round()
+        .as_ivec2()
+        .clamp(IVec2::splat(-MAX_PREFETCH), IVec2::splat(MAX_PREFETCH))
 }
 
 fn update_landscape(
     maybe_landscape: Option<Res<Landscape>>,
+    mut last_landscape: Local<Option<Landscape>>,
     chunk_map: Res<ChunkMap>,
     mut load_writer: EventWriter<ChunkLoad>,
     mut unload_writer: EventWriter<ChunkUnload>,
 ) {
     trace!("Updating landscape!");
-    let new_landscape_chunks = {
-        if let Some(landscape) = maybe_landscape {
-            let radius = landscape.radius as i32;
-            let center = landscape.center;
-            let mut chunks = (-radius..=radius)
-                .flat_map(|x| {
-                    (-radius..=radius).map(move |z| Chunk::new(x + center.x, z + center.y))
-                })
-                .collect::<Vec<_>>();
-
-            let center: Chunk = center.into();
-            chunks.sort_by(|a, b| {
-                let a_dist = a.distance(center).length_squared();
-                let b_dist = b.distance(center).length_squared();
-                a_dist.cmp(&b_dist)
-            });
 
-            chunks
-        } else {
-            vec![]
-        }
-    };
+    let landscape = maybe_landscape.map(|landscape| *landscape);
+    let last = std::mem::replace(&mut *last_landscape, landscape);
 
     let mut unloaded = 0;
-    chunk_map
-        .keys()
-        .filter(|&c| !new_landscape_chunks.contains(c))
-        .for_each(|&c| {
+    last.map(|last| last.difference(landscape.as_ref()))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|c| chunk_map.contains_key(c))
+        .for_each(|c| {
             unload_writer.send(ChunkUnload(c));
             unloaded += 1;
         });
 
+    let mut added = landscape
+        .map(|landscape| landscape.difference(last.as_ref()))
+        .unwrap_or_default();
+
+    if let Some(landscape) = landscape {
+        let center: Chunk = landscape.center.into();
+        added.sort_by_key(|c| c.distance(center).length_squared());
+    }
+
     let mut loaded = 0;
-    new_landscape_chunks
+    added
         .into_iter()
         .filter(|c| !chunk_map.contains_key(c))
         .for_each(|c| {
@@ -74,9 +245,77 @@ fn update_landscape(
     trace!("[update_landscape] Unloaded: {unloaded}, loaded: {loaded}");
 }
 
+/// Sweeps loaded and loading chunks against landscape, since [`update_landscape`] only visits
+/// chunks which entered or left it. Chunks outside landscape are unloaded, or their loading is
+/// canceled, and chunks inside it which are neither loaded nor loading are requested again.
+fn sweep_landscape(
+    mut commands: Commands,
+    maybe_landscape: Option<Res<Landscape>>,
+    chunk_map: Res<ChunkMap>,
+    quarantine: Res<Quarantine>,
+    q_loading: Query<(Entity, &Handle<ChunkAsset>), Without<ChunkLocal>>,
+    mut load_writer: EventWriter<ChunkLoad>,
+    mut unload_writer: EventWriter<ChunkUnload>,
+) {
+    let landscape = maybe_landscape.map(|landscape| *landscape);
+    let contains = |chunk| landscape.is_some_and(|l| l.contains(chunk));
+
+    let mut unloaded = 0;
+    for &chunk in chunk_map.keys().filter(|&&c| !contains(c)) {
+        unload_writer.send(ChunkUnload(chunk));
+        unloaded += 1;
+    }
+
+    let mut canceled = 0;
+    let mut loading = HashSet::new();
+    for (entity, handle) in &q_loading {
+        // Handles without path are discarded by `chunks_spawn`.
+        let Some(chunk) = handle.path().map(|path| Chunk::from_path(path.path())) else {
+            continue;
+        };
+
+        if contains(chunk) {
+            loading.insert(chunk);
+        } else {
+            commands.entity(entity).despawn();
+            canceled += 1;
+        }
+    }
+
+    let mut missing = landscape
+        .map(|landscape| {
+            let (min, max) = landscape.bounds();
+            (min.x..=max.x)
+                .flat_map(|x| (min.y..=max.y).map(move |z| Chunk::new(x, z)))
+                .filter(|c| {
+                    !chunk_map.contains_key(c) && !loading.contains(c) && !quarantine.contains(*c)
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if let Some(landscape) = landscape {
+        let center: Chunk = landscape.center.into();
+        missing.sort_by_key(|c| c.distance(center).length_squared());
+    }
+
+    let requested = missing.len();
+    for chunk in missing {
+        load_writer.send(ChunkLoad(chunk));
+    }
+
+    if unloaded + canceled + requested > 0 {
+        debug!(
+            "[sweep_landscape] Unloaded: {unloaded}, canceled: {canceled}, requested: {requested}"
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use bevy::app::ScheduleRunnerPlugin;
+    use bevy::{app::ScheduleRunnerPlugin, ecs::system::RunSystemOnce};
+
+    use crate::set::PlayerVelocity;
 
     use super::*;
 
@@ -155,4 +394,299 @@ mod tests {
         assert_eq!(load_events.len(), 1, "1 Chunk event should be load");
         assert!(unload_events.is_empty(), "No unload events should be sent");
     }
+
+    fn setup_app() -> App {
+        let mut app = App::new();
+
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_once()))
+            .init_resource::<ChunkMap>()
+            .init_resource::<Quarantine>()
+            .add_event::<ChunkLoad>()
+            .add_event::<ChunkUnload>()
+            .add_plugins(super::LandscapePlugin);
+
+        app
+    }
+
+    /// Moves landscape and applies load and unload events to chunk map, like chunk management.
+    ///
+    /// **Returns** loaded and unloaded chunks.
+    fn move_landscape(app: &mut App, center: IVec2, radius: u8) -> (Vec<Chunk>, Vec<Chunk>) {
+        app.world.insert_resource(Landscape {
+            center,
+            radius,
+            ..Default::default()
+        });
+        app.update();
+
+        let loaded = app
+            .world
+            .resource_mut::<Events<ChunkLoad>>()
+            .drain()
+            .map(|ChunkLoad(c)| c)
+            .collect::<Vec<_>>();
+        let unloaded = app
+            .world
+            .resource_mut::<Events<ChunkUnload>>()
+            .drain()
+            .map(|ChunkUnload(c)| c)
+            .collect::<Vec<_>>();
+
+        let mut chunk_map = app.world.resource_mut::<ChunkMap>();
+        for c in &unloaded {
+            chunk_map.remove(c);
+        }
+        for &c in &loaded {
+            chunk_map.insert(c, Entity::PLACEHOLDER);
+        }
+
+        (loaded, unloaded)
+    }
+
+    fn assert_landscape_loaded(app: &App, center: IVec2, radius: u8) {
+        let landscape = Landscape {
+            center,
+            radius,
+            ..Default::default()
+        };
+        let chunk_map = app.world.resource::<ChunkMap>();
+
+        assert_eq!(chunk_map.len(), (radius as usize * 2 + 1).pow(2));
+        assert!(
+            chunk_map.keys().all(|&c| landscape.contains(c)),
+            "Only chunks inside landscape should be loaded"
+        );
+    }
+
+    #[test]
+    fn update_landscape_move_border_only() {
+        // arrange
+        let mut app = setup_app();
+        move_landscape(&mut app, IVec2::ZERO, 2);
+
+        // act
+        let (loaded, unloaded) = move_landscape(&mut app, IVec2::new(1, 0), 2);
+
+        // assert
+        assert_eq!(
+            loaded.len(),
+            5,
+            "Only the new border column should be loaded"
+        );
+        assert!(loaded.iter().all(|c| c.x() == 3));
+        assert_eq!(
+            unloaded.len(),
+            5,
+            "Only the old border column should be unloaded"
+        );
+        assert!(unloaded.iter().all(|c| c.x() == -2));
+        assert_landscape_loaded(&app, IVec2::new(1, 0), 2);
+    }
+
+    #[test]
+    fn update_landscape_move_diagonal() {
+        // arrange
+        let mut app = setup_app();
+        move_landscape(&mut app, IVec2::ZERO, 2);
+
+        // act
+        let (loaded, unloaded) = move_landscape(&mut app, IVec2::new(1, -1), 2);
+
+        // assert
+        assert_eq!(loaded.len(), 9, "A column and a row should be loaded");
+        assert!(loaded.iter().all(|c| c.x() == 3 || c.z() == -3));
+        assert_eq!(unloaded.len(), 9, "A column and a row should be unloaded");
+        assert!(unloaded.iter().all(|c| c.x() == -2 || c.z() == 2));
+        assert_landscape_loaded(&app, IVec2::new(1, -1), 2);
+    }
+
+    #[test]
+    fn update_landscape_move_fast() {
+        // arrange
+        let mut app = setup_app();
+        move_landscape(&mut app, IVec2::ZERO, 1);
+
+        // act
+        let (loaded, unloaded) = move_landscape(&mut app, IVec2::new(10, 7), 1);
+
+        // assert
+        assert_eq!(loaded.len(), 9, "Whole new landscape should be loaded");
+        assert_eq!(unloaded.len(), 9, "Whole old landscape should be unloaded");
+        assert_eq!(
+            loaded[0],
+            Chunk::new(10, 7),
+            "Chunks closer to center should be loaded first"
+        );
+        assert_landscape_loaded(&app, IVec2::new(10, 7), 1);
+    }
+
+    #[test]
+    fn update_landscape_shrink_and_remove() {
+        // arrange
+        let mut app = setup_app();
+        move_landscape(&mut app, IVec2::ZERO, 2);
+
+        // act
+        let (loaded, unloaded) = move_landscape(&mut app, IVec2::ZERO, 1);
+
+        // assert
+        assert!(loaded.is_empty());
+        assert_eq!(unloaded.len(), 16);
+        assert_landscape_loaded(&app, IVec2::ZERO, 1);
+
+        // act
+        app.world.remove_resource::<Landscape>();
+        app.update();
+
+        // assert
+        let unload_events = app.world.resource::<Events<ChunkUnload>>();
+        assert_eq!(unload_events.len(), 9, "All chunks should be unloaded");
+    }
+
+    #[test]
+    fn sweep_landscape_move_while_loading() {
+        // arrange
+        let mut app = setup_app();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<ChunkAsset>();
+        move_landscape(&mut app, IVec2::ZERO, 1);
+
+        // Chunk which leaves landscape and another one which stays are still loading, while a
+        // third one was lost, so it is neither loaded nor loading.
+        let (leaving, staying, lost) = (Chunk::new(1, 0), Chunk::new(0, -1), Chunk::new(0, 1));
+        let mut spawn_loading = |chunk: Chunk| {
+            app.world.resource_mut::<ChunkMap>().remove(&chunk);
+            let handle = app
+                .world
+                .resource::<AssetServer>()
+                .load::<ChunkAsset>(chunk.path());
+            app.world.spawn(handle).id()
+        };
+        let leaving_entity = spawn_loading(leaving);
+        let staying_entity = spawn_loading(staying);
+        app.world.resource_mut::<ChunkMap>().remove(&lost);
+
+        let (_, unloaded) = move_landscape(&mut app, IVec2::new(-1, 0), 1);
+        assert!(
+            !unloaded.contains(&leaving),
+            "Delta path only unloads loaded chunks"
+        );
+
+        // act
+        app.world.run_system_once(sweep_landscape);
+
+        // assert
+        assert!(
+            app.world.get_entity(leaving_entity).is_none(),
+            "Loading of chunk which left landscape should be canceled"
+        );
+        assert!(
+            app.world.get_entity(staying_entity).is_some(),
+            "Loading of chunk inside landscape should be kept"
+        );
+
+        let loaded = app
+            .world
+            .resource_mut::<Events<ChunkLoad>>()
+            .drain()
+            .map(|ChunkLoad(c)| c)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            loaded,
+            vec![lost],
+            "Only lost chunk should be requested again"
+        );
+        assert!(app.world.resource::<Events<ChunkUnload>>().is_empty());
+    }
+
+    #[test]
+    fn sweep_landscape_unload_outside() {
+        // arrange
+        let mut app = setup_app();
+        move_landscape(&mut app, IVec2::ZERO, 1);
+
+        let outside = Chunk::new(5, 5);
+        app.world
+            .resource_mut::<ChunkMap>()
+            .insert(outside, Entity::PLACEHOLDER);
+
+        // act
+        app.world.run_system_once(sweep_landscape);
+
+        // assert
+        let unloaded = app
+            .world
+            .resource_mut::<Events<ChunkUnload>>()
+            .drain()
+            .map(|ChunkUnload(c)| c)
+            .collect::<Vec<_>>();
+        assert_eq!(unloaded, vec![outside]);
+        assert!(app.world.resource::<Events<ChunkLoad>>().is_empty());
+    }
+
+    #[test]
+    fn update_landscape_prefetch() {
+        // arrange
+        let mut app = setup_app();
+        let id = projekto_proto::ClientId::default();
+
+        let mut velocity = PlayerVelocity::at(Vec3::ZERO, 0.0);
+        velocity.sample(Vec3::new(3.2, 0.0, 0.0), 0.2);
+
+        app.init_resource::<PlayerTransforms>()
+            .init_resource::<PlayerVelocities>();
+        app.world
+            .resource_mut::<PlayerTransforms>()
+            .insert(id, Transform::default());
+        app.world
+            .resource_mut::<PlayerVelocities>()
+            .insert(id, velocity);
+
+        // act
+        let (loaded, _) = move_landscape(&mut app, IVec2::ZERO, 1);
+
+        // assert
+        let landscape = *app.world.resource::<Landscape>();
+        assert_eq!(
+            landscape.prefetch,
+            IVec2::new(2, 0),
+            "Landscape should be extended 2 seconds ahead of player"
+        );
+        assert_eq!(loaded.len(), 15);
+        assert!(landscape.contains(Chunk::new(3, 1)));
+        assert!(!landscape.contains(Chunk::new(-2, 0)));
+        assert!(
+            loaded[..9].iter().all(|c| c.x() <= 1),
+            "Prefetched chunks should be loaded last"
+        );
+
+        // act
+        velocity.sample(Vec3::new(3.2, 0.0, 0.0), 0.4);
+        app.world
+            .resource_mut::<PlayerVelocities>()
+            .insert(id, velocity);
+        app.update();
+
+        // assert
+        let unloaded = app.world.resource::<Events<ChunkUnload>>();
+        assert_eq!(
+            unloaded.len(),
+            6,
+            "Prefetched chunks should be unloaded once player stops"
+        );
+    }
+
+    #[test]
+    fn prefetch_offset() {
+        assert_eq!(super::prefetch_offset(Vec3::ZERO), IVec2::ZERO);
+        assert_eq!(
+            super::prefetch_offset(Vec3::new(-8.0, 100.0, 4.0)),
+            IVec2::new(-1, 1),
+            "Vertical velocity should be ignored"
+        );
+        assert_eq!(
+            super::prefetch_offset(Vec3::new(1000.0, 0.0, 0.0)),
+            IVec2::new(MAX_PREFETCH, 0)
+        );
+    }
 }
@@ -1,106 +1,249 @@
-use bevy::{prelude::*, utils::HashSet};
-use projekto_core::{chunk, voxel};
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future::poll_once, AsyncComputeTaskPool, Task, TaskPool},
+    utils::HashSet,
+};
+use projekto_core::{
+    chunk::{self, Chunk, ChunkStorage},
+    voxel,
+};
 
-use crate::{light, meshing, WorldSet};
+use crate::{
+    debug::ChunkMeshed,
+    error::{Quarantine, ServerError},
+    light,
+    mesher::{MeshingFailure, MeshingJob, MeshingPool},
+    meshing::{self, MissingNeighborPolicy},
+    MeshValidity, WorldSet,
+};
 
 use crate::bundle::{
-    ChunkFacesOcclusion, ChunkFacesSoftLight, ChunkKind, ChunkLight, ChunkLocal, ChunkQuery,
-    ChunkVertex,
+    ChunkColumns, ChunkContentHash, ChunkDecorations, ChunkFacesOcclusion, ChunkFacesSoftLight,
+    ChunkKind, ChunkLight, ChunkLightSamples, ChunkLiquidVertex, ChunkLocal, ChunkMap,
+    ChunkMeshValidity, ChunkNeighbors, ChunkQuery, ChunkVertex, ChunkVertexHash,
 };
 
+use super::{invalidate_light_samples, LightRecomputeTask, NeighborhoodChanged, PlayerTransforms};
+
 pub struct MeshingPlugin;
 
 impl Plugin for MeshingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                faces_occlusion, //.run_if(any_chunk::<Changed<ChunkKind>>),
-                faces_light_softening,
-                // .run_if(any_chunk::<Or<(Changed<ChunkKind>, Changed<ChunkLight>)>>),
-                generate_vertices,
-                // .run_if(any_chunk::<Or<(Changed<ChunkKind>, Changed<ChunkLight>)>>),
+        app.insert_resource(MeshingPool::start())
+            .init_resource::<Quarantine>()
+            .init_resource::<MissingNeighborPolicy>()
+            .add_systems(
+                Update,
+                (
+                    (spawn_faces_occlusion, invalidate_stale_light_samples)
+                        .in_set(WorldSet::ChunkInitialization),
+                    (collect_faces_occlusion, collect_meshing).in_set(WorldSet::CollectAsync),
+                ),
             )
-                .chain()
-                .in_set(WorldSet::Meshing),
-        );
+            .add_systems(Last, reset_kind_changes)
+            .add_systems(
+                Update,
+                (
+                    faces_light_softening,
+                    // .run_if(any_chunk::<Or<(Changed<ChunkKind>, Changed<ChunkLight>)>>),
+                    dispatch_meshing,
+                    // .run_if(any_chunk::<Or<(Changed<ChunkKind>, Changed<ChunkLight>)>>),
+                    hash_vertices,
+                    hash_content,
+                    summarize_columns,
+                    scatter_decorations,
+                )
+                    .chain()
+                    .in_set(WorldSet::Meshing),
+            );
     }
 }
 
-fn faces_occlusion(
-    q_changed_chunks: Query<&ChunkLocal, Changed<ChunkKind>>,
-    q_kinds: ChunkQuery<&ChunkKind>,
-    mut q_occlusions: ChunkQuery<&mut ChunkFacesOcclusion>,
+/// Meshing job sent to [`MeshingPool`]. Results of any other job are outdated and discarded.
+#[derive(Component, Debug)]
+pub(crate) struct MeshingTask(u64);
+
+/// Faces occlusion being computed on [`AsyncComputeTaskPool`]. Chunks with a pending task aren't
+/// meshed, since their current faces occlusion is outdated.
+#[derive(Component)]
+pub(crate) struct FacesOcclusionTask(Task<ChunkStorage<voxel::FacesOcclusion>>);
+
+fn spawn_faces_occlusion(
+    mut commands: Commands,
+    q_changed_chunks: Query<(Entity, &ChunkKind, &ChunkNeighbors), Changed<ChunkKind>>,
+    q_kinds: Query<(&ChunkKind, &ChunkNeighbors)>,
+    mut reader: EventReader<NeighborhoodChanged>,
+    missing: Res<MissingNeighborPolicy>,
 ) {
     let mut count = 0;
-    let mut fully_occluded = 0;
+    let missing = *missing;
+    let pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
 
+    // When a chunk border is updated, neighbors on that side must be checked too. Spawned chunks
+    // have no changes, since their neighbors are notified by `NeighborhoodChanged` instead.
     q_changed_chunks
         .iter()
-        .flat_map(|local| {
-            // TODO: There should be a better way to avoid update everything.
-            // When a chunk kind is updated, we have to check all its surrounding.
-            let neighbors = chunk::SIDES.map(|s| local.neighbor(s.dir()));
-            std::iter::once(**local).chain(neighbors)
+        .flat_map(|(entity, kind, neighbors)| {
+            let affected = kind
+                .changed_sides()
+                .filter_map(|side| neighbors[side.index()])
+                .collect::<Vec<_>>();
+            std::iter::once(entity).chain(affected)
         })
+        .chain(reader.read().map(|evt| evt.entity))
         .collect::<HashSet<_>>()
         .into_iter()
-        .filter(|&chunk| q_kinds.chunk_exists(chunk))
-        .for_each(|chunk| {
-            let mut neighborhood = [None; chunk::SIDE_COUNT];
-
-            // Update neighborhood
-            chunk::SIDES.iter().for_each(|side| {
-                let neighbor = chunk.neighbor(side.dir());
-                neighborhood[side.index()] = q_kinds.get_chunk(neighbor).map(|kind| &**kind);
+        .for_each(|entity| {
+            // Chunk may be despawned already.
+            let Ok((kind, neighbors)) = q_kinds.get(entity) else {
+                return;
+            };
+
+            // Tasks outlive this system, so they need their own copy of kinds.
+            let neighborhood = neighbors.map(|neighbor| {
+                neighbor
+                    .and_then(|neighbor| q_kinds.get(neighbor).ok())
+                    .map(|(kind, _)| kind.storage().clone())
             });
+            let kind = kind.storage().clone();
 
-            let mut faces_occlusion = q_occlusions.get_chunk_mut(chunk).expect("Entity exists");
-            let kind = q_kinds.get_chunk(chunk).expect("Entity exists");
-            meshing::faces_occlusion(kind, &mut faces_occlusion, &neighborhood);
+            let task = pool.spawn(async move {
+                let neighborhood = neighborhood.each_ref().map(Option::as_ref);
+                let mut faces_occlusion = ChunkStorage::default();
+                meshing::faces_occlusion(&kind, &mut faces_occlusion, &neighborhood, missing);
+                faces_occlusion
+            });
 
-            if faces_occlusion.iter().all(|occ| occ.is_fully_occluded()) {
-                fully_occluded += 1;
-            }
+            // Replaces any pending task, which is dropped and thus cancelled, since it is outdated.
+            commands.entity(entity).insert(FacesOcclusionTask(task));
             count += 1;
         });
 
     if count > 0 {
-        trace!("[faces_occlusion] {count} chunks faces occlusion computed. {fully_occluded} chunks fully occluded.");
+        trace!("[spawn_faces_occlusion] {count} chunks faces occlusion tasks spawned.");
+    }
+}
+
+/// Forgets kind changes made on this tick, after all systems had a chance to check them. Change
+/// detection is bypassed, since this isn't an actual change.
+fn reset_kind_changes(mut q: Query<&mut ChunkKind, Changed<ChunkKind>>) {
+    for mut kind in &mut q {
+        kind.bypass_change_detection().reset();
+    }
+}
+
+fn collect_faces_occlusion(
+    mut commands: Commands,
+    mut q: Query<(Entity, &mut FacesOcclusionTask, &mut ChunkFacesOcclusion)>,
+) {
+    let mut count = 0;
+    let mut fully_occluded = 0;
+
+    for (entity, mut task, mut faces_occlusion) in &mut q {
+        let Some(result) = block_on(poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        if result.iter().all(|occ| occ.is_fully_occluded()) {
+            fully_occluded += 1;
+        }
+
+        faces_occlusion.0 = result;
+        commands.entity(entity).remove::<FacesOcclusionTask>();
+        count += 1;
+    }
+
+    if count > 0 {
+        trace!("[collect_faces_occlusion] {count} chunks faces occlusion computed. {fully_occluded} chunks fully occluded.");
+    }
+}
+
+/// Invalidates light samples which are stale due to kind or neighborhood changes. Samples of unlit
+/// voxels depend on whether they block light, and samples of missing neighbors depend on policy.
+/// Light changes are invalidated by propagation, which knows the voxels it changed.
+///
+/// Runs every tick, unlike [`WorldSet::Meshing`], since kind changes are reset at the end of it.
+fn invalidate_stale_light_samples(
+    q_kinds: Query<(&ChunkLocal, &ChunkKind, &ChunkNeighbors), Changed<ChunkKind>>,
+    q_neighbors: Query<&ChunkLocal, Changed<ChunkNeighbors>>,
+    q_added: Query<&ChunkLocal, Added<ChunkLocal>>,
+    mut q_samples: ChunkQuery<&mut ChunkLightSamples>,
+) {
+    for (&ChunkLocal(chunk), kind, neighbors) in &q_kinds {
+        if !kind.has_changes() {
+            continue;
+        }
+
+        let voxels = kind.changed().collect::<Vec<_>>();
+        invalidate_light_samples(&mut q_samples, chunk, Some(&voxels));
+
+        // Missing neighbors may be assumed solid up to the height of this chunk columns.
+        if neighbors.iter().any(Option::is_none) {
+            if let Some(mut samples) = q_samples.get_chunk_mut(chunk) {
+                samples.invalidate_all();
+            }
+        }
+    }
+
+    for &ChunkLocal(chunk) in &q_neighbors {
+        if let Some(mut samples) = q_samples.get_chunk_mut(chunk) {
+            for side in chunk::SIDES {
+                samples.invalidate_border(side.dir());
+            }
+        }
+    }
+
+    // Diagonal neighbors aren't linked, so new chunks invalidate their samples directly.
+    for &ChunkLocal(chunk) in &q_added {
+        invalidate_light_samples(&mut q_samples, chunk, None);
     }
 }
 
 fn faces_light_softening(
-    q_changed_chunks: Query<&ChunkLocal, Or<(Changed<ChunkKind>, Changed<ChunkLight>)>>,
+    q_changed_chunks: Query<
+        (Entity, &ChunkNeighbors),
+        Or<(
+            Changed<ChunkKind>,
+            Changed<ChunkLight>,
+            Changed<ChunkFacesOcclusion>,
+            Changed<ChunkNeighbors>,
+        )>,
+    >,
     q_chunks: ChunkQuery<(&ChunkLocal, &ChunkKind, &ChunkLight, &ChunkFacesOcclusion)>,
-    mut q_soft_light: ChunkQuery<&mut ChunkFacesSoftLight>,
+    mut q_soft_light: Query<(&mut ChunkFacesSoftLight, &mut ChunkLightSamples)>,
+    q_pending: Query<(), Or<(With<FacesOcclusionTask>, With<LightRecomputeTask>)>>,
+    missing: Res<MissingNeighborPolicy>,
 ) {
     let mut count = 0;
 
+    // When a chunk kind or light is updated, its neighbors borders must be softened too.
     q_changed_chunks
         .iter()
-        .flat_map(|local| {
-            // TODO: There should be a better way to avoid update everything.
-            // When a chunk kind or light is updated, we have to check all its surrounding.
-            let neighbors = chunk::SIDES.map(|s| local.neighbor(s.dir()));
-            std::iter::once(**local).chain(neighbors)
+        .flat_map(|(entity, neighbors)| {
+            std::iter::once(entity).chain(neighbors.iter().flatten().copied())
         })
         .collect::<HashSet<_>>()
         .into_iter()
-        .filter(|&chunk| q_chunks.chunk_exists(chunk))
-        .for_each(|chunk| {
-            let (_, _, _, occlusion) = q_chunks.get_chunk(chunk).expect("Chunk must exists");
+        // Those will be softened again when their faces occlusion or light is collected.
+        .filter(|&entity| !q_pending.contains(entity))
+        .for_each(|entity| {
+            let Ok((&ChunkLocal(chunk), _, _, occlusion)) = q_chunks.get(entity) else {
+                return;
+            };
 
-            let mut soft_light = q_soft_light
-                .get_chunk_mut(chunk)
-                .expect("Chunk must exists");
+            let Ok((mut soft_light, mut samples)) = q_soft_light.get_mut(entity) else {
+                error!("Chunk {chunk} has no soft light. Skipping it.");
+                return;
+            };
 
             light::smooth_lighting(
                 chunk,
                 occlusion,
                 &mut soft_light,
-                |chunk| q_chunks.get_chunk(chunk).map(|c| &**c.1),
+                &mut samples,
+                |chunk| q_chunks.get_chunk(chunk).map(|c| c.1.storage()),
                 |chunk| q_chunks.get_chunk(chunk).map(|c| &**c.2),
+                *missing,
             );
 
             count += 1;
@@ -111,42 +254,207 @@ fn faces_light_softening(
     }
 }
 
-fn generate_vertices(
+fn dispatch_meshing(
+    mut commands: Commands,
     q_changed_chunks: Query<
         (
             Entity,
+            &ChunkLocal,
             &ChunkKind,
             &ChunkFacesOcclusion,
             &ChunkFacesSoftLight,
+            &ChunkMeshValidity,
+        ),
+        (
+            Or<(Changed<ChunkKind>, Changed<ChunkFacesSoftLight>)>,
+            Without<FacesOcclusionTask>,
+            Without<LightRecomputeTask>,
         ),
-        Or<(Changed<ChunkKind>, Changed<ChunkFacesSoftLight>)>,
     >,
-    mut q_vertex: Query<&mut ChunkVertex>,
+    q_content_hash: ChunkQuery<&ChunkContentHash>,
+    mut pool: ResMut<MeshingPool>,
+    players: Res<PlayerTransforms>,
+    quarantine: Res<Quarantine>,
 ) {
+    let players = players
+        .values()
+        .map(|transform| Chunk::from(transform.translation))
+        .collect::<Vec<_>>();
+
     let mut count = 0;
-    let mut map = [0; voxel::SIDE_COUNT];
-    q_changed_chunks
-        .iter()
-        .for_each(|(entity, kind, faces_occlusion, faces_soft_light)| {
-            if faces_occlusion.iter().all(|occ| occ.is_fully_occluded()) {
+    let mut skipped = 0;
+
+    q_changed_chunks.iter().for_each(
+        |(entity, local, kind, faces_occlusion, faces_soft_light, validity)| {
+            // Chunks which failed meshing would fail again, since they have the same content.
+            if faces_occlusion.iter().all(|occ| occ.is_fully_occluded())
+                || quarantine.contains(**local)
+            {
                 return;
             }
 
-            // let faces = meshing::faces_merge(kind, faces_occlusion, faces_soft_light);
-            let faces = meshing::generate_faces(kind, faces_occlusion, faces_soft_light);
-
-            faces.iter().for_each(|face| {
-                map[face.side.index()] += 1;
+            let hash = meshing::neighborhood_hash(**local, |chunk| {
+                q_content_hash.get_chunk(chunk).map(|h| h.0)
             });
 
-            let mut vertex = meshing::generate_vertices(faces);
+            // Current vertices were generated from the same content, like when they were loaded
+            // along with the chunk, so any pending job is outdated.
+            if validity.is_valid(hash) {
+                commands.entity(entity).remove::<MeshingTask>();
+                skipped += 1;
+                return;
+            }
 
-            let mut chunk_vertex = q_vertex.get_mut(entity).expect("Entity must exists");
-            std::mem::swap(&mut vertex, &mut chunk_vertex);
+            // Chunks closer to any player are meshed first.
+            let priority = players
+                .iter()
+                .map(|&player| local.distance(player).abs().max_element() as u32)
+                .min()
+                .unwrap_or_default();
 
+            let id = pool.next_id();
+            pool.send(MeshingJob {
+                chunk: **local,
+                id,
+                priority,
+                hash,
+                kind: kind.storage().clone(),
+                occlusion: faces_occlusion.0.clone(),
+                soft_light: faces_soft_light.0.clone(),
+            });
+
+            // Replaces any pending task, which result will be discarded, since it is outdated.
+            commands.entity(entity).insert(MeshingTask(id));
             count += 1;
+        },
+    );
+
+    if count > 0 || skipped > 0 {
+        trace!(
+            "[dispatch_meshing] {count} chunks meshing jobs sent. {skipped} chunks already meshed."
+        );
+    }
+}
+
+fn collect_meshing(
+    mut commands: Commands,
+    pool: Res<MeshingPool>,
+    chunk_map: Res<ChunkMap>,
+    mut q: Query<(
+        &MeshingTask,
+        &mut ChunkVertex,
+        &mut ChunkLiquidVertex,
+        &mut ChunkMeshValidity,
+    )>,
+    mut writer: EventWriter<ChunkMeshed>,
+    mut quarantine: ResMut<Quarantine>,
+) {
+    let mut count = 0;
+
+    while let Some(result) = pool.try_recv() {
+        let result = match result {
+            Ok(result) => result,
+            Err(MeshingFailure { chunk, id, error }) => {
+                // Only the latest job matters, since older ones were meshed from outdated content.
+                let latest = chunk_map
+                    .get(&chunk)
+                    .copied()
+                    .filter(|&entity| q.get(entity).is_ok_and(|(task, ..)| task.0 == id));
+
+                if let Some(entity) = latest {
+                    commands.entity(entity).remove::<MeshingTask>();
+                    quarantine.insert(ServerError::Mesh {
+                        chunk,
+                        source: error,
+                    });
+                }
+                continue;
+            }
+        };
+
+        // Chunk may be despawned already, or meshed again since this job was sent.
+        let Some(&entity) = chunk_map.get(&result.chunk) else {
+            continue;
+        };
+        let Ok((task, mut vertex, mut liquid_vertex, mut validity)) = q.get_mut(entity) else {
+            continue;
+        };
+        if task.0 != result.id {
+            continue;
+        }
+
+        writer.send(ChunkMeshed {
+            chunk: result.chunk,
+            vertices: result.vertex.len() + result.liquid_vertex.len(),
+            micros: result.micros,
         });
+
+        vertex.0 = result.vertex;
+        validity.0 = MeshValidity::new(result.hash);
+
+        // Avoids notifying clients when chunk has no liquid at all, which is the usual case.
+        if liquid_vertex.0 != result.liquid_vertex {
+            liquid_vertex.0 = result.liquid_vertex;
+        }
+
+        commands.entity(entity).remove::<MeshingTask>();
+        count += 1;
+    }
+
+    if count > 0 {
+        trace!("[collect_meshing] {count} chunks meshed.");
+    }
+}
+
+fn hash_vertices(mut q: Query<(&ChunkVertex, &mut ChunkVertexHash), Changed<ChunkVertex>>) {
+    for (vertex, mut hash) in &mut q {
+        hash.0 = voxel::vertex_hash(vertex);
+    }
+}
+
+fn hash_content(
+    mut q: Query<
+        (Ref<ChunkKind>, Ref<ChunkLight>, &mut ChunkContentHash),
+        Or<(Changed<ChunkKind>, Changed<ChunkLight>)>,
+    >,
+) {
+    for (kind, light, mut hash) in &mut q {
+        // Newly spawned chunks already have the hash which was computed when they were saved.
+        if kind.is_added() && light.is_added() {
+            continue;
+        }
+
+        hash.0 = chunk::content_hash(&kind, &light);
+    }
+}
+
+fn summarize_columns(mut q: Query<(&ChunkKind, &mut ChunkColumns), Changed<ChunkKind>>) {
+    let mut count = 0;
+    for (kind, mut columns) in &mut q {
+        columns.0 = chunk::column_summaries(kind);
+        count += 1;
+    }
+
+    if count > 0 {
+        trace!("[summarize_columns] {count} chunks columns summarized.");
+    }
+}
+
+fn scatter_decorations(
+    mut q: Query<(&ChunkLocal, &ChunkKind, &mut ChunkDecorations), Changed<ChunkKind>>,
+) {
+    let mut count = 0;
+    for (ChunkLocal(chunk), kind, mut decorations) in &mut q {
+        let scattered = chunk::decorations(*chunk, kind);
+
+        // Avoids notifying clients when an edit didn't touch any decoration.
+        if decorations.0 != scattered {
+            decorations.0 = scattered;
+            count += 1;
+        }
+    }
+
     if count > 0 {
-        trace!("[generate_vertices] {count} chunks vertices generated. {map:?}");
+        trace!("[scatter_decorations] {count} chunks decorations scattered.");
     }
 }
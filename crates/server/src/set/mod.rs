@@ -2,6 +2,7 @@
 
 // mod chunk_initialization;
 mod chunk_management;
+mod edit_history;
 mod landscape;
 mod meshing;
 mod propagation;
@@ -10,8 +11,11 @@ mod send_responses;
 
 // pub use chunk_initialization::*;
 pub use chunk_management::*;
+pub(crate) use edit_history::*;
 pub use landscape::*;
 pub use meshing::*;
 pub use propagation::*;
 pub(crate) use receive_requests::*;
 pub(crate) use send_responses::*;
+
+pub use receive_requests::TeleportPlayer;
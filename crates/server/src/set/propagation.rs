@@ -1,23 +1,44 @@
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    tasks::{block_on, futures_lite::future::poll_once, AsyncComputeTaskPool, Task, TaskPool},
+    utils::HashMap,
+};
 use projekto_core::{
-    chunk::Chunk,
+    chunk::{self, Chunk, ChunkStorage},
     voxel::{self, Voxel},
 };
 
 use crate::{
-    bundle::{ChunkKind, ChunkLight, ChunkQuery},
-    light::{self, NeighborLightPropagation},
-    WorldSet,
+    bundle::{ChunkKind, ChunkLight, ChunkLightSamples, ChunkLocal, ChunkNeighbors, ChunkQuery},
+    light::{self, NeighborLightPropagation, RemovedLight},
+    WorldSet, WorldTime,
 };
 
+use super::NeighborhoodChanged;
+
 pub struct PropagationPlugin;
 
 impl Plugin for PropagationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<LightUpdate>().add_systems(
-            Update,
-            (propagate_light.run_if(on_event::<LightUpdate>()),).in_set(WorldSet::Propagation),
-        );
+        app.add_event::<LightUpdate>()
+            .add_event::<LightRemoval>()
+            .init_resource::<SlantedSunlight>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        propagate_to_new_neighbors.run_if(on_event::<NeighborhoodChanged>()),
+                        propagate_light
+                            .run_if(on_event::<LightUpdate>().or_else(on_event::<LightRemoval>())),
+                    )
+                        .chain()
+                        .in_set(WorldSet::Propagation),
+                    spawn_sunlight_recompute.in_set(WorldSet::Propagation),
+                    (collect_light_recompute, collect_sunlight_recompute)
+                        .in_set(WorldSet::CollectAsync),
+                ),
+            );
     }
 }
 
@@ -28,19 +49,460 @@ pub struct LightUpdate {
     pub values: Vec<(Voxel, u8)>,
 }
 
+/// Light to be removed from voxels of a chunk, paired with removed intensity. Voxels brighter than
+/// it are lit by other sources and are kept. See [`light::remove`].
+#[derive(Event, Debug, Clone)]
+pub struct LightRemoval {
+    pub chunk: Chunk,
+    pub ty: voxel::LightTy,
+    pub values: Vec<(Voxel, u8)>,
+}
+
+/// Sends [`LightRemoval`] and [`LightUpdate`] needed after voxels kinds are changed.
+#[derive(SystemParam)]
+pub struct LightEdits<'w> {
+    updates: EventWriter<'w, LightUpdate>,
+    removals: EventWriter<'w, LightRemoval>,
+}
+
+impl LightEdits<'_> {
+    /// Sends light events for the given voxels of the chunk, which changed from the first kind to
+    /// the second one. `light` must be the chunk light before the changes.
+    pub fn send(
+        &mut self,
+        chunk: Chunk,
+        light: &ChunkStorage<voxel::Light>,
+        edits: &[(Voxel, voxel::Kind, voxel::Kind)],
+    ) {
+        for ty in [voxel::LightTy::Natural, voxel::LightTy::Artificial] {
+            let edits = edits
+                .iter()
+                .map(|&(voxel, before, after)| {
+                    (voxel, light::edit(light, voxel, ty, before, after))
+                })
+                .collect::<Vec<_>>();
+
+            // Neighborhood of other edits may be lit by removed light, so it can't be trusted and
+            // those voxels are refilled by removal too. Removal lights emitters and sky again.
+            let (removals, updates) = if edits.iter().any(|(_, edit)| edit.remove) {
+                let removals = edits
+                    .iter()
+                    .map(|&(voxel, _)| (voxel, light.get(voxel).get(ty)))
+                    .collect();
+                (removals, vec![])
+            } else {
+                let updates = edits
+                    .iter()
+                    .filter(|(_, edit)| edit.intensity > 0)
+                    .map(|&(voxel, edit)| (voxel, edit.intensity))
+                    .collect();
+                (vec![], updates)
+            };
+
+            if !removals.is_empty() {
+                self.removals.send(LightRemoval {
+                    chunk,
+                    ty,
+                    values: removals,
+                });
+            }
+
+            if !updates.is_empty() {
+                self.updates.send(LightUpdate {
+                    chunk,
+                    ty,
+                    values: updates,
+                });
+            }
+        }
+    }
+}
+
+/// Invalidates light samples of the given voxels of a chunk, on the chunk itself and on neighbors
+/// which sample its border. When `voxels` is `None`, all voxels of the chunk are invalidated.
+pub(crate) fn invalidate_light_samples(
+    q_samples: &mut ChunkQuery<&mut ChunkLightSamples>,
+    chunk: Chunk,
+    voxels: Option<&[Voxel]>,
+) {
+    for x in -1..=1 {
+        for z in -1..=1 {
+            let offset = IVec2::new(x, z);
+            let Some(mut samples) = q_samples.get_chunk_mut(chunk.neighbor(offset)) else {
+                continue;
+            };
+
+            match voxels {
+                Some(voxels) => samples.invalidate(-offset, voxels.iter().copied()),
+                None if offset == IVec2::ZERO => samples.invalidate_all(),
+                None => samples.invalidate_border(-offset),
+            }
+        }
+    }
+}
+
+/// Light being recomputed from scratch on [`AsyncComputeTaskPool`], since the persisted one isn't
+/// valid anymore. See [`crate::LightValidity`].
+#[derive(Component)]
+pub(crate) struct LightRecomputeTask(Task<(u64, ChunkStorage<voxel::Light>)>);
+
+impl LightRecomputeTask {
+    pub(crate) fn spawn(kind: ChunkStorage<voxel::Kind>) -> Self {
+        let pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+
+        Self(pool.spawn(async move {
+            let mut light = ChunkStorage::default();
+            light::init_natural(&kind, &mut light);
+            (chunk::kind_hash(&kind), light)
+        }))
+    }
+}
+
+/// Installs recomputed light of chunks and queues what generation and loading would light them
+/// with too, which is emission of their emitters and border light of linked neighbors. Light is
+/// only computed straight down by [`LightRecomputeTask`], so those are propagated afterwards by
+/// [`propagate_light`].
+fn collect_light_recompute(
+    mut commands: Commands,
+    mut q_tasks: Query<(Entity, &ChunkKind, &mut LightRecomputeTask)>,
+    mut q_light: Query<(&ChunkLocal, &ChunkKind, &mut ChunkLight, &ChunkNeighbors)>,
+    mut q_samples: ChunkQuery<&mut ChunkLightSamples>,
+    mut writer: EventWriter<LightUpdate>,
+) {
+    let mut finished = vec![];
+
+    for (entity, kind, mut task) in &mut q_tasks {
+        let Some((kind_hash, result)) = block_on(poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        // Kinds were changed while light was being computed, so it is outdated already.
+        if kind_hash != chunk::kind_hash(kind) {
+            *task = LightRecomputeTask::spawn(kind.storage().clone());
+            continue;
+        }
+
+        finished.push((entity, result));
+    }
+
+    let count = finished.len();
+    let mut map = HashMap::<(Chunk, voxel::LightTy), Vec<_>>::new();
+
+    for (entity, result) in finished {
+        let Ok((local, kind, mut light, _)) = q_light.get_mut(entity) else {
+            continue;
+        };
+        let chunk = **local;
+
+        light.0 = result;
+        invalidate_light_samples(&mut q_samples, chunk, None);
+
+        let emitters = chunk::voxels()
+            .map(|voxel| (voxel, kind.get(voxel).light_emission()))
+            .filter(|&(_, emission)| emission > 0)
+            .collect::<Vec<_>>();
+        if !emitters.is_empty() {
+            map.entry((chunk, voxel::LightTy::Artificial))
+                .or_default()
+                .extend(emitters);
+        }
+
+        let Ok((_, kind, light, neighbors)) = q_light.get(entity) else {
+            continue;
+        };
+
+        for side in chunk::SIDES {
+            let Some(neighbor_entity) = neighbors[side.index()] else {
+                continue;
+            };
+
+            // Light goes both ways, since light of each side was computed without the other one.
+            let neighbor = chunk.neighbor(side.dir());
+            let outgoing = light::propagate_border(kind, light, side)
+                .into_iter()
+                .map(|propagation| (neighbor, propagation));
+            let incoming = q_light
+                .get(neighbor_entity)
+                .map(|(_, kind, light, _)| light::propagate_border(kind, light, side.opposite()))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|propagation| (chunk, propagation));
+
+            for (
+                chunk,
+                NeighborLightPropagation {
+                    voxel,
+                    ty,
+                    intensity,
+                    ..
+                },
+            ) in outgoing.chain(incoming)
+            {
+                map.entry((chunk, ty)).or_default().push((voxel, intensity));
+            }
+        }
+
+        // Light is computed straight down, so slanted sun light is recomputed if enabled.
+        commands
+            .entity(entity)
+            .remove::<(LightRecomputeTask, SunlightStep)>();
+    }
+
+    map.into_iter().for_each(|((chunk, ty), values)| {
+        writer.send(LightUpdate { chunk, ty, values });
+    });
+
+    if count > 0 {
+        trace!("[collect_light_recompute] {count} chunks light recomputed.");
+    }
+}
+
+/// Optional mode where sun light comes slanted, following sun during the day, instead of straight
+/// down, which gives directional shading without shadow maps. See [`light::init_natural_slanted`].
+///
+/// Natural light of all loaded chunks is recomputed each time [`WorldTime`] reaches a new step, so
+/// steps should be coarse. Voxels edits still propagate sun light straight down until next step.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct SlantedSunlight {
+    pub enabled: bool,
+    /// Voxels sun light moves along X axis, for each voxel it goes down, at sunrise and sunset.
+    pub max_slant: f32,
+    /// How many times natural light is recomputed during a day.
+    pub steps_per_day: u32,
+}
+
+impl Default for SlantedSunlight {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_slant: 1.0,
+            steps_per_day: 24,
+        }
+    }
+}
+
+impl SlantedSunlight {
+    /// **Returns** the step of the given time of the day along with its slant, or `None` if
+    /// disabled. Sun rises at 0.25 and sets at 0.75 with opposite slants, which are kept during the
+    /// night before and after them.
+    pub fn step(&self, day_time: f32) -> Option<(u32, f32)> {
+        if !self.enabled || self.steps_per_day == 0 {
+            return None;
+        }
+
+        let step = (day_time * self.steps_per_day as f32) as u32 % self.steps_per_day;
+        let step_time = step as f32 / self.steps_per_day as f32;
+        let sun_progress = ((step_time - 0.25) / 0.5).clamp(0.0, 1.0);
+
+        Some((step, self.max_slant * (1.0 - 2.0 * sun_progress)))
+    }
+}
+
+/// Step of [`SlantedSunlight`] natural light of a chunk was computed with.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SunlightStep(u32);
+
+/// Natural light being recomputed on [`AsyncComputeTaskPool`] for another [`SlantedSunlight`] step,
+/// or straight down if it was disabled.
+#[derive(Component)]
+pub(crate) struct SunlightRecomputeTask(Task<(u64, Option<u32>, ChunkStorage<voxel::Light>)>);
+
+impl SunlightRecomputeTask {
+    fn spawn(kind: ChunkStorage<voxel::Kind>, step: Option<(u32, f32)>) -> Self {
+        let pool = AsyncComputeTaskPool::get_or_init(TaskPool::default);
+
+        Self(pool.spawn(async move {
+            let mut light = ChunkStorage::default();
+            match step {
+                Some((_, slant)) => light::init_natural_slanted(&kind, &mut light, slant),
+                None => light::init_natural(&kind, &mut light),
+            };
+            (chunk::kind_hash(&kind), step.map(|(step, _)| step), light)
+        }))
+    }
+}
+
+fn spawn_sunlight_recompute(
+    mut commands: Commands,
+    sunlight: Res<SlantedSunlight>,
+    world_time: Res<WorldTime>,
+    q: Query<
+        (Entity, &ChunkKind, Option<&SunlightStep>),
+        (Without<SunlightRecomputeTask>, Without<LightRecomputeTask>),
+    >,
+) {
+    let step = sunlight.step(world_time.day_time);
+    let mut count = 0;
+
+    for (entity, kind, current) in &q {
+        if current.map(|s| s.0) == step.map(|(step, _)| step) {
+            continue;
+        }
+
+        commands
+            .entity(entity)
+            .insert(SunlightRecomputeTask::spawn(kind.storage().clone(), step));
+        count += 1;
+    }
+
+    if count > 0 {
+        trace!("[spawn_sunlight_recompute] {count} chunks natural light recompute spawned.");
+    }
+}
+
+fn collect_sunlight_recompute(
+    mut commands: Commands,
+    mut q: Query<(
+        Entity,
+        &ChunkLocal,
+        &ChunkKind,
+        &mut SunlightRecomputeTask,
+        &mut ChunkLight,
+    )>,
+    mut q_samples: ChunkQuery<&mut ChunkLightSamples>,
+) {
+    let mut count = 0;
+
+    for (entity, local, kind, mut task, mut light) in &mut q {
+        let Some((kind_hash, step, result)) = block_on(poll_once(&mut task.0)) else {
+            continue;
+        };
+
+        let mut entity = commands.entity(entity);
+        entity.remove::<SunlightRecomputeTask>();
+
+        // Kinds were changed while light was being computed, so it is spawned again next frame.
+        if kind_hash != chunk::kind_hash(kind) {
+            continue;
+        }
+
+        // Artificial light doesn't depend on sun, so only natural light is replaced.
+        for voxel in chunk::voxels() {
+            let intensity = result.get(voxel).get(voxel::LightTy::Natural);
+            light.set_type(voxel, voxel::LightTy::Natural, intensity);
+        }
+        invalidate_light_samples(&mut q_samples, **local, None);
+
+        match step {
+            Some(step) => entity.insert(SunlightStep(step)),
+            None => entity.remove::<SunlightStep>(),
+        };
+        count += 1;
+    }
+
+    if count > 0 {
+        trace!("[collect_sunlight_recompute] {count} chunks natural light recomputed.");
+    }
+}
+
+/// Propagates border light of chunks which neighborhood changed into their linked neighbors, so
+/// chunks spawned after their neighbors were lit receive light from them and vice versa. Only
+/// notified chunks are woken, and light which doesn't brighten anything is dropped by
+/// [`propagate_light`].
+fn propagate_to_new_neighbors(
+    q: Query<(&ChunkLocal, &ChunkKind, &ChunkLight, &ChunkNeighbors)>,
+    mut reader: EventReader<NeighborhoodChanged>,
+    mut writer: EventWriter<LightUpdate>,
+) {
+    let mut map = HashMap::<(Chunk, voxel::LightTy), Vec<_>>::new();
+
+    for &NeighborhoodChanged { entity, .. } in reader.read() {
+        // Chunk may be despawned already.
+        let Ok((local, kind, light, neighbors)) = q.get(entity) else {
+            continue;
+        };
+
+        for side in chunk::SIDES {
+            if neighbors[side.index()].is_none() {
+                continue;
+            }
+
+            let neighbor = local.neighbor(side.dir());
+            for NeighborLightPropagation {
+                voxel,
+                ty,
+                intensity,
+                ..
+            } in light::propagate_border(kind, light, side)
+            {
+                map.entry((neighbor, ty))
+                    .or_default()
+                    .push((voxel, intensity));
+            }
+        }
+    }
+
+    let events = map.len();
+    map.into_iter().for_each(|((chunk, ty), values)| {
+        writer.send(LightUpdate { chunk, ty, values });
+    });
+
+    trace!("[propagate_to_new_neighbors] {events} propagation events sent.");
+}
+
+/// Light is only propagated into linked neighbors, so no events are sent to chunks which aren't
+/// spawned. Those receive border light once linked, by [`propagate_to_new_neighbors`].
 fn propagate_light(
-    mut q_light: ChunkQuery<(&ChunkKind, &mut ChunkLight)>,
+    mut q_light: ChunkQuery<(&ChunkKind, &mut ChunkLight, &ChunkNeighbors)>,
+    mut q_samples: ChunkQuery<&mut ChunkLightSamples>,
     mut params: ParamSet<(EventReader<LightUpdate>, EventWriter<LightUpdate>)>,
+    mut removal_params: ParamSet<(EventReader<LightRemoval>, EventWriter<LightRemoval>)>,
 ) {
     let mut count = 0;
 
+    // Voxels which light was changed on each chunk, so light samples of them are invalidated.
+    let mut changed = HashMap::<Chunk, Vec<Voxel>>::new();
+
+    // Light is removed first, so updates aren't overwritten by it. Voxels still lit around removed
+    // area are propagated again, along with updated ones.
+    let mut propagate_voxels = HashMap::<(Chunk, voxel::LightTy), Vec<Voxel>>::new();
+    let mut remove_from_neighbors = HashMap::<(Chunk, voxel::LightTy), Vec<_>>::new();
+
+    for LightRemoval { chunk, ty, values } in removal_params.p0().read() {
+        let Some((kind, mut light, links)) = q_light.get_chunk_mut(*chunk) else {
+            continue;
+        };
+
+        let RemovedLight {
+            refill,
+            neighbors,
+            changed: removed,
+        } = light::remove(kind, &mut light, *ty, values.iter().copied());
+        changed.entry(*chunk).or_default().extend(removed);
+
+        propagate_voxels
+            .entry((*chunk, *ty))
+            .or_default()
+            .extend(refill);
+
+        neighbors
+            .into_iter()
+            .filter(|propagation| links[propagation.side.index()].is_some())
+            .for_each(
+                |NeighborLightPropagation {
+                     side,
+                     voxel,
+                     ty,
+                     intensity,
+                 }| {
+                    let neighbor = chunk.neighbor(side.dir());
+                    remove_from_neighbors
+                        .entry((neighbor, ty))
+                        .or_default()
+                        .push((voxel, intensity));
+                },
+            );
+
+        count += 1;
+    }
+
     let propagate_to_neighbors = params
         .p0()
         .read()
         .fold(
-            HashMap::<(Chunk, voxel::LightTy), Vec<Voxel>>::new(),
+            propagate_voxels,
             |mut map, LightUpdate { chunk, ty, values }| {
-                if let Some((_, mut light)) = q_light.get_chunk_mut(*chunk) {
+                if let Some((_, mut light, _)) = q_light.get_chunk_mut(*chunk) {
                     values.iter().for_each(|&(voxel, intensity)| {
                         if intensity > light.get(voxel).get(*ty) {
                             light.set_type(voxel, *ty, intensity);
@@ -58,30 +520,44 @@ fn propagate_light(
         .fold(
             HashMap::<(Chunk, voxel::LightTy), Vec<_>>::new(),
             |mut map, ((chunk, light_ty), voxels)| {
-                let (kind, mut light) = q_light
+                let (kind, mut light, links) = q_light
                     .get_chunk_mut(chunk)
                     .expect("Missing entities was filtered already");
-                let neighborhood_propagation =
-                    light::propagate(kind, &mut light, light_ty, voxels.iter().copied());
-
-                neighborhood_propagation.into_iter().for_each(
-                    |NeighborLightPropagation {
-                         side,
-                         voxel,
-                         ty,
-                         intensity,
-                     }| {
-                        let neighbor = chunk.neighbor(side.dir());
-                        map.entry((neighbor, ty))
-                            .or_insert(vec![])
-                            .push((voxel, intensity));
-                    },
+                let changed = changed.entry(chunk).or_default();
+                changed.extend_from_slice(&voxels);
+                let neighborhood_propagation = light::propagate_tracked(
+                    kind,
+                    &mut light,
+                    light_ty,
+                    voxels.iter().copied(),
+                    changed,
                 );
 
+                neighborhood_propagation
+                    .into_iter()
+                    .filter(|propagation| links[propagation.side.index()].is_some())
+                    .for_each(
+                        |NeighborLightPropagation {
+                             side,
+                             voxel,
+                             ty,
+                             intensity,
+                         }| {
+                            let neighbor = chunk.neighbor(side.dir());
+                            map.entry((neighbor, ty))
+                                .or_insert(vec![])
+                                .push((voxel, intensity));
+                        },
+                    );
+
                 map
             },
         );
 
+    for (chunk, voxels) in changed {
+        invalidate_light_samples(&mut q_samples, chunk, Some(&voxels));
+    }
+
     let events = propagate_to_neighbors.len();
     let mut writer = params.p1();
     propagate_to_neighbors
@@ -90,5 +566,377 @@ fn propagate_light(
             writer.send(LightUpdate { chunk, ty, values });
         });
 
-    trace!("[propagate_light] {count} chunks light propagated. {events} propagation events sent.");
+    let removals = remove_from_neighbors.len();
+    let mut writer = removal_params.p1();
+    remove_from_neighbors
+        .into_iter()
+        .for_each(|((chunk, ty), values)| {
+            writer.send(LightRemoval { chunk, ty, values });
+        });
+
+    trace!("[propagate_light] {count} chunks light propagated. {events} propagation and {removals} removal events sent.");
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{app::ScheduleRunnerPlugin, ecs::system::RunSystemOnce};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::bundle::{ChunkBundle, ChunkMap};
+
+    use super::*;
+
+    /// Spawns a single chunk, with light computed from scratch, which is edited by
+    /// [`edit_voxel`] and lit by [`propagate_light`].
+    fn setup_edit_app(kind: ChunkStorage<voxel::Kind>) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_once()))
+            .init_resource::<ChunkMap>()
+            .add_event::<LightUpdate>()
+            .add_event::<LightRemoval>()
+            .add_systems(Update, propagate_light);
+
+        let chunk = Chunk::default();
+        let entity = app
+            .world
+            .spawn(ChunkBundle {
+                local: ChunkLocal(chunk),
+                light: ChunkLight(recompute_light(&kind)),
+                kind: ChunkKind(kind.into()),
+                ..Default::default()
+            })
+            .id();
+        app.world.resource_mut::<ChunkMap>().insert(chunk, entity);
+
+        (app, entity)
+    }
+
+    /// Edits the given voxel the same way players do, sending light events with [`LightEdits`],
+    /// and runs light propagation.
+    fn edit_voxel(app: &mut App, voxel: Voxel, after: voxel::Kind) {
+        app.world.run_system_once(
+            move |mut q: ChunkQuery<(&mut ChunkKind, &ChunkLight)>, mut edits: LightEdits| {
+                let chunk = Chunk::default();
+                let (mut kind, light) = q.get_chunk_mut(chunk).expect("Chunk is spawned");
+                let before = kind.get(voxel);
+                kind.0.set(voxel, after);
+                edits.send(chunk, light, &[(voxel, before, after)]);
+            },
+        );
+        app.update();
+    }
+
+    fn chunk_state(
+        app: &App,
+        entity: Entity,
+    ) -> (ChunkStorage<voxel::Kind>, ChunkStorage<voxel::Light>) {
+        let kind = app.world.get::<ChunkKind>(entity).unwrap();
+        let light = app.world.get::<ChunkLight>(entity).unwrap();
+        (kind.storage().clone(), light.0.clone())
+    }
+
+    /// Computes both natural and artificial light of the given chunk from scratch.
+    fn recompute_light(kind: &ChunkStorage<voxel::Kind>) -> ChunkStorage<voxel::Light> {
+        let mut light = ChunkStorage::default();
+        let _ = light::init_natural(kind, &mut light);
+
+        let emitters = chunk::voxels()
+            .filter(|&voxel| kind.get(voxel).is_light_emitter())
+            .collect::<Vec<_>>();
+        emitters.iter().for_each(|&voxel| {
+            light.set_type(
+                voxel,
+                voxel::LightTy::Artificial,
+                kind.get(voxel).light_emission(),
+            );
+        });
+        let _ = light::propagate(
+            kind,
+            &mut light,
+            voxel::LightTy::Artificial,
+            emitters.into_iter(),
+        );
+
+        light
+    }
+
+    #[test]
+    fn remove_emitter() {
+        // arrange
+        let (mut app, entity) = setup_edit_app(ChunkStorage::default());
+        let voxel = Voxel::new(8, 100, 8);
+
+        // act
+        edit_voxel(&mut app, voxel, 4.into());
+
+        // assert
+        let (_, light) = chunk_state(&app, entity);
+        assert_eq!(light.get(voxel).get(voxel::LightTy::Artificial), 10);
+        assert_eq!(
+            light.get(voxel + IVec3::X).get(voxel::LightTy::Artificial),
+            9
+        );
+
+        // act
+        edit_voxel(&mut app, voxel, voxel::Kind::NONE);
+
+        // assert
+        let (_, light) = chunk_state(&app, entity);
+        assert!(
+            light.iter().all(|l| l.get(voxel::LightTy::Artificial) == 0),
+            "All light of removed emitter should be removed"
+        );
+    }
+
+    #[test]
+    fn remove_emitter_refill_other() {
+        // arrange
+        let (mut app, entity) = setup_edit_app(ChunkStorage::default());
+        let (removed, kept) = (Voxel::new(4, 100, 8), Voxel::new(8, 100, 8));
+
+        edit_voxel(&mut app, kept, 4.into());
+        edit_voxel(&mut app, removed, 6.into());
+
+        let (_, light) = chunk_state(&app, entity);
+        assert_eq!(
+            light.get(kept).get(voxel::LightTy::Artificial),
+            10,
+            "Emitter keeps its own emission"
+        );
+        assert_eq!(
+            light
+                .get(Voxel::new(6, 100, 8))
+                .get(voxel::LightTy::Artificial),
+            10
+        );
+
+        // act
+        edit_voxel(&mut app, removed, voxel::Kind::NONE);
+
+        // assert
+        let (kind, light) = chunk_state(&app, entity);
+        assert_eq!(
+            light
+                .get(Voxel::new(6, 100, 8))
+                .get(voxel::LightTy::Artificial),
+            8,
+            "Removed area should be refilled by remaining emitter"
+        );
+        assert_eq!(light, recompute_light(&kind));
+    }
+
+    #[test]
+    fn remove_block_under_sky() {
+        // arrange
+        let (mut app, entity) = setup_edit_app(ChunkStorage::default());
+        let voxel = Voxel::new(8, 100, 8);
+
+        // act
+        edit_voxel(&mut app, voxel, 1.into());
+
+        // assert
+        let (kind, light) = chunk_state(&app, entity);
+        assert_eq!(
+            light.get(voxel - IVec3::Y).get(voxel::LightTy::Natural),
+            voxel::Light::MAX_NATURAL_INTENSITY - 1,
+            "Voxels below should be lit from sides only"
+        );
+        assert_eq!(light, recompute_light(&kind));
+
+        // act
+        edit_voxel(&mut app, voxel, voxel::Kind::NONE);
+
+        // assert
+        let (kind, light) = chunk_state(&app, entity);
+        assert_eq!(light, recompute_light(&kind));
+    }
+
+    #[test]
+    fn random_edits_match_recompute() {
+        const SURFACE: i32 = 8;
+
+        // None, opaque, emitter, transparent and emitter again, with a different intensity.
+        let kinds = [0, 1, 4, 5, 6].map(voxel::Kind::id);
+
+        for seed in 0..4 {
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            let mut kind = ChunkStorage::<voxel::Kind>::default();
+            chunk::voxels()
+                .filter(|voxel| voxel.y < SURFACE)
+                .for_each(|voxel| kind.set(voxel, 1.into()));
+            let (mut app, entity) = setup_edit_app(kind);
+
+            for i in 0..64 {
+                // Edits are kept close to each other, so their light overlaps. Some of them are on
+                // top of chunk, where sun light comes from.
+                let y = if rng.gen_bool(0.1) {
+                    chunk::Y_END
+                } else {
+                    rng.gen_range(SURFACE - 4..SURFACE + 4)
+                };
+                let voxel = Voxel::new(rng.gen_range(0..8), y, rng.gen_range(0..8));
+                let after = kinds[rng.gen_range(0..kinds.len())];
+
+                edit_voxel(&mut app, voxel, after);
+
+                let (kind, light) = chunk_state(&app, entity);
+                assert_eq!(
+                    light,
+                    recompute_light(&kind),
+                    "Seed {seed}, edit {i}: setting {voxel} to {after:?} diverged from recompute"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn propagate_to_new_neighbors_linked_only() {
+        // arrange
+        let mut app = App::new();
+        app.add_event::<NeighborhoodChanged>()
+            .add_event::<LightUpdate>();
+
+        let kind = ChunkStorage::default();
+        let mut light = ChunkStorage::default();
+        light::init_natural(&kind, &mut light);
+
+        let chunk = Chunk::default();
+        let left = app.world.spawn_empty().id();
+        let mut links = [None; chunk::SIDE_COUNT];
+        links[chunk::ChunkSide::Left.index()] = Some(left);
+
+        let entity = app
+            .world
+            .spawn(ChunkBundle {
+                local: ChunkLocal(chunk),
+                light: ChunkLight(light),
+                neighbors: ChunkNeighbors(links),
+                ..Default::default()
+            })
+            .id();
+        app.world.send_event(NeighborhoodChanged { chunk, entity });
+
+        // act
+        app.world.run_system_once(propagate_to_new_neighbors);
+
+        // assert
+        let events = app
+            .world
+            .resource_mut::<Events<LightUpdate>>()
+            .drain()
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 1, "Only linked neighbor should receive light");
+        assert_eq!(events[0].chunk, chunk.neighbor(IVec2::NEG_X));
+        assert_eq!(events[0].ty, voxel::LightTy::Natural);
+        assert_eq!(
+            events[0].values.len(),
+            chunk::Y_AXIS_SIZE * chunk::Z_AXIS_SIZE
+        );
+    }
+
+    #[test]
+    fn recompute_light_propagates_emitters_and_neighbors() {
+        // arrange
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_once()))
+            .init_resource::<ChunkMap>()
+            .add_event::<LightUpdate>()
+            .add_event::<LightRemoval>()
+            .add_systems(Update, (propagate_light, collect_light_recompute).chain());
+
+        // Chunk is covered, so only light coming from neighbor and from emitter reaches inside.
+        let emitter = Voxel::new(1, 100, 8);
+        let mut kind = ChunkStorage::<voxel::Kind>::default();
+        kind.set(emitter, 4.into());
+        for x in 0..=chunk::X_END {
+            for z in 0..=chunk::Z_END {
+                kind.set(Voxel::new(x, chunk::Y_END, z), 1.into());
+            }
+        }
+
+        let mut neighbor_light = ChunkStorage::default();
+        light::init_natural(&ChunkStorage::default(), &mut neighbor_light);
+
+        let chunk = Chunk::default();
+        let left = chunk.neighbor(IVec2::NEG_X);
+        let (entity, left_entity) = (app.world.spawn_empty().id(), app.world.spawn_empty().id());
+
+        let mut links = [None; chunk::SIDE_COUNT];
+        links[chunk::ChunkSide::Left.index()] = Some(left_entity);
+        app.world.entity_mut(entity).insert((
+            ChunkBundle {
+                local: ChunkLocal(chunk),
+                kind: ChunkKind(kind.clone().into()),
+                neighbors: ChunkNeighbors(links),
+                ..Default::default()
+            },
+            // Persisted light is invalid, so it is recomputed from scratch.
+            LightRecomputeTask::spawn(kind),
+        ));
+
+        let mut links = [None; chunk::SIDE_COUNT];
+        links[chunk::ChunkSide::Right.index()] = Some(entity);
+        app.world.entity_mut(left_entity).insert(ChunkBundle {
+            local: ChunkLocal(left),
+            light: ChunkLight(neighbor_light),
+            neighbors: ChunkNeighbors(links),
+            ..Default::default()
+        });
+
+        app.world.resource_mut::<ChunkMap>().insert(chunk, entity);
+        app.world
+            .resource_mut::<ChunkMap>()
+            .insert(left, left_entity);
+
+        // act
+        let mut ticks = 0;
+        while app.world.get::<LightRecomputeTask>(entity).is_some() {
+            assert!(ticks < 1000, "Light recompute should finish");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            app.update();
+            ticks += 1;
+        }
+        (0..4).for_each(|_| app.update());
+
+        // assert
+        let light = app.world.get::<ChunkLight>(entity).unwrap();
+        assert_eq!(light.get(emitter).get(voxel::LightTy::Artificial), 10);
+        assert_eq!(
+            light
+                .get(Voxel::new(0, 100, 8))
+                .get(voxel::LightTy::Natural),
+            voxel::Light::MAX_NATURAL_INTENSITY - 1,
+            "Neighbor light should reach covered chunk"
+        );
+
+        let left_light = app.world.get::<ChunkLight>(left_entity).unwrap();
+        assert_eq!(
+            left_light
+                .get(Voxel::new(chunk::X_END, 100, 8))
+                .get(voxel::LightTy::Artificial),
+            8,
+            "Emitter light should reach neighbor"
+        );
+    }
+
+    #[test]
+    fn slanted_sunlight_step() {
+        let mut sunlight = SlantedSunlight {
+            enabled: false,
+            max_slant: 2.0,
+            steps_per_day: 4,
+        };
+        assert_eq!(sunlight.step(0.5), None, "Disabled has no steps");
+
+        sunlight.enabled = true;
+        assert_eq!(sunlight.step(0.3), Some((1, 2.0)), "Sunrise slant");
+        assert_eq!(sunlight.step(0.6), Some((2, 0.0)), "Noon has no slant");
+        assert_eq!(sunlight.step(0.8), Some((3, -2.0)), "Sunset slant");
+        assert_eq!(
+            sunlight.step(0.1),
+            Some((0, 2.0)),
+            "Night before sunrise keeps sunrise slant"
+        );
+    }
 }
@@ -1,46 +1,1323 @@
-use bevy::prelude::*;
+use std::path::PathBuf;
 
-use projekto_messages::LandscapeUpdate;
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future::poll_once, IoTaskPool, Task, TaskPool},
+};
+
+use projekto_core::{chunk, voxel};
+use projekto_messages::{
+    ChunkInspect, ChunkInspection, ChunkKindSubscribe, ChunkLoad, EditRedo, EditUndo,
+    LandscapeUpdate, PlayerTransform, RegionClaim, RegionUnclaim, StructureCopied, StructureCopy,
+    StructurePaste, Teleport, VoxelUpdate, VoxelUpdateRejected, WorldExport, WorldExported,
+};
 use projekto_proto::{ClientId, RegisterMessageHandler};
 
 use crate::{
-    bundle::{ChunkLocal, ChunkVertex},
-    net::Clients,
+    bundle::{
+        ChunkColumns, ChunkContentHash, ChunkDecorations, ChunkFacesOcclusion, ChunkKind,
+        ChunkLight, ChunkLiquidVertex, ChunkLocal, ChunkQuery, ChunkVertex, ChunkVertexHash,
+    },
+    export,
+    net::{ClientCapabilities, Clients},
+    protection::{Admins, ProtectedRegion, Protection},
+    terraform::{self, ApplyChunkDiff, Clipboards, Placement, StructureTemplate},
+    ServerTick, WorldSet,
+};
+
+use super::{
+    EditHistory, KindSubscriptions, Landscape, LightEdits, PlayerTransforms, PlayerVelocities,
+    PlayerVelocity, VoxelEdit,
 };
 
-use super::Landscape;
+/// Players moving farther than this, in a single update, are teleported instead of interpolated
+/// by other clients.
+const TELEPORT_DISTANCE: f32 = 8.0;
 
 pub(crate) struct ReceiveRequestsPlugin;
 
 impl Plugin for ReceiveRequestsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_message_handler(handle_landscape_update);
+        app.init_resource::<EditHistory>()
+            .init_resource::<Admins>()
+            .add_event::<TeleportPlayer>()
+            .add_systems(
+                PreUpdate,
+                teleport_players
+                    .run_if(on_event::<TeleportPlayer>())
+                    .in_set(WorldSet::ReceiveRequests),
+            )
+            .add_systems(Update, collect_world_exports.in_set(WorldSet::CollectAsync))
+            .add_message_handler(handle_landscape_update)
+            .add_message_handler(handle_chunk_load)
+            .add_message_handler(handle_voxel_update)
+            .add_message_handler(handle_chunk_kind_subscribe)
+            .add_message_handler(handle_player_transform)
+            .add_message_handler(handle_chunk_inspect)
+            .add_message_handler(handle_world_export)
+            .add_message_handler(handle_edit_undo)
+            .add_message_handler(handle_edit_redo)
+            .add_message_handler(handle_structure_copy)
+            .add_message_handler(handle_structure_paste)
+            .set_message_handler(handle_region_claim)
+            .add_message_handler(handle_region_unclaim);
+    }
+}
+
+/// Moves a player to the given position. Player is notified with [`Teleport`] and landscape is
+/// moved right away, so chunks around the destination are streamed before the player reports its
+/// new position.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TeleportPlayer {
+    pub id: ClientId,
+    pub position: Vec3,
+}
+
+fn teleport_players(
+    mut reader: EventReader<TeleportPlayer>,
+    clients: Res<Clients>,
+    landscape: Option<Res<Landscape>>,
+    mut commands: Commands,
+) {
+    for &TeleportPlayer { id, position } in reader.read() {
+        if !position.is_finite() {
+            warn!("[{id}] Ignoring teleport to invalid position {position}");
+            continue;
+        }
+
+        let Some(client) = clients.get(&id) else {
+            debug!("[{id}] Ignoring teleport of disconnected player");
+            continue;
+        };
+
+        debug!("[{id}] Teleporting player to {position}");
+        let _ = client.channel().send(Teleport { position });
+
+        if let Some(landscape) = &landscape {
+            // Player velocity doesn't make sense across a teleport and ring around the old position
+            // is useless, so only the destination is loaded, until server is idle again.
+            commands.insert_resource(Landscape {
+                center: chunk::Chunk::from(position).xz(),
+                radius: landscape.radius,
+                ..Default::default()
+            });
+        }
     }
 }
 
 fn handle_landscape_update(
     In((id, msg)): In<(ClientId, LandscapeUpdate)>,
-    q: Query<(&ChunkLocal, &ChunkVertex)>,
+    q: Query<(
+        &ChunkLocal,
+        &ChunkVertexHash,
+        &ChunkContentHash,
+        &ChunkVertex,
+        &ChunkKind,
+        &ChunkColumns,
+        &ChunkDecorations,
+        &ChunkLiquidVertex,
+    )>,
     clients: Res<Clients>,
+    subscriptions: Res<KindSubscriptions>,
+    capabilities: Res<ClientCapabilities>,
+    landscape: Option<Res<Landscape>>,
     mut commands: Commands,
 ) {
     trace!("[{id}], handle_landscape_update");
 
-    commands.insert_resource(Landscape {
+    let kind_radius = subscriptions.get(&id).copied();
+    let delta_updates = capabilities.of(id).delta_updates;
+
+    let landscape = Landscape {
         center: msg.center,
         radius: msg.radius,
-    });
+        ..landscape.as_deref().copied().unwrap_or_default()
+    };
+    commands.insert_resource(landscape);
 
-    for (ChunkLocal(chunk), ChunkVertex(vertex)) in &q {
-        if vertex.is_empty() {
+    for (
+        ChunkLocal(chunk),
+        ChunkVertexHash(hash),
+        content_hash,
+        ChunkVertex(vertex),
+        ChunkKind(kind),
+        columns,
+        decorations,
+        liquid_vertex,
+    ) in &q
+    {
+        if vertex.is_empty() || !landscape.is_visible(*chunk) {
             continue;
         }
 
         if let Some(client) = clients.get(&id) {
-            let _ = client.channel().send(projekto_messages::ChunkVertex {
+            if delta_updates {
+                // Clients may have this chunk mesh cached, so only send the hash. Vertices are
+                // sent when requested by `ChunkLoad`.
+                let _ = client.channel().send(projekto_messages::ChunkVertexHash {
+                    chunk: *chunk,
+                    hash: *hash,
+                });
+            } else {
+                super::send_chunk_vertex(client, *chunk, content_hash.0, vertex);
+            }
+
+            if !columns.is_empty() {
+                let _ = client
+                    .channel()
+                    .send(projekto_messages::ChunkColumnSummary {
+                        chunk: *chunk,
+                        columns: columns.0.clone(),
+                    });
+            }
+
+            if !decorations.is_empty() {
+                let _ = client.channel().send(projekto_messages::ChunkDecorations {
+                    chunk: *chunk,
+                    decorations: decorations.0.clone(),
+                });
+            }
+
+            if !liquid_vertex.is_empty() {
+                let _ = client.channel().send(projekto_messages::ChunkLiquidVertex {
+                    chunk: *chunk,
+                    vertex: liquid_vertex.0.clone(),
+                });
+            }
+
+            if kind_radius.is_some_and(|radius| super::is_within_radius(msg.center, *chunk, radius))
+            {
+                let _ = client.channel().send(projekto_messages::ChunkKind {
+                    chunk: *chunk,
+                    kind: kind.storage().clone(),
+                });
+            }
+        }
+    }
+}
+
+fn handle_chunk_load(
+    In((id, msg)): In<(ClientId, ChunkLoad)>,
+    q: ChunkQuery<(&ChunkContentHash, &ChunkVertex)>,
+    clients: Res<Clients>,
+) {
+    trace!("[{id}], handle_chunk_load");
+
+    let ChunkLoad { chunk } = msg;
+
+    let (Some((hash, vertex)), Some(client)) = (q.get_chunk(chunk), clients.get(&id)) else {
+        return;
+    };
+
+    super::send_chunk_vertex(client, chunk, hash.0, vertex);
+}
+
+fn handle_chunk_inspect(
+    In((id, msg)): In<(ClientId, ChunkInspect)>,
+    q: ChunkQuery<(&ChunkKind, &ChunkLight, &ChunkFacesOcclusion, &ChunkVertex)>,
+    clients: Res<Clients>,
+    admins: Res<Admins>,
+) {
+    trace!("[{id}], handle_chunk_inspect");
+
+    let ChunkInspect { chunk } = msg;
+
+    if !admins.contains(&id) {
+        debug!("[{id}] Rejecting chunk inspect of {chunk}: not an admin");
+        return;
+    }
+
+    let (Some((kind, light, occlusion, vertex)), Some(client)) =
+        (q.get_chunk(chunk), clients.get(&id))
+    else {
+        return;
+    };
+
+    let _ = client
+        .channel()
+        .send(inspect_chunk(chunk, kind, light, occlusion, vertex));
+}
+
+/// **Returns** the inspection of the given chunk, with its layers and occlusion stats.
+fn inspect_chunk(
+    chunk: chunk::Chunk,
+    kind: &ChunkKind,
+    light: &ChunkLight,
+    occlusion: &ChunkFacesOcclusion,
+    vertex: &ChunkVertex,
+) -> ChunkInspection {
+    let (occluded_faces, fully_occluded_voxels) = chunk::zip::zip(kind, occlusion)
+        .filter(|(_, kind, _)| !kind.is_none())
+        .map(|(_, _, &occlusion)| occlusion)
+        .fold((0, 0), |(faces, voxels), occlusion| {
+            let occluded = voxel::SIDES
+                .iter()
+                .filter(|&&side| occlusion.is_occluded(side))
+                .count() as u32;
+            (
+                faces + occluded,
+                voxels + u32::from(occlusion.is_fully_occluded()),
+            )
+        });
+
+    ChunkInspection {
+        chunk,
+        kind: kind.storage().clone(),
+        light: light.0.clone(),
+        occluded_faces,
+        fully_occluded_voxels,
+        vertex_count: vertex.len() as u32,
+    }
+}
+
+/// World export being written on [`IoTaskPool`]. Client is notified once it is done.
+#[derive(Component)]
+pub(crate) struct WorldExportTask {
+    id: ClientId,
+    center: chunk::Chunk,
+    count: u32,
+    task: Task<std::io::Result<PathBuf>>,
+}
+
+fn handle_world_export(
+    In((id, msg)): In<(ClientId, WorldExport)>,
+    q: Query<(&ChunkLocal, &ChunkVertex)>,
+    admins: Res<Admins>,
+    mut commands: Commands,
+) {
+    trace!("[{id}], handle_world_export");
+
+    let WorldExport { center, radius } = msg;
+
+    if !admins.contains(&id) {
+        debug!("[{id}] Rejecting world export around {center}: not an admin");
+        return;
+    }
+
+    // Task outlives this system, so it needs its own copy of vertices.
+    let chunks = q
+        .iter()
+        .filter(|(ChunkLocal(chunk), ChunkVertex(vertex))| {
+            !vertex.is_empty() && super::is_within_radius(center.into(), *chunk, radius)
+        })
+        .map(|(ChunkLocal(chunk), ChunkVertex(vertex))| (*chunk, vertex.clone()))
+        .collect::<Vec<_>>();
+    let count = chunks.len() as u32;
+
+    let pool = IoTaskPool::get_or_init(TaskPool::default);
+    let task = pool.spawn(async move {
+        export::export_world(
+            center,
+            chunks.iter().map(|(chunk, vertex)| (*chunk, &vertex[..])),
+        )
+    });
+
+    commands.spawn(WorldExportTask {
+        id,
+        center,
+        count,
+        task,
+    });
+}
+
+fn collect_world_exports(
+    mut commands: Commands,
+    mut q: Query<(Entity, &mut WorldExportTask)>,
+    clients: Res<Clients>,
+) {
+    for (entity, mut export) in &mut q {
+        let Some(result) = block_on(poll_once(&mut export.task)) else {
+            continue;
+        };
+
+        commands.entity(entity).despawn();
+
+        let WorldExportTask {
+            id, center, count, ..
+        } = *export;
+
+        match result {
+            Ok(path) => {
+                info!("[{id}] Exported {count} chunks around {center} to {path:?}");
+                if let Some(client) = clients.get(&id) {
+                    let _ = client.channel().send(WorldExported {
+                        path: path.to_string_lossy().to_string(),
+                        chunks: count,
+                    });
+                }
+            }
+            Err(err) => error!("[{id}] Failed to export chunks around {center}. Error: {err}"),
+        }
+    }
+}
+
+fn handle_chunk_kind_subscribe(
+    In((id, msg)): In<(ClientId, ChunkKindSubscribe)>,
+    q: Query<(&ChunkLocal, &ChunkKind)>,
+    clients: Res<Clients>,
+    landscape: Option<Res<Landscape>>,
+    capabilities: Res<ClientCapabilities>,
+    mut subscriptions: ResMut<KindSubscriptions>,
+) {
+    trace!("[{id}], handle_chunk_kind_subscribe");
+
+    let ChunkKindSubscribe { radius } = msg;
+
+    if !capabilities.of(id).kind_cache {
+        warn!("[{id}] Client without kind cache tried to subscribe to chunk kinds. Ignoring.");
+        return;
+    }
+
+    if radius == 0 {
+        subscriptions.remove(&id);
+        return;
+    }
+
+    subscriptions.insert(id, radius);
+
+    // Chunks already loaded won't be notified again until they change, so send them right away.
+    let (Some(landscape), Some(client)) = (landscape, clients.get(&id)) else {
+        return;
+    };
+
+    for (ChunkLocal(chunk), ChunkKind(kind)) in &q {
+        if super::is_within_radius(landscape.center, *chunk, radius) {
+            let _ = client.channel().send(projekto_messages::ChunkKind {
                 chunk: *chunk,
-                vertex: vertex.clone(),
+                kind: kind.storage().clone(),
             });
         }
     }
 }
+
+fn handle_player_transform(
+    In((id, msg)): In<(ClientId, PlayerTransform)>,
+    clients: Res<Clients>,
+    capabilities: Res<ClientCapabilities>,
+    mut players: ResMut<PlayerTransforms>,
+    mut velocities: ResMut<PlayerVelocities>,
+    time: Res<Time>,
+    tick: Res<ServerTick>,
+) {
+    let PlayerTransform { position, rotation } = msg;
+
+    if !position.is_finite() || !rotation.is_finite() {
+        debug!("[{id}] Ignoring invalid player transform");
+        return;
+    }
+
+    let transform = Transform::from_translation(position).with_rotation(rotation);
+    // First transform of a player is also a teleport, since there is nothing to interpolate from.
+    let teleport = match players.insert(id, transform) {
+        Some(previous) => previous.translation.distance(position) > TELEPORT_DISTANCE,
+        None => true,
+    };
+
+    let now = time.elapsed_seconds();
+    match velocities.get_mut(&id) {
+        Some(velocity) if !teleport => velocity.sample(position, now),
+        _ => {
+            velocities.insert(id, PlayerVelocity::at(position, now));
+        }
+    }
+
+    // Spectators are only watching, so other players don't see them.
+    if capabilities.of(id).spectator {
+        return;
+    }
+
+    for (other_id, client) in clients.iter() {
+        if *other_id == id {
+            continue;
+        }
+
+        let _ = client
+            .channel()
+            .send(projekto_messages::RemotePlayerTransform {
+                player: id.into(),
+                position,
+                rotation,
+                teleport,
+                tick: tick.0,
+            });
+    }
+}
+
+fn handle_voxel_update(
+    In((id, msg)): In<(ClientId, VoxelUpdate)>,
+    mut q: ChunkQuery<(&mut ChunkKind, &ChunkLight)>,
+    clients: Res<Clients>,
+    protection: Protection,
+    mut history: ResMut<EditHistory>,
+    mut light_edits: LightEdits,
+) {
+    trace!("[{id}], handle_voxel_update");
+
+    let VoxelUpdate { chunk, voxel, kind } = msg;
+
+    let reject = |reason: &str| {
+        debug!("[{id}] Rejecting voxel update {voxel} on {chunk}: {reason}");
+        if let Some(client) = clients.get(&id) {
+            let _ = client.channel().send(VoxelUpdateRejected { chunk, voxel });
+        }
+    };
+
+    if !chunk::is_inside(voxel) {
+        return reject("voxel out of chunk bounds");
+    }
+
+    if !kind.exists() {
+        return reject("unknown kind");
+    }
+
+    if !protection.can_edit(id, chunk, kind) {
+        return reject("chunk is protected");
+    }
+
+    let Some((mut chunk_kind, chunk_light)) = q.get_chunk_mut(chunk) else {
+        return reject("chunk not loaded");
+    };
+
+    let current = chunk_kind.get(voxel);
+    if current == kind {
+        return reject("voxel already has the given kind");
+    }
+
+    // Layers, like snow, are replaced by solid voxels placed on them.
+    if kind.is_solid() && current.is_solid() && !current.is_layer() {
+        return reject("voxel is already occupied");
+    }
+
+    history.record(
+        id,
+        VoxelEdit {
+            chunk,
+            voxel,
+            before: current,
+            after: kind,
+        },
+    );
+
+    set_voxel_kind(
+        chunk,
+        voxel,
+        kind,
+        (&mut chunk_kind, chunk_light),
+        &mut light_edits,
+    );
+}
+
+fn handle_edit_undo(
+    In((id, msg)): In<(ClientId, EditUndo)>,
+    mut q: ChunkQuery<(&mut ChunkKind, &ChunkLight)>,
+    protection: Protection,
+    mut history: ResMut<EditHistory>,
+    mut light_edits: LightEdits,
+) {
+    trace!("[{id}], handle_edit_undo");
+
+    let edits = history.undo(id, msg.count as usize);
+    apply_edits(id, edits, &mut q, &protection, &mut light_edits);
+}
+
+fn handle_edit_redo(
+    In((id, msg)): In<(ClientId, EditRedo)>,
+    mut q: ChunkQuery<(&mut ChunkKind, &ChunkLight)>,
+    protection: Protection,
+    mut history: ResMut<EditHistory>,
+    mut light_edits: LightEdits,
+) {
+    trace!("[{id}], handle_edit_redo");
+
+    let edits = history.redo(id, msg.count as usize);
+    apply_edits(id, edits, &mut q, &protection, &mut light_edits);
+}
+
+fn handle_structure_copy(
+    In((id, msg)): In<(ClientId, StructureCopy)>,
+    q: ChunkQuery<&ChunkKind>,
+    clients: Res<Clients>,
+    mut clipboards: ResMut<Clipboards>,
+) {
+    trace!("[{id}], handle_structure_copy");
+
+    let StructureCopy { min, max } = msg;
+
+    let template = StructureTemplate::copy(min, max, |world| {
+        let (chunk, voxel) = terraform::split_world(world);
+        if !chunk::is_inside(voxel) {
+            return None;
+        }
+        q.get_chunk(chunk).map(|kind| kind.get(voxel))
+    });
+
+    let Some(template) = template else {
+        debug!("[{id}] Rejecting structure copy from {min} to {max}: too big or not loaded");
+        return;
+    };
+
+    if let Some(client) = clients.get(&id) {
+        let _ = client.channel().send(StructureCopied {
+            size: template.size(),
+        });
+    }
+
+    clipboards.insert(id, template);
+}
+
+fn handle_structure_paste(
+    In((id, msg)): In<(ClientId, StructurePaste)>,
+    clipboards: Res<Clipboards>,
+    protection: Protection,
+    mut writer: EventWriter<ApplyChunkDiff>,
+) {
+    trace!("[{id}], handle_structure_paste");
+
+    let StructurePaste {
+        origin,
+        rotation,
+        mirror,
+    } = msg;
+
+    let Some(template) = clipboards.get(&id) else {
+        debug!("[{id}] Ignoring structure paste: nothing was copied");
+        return;
+    };
+
+    let diff = template.paste(origin, Placement { rotation, mirror });
+
+    let protected = diff.chunks().any(|chunk| {
+        diff.changes(chunk)
+            .iter()
+            .any(|&(_, kind)| !protection.can_edit(id, chunk, kind))
+    });
+    if protected {
+        debug!("[{id}] Rejecting structure paste at {origin}: a chunk is protected");
+        return;
+    }
+
+    writer.send(ApplyChunkDiff(diff));
+}
+
+fn handle_region_claim(
+    In((id, msg)): In<(ClientId, RegionClaim)>,
+    mut commands: Commands,
+    protection: Protection,
+) {
+    trace!("[{id}], handle_region_claim");
+
+    let RegionClaim {
+        min,
+        max,
+        allowed,
+        flags,
+    } = msg;
+
+    if !protection.is_admin(id) {
+        debug!("[{id}] Rejecting region claim from {min} to {max}: not an admin");
+        return;
+    }
+
+    let mut region = ProtectedRegion::new(min, max, id);
+    region
+        .allowed
+        .extend(allowed.into_iter().map(ClientId::from));
+    region.flags = flags;
+
+    info!(
+        "[{id}] Claimed region from {} to {}",
+        region.min, region.max
+    );
+    commands.spawn(region);
+}
+
+fn handle_region_unclaim(
+    In((id, RegionUnclaim { chunk })): In<(ClientId, RegionUnclaim)>,
+    mut commands: Commands,
+    admins: Res<Admins>,
+    q_regions: Query<(Entity, &ProtectedRegion)>,
+) {
+    trace!("[{id}], handle_region_unclaim");
+
+    if !admins.contains(&id) {
+        debug!("[{id}] Rejecting region unclaim of {chunk}: not an admin");
+        return;
+    }
+
+    for (entity, region) in &q_regions {
+        if region.contains(chunk) {
+            info!(
+                "[{id}] Unclaimed region from {} to {}",
+                region.min, region.max
+            );
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Applies edits taken from [`EditHistory`]. Edits whose voxel was changed since then, or whose
+/// chunk isn't loaded anymore, are skipped, so newer changes are never overwritten. Edits on
+/// chunks protected since then are skipped too.
+fn apply_edits(
+    id: ClientId,
+    edits: Vec<VoxelEdit>,
+    q: &mut ChunkQuery<(&mut ChunkKind, &ChunkLight)>,
+    protection: &Protection,
+    light_edits: &mut LightEdits,
+) {
+    for VoxelEdit {
+        chunk,
+        voxel,
+        before,
+        after,
+    } in edits
+    {
+        if !protection.can_edit(id, chunk, after) {
+            debug!("[{id}] Skipping edit {voxel} on {chunk}: chunk is protected");
+            continue;
+        }
+
+        let Some((mut chunk_kind, chunk_light)) = q.get_chunk_mut(chunk) else {
+            debug!("[{id}] Skipping edit {voxel} on {chunk}: chunk not loaded");
+            continue;
+        };
+
+        if chunk_kind.get(voxel) != before {
+            debug!("[{id}] Skipping edit {voxel} on {chunk}: voxel was changed since then");
+            continue;
+        }
+
+        set_voxel_kind(
+            chunk,
+            voxel,
+            after,
+            (&mut chunk_kind, chunk_light),
+            light_edits,
+        );
+    }
+}
+
+/// Sets the given voxel kind and requests light to be updated around it. See [`LightEdits`].
+///
+/// Layers, like snow, need a solid voxel to rest on, so the layer above the given voxel is removed
+/// too when it is left without support. Transparent solids and other layers still support it.
+fn set_voxel_kind(
+    chunk: chunk::Chunk,
+    voxel: voxel::Voxel,
+    kind: voxel::Kind,
+    (chunk_kind, chunk_light): (&mut ChunkKind, &ChunkLight),
+    light_edits: &mut LightEdits,
+) {
+    let before = chunk_kind.get(voxel);
+    chunk_kind.0.set(voxel, kind);
+
+    let mut edits = vec![(voxel, before, kind)];
+
+    let above = voxel + IVec3::Y;
+    if chunk::is_inside(above) && (!kind.is_solid() || kind.is_liquid()) {
+        let above_kind = chunk_kind.get(above);
+        if above_kind.is_layer() {
+            chunk_kind.0.set(above, voxel::Kind::NONE);
+            edits.push((above, above_kind, voxel::Kind::NONE));
+        }
+    }
+
+    light_edits.send(chunk, chunk_light, &edits);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{app::ScheduleRunnerPlugin, ecs::system::RunSystemOnce};
+    use projekto_core::{
+        chunk::Chunk,
+        voxel::{LightTy, Voxel},
+    };
+    use projekto_proto::Client;
+
+    use crate::{
+        bundle::{ChunkBundle, ChunkMap},
+        set::{LightRemoval, LightUpdate},
+    };
+
+    use super::*;
+
+    fn setup_app(chunk: Chunk) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_once()))
+            .init_resource::<Clients>()
+            .init_resource::<ClientCapabilities>()
+            .init_resource::<KindSubscriptions>()
+            .init_resource::<PlayerTransforms>()
+            .init_resource::<PlayerVelocities>()
+            .init_resource::<EditHistory>()
+            .init_resource::<Admins>()
+            .init_resource::<ServerTick>()
+            .add_event::<LightUpdate>()
+            .add_event::<LightRemoval>();
+
+        let mut bundle = ChunkBundle {
+            local: ChunkLocal(chunk),
+            ..Default::default()
+        };
+
+        chunk::voxels()
+            .filter(|voxel| voxel.y < 10)
+            .for_each(|voxel| bundle.kind.0.set(voxel, voxel::Kind::id(1)));
+
+        chunk::voxels()
+            .filter(|voxel| voxel.y >= 10)
+            .for_each(|voxel| {
+                bundle
+                    .light
+                    .set_type(voxel, LightTy::Natural, voxel::Light::MAX_NATURAL_INTENSITY);
+            });
+
+        let entity = app.world.spawn(bundle).id();
+        app.world
+            .insert_resource(ChunkMap([(chunk, entity)].into_iter().collect()));
+
+        app
+    }
+
+    fn get_kind(app: &mut App, chunk: Chunk, voxel: Voxel) -> voxel::Kind {
+        let entity = app.world.resource::<ChunkMap>()[&chunk];
+        app.world.get::<ChunkKind>(entity).unwrap().get(voxel)
+    }
+
+    #[test]
+    fn voxel_update_break() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let voxel = Voxel::new(1, 9, 1);
+        let mut app = setup_app(chunk);
+
+        // act
+        app.world.run_system_once_with(
+            (
+                ClientId::default(),
+                VoxelUpdate {
+                    chunk,
+                    voxel,
+                    kind: voxel::Kind::NONE,
+                },
+            ),
+            handle_voxel_update,
+        );
+
+        // assert
+        assert_eq!(get_kind(&mut app, chunk, voxel), voxel::Kind::NONE);
+
+        let events = app.world.resource::<Events<LightUpdate>>();
+        let mut reader = events.get_reader();
+        let update = reader
+            .read(events)
+            .find(|update| update.ty == LightTy::Natural)
+            .expect("Natural light should be propagated into broken voxel");
+
+        assert_eq!(
+            update.values,
+            vec![(voxel, voxel::Light::MAX_NATURAL_INTENSITY)]
+        );
+    }
+
+    #[test]
+    fn voxel_update_break_under_layer() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let voxel = Voxel::new(1, 9, 1);
+        let snow = voxel::Kind::by_name("Snow").unwrap();
+        let mut app = setup_app(chunk);
+
+        let entity = app.world.resource::<ChunkMap>()[&chunk];
+        app.world
+            .get_mut::<ChunkKind>(entity)
+            .unwrap()
+            .0
+            .set(voxel + IVec3::Y, snow);
+
+        // act
+        app.world.run_system_once_with(
+            (
+                ClientId::default(),
+                VoxelUpdate {
+                    chunk,
+                    voxel,
+                    kind: voxel::Kind::NONE,
+                },
+            ),
+            handle_voxel_update,
+        );
+
+        // assert
+        assert_eq!(get_kind(&mut app, chunk, voxel), voxel::Kind::NONE);
+        assert_eq!(
+            get_kind(&mut app, chunk, voxel + IVec3::Y),
+            voxel::Kind::NONE,
+            "Layer without support should be removed"
+        );
+    }
+
+    #[test]
+    fn voxel_update_keeps_supported_layer() {
+        let chunk = Chunk::new(0, 0);
+        let voxel = Voxel::new(1, 10, 1);
+        let snow = voxel::Kind::by_name("Snow").unwrap();
+        let glass = voxel::Kind::by_name("Glass").unwrap();
+
+        // Transparent solids and other layers still support a layer placed above them.
+        for kind in [glass, snow] {
+            // arrange
+            let mut app = setup_app(chunk);
+            let entity = app.world.resource::<ChunkMap>()[&chunk];
+            app.world
+                .get_mut::<ChunkKind>(entity)
+                .unwrap()
+                .0
+                .set(voxel + IVec3::Y, snow);
+
+            // act
+            app.world.run_system_once_with(
+                (ClientId::default(), VoxelUpdate { chunk, voxel, kind }),
+                handle_voxel_update,
+            );
+
+            // assert
+            assert_eq!(get_kind(&mut app, chunk, voxel), kind);
+            assert_eq!(
+                get_kind(&mut app, chunk, voxel + IVec3::Y),
+                snow,
+                "Layer placed above {kind:?} should be kept"
+            );
+        }
+    }
+
+    #[test]
+    fn voxel_update_protected() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let voxel = Voxel::new(1, 9, 1);
+        let mut app = setup_app(chunk);
+
+        let (owner, stranger, admin) = (1.into(), 2.into(), 3.into());
+        app.world
+            .spawn(ProtectedRegion::new(chunk, Chunk::new(1, 1), owner));
+        app.world.resource_mut::<Admins>().insert(admin);
+
+        let update = |id: ClientId| {
+            (
+                id,
+                VoxelUpdate {
+                    chunk,
+                    voxel,
+                    kind: voxel::Kind::NONE,
+                },
+            )
+        };
+
+        // act
+        app.world
+            .run_system_once_with(update(stranger), handle_voxel_update);
+
+        // assert
+        assert_eq!(
+            get_kind(&mut app, chunk, voxel),
+            voxel::Kind::id(1),
+            "Protected voxel should not be changed"
+        );
+
+        // act
+        app.world
+            .run_system_once_with(update(admin), handle_voxel_update);
+
+        // assert
+        assert_eq!(get_kind(&mut app, chunk, voxel), voxel::Kind::NONE);
+    }
+
+    #[test]
+    fn region_claim_admin_only() {
+        // arrange
+        let mut app = setup_app(Chunk::new(0, 0));
+        let admin = ClientId::from(1);
+        app.world.resource_mut::<Admins>().insert(admin);
+
+        let claim = |id: ClientId| {
+            (
+                id,
+                RegionClaim {
+                    min: Chunk::new(-2, -2),
+                    max: Chunk::new(2, 2),
+                    allowed: vec![5],
+                    flags: Default::default(),
+                },
+            )
+        };
+
+        // act
+        app.world
+            .run_system_once_with(claim(2.into()), handle_region_claim);
+        app.world
+            .run_system_once_with(claim(admin), handle_region_claim);
+
+        // assert
+        let regions = app
+            .world
+            .query::<&ProtectedRegion>()
+            .iter(&app.world)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(regions.len(), 1, "Only admins can claim regions");
+        assert_eq!(regions[0].owner, admin);
+        assert!(regions[0].allowed.contains(&ClientId::from(5)));
+
+        // act
+        app.world.run_system_once_with(
+            (
+                admin,
+                RegionUnclaim {
+                    chunk: Chunk::new(1, -1),
+                },
+            ),
+            handle_region_unclaim,
+        );
+
+        // assert
+        assert_eq!(
+            app.world
+                .query::<&ProtectedRegion>()
+                .iter(&app.world)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn voxel_update_place_occupied() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let voxel = Voxel::new(1, 9, 1);
+        let mut app = setup_app(chunk);
+
+        // act
+        app.world.run_system_once_with(
+            (
+                ClientId::default(),
+                VoxelUpdate {
+                    chunk,
+                    voxel,
+                    kind: voxel::Kind::id(3),
+                },
+            ),
+            handle_voxel_update,
+        );
+
+        // assert
+        assert_eq!(
+            get_kind(&mut app, chunk, voxel),
+            voxel::Kind::id(1),
+            "Occupied voxel should not be replaced"
+        );
+    }
+
+    #[test]
+    fn voxel_update_place_blocks_light() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let voxel = Voxel::new(1, 10, 1);
+        let mut app = setup_app(chunk);
+
+        // act
+        app.world.run_system_once_with(
+            (
+                ClientId::default(),
+                VoxelUpdate {
+                    chunk,
+                    voxel,
+                    kind: voxel::Kind::id(3),
+                },
+            ),
+            handle_voxel_update,
+        );
+
+        // assert
+        assert_eq!(get_kind(&mut app, chunk, voxel), voxel::Kind::id(3));
+
+        let events = app.world.resource::<Events<LightRemoval>>();
+        let mut reader = events.get_reader();
+        let removal = reader
+            .read(events)
+            .find(|removal| removal.ty == LightTy::Natural)
+            .expect("Opaque voxel should have its light removed");
+
+        assert_eq!(
+            removal.values,
+            vec![(voxel, voxel::Light::MAX_NATURAL_INTENSITY)]
+        );
+    }
+
+    #[test]
+    fn edit_undo_redo() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let voxel = Voxel::new(1, 10, 1);
+        let other = Voxel::new(2, 10, 1);
+        let mut app = setup_app(chunk);
+        let id = ClientId::default();
+        let place = |voxel| VoxelUpdate {
+            chunk,
+            voxel,
+            kind: voxel::Kind::id(3),
+        };
+
+        app.world
+            .run_system_once_with((id, place(voxel)), handle_voxel_update);
+        app.world
+            .run_system_once_with((id, place(other)), handle_voxel_update);
+
+        // act
+        app.world
+            .run_system_once_with((id, EditUndo { count: 2 }), handle_edit_undo);
+
+        // assert
+        assert_eq!(get_kind(&mut app, chunk, voxel), voxel::Kind::NONE);
+        assert_eq!(get_kind(&mut app, chunk, other), voxel::Kind::NONE);
+
+        // Voxel changed by someone else can't be redone.
+        let entity = app.world.resource::<ChunkMap>()[&chunk];
+        app.world
+            .get_mut::<ChunkKind>(entity)
+            .unwrap()
+            .0
+            .set(other, voxel::Kind::id(1));
+
+        // act
+        app.world
+            .run_system_once_with((id, EditRedo { count: 2 }), handle_edit_redo);
+
+        // assert
+        assert_eq!(get_kind(&mut app, chunk, voxel), voxel::Kind::id(3));
+        assert_eq!(
+            get_kind(&mut app, chunk, other),
+            voxel::Kind::id(1),
+            "Newer changes should not be overwritten"
+        );
+    }
+
+    #[test]
+    fn teleport_player() {
+        // arrange
+        let mut app = setup_app(Chunk::new(0, 0));
+        let (client, channel) = projekto_proto::Client::loopback(1);
+        let id = client.id();
+        app.world.resource_mut::<Clients>().insert(id, client);
+        app.world.insert_resource(Landscape {
+            center: IVec2::ZERO,
+            radius: 4,
+            prefetch: IVec2::new(2, 0),
+            ring: 2,
+        });
+        app.add_event::<TeleportPlayer>();
+
+        let position = Vec3::new(100.0, 50.0, -40.0);
+        app.world.send_event(TeleportPlayer { id, position });
+
+        // act
+        app.world.run_system_once(teleport_players);
+
+        // assert
+        let teleport = channel
+            .try_recv_all()
+            .into_iter()
+            .find_map(|boxed| boxed.downcast::<Teleport>().ok())
+            .expect("Player should be notified");
+        assert_eq!(teleport.position, position);
+
+        let landscape = app.world.resource::<Landscape>();
+        assert_eq!(landscape.center, Chunk::from(position).xz());
+        assert_eq!(landscape.radius, 4, "Landscape radius should be kept");
+        assert_eq!(landscape.prefetch, IVec2::ZERO, "Prefetch should be reset");
+        assert_eq!(landscape.ring, 0, "Ring should be reset");
+    }
+
+    #[test]
+    fn chunk_kind_subscribe() {
+        // arrange
+        let mut app = setup_app(Chunk::new(0, 0));
+        let id = ClientId::default();
+
+        // act
+        app.world.run_system_once_with(
+            (id, ChunkKindSubscribe { radius: 3 }),
+            handle_chunk_kind_subscribe,
+        );
+
+        // assert
+        assert_eq!(app.world.resource::<KindSubscriptions>().get(&id), Some(&3));
+
+        // act
+        app.world.run_system_once_with(
+            (id, ChunkKindSubscribe { radius: 0 }),
+            handle_chunk_kind_subscribe,
+        );
+
+        // assert
+        assert!(
+            app.world.resource::<KindSubscriptions>().is_empty(),
+            "Zero radius should unsubscribe"
+        );
+    }
+
+    #[test]
+    fn chunk_kind_subscribe_without_kind_cache() {
+        // arrange
+        let mut app = setup_app(Chunk::new(0, 0));
+        let id = ClientId::default();
+        app.world.resource_mut::<ClientCapabilities>().insert(
+            id,
+            projekto_messages::Capabilities {
+                kind_cache: false,
+                ..default()
+            },
+        );
+
+        // act
+        app.world.run_system_once_with(
+            (id, ChunkKindSubscribe { radius: 3 }),
+            handle_chunk_kind_subscribe,
+        );
+
+        // assert
+        assert!(
+            app.world.resource::<KindSubscriptions>().is_empty(),
+            "Clients without kind cache can't subscribe"
+        );
+    }
+
+    #[test]
+    fn player_transform() {
+        // arrange
+        let mut app = setup_app(Chunk::new(0, 0));
+        let id = ClientId::default();
+        let msg = |position| PlayerTransform {
+            position,
+            rotation: Quat::IDENTITY,
+        };
+
+        // act
+        app.world
+            .run_system_once_with((id, msg(Vec3::new(1.0, 2.0, 3.0))), handle_player_transform);
+        app.world
+            .run_system_once_with((id, msg(Vec3::NAN)), handle_player_transform);
+
+        // assert
+        assert_eq!(
+            app.world
+                .resource::<PlayerTransforms>()
+                .get(&id)
+                .map(|transform| transform.translation),
+            Some(Vec3::new(1.0, 2.0, 3.0)),
+            "Invalid transform should be ignored"
+        );
+    }
+
+    #[test]
+    fn chunk_inspection() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let app = setup_app(chunk);
+        let entity = app.world.resource::<ChunkMap>()[&chunk];
+
+        let mut occlusion = ChunkFacesOcclusion::default();
+        let mut partial = voxel::FacesOcclusion::default();
+        partial.set(voxel::Side::Up, true);
+        occlusion
+            .0
+            .set(Voxel::new(0, 0, 0), voxel::FacesOcclusion::fully_occluded());
+        occlusion.0.set(Voxel::new(1, 0, 0), partial);
+        // Empty voxels are ignored, even if occluded.
+        occlusion.0.set(
+            Voxel::new(0, 20, 0),
+            voxel::FacesOcclusion::fully_occluded(),
+        );
+
+        let vertex = ChunkVertex(vec![voxel::Vertex::default(); 6]);
+
+        // act
+        let world = &app.world;
+        let inspection = inspect_chunk(
+            chunk,
+            world.get::<ChunkKind>(entity).unwrap(),
+            world.get::<ChunkLight>(entity).unwrap(),
+            &occlusion,
+            &vertex,
+        );
+
+        // assert
+        assert_eq!(inspection.chunk, chunk);
+        assert_eq!(inspection.kind.get(Voxel::new(0, 0, 0)), voxel::Kind::id(1));
+        assert_eq!(
+            inspection
+                .light
+                .get(Voxel::new(0, 20, 0))
+                .get(LightTy::Natural),
+            voxel::Light::MAX_NATURAL_INTENSITY
+        );
+        assert_eq!(inspection.occluded_faces, voxel::SIDE_COUNT as u32 + 1);
+        assert_eq!(inspection.fully_occluded_voxels, 1);
+        assert_eq!(inspection.vertex_count, 6);
+    }
+
+    #[test]
+    fn debug_requests_admin_only() {
+        // arrange
+        let chunk = Chunk::new(0, 0);
+        let mut app = setup_app(chunk);
+        let (stranger, stranger_channel) = Client::loopback(1);
+        let (admin, admin_channel) = Client::loopback(2);
+        let (stranger, admin) = {
+            let mut clients = app.world.resource_mut::<Clients>();
+            let ids = (stranger.id(), admin.id());
+            clients.insert(ids.0, stranger);
+            clients.insert(ids.1, admin);
+            ids
+        };
+        app.world.resource_mut::<Admins>().insert(admin);
+
+        // act
+        for id in [stranger, admin] {
+            app.world
+                .run_system_once_with((id, ChunkInspect { chunk }), handle_chunk_inspect);
+        }
+        app.world.run_system_once_with(
+            (
+                stranger,
+                WorldExport {
+                    center: chunk,
+                    radius: 1,
+                },
+            ),
+            handle_world_export,
+        );
+
+        // assert
+        assert!(
+            stranger_channel.try_recv_all().is_empty(),
+            "Only admins can inspect chunks"
+        );
+        let inspections = admin_channel
+            .try_recv_all()
+            .into_iter()
+            .filter_map(|boxed| boxed.downcast::<ChunkInspection>().ok())
+            .collect::<Vec<_>>();
+        assert_eq!(inspections.len(), 1);
+        assert_eq!(inspections[0].chunk, chunk);
+
+        assert_eq!(
+            app.world
+                .query::<&WorldExportTask>()
+                .iter(&app.world)
+                .count(),
+            0,
+            "Only admins can export world"
+        );
+    }
+}
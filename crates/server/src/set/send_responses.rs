@@ -1,26 +1,149 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
+use projekto_messages::{ClientMessage, ServerMessage, CHUNK_VERTEX_PART_LEN};
+use projekto_proto::{Client, ClientId};
 
 use crate::{
-    bundle::{ChunkLocal, ChunkVertex},
+    bundle::{
+        ChunkColumns, ChunkContentHash, ChunkDecorations, ChunkKind, ChunkLiquidVertex, ChunkLocal,
+        ChunkVertex,
+    },
     net::Clients,
+    terraform::Clipboards,
     WorldSet,
 };
+use projekto_core::{chunk::Chunk, voxel};
 use projekto_messages as messages;
 
+use super::{EditHistory, Landscape};
+
+/// Radius, in chunks from landscape center, which each client asked to receive chunk kinds, so it
+/// is able to interact with nearby voxels. Clients which didn't subscribe only receive vertices.
+#[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
+pub(crate) struct KindSubscriptions(HashMap<ClientId, u8>);
+
+/// Last transform of each connected player, which is relayed to other players.
+#[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
+pub(crate) struct PlayerTransforms(HashMap<ClientId, Transform>);
+
+/// Minimum time, in seconds, between player velocity samples, so transforms received right after
+/// each other don't make velocity jitter.
+const VELOCITY_SAMPLE_SECS: f32 = 0.1;
+/// Players which didn't move for this long, in seconds, are considered stopped, since clients only
+/// send their transforms when it changes.
+const VELOCITY_STALE_SECS: f32 = 1.0;
+
+/// Velocity of a player, estimated from its transforms over time.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct PlayerVelocity {
+    velocity: Vec3,
+    sample_position: Vec3,
+    /// Elapsed time, in seconds, when the last sample was taken.
+    sample_time: f32,
+}
+
+impl PlayerVelocity {
+    /// Creates a stopped player velocity, at the given position and time.
+    pub fn at(position: Vec3, time: f32) -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            sample_position: position,
+            sample_time: time,
+        }
+    }
+
+    /// Updates velocity with the given player position, at the given time.
+    pub fn sample(&mut self, position: Vec3, time: f32) {
+        let elapsed = time - self.sample_time;
+        if elapsed >= VELOCITY_SAMPLE_SECS {
+            self.velocity = (position - self.sample_position) / elapsed;
+            self.sample_position = position;
+            self.sample_time = time;
+        }
+    }
+
+    /// **Returns** player velocity at the given time, which is zero if it stopped sending
+    /// transforms.
+    pub fn current(&self, time: f32) -> Vec3 {
+        if time - self.sample_time > VELOCITY_STALE_SECS {
+            Vec3::ZERO
+        } else {
+            self.velocity
+        }
+    }
+}
+
+/// Velocity of each connected player, used to prefetch chunks ahead of it.
+#[derive(Resource, Default, Debug, Clone, Deref, DerefMut)]
+pub(crate) struct PlayerVelocities(HashMap<ClientId, PlayerVelocity>);
+
+pub(crate) fn is_within_radius(center: IVec2, chunk: Chunk, radius: u8) -> bool {
+    chunk.distance(center.into()).abs().max_element() <= radius as i32
+}
+
+/// Sends chunk vertices to the given client, split in [`messages::ChunkVertexPart`]s when there
+/// are more than [`CHUNK_VERTEX_PART_LEN`] vertices.
+pub(crate) fn send_chunk_vertex(
+    client: &Client<ClientMessage, ServerMessage>,
+    chunk: Chunk,
+    hash: u64,
+    vertex: &[voxel::Vertex],
+) {
+    if vertex.len() <= CHUNK_VERTEX_PART_LEN {
+        let _ = client.channel().send(messages::ChunkVertex {
+            chunk,
+            hash,
+            vertex: vertex.to_vec(),
+        });
+        return;
+    }
+
+    let total = vertex.len().div_ceil(CHUNK_VERTEX_PART_LEN) as u16;
+    for (part, data) in vertex.chunks(CHUNK_VERTEX_PART_LEN).enumerate() {
+        let _ = client.channel().send(messages::ChunkVertexPart {
+            chunk,
+            hash,
+            part: part as u16,
+            total,
+            data: data.to_vec(),
+        });
+    }
+}
+
+/// **Returns** `true` if updates of the given chunk should be sent to clients. Chunks loaded ahead
+/// of time are only sent once they are inside landscape radius.
+fn is_visible(landscape: &Option<Res<Landscape>>, chunk: Chunk) -> bool {
+    match landscape {
+        Some(landscape) => landscape.is_visible(chunk),
+        None => true,
+    }
+}
+
 pub(crate) struct SendResponsesPlugin;
 
 impl Plugin for SendResponsesPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            PostUpdate,
-            notify_chunk_vertex_updated.in_set(WorldSet::SendResponses),
-        );
+        app.init_resource::<KindSubscriptions>()
+            .init_resource::<PlayerTransforms>()
+            .init_resource::<PlayerVelocities>()
+            .add_systems(
+                PostUpdate,
+                (
+                    notify_chunk_vertex_updated,
+                    notify_chunk_kind_updated,
+                    notify_chunk_columns_updated,
+                    notify_chunk_decorations_updated,
+                    notify_chunk_liquid_vertex_updated,
+                    notify_player_left,
+                )
+                    .in_set(WorldSet::SendResponses),
+            );
     }
 }
 
 fn notify_chunk_vertex_updated(
     clients: Res<Clients>,
-    q: Query<(&ChunkLocal, &ChunkVertex), Changed<ChunkVertex>>,
+    landscape: Option<Res<Landscape>>,
+    q: Query<(&ChunkLocal, &ChunkContentHash, &ChunkVertex), Changed<ChunkVertex>>,
 ) {
     if q.is_empty() {
         return;
@@ -31,15 +154,209 @@ fn notify_chunk_vertex_updated(
         return;
     }
 
-    for (ChunkLocal(chunk), ChunkVertex(vertex)) in &q {
-        if vertex.is_empty() {
+    for (ChunkLocal(chunk), ChunkContentHash(hash), ChunkVertex(vertex)) in &q {
+        if vertex.is_empty() || !is_visible(&landscape, *chunk) {
             continue;
         }
         for client in clients.values() {
-            let _ = client.channel().send(messages::ChunkVertex {
+            send_chunk_vertex(client, *chunk, *hash, vertex);
+        }
+    }
+}
+
+fn notify_chunk_kind_updated(
+    clients: Res<Clients>,
+    subscriptions: Res<KindSubscriptions>,
+    landscape: Option<Res<Landscape>>,
+    q: Query<(&ChunkLocal, &ChunkKind), Changed<ChunkKind>>,
+) {
+    if q.is_empty() || subscriptions.is_empty() {
+        return;
+    }
+
+    let Some(landscape) = landscape else {
+        return;
+    };
+
+    for (ChunkLocal(chunk), ChunkKind(kind)) in &q {
+        for (id, &radius) in subscriptions.iter() {
+            if !is_within_radius(landscape.center, *chunk, radius) {
+                continue;
+            }
+
+            if let Some(client) = clients.get(id) {
+                let _ = client.channel().send(messages::ChunkKind {
+                    chunk: *chunk,
+                    kind: kind.storage().clone(),
+                });
+            }
+        }
+    }
+}
+
+fn notify_chunk_columns_updated(
+    clients: Res<Clients>,
+    landscape: Option<Res<Landscape>>,
+    q: Query<(&ChunkLocal, &ChunkColumns), Changed<ChunkColumns>>,
+) {
+    if q.is_empty() || clients.is_empty() {
+        return;
+    }
+
+    for (ChunkLocal(chunk), ChunkColumns(columns)) in &q {
+        if columns.is_empty() || !is_visible(&landscape, *chunk) {
+            continue;
+        }
+
+        for client in clients.values() {
+            let _ = client.channel().send(messages::ChunkColumnSummary {
+                chunk: *chunk,
+                columns: columns.clone(),
+            });
+        }
+    }
+}
+
+fn notify_chunk_decorations_updated(
+    clients: Res<Clients>,
+    landscape: Option<Res<Landscape>>,
+    q: Query<(&ChunkLocal, &ChunkDecorations), Changed<ChunkDecorations>>,
+) {
+    if q.is_empty() || clients.is_empty() {
+        return;
+    }
+
+    // Empty decorations are also sent, so clients remove the ones which were edited away.
+    for (ChunkLocal(chunk), ChunkDecorations(decorations)) in &q {
+        if !is_visible(&landscape, *chunk) {
+            continue;
+        }
+
+        for client in clients.values() {
+            let _ = client.channel().send(messages::ChunkDecorations {
+                chunk: *chunk,
+                decorations: decorations.clone(),
+            });
+        }
+    }
+}
+
+fn notify_chunk_liquid_vertex_updated(
+    clients: Res<Clients>,
+    landscape: Option<Res<Landscape>>,
+    q: Query<(&ChunkLocal, &ChunkLiquidVertex), Changed<ChunkLiquidVertex>>,
+) {
+    if q.is_empty() || clients.is_empty() {
+        return;
+    }
+
+    // Empty vertices are also sent, so clients remove liquids which were edited away.
+    for (ChunkLocal(chunk), ChunkLiquidVertex(vertex)) in &q {
+        if !is_visible(&landscape, *chunk) {
+            continue;
+        }
+
+        for client in clients.values() {
+            let _ = client.channel().send(messages::ChunkLiquidVertex {
                 chunk: *chunk,
                 vertex: vertex.clone(),
             });
         }
     }
 }
+
+fn notify_player_left(
+    clients: Res<Clients>,
+    mut players: ResMut<PlayerTransforms>,
+    mut velocities: ResMut<PlayerVelocities>,
+    mut subscriptions: ResMut<KindSubscriptions>,
+    mut history: ResMut<EditHistory>,
+    mut clipboards: ResMut<Clipboards>,
+) {
+    let left = players
+        .keys()
+        .filter(|id| !clients.contains_key(*id))
+        .copied()
+        .collect::<Vec<_>>();
+
+    subscriptions.retain(|id, _| clients.contains_key(id));
+    velocities.retain(|id, _| clients.contains_key(id));
+    history.retain(|id| clients.contains_key(id));
+    clipboards.retain(|id, _| clients.contains_key(id));
+
+    for id in left {
+        players.remove(&id);
+
+        for client in clients.values() {
+            let _ = client
+                .channel()
+                .send(messages::RemotePlayerLeft { player: id.into() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertices(count: usize) -> Vec<voxel::Vertex> {
+        (0..count)
+            .map(|i| voxel::Vertex {
+                position: Vec3::splat(i as f32),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_chunk_vertex_is_sent_whole() {
+        // arrange
+        let (client, channel) = Client::loopback(1);
+        let vertex = vertices(8);
+
+        // act
+        send_chunk_vertex(&client, Chunk::new(1, 2), 42, &vertex);
+
+        // assert
+        let received = channel.try_recv_all();
+        assert_eq!(received.len(), 1);
+        let msg = received
+            .into_iter()
+            .find_map(|boxed| boxed.downcast::<messages::ChunkVertex>().ok())
+            .expect("Should be a whole chunk vertex");
+        assert_eq!(msg.vertex, vertex);
+    }
+
+    #[test]
+    fn huge_chunk_vertex_is_sent_in_parts() {
+        // arrange
+        let (client, channel) = Client::loopback(1);
+        let vertex = vertices(CHUNK_VERTEX_PART_LEN * 2 + 4);
+
+        // act
+        send_chunk_vertex(&client, Chunk::new(1, 2), 42, &vertex);
+
+        // assert
+        let received = channel.try_recv_all();
+        assert_eq!(received.len(), 3);
+        let parts = received
+            .into_iter()
+            .filter_map(|boxed| boxed.downcast::<messages::ChunkVertexPart>().ok())
+            .collect::<Vec<_>>();
+
+        assert_eq!(parts.len(), 3, "All messages should be parts");
+        assert!(parts.iter().all(|part| part.total == 3 && part.hash == 42));
+        assert!(parts
+            .iter()
+            .enumerate()
+            .all(|(i, part)| part.part == i as u16));
+        assert_eq!(parts[2].data.len(), 4);
+        assert_eq!(
+            parts
+                .into_iter()
+                .flat_map(|part| part.data)
+                .collect::<Vec<_>>(),
+            vertex
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Area edits, like explosions, which change lots of voxels across many chunks at once.
+//!
+//! Edits are computed as a [`ChunkDiff`] and applied by sending [`ApplyChunkDiff`]. Light is
+//! updated with a single [`LightUpdate`](crate::set::LightUpdate) or
+//! [`LightRemoval`](crate::set::LightRemoval) per chunk and light type, and since faces occlusion
+//! is only computed for changed chunks, each chunk is processed once, no matter how many voxels
+//! were changed on it.
+
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::{
+    chunk::{self, Chunk, TrackedStorage},
+    voxel::{self, Voxel},
+};
+
+use crate::{
+    bundle::{ChunkKind, ChunkLight, ChunkQuery},
+    set::LightEdits,
+    WorldSet,
+};
+
+mod structure;
+
+pub(crate) use structure::Clipboards;
+pub use structure::{Placement, StructureTemplate, MAX_STRUCTURE_VOLUME};
+
+pub(crate) struct TerraformPlugin;
+
+impl Plugin for TerraformPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Clipboards>()
+            .add_event::<ApplyChunkDiff>()
+            .add_systems(
+                PreUpdate,
+                apply_chunk_diffs
+                    .run_if(on_event::<ApplyChunkDiff>())
+                    .in_set(WorldSet::ReceiveRequests),
+            );
+    }
+}
+
+/// Voxel kind changes, grouped by chunk. When the same voxel is changed many times, the last
+/// change wins.
+#[derive(Debug, Default, Clone)]
+pub struct ChunkDiff(HashMap<Chunk, Vec<(Voxel, voxel::Kind)>>);
+
+impl ChunkDiff {
+    /// Collects changes recorded on kinds of the given chunks, with their current kind, so changes
+    /// made by a system can be replayed or sent somewhere else.
+    pub fn from_tracked<'a>(
+        chunks: impl IntoIterator<Item = (Chunk, &'a TrackedStorage<voxel::Kind>)>,
+    ) -> Self {
+        let mut diff = Self::default();
+        for (chunk, kind) in chunks {
+            kind.changed()
+                .for_each(|voxel| diff.set(chunk, voxel, kind.get(voxel)));
+        }
+        diff
+    }
+
+    /// Sets the kind of the given voxel, in world coordinates.
+    pub fn set_world(&mut self, world: IVec3, kind: voxel::Kind) {
+        let (chunk, voxel) = split_world(world);
+        self.set(chunk, voxel, kind);
+    }
+
+    /// Sets the kind of the given voxel. Voxels outside chunk bounds are ignored.
+    pub fn set(&mut self, chunk: Chunk, voxel: Voxel, kind: voxel::Kind) {
+        if chunk::is_inside(voxel) {
+            self.0.entry(chunk).or_default().push((voxel, kind));
+        }
+    }
+
+    /// **Returns** the number of voxel changes, on all chunks.
+    pub fn len(&self) -> usize {
+        self.0.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = Chunk> + '_ {
+        self.0.keys().copied()
+    }
+
+    /// **Returns** voxel changes of the given chunk, in the order they were set.
+    pub fn changes(&self, chunk: Chunk) -> &[(Voxel, voxel::Kind)] {
+        self.0.get(&chunk).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// **Returns** the chunk which contains the given voxel, in world coordinates, and the voxel
+/// coordinates inside that chunk. Height is kept as is, so it may be outside chunk bounds.
+pub(crate) fn split_world(world: IVec3) -> (Chunk, Voxel) {
+    let chunk = Chunk::new(
+        world.x.div_euclid(chunk::X_AXIS_SIZE as i32),
+        world.z.div_euclid(chunk::Z_AXIS_SIZE as i32),
+    );
+    let voxel = Voxel::new(
+        world.x.rem_euclid(chunk::X_AXIS_SIZE as i32),
+        world.y,
+        world.z.rem_euclid(chunk::Z_AXIS_SIZE as i32),
+    );
+
+    (chunk, voxel)
+}
+
+/// Applies the given diff on loaded chunks. Changes on chunks which aren't loaded are discarded.
+#[derive(Event, Debug, Clone)]
+pub struct ApplyChunkDiff(pub ChunkDiff);
+
+/// Sets all voxels inside the given sphere, in world coordinates, to the given kind. Use
+/// [`voxel::Kind::none`] to carve a hole.
+///
+/// **Returns** changes which must be applied with [`ApplyChunkDiff`].
+pub fn carve_sphere(center: Vec3, radius: f32, kind: voxel::Kind) -> ChunkDiff {
+    let mut diff = ChunkDiff::default();
+
+    if !center.is_finite() || !radius.is_finite() || radius < 0.0 {
+        return diff;
+    }
+
+    let min = (center - radius).floor().as_ivec3();
+    let max = (center + radius).ceil().as_ivec3();
+    let radius_squared = radius * radius;
+
+    for x in min.x..=max.x {
+        for z in min.z..=max.z {
+            for y in min.y.max(0)..=max.y.min(chunk::Y_END) {
+                let world = IVec3::new(x, y, z);
+                // Distance is measured from voxel center.
+                if (world.as_vec3() + 0.5).distance_squared(center) <= radius_squared {
+                    diff.set_world(world, kind);
+                }
+            }
+        }
+    }
+
+    diff
+}
+
+fn apply_chunk_diffs(
+    mut reader: EventReader<ApplyChunkDiff>,
+    mut q: ChunkQuery<(&mut ChunkKind, &ChunkLight)>,
+    mut light_edits: LightEdits,
+) {
+    let mut changed = 0;
+    let mut skipped = 0;
+
+    for ApplyChunkDiff(diff) in reader.read() {
+        for (&chunk, changes) in &diff.0 {
+            let Some((mut chunk_kind, chunk_light)) = q.get_chunk_mut(chunk) else {
+                skipped += changes.len();
+                continue;
+            };
+
+            // All kinds are set first, so light is computed from the final state of the chunk.
+            let mut edits = vec![];
+            for &(voxel, kind) in changes {
+                let before = chunk_kind.get(voxel);
+                if before == kind {
+                    continue;
+                }
+
+                chunk_kind.0.set(voxel, kind);
+                edits.push((voxel, before, kind));
+                changed += 1;
+            }
+
+            light_edits.send(chunk, chunk_light, &edits);
+        }
+    }
+
+    trace!("[apply_chunk_diffs] {changed} voxels changed. {skipped} changes on unloaded chunks skipped.");
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{app::ScheduleRunnerPlugin, ecs::system::RunSystemOnce};
+    use projekto_core::voxel::LightTy;
+
+    use crate::{
+        bundle::{ChunkBundle, ChunkLocal, ChunkMap},
+        set::{LightRemoval, LightUpdate},
+    };
+
+    use super::*;
+
+    #[test]
+    fn carve_sphere_across_chunks() {
+        // act
+        let diff = carve_sphere(Vec3::new(0.0, 20.0, 0.0), 2.0, voxel::Kind::none());
+
+        // assert
+        let mut chunks = diff.chunks().collect::<Vec<_>>();
+        chunks.sort_by_key(|c| (c.x(), c.z()));
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk::new(-1, -1),
+                Chunk::new(-1, 0),
+                Chunk::new(0, -1),
+                Chunk::new(0, 0)
+            ]
+        );
+
+        // Sphere is centered on chunks corner, so each chunk has the same share of it.
+        let len = diff.changes(Chunk::new(0, 0)).len();
+        assert!(len > 0);
+        assert!(chunks.iter().all(|&c| diff.changes(c).len() == len));
+        assert_eq!(diff.len(), len * 4);
+
+        assert!(diff
+            .changes(Chunk::new(-1, -1))
+            .iter()
+            .all(|(voxel, _)| voxel.x >= chunk::X_END - 1 && voxel.z >= chunk::Z_END - 1));
+    }
+
+    #[test]
+    fn carve_sphere_clamp_height() {
+        let diff = carve_sphere(Vec3::new(8.0, 0.0, 8.0), 3.0, voxel::Kind::none());
+
+        assert!(!diff.is_empty());
+        assert!(diff
+            .changes(Chunk::new(0, 0))
+            .iter()
+            .all(|(voxel, _)| voxel.y >= 0));
+    }
+
+    #[test]
+    fn apply_chunk_diff_batched() {
+        // arrange
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_once()))
+            .add_event::<LightUpdate>()
+            .add_event::<LightRemoval>()
+            .add_event::<ApplyChunkDiff>();
+
+        let mut map = ChunkMap::default();
+        for chunk in [Chunk::new(0, 0), Chunk::new(1, 0)] {
+            let mut bundle = ChunkBundle {
+                local: ChunkLocal(chunk),
+                ..Default::default()
+            };
+
+            chunk::voxels().for_each(|voxel| {
+                if voxel.y < 10 {
+                    bundle.kind.0.set(voxel, voxel::Kind::id(1));
+                } else {
+                    bundle.light.set_type(
+                        voxel,
+                        LightTy::Natural,
+                        voxel::Light::MAX_NATURAL_INTENSITY,
+                    );
+                }
+            });
+
+            map.insert(chunk, app.world.spawn(bundle).id());
+        }
+        app.world.insert_resource(map);
+
+        let diff = carve_sphere(Vec3::new(16.0, 9.0, 8.0), 3.0, voxel::Kind::none());
+        app.world.send_event(ApplyChunkDiff(diff.clone()));
+
+        // act
+        app.world.run_system_once(apply_chunk_diffs);
+
+        // assert
+        let map = app.world.resource::<ChunkMap>().clone();
+        for chunk in diff.chunks() {
+            let kind = app.world.get::<ChunkKind>(map[&chunk]).unwrap();
+            assert!(diff
+                .changes(chunk)
+                .iter()
+                .all(|&(voxel, _)| kind.get(voxel).is_none()));
+        }
+
+        let events = app.world.resource::<Events<LightUpdate>>();
+        let updates = events
+            .get_reader()
+            .read(events)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            updates.len(),
+            2,
+            "Only one natural light update per chunk should be sent"
+        );
+        assert!(updates
+            .iter()
+            .all(|update| update.ty == LightTy::Natural && !update.values.is_empty()));
+    }
+
+    #[test]
+    fn chunk_diff_from_tracked() {
+        // arrange
+        let mut kind = TrackedStorage::default();
+        kind.set(Voxel::new(1, 2, 3), voxel::Kind::id(1));
+        kind.set(Voxel::new(1, 2, 3), voxel::Kind::id(2));
+        kind.set(Voxel::new(4, 5, 6), voxel::Kind::none());
+
+        // act
+        let diff = ChunkDiff::from_tracked([(Chunk::new(2, 2), &kind)]);
+
+        // assert
+        assert_eq!(
+            diff.changes(Chunk::new(2, 2)),
+            [(Voxel::new(1, 2, 3), voxel::Kind::id(2))],
+            "Only last value of actually changed voxels should be collected"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use bevy::{prelude::*, utils::HashMap};
+use projekto_core::voxel;
+use projekto_proto::ClientId;
+
+use super::ChunkDiff;
+
+/// Max voxels a single structure may have, so a single request can't stall the server.
+pub const MAX_STRUCTURE_VOLUME: usize = 64 * 64 * 64;
+
+/// Voxel kinds copied from a box region, which can be pasted anywhere else, like a schematic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureTemplate {
+    size: IVec3,
+    kinds: Vec<voxel::Kind>,
+}
+
+impl StructureTemplate {
+    /// Copies all voxels between `min` and `max`, inclusive, in world coordinates. Corners may be
+    /// given in any order.
+    ///
+    /// **Returns** `None` if region is bigger than [`MAX_STRUCTURE_VOLUME`] or if `get_kind` fails
+    /// to get any voxel, like when its chunk isn't loaded.
+    pub fn copy(
+        min: IVec3,
+        max: IVec3,
+        mut get_kind: impl FnMut(IVec3) -> Option<voxel::Kind>,
+    ) -> Option<Self> {
+        let (min, max) = (min.min(max), min.max(max));
+        let size = max - min + IVec3::ONE;
+
+        let volume = size.x as i64 * size.y as i64 * size.z as i64;
+        if volume > MAX_STRUCTURE_VOLUME as i64 {
+            return None;
+        }
+
+        let mut kinds = Vec::with_capacity(volume as usize);
+        for x in 0..size.x {
+            for z in 0..size.z {
+                for y in 0..size.y {
+                    kinds.push(get_kind(min + IVec3::new(x, y, z))?);
+                }
+            }
+        }
+
+        Some(Self { size, kinds })
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    /// **Returns** the kind of the given voxel, relative to the structure min corner.
+    pub fn get(&self, local: IVec3) -> voxel::Kind {
+        let IVec3 { x, y, z } = local;
+        self.kinds[((x * self.size.z + z) * self.size.y + y) as usize]
+    }
+
+    /// Places all voxels of this structure, including empty ones, with its min corner at the given
+    /// origin, in world coordinates, after applying the given placement.
+    ///
+    /// **Returns** changes which must be applied with [`super::ApplyChunkDiff`].
+    pub fn paste(&self, origin: IVec3, placement: Placement) -> ChunkDiff {
+        let mut diff = ChunkDiff::default();
+
+        for x in 0..self.size.x {
+            for z in 0..self.size.z {
+                for y in 0..self.size.y {
+                    let local = IVec3::new(x, y, z);
+                    let placed = placement.apply(local, self.size);
+                    diff.set_world(origin + placed, self.get(local));
+                }
+            }
+        }
+
+        diff
+    }
+}
+
+/// How a [`StructureTemplate`] is placed, relative to the way it was copied.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    /// Quarter turns, clockwise when seen from above, around the vertical axis.
+    pub rotation: u8,
+    /// Mirrors structure on X axis, before rotating it.
+    pub mirror: bool,
+}
+
+impl Placement {
+    /// **Returns** where the given voxel, relative to the structure min corner, is placed. Placed
+    /// structures always start on origin, no matter how they were rotated.
+    pub fn apply(&self, local: IVec3, size: IVec3) -> IVec3 {
+        let (mut x, mut z) = (local.x, local.z);
+        let (mut size_x, mut size_z) = (size.x, size.z);
+
+        if self.mirror {
+            x = size_x - 1 - x;
+        }
+
+        for _ in 0..self.rotation % 4 {
+            (x, z) = (size_z - 1 - z, x);
+            (size_x, size_z) = (size_z, size_x);
+        }
+
+        IVec3::new(x, local.y, z)
+    }
+}
+
+/// Last structure copied by each player, waiting to be pasted.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub(crate) struct Clipboards(HashMap<ClientId, StructureTemplate>);
+
+#[cfg(test)]
+mod tests {
+    use projekto_core::chunk::Chunk;
+
+    use super::*;
+
+    /// Kind ids encode the voxel position, so it is easy to check where each voxel ends up.
+    fn kind_at(world: IVec3) -> voxel::Kind {
+        voxel::Kind::id((world.x * 100 + world.y * 10 + world.z) as u16)
+    }
+
+    #[test]
+    fn copy() {
+        let template =
+            StructureTemplate::copy(IVec3::new(3, 2, 1), IVec3::ONE, |w| Some(kind_at(w))).unwrap();
+
+        assert_eq!(template.size(), IVec3::new(3, 2, 1));
+        assert_eq!(template.get(IVec3::ZERO), kind_at(IVec3::ONE));
+        assert_eq!(
+            template.get(IVec3::new(2, 1, 0)),
+            kind_at(IVec3::new(3, 2, 1))
+        );
+
+        assert_eq!(
+            StructureTemplate::copy(IVec3::ZERO, IVec3::ONE, |w| (w.x == 0).then(|| kind_at(w))),
+            None,
+            "Copy should fail when any voxel is missing"
+        );
+        assert_eq!(
+            StructureTemplate::copy(IVec3::ZERO, IVec3::splat(64), |w| Some(kind_at(w))),
+            None,
+            "Copy should fail when structure is too big"
+        );
+    }
+
+    #[test]
+    fn placement() {
+        let size = IVec3::new(3, 1, 2);
+        let corner = IVec3::new(2, 0, 0);
+
+        let rotate = |rotation| Placement {
+            rotation,
+            mirror: false,
+        };
+
+        assert_eq!(rotate(0).apply(corner, size), corner);
+        assert_eq!(rotate(1).apply(corner, size), IVec3::new(1, 0, 2));
+        assert_eq!(rotate(2).apply(corner, size), IVec3::new(0, 0, 1));
+        assert_eq!(rotate(3).apply(corner, size), IVec3::new(0, 0, 0));
+        assert_eq!(rotate(4).apply(corner, size), corner);
+
+        let mirror = Placement {
+            rotation: 0,
+            mirror: true,
+        };
+        assert_eq!(mirror.apply(corner, size), IVec3::new(0, 0, 0));
+    }
+
+    #[test]
+    fn paste_across_chunks() {
+        // arrange
+        let template =
+            StructureTemplate::copy(IVec3::ZERO, IVec3::new(2, 0, 1), |w| Some(kind_at(w)))
+                .unwrap();
+
+        // act
+        let diff = template.paste(
+            IVec3::new(15, 10, 15),
+            Placement {
+                rotation: 1,
+                mirror: false,
+            },
+        );
+
+        // assert
+        assert_eq!(diff.len(), 6);
+        assert_eq!(diff.chunks().count(), 4);
+
+        // Rotated structure is 2 voxels wide on X, so its max X corner ends up on next chunk.
+        assert!(diff
+            .changes(Chunk::new(1, 1))
+            .contains(&(IVec3::new(0, 10, 0), kind_at(IVec3::new(1, 0, 0)))));
+    }
+}
@@ -0,0 +1,509 @@
+//! Headless server and scripted fake clients, so end-to-end behaviors can be tested without
+//! network or rendering.
+//!
+//! Server is ticked manually with a fixed delta time, so timers always fire on the same tick.
+//! Chunks are still generated on world gen thread, initialized on async task pool and meshed on
+//! meshing workers, so helpers which await for something keep ticking until it happens or
+//! [`MAX_TICKS`] is reached.
+//!
+//! Chunks aren't loaded ahead of time, unless a test enables [`PremeshRing`], so only requested
+//! chunks are loaded.
+
+use std::time::Duration;
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::*, time::TimeUpdateStrategy, utils::HashMap};
+use projekto_core::{
+    chunk::Chunk,
+    voxel::{self, Voxel},
+};
+use projekto_messages::{ChunkVertexPart, ClientMessage, ServerMessage};
+use projekto_proto::{BoxedMessage, Channel, Client, ClientId, Message};
+
+use crate::{
+    bundle::{ChunkKind, ChunkMap, ChunkVertex},
+    net::Clients,
+    set::{FacesOcclusionTask, LightRecomputeTask, MeshingTask, PremeshRing},
+    setup_chunk_asset_loader, WorldServerPlugin,
+};
+
+/// Time advanced by each server tick.
+const TICK_DELTA: Duration = Duration::from_millis(50);
+/// Real time to wait between ticks while awaiting, so world gen thread and async tasks can make
+/// progress.
+const TICK_WAIT: Duration = Duration::from_millis(5);
+/// Max ticks to wait for something before failing.
+const MAX_TICKS: usize = 2000;
+
+pub(crate) struct TestServer {
+    pub app: App,
+    next_client_id: u32,
+}
+
+impl TestServer {
+    pub fn new() -> Self {
+        let mut app = App::new();
+
+        setup_chunk_asset_loader(&mut app);
+
+        app.add_plugins((
+            AssetPlugin::default(),
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_once()),
+            WorldServerPlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TICK_DELTA))
+        .insert_resource(PremeshRing(0));
+
+        // App is ticked manually, so plugins must be finished here, like the runner would do.
+        app.finish();
+        app.cleanup();
+
+        Self {
+            app,
+            // Keep away from ids given to network clients.
+            next_client_id: u32::MAX,
+        }
+    }
+
+    /// Runs a single server update.
+    pub fn tick(&mut self) {
+        self.app.update();
+    }
+
+    /// Ticks server until the given condition is met.
+    ///
+    /// # Panics
+    ///
+    /// If the condition isn't met after [`MAX_TICKS`].
+    pub fn tick_until(&mut self, what: &str, mut condition: impl FnMut(&mut App) -> bool) {
+        for _ in 0..MAX_TICKS {
+            if condition(&mut self.app) {
+                return;
+            }
+
+            self.tick();
+            std::thread::sleep(TICK_WAIT);
+        }
+
+        panic!("Timeout while waiting for {what}");
+    }
+
+    /// Ticks server enough times to advance the given time.
+    pub fn tick_for(&mut self, duration: Duration) {
+        let ticks = duration.as_millis().div_ceil(TICK_DELTA.as_millis());
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    /// Ticks server until there are no chunks waiting for async tasks, then enough time for meshing
+    /// to catch up with them and for meshing workers to finish.
+    pub fn settle(&mut self) {
+        fn idle(app: &mut App) -> bool {
+            app.world
+                .query_filtered::<(), Or<(
+                    With<FacesOcclusionTask>,
+                    With<LightRecomputeTask>,
+                    With<MeshingTask>,
+                )>>()
+                .iter(&app.world)
+                .next()
+                .is_none()
+        }
+
+        self.tick_until("async tasks", idle);
+        self.tick_for(Duration::from_secs(1));
+        self.tick_until("meshing workers", idle);
+    }
+
+    /// Connects a new fake client, which talks to server through a loopback channel.
+    pub fn connect(&mut self) -> TestClient {
+        let (client, channel) = Client::loopback(self.next_client_id);
+        self.next_client_id -= 1;
+
+        let id = client.id();
+        self.app.world.resource_mut::<Clients>().insert(id, client);
+
+        TestClient {
+            id,
+            channel,
+            received: vec![],
+        }
+    }
+
+    /// **Returns** the kind of the given voxel, or `None` if the chunk isn't loaded.
+    pub fn voxel_kind(&self, chunk: Chunk, voxel: Voxel) -> Option<voxel::Kind> {
+        let entity = self.app.world.resource::<ChunkMap>().get(&chunk)?;
+        self.app
+            .world
+            .get::<ChunkKind>(*entity)
+            .map(|kind| kind.get(voxel))
+    }
+
+    /// **Returns** the vertices of the given chunk, or `None` if the chunk isn't loaded.
+    pub fn chunk_vertex(&self, chunk: Chunk) -> Option<Vec<voxel::Vertex>> {
+        let entity = self.app.world.resource::<ChunkMap>().get(&chunk)?;
+        self.app
+            .world
+            .get::<ChunkVertex>(*entity)
+            .map(|vertex| vertex.0.clone())
+    }
+
+    pub fn assert_voxel_kind(&self, chunk: Chunk, voxel: Voxel, kind: voxel::Kind) {
+        assert_eq!(
+            self.voxel_kind(chunk, voxel),
+            Some(kind),
+            "Voxel {voxel} on chunk {chunk} should have kind {kind:?}"
+        );
+    }
+}
+
+/// Fake client, which sends requests and records every response sent by server to it. Chunk
+/// vertices streamed in parts are merged back into a single `ChunkVertex` once all parts arrive,
+/// so tests don't need to care about streaming.
+pub(crate) struct TestClient {
+    pub id: ClientId,
+    channel: Channel<ClientMessage, ServerMessage>,
+    /// Messages received but not awaited yet.
+    received: Vec<BoxedMessage<ServerMessage>>,
+}
+
+impl TestClient {
+    pub fn send(&self, msg: impl Message<ClientMessage>) {
+        self.channel
+            .send(msg)
+            .expect("Loopback channel should be open");
+    }
+
+    /// Discards all messages received so far.
+    pub fn clear(&mut self) {
+        self.channel.try_recv_all();
+        self.received.clear();
+    }
+
+    /// Ticks server until it sends a message of type `M` which matches the given predicate. Other
+    /// messages are kept, so they can be awaited later.
+    ///
+    /// # Panics
+    ///
+    /// If no such message is received after [`MAX_TICKS`].
+    pub fn await_message<M: Message<ServerMessage>>(
+        &mut self,
+        server: &mut TestServer,
+        mut predicate: impl FnMut(&M) -> bool,
+    ) -> M {
+        for _ in 0..MAX_TICKS {
+            self.received.extend(self.channel.try_recv_all());
+            self.merge_chunk_vertex_parts();
+
+            let mut pending = std::mem::take(&mut self.received).into_iter();
+            while let Some(boxed) = pending.next() {
+                match boxed.downcast::<M>() {
+                    Ok(msg) if predicate(&msg) => {
+                        self.received.extend(pending);
+                        return msg;
+                    }
+                    Ok(msg) => self.received.push(Box::new(msg)),
+                    Err(boxed) => self.received.push(boxed),
+                }
+            }
+
+            server.tick();
+            std::thread::sleep(TICK_WAIT);
+        }
+
+        panic!(
+            "[{}] Timeout while waiting for {}",
+            self.id,
+            std::any::type_name::<M>()
+        );
+    }
+
+    /// Replaces complete sets of [`ChunkVertexPart`]s received by the `ChunkVertex` they are part
+    /// of. Incomplete sets are kept, waiting for the remaining parts.
+    fn merge_chunk_vertex_parts(&mut self) {
+        let mut streamed = HashMap::<(Chunk, u64), Vec<ChunkVertexPart>>::new();
+        let mut others = vec![];
+        for boxed in std::mem::take(&mut self.received) {
+            match boxed.downcast::<ChunkVertexPart>() {
+                Ok(part) => streamed
+                    .entry((part.chunk, part.hash))
+                    .or_default()
+                    .push(part),
+                Err(boxed) => others.push(boxed),
+            }
+        }
+        self.received = others;
+
+        for ((chunk, hash), mut parts) in streamed {
+            parts.sort_by_key(|part| part.part);
+            parts.dedup_by_key(|part| part.part);
+
+            if parts.len() < parts[0].total as usize {
+                self.received.extend(
+                    parts
+                        .into_iter()
+                        .map(|part| Box::new(part) as BoxedMessage<_>),
+                );
+                continue;
+            }
+
+            self.received.push(Box::new(projekto_messages::ChunkVertex {
+                chunk,
+                hash,
+                vertex: parts.into_iter().flat_map(|part| part.data).collect(),
+            }));
+        }
+    }
+
+    /// Awaits vertices of the given chunk, which matches the given predicate.
+    pub fn await_chunk_vertex(
+        &mut self,
+        server: &mut TestServer,
+        chunk: Chunk,
+        mut predicate: impl FnMut(&[voxel::Vertex]) -> bool,
+    ) -> Vec<voxel::Vertex> {
+        self.await_message(server, |msg: &projekto_messages::ChunkVertex| {
+            msg.chunk == chunk && predicate(&msg.vertex)
+        })
+        .vertex
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use projekto_core::chunk::{self, ChunkStorage};
+    use projekto_messages::{ChunkDecorations, ChunkVertexHash, LandscapeUpdate, VoxelUpdate};
+
+    use crate::{
+        bundle::{ChunkFacesOcclusion, ChunkLight, ChunkLightSamples},
+        light::{self, LightSamples},
+        meshing::MissingNeighborPolicy,
+        terraform::{ApplyChunkDiff, ChunkDiff},
+        MeshingTimer,
+    };
+
+    use super::*;
+
+    #[test]
+    fn edit_propagates_to_neighbor_chunk_mesh() {
+        // arrange
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+
+        let chunk = Chunk::new(0, 0);
+        let neighbor = Chunk::new(1, 0);
+        let voxel = Voxel::new(chunk::X_END, 0, 0);
+        let neighbor_voxel = Voxel::new(0, 0, 0);
+
+        client.send(LandscapeUpdate {
+            center: IVec2::ZERO,
+            radius: 1,
+        });
+
+        client.await_chunk_vertex(&mut server, neighbor, |vertex| !vertex.is_empty());
+        server.tick_until("landscape to load", |app| {
+            app.world.resource::<ChunkMap>().len() == 9
+        });
+
+        // Let meshing catch up with neighbors loaded later.
+        server.settle();
+        client.clear();
+
+        let before = server.chunk_vertex(neighbor).unwrap();
+
+        server.assert_voxel_kind(chunk, voxel, voxel::Kind::id(3));
+        server.assert_voxel_kind(neighbor, neighbor_voxel, voxel::Kind::id(3));
+
+        // act
+        client.send(VoxelUpdate {
+            chunk,
+            voxel,
+            kind: voxel::Kind::none(),
+        });
+
+        // assert
+        server.tick_until("voxel update", |app| {
+            app.world
+                .resource::<ChunkMap>()
+                .get(&chunk)
+                .and_then(|&entity| app.world.get::<ChunkKind>(entity))
+                .is_some_and(|kind| kind.get(voxel).is_none())
+        });
+
+        let after = client.await_chunk_vertex(&mut server, neighbor, |vertex| vertex != before);
+        assert_eq!(server.chunk_vertex(neighbor), Some(after.clone()));
+        assert!(
+            after.len() > before.len(),
+            "Neighbor voxel face should be visible"
+        );
+    }
+
+    #[test]
+    fn decorations_follow_surface() {
+        // arrange
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        let chunk = Chunk::new(0, 0);
+
+        client.send(LandscapeUpdate {
+            center: IVec2::ZERO,
+            radius: 1,
+        });
+        client.await_chunk_vertex(&mut server, chunk, |vertex| !vertex.is_empty());
+        server.settle();
+        // Generated terrain may be decorated already, so only decorations of the layer are awaited.
+        client.clear();
+
+        // Lay down a grass layer high above generated terrain.
+        let grass = voxel::Kind::id(2);
+        let mut diff = ChunkDiff::default();
+        chunk::top_voxels().for_each(|top| diff.set(chunk, top - IVec3::Y * 55, grass));
+        server.app.world.send_event(ApplyChunkDiff(diff));
+
+        let decorations = client
+            .await_message(&mut server, |msg: &ChunkDecorations| {
+                msg.chunk == chunk && !msg.decorations.is_empty()
+            })
+            .decorations;
+        assert!(decorations.iter().all(|d| d.voxel().y == chunk::Y_END - 54));
+
+        let decoration = decorations[0];
+        let surface = decoration.voxel() - IVec3::Y;
+        server.settle();
+        server.assert_voxel_kind(chunk, surface, grass);
+
+        // act
+        client.send(VoxelUpdate {
+            chunk,
+            voxel: surface,
+            kind: voxel::Kind::none(),
+        });
+
+        // assert
+        let after = client
+            .await_message(&mut server, |msg: &ChunkDecorations| msg.chunk == chunk)
+            .decorations;
+        assert!(
+            !after.contains(&decoration),
+            "Decoration should be removed when its surface is dug"
+        );
+    }
+
+    #[test]
+    fn light_samples_follow_edits_between_meshing() {
+        // arrange
+        let mut server = TestServer::new();
+        let mut client = server.connect();
+        let chunk = Chunk::new(0, 0);
+        // Deep underground, so it is dark before and after being dug and light doesn't change.
+        let voxel = Voxel::new(8, 10, 8);
+
+        client.send(LandscapeUpdate {
+            center: IVec2::ZERO,
+            radius: 1,
+        });
+        client.await_chunk_vertex(&mut server, chunk, |vertex| !vertex.is_empty());
+        server.settle();
+        server.assert_voxel_kind(chunk, voxel, voxel::Kind::id(3));
+
+        // Edit right after meshing ran, so it isn't due again on the tick the edit is applied.
+        server.tick_until("meshing to run", |app| {
+            app.world.resource::<MeshingTimer>().elapsed() == Duration::ZERO
+        });
+
+        let mut diff = ChunkDiff::default();
+        diff.set(chunk, voxel, voxel::Kind::none());
+        server.app.world.send_event(ApplyChunkDiff(diff));
+
+        // act
+        server.tick();
+        assert!(
+            !server.app.world.resource::<MeshingTimer>().finished(),
+            "Meshing shouldn't be due on the tick the edit is applied"
+        );
+        server.settle();
+
+        // assert
+        server.assert_voxel_kind(chunk, voxel, voxel::Kind::none());
+
+        let world = &server.app.world;
+        let map = world.resource::<ChunkMap>();
+        let entity = *map.get(&chunk).unwrap();
+        let get_kind = |chunk| {
+            map.get(&chunk)
+                .and_then(|&entity| world.get::<ChunkKind>(entity))
+                .map(|kind| kind.storage())
+        };
+        let get_light = |chunk| {
+            map.get(&chunk)
+                .and_then(|&entity| world.get::<ChunkLight>(entity))
+                .map(|light| &**light)
+        };
+
+        let mut expected = LightSamples::default();
+        light::smooth_lighting(
+            chunk,
+            world.get::<ChunkFacesOcclusion>(entity).unwrap(),
+            &mut ChunkStorage::default(),
+            &mut expected,
+            get_kind,
+            get_light,
+            *world.resource::<MissingNeighborPolicy>(),
+        );
+
+        assert_eq!(
+            world.get::<ChunkLightSamples>(entity).unwrap().0,
+            expected,
+            "Light samples should be gathered again after an edit"
+        );
+    }
+
+    #[test]
+    fn premesh_ring_is_not_sent_until_requested() {
+        // arrange
+        let mut server = TestServer::new();
+        server.app.world.insert_resource(PremeshRing(1));
+        let mut client = server.connect();
+        let center = Chunk::new(0, 0);
+        let ring = Chunk::new(1, 0);
+
+        client.send(LandscapeUpdate {
+            center: IVec2::ZERO,
+            radius: 0,
+        });
+
+        // act
+        server.tick_until("ring to be meshed", |app| {
+            app.world.resource::<ChunkMap>().len() == 9
+                && app
+                    .world
+                    .resource::<ChunkMap>()
+                    .get(&ring)
+                    .and_then(|&entity| app.world.get::<ChunkVertex>(entity))
+                    .is_some_and(|vertex| !vertex.is_empty())
+        });
+        server.settle();
+
+        // assert
+        client.received.extend(client.channel.try_recv_all());
+        let sent = std::mem::take(&mut client.received)
+            .into_iter()
+            .filter_map(|boxed| boxed.downcast::<projekto_messages::ChunkVertex>().ok())
+            .map(|msg| msg.chunk)
+            .collect::<Vec<_>>();
+        assert!(sent.contains(&center));
+        assert!(
+            sent.iter().all(|&chunk| chunk == center),
+            "Ring chunks should not be sent before requested"
+        );
+
+        // act
+        client.send(LandscapeUpdate {
+            center: IVec2::ZERO,
+            radius: 1,
+        });
+
+        // assert
+        client.await_message(&mut server, |msg: &ChunkVertexHash| msg.chunk == ring);
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use projekto_core::time::WorldTime;
+use projekto_messages as messages;
+
+use crate::{net::Clients, WorldSet};
+
+/// How often world time is sent to clients, which advances it locally between syncs.
+const TIME_SYNC_INTERVAL_MS: u64 = 1000;
+
+pub(crate) struct WorldTimePlugin;
+
+impl Plugin for WorldTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldTime>()
+            .init_resource::<ServerTick>()
+            .register_type::<WorldTime>()
+            .add_systems(First, advance_server_tick)
+            .add_systems(Update, advance_world_time)
+            .add_systems(
+                PostUpdate,
+                sync_world_time
+                    .run_if(on_timer(Duration::from_millis(TIME_SYNC_INTERVAL_MS)))
+                    .in_set(WorldSet::SendResponses),
+            );
+    }
+}
+
+/// Server ticks since server started, which clients use to estimate server time. Server runs a
+/// tick every [`SERVER_TICK_MS`](messages::SERVER_TICK_MS).
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Deref)]
+pub struct ServerTick(pub u64);
+
+pub(crate) fn advance_server_tick(mut tick: ResMut<ServerTick>) {
+    tick.0 += 1;
+}
+
+fn advance_world_time(time: Res<Time>, mut world_time: ResMut<WorldTime>) {
+    world_time.advance(time.delta_seconds());
+}
+
+fn sync_world_time(clients: Res<Clients>, world_time: Res<WorldTime>, tick: Res<ServerTick>) {
+    for client in clients.values() {
+        let _ = client.channel().send(messages::TimeSync {
+            day_time: world_time.day_time,
+            day_length: world_time.day_length,
+            tick: tick.0,
+        });
+    }
+}
@@ -0,0 +1,53 @@
+//! Spans around each [`WorldSet`], so profilers, like tracy or chrome tracing, show how much of the
+//! tick each stage takes. Systems are already instrumented by bevy `trace` feature, but it is hard
+//! to tell which stage they belong to.
+
+use bevy::{
+    ecs::schedule::ScheduleLabel,
+    prelude::*,
+    utils::{tracing::span::EnteredSpan, HashMap},
+};
+
+use crate::WorldSet;
+
+pub(crate) struct TracePlugin;
+
+impl Plugin for TracePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_non_send_resource(WorldSetSpans::default());
+
+        let stages = [
+            (PreUpdate.intern(), WorldSet::ReceiveRequests),
+            (Update.intern(), WorldSet::LandscapeUpdate),
+            (Update.intern(), WorldSet::ChunkManagement),
+            (Update.intern(), WorldSet::ChunkInitialization),
+            (Update.intern(), WorldSet::Propagation),
+            (Update.intern(), WorldSet::CollectAsync),
+            (Update.intern(), WorldSet::Meshing),
+            (PostUpdate.intern(), WorldSet::SendResponses),
+        ];
+
+        for (schedule, set) in stages {
+            app.add_systems(schedule, enter_span(set).before(set))
+                .add_systems(schedule, exit_span(set).after(set));
+        }
+    }
+}
+
+/// Spans of stages which are currently running. Entered spans must be exited on the same thread,
+/// so this is a non-send resource.
+#[derive(Default)]
+struct WorldSetSpans(HashMap<WorldSet, EnteredSpan>);
+
+fn enter_span(set: WorldSet) -> impl FnMut(NonSendMut<WorldSetSpans>) {
+    move |mut spans| {
+        let span = info_span!("world_set", stage = ?set).entered();
+        spans.0.insert(set, span);
+    }
+}
+
+fn exit_span(set: WorldSet) -> impl FnMut(NonSendMut<WorldSetSpans>) {
+    move |mut spans| {
+        spans.0.remove(&set);
+    }
+}